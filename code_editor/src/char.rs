@@ -1,6 +1,7 @@
 pub trait CharExt {
     fn is_opening_delimiter(self) -> bool;
     fn is_closing_delimiter(self) -> bool;
+    fn is_quote(self) -> bool;
     fn column_count(self) -> usize;
     fn opposite_delimiter(&self) -> Option<char>;
 }
@@ -20,6 +21,15 @@ impl CharExt for char {
         }
     }
 
+    // Single quotes are left out, because in Rust they mostly start lifetimes rather than
+    // character literals.
+    fn is_quote(self) -> bool {
+        match self {
+            '"' | '`' => true,
+            _ => false,
+        }
+    }
+
     fn column_count(self) -> usize {
         1
     }
@@ -1,7 +1,13 @@
 use {
     crate::{
         decoration::{Decoration, DecorationType},
+        keymap::{
+            format_key_strokes, Command, KeyStroke, Keymap, KeymapConfig, KeymapMatch, KeymapPreset,
+        },
         layout::{BlockElement, WrappedElement},
+        lsp::{CompletionItem, Signature},
+        regex::{self, RegexError},
+        search::SearchOptions,
         selection::Affinity,
         session::{SelectionMode, Session},
         settings::Settings,
@@ -37,6 +43,8 @@ live_design! {
         delimiter_highlight: #f,
         error_decoration: #f00,
         warning_decoration: #0f0,
+        info_decoration: #3794ff,
+        hint_decoration: #8,
     }
 
     DrawIndentGuide = {{DrawIndentGuide}} {
@@ -111,6 +119,24 @@ live_design! {
     DrawCodeText = {{DrawCodeText}} {
     }
 
+    FindPanelColors = {{FindPanelColors}} {
+        text: #C0C0C0,
+        label: #8,
+        field: #2a,
+        focused_field: #1e1e1e,
+        caret: #C0C0C0,
+        toggle_on: #0f5fa8,
+        error: #f55,
+    }
+
+    PopupColors = {{PopupColors}} {
+        text: #C0C0C0,
+        detail: #8,
+        selection: #0f5fa8,
+        active_parameter: #fffcc9,
+        quick_fix: #3794ff,
+    }
+
     CodeEditor = {{CodeEditor}} {
         width: Fill,
         height: Fill,
@@ -145,6 +171,60 @@ live_design! {
         draw_decoration: {
           //  draw_depth: 2.0,
         }
+        draw_search_match: {
+            fn pixel(self) -> vec4 {
+                let sdf = Sdf2d::viewport(self.pos * self.rect_size);
+                sdf.box(0., 0., self.rect_size.x, self.rect_size.y, 1.5);
+                return sdf.fill(self.color);
+            }
+            color: #ffd70040
+        }
+        draw_find_panel: {
+            fn pixel(self) -> vec4 {
+                let sdf = Sdf2d::viewport(self.pos * self.rect_size);
+                sdf.box(1., 1., self.rect_size.x - 2., self.rect_size.y - 2., 3.);
+                sdf.fill_keep(self.color);
+                sdf.stroke(#4, 1.);
+                return sdf.result;
+            }
+            color: #33
+        }
+        draw_find_field: {
+            fn pixel(self) -> vec4 {
+                let sdf = Sdf2d::viewport(self.pos * self.rect_size);
+                sdf.box(0., 0., self.rect_size.x, self.rect_size.y, 2.);
+                return sdf.fill(self.color);
+            }
+        }
+        draw_find_text: {
+            text_style: <THEME_FONT_CODE> {}
+            fn get_color(self) -> vec4 {
+                return self.color;
+            }
+        }
+        draw_popup: {
+            fn pixel(self) -> vec4 {
+                let sdf = Sdf2d::viewport(self.pos * self.rect_size);
+                sdf.box(1., 1., self.rect_size.x - 2., self.rect_size.y - 2., 3.);
+                sdf.fill_keep(self.color);
+                sdf.stroke(#4, 1.);
+                return sdf.result;
+            }
+            color: #25
+        }
+        draw_popup_selection: {
+            fn pixel(self) -> vec4 {
+                let sdf = Sdf2d::viewport(self.pos * self.rect_size);
+                sdf.box(0., 0., self.rect_size.x, self.rect_size.y, 2.);
+                return sdf.fill(self.color);
+            }
+        }
+        draw_popup_text: {
+            text_style: <THEME_FONT_CODE> {}
+            fn get_color(self) -> vec4 {
+                return self.color;
+            }
+        }
         draw_selection: {
            // draw_depth: 3.0,
         }
@@ -239,6 +319,24 @@ pub struct CodeEditor {
     #[live]
     draw_decoration: DrawDecoration,
     #[live]
+    draw_search_match: DrawColor,
+    #[live]
+    draw_find_panel: DrawColor,
+    #[live]
+    draw_find_field: DrawColor,
+    #[live]
+    draw_find_text: DrawText,
+    #[live]
+    find_panel_colors: FindPanelColors,
+    #[live]
+    draw_popup: DrawColor,
+    #[live]
+    draw_popup_selection: DrawColor,
+    #[live]
+    draw_popup_text: DrawText,
+    #[live]
+    popup_colors: PopupColors,
+    #[live]
     draw_selection: DrawSelection,
     #[live]
     draw_cursor: DrawColor,
@@ -275,8 +373,114 @@ pub struct CodeEditor {
 
     #[rust]
     blink_timer: Timer,
+
+    #[rust]
+    find_panel: FindPanel,
+
+    #[live(0.5)]
+    hover_delay: f64,
+
+    #[rust]
+    hover_timer: Timer,
+
+    #[rust]
+    hover_position: Option<Position>,
+
+    #[rust]
+    popup: Popup,
+
+    #[live]
+    keymap: KeymapConfig,
+
+    #[rust(Keymap::preset(KeymapPreset::Default))]
+    bindings: Keymap,
+
+    #[rust]
+    pending_key_strokes: Vec<KeyStroke>,
+
+    #[rust]
+    swallow_text_input: bool,
+
+    #[rust]
+    command_palette: CommandPalette,
+}
+
+/// The state of the find and replace panel, which is drawn on top of the top right corner of
+/// the editor. While it has the key focus, text input goes to its fields instead of the session.
+#[derive(Default)]
+struct FindPanel {
+    is_open: bool,
+    is_replace_open: bool,
+    has_focus: bool,
+    is_replace_focused: bool,
+    query: String,
+    replace: String,
+    options: SearchOptions,
+    error: Option<RegexError>,
+    rect: Rect,
+    toggle_rects: [Rect; 3],
 }
 
+/// A popup with the result of a language server request, drawn below the line of the position it
+/// belongs to. While completions are shown, the arrow keys, return, tab and escape go to the
+/// popup instead of the session.
+#[derive(Default)]
+struct Popup {
+    content: Option<PopupContent>,
+    position: Position,
+    rect: Rect,
+    row_height: f64,
+    first_visible_row_index: usize,
+}
+
+enum PopupContent {
+    Completion {
+        items: Vec<CompletionItem>,
+        filtered_indices: Vec<usize>,
+        selected_index: usize,
+    },
+    Hover(String),
+    SignatureHelp(Signature),
+    /// The message and related locations of a decoration, followed by a row for each of its quick
+    /// fixes.
+    Decoration {
+        lines: Vec<String>,
+        quick_fix_titles: Vec<String>,
+    },
+}
+
+impl Popup {
+    fn is_completion(&self) -> bool {
+        match self.content {
+            Some(PopupContent::Completion { .. }) => true,
+            _ => false,
+        }
+    }
+}
+
+/// The command palette, drawn at the top of the editor. While it is open, it has the key focus,
+/// and lists the commands that match its query, best matches first.
+#[derive(Default)]
+struct CommandPalette {
+    is_open: bool,
+    query: String,
+    commands: Vec<Command>,
+    selected_index: usize,
+    first_visible_row_index: usize,
+    rect: Rect,
+    row_height: f64,
+}
+
+const POPUP_MAX_ROWS: usize = 12;
+const POPUP_MAX_COLUMNS: usize = 80;
+
+const FIND_PANEL_LABEL_COLUMNS: usize = 8;
+const FIND_PANEL_FIELD_COLUMNS: usize = 24;
+const FIND_PANEL_STATUS_COLUMNS: usize = 13;
+const FIND_PANEL_TOGGLES: [&str; 3] = ["Aa", "W", ".*"];
+
+const COMMAND_PALETTE_COLUMNS: usize = 56;
+
 enum KeepCursorInView {
     Once,
     Always(DVec2, NextFrame),
@@ -307,6 +511,21 @@ impl LiveHook for CodeEditor {
     fn before_live_design(cx: &mut Cx) {
         register_widget!(cx, CodeEditor)
     }
+
+    fn after_apply(&mut self, _cx: &mut Cx, from: ApplyFrom, _index: usize, _nodes: &[LiveNode]) {
+        // The animator applies the cursor blink, which would throw away a keymap that was set
+        // with `set_keymap`.
+        if let ApplyFrom::Animate | ApplyFrom::AnimatorInit = from {
+            return;
+        }
+        self.bindings = match Keymap::from_config(&self.keymap) {
+            Ok(keymap) => keymap,
+            Err(error) => {
+                error!("invalid keymap: {}", error);
+                Keymap::preset(self.keymap.preset)
+            }
+        };
+    }
 }
 
 impl Widget for CodeEditor {
@@ -348,6 +567,9 @@ impl CodeEditor {
 
         let last_added_selection =
             session.selections()[session.last_added_selection_index().unwrap()];
+        // Lines are laid out lazily, so lay out the one with the cursor before scrolling to it.
+        let cursor_line_index = last_added_selection.cursor.position.line_index;
+        session.layout_lines(cursor_line_index, cursor_line_index + 1);
         let (cursor_x, cursor_y) = session.layout().logical_to_normalized_position(
             last_added_selection.cursor.position,
             last_added_selection.cursor.affinity,
@@ -472,6 +694,18 @@ impl CodeEditor {
             None
         });
 
+        if self.find_panel.is_open
+            && self.find_panel.error.is_none()
+            && !self.find_panel.query.is_empty()
+            && session.search().as_ref().map_or(true, |search| {
+                search.query() != self.find_panel.query
+                    || search.options() != self.find_panel.options
+            })
+        {
+            // The session was switched, so it doesn't know about the query yet.
+            self.update_search(session);
+        }
+
         let scroll_pos = self.scroll_bars.get_scroll_pos();
 
         self.line_start = session
@@ -480,15 +714,24 @@ impl CodeEditor {
         self.line_end = session.layout().find_first_line_starting_after_y(
             (scroll_pos.y + self.viewport_rect.size.y) / self.cell_size.y,
         );
+        session.layout_lines(self.line_start, self.line_end);
+        // Laying out the visible lines can make them taller, so fewer of them may fit.
+        self.line_end = session.layout().find_first_line_starting_after_y(
+            (scroll_pos.y + self.viewport_rect.size.y) / self.cell_size.y,
+        );
         self.unscrolled_rect = cx.turtle().unscrolled_rect();
         self.draw_bg.draw_abs(cx, cx.turtle().unscrolled_rect());
 
         self.draw_gutter(cx, session);
         self.draw_selection_layer(cx, session);
+        self.draw_search_match_layer(cx, session);
         self.draw_text_layer(cx, session);
         self.draw_indent_guide_layer(cx, session);
         self.draw_decoration_layer(cx, session);
         self.draw_selection_layer(cx, session);
+        self.draw_find_panel(cx, session);
+        self.draw_popup(cx, session);
+        self.draw_command_palette(cx);
 
         // Get the last added selection.
         // Get the normalized cursor position. To go from normalized to screen position, multiply by
@@ -550,6 +793,731 @@ impl CodeEditor {
         }
     }
 
+    /// Opens the find panel and gives it the key focus. A selection on a single line becomes the
+    /// query.
+    pub fn open_find_panel(&mut self, cx: &mut Cx, session: &mut Session, show_replace: bool) {
+        let selection = session.selections()[session.last_added_selection_index().unwrap()];
+        if !selection.is_empty() && selection.start().line_index == selection.end().line_index {
+            let text = session
+                .document()
+                .as_text()
+                .slice(selection.start(), selection.length())
+                .to_string();
+            self.find_panel.query = if self.find_panel.options.regex {
+                regex::escape(&text)
+            } else {
+                text
+            };
+        }
+        self.find_panel.is_open = true;
+        self.find_panel.is_replace_open = show_replace;
+        self.find_panel.has_focus = true;
+        self.find_panel.is_replace_focused = show_replace && !self.find_panel.query.is_empty();
+        self.update_search(session);
+        self.redraw(cx);
+    }
+
+    pub fn close_find_panel(&mut self, cx: &mut Cx, session: &mut Session) {
+        self.find_panel.is_open = false;
+        self.find_panel.has_focus = false;
+        session.clear_search();
+        self.redraw(cx);
+    }
+
+    fn update_search(&mut self, session: &Session) {
+        match session.set_search(&self.find_panel.query, self.find_panel.options) {
+            Ok(_) => self.find_panel.error = None,
+            Err(error) => {
+                self.find_panel.error = Some(error);
+                session.clear_search();
+            }
+        }
+    }
+
+    // Searches for the changed query, starting at the current match so the selection stays put
+    // while the query is extended.
+    fn update_search_incrementally(&mut self, session: &Session) {
+        self.update_search(session);
+        let start = session.selections()[session.last_added_selection_index().unwrap()].start();
+        session.find_next_from(start);
+    }
+
+    // Handles the keys of the find panel while it has the key focus. Returns true if the
+    // selections were changed.
+    fn handle_find_key_down(
+        &mut self,
+        cx: &mut Cx,
+        key_event: &KeyEvent,
+        session: &mut Session,
+        dispatch_action: &mut dyn FnMut(&mut Cx, CodeEditorAction),
+    ) -> bool {
+        let KeyModifiers {
+            shift,
+            control,
+            alt,
+            logo,
+        } = key_event.modifiers;
+        let command = control || logo;
+        match key_event.key_code {
+            KeyCode::Escape => {
+                self.close_find_panel(cx, session);
+                false
+            }
+            KeyCode::ReturnKey if alt => session.select_all_matches(),
+            KeyCode::ReturnKey if self.find_panel.is_replace_focused => {
+                let replace = self.find_panel.replace.clone();
+                let has_replaced = if command {
+                    session.replace_all(&replace) > 0
+                } else {
+                    session.replace(&replace);
+                    true
+                };
+                if has_replaced {
+                    dispatch_action(cx, CodeEditorAction::TextDidChange);
+                }
+                self.redraw(cx);
+                true
+            }
+            KeyCode::ReturnKey => {
+                if shift {
+                    session.find_prev()
+                } else {
+                    session.find_next()
+                }
+            }
+            KeyCode::Tab => {
+                if self.find_panel.is_replace_open {
+                    self.find_panel.is_replace_focused = !self.find_panel.is_replace_focused;
+                    self.redraw(cx);
+                }
+                false
+            }
+            KeyCode::Backspace => {
+                if self.find_panel.is_replace_focused {
+                    self.find_panel.replace.pop();
+                    self.redraw(cx);
+                    false
+                } else {
+                    self.find_panel.query.pop();
+                    self.update_search_incrementally(session);
+                    self.redraw(cx);
+                    true
+                }
+            }
+            KeyCode::KeyC | KeyCode::KeyW | KeyCode::KeyR if command && alt => {
+                self.toggle_find_option(key_event.key_code, session);
+                self.redraw(cx);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn toggle_find_option(&mut self, key_code: KeyCode, session: &Session) {
+        let options = &mut self.find_panel.options;
+        match key_code {
+            KeyCode::KeyC => options.case_sensitive = !options.case_sensitive,
+            KeyCode::KeyW => options.whole_word = !options.whole_word,
+            KeyCode::KeyR => options.regex = !options.regex,
+            _ => return,
+        }
+        self.update_search_incrementally(session);
+    }
+
+    /// Replaces the keymap that was created from the `keymap` in the live DSL, like with one that
+    /// was read with `Keymap::from_toml`.
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.bindings = keymap;
+        self.pending_key_strokes.clear();
+    }
+
+    pub fn keymap(&self) -> &Keymap {
+        &self.bindings
+    }
+
+    // Looks up a key in the keymap, together with the keys of the chord that were pressed before
+    // it, and runs the command that they are bound to. Returns true if the selections were
+    // changed.
+    fn handle_key_down(
+        &mut self,
+        cx: &mut Cx,
+        key_event: &KeyEvent,
+        session: &mut Session,
+        dispatch_action: &mut dyn FnMut(&mut Cx, CodeEditorAction),
+    ) -> bool {
+        let key_stroke = KeyStroke::from_key_event(key_event);
+        if key_stroke.is_modifier() {
+            return false;
+        }
+        self.pending_key_strokes.push(key_stroke);
+        let keymap_match = self.bindings.lookup(&self.pending_key_strokes);
+        // A key doesn't type its text if it is bound, or if it is part of a chord, even one that
+        // turns out not to be bound.
+        self.swallow_text_input = key_stroke.types_text()
+            && (keymap_match != KeymapMatch::None || self.pending_key_strokes.len() > 1);
+        if keymap_match != KeymapMatch::Pending {
+            self.pending_key_strokes.clear();
+        }
+        match keymap_match {
+            // While the find panel has the key focus, the other commands would edit the text
+            // behind its back.
+            KeymapMatch::Command(command)
+                if !self.find_panel.has_focus || is_find_command(command) =>
+            {
+                self.run_command(cx, command, session, dispatch_action)
+            }
+            _ => false,
+        }
+    }
+
+    // Runs a command. Returns true if the selections were changed.
+    fn run_command(
+        &mut self,
+        cx: &mut Cx,
+        command: Command,
+        session: &mut Session,
+        dispatch_action: &mut dyn FnMut(&mut Cx, CodeEditorAction),
+    ) -> bool {
+        let page_line_count = (self.line_end - self.line_start).saturating_sub(3);
+        let mut text_did_change = false;
+        let selections_did_change = match command {
+            Command::MoveLeft | Command::SelectLeft => {
+                session.move_left(command == Command::MoveLeft);
+                true
+            }
+            Command::MoveRight | Command::SelectRight => {
+                session.move_right(command == Command::MoveRight);
+                true
+            }
+            Command::MoveUp | Command::SelectUp => {
+                session.move_up(command == Command::MoveUp);
+                true
+            }
+            Command::MoveDown | Command::SelectDown => {
+                session.move_down(command == Command::MoveDown);
+                true
+            }
+            Command::MoveToPrevWord | Command::SelectToPrevWord => {
+                session.move_to_prev_word(command == Command::MoveToPrevWord);
+                true
+            }
+            Command::MoveToNextWord | Command::SelectToNextWord => {
+                session.move_to_next_word(command == Command::MoveToNextWord);
+                true
+            }
+            Command::MoveToLineStart | Command::SelectToLineStart => {
+                session.home(command == Command::MoveToLineStart);
+                true
+            }
+            Command::MoveToLineEnd | Command::SelectToLineEnd => {
+                session.end(command == Command::MoveToLineEnd);
+                true
+            }
+            Command::MovePageUp | Command::SelectPageUp => {
+                for _ in 0..page_line_count {
+                    session.move_up(command == Command::MovePageUp);
+                }
+                true
+            }
+            Command::MovePageDown | Command::SelectPageDown => {
+                for _ in 0..page_line_count {
+                    session.move_down(command == Command::MovePageDown);
+                }
+                true
+            }
+            Command::MoveToFileStart | Command::SelectToFileStart => {
+                session.move_to_file_start(command == Command::MoveToFileStart);
+                true
+            }
+            Command::MoveToFileEnd | Command::SelectToFileEnd => {
+                session.move_to_file_end(command == Command::MoveToFileEnd);
+                true
+            }
+            Command::SelectAll => {
+                session.select_all();
+                true
+            }
+            Command::NewLine => {
+                session.enter();
+                text_did_change = true;
+                true
+            }
+            Command::Indent => {
+                session.indent();
+                text_did_change = true;
+                true
+            }
+            Command::Outdent => {
+                session.outdent();
+                text_did_change = true;
+                true
+            }
+            Command::Delete => {
+                session.delete();
+                text_did_change = true;
+                true
+            }
+            Command::Backspace => {
+                session.backspace();
+                text_did_change = true;
+                true
+            }
+            Command::DeleteWordBackward => {
+                session.delete_word_backward();
+                text_did_change = true;
+                true
+            }
+            Command::DeleteWordForward => {
+                session.delete_word_forward();
+                text_did_change = true;
+                true
+            }
+            Command::DuplicateLines => {
+                session.duplicate_lines();
+                text_did_change = true;
+                true
+            }
+            Command::MoveLinesUp => {
+                session.move_lines_up();
+                text_did_change = true;
+                true
+            }
+            Command::MoveLinesDown => {
+                session.move_lines_down();
+                text_did_change = true;
+                true
+            }
+            Command::JoinLines => {
+                session.join_lines();
+                text_did_change = true;
+                true
+            }
+            Command::ToggleLineComment => {
+                session.toggle_line_comment();
+                text_did_change = true;
+                true
+            }
+            Command::QuickFix => {
+                let position = last_added_cursor_position(session);
+                let quick_fix = decoration_at(&session.document().decorations(), position)
+                    .and_then(|decoration| decoration.quick_fixes.first().cloned());
+                text_did_change =
+                    quick_fix.map_or(false, |quick_fix| session.apply_quick_fix(&quick_fix));
+                text_did_change
+            }
+            Command::Undo => {
+                text_did_change = session.undo();
+                text_did_change
+            }
+            Command::Redo => {
+                text_did_change = session.redo();
+                text_did_change
+            }
+            Command::Find | Command::FindAndReplace => {
+                self.open_find_panel(cx, session, command == Command::FindAndReplace);
+                false
+            }
+            Command::FindNext => session.find_next(),
+            Command::FindPrev => session.find_prev(),
+            Command::SelectAllMatches => session.select_all_matches(),
+            Command::TriggerCompletion => {
+                let position = last_added_cursor_position(session);
+                dispatch_action(cx, CodeEditorAction::RequestCompletion(position));
+                false
+            }
+            Command::GoToDefinition => {
+                let position = last_added_cursor_position(session);
+                dispatch_action(cx, CodeEditorAction::GoToDefinition(position));
+                false
+            }
+            Command::ToggleWordWrap => {
+                self.word_wrap = !self.word_wrap;
+                false
+            }
+            Command::IncreaseFontSize => {
+                self.increase_font_size();
+                false
+            }
+            Command::DecreaseFontSize => {
+                self.decrease_font_size();
+                false
+            }
+            Command::ResetFontSize => {
+                self.reset_font_size();
+                false
+            }
+            Command::CommandPalette => {
+                self.open_command_palette(cx);
+                false
+            }
+        };
+        if text_did_change {
+            dispatch_action(cx, CodeEditorAction::TextDidChange);
+        }
+        self.redraw(cx);
+        selections_did_change
+    }
+
+    /// Opens the command palette and gives it the key focus.
+    pub fn open_command_palette(&mut self, cx: &mut Cx) {
+        self.command_palette.is_open = true;
+        self.command_palette.query.clear();
+        self.find_panel.has_focus = false;
+        self.popup.content = None;
+        self.update_command_palette();
+        self.redraw(cx);
+    }
+
+    pub fn close_command_palette(&mut self, cx: &mut Cx) {
+        self.command_palette.is_open = false;
+        self.redraw(cx);
+    }
+
+    // Filters the commands by the query, best matches first.
+    fn update_command_palette(&mut self) {
+        let query = self.command_palette.query.to_lowercase();
+        let mut commands: Vec<_> = Command::ALL
+            .iter()
+            .filter_map(|&command| {
+                let score = fuzzy_score(&query, &command.title().to_lowercase())?;
+                Some((score, command))
+            })
+            .collect();
+        // The sort is stable, so commands that match equally well keep their order.
+        commands.sort_by_key(|&(score, _)| score);
+        self.command_palette.commands = commands.into_iter().map(|(_, command)| command).collect();
+        self.command_palette.selected_index = 0;
+        self.command_palette.first_visible_row_index = 0;
+    }
+
+    // Handles the keys that go to the command palette while it is open. Returns true if the
+    // selections were changed.
+    fn handle_command_palette_key_down(
+        &mut self,
+        cx: &mut Cx,
+        key_event: &KeyEvent,
+        session: &mut Session,
+        dispatch_action: &mut dyn FnMut(&mut Cx, CodeEditorAction),
+    ) -> bool {
+        match key_event.key_code {
+            KeyCode::Escape => {
+                self.close_command_palette(cx);
+                false
+            }
+            KeyCode::ReturnKey => self.run_selected_command(cx, session, dispatch_action),
+            KeyCode::ArrowUp | KeyCode::ArrowDown if !self.command_palette.commands.is_empty() => {
+                let palette = &mut self.command_palette;
+                let count = palette.commands.len();
+                palette.selected_index = if key_event.key_code == KeyCode::ArrowUp {
+                    (palette.selected_index + count - 1) % count
+                } else {
+                    (palette.selected_index + 1) % count
+                };
+                scroll_row_into_view(&mut palette.first_visible_row_index, palette.selected_index);
+                self.redraw(cx);
+                false
+            }
+            KeyCode::Backspace => {
+                self.command_palette.query.pop();
+                self.update_command_palette();
+                self.redraw(cx);
+                false
+            }
+            _ => false,
+        }
+    }
+
+    // Closes the command palette and runs the command that is selected in it. Returns true if
+    // the selections were changed.
+    fn run_selected_command(
+        &mut self,
+        cx: &mut Cx,
+        session: &mut Session,
+        dispatch_action: &mut dyn FnMut(&mut Cx, CodeEditorAction),
+    ) -> bool {
+        self.close_command_palette(cx);
+        match self
+            .command_palette
+            .commands
+            .get(self.command_palette.selected_index)
+        {
+            Some(&command) => self.run_command(cx, command, session, dispatch_action),
+            None => false,
+        }
+    }
+
+    /// Shows the completions for a `RequestCompletion`, unless the cursor has left the identifier
+    /// they were requested for. The items are filtered by the identifier before the cursor as
+    /// typing continues.
+    pub fn show_completions(
+        &mut self,
+        cx: &mut Cx,
+        session: &Session,
+        position: Position,
+        items: Vec<CompletionItem>,
+    ) {
+        let cursor = last_added_cursor_position(session);
+        let start = identifier_start(session, cursor);
+        if items.is_empty()
+            || cursor.line_index != position.line_index
+            || position.byte_index < start.byte_index
+            || position.byte_index > cursor.byte_index
+        {
+            return;
+        }
+        self.popup.content = Some(PopupContent::Completion {
+            items,
+            filtered_indices: Vec::new(),
+            selected_index: 0,
+        });
+        self.popup.position = start;
+        self.popup.first_visible_row_index = 0;
+        self.update_completions(session);
+        self.redraw(cx);
+    }
+
+    /// Shows the result of a `RequestHover`, if the mouse still rests on the position it was
+    /// requested for.
+    pub fn show_hover(&mut self, cx: &mut Cx, position: Position, contents: String) {
+        if self.hover_position != Some(position) || self.popup.is_completion() {
+            return;
+        }
+        let contents = contents.trim_end();
+        if contents.is_empty() {
+            return;
+        }
+        self.popup.content = Some(PopupContent::Hover(contents.to_string()));
+        self.popup.position = position;
+        self.redraw(cx);
+    }
+
+    /// Shows the result of a `RequestSignatureHelp`. If there is no signature, the signature help
+    /// that is currently shown is closed.
+    pub fn show_signature_help(
+        &mut self,
+        cx: &mut Cx,
+        session: &Session,
+        position: Position,
+        signature: Option<Signature>,
+    ) {
+        if self.popup.is_completion()
+            || last_added_cursor_position(session).line_index != position.line_index
+        {
+            return;
+        }
+        match signature {
+            Some(signature) => {
+                self.popup.content = Some(PopupContent::SignatureHelp(signature));
+                self.popup.position = position;
+            }
+            None => {
+                if let Some(PopupContent::SignatureHelp(_)) = self.popup.content {
+                    self.popup.content = None;
+                }
+            }
+        }
+        self.redraw(cx);
+    }
+
+    // Shows the message, related locations and quick fixes of the decoration at the given
+    // position, if there is one. Returns false if there is nothing to show.
+    fn show_decoration(&mut self, cx: &mut Cx, session: &Session, position: Position) -> bool {
+        if self.popup.is_completion() {
+            return false;
+        }
+        let decorations = session.document().decorations();
+        let decoration = match decoration_at(&decorations, position) {
+            Some(decoration) => decoration,
+            None => return false,
+        };
+        let mut lines: Vec<String> = decoration.message.lines().map(String::from).collect();
+        for related_location in &decoration.related_locations {
+            lines.push(format!(
+                "{}:{}:{}: {}",
+                related_location.path,
+                related_location.start.line_index + 1,
+                related_location.start.byte_index + 1,
+                related_location.message
+            ));
+        }
+        // The quick fixes are always shown, as far as they fit.
+        let quick_fix_count = decoration.quick_fixes.len().min(POPUP_MAX_ROWS);
+        lines.truncate(POPUP_MAX_ROWS - quick_fix_count);
+        let quick_fix_titles = decoration.quick_fixes[..quick_fix_count]
+            .iter()
+            .map(|quick_fix| format!("Fix: {}", quick_fix.title))
+            .collect();
+        drop(decorations);
+        self.popup.content = Some(PopupContent::Decoration {
+            lines,
+            quick_fix_titles,
+        });
+        self.popup.position = position;
+        self.redraw(cx);
+        true
+    }
+
+    // Applies the quick fix with the given index of the decoration at the given position, and
+    // closes the popup. Returns true if the text was changed.
+    fn apply_quick_fix(
+        &mut self,
+        cx: &mut Cx,
+        session: &mut Session,
+        position: Position,
+        index: usize,
+        dispatch_action: &mut dyn FnMut(&mut Cx, CodeEditorAction),
+    ) -> bool {
+        let quick_fix = decoration_at(&session.document().decorations(), position)
+            .and_then(|decoration| decoration.quick_fixes.get(index).cloned());
+        self.close_popup(cx);
+        match quick_fix {
+            Some(quick_fix) if session.apply_quick_fix(&quick_fix) => {
+                dispatch_action(cx, CodeEditorAction::TextDidChange);
+                self.redraw(cx);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn close_popup(&mut self, cx: &mut Cx) {
+        if self.popup.content.take().is_some() {
+            self.redraw(cx);
+        }
+    }
+
+    // Filters the completions by the identifier before the cursor, and closes them if the cursor
+    // has left the identifier or nothing matches anymore.
+    fn update_completions(&mut self, session: &Session) {
+        let cursor = last_added_cursor_position(session);
+        let start = identifier_start(session, cursor);
+        if start != self.popup.position {
+            self.popup.content = None;
+            return;
+        }
+        let prefix = session.document().as_text().as_lines()[cursor.line_index]
+            [start.byte_index..cursor.byte_index]
+            .to_lowercase();
+        if let Some(PopupContent::Completion {
+            items,
+            filtered_indices,
+            selected_index,
+        }) = &mut self.popup.content
+        {
+            *filtered_indices = items
+                .iter()
+                .enumerate()
+                .filter(|(_, item)| is_subsequence(&prefix, &item.filter_text.to_lowercase()))
+                .map(|(index, _)| index)
+                .collect();
+            *selected_index = 0;
+            self.popup.first_visible_row_index = 0;
+            if filtered_indices.is_empty() {
+                self.popup.content = None;
+            }
+        }
+    }
+
+    // Keeps the popup in line with the cursor after it was moved or the text was changed.
+    fn update_popup(&mut self, session: &Session) {
+        match self.popup.content {
+            Some(PopupContent::Completion { .. }) => self.update_completions(session),
+            Some(PopupContent::Hover(_)) | Some(PopupContent::Decoration { .. }) => {
+                self.popup.content = None
+            }
+            Some(PopupContent::SignatureHelp(_)) => {
+                if last_added_cursor_position(session).line_index != self.popup.position.line_index
+                {
+                    self.popup.content = None;
+                }
+            }
+            None => {}
+        }
+    }
+
+    fn accept_completion(
+        &mut self,
+        cx: &mut Cx,
+        session: &mut Session,
+        dispatch_action: &mut dyn FnMut(&mut Cx, CodeEditorAction),
+    ) {
+        if let Some(PopupContent::Completion {
+            items,
+            filtered_indices,
+            selected_index,
+        }) = self.popup.content.take()
+        {
+            session.complete(items[filtered_indices[selected_index]].insert_text.as_str().into());
+            dispatch_action(cx, CodeEditorAction::TextDidChange);
+        }
+        self.redraw(cx);
+    }
+
+    // Handles the keys that go to the completions while they are shown. Returns true if the text
+    // was changed.
+    fn handle_completion_key_down(
+        &mut self,
+        cx: &mut Cx,
+        key_event: &KeyEvent,
+        session: &mut Session,
+        dispatch_action: &mut dyn FnMut(&mut Cx, CodeEditorAction),
+    ) -> bool {
+        match key_event.key_code {
+            KeyCode::ReturnKey | KeyCode::Tab => {
+                self.accept_completion(cx, session, dispatch_action);
+                return true;
+            }
+            KeyCode::Escape => {
+                self.close_popup(cx);
+                return false;
+            }
+            _ => {}
+        }
+        let selected_index = match &mut self.popup.content {
+            Some(PopupContent::Completion {
+                filtered_indices,
+                selected_index,
+                ..
+            }) => {
+                let count = filtered_indices.len();
+                *selected_index = if key_event.key_code == KeyCode::ArrowUp {
+                    (*selected_index + count - 1) % count
+                } else {
+                    (*selected_index + 1) % count
+                };
+                *selected_index
+            }
+            _ => return false,
+        };
+        scroll_row_into_view(&mut self.popup.first_visible_row_index, selected_index);
+        self.redraw(cx);
+        false
+    }
+
+    // Asks for completions or signature help when the text that was just typed calls for it.
+    fn request_after_input(
+        &mut self,
+        cx: &mut Cx,
+        input: &str,
+        session: &Session,
+        dispatch_action: &mut dyn FnMut(&mut Cx, CodeEditorAction),
+    ) {
+        let cursor = last_added_cursor_position(session);
+        let is_path = session.document().as_text().as_lines()[cursor.line_index]
+            [..cursor.byte_index]
+            .ends_with("::");
+        if input.ends_with('.') || is_path {
+            self.popup.content = None;
+            dispatch_action(cx, CodeEditorAction::RequestCompletion(cursor));
+        } else if input == "(" || input == "," {
+            dispatch_action(cx, CodeEditorAction::RequestSignatureHelp(cursor));
+        } else if input == ")" {
+            if let Some(PopupContent::SignatureHelp(_)) = self.popup.content {
+                self.popup.content = None;
+            }
+        }
+    }
+
     pub fn handle_event(
         &mut self,
         cx: &mut Cx,
@@ -589,6 +1557,18 @@ impl CodeEditor {
             }
             self.blink_timer = cx.start_timeout(self.blink_speed)
         }
+        if self.hover_timer.is_event(event).is_some() {
+            if let Some(position) = self.hover_position {
+                if !self.show_decoration(cx, session, position) {
+                    dispatch_action(cx, CodeEditorAction::RequestHover(position));
+                }
+            }
+        }
+        // The text of a key is sent after the key itself, so a key that is bound can only tell
+        // the text input that follows it to be dropped.
+        if let Event::KeyDown(_) = event {
+            self.swallow_text_input = false;
+        }
         let mut keyboard_moved_cursor = false;
         match event.hits(cx, self.scroll_bars.area()) {
             Hit::KeyFocusLost(_) => {
@@ -597,174 +1577,160 @@ impl CodeEditor {
             Hit::KeyFocus(_) => {
                 self.animator_play(cx, id!(focus.on));
             }
-            Hit::KeyDown(KeyEvent {
-                key_code: KeyCode::Escape,
-                is_repeat: false,
-                ..
-            }) => {
-                session.fold();
-                if !self.keep_cursor_in_view.is_locked() {
-                    self.keep_cursor_in_view = KeepCursorInView::LockStart;
-                }
-                self.redraw(cx);
-            }
-            Hit::KeyUp(KeyEvent {
-                key_code: KeyCode::Escape,
-                ..
-            }) => {
-                session.unfold();
-                if !self.keep_cursor_in_view.is_locked() {
-                    self.keep_cursor_in_view = KeepCursorInView::LockStart;
-                }
-                self.redraw(cx);
-            }
-            Hit::KeyDown(KeyEvent {
-                key_code: KeyCode::Minus,
-                modifiers: KeyModifiers { control, logo, .. },
-                ..
-            }) => {
-                if control || logo {
-                    self.decrease_font_size();
-                    self.redraw(cx);
-                }
+            Hit::TextInput(TextInputEvent {
+                was_paste: false, ..
+            }) if self.swallow_text_input => {
+                self.swallow_text_input = false;
             }
-            Hit::KeyDown(KeyEvent {
-                key_code: KeyCode::Key0,
-                modifiers: KeyModifiers { control, logo, .. },
-                ..
-            }) => {
-                if control || logo {
-                    self.reset_font_size();
-                    self.redraw(cx);
+            Hit::KeyDown(key_event) if self.command_palette.is_open => {
+                if self.handle_command_palette_key_down(cx, &key_event, session, dispatch_action) {
+                    keyboard_moved_cursor = true;
                 }
             }
-            Hit::KeyDown(KeyEvent {
-                key_code: KeyCode::Equals,
-                modifiers: KeyModifiers { control, logo, .. },
-                ..
-            }) => {
-                if control || logo {
-                    self.increase_font_size();
-                    self.redraw(cx);
-                }
+            Hit::TextInput(TextInputEvent { ref input, .. })
+                if self.command_palette.is_open && input.len() > 0 =>
+            {
+                self.command_palette.query.push_str(input);
+                self.update_command_palette();
+                self.redraw(cx);
             }
-            Hit::KeyDown(KeyEvent {
-                key_code: KeyCode::KeyW,
-                modifiers: KeyModifiers { control, logo, .. },
-                ..
-            }) => {
-                if control || logo {
-                    self.word_wrap = !self.word_wrap;
-                    self.redraw(cx);
+            Hit::FingerDown(FingerDownEvent { abs, .. })
+                if self.command_palette.is_open && self.command_palette.rect.contains(abs) =>
+            {
+                let palette = &mut self.command_palette;
+                let row_index =
+                    ((abs.y - palette.rect.pos.y - 8.0) / palette.row_height).max(0.0) as usize;
+                // The first row is the query.
+                if row_index > 0
+                    && palette.first_visible_row_index + row_index - 1 < palette.commands.len()
+                {
+                    palette.selected_index = palette.first_visible_row_index + row_index - 1;
+                    if self.run_selected_command(cx, session, dispatch_action) {
+                        keyboard_moved_cursor = true;
+                    }
                 }
             }
-            Hit::KeyDown(KeyEvent {
-                key_code: KeyCode::KeyA,
-                modifiers: KeyModifiers {control, logo, ..},
-                ..
-            }) => {
-                if control || logo {
-                    //session.select_all();
+            Hit::FingerMove(FingerMoveEvent { abs_start, .. })
+                if self.command_palette.is_open
+                    && self.command_palette.rect.contains(abs_start) => {}
+            Hit::KeyDown(key_event)
+                if self.find_panel.has_focus && is_find_panel_key(&key_event) =>
+            {
+                if self.handle_find_key_down(cx, &key_event, session, dispatch_action) {
+                    keyboard_moved_cursor = true;
                     self.redraw(cx);
                 }
             }
-            Hit::KeyDown(KeyEvent {
-                key_code: KeyCode::ArrowLeft,
-                modifiers:
-                    KeyModifiers {
-                        shift,
-                        control,
-                        logo,
-                        ..
-                    },
-                ..
-            }) => {
-                if control || logo {
-                    //session.move_to_start_of_line(!shift);
+            Hit::TextInput(TextInputEvent { ref input, .. })
+                if self.find_panel.has_focus && input.len() > 0 =>
+            {
+                if self.find_panel.is_replace_focused {
+                    self.find_panel.replace.push_str(input);
                 } else {
-                    session.move_left(!shift);
+                    self.find_panel.query.push_str(input);
+                    self.update_search_incrementally(session);
+                    keyboard_moved_cursor = true;
                 }
-                keyboard_moved_cursor = true;
                 self.redraw(cx);
             }
-            Hit::KeyDown(KeyEvent {
-                key_code: KeyCode::ArrowRight,
-                modifiers:
-                    KeyModifiers {
-                        shift,
-                        control,
-                        logo,
-                        ..
-                    },
-                ..
-            }) => {
-                if control || logo {
-                    //session.move_to_end_of_line(!shift);
-                } else {
-                    session.move_right(!shift);
+            Hit::TextCopy(_) | Hit::TextCut(_)
+                if self.find_panel.has_focus || self.command_palette.is_open => {}
+            Hit::FingerDown(FingerDownEvent { abs, .. })
+                if self.find_panel.is_open && self.find_panel.rect.contains(abs) =>
+            {
+                cx.set_key_focus(self.scroll_bars.area());
+                self.find_panel.has_focus = true;
+                let toggle_keys = [KeyCode::KeyC, KeyCode::KeyW, KeyCode::KeyR];
+                if let Some(index) = self
+                    .find_panel
+                    .toggle_rects
+                    .iter()
+                    .position(|rect| rect.contains(abs))
+                {
+                    self.toggle_find_option(toggle_keys[index], session);
+                    keyboard_moved_cursor = true;
+                } else if self.find_panel.is_replace_open {
+                    self.find_panel.is_replace_focused = abs.y
+                        > self.find_panel.rect.pos.y + self.find_panel.rect.size.y * 0.5;
                 }
-
-                keyboard_moved_cursor = true;
                 self.redraw(cx);
             }
-            Hit::KeyDown(KeyEvent {
-                key_code: KeyCode::ArrowUp,
-                modifiers: KeyModifiers { shift, .. },
-                ..
-            }) => {
-                session.move_up(!shift);
-                keyboard_moved_cursor = true;
-                self.redraw(cx);
-            }
-            Hit::KeyDown(KeyEvent {
-                key_code: KeyCode::ArrowDown,
-                modifiers: KeyModifiers { shift, .. },
-                ..
-            }) => {
-                session.move_down(!shift);
-                keyboard_moved_cursor = true;
-                self.redraw(cx);
+            Hit::FingerMove(FingerMoveEvent { abs_start, .. })
+                if self.find_panel.is_open && self.find_panel.rect.contains(abs_start) => {}
+            Hit::KeyDown(key_event)
+                if self.popup.is_completion() && is_completion_key(&key_event) =>
+            {
+                if self.handle_completion_key_down(cx, &key_event, session, dispatch_action) {
+                    keyboard_moved_cursor = true;
+                }
             }
             Hit::KeyDown(KeyEvent {
-                key_code: KeyCode::Home,
-                modifiers: KeyModifiers { shift, .. },
+                key_code: KeyCode::Escape,
                 ..
-            }) => {
-                session.home(!shift);
-                keyboard_moved_cursor = true;
-                self.redraw(cx);
+            }) if self.popup.content.is_some() => {
+                self.close_popup(cx);
             }
-            Hit::KeyDown(KeyEvent {
-                key_code: KeyCode::End,
-                modifiers: KeyModifiers { shift, .. },
-                ..
-            }) => {
-                session.end(!shift);
-                keyboard_moved_cursor = true;
-                self.redraw(cx);
+            Hit::FingerDown(FingerDownEvent { abs, .. })
+                if self.popup.content.is_some() && self.popup.rect.contains(abs) =>
+            {
+                if self.popup.is_completion() {
+                    let row_index = ((abs.y - self.popup.rect.pos.y - 4.0) / self.popup.row_height)
+                        .max(0.0) as usize;
+                    if let Some(PopupContent::Completion {
+                        filtered_indices,
+                        selected_index,
+                        ..
+                    }) = &mut self.popup.content
+                    {
+                        *selected_index = (self.popup.first_visible_row_index + row_index)
+                            .min(filtered_indices.len() - 1);
+                    }
+                    self.accept_completion(cx, session, dispatch_action);
+                    keyboard_moved_cursor = true;
+                } else if let Some(PopupContent::Decoration {
+                    lines,
+                    quick_fix_titles,
+                }) = &self.popup.content
+                {
+                    let row_index = ((abs.y - self.popup.rect.pos.y - 4.0) / self.popup.row_height)
+                        .max(0.0) as usize;
+                    if row_index >= lines.len() && row_index - lines.len() < quick_fix_titles.len()
+                    {
+                        let index = row_index - lines.len();
+                        let position = self.popup.position;
+                        self.apply_quick_fix(cx, session, position, index, dispatch_action);
+                    }
+                }
             }
+            Hit::FingerMove(FingerMoveEvent { abs_start, .. })
+                if self.popup.content.is_some() && self.popup.rect.contains(abs_start) => {}
             Hit::KeyDown(KeyEvent {
-                key_code: KeyCode::PageUp,
-                modifiers: KeyModifiers { shift, .. },
+                key_code: KeyCode::Escape,
+                is_repeat: false,
                 ..
             }) => {
-                for _ in 0..self.line_end - self.line_start - 3 {
-                    session.move_up(!shift);
+                self.pending_key_strokes.clear();
+                session.fold();
+                if !self.keep_cursor_in_view.is_locked() {
+                    self.keep_cursor_in_view = KeepCursorInView::LockStart;
                 }
-                keyboard_moved_cursor = true;
                 self.redraw(cx);
             }
-            Hit::KeyDown(KeyEvent {
-                key_code: KeyCode::PageDown,
-                modifiers: KeyModifiers { shift, .. },
+            Hit::KeyUp(KeyEvent {
+                key_code: KeyCode::Escape,
                 ..
             }) => {
-                for _ in 0..self.line_end - self.line_start - 3 {
-                    session.move_down(!shift);
+                session.unfold();
+                if !self.keep_cursor_in_view.is_locked() {
+                    self.keep_cursor_in_view = KeepCursorInView::LockStart;
                 }
-                keyboard_moved_cursor = true;
                 self.redraw(cx);
             }
+            Hit::KeyDown(key_event) => {
+                if self.handle_key_down(cx, &key_event, session, dispatch_action) {
+                    keyboard_moved_cursor = true;
+                }
+            }
             Hit::TextInput(TextInputEvent {
                 ref input,
                 was_paste: false,
@@ -774,6 +1740,7 @@ impl CodeEditor {
                 self.redraw(cx);
                 keyboard_moved_cursor = true;
                 dispatch_action(cx, CodeEditorAction::TextDidChange);
+                self.request_after_input(cx, input, session, dispatch_action);
             }
             Hit::TextInput(TextInputEvent {
                 ref input,
@@ -785,53 +1752,11 @@ impl CodeEditor {
                 keyboard_moved_cursor = true;
                 dispatch_action(cx, CodeEditorAction::TextDidChange);
             }
-            Hit::KeyDown(KeyEvent {
-                key_code: KeyCode::ReturnKey,
-                ..
-            }) => {
-                session.enter();
-                self.redraw(cx);
-                keyboard_moved_cursor = true;
-                dispatch_action(cx, CodeEditorAction::TextDidChange);
-            }
-            Hit::KeyDown(KeyEvent {
-                key_code: KeyCode::Tab,
-                modifiers: KeyModifiers { shift: false, .. },
-                ..
-            }) => {
-                session.indent();
-                self.redraw(cx);
-                keyboard_moved_cursor = true;
-                dispatch_action(cx, CodeEditorAction::TextDidChange);
-            }
-            Hit::KeyDown(KeyEvent {
-                key_code: KeyCode::Tab,
-                modifiers: KeyModifiers { shift: true, .. },
-                ..
-            }) => {
-                session.outdent();
-                self.redraw(cx);
-                keyboard_moved_cursor = true;
-                dispatch_action(cx, CodeEditorAction::TextDidChange);
-            }
-            Hit::KeyDown(KeyEvent {
-                key_code: KeyCode::Delete,
-                ..
-            }) => {
-                session.delete();
-                self.redraw(cx);
-                keyboard_moved_cursor = true;
-                dispatch_action(cx, CodeEditorAction::TextDidChange);
-            }
-            Hit::KeyDown(KeyEvent {
-                key_code: KeyCode::Backspace,
-                ..
-            }) => {
-                session.backspace();
-                self.redraw(cx);
-                keyboard_moved_cursor = true;
-                dispatch_action(cx, CodeEditorAction::TextDidChange);
-            }
+            // On some platforms, the keys that copy and cut text also send these events, which
+            // are dropped if the keymap uses the keys for something else, like the chords of the
+            // Emacs preset.
+            Hit::TextCopy(_) if self.bindings.is_bound(KeyStroke::shortcut(KeyCode::KeyC)) => {}
+            Hit::TextCut(_) if self.bindings.is_bound(KeyStroke::shortcut(KeyCode::KeyX)) => {}
             Hit::TextCopy(ce) => {
                 *ce.response.borrow_mut() = Some(session.copy());
                 keyboard_moved_cursor = true;
@@ -842,37 +1767,29 @@ impl CodeEditor {
                 keyboard_moved_cursor = true;
                 self.redraw(cx);
             }
-            Hit::KeyDown(KeyEvent {
-                key_code: KeyCode::KeyZ,
+            Hit::FingerDown(FingerDownEvent {
+                abs,
                 modifiers:
                     KeyModifiers {
-                        logo: true,
+                        alt: false,
                         shift: false,
-                        ..
-                    },
-                ..
-            }) => {
-                if session.undo() {
-                    cx.redraw_all();
-                    dispatch_action(cx, CodeEditorAction::TextDidChange);
-                    keyboard_moved_cursor = true;
-                }
-            }
-            Hit::KeyDown(KeyEvent {
-                key_code: KeyCode::KeyZ,
-                modifiers:
-                    KeyModifiers {
-                        logo: true,
-                        shift: true,
-                        ..
+                        control,
+                        logo,
                     },
                 ..
-            }) => {
-                if session.redo() {
-                    self.redraw(cx);
-                    dispatch_action(cx, CodeEditorAction::TextDidChange);
-                    keyboard_moved_cursor = true;
+            }) if control || logo => {
+                self.animator_play(cx, id!(focus.on));
+                cx.set_key_focus(self.scroll_bars.area());
+                self.find_panel.has_focus = false;
+                self.command_palette.is_open = false;
+                self.popup.content = None;
+                let ((cursor, affinity), is_in_gutter) = self.pick(session, abs);
+                session.set_selection(cursor, affinity, SelectionMode::Simple);
+                if !is_in_gutter {
+                    dispatch_action(cx, CodeEditorAction::GoToDefinition(cursor));
                 }
+                self.reset_cursor_blinker(cx);
+                self.redraw(cx);
             }
             Hit::FingerDown(FingerDownEvent {
                 abs,
@@ -887,6 +1804,9 @@ impl CodeEditor {
             }) => {
                 self.animator_play(cx, id!(focus.on));
                 cx.set_key_focus(self.scroll_bars.area());
+                self.find_panel.has_focus = false;
+                self.command_palette.is_open = false;
+                self.popup.content = None;
                 let ((cursor, affinity), is_in_gutter) = self.pick(session, abs);
                 session.set_selection(
                     cursor,
@@ -919,6 +1839,9 @@ impl CodeEditor {
             }) => {
                 self.animator_play(cx, id!(focus.on));
                 cx.set_key_focus(self.scroll_bars.area());
+                self.find_panel.has_focus = false;
+                self.command_palette.is_open = false;
+                self.popup.content = None;
                 let ((cursor, affinity), is_in_gutter) = self.pick(session, abs);
                 session.add_selection(
                     cursor,
@@ -942,8 +1865,20 @@ impl CodeEditor {
                 self.reset_cursor_blinker(cx);
                 self.keep_cursor_in_view = KeepCursorInView::Off;
             }
-            Hit::FingerHoverIn(_) | Hit::FingerHoverOver(_) => {
-                cx.set_cursor(MouseCursor::Text);
+            Hit::FingerHoverIn(FingerHoverEvent { abs, .. })
+            | Hit::FingerHoverOver(FingerHoverEvent { abs, .. }) => {
+                if self.popup.content.is_some() && self.popup.rect.contains(abs)
+                    || self.command_palette.is_open && self.command_palette.rect.contains(abs)
+                {
+                    cx.set_cursor(MouseCursor::Default);
+                } else {
+                    cx.set_cursor(MouseCursor::Text);
+                    self.update_hover_position(cx, session, abs);
+                }
+            }
+            Hit::FingerHoverOut(_) => {
+                cx.stop_timer(self.hover_timer);
+                self.hover_position = None;
             }
             Hit::FingerDown(FingerDownEvent {
                 abs,
@@ -964,6 +1899,7 @@ impl CodeEditor {
             _ => {}
         }
         if keyboard_moved_cursor {
+            self.update_popup(session);
             self.keep_cursor_in_view = KeepCursorInView::Once;
             self.reset_cursor_blinker(cx);
         }
@@ -1176,8 +2112,22 @@ impl CodeEditor {
     }
 
     fn draw_decoration_layer(&mut self, cx: &mut Cx2d<'_>, session: &Session) {
-        let mut active_decoration = None;
         let decorations = session.document().decorations();
+        self.draw_decorations(cx, session, &decorations);
+    }
+
+    fn draw_search_match_layer(&mut self, cx: &mut Cx2d<'_>, session: &Session) {
+        let search_matches = session.search_matches();
+        self.draw_decorations(cx, session, &search_matches);
+    }
+
+    fn draw_decorations(
+        &mut self,
+        cx: &mut Cx2d<'_>,
+        session: &Session,
+        decorations: &[Decoration],
+    ) {
+        let mut active_decoration = None;
         let mut decorations = decorations.iter();
         while decorations.as_slice().first().map_or(false, |decoration| {
             decoration.end().line_index < self.line_start
@@ -1188,7 +2138,7 @@ impl CodeEditor {
             decoration.start().line_index < self.line_start
         }) {
             active_decoration = Some(ActiveDecoration {
-                decoration: *decorations.next().unwrap(),
+                decoration: decorations.next().unwrap(),
                 start_x: 0.0,
             });
         }
@@ -1225,6 +2175,379 @@ impl CodeEditor {
         .draw_selection_layer(cx, session)
     }
 
+    fn draw_find_panel(&mut self, cx: &mut Cx2d<'_>, session: &Session) {
+        if !self.find_panel.is_open {
+            return;
+        }
+        let cell_size =
+            self.draw_find_text.text_style.font_size * self.draw_find_text.get_monospace_base(cx);
+        let row_height = cell_size.y + 8.0;
+        let column_count = 1
+            + FIND_PANEL_LABEL_COLUMNS
+            + FIND_PANEL_FIELD_COLUMNS
+            + 1
+            + 3 * FIND_PANEL_TOGGLES.len()
+            + 1
+            + FIND_PANEL_STATUS_COLUMNS;
+        let row_count = if self.find_panel.is_replace_open { 2 } else { 1 };
+        let size = dvec2(
+            column_count as f64 * cell_size.x,
+            row_count as f64 * row_height + 8.0,
+        );
+        let rect = Rect {
+            pos: self.unscrolled_rect.pos
+                + dvec2(self.unscrolled_rect.size.x - size.x - 20.0, 4.0),
+            size,
+        };
+        self.find_panel.rect = rect;
+        self.draw_find_panel.draw_abs(cx, rect);
+
+        let colors = &self.find_panel_colors;
+        let column_pos = |column: usize, row: usize| {
+            rect.pos + dvec2(column as f64 * cell_size.x, 4.0 + row as f64 * row_height)
+        };
+        let fields = [
+            ("Find", &self.find_panel.query, !self.find_panel.is_replace_focused),
+            ("Replace", &self.find_panel.replace, self.find_panel.is_replace_focused),
+        ];
+        for (row, &(label, text, is_focused)) in fields[..row_count].iter().enumerate() {
+            self.draw_find_text.color = colors.label;
+            self.draw_find_text
+                .draw_abs(cx, column_pos(1, row) + dvec2(0.0, 4.0), label);
+            let field_rect = Rect {
+                pos: column_pos(1 + FIND_PANEL_LABEL_COLUMNS, row) - dvec2(4.0, 0.0),
+                size: dvec2(
+                    FIND_PANEL_FIELD_COLUMNS as f64 * cell_size.x + 4.0,
+                    row_height,
+                ),
+            };
+            self.draw_find_field.color = if is_focused && self.find_panel.has_focus {
+                colors.focused_field
+            } else {
+                colors.field
+            };
+            self.draw_find_field.draw_abs(cx, field_rect);
+            // Only the end of a text that doesn't fit is shown.
+            let char_count = text.chars().count();
+            let visible_text: String = text
+                .chars()
+                .skip(char_count.saturating_sub(FIND_PANEL_FIELD_COLUMNS - 1))
+                .collect();
+            self.draw_find_text.color = if row == 0 && self.find_panel.error.is_some() {
+                colors.error
+            } else {
+                colors.text
+            };
+            let text_pos = column_pos(1 + FIND_PANEL_LABEL_COLUMNS, row) + dvec2(0.0, 4.0);
+            self.draw_find_text.draw_abs(cx, text_pos, &visible_text);
+            if is_focused && self.find_panel.has_focus {
+                self.draw_find_field.color = colors.caret;
+                self.draw_find_field.draw_abs(
+                    cx,
+                    Rect {
+                        pos: text_pos
+                            + dvec2(visible_text.chars().count() as f64 * cell_size.x, 0.0),
+                        size: dvec2(2.0, cell_size.y),
+                    },
+                );
+            }
+        }
+
+        let options = self.find_panel.options;
+        let toggle_states = [options.case_sensitive, options.whole_word, options.regex];
+        let toggle_column = 1 + FIND_PANEL_LABEL_COLUMNS + FIND_PANEL_FIELD_COLUMNS + 1;
+        for (index, (label, is_on)) in FIND_PANEL_TOGGLES.iter().zip(toggle_states).enumerate() {
+            let toggle_rect = Rect {
+                pos: column_pos(toggle_column + 3 * index, 0),
+                size: dvec2(2.5 * cell_size.x, row_height),
+            };
+            self.find_panel.toggle_rects[index] = toggle_rect;
+            if is_on {
+                self.draw_find_field.color = colors.toggle_on;
+                self.draw_find_field.draw_abs(cx, toggle_rect);
+            }
+            self.draw_find_text.color = if is_on { colors.text } else { colors.label };
+            let label_width = label.len() as f64 * cell_size.x;
+            self.draw_find_text.draw_abs(
+                cx,
+                toggle_rect.pos + dvec2((toggle_rect.size.x - label_width) * 0.5, 4.0),
+                label,
+            );
+        }
+
+        let match_count = session.search_matches().len();
+        let status = if self.find_panel.error.is_some() {
+            self.draw_find_text.color = colors.error;
+            "Invalid regex".to_string()
+        } else {
+            self.draw_find_text.color = colors.label;
+            match (session.current_search_match_index(), match_count) {
+                _ if self.find_panel.query.is_empty() => String::new(),
+                (_, 0) => "No results".to_string(),
+                (Some(index), count) => format!("{} of {}", index + 1, count),
+                (None, count) => format!("{} found", count),
+            }
+        };
+        self.draw_find_text.draw_abs(
+            cx,
+            column_pos(toggle_column + 3 * FIND_PANEL_TOGGLES.len() + 1, 0) + dvec2(0.0, 4.0),
+            &status,
+        );
+    }
+
+    fn draw_popup(&mut self, cx: &mut Cx2d<'_>, session: &Session) {
+        let content = match &self.popup.content {
+            Some(content) => content,
+            None => return,
+        };
+        let cell_size = self.draw_popup_text.text_style.font_size
+            * self.draw_popup_text.get_monospace_base(cx);
+        let row_height = cell_size.y + 4.0;
+        self.popup.row_height = row_height;
+
+        // Each row is a list of text pieces with their colors.
+        let colors = &self.popup_colors;
+        let mut rows: Vec<Vec<(&str, Vec4)>> = Vec::new();
+        let mut selected_row_index = None;
+        match content {
+            PopupContent::Completion {
+                items,
+                filtered_indices,
+                selected_index,
+            } => {
+                let first_row_index = self.popup.first_visible_row_index;
+                for (row_index, &item_index) in filtered_indices
+                    .iter()
+                    .enumerate()
+                    .skip(first_row_index)
+                    .take(POPUP_MAX_ROWS)
+                {
+                    let item = &items[item_index];
+                    let label = truncate_columns(&item.label, POPUP_MAX_COLUMNS);
+                    let mut row = vec![(label, colors.text)];
+                    if let Some(detail) = &item.detail {
+                        let column_count = POPUP_MAX_COLUMNS
+                            .saturating_sub(label.chars().count() + 2)
+                            .min(POPUP_MAX_COLUMNS / 2);
+                        row.push(("  ", colors.detail));
+                        row.push((truncate_columns(detail, column_count), colors.detail));
+                    }
+                    rows.push(row);
+                    if row_index == *selected_index {
+                        selected_row_index = Some(row_index - first_row_index);
+                    }
+                }
+            }
+            PopupContent::Hover(contents) => {
+                for line in contents.lines().take(POPUP_MAX_ROWS) {
+                    rows.push(vec![(truncate_columns(line, POPUP_MAX_COLUMNS), colors.text)]);
+                }
+            }
+            PopupContent::SignatureHelp(signature) => {
+                let label = &signature.label;
+                rows.push(match &signature.active_parameter {
+                    Some(range) => vec![
+                        (&label[..range.start], colors.detail),
+                        (&label[range.clone()], colors.active_parameter),
+                        (&label[range.end..], colors.detail),
+                    ],
+                    None => vec![(label.as_str(), colors.detail)],
+                });
+            }
+            PopupContent::Decoration {
+                lines,
+                quick_fix_titles,
+            } => {
+                for line in lines {
+                    rows.push(vec![(truncate_columns(line, POPUP_MAX_COLUMNS), colors.text)]);
+                }
+                for title in quick_fix_titles {
+                    rows.push(vec![(
+                        truncate_columns(title, POPUP_MAX_COLUMNS),
+                        colors.quick_fix,
+                    )]);
+                }
+            }
+        }
+        let column_count = rows
+            .iter()
+            .map(|row| row.iter().map(|(text, _)| text.chars().count()).sum::<usize>())
+            .max()
+            .unwrap_or(0);
+        let size = dvec2(
+            (column_count + 2) as f64 * cell_size.x,
+            rows.len() as f64 * row_height + 8.0,
+        );
+
+        // The popup goes below the line of its position, or above it if there is no room below.
+        let (x, y) = session
+            .layout()
+            .logical_to_normalized_position(self.popup.position, Affinity::Before);
+        let line_pos = self.viewport_rect.pos + dvec2(x, y) * self.cell_size;
+        let visible_rect = self.unscrolled_rect;
+        let mut pos = line_pos + dvec2(-cell_size.x, self.cell_size.y + 2.0);
+        if pos.y + size.y > visible_rect.pos.y + visible_rect.size.y
+            && line_pos.y - size.y - 2.0 > visible_rect.pos.y
+        {
+            pos.y = line_pos.y - size.y - 2.0;
+        }
+        pos.x = pos
+            .x
+            .min(visible_rect.pos.x + visible_rect.size.x - size.x)
+            .max(visible_rect.pos.x);
+        let rect = Rect { pos, size };
+        self.popup.rect = rect;
+        self.draw_popup.draw_abs(cx, rect);
+
+        for (row_index, row) in rows.iter().enumerate() {
+            let row_pos = rect.pos + dvec2(cell_size.x, 4.0 + row_index as f64 * row_height);
+            if selected_row_index == Some(row_index) {
+                self.draw_popup_selection.color = colors.selection;
+                self.draw_popup_selection.draw_abs(
+                    cx,
+                    Rect {
+                        pos: dvec2(rect.pos.x + 2.0, row_pos.y),
+                        size: dvec2(rect.size.x - 4.0, row_height),
+                    },
+                );
+            }
+            let mut column_index = 0;
+            for &(text, color) in row {
+                self.draw_popup_text.color = color;
+                self.draw_popup_text.draw_abs(
+                    cx,
+                    row_pos + dvec2(column_index as f64 * cell_size.x, 2.0),
+                    text,
+                );
+                column_index += text.chars().count();
+            }
+        }
+    }
+
+    fn draw_command_palette(&mut self, cx: &mut Cx2d<'_>) {
+        if !self.command_palette.is_open {
+            return;
+        }
+        let cell_size =
+            self.draw_popup_text.text_style.font_size * self.draw_popup_text.get_monospace_base(cx);
+        let row_height = cell_size.y + 4.0;
+        self.command_palette.row_height = row_height;
+
+        // The palette has a row for the query, and at least one for the commands.
+        let row_count = self.command_palette.commands.len().clamp(1, POPUP_MAX_ROWS) + 1;
+        let size = dvec2(
+            (COMMAND_PALETTE_COLUMNS + 2) as f64 * cell_size.x,
+            row_count as f64 * row_height + 12.0,
+        );
+        let visible_rect = self.unscrolled_rect;
+        let rect = Rect {
+            pos: visible_rect.pos + dvec2(((visible_rect.size.x - size.x) * 0.5).max(0.0), 4.0),
+            size,
+        };
+        self.command_palette.rect = rect;
+        self.draw_popup.draw_abs(cx, rect);
+
+        let colors = &self.popup_colors;
+        let text_pos = rect.pos + dvec2(cell_size.x, 6.0);
+        self.draw_find_field.color = self.find_panel_colors.focused_field;
+        self.draw_find_field.draw_abs(
+            cx,
+            Rect {
+                pos: text_pos - dvec2(4.0, 2.0),
+                size: dvec2(
+                    COMMAND_PALETTE_COLUMNS as f64 * cell_size.x + 8.0,
+                    row_height,
+                ),
+            },
+        );
+        // Only the end of a query that doesn't fit is shown.
+        let char_count = self.command_palette.query.chars().count();
+        let visible_query: String = self
+            .command_palette
+            .query
+            .chars()
+            .skip(char_count.saturating_sub(COMMAND_PALETTE_COLUMNS - 1))
+            .collect();
+        self.draw_popup_text.color = colors.text;
+        self.draw_popup_text.draw_abs(cx, text_pos, &visible_query);
+        self.draw_find_field.color = self.find_panel_colors.caret;
+        self.draw_find_field.draw_abs(
+            cx,
+            Rect {
+                pos: text_pos + dvec2(visible_query.chars().count() as f64 * cell_size.x, 0.0),
+                size: dvec2(2.0, cell_size.y),
+            },
+        );
+
+        if self.command_palette.commands.is_empty() {
+            self.draw_popup_text.color = colors.detail;
+            self.draw_popup_text.draw_abs(
+                cx,
+                text_pos + dvec2(0.0, row_height + 4.0),
+                "No matching commands",
+            );
+        }
+        // Each command is shown with the first keys it is bound to.
+        let first_row_index = self.command_palette.first_visible_row_index;
+        for (row_index, &command) in self
+            .command_palette
+            .commands
+            .iter()
+            .enumerate()
+            .skip(first_row_index)
+            .take(POPUP_MAX_ROWS)
+        {
+            let row_pos = rect.pos
+                + dvec2(
+                    cell_size.x,
+                    8.0 + (row_index - first_row_index + 1) as f64 * row_height,
+                );
+            if row_index == self.command_palette.selected_index {
+                self.draw_popup_selection.color = colors.selection;
+                self.draw_popup_selection.draw_abs(
+                    cx,
+                    Rect {
+                        pos: dvec2(rect.pos.x + 2.0, row_pos.y),
+                        size: dvec2(rect.size.x - 4.0, row_height),
+                    },
+                );
+            }
+            self.draw_popup_text.color = colors.text;
+            self.draw_popup_text
+                .draw_abs(cx, row_pos + dvec2(0.0, 2.0), command.title());
+            if let Some(key_strokes) = self.bindings.bindings_for(command).next() {
+                let keys = format_key_strokes(key_strokes);
+                let column_index = COMMAND_PALETTE_COLUMNS.saturating_sub(keys.chars().count());
+                self.draw_popup_text.color = colors.detail;
+                self.draw_popup_text.draw_abs(
+                    cx,
+                    row_pos + dvec2(column_index as f64 * cell_size.x, 2.0),
+                    &keys,
+                );
+            }
+        }
+    }
+
+    // Restarts the hover delay when the mouse moves to another position in the text. The hover
+    // info that is shown for the previous position is closed.
+    fn update_hover_position(&mut self, cx: &mut Cx, session: &Session, abs: DVec2) {
+        let ((position, _), is_in_gutter) = self.pick(session, abs);
+        let position = if is_in_gutter { None } else { Some(position) };
+        if position == self.hover_position {
+            return;
+        }
+        self.hover_position = position;
+        cx.stop_timer(self.hover_timer);
+        if position.is_some() {
+            self.hover_timer = cx.start_timeout(self.hover_delay);
+        }
+        if let Some(PopupContent::Hover(_)) | Some(PopupContent::Decoration { .. }) =
+            self.popup.content
+        {
+            self.close_popup(cx);
+        }
+    }
+
     fn pick(&self, session: &Session, position: DVec2) -> ((Position, Affinity), bool) {
         let position = (position - self.viewport_rect.pos) / self.cell_size;
         if position.y < 0.0 {
@@ -1439,11 +2762,124 @@ impl CodeEditor {
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum CodeEditorAction {
     TextDidChange,
+    /// Completions were asked for at the given position, either explicitly with the
+    /// `trigger_completion` command or by typing `.` or `::`. Show them with `show_completions`.
+    RequestCompletion(Position),
+    /// The mouse rested on the given position. Show the result with `show_hover`.
+    RequestHover(Position),
+    /// A call's argument list was opened or continued at the given position. Show the result with
+    /// `show_signature_help`.
+    RequestSignatureHelp(Position),
+    /// The `go_to_definition` command was run, or the given position was clicked while holding
+    /// Ctrl or Cmd.
+    GoToDefinition(Position),
+}
+
+// The keys that go to the find panel while it has the key focus. Other keys go to the keymap.
+fn is_find_panel_key(key_event: &KeyEvent) -> bool {
+    let KeyModifiers {
+        control, alt, logo, ..
+    } = key_event.modifiers;
+    match key_event.key_code {
+        KeyCode::Escape | KeyCode::ReturnKey | KeyCode::Tab | KeyCode::Backspace => true,
+        KeyCode::KeyC | KeyCode::KeyW | KeyCode::KeyR => (control || logo) && alt,
+        _ => false,
+    }
+}
+
+// The commands that can be run while the find panel has the key focus.
+fn is_find_command(command: Command) -> bool {
+    match command {
+        Command::Find
+        | Command::FindAndReplace
+        | Command::FindNext
+        | Command::FindPrev
+        | Command::SelectAllMatches
+        | Command::CommandPalette => true,
+        _ => false,
+    }
+}
+
+fn is_completion_key(key_event: &KeyEvent) -> bool {
+    match key_event.key_code {
+        KeyCode::ArrowUp | KeyCode::ArrowDown | KeyCode::ReturnKey | KeyCode::Escape => true,
+        KeyCode::Tab => !key_event.modifiers.shift,
+        _ => false,
+    }
+}
+
+fn last_added_cursor_position(session: &Session) -> Position {
+    session.selections()[session.last_added_selection_index().unwrap()]
+        .cursor
+        .position
+}
+
+fn identifier_start(session: &Session, position: Position) -> Position {
+    Position {
+        line_index: position.line_index,
+        byte_index: session.document().as_text().as_lines()[position.line_index]
+            .find_identifier_start(position.byte_index),
+    }
+}
+
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut chars = haystack.chars();
+    needle.chars().all(|char| chars.any(|other_char| other_char == char))
+}
+
+// Returns how well `needle` matches `haystack` as a subsequence, lower being better, or `None`
+// if it doesn't match. Every char that is skipped costs one, as does a match that is not at the
+// start of a word, so `fn` ranks `Find Next` above `Go to Definition`.
+fn fuzzy_score(needle: &str, haystack: &str) -> Option<usize> {
+    let mut score = 0;
+    let mut prev_char = ' ';
+    let mut chars = haystack.chars();
+    for needle_char in needle.chars() {
+        loop {
+            let char = chars.next()?;
+            let is_word_start = prev_char == ' ';
+            prev_char = char;
+            if char == needle_char {
+                if !is_word_start {
+                    score += 1;
+                }
+                break;
+            }
+            score += 1;
+        }
+    }
+    Some(score)
+}
+
+// Returns the decoration at the given position that has something to show in a popup.
+fn decoration_at(decorations: &[Decoration], position: Position) -> Option<&Decoration> {
+    decorations.iter().find(|decoration| {
+        decoration.contains(position)
+            && (!decoration.message.is_empty()
+                || !decoration.related_locations.is_empty()
+                || !decoration.quick_fixes.is_empty())
+    })
+}
+
+// Scrolls a list of at most `POPUP_MAX_ROWS` visible rows so that the given row is visible.
+fn scroll_row_into_view(first_visible_row_index: &mut usize, row_index: usize) {
+    if row_index < *first_visible_row_index {
+        *first_visible_row_index = row_index;
+    } else if row_index >= *first_visible_row_index + POPUP_MAX_ROWS {
+        *first_visible_row_index = row_index + 1 - POPUP_MAX_ROWS;
+    }
+}
+
+fn truncate_columns(text: &str, column_count: usize) -> &str {
+    match text.char_indices().nth(column_count) {
+        Some((index, _)) => &text[..index],
+        None => text,
+    }
 }
 
 struct DrawDecorationLayer<'a> {
     code_editor: &'a mut CodeEditor,
-    active_decoration: Option<ActiveDecoration>,
+    active_decoration: Option<ActiveDecoration<'a>>,
     decorations: Iter<'a, Decoration>,
 }
 
@@ -1586,7 +3022,7 @@ impl<'a> DrawDecorationLayer<'a> {
                 decoration.start() == position && affinity == Affinity::After
             })
         {
-            let decoration = *self.decorations.next().unwrap();
+            let decoration = self.decorations.next().unwrap();
             if !decoration.is_empty() {
                 let (start_x, _) = line.grid_to_normalized_position(row_index, column_index);
                 self.active_decoration = Some(ActiveDecoration {
@@ -1607,31 +3043,35 @@ impl<'a> DrawDecorationLayer<'a> {
     ) {
         let start_x = mem::take(&mut self.active_decoration.as_mut().unwrap().start_x);
         let (x, y) = line.grid_to_normalized_position(row_index, column_index);
+        let rect = Rect {
+            pos: DVec2 {
+                x: start_x,
+                y: origin_y + y,
+            } * self.code_editor.cell_size
+                + self.code_editor.viewport_rect.pos,
+            size: DVec2 {
+                x: x - start_x,
+                y: line.scale(),
+            } * self.code_editor.cell_size,
+        };
         self.code_editor.draw_decoration.color =
             match self.active_decoration.as_mut().unwrap().decoration.ty {
-                DecorationType::Warning => self.code_editor.token_colors.warning_decoration,
                 DecorationType::Error => self.code_editor.token_colors.error_decoration,
+                DecorationType::Warning => self.code_editor.token_colors.warning_decoration,
+                DecorationType::Info => self.code_editor.token_colors.info_decoration,
+                DecorationType::Hint => self.code_editor.token_colors.hint_decoration,
+                DecorationType::SearchMatch => {
+                    self.code_editor.draw_search_match.draw_abs(cx, rect);
+                    return;
+                }
             };
 
-        self.code_editor.draw_decoration.draw_abs(
-            cx,
-            Rect {
-                pos: DVec2 {
-                    x: start_x,
-                    y: origin_y + y,
-                } * self.code_editor.cell_size
-                    + self.code_editor.viewport_rect.pos,
-                size: DVec2 {
-                    x: x - start_x,
-                    y: line.scale(),
-                } * self.code_editor.cell_size,
-            },
-        );
+        self.code_editor.draw_decoration.draw_abs(cx, rect);
     }
 }
 
-struct ActiveDecoration {
-    decoration: Decoration,
+struct ActiveDecoration<'a> {
+    decoration: &'a Decoration,
     start_x: f64,
 }
 
@@ -1921,6 +3361,42 @@ struct TokenColors {
     error_decoration: Vec4,
     #[live]
     warning_decoration: Vec4,
+    #[live]
+    info_decoration: Vec4,
+    #[live]
+    hint_decoration: Vec4,
+}
+
+#[derive(Live, LiveHook)]
+struct FindPanelColors {
+    #[live]
+    text: Vec4,
+    #[live]
+    label: Vec4,
+    #[live]
+    field: Vec4,
+    #[live]
+    focused_field: Vec4,
+    #[live]
+    caret: Vec4,
+    #[live]
+    toggle_on: Vec4,
+    #[live]
+    error: Vec4,
+}
+
+#[derive(Live, LiveHook)]
+struct PopupColors {
+    #[live]
+    text: Vec4,
+    #[live]
+    detail: Vec4,
+    #[live]
+    selection: Vec4,
+    #[live]
+    active_parameter: Vec4,
+    #[live]
+    quick_fix: Vec4,
 }
 
 #[derive(Live, LiveHook)]
@@ -7,58 +7,105 @@ use {
 pub enum DecorationType {
     Error,
     Warning,
+    Info,
+    Hint,
+    SearchMatch,
 }
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+/// A range of text that is marked, such as a problem reported by the compiler or a search match.
+///
+/// Problems can explain themselves with a message and related locations, which are shown when
+/// hovering the decoration, and can offer quick fixes.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Decoration {
     pub id: usize,
     pub ty: DecorationType,
     start: Position,
     end: Position,
+    pub message: String,
+    pub related_locations: Vec<RelatedLocation>,
+    pub quick_fixes: Vec<QuickFix>,
 }
 
 impl Decoration {
     pub fn new(id: usize, start: Position, end: Position, ty: DecorationType) -> Self {
-        if start > end {
-            return Self {
-                ty,
-                id,
-                start: end,
-                end: start,
-            };
+        let (start, end) = if start > end {
+            (end, start)
+        } else {
+            (start, end)
+        };
+        Self {
+            id,
+            ty,
+            start,
+            end,
+            message: String::new(),
+            related_locations: Vec::new(),
+            quick_fixes: Vec::new(),
         }
-        Self { ty, id, start, end }
     }
 
-    pub fn is_empty(self) -> bool {
+    pub fn is_empty(&self) -> bool {
         self.start == self.end
     }
 
-    pub fn overlaps_with(self, other: Self) -> bool {
+    pub fn overlaps_with(&self, other: &Self) -> bool {
         self.end() > other.start()
     }
 
-    pub fn length(self) -> Length {
+    pub fn contains(&self, position: Position) -> bool {
+        self.start <= position && position <= self.end
+    }
+
+    pub fn length(&self) -> Length {
         self.end - self.start
     }
 
-    pub fn start(self) -> Position {
+    pub fn start(&self) -> Position {
         self.start
     }
 
-    pub fn end(self) -> Position {
+    pub fn end(&self) -> Position {
         self.end
     }
 
-    pub fn apply_edit(self, edit: &Edit) -> Self {
-        Self {
-            start: self.start.apply_edit(edit),
-            end: self.end.apply_edit(edit),
-            ..self
+    pub fn apply_edit(&mut self, edit: &Edit) {
+        self.start = self.start.apply_edit(edit);
+        self.end = self.end.apply_edit(edit);
+        for quick_fix in &mut self.quick_fixes {
+            for replacement in &mut quick_fix.replacements {
+                replacement.start = replacement.start.apply_edit(edit);
+                replacement.end = replacement.end.apply_edit(edit);
+            }
         }
     }
 }
 
+/// A location that helps to explain a decoration, such as the place where a borrowed value was
+/// moved. The location may be in another file, so it is not kept up to date with edits.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct RelatedLocation {
+    pub path: String,
+    pub start: Position,
+    pub end: Position,
+    pub message: String,
+}
+
+/// A change that fixes the problem a decoration points out, made up of replacements in the same
+/// document as the decoration.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct QuickFix {
+    pub title: String,
+    pub replacements: Vec<Replacement>,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Replacement {
+    pub start: Position,
+    pub end: Position,
+    pub text: String,
+}
+
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct DecorationSet {
     decorations: Vec<Decoration>,
@@ -94,9 +141,26 @@ impl DecorationSet {
         self.decorations.clear();
     }
 
+    /// Returns whether `decoration` overlaps with, or starts at the same position as, a decoration
+    /// in this set, that is, whether adding it would remove any.
+    pub fn overlaps(&self, decoration: &Decoration) -> bool {
+        let index = match self
+            .decorations
+            .binary_search_by_key(&decoration.start(), |decoration| decoration.start())
+        {
+            Ok(_) => return true,
+            Err(index) => index,
+        };
+        index > 0 && self.decorations[index - 1].overlaps_with(decoration)
+            || self
+                .decorations
+                .get(index)
+                .is_some_and(|next| decoration.overlaps_with(next))
+    }
+
     pub fn apply_edit(&mut self, edit: &Edit) {
         for decoration in &mut self.decorations {
-            *decoration = decoration.apply_edit(edit);
+            decoration.apply_edit(edit);
         }
     }
 
@@ -104,7 +168,7 @@ impl DecorationSet {
         let mut index = index;
         while index > 0 {
             let prev_index = index - 1;
-            if !self.decorations[prev_index].overlaps_with(self.decorations[index]) {
+            if !self.decorations[prev_index].overlaps_with(&self.decorations[index]) {
                 break;
             }
             self.decorations.remove(prev_index);
@@ -112,7 +176,7 @@ impl DecorationSet {
         }
         while index + 1 < self.decorations.len() {
             let next_index = index + 1;
-            if !self.decorations[index].overlaps_with(self.decorations[next_index]) {
+            if !self.decorations[index].overlaps_with(&self.decorations[next_index]) {
                 break;
             }
             self.decorations.remove(next_index);
@@ -144,3 +208,40 @@ impl<'a> IntoIterator for &'a DecorationSet {
         self.iter()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decoration(start: usize, end: usize) -> Decoration {
+        Decoration::new(
+            0,
+            Position {
+                line_index: 0,
+                byte_index: start,
+            },
+            Position {
+                line_index: 0,
+                byte_index: end,
+            },
+            DecorationType::Error,
+        )
+    }
+
+    #[test]
+    fn overlaps() {
+        let mut set = DecorationSet::new();
+        assert!(!set.overlaps(&decoration(0, 1)));
+        set.add_decoration(decoration(2, 4));
+        set.add_decoration(decoration(6, 8));
+        assert!(!set.overlaps(&decoration(0, 2)));
+        assert!(!set.overlaps(&decoration(4, 6)));
+        assert!(!set.overlaps(&decoration(8, 9)));
+        assert!(set.overlaps(&decoration(1, 3)));
+        assert!(set.overlaps(&decoration(3, 3)));
+        assert!(set.overlaps(&decoration(5, 7)));
+        assert!(set.overlaps(&decoration(0, 10)));
+        // An empty decoration where another starts would replace it.
+        assert!(set.overlaps(&decoration(6, 6)));
+    }
+}
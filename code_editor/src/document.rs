@@ -14,7 +14,7 @@ use {
         tokenizer::Tokenizer,
     },
     std::{
-        cell::{Ref, RefCell},
+        cell::{Cell, Ref, RefCell},
         cmp::Ordering,
         collections::HashMap,
         iter,
@@ -29,11 +29,29 @@ pub struct Document(Rc<DocumentInner>);
 
 impl Document {
     pub fn new(text: Text, decorations: DecorationSet) -> Self {
+        Self::with_tokenizer(text, decorations, Tokenizer::default())
+    }
+
+    /// Creates a document that is coloured by the given tokenizer, see `Tokenizer::for_path`.
+    pub fn with_tokenizer(text: Text, decorations: DecorationSet, tokenizer: Tokenizer) -> Self {
         let line_count = text.as_lines().len();
-        let tokens: Vec<_> = (0..line_count)
-            .map(|line| tokenize(&text.as_lines()[line]).collect::<Vec<_>>())
+        // Lines are only tokenized once they are laid out, see `layout_lines`. Until then, every
+        // line is a single token.
+        let tokens: Vec<_> = text
+            .as_lines()
+            .iter()
+            .map(|line| {
+                if line.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![Token {
+                        len: line.len(),
+                        kind: TokenKind::Unknown,
+                    }]
+                }
+            })
             .collect();
-        let inner = Self(Rc::new(DocumentInner {
+        Self(Rc::new(DocumentInner {
             history: RefCell::new(History::from(text)),
             layout: RefCell::new(DocumentLayout {
                 indent_state: (0..line_count).map(|_| None).collect(),
@@ -41,16 +59,16 @@ impl Document {
                 inline_inlays: (0..line_count).map(|_| Vec::new()).collect(),
                 block_inlays: Vec::new(),
             }),
-            tokenizer: RefCell::new(Tokenizer::new(line_count)),
+            indent_state_end: Cell::new(0),
+            tokenizer: RefCell::new(tokenizer),
+            shown_decorations: RefCell::new(decorations.clone()),
             decorations: RefCell::new(decorations),
+            diagnostics: RefCell::new(DecorationSet::new()),
             edit_senders: RefCell::new(HashMap::new()),
-        }));
-        inner.update_indent_state();
-        inner.0.tokenizer.borrow_mut().update(
-            &inner.0.history.borrow().as_text(),
-            &mut inner.0.layout.borrow_mut().tokens,
-        );
-        inner
+            edit_listeners: RefCell::new(Vec::new()),
+            change_listeners: RefCell::new(Vec::new()),
+            is_read_only: Cell::new(false),
+        }))
     }
 
     pub fn as_text(&self) -> Ref<'_, Text> {
@@ -61,12 +79,43 @@ impl Document {
         self.0.layout.borrow()
     }
 
+    /// Returns the decorations that are shown, which are the decorations added with
+    /// `add_decoration` and the diagnostics that don't overlap with them.
     pub fn decorations(&self) -> Ref<'_, [Decoration]> {
-        Ref::map(self.0.decorations.borrow(), |decorations| {
+        Ref::map(self.0.shown_decorations.borrow(), |decorations| {
             decorations.as_decorations()
         })
     }
 
+    /// Returns the token that starts a line comment in the language of this document, if any.
+    pub fn line_comment(&self) -> Option<&'static str> {
+        self.0.tokenizer.borrow().line_comment()
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.0.is_read_only.get()
+    }
+
+    /// Makes the sessions of this document unable to edit it, including undo and redo. Edits
+    /// applied with `apply_edits` still go through. This is meant for documents that are too large
+    /// to edit comfortably, like a text created with `Text::map_file`.
+    pub fn set_read_only(&self, is_read_only: bool) {
+        self.0.is_read_only.set(is_read_only);
+    }
+
+    /// Brings the tokens and indent state of the lines before `end` up to date. These are computed
+    /// lazily, so that opening or editing a large document only costs as much as the lines that
+    /// are actually laid out.
+    pub fn layout_lines(&self, end: usize) {
+        let end = end.min(self.as_text().as_lines().len());
+        self.update_indent_state(end);
+        self.0.tokenizer.borrow_mut().update(
+            self.0.history.borrow().as_text(),
+            &mut self.0.layout.borrow_mut().tokens,
+            end,
+        );
+    }
+
     pub fn edit_selections(
         &self,
         session_id: SessionId,
@@ -75,6 +124,9 @@ impl Document {
         settings: &Settings,
         mut f: impl FnMut(Editor<'_>, Position, Length),
     ) {
+        if self.is_read_only() {
+            return;
+        }
         let mut history = self.0.history.borrow_mut();
         history.push_or_extend_group(session_id, kind, selections);
         let mut edits = Vec::new();
@@ -135,7 +187,7 @@ impl Document {
         }
         drop(history);
         self.autoindent(&line_ranges, settings.tab_column_count, &mut edits);
-        self.update_after_edit(session_id, None, &edits);
+        self.update_after_edit(Some(session_id), None, &edits);
     }
 
     pub fn edit_linewise(
@@ -145,6 +197,9 @@ impl Document {
         selections: &SelectionSet,
         mut f: impl FnMut(Editor, usize),
     ) {
+        if self.is_read_only() {
+            return;
+        }
         let mut history = self.0.history.borrow_mut();
         history.push_or_extend_group(origin_id, kind, selections);
         let mut edits = Vec::new();
@@ -171,15 +226,119 @@ impl Document {
             }
         }
         drop(history);
-        self.update_after_edit(origin_id, None, &edits);
+        self.update_after_edit(Some(origin_id), None, &edits);
+    }
+
+    /// Calls `f` once to make edits that don't follow the selections, such as the replacements of
+    /// a quick fix. The selections are moved along with the text they are in.
+    pub fn edit(
+        &self,
+        origin_id: SessionId,
+        kind: EditKind,
+        selections: &SelectionSet,
+        f: impl FnOnce(Editor),
+    ) {
+        if self.is_read_only() {
+            return;
+        }
+        let mut history = self.0.history.borrow_mut();
+        history.push_or_extend_group(origin_id, kind, selections);
+        let mut edits = Vec::new();
+        f(Editor {
+            history: &mut *history,
+            edits: &mut edits,
+        });
+        drop(history);
+        self.update_after_edit(Some(origin_id), None, &edits);
+    }
+
+    /// Like `edit_linewise`, but calls `f` once for every range of lines that the selections span,
+    /// after merging the ranges that overlap or touch. Edits for one range may insert or delete
+    /// lines, the ranges after it are moved accordingly.
+    pub fn edit_line_ranges(
+        &self,
+        origin_id: SessionId,
+        kind: EditKind,
+        selections: &SelectionSet,
+        mut f: impl FnMut(Editor, Range<usize>),
+    ) {
+        if self.is_read_only() {
+            return;
+        }
+        let mut history = self.0.history.borrow_mut();
+        history.push_or_extend_group(origin_id, kind, selections);
+        let mut edits = Vec::new();
+        let mut line_delta = 0;
+        for line_range in selections
+            .iter()
+            .copied()
+            .map(|selection| selection.line_range())
+            .merge(|line_range_0, line_range_1| {
+                if line_range_0.end >= line_range_1.start {
+                    Ok(line_range_0.start..line_range_1.end)
+                } else {
+                    Err((line_range_0, line_range_1))
+                }
+            })
+        {
+            let edit_start = edits.len();
+            f(
+                Editor {
+                    history: &mut *history,
+                    edits: &mut edits,
+                },
+                (line_range.start as isize + line_delta) as usize
+                    ..(line_range.end as isize + line_delta) as usize,
+            );
+            for edit in &edits[edit_start..] {
+                line_delta += match edit.change {
+                    Change::Insert(_, ref text) => text.length().line_count as isize,
+                    Change::Delete(_, length) => -(length.line_count as isize),
+                };
+            }
+        }
+        drop(history);
+        self.update_after_edit(Some(origin_id), None, &edits);
     }
 
     pub fn add_decoration(&mut self, decoration: Decoration) {
-        self.0.decorations.borrow_mut().add_decoration(decoration);
+        self.0
+            .decorations
+            .borrow_mut()
+            .add_decoration(decoration.clone());
+        self.0
+            .shown_decorations
+            .borrow_mut()
+            .add_decoration(decoration);
     }
 
+    /// Removes the decorations added with `add_decoration`, but not the diagnostics.
     pub fn clear_decorations(&mut self) {
-        self.0.decorations.borrow_mut().clear()
+        self.0.decorations.borrow_mut().clear();
+        self.update_shown_decorations();
+    }
+
+    /// Replaces the diagnostics, such as the ones a language server publishes. They are kept
+    /// apart from the other decorations, so that either can be replaced without losing the other,
+    /// and are only shown where they don't overlap with those.
+    pub fn set_diagnostics(&mut self, diagnostics: impl IntoIterator<Item = Decoration>) {
+        let mut set = self.0.diagnostics.borrow_mut();
+        set.clear();
+        for diagnostic in diagnostics {
+            set.add_decoration(diagnostic);
+        }
+        drop(set);
+        self.update_shown_decorations();
+    }
+
+    fn update_shown_decorations(&self) {
+        let mut shown_decorations = self.0.decorations.borrow().clone();
+        for diagnostic in self.0.diagnostics.borrow().iter() {
+            if !shown_decorations.overlaps(diagnostic) {
+                shown_decorations.add_decoration(diagnostic.clone());
+            }
+        }
+        *self.0.shown_decorations.borrow_mut() = shown_decorations;
     }
 
     pub fn add_session(
@@ -197,6 +356,31 @@ impl Document {
         self.0.edit_senders.borrow_mut().remove(&session_id);
     }
 
+    /// Adds a listener for the edits that are made through the sessions of this document, including
+    /// undo and redo, in the order they are applied. Edits applied with `apply_edits` are not sent.
+    /// A listener is removed once its receiver is dropped.
+    pub fn add_edit_listener(&self, edit_sender: Sender<Vec<Edit>>) {
+        self.0.edit_listeners.borrow_mut().push(edit_sender);
+    }
+
+    /// Adds a listener for every edit that is applied to this document, including the edits
+    /// applied with `apply_edits`, in the order they are applied. A listener is removed once its
+    /// receiver is dropped.
+    pub fn add_change_listener(&self, edit_sender: Sender<Vec<Edit>>) {
+        self.0.change_listeners.borrow_mut().push(edit_sender);
+    }
+
+    /// Applies edits that don't come from any session, like the edits of another participant in a
+    /// collab session. Every session is updated as if another session made them.
+    pub fn apply_edits(&self, edits: Vec<Edit>) {
+        let mut history = self.0.history.borrow_mut();
+        for edit in &edits {
+            history.apply_external_edit(edit.clone());
+        }
+        drop(history);
+        self.update_after_edit(None, None, &edits);
+    }
+
     fn autoindent(
         &self,
         line_ranges: &[Range<usize>],
@@ -239,9 +423,10 @@ impl Document {
                 }
             })
         {
-            let mut desired_indentation_column_count = self.as_text().as_lines()
-                [..line_range.start]
-                .iter()
+            let mut desired_indentation_column_count = self
+                .as_text()
+                .as_lines()
+                .range(0..line_range.start)
                 .rev()
                 .find_map(|line| next_line_indent_column_count(line, indent_column_count))
                 .unwrap_or(0);
@@ -259,7 +444,8 @@ impl Document {
                     })
                     .unwrap_or(false)
                 {
-                    desired_indentation_column_count -= 4;
+                    desired_indentation_column_count =
+                        desired_indentation_column_count.saturating_sub(indent_column_count);
                 }
                 self.edit_lines_internal(line, edits, |line| {
                     crate::session::reindent(line, |_| desired_indentation_column_count)
@@ -320,10 +506,13 @@ impl Document {
     }
 
     pub fn undo(&self, origin_id: SessionId, selections: &SelectionSet) -> bool {
+        if self.is_read_only() {
+            return false;
+        }
         let mut changes = Vec::new();
         let selections = self.0.history.borrow_mut().undo(selections, &mut changes);
         if let Some(selections) = selections {
-            self.update_after_edit(origin_id, Some(selections), &changes);
+            self.update_after_edit(Some(origin_id), Some(selections), &changes);
             true
         } else {
             false
@@ -331,10 +520,13 @@ impl Document {
     }
 
     pub fn redo(&self, origin_id: SessionId, selections: &SelectionSet) -> bool {
+        if self.is_read_only() {
+            return false;
+        }
         let mut changes = Vec::new();
         let selections = self.0.history.borrow_mut().redo(selections, &mut changes);
         if let Some(selections) = selections {
-            self.update_after_edit(origin_id, Some(selections), &changes);
+            self.update_after_edit(Some(origin_id), Some(selections), &changes);
             true
         } else {
             false
@@ -343,12 +535,19 @@ impl Document {
 
     fn update_after_edit(
         &self,
-        origin_id: SessionId,
+        origin_id: Option<SessionId>,
         selections: Option<SelectionSet>,
         edits: &[Edit],
     ) {
         let mut layout = self.0.layout.borrow_mut();
         for edit in edits {
+            let line_index = match edit.change {
+                Change::Insert(position, _) => position.line_index,
+                Change::Delete(start, _) => start.line_index,
+            };
+            if line_index < self.0.indent_state_end.get() {
+                self.0.indent_state_end.set(line_index);
+            }
             match edit.change {
                 Change::Insert(position, ref text) => {
                     layout.indent_state[position.line_index] = None;
@@ -376,18 +575,28 @@ impl Document {
             self.apply_change_to_inline_inlays(&edit.change, edit.drift);
             self.0.tokenizer.borrow_mut().apply_change(&edit.change);
         }
-        self.update_indent_state();
-        self.0.tokenizer.borrow_mut().update(
-            self.0.history.borrow().as_text(),
-            &mut self.0.layout.borrow_mut().tokens,
-        );
-        let mut decorations = self.0.decorations.borrow_mut();
-        for edit in edits {
-            decorations.apply_edit(edit);
+        for decorations in [
+            &self.0.decorations,
+            &self.0.diagnostics,
+            &self.0.shown_decorations,
+        ] {
+            let mut decorations = decorations.borrow_mut();
+            for edit in edits {
+                decorations.apply_edit(edit);
+            }
+        }
+        self.0
+            .change_listeners
+            .borrow_mut()
+            .retain(|change_listener| change_listener.send(edits.to_vec()).is_ok());
+        if origin_id.is_some() {
+            self.0
+                .edit_listeners
+                .borrow_mut()
+                .retain(|edit_listener| edit_listener.send(edits.to_vec()).is_ok());
         }
-        drop(decorations);
         for (&session_id, edit_sender) in &*self.0.edit_senders.borrow() {
-            if session_id == origin_id {
+            if Some(session_id) == origin_id {
                 edit_sender
                     .send((selections.clone(), edits.to_vec()))
                     .unwrap();
@@ -604,13 +813,23 @@ impl Document {
         }
     }
 
-    fn update_indent_state(&self) {
+    fn update_indent_state(&self, end: usize) {
+        let start = self.0.indent_state_end.get();
+        if start >= end {
+            return;
+        }
         let mut layout = self.0.layout.borrow_mut();
         let indent_state = &mut layout.indent_state;
         let history = self.0.history.borrow();
         let lines = history.as_text().as_lines();
-        let mut current_indent_column_count = 0;
-        for line_index in 0..lines.len() {
+        let mut current_indent_column_count = match start.checked_sub(1) {
+            Some(line_index) => match indent_state[line_index].unwrap() {
+                IndentState::Empty(indent_column_count) => indent_column_count,
+                IndentState::NonEmpty(_, next_indent_column_count) => next_indent_column_count,
+            },
+            None => 0,
+        };
+        for line_index in start..end {
             match indent_state[line_index] {
                 Some(IndentState::NonEmpty(_, next_indent_column_count)) => {
                     current_indent_column_count = next_indent_column_count;
@@ -644,6 +863,7 @@ impl Document {
                 }
             }
         }
+        self.0.indent_state_end.set(end);
     }
 }
 
@@ -682,9 +902,17 @@ impl<'a> Editor<'a> {
 struct DocumentInner {
     history: RefCell<History>,
     layout: RefCell<DocumentLayout>,
+    // The indent state of the lines before this one is up to date.
+    indent_state_end: Cell<usize>,
     tokenizer: RefCell<Tokenizer>,
     decorations: RefCell<DecorationSet>,
+    diagnostics: RefCell<DecorationSet>,
+    // The decorations and the diagnostics that don't overlap with them.
+    shown_decorations: RefCell<DecorationSet>,
     edit_senders: RefCell<HashMap<SessionId, Sender<(Option<SelectionSet>, Vec<Edit>)>>>,
+    edit_listeners: RefCell<Vec<Sender<Vec<Edit>>>>,
+    change_listeners: RefCell<Vec<Sender<Vec<Edit>>>>,
+    is_read_only: Cell<bool>,
 }
 
 fn tokenize(text: &str) -> impl Iterator<Item = Token> + '_ {
@@ -697,3 +925,74 @@ fn tokenize(text: &str) -> impl Iterator<Item = Token> + '_ {
         },
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::decoration::DecorationType};
+
+    fn decoration(start: usize, end: usize, ty: DecorationType) -> Decoration {
+        Decoration::new(
+            0,
+            Position {
+                line_index: 0,
+                byte_index: start,
+            },
+            Position {
+                line_index: 0,
+                byte_index: end,
+            },
+            ty,
+        )
+    }
+
+    fn shown(document: &Document) -> Vec<(usize, DecorationType)> {
+        document
+            .decorations()
+            .iter()
+            .map(|decoration| (decoration.start().byte_index, decoration.ty))
+            .collect()
+    }
+
+    #[test]
+    fn diagnostics_dont_replace_decorations() {
+        let mut document = Document::new(Text::from("fn main() { x }"), DecorationSet::new());
+        document.add_decoration(decoration(12, 13, DecorationType::Error));
+        document.set_diagnostics(vec![
+            decoration(3, 7, DecorationType::Warning),
+            // Overlaps with the decoration, so it isn't shown.
+            decoration(12, 14, DecorationType::Hint),
+        ]);
+        assert_eq!(
+            shown(&document),
+            vec![(3, DecorationType::Warning), (12, DecorationType::Error)]
+        );
+
+        // Publishing no diagnostics keeps the decoration.
+        document.set_diagnostics(Vec::new());
+        assert_eq!(shown(&document), vec![(12, DecorationType::Error)]);
+
+        // Clearing the decorations keeps the diagnostics, which now have room.
+        document.set_diagnostics(vec![decoration(12, 14, DecorationType::Hint)]);
+        document.clear_decorations();
+        assert_eq!(shown(&document), vec![(12, DecorationType::Hint)]);
+
+        // Both move with edits.
+        document.add_decoration(decoration(0, 2, DecorationType::Error));
+        document.apply_edits(vec![Edit {
+            change: Change::Insert(
+                Position {
+                    line_index: 0,
+                    byte_index: 0,
+                },
+                Text::from("pub "),
+            ),
+            drift: Drift::Before,
+        }]);
+        assert_eq!(
+            shown(&document),
+            vec![(4, DecorationType::Error), (16, DecorationType::Hint)]
+        );
+        document.clear_decorations();
+        assert_eq!(shown(&document), vec![(16, DecorationType::Hint)]);
+    }
+}
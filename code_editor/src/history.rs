@@ -1,7 +1,7 @@
 use crate::{
     selection::SelectionSet,
     session::SessionId,
-    text::{Edit, Text},
+    text::{Change, Drift, Edit, Length, Position, Text},
 };
 
 #[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
@@ -51,6 +51,16 @@ impl History {
         self.redo_stack.clear();
     }
 
+    /// Applies an edit that doesn't come from any session. The edits on the undo and redo stacks
+    /// are transformed so they apply to the new text, and undoing them never removes the text of
+    /// the external edit.
+    pub fn apply_external_edit(&mut self, edit: Edit) {
+        self.undo_stack.transform(&edit);
+        self.redo_stack.transform(&edit);
+        self.text.apply_change(edit.change);
+        self.current_desc = None;
+    }
+
     pub fn undo(
         &mut self,
         selections: &SelectionSet,
@@ -166,6 +176,46 @@ impl Stack {
         self.groups.clear();
         self.edits.clear();
     }
+
+    // Transforms the stack so it applies to the text after `edit`. The top edit applies to the
+    // current text, so the stack is walked from the top, and `edit` is transformed along with it
+    // to apply to the text before every edit in turn.
+    fn transform(&mut self, edit: &Edit) {
+        let mut changes = vec![edit.change.clone()];
+        let mut group_edits = Vec::with_capacity(self.groups.len());
+        let mut edit_end = self.edits.len();
+        for group in self.groups.iter_mut().rev() {
+            let mut edits = Vec::new();
+            for group_edit in self.edits[group.edit_start..edit_end].iter().rev() {
+                let (transformed, transformed_changes) =
+                    transform(std::slice::from_ref(&group_edit.change), &changes);
+                edits.extend(transformed.into_iter().map(|change| Edit {
+                    change,
+                    drift: group_edit.drift,
+                }));
+                changes = transformed_changes;
+            }
+            // Edits are popped in reverse.
+            edits.reverse();
+            group_edits.push(edits);
+            // The selections of a group are those of the text before its edits.
+            for change in &changes {
+                group.selections.apply_edit(
+                    &Edit {
+                        change: change.clone(),
+                        drift: edit.drift,
+                    },
+                    None,
+                );
+            }
+            edit_end = group.edit_start;
+        }
+        self.edits.clear();
+        for (group, edits) in self.groups.iter_mut().zip(group_edits.into_iter().rev()) {
+            group.edit_start = self.edits.len();
+            self.edits.extend(edits);
+        }
+    }
 }
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -173,3 +223,231 @@ struct Group {
     selections: SelectionSet,
     edit_start: usize,
 }
+
+// Transforms two lists of changes that apply to the same text, so that `a` can be applied after
+// `b` and the other way around, with the same result. Where an insertion falls inside a deletion,
+// the deletion is split around it, so that the inserted text is kept.
+fn transform(a: &[Change], b: &[Change]) -> (Vec<Change>, Vec<Change>) {
+    match (a, b) {
+        ([], _) | (_, []) => (a.to_vec(), b.to_vec()),
+        ([a], [b]) => transform_change(a, b),
+        ([_], [b_first, b_rest @ ..]) => {
+            let (a, b_first) = transform(a, std::slice::from_ref(b_first));
+            let (a, b_rest) = transform(&a, b_rest);
+            (a, b_first.into_iter().chain(b_rest).collect())
+        }
+        ([a_first, a_rest @ ..], _) => {
+            let (a_first, b) = transform(std::slice::from_ref(a_first), b);
+            let (a_rest, b) = transform(a_rest, &b);
+            (a_first.into_iter().chain(a_rest).collect(), b)
+        }
+    }
+}
+
+fn transform_change(a: &Change, b: &Change) -> (Vec<Change>, Vec<Change>) {
+    // Moves `position` over `change`. At an insertion, `is_after` decides which side it ends up on.
+    let map = |position: Position, change: &Change, is_after: bool| {
+        position.apply_edit(&Edit {
+            change: change.clone(),
+            drift: if is_after { Drift::Before } else { Drift::After },
+        })
+    };
+    match (a, b) {
+        (Change::Insert(a_point, a_text), Change::Insert(b_point, b_text)) => (
+            // At the same point, the text of `b` comes first.
+            vec![Change::Insert(map(*a_point, b, true), a_text.clone())],
+            vec![Change::Insert(map(*b_point, a, false), b_text.clone())],
+        ),
+        (Change::Insert(point, text), Change::Delete(start, length)) => {
+            transform_insert_delete(*point, text, *start, *length)
+        }
+        (Change::Delete(start, length), Change::Insert(point, text)) => {
+            let (b, a) = transform_insert_delete(*point, text, *start, *length);
+            (a, b)
+        }
+        (Change::Delete(a_start, a_length), Change::Delete(b_start, b_length)) => {
+            let a_end = *a_start + *a_length;
+            let b_end = *b_start + *b_length;
+            let a_start_after = map(*a_start, b, false);
+            let b_start_after = map(*b_start, a, false);
+            (
+                vec![Change::Delete(a_start_after, map(a_end, b, false) - a_start_after)],
+                vec![Change::Delete(b_start_after, map(b_end, a, false) - b_start_after)],
+            )
+        }
+    }
+}
+
+// Returns the insertion transformed over the deletion, and the other way around.
+fn transform_insert_delete(
+    point: Position,
+    text: &Text,
+    start: Position,
+    length: Length,
+) -> (Vec<Change>, Vec<Change>) {
+    let end = start + length;
+    if point <= start {
+        (
+            vec![Change::Insert(point, text.clone())],
+            vec![Change::Delete(point + text.length() + (start - point), length)],
+        )
+    } else if point >= end {
+        (
+            vec![Change::Insert(start + (point - end), text.clone())],
+            vec![Change::Delete(start, length)],
+        )
+    } else {
+        // The deletion is split in two around the inserted text, the second part first so that
+        // the first doesn't move.
+        (
+            vec![Change::Insert(start, text.clone())],
+            vec![
+                Change::Delete(point + text.length(), end - point),
+                Change::Delete(start, point - start),
+            ],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(line_index: usize, byte_index: usize) -> Position {
+        Position {
+            line_index,
+            byte_index,
+        }
+    }
+
+    fn edit(change: Change) -> Edit {
+        Edit {
+            change,
+            drift: Drift::Before,
+        }
+    }
+
+    fn apply(text: &Text, changes: &[Change]) -> String {
+        let mut text = text.clone();
+        for change in changes {
+            text.apply_change(change.clone());
+        }
+        text.to_string()
+    }
+
+    // Every insertion and deletion in a small text with two lines.
+    fn all_changes(text: &Text) -> Vec<Change> {
+        let lines = text.as_lines();
+        let mut positions = Vec::new();
+        for line_index in 0..lines.len() {
+            for byte_index in 0..=lines[line_index].len() {
+                positions.push(position(line_index, byte_index));
+            }
+        }
+        let mut changes = Vec::new();
+        for &start in &positions {
+            changes.push(Change::Insert(start, Text::from("X")));
+            changes.push(Change::Insert(start, Text::from("Y\nZ")));
+            for &end in positions.iter().filter(|&&end| end >= start) {
+                changes.push(Change::Delete(start, end - start));
+            }
+        }
+        changes
+    }
+
+    #[test]
+    fn transformed_changes_converge() {
+        let text = Text::from("ab\ncd");
+        let changes = all_changes(&text);
+        for a in &changes {
+            for b in &changes {
+                let (a_after_b, b_after_a) = transform(std::slice::from_ref(a), std::slice::from_ref(b));
+                let a_then_b = apply(&text, &[&[a.clone()][..], &b_after_a].concat());
+                let b_then_a = apply(&text, &[&[b.clone()][..], &a_after_b].concat());
+                assert_eq!(a_then_b, b_then_a, "{:?} {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn transformed_lists_converge() {
+        let text = Text::from("abcdef");
+        let a = vec![
+            Change::Delete(position(0, 1), Length { line_count: 0, byte_count: 3 }),
+            Change::Insert(position(0, 2), Text::from("Q")),
+        ];
+        let b = vec![
+            Change::Insert(position(0, 2), Text::from("X")),
+            Change::Delete(position(0, 0), Length { line_count: 0, byte_count: 2 }),
+        ];
+        let (a_after_b, b_after_a) = transform(&a, &b);
+        assert_eq!(
+            apply(&text, &[a.clone(), b_after_a].concat()),
+            apply(&text, &[b.clone(), a_after_b].concat())
+        );
+    }
+
+    #[test]
+    fn deletions_keep_inserted_text() {
+        let text = Text::from("abcdef");
+        let delete = Change::Delete(position(0, 1), Length { line_count: 0, byte_count: 3 });
+        let insert = Change::Insert(position(0, 2), Text::from("X"));
+        let (delete_after_insert, _) = transform(&[delete], std::slice::from_ref(&insert));
+        assert_eq!(apply(&text, &[&[insert][..], &delete_after_insert].concat()), "aXef");
+    }
+
+    fn history(text: &str) -> History {
+        History::from(Text::from(text))
+    }
+
+    fn apply_local(history: &mut History, change: Change) {
+        history.undo_stack.push_group(SelectionSet::new());
+        history.apply_edit(edit(change));
+    }
+
+    fn undo(history: &mut History) -> bool {
+        let mut edits = Vec::new();
+        history.undo(&SelectionSet::new(), &mut edits).is_some()
+    }
+
+    fn redo(history: &mut History) -> bool {
+        let mut edits = Vec::new();
+        history.redo(&SelectionSet::new(), &mut edits).is_some()
+    }
+
+    #[test]
+    fn undo_survives_external_edits() {
+        let mut history = history("aef");
+        apply_local(&mut history, Change::Insert(position(0, 1), Text::from("bcd")));
+        history.apply_external_edit(edit(Change::Insert(position(0, 0), Text::from("12\n"))));
+        history.apply_external_edit(edit(Change::Insert(position(1, 2), Text::from("X"))));
+        assert_eq!(history.as_text().to_string(), "12\nabXcdef");
+        assert!(undo(&mut history));
+        assert_eq!(history.as_text().to_string(), "12\naXef");
+        assert!(redo(&mut history));
+        assert_eq!(history.as_text().to_string(), "12\nabXcdef");
+    }
+
+    #[test]
+    fn undo_restores_text_deleted_by_external_edits() {
+        let mut history = history("abcdef");
+        apply_local(&mut history, Change::Insert(position(0, 6), Text::from("gh")));
+        apply_local(
+            &mut history,
+            Change::Delete(position(0, 1), Length { line_count: 0, byte_count: 2 }),
+        );
+        history.apply_external_edit(edit(Change::Delete(
+            position(0, 0),
+            Length { line_count: 0, byte_count: 5 },
+        )));
+        assert_eq!(history.as_text().to_string(), "h");
+        assert!(undo(&mut history));
+        assert_eq!(history.as_text().to_string(), "bch");
+        assert!(undo(&mut history));
+        assert_eq!(history.as_text().to_string(), "bc");
+        assert!(!undo(&mut history));
+        assert!(redo(&mut history));
+        assert!(redo(&mut history));
+        assert_eq!(history.as_text().to_string(), "h");
+    }
+}
@@ -0,0 +1,706 @@
+use {
+    makepad_toml_parser::{parse_toml, Toml},
+    makepad_widgets::*,
+    std::fmt,
+};
+
+macro_rules! commands {
+    ($($command:ident = $name:literal, $title:literal;)*) => {
+        /// An operation of the code editor that can be bound to keys and run from the command
+        /// palette.
+        #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+        pub enum Command {
+            $($command,)*
+        }
+
+        impl Command {
+            /// Every command, in the order in which the command palette lists them.
+            pub const ALL: &'static [Self] = &[$(Self::$command,)*];
+
+            /// The name of the command in keymaps, like `move_left`.
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Self::$command => $name,)*
+                }
+            }
+
+            /// The title of the command in the command palette, like `Move Left`.
+            pub fn title(self) -> &'static str {
+                match self {
+                    $(Self::$command => $title,)*
+                }
+            }
+        }
+    };
+}
+
+commands! {
+    MoveLeft = "move_left", "Move Left";
+    MoveRight = "move_right", "Move Right";
+    MoveUp = "move_up", "Move Up";
+    MoveDown = "move_down", "Move Down";
+    MoveToPrevWord = "move_to_prev_word", "Move to Previous Word";
+    MoveToNextWord = "move_to_next_word", "Move to Next Word";
+    MoveToLineStart = "move_to_line_start", "Move to Line Start";
+    MoveToLineEnd = "move_to_line_end", "Move to Line End";
+    MovePageUp = "move_page_up", "Move Page Up";
+    MovePageDown = "move_page_down", "Move Page Down";
+    MoveToFileStart = "move_to_file_start", "Move to File Start";
+    MoveToFileEnd = "move_to_file_end", "Move to File End";
+    SelectLeft = "select_left", "Select Left";
+    SelectRight = "select_right", "Select Right";
+    SelectUp = "select_up", "Select Up";
+    SelectDown = "select_down", "Select Down";
+    SelectToPrevWord = "select_to_prev_word", "Select to Previous Word";
+    SelectToNextWord = "select_to_next_word", "Select to Next Word";
+    SelectToLineStart = "select_to_line_start", "Select to Line Start";
+    SelectToLineEnd = "select_to_line_end", "Select to Line End";
+    SelectPageUp = "select_page_up", "Select Page Up";
+    SelectPageDown = "select_page_down", "Select Page Down";
+    SelectToFileStart = "select_to_file_start", "Select to File Start";
+    SelectToFileEnd = "select_to_file_end", "Select to File End";
+    SelectAll = "select_all", "Select All";
+    NewLine = "new_line", "New Line";
+    Indent = "indent", "Indent";
+    Outdent = "outdent", "Outdent";
+    Delete = "delete", "Delete";
+    Backspace = "backspace", "Delete Backward";
+    DeleteWordBackward = "delete_word_backward", "Delete Word Backward";
+    DeleteWordForward = "delete_word_forward", "Delete Word Forward";
+    DuplicateLines = "duplicate_lines", "Duplicate Lines";
+    MoveLinesUp = "move_lines_up", "Move Lines Up";
+    MoveLinesDown = "move_lines_down", "Move Lines Down";
+    JoinLines = "join_lines", "Join Lines";
+    ToggleLineComment = "toggle_line_comment", "Toggle Line Comment";
+    Undo = "undo", "Undo";
+    Redo = "redo", "Redo";
+    Find = "find", "Find";
+    FindAndReplace = "find_and_replace", "Find and Replace";
+    FindNext = "find_next", "Find Next";
+    FindPrev = "find_prev", "Find Previous";
+    SelectAllMatches = "select_all_matches", "Select All Matches";
+    TriggerCompletion = "trigger_completion", "Trigger Completion";
+    GoToDefinition = "go_to_definition", "Go to Definition";
+    QuickFix = "quick_fix", "Quick Fix";
+    ToggleWordWrap = "toggle_word_wrap", "Toggle Word Wrap";
+    IncreaseFontSize = "increase_font_size", "Increase Font Size";
+    DecreaseFontSize = "decrease_font_size", "Decrease Font Size";
+    ResetFontSize = "reset_font_size", "Reset Font Size";
+    CommandPalette = "command_palette", "Show All Commands";
+}
+
+impl Command {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|command| command.name() == name)
+    }
+}
+
+/// The bindings of key strokes to commands. Besides a single key stroke, a command can be bound
+/// to a chord of several key strokes that are pressed after each other.
+#[derive(Clone, Debug, Default)]
+pub struct Keymap {
+    bindings: Vec<(Vec<KeyStroke>, Command)>,
+}
+
+impl Keymap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn preset(preset: KeymapPreset) -> Self {
+        let mut keymap = Self::new();
+        keymap.bind_all(DEFAULT_BINDINGS);
+        match preset {
+            KeymapPreset::Default => {}
+            KeymapPreset::Emacs => keymap.bind_all(EMACS_BINDINGS),
+            KeymapPreset::Vim => keymap.bind_all(VIM_BINDINGS),
+        }
+        keymap
+    }
+
+    pub fn from_config(config: &KeymapConfig) -> Result<Self, KeymapError> {
+        let mut keymap = Self::preset(config.preset);
+        for binding in &config.bindings {
+            keymap.apply_binding(&binding.keys, &binding.command)?;
+        }
+        Ok(keymap)
+    }
+
+    /// Reads a keymap from TOML like:
+    ///
+    /// ```toml
+    /// preset = "emacs"
+    ///
+    /// [bindings]
+    /// "ctrl+k ctrl+d" = "select_all_matches"
+    /// "f1" = ""
+    /// ```
+    ///
+    /// The bindings are added to those of the preset, which is `default` if it is left out. An
+    /// empty command removes the binding of the preset.
+    pub fn from_toml(string: &str) -> Result<Self, KeymapError> {
+        let toml = parse_toml(string).map_err(|error| KeymapError::new(format!("{:?}", error)))?;
+        let mut keymap = match toml.get("preset") {
+            Some(Toml::Str(name, _)) => Self::preset(
+                KeymapPreset::from_name(name)
+                    .ok_or_else(|| KeymapError::new(format!("unknown preset `{}`", name)))?,
+            ),
+            Some(_) => return Err(KeymapError::new("the preset is not a string".to_string())),
+            None => Self::preset(KeymapPreset::Default),
+        };
+        let mut bindings = Vec::new();
+        for (key, value) in &toml {
+            if key == "preset" {
+                continue;
+            }
+            let keys = key
+                .strip_prefix("bindings.")
+                .ok_or_else(|| KeymapError::new(format!("unknown key `{}`", key)))?;
+            match value {
+                Toml::Str(command, _) => bindings.push((keys, command.as_str())),
+                _ => {
+                    return Err(KeymapError::new(format!(
+                        "the command for `{}` is not a string",
+                        keys
+                    )))
+                }
+            }
+        }
+        // The table is unordered, so sort the bindings to resolve conflicts between them the same
+        // way every time.
+        bindings.sort();
+        for (keys, command) in bindings {
+            keymap.apply_binding(keys, command)?;
+        }
+        Ok(keymap)
+    }
+
+    /// Binds key strokes to a command. Bindings that conflict with them are removed, which are
+    /// bindings to the same key strokes, and chords that start with them or that they start with.
+    pub fn bind(&mut self, key_strokes: Vec<KeyStroke>, command: Command) {
+        self.unbind(&key_strokes);
+        self.bindings.push((key_strokes, command));
+    }
+
+    /// Removes the bindings that conflict with the given key strokes.
+    pub fn unbind(&mut self, key_strokes: &[KeyStroke]) {
+        self.bindings.retain(|(other_key_strokes, _)| {
+            !other_key_strokes.starts_with(key_strokes)
+                && !key_strokes.starts_with(other_key_strokes)
+        });
+    }
+
+    /// Returns the key strokes that are bound to a command, in the order they were bound in.
+    pub fn bindings_for(&self, command: Command) -> impl Iterator<Item = &[KeyStroke]> {
+        self.bindings
+            .iter()
+            .filter(move |(_, other_command)| *other_command == command)
+            .map(|(key_strokes, _)| key_strokes.as_slice())
+    }
+
+    /// Returns true if a key stroke is bound on its own or starts a chord.
+    pub fn is_bound(&self, key_stroke: KeyStroke) -> bool {
+        self.bindings
+            .iter()
+            .any(|(key_strokes, _)| key_strokes[0] == key_stroke)
+    }
+
+    /// Looks up the key strokes that were pressed after each other.
+    pub fn lookup(&self, key_strokes: &[KeyStroke]) -> KeymapMatch {
+        let mut keymap_match = KeymapMatch::None;
+        for (other_key_strokes, command) in &self.bindings {
+            if other_key_strokes == key_strokes {
+                return KeymapMatch::Command(*command);
+            }
+            if other_key_strokes.starts_with(key_strokes) {
+                keymap_match = KeymapMatch::Pending;
+            }
+        }
+        keymap_match
+    }
+
+    fn apply_binding(&mut self, keys: &str, command: &str) -> Result<(), KeymapError> {
+        let key_strokes = parse_key_strokes(keys)
+            .ok_or_else(|| KeymapError::new(format!("invalid keys `{}`", keys)))?;
+        if command.is_empty() {
+            self.unbind(&key_strokes);
+        } else {
+            let command = Command::from_name(command)
+                .ok_or_else(|| KeymapError::new(format!("unknown command `{}`", command)))?;
+            self.bind(key_strokes, command);
+        }
+        Ok(())
+    }
+
+    fn bind_all(&mut self, bindings: &[(&str, Command)]) {
+        for &(keys, command) in bindings {
+            self.bind(parse_key_strokes(keys).unwrap(), command);
+        }
+    }
+}
+
+/// What the key strokes that were pressed after each other are bound to.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum KeymapMatch {
+    Command(Command),
+    /// The key strokes start a chord, which needs more key strokes.
+    Pending,
+    None,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct KeymapError {
+    pub message: String,
+}
+
+impl KeymapError {
+    fn new(message: String) -> Self {
+        Self { message }
+    }
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// The bindings that a keymap starts from. Every preset extends the default bindings.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Live, LiveHook)]
+#[live_ignore]
+pub enum KeymapPreset {
+    #[pick]
+    Default,
+    /// Emacs bindings on Ctrl and Alt. Ctrl+V and Ctrl+Y stay paste, and Ctrl+X starts chords
+    /// like `ctrl+x u`.
+    Emacs,
+    /// Vi bindings on Alt, since the editor has no modes, like `alt+j` to move down and
+    /// `alt+g alt+g` to move to the start of the file.
+    Vim,
+}
+
+impl KeymapPreset {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Self::Default),
+            "emacs" => Some(Self::Emacs),
+            "vim" => Some(Self::Vim),
+            _ => None,
+        }
+    }
+}
+
+/// A keymap in the live DSL, like:
+///
+/// ```text
+/// keymap: {
+///     preset: Emacs,
+///     bindings: [
+///         {keys: "ctrl+k ctrl+d", command: "select_all_matches"},
+///         {keys: "f1", command: ""},
+///     ]
+/// }
+/// ```
+///
+/// The bindings are added to those of the preset, as in `Keymap::from_toml`.
+#[derive(Live, LiveHook)]
+pub struct KeymapConfig {
+    #[live]
+    pub preset: KeymapPreset,
+    #[live]
+    pub bindings: Vec<KeyBindingConfig>,
+}
+
+#[derive(Live, LiveHook)]
+pub struct KeyBindingConfig {
+    #[live]
+    pub keys: String,
+    #[live]
+    pub command: String,
+}
+
+/// A key together with the modifiers that are held while it is pressed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KeyStroke {
+    pub key_code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyStroke {
+    pub fn new(key_code: KeyCode, modifiers: KeyModifiers) -> Self {
+        Self {
+            key_code,
+            modifiers,
+        }
+    }
+
+    /// Returns the key together with the modifier of shortcuts, which is Cmd on macOS and Ctrl
+    /// everywhere else.
+    pub fn shortcut(key_code: KeyCode) -> Self {
+        Self::new(key_code, shortcut_modifiers())
+    }
+
+    pub fn from_key_event(key_event: &KeyEvent) -> Self {
+        Self::new(key_event.key_code, key_event.modifiers)
+    }
+
+    /// Parses a key stroke like `ctrl+shift+left`. The modifiers are `ctrl`, `alt` (or `option`),
+    /// `shift`, `cmd` (or `logo`, `super`), `mod`, which is `cmd` on macOS and `ctrl` everywhere
+    /// else, and `word`, which is `alt` on macOS and `ctrl` everywhere else.
+    pub fn parse(string: &str) -> Option<Self> {
+        let mut parts = string.split('+');
+        let key_name = parts.next_back()?;
+        let mut modifiers = KeyModifiers::default();
+        for part in parts {
+            match part.to_lowercase().as_str() {
+                "ctrl" | "control" => modifiers.control = true,
+                "alt" | "option" => modifiers.alt = true,
+                "shift" => modifiers.shift = true,
+                "cmd" | "logo" | "super" => modifiers.logo = true,
+                "mod" => {
+                    let shortcut_modifiers = shortcut_modifiers();
+                    modifiers.control |= shortcut_modifiers.control;
+                    modifiers.logo |= shortcut_modifiers.logo;
+                }
+                "word" => {
+                    let word_modifiers = word_modifiers();
+                    modifiers.control |= word_modifiers.control;
+                    modifiers.alt |= word_modifiers.alt;
+                }
+                _ => return None,
+            }
+        }
+        Some(Self::new(key_code_from_name(key_name)?, modifiers))
+    }
+
+    /// Returns true if the key is a modifier key, which can't be bound on its own.
+    pub fn is_modifier(&self) -> bool {
+        match self.key_code {
+            KeyCode::Control | KeyCode::Alt | KeyCode::Shift | KeyCode::Logo => true,
+            _ => false,
+        }
+    }
+
+    /// Returns true if pressing the key also sends the text it types, as it does for printable
+    /// keys without Ctrl or Cmd.
+    pub fn types_text(&self) -> bool {
+        !self.modifiers.control
+            && !self.modifiers.logo
+            && KEY_NAMES
+                .iter()
+                .position(|&(key_code, _)| key_code == self.key_code)
+                .map_or(false, |index| index < PRINTABLE_KEY_COUNT)
+    }
+}
+
+impl fmt::Display for KeyStroke {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.modifiers.control {
+            write!(f, "ctrl+")?;
+        }
+        if self.modifiers.alt {
+            write!(f, "alt+")?;
+        }
+        if self.modifiers.shift {
+            write!(f, "shift+")?;
+        }
+        if self.modifiers.logo {
+            write!(f, "cmd+")?;
+        }
+        match KEY_NAMES
+            .iter()
+            .find(|&&(key_code, _)| key_code == self.key_code)
+        {
+            Some((_, name)) => write!(f, "{}", name),
+            None => write!(f, "{:?}", self.key_code),
+        }
+    }
+}
+
+/// Parses a sequence of key strokes separated by spaces, like `ctrl+k ctrl+c`. Every key stroke
+/// but the last starts a chord.
+pub fn parse_key_strokes(string: &str) -> Option<Vec<KeyStroke>> {
+    let key_strokes = string
+        .split_whitespace()
+        .map(KeyStroke::parse)
+        .collect::<Option<Vec<_>>>()?;
+    if key_strokes.is_empty() {
+        return None;
+    }
+    Some(key_strokes)
+}
+
+/// Writes a sequence of key strokes in the format that `parse_key_strokes` reads.
+pub fn format_key_strokes(key_strokes: &[KeyStroke]) -> String {
+    key_strokes
+        .iter()
+        .map(|stroke| stroke.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn shortcut_modifiers() -> KeyModifiers {
+    let is_macos = cfg!(target_os = "macos");
+    KeyModifiers {
+        control: !is_macos,
+        logo: is_macos,
+        ..KeyModifiers::default()
+    }
+}
+
+// The modifier that makes the arrow keys move by words.
+fn word_modifiers() -> KeyModifiers {
+    let is_macos = cfg!(target_os = "macos");
+    KeyModifiers {
+        control: !is_macos,
+        alt: is_macos,
+        ..KeyModifiers::default()
+    }
+}
+
+fn key_code_from_name(name: &str) -> Option<KeyCode> {
+    let name = name.to_lowercase();
+    let name = match name.as_str() {
+        "return" => "enter",
+        "esc" => "escape",
+        "del" => "delete",
+        "arrowleft" => "left",
+        "arrowright" => "right",
+        "arrowup" => "up",
+        "arrowdown" => "down",
+        "pgup" => "pageup",
+        "pgdown" => "pagedown",
+        name => name,
+    };
+    KEY_NAMES
+        .iter()
+        .find(|&&(_, key_name)| key_name == name)
+        .map(|&(key_code, _)| key_code)
+        .or_else(|| {
+            PUNCTUATION_NAMES
+                .iter()
+                .find(|&&(_, key_name)| key_name == name)
+                .map(|&(key_code, _)| key_code)
+        })
+}
+
+// The first `PRINTABLE_KEY_COUNT` keys type text.
+const PRINTABLE_KEY_COUNT: usize = 48;
+
+const KEY_NAMES: [(KeyCode, &str); 74] = [
+    (KeyCode::KeyA, "a"),
+    (KeyCode::KeyB, "b"),
+    (KeyCode::KeyC, "c"),
+    (KeyCode::KeyD, "d"),
+    (KeyCode::KeyE, "e"),
+    (KeyCode::KeyF, "f"),
+    (KeyCode::KeyG, "g"),
+    (KeyCode::KeyH, "h"),
+    (KeyCode::KeyI, "i"),
+    (KeyCode::KeyJ, "j"),
+    (KeyCode::KeyK, "k"),
+    (KeyCode::KeyL, "l"),
+    (KeyCode::KeyM, "m"),
+    (KeyCode::KeyN, "n"),
+    (KeyCode::KeyO, "o"),
+    (KeyCode::KeyP, "p"),
+    (KeyCode::KeyQ, "q"),
+    (KeyCode::KeyR, "r"),
+    (KeyCode::KeyS, "s"),
+    (KeyCode::KeyT, "t"),
+    (KeyCode::KeyU, "u"),
+    (KeyCode::KeyV, "v"),
+    (KeyCode::KeyW, "w"),
+    (KeyCode::KeyX, "x"),
+    (KeyCode::KeyY, "y"),
+    (KeyCode::KeyZ, "z"),
+    (KeyCode::Key0, "0"),
+    (KeyCode::Key1, "1"),
+    (KeyCode::Key2, "2"),
+    (KeyCode::Key3, "3"),
+    (KeyCode::Key4, "4"),
+    (KeyCode::Key5, "5"),
+    (KeyCode::Key6, "6"),
+    (KeyCode::Key7, "7"),
+    (KeyCode::Key8, "8"),
+    (KeyCode::Key9, "9"),
+    (KeyCode::Space, "space"),
+    (KeyCode::Backtick, "backtick"),
+    (KeyCode::Minus, "minus"),
+    (KeyCode::Equals, "equals"),
+    (KeyCode::LBracket, "lbracket"),
+    (KeyCode::RBracket, "rbracket"),
+    (KeyCode::Semicolon, "semicolon"),
+    (KeyCode::Quote, "quote"),
+    (KeyCode::Backslash, "backslash"),
+    (KeyCode::Comma, "comma"),
+    (KeyCode::Period, "period"),
+    (KeyCode::Slash, "slash"),
+    (KeyCode::ArrowLeft, "left"),
+    (KeyCode::ArrowRight, "right"),
+    (KeyCode::ArrowUp, "up"),
+    (KeyCode::ArrowDown, "down"),
+    (KeyCode::Home, "home"),
+    (KeyCode::End, "end"),
+    (KeyCode::PageUp, "pageup"),
+    (KeyCode::PageDown, "pagedown"),
+    (KeyCode::ReturnKey, "enter"),
+    (KeyCode::Tab, "tab"),
+    (KeyCode::Backspace, "backspace"),
+    (KeyCode::Delete, "delete"),
+    (KeyCode::Insert, "insert"),
+    (KeyCode::Escape, "escape"),
+    (KeyCode::F1, "f1"),
+    (KeyCode::F2, "f2"),
+    (KeyCode::F3, "f3"),
+    (KeyCode::F4, "f4"),
+    (KeyCode::F5, "f5"),
+    (KeyCode::F6, "f6"),
+    (KeyCode::F7, "f7"),
+    (KeyCode::F8, "f8"),
+    (KeyCode::F9, "f9"),
+    (KeyCode::F10, "f10"),
+    (KeyCode::F11, "f11"),
+    (KeyCode::F12, "f12"),
+];
+
+// Punctuation keys can also be written as the character they type without Shift.
+const PUNCTUATION_NAMES: [(KeyCode, &str); 11] = [
+    (KeyCode::Minus, "-"),
+    (KeyCode::Equals, "="),
+    (KeyCode::LBracket, "["),
+    (KeyCode::RBracket, "]"),
+    (KeyCode::Semicolon, ";"),
+    (KeyCode::Quote, "'"),
+    (KeyCode::Backslash, "\\"),
+    (KeyCode::Comma, ","),
+    (KeyCode::Period, "."),
+    (KeyCode::Slash, "/"),
+    (KeyCode::Backtick, "`"),
+];
+
+const DEFAULT_BINDINGS: &[(&str, Command)] = &[
+    ("left", Command::MoveLeft),
+    ("right", Command::MoveRight),
+    ("up", Command::MoveUp),
+    ("down", Command::MoveDown),
+    ("word+left", Command::MoveToPrevWord),
+    ("word+right", Command::MoveToNextWord),
+    ("home", Command::MoveToLineStart),
+    ("end", Command::MoveToLineEnd),
+    ("pageup", Command::MovePageUp),
+    ("pagedown", Command::MovePageDown),
+    ("mod+home", Command::MoveToFileStart),
+    ("mod+end", Command::MoveToFileEnd),
+    ("shift+left", Command::SelectLeft),
+    ("shift+right", Command::SelectRight),
+    ("shift+up", Command::SelectUp),
+    ("shift+down", Command::SelectDown),
+    ("word+shift+left", Command::SelectToPrevWord),
+    ("word+shift+right", Command::SelectToNextWord),
+    ("shift+home", Command::SelectToLineStart),
+    ("shift+end", Command::SelectToLineEnd),
+    ("shift+pageup", Command::SelectPageUp),
+    ("shift+pagedown", Command::SelectPageDown),
+    ("mod+shift+home", Command::SelectToFileStart),
+    ("mod+shift+end", Command::SelectToFileEnd),
+    ("mod+a", Command::SelectAll),
+    ("enter", Command::NewLine),
+    ("shift+enter", Command::NewLine),
+    ("tab", Command::Indent),
+    ("shift+tab", Command::Outdent),
+    ("delete", Command::Delete),
+    ("backspace", Command::Backspace),
+    ("shift+backspace", Command::Backspace),
+    ("word+backspace", Command::DeleteWordBackward),
+    ("word+delete", Command::DeleteWordForward),
+    ("mod+shift+d", Command::DuplicateLines),
+    ("alt+shift+down", Command::DuplicateLines),
+    ("alt+up", Command::MoveLinesUp),
+    ("alt+down", Command::MoveLinesDown),
+    ("mod+j", Command::JoinLines),
+    ("mod+slash", Command::ToggleLineComment),
+    ("mod+period", Command::QuickFix),
+    ("mod+z", Command::Undo),
+    ("mod+shift+z", Command::Redo),
+    ("mod+y", Command::Redo),
+    ("mod+f", Command::Find),
+    ("mod+alt+f", Command::FindAndReplace),
+    ("mod+h", Command::FindAndReplace),
+    ("mod+g", Command::FindNext),
+    ("f3", Command::FindNext),
+    ("mod+shift+g", Command::FindPrev),
+    ("shift+f3", Command::FindPrev),
+    ("mod+shift+l", Command::SelectAllMatches),
+    ("ctrl+space", Command::TriggerCompletion),
+    ("f12", Command::GoToDefinition),
+    ("mod+w", Command::ToggleWordWrap),
+    ("mod+equals", Command::IncreaseFontSize),
+    ("mod+minus", Command::DecreaseFontSize),
+    ("mod+0", Command::ResetFontSize),
+    ("mod+shift+p", Command::CommandPalette),
+    ("f1", Command::CommandPalette),
+];
+
+const EMACS_BINDINGS: &[(&str, Command)] = &[
+    ("ctrl+b", Command::MoveLeft),
+    ("ctrl+f", Command::MoveRight),
+    ("ctrl+p", Command::MoveUp),
+    ("ctrl+n", Command::MoveDown),
+    ("ctrl+a", Command::MoveToLineStart),
+    ("ctrl+e", Command::MoveToLineEnd),
+    ("alt+b", Command::MoveToPrevWord),
+    ("alt+f", Command::MoveToNextWord),
+    ("alt+v", Command::MovePageUp),
+    ("alt+shift+comma", Command::MoveToFileStart),
+    ("alt+shift+period", Command::MoveToFileEnd),
+    ("ctrl+x h", Command::SelectAll),
+    ("ctrl+d", Command::Delete),
+    ("ctrl+h", Command::Backspace),
+    ("alt+backspace", Command::DeleteWordBackward),
+    ("alt+d", Command::DeleteWordForward),
+    ("alt+shift+6", Command::JoinLines),
+    ("alt+semicolon", Command::ToggleLineComment),
+    ("ctrl+slash", Command::Undo),
+    ("ctrl+x u", Command::Undo),
+    ("ctrl+shift+slash", Command::Redo),
+    ("ctrl+s", Command::Find),
+    ("ctrl+r", Command::FindPrev),
+    ("alt+shift+5", Command::FindAndReplace),
+    ("alt+slash", Command::TriggerCompletion),
+    ("alt+period", Command::GoToDefinition),
+    ("alt+x", Command::CommandPalette),
+];
+
+const VIM_BINDINGS: &[(&str, Command)] = &[
+    ("alt+h", Command::MoveLeft),
+    ("alt+l", Command::MoveRight),
+    ("alt+k", Command::MoveUp),
+    ("alt+j", Command::MoveDown),
+    ("alt+0", Command::MoveToLineStart),
+    ("alt+shift+4", Command::MoveToLineEnd),
+    ("alt+b", Command::MoveToPrevWord),
+    ("alt+e", Command::MoveToNextWord),
+    ("ctrl+b", Command::MovePageUp),
+    ("ctrl+f", Command::MovePageDown),
+    ("alt+g alt+g", Command::MoveToFileStart),
+    ("alt+shift+g", Command::MoveToFileEnd),
+    ("alt+shift+h", Command::SelectLeft),
+    ("alt+shift+l", Command::SelectRight),
+    ("alt+shift+k", Command::SelectUp),
+    ("alt+shift+j", Command::SelectDown),
+    ("alt+shift+b", Command::SelectToPrevWord),
+    ("alt+shift+e", Command::SelectToNextWord),
+    ("alt+x", Command::Delete),
+    ("alt+u", Command::Undo),
+    ("ctrl+r", Command::Redo),
+    ("alt+slash", Command::Find),
+    ("alt+n", Command::FindNext),
+    ("alt+shift+n", Command::FindPrev),
+    ("ctrl+n", Command::TriggerCompletion),
+    ("ctrl+rbracket", Command::GoToDefinition),
+    ("alt+shift+semicolon", Command::CommandPalette),
+];
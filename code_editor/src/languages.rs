@@ -0,0 +1,455 @@
+//! The languages the code editor knows about besides Rust, which lives in `tokenizer`.
+
+use crate::{
+    token::TokenKind,
+    tokenizer::{CharExt, Cursor, Language},
+};
+
+/// The state of languages whose only multiline construct is a block comment.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum BlockCommentState {
+    #[default]
+    Initial,
+    BlockComment,
+}
+
+/// The DSL that `live_design!` blocks are written in.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Live;
+
+impl Language for Live {
+    type State = BlockCommentState;
+
+    fn next(&self, state: Self::State, cursor: &mut Cursor) -> (Self::State, TokenKind) {
+        if state == BlockCommentState::BlockComment {
+            return block_comment_tail(cursor);
+        }
+        match (cursor.peek(0), cursor.peek(1)) {
+            ('/', '/') => line_comment(cursor),
+            ('/', '*') => {
+                cursor.skip(2);
+                block_comment_tail(cursor)
+            }
+            // Colors such as `#f` or `#C0C0C0`.
+            ('#', char) if char.is_ascii_hexdigit() => {
+                cursor.skip(1);
+                cursor.skip_while(|char| char.is_ascii_alphanumeric());
+                (BlockCommentState::Initial, TokenKind::Number)
+            }
+            ('"', _) => (BlockCommentState::Initial, string(cursor, '"')),
+            (char, _) if char.is_identifier_start() => {
+                let kind = match identifier(cursor) {
+                    "if" | "else" | "match" | "return" => TokenKind::BranchKeyword,
+                    "for" | "while" | "loop" | "break" | "continue" => TokenKind::LoopKeyword,
+                    "fn" | "let" | "mut" | "self" | "true" | "false" | "const" | "use" | "pub"
+                    | "struct" | "impl" | "in" | "instance" | "uniform" | "varying"
+                    | "texture" | "bool" | "int" | "float" | "vec2" | "vec3" | "vec4" | "mat4"
+                    | "texture2d" => TokenKind::OtherKeyword,
+                    string => identifier_kind(string, cursor),
+                };
+                (BlockCommentState::Initial, kind)
+            }
+            _ => (BlockCommentState::Initial, number_or_punctuation(cursor)),
+        }
+    }
+
+    fn line_comment(&self) -> Option<&'static str> {
+        Some("//")
+    }
+}
+
+/// GLSL and Metal shader code.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Shader;
+
+impl Language for Shader {
+    type State = BlockCommentState;
+
+    fn next(&self, state: Self::State, cursor: &mut Cursor) -> (Self::State, TokenKind) {
+        if state == BlockCommentState::BlockComment {
+            return block_comment_tail(cursor);
+        }
+        match (cursor.peek(0), cursor.peek(1)) {
+            ('/', '/') => line_comment(cursor),
+            ('/', '*') => {
+                cursor.skip(2);
+                block_comment_tail(cursor)
+            }
+            // Preprocessor directives such as `#version` or `#include`.
+            ('#', _) if cursor.before().trim().is_empty() => {
+                cursor.skip(1);
+                cursor.skip_while(|char| char.is_whitespace());
+                cursor.skip_while(|char| char.is_identifier_continue());
+                (BlockCommentState::Initial, TokenKind::OtherKeyword)
+            }
+            ('"', _) => (BlockCommentState::Initial, string(cursor, '"')),
+            (char, _) if char.is_identifier_start() => {
+                let kind = match identifier(cursor) {
+                    "if" | "else" | "switch" | "case" | "default" | "return" | "discard" => {
+                        TokenKind::BranchKeyword
+                    }
+                    "for" | "while" | "do" | "break" | "continue" => TokenKind::LoopKeyword,
+                    string if is_shader_keyword(string) => TokenKind::OtherKeyword,
+                    string => identifier_kind(string, cursor),
+                };
+                (BlockCommentState::Initial, kind)
+            }
+            _ => (BlockCommentState::Initial, number_or_punctuation(cursor)),
+        }
+    }
+
+    fn line_comment(&self) -> Option<&'static str> {
+        Some("//")
+    }
+}
+
+fn is_shader_keyword(string: &str) -> bool {
+    match string {
+        "attribute" | "bool" | "const" | "constant" | "device" | "double" | "false" | "flat"
+        | "float" | "fragment" | "half" | "highp" | "in" | "inout" | "int" | "kernel"
+        | "layout" | "lowp" | "mediump" | "namespace" | "out" | "precision" | "sampler"
+        | "static" | "struct" | "thread" | "threadgroup" | "true" | "uint" | "uniform"
+        | "using" | "varying" | "vertex" | "void" => true,
+        _ => {
+            // Vector, matrix, sampler and texture types such as `vec3`, `float4x4`, `sampler2D`
+            // or `texture2d`.
+            let prefix = string.trim_end_matches(|char: char| {
+                char.is_ascii_digit() || matches!(char, 'x' | 'd' | 'D')
+            });
+            prefix.len() < string.len()
+                && matches!(
+                    prefix,
+                    "vec" | "ivec" | "uvec" | "bvec" | "dvec" | "mat" | "dmat" | "float" | "half"
+                        | "int" | "uint" | "bool" | "sampler" | "texture"
+                )
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum TomlState {
+    #[default]
+    Initial,
+    MultilineString,
+    MultilineLiteralString,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Toml;
+
+impl Language for Toml {
+    type State = TomlState;
+
+    fn next(&self, state: Self::State, cursor: &mut Cursor) -> (Self::State, TokenKind) {
+        match state {
+            TomlState::MultilineString => return multiline_string_tail(cursor, '"'),
+            TomlState::MultilineLiteralString => return multiline_string_tail(cursor, '\''),
+            TomlState::Initial => {}
+        }
+        match (cursor.peek(0), cursor.peek(1), cursor.peek(2)) {
+            ('#', _, _) => {
+                cursor.skip_line();
+                (TomlState::Initial, TokenKind::Comment)
+            }
+            ('"', '"', '"') => {
+                cursor.skip(3);
+                multiline_string_tail(cursor, '"')
+            }
+            ('\'', '\'', '\'') => {
+                cursor.skip(3);
+                multiline_string_tail(cursor, '\'')
+            }
+            ('"', _, _) => (TomlState::Initial, string(cursor, '"')),
+            ('\'', _, _) => {
+                cursor.skip(1);
+                cursor.skip_while(|char| char != '\'');
+                cursor.skip_if(|char| char == '\'');
+                (TomlState::Initial, TokenKind::String)
+            }
+            // Table headers such as `[dependencies]` or `[[bin]]`.
+            ('[', _, _) if cursor.before().trim().is_empty() => {
+                cursor.skip_while(|char| char != ']');
+                cursor.skip_while(|char| char == ']');
+                (TomlState::Initial, TokenKind::Typename)
+            }
+            ('+' | '-', char, _) | (char, _, _) if char.is_ascii_digit() => {
+                cursor.skip(1);
+                // This also covers dates and times such as `1979-05-27T07:32:00Z`.
+                cursor.skip_while(|char| {
+                    char.is_ascii_alphanumeric() || matches!(char, '_' | '.' | ':' | '-' | '+')
+                });
+                (TomlState::Initial, TokenKind::Number)
+            }
+            (char, _, _) if is_bare_key_char(char) => {
+                let start = cursor.index();
+                cursor.skip_while(is_bare_key_char);
+                let kind = match &cursor.before()[start..] {
+                    "true" | "false" => TokenKind::OtherKeyword,
+                    "inf" | "nan" => TokenKind::Number,
+                    _ => TokenKind::Identifier,
+                };
+                (TomlState::Initial, kind)
+            }
+            _ => (TomlState::Initial, number_or_punctuation(cursor)),
+        }
+    }
+
+    fn line_comment(&self) -> Option<&'static str> {
+        Some("#")
+    }
+}
+
+fn is_bare_key_char(char: char) -> bool {
+    char.is_ascii_alphanumeric() || char == '_' || char == '-'
+}
+
+fn multiline_string_tail(cursor: &mut Cursor, quote: char) -> (TomlState, TokenKind) {
+    loop {
+        match cursor.peek(0) {
+            '\0' => {
+                let state = if quote == '"' {
+                    TomlState::MultilineString
+                } else {
+                    TomlState::MultilineLiteralString
+                };
+                break (state, TokenKind::String);
+            }
+            '\\' if quote == '"' => cursor.skip(2),
+            char if char == quote && cursor.peek(1) == quote && cursor.peek(2) == quote => {
+                cursor.skip(3);
+                // A string can end in up to two quotes of its own.
+                cursor.skip_if(|char| char == quote);
+                cursor.skip_if(|char| char == quote);
+                break (TomlState::Initial, TokenKind::String);
+            }
+            _ => cursor.skip(1),
+        }
+    }
+}
+
+/// JSON, with `//` comments as allowed by many configuration files. Toggling line comments does
+/// nothing, a `.json` file with comments in it is no longer JSON.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Json;
+
+impl Language for Json {
+    type State = ();
+
+    fn next(&self, _state: (), cursor: &mut Cursor) -> ((), TokenKind) {
+        let kind = match (cursor.peek(0), cursor.peek(1)) {
+            ('/', '/') => {
+                cursor.skip_line();
+                TokenKind::Comment
+            }
+            ('"', _) => {
+                string(cursor, '"');
+                // Keys are coloured differently from values.
+                if cursor.rest().trim_start().starts_with(':') {
+                    TokenKind::Identifier
+                } else {
+                    TokenKind::String
+                }
+            }
+            ('-', char) | (char, _) if char.is_ascii_digit() => {
+                cursor.skip(1);
+                cursor.skip_while(|char| {
+                    char.is_ascii_digit() || matches!(char, '.' | 'e' | 'E' | '+' | '-')
+                });
+                TokenKind::Number
+            }
+            (char, _) if char.is_identifier_start() => match identifier(cursor) {
+                "true" | "false" | "null" => TokenKind::OtherKeyword,
+                _ => TokenKind::Unknown,
+            },
+            _ => number_or_punctuation(cursor),
+        };
+        ((), kind)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum MarkdownState {
+    #[default]
+    Initial,
+    /// Inside a fenced code block, which is closed by at least `len` times `char`.
+    FencedCode { char: char, len: usize },
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Markdown;
+
+impl Language for Markdown {
+    type State = MarkdownState;
+
+    fn next(&self, state: Self::State, cursor: &mut Cursor) -> (Self::State, TokenKind) {
+        let is_line_start = cursor.before().trim().is_empty();
+        let line = cursor.rest().trim_start();
+        if let MarkdownState::FencedCode { char, len } = state {
+            // Code blocks are tokenized a line at a time.
+            let fence_len = line.chars().take_while(|&c| c == char).count();
+            cursor.skip_line();
+            if fence_len >= len && line[fence_len..].trim().is_empty() {
+                return (MarkdownState::Initial, TokenKind::Punctuator);
+            }
+            return (state, TokenKind::String);
+        }
+        if is_line_start && cursor.peek(0).is_whitespace() {
+            cursor.skip_while(|char| char.is_whitespace());
+            return (state, TokenKind::Whitespace);
+        }
+        if is_line_start {
+            for char in ['`', '~'] {
+                let len = line.chars().take_while(|&c| c == char).count();
+                if len >= 3 {
+                    cursor.skip_line();
+                    return (MarkdownState::FencedCode { char, len }, TokenKind::Punctuator);
+                }
+            }
+            let heading_len = line.chars().take_while(|&c| c == '#').count();
+            let heading = &line[heading_len..];
+            if (1..=6).contains(&heading_len) && (heading.is_empty() || heading.starts_with(' ')) {
+                cursor.skip_line();
+                return (state, TokenKind::Typename);
+            }
+            if line.starts_with('>') {
+                cursor.skip_line();
+                return (state, TokenKind::Comment);
+            }
+            // List items.
+            let number_len = line.chars().take_while(|c| c.is_ascii_digit()).count();
+            let marker_len = match line[number_len..].chars().next() {
+                Some('.' | ')') if number_len > 0 => number_len + 1,
+                Some('-' | '*' | '+') if number_len == 0 => 1,
+                _ => 0,
+            };
+            if marker_len > 0 && line[marker_len..].starts_with(' ') {
+                cursor.skip(marker_len);
+                return (state, TokenKind::Punctuator);
+            }
+        }
+        let kind = match (cursor.peek(0), cursor.peek(1)) {
+            ('`', _) => {
+                let len = cursor.rest().chars().take_while(|&c| c == '`').count();
+                cursor.skip(len);
+                let fence = "`".repeat(len);
+                match cursor.rest().find(&fence) {
+                    Some(index) => {
+                        let count = cursor.rest()[..index].chars().count() + len;
+                        cursor.skip(count);
+                    }
+                    None => cursor.skip_line(),
+                }
+                TokenKind::String
+            }
+            // The link of `[text](link)`.
+            ('(', _) if cursor.before().ends_with(']') => {
+                cursor.skip_while(|char| char != ')');
+                cursor.skip_if(|char| char == ')');
+                TokenKind::String
+            }
+            ('[' | ']', _) => {
+                cursor.skip(1);
+                TokenKind::Delimiter
+            }
+            ('*' | '_', _) => {
+                let char = cursor.peek(0);
+                cursor.skip_while(|c| c == char);
+                TokenKind::Punctuator
+            }
+            (char, _) if char.is_whitespace() => {
+                cursor.skip_while(|char| char.is_whitespace());
+                TokenKind::Whitespace
+            }
+            _ => {
+                cursor.skip(1);
+                cursor.skip_while(|char| {
+                    !char.is_whitespace() && !matches!(char, '`' | '[' | ']' | '(' | '*' | '_')
+                });
+                TokenKind::Identifier
+            }
+        };
+        (state, kind)
+    }
+}
+
+fn line_comment(cursor: &mut Cursor) -> (BlockCommentState, TokenKind) {
+    cursor.skip_line();
+    (BlockCommentState::Initial, TokenKind::Comment)
+}
+
+fn block_comment_tail(cursor: &mut Cursor) -> (BlockCommentState, TokenKind) {
+    loop {
+        match (cursor.peek(0), cursor.peek(1)) {
+            ('*', '/') => {
+                cursor.skip(2);
+                break (BlockCommentState::Initial, TokenKind::Comment);
+            }
+            ('\0', _) => break (BlockCommentState::BlockComment, TokenKind::Comment),
+            _ => cursor.skip(1),
+        }
+    }
+}
+
+// Skips a string that ends on the same line, or at the end of the line if it is not terminated.
+fn string(cursor: &mut Cursor, quote: char) -> TokenKind {
+    cursor.skip(1);
+    loop {
+        match cursor.peek(0) {
+            '\0' => break,
+            '\\' => cursor.skip(2),
+            char if char == quote => {
+                cursor.skip(1);
+                break;
+            }
+            _ => cursor.skip(1),
+        }
+    }
+    TokenKind::String
+}
+
+fn identifier<'a>(cursor: &mut Cursor<'a>) -> &'a str {
+    let start = cursor.index();
+    cursor.skip_while(|char| char.is_identifier_continue());
+    &cursor.before()[start..]
+}
+
+// Colours an identifier that is not a keyword the same way the Rust tokenizer does.
+fn identifier_kind(string: &str, cursor: &Cursor) -> TokenKind {
+    let mut chars = string.chars();
+    if chars.next().unwrap().is_uppercase() {
+        match chars.next() {
+            Some(char) if char.is_uppercase() => TokenKind::Constant,
+            _ => TokenKind::Typename,
+        }
+    } else if cursor.peek(0) == '(' {
+        TokenKind::Function
+    } else {
+        TokenKind::Identifier
+    }
+}
+
+fn number_or_punctuation(cursor: &mut Cursor) -> TokenKind {
+    match (cursor.peek(0), cursor.peek(1)) {
+        ('.', char) | (char, _) if char.is_ascii_digit() => {
+            cursor.skip(1);
+            cursor.skip_while(|char| char.is_ascii_alphanumeric() || char == '_' || char == '.');
+            TokenKind::Number
+        }
+        ('(' | ')' | '[' | ']' | '{' | '}', _) => {
+            cursor.skip(1);
+            TokenKind::Delimiter
+        }
+        (char, _) if char.is_whitespace() => {
+            cursor.skip_while(|char| char.is_whitespace());
+            TokenKind::Whitespace
+        }
+        (char, _) if char.is_ascii_punctuation() => {
+            cursor.skip(1);
+            TokenKind::Punctuator
+        }
+        _ => {
+            cursor.skip(1);
+            TokenKind::Unknown
+        }
+    }
+}
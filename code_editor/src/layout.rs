@@ -5,7 +5,7 @@ use {
         selection::Affinity,
         session::SessionLayout,
         str::StrExt,
-        text::{Position, Text},
+        text::{self, Position, Text},
         widgets::{BlockWidget, InlineWidget},
         wrap::WrapData,
         Token,
@@ -89,7 +89,7 @@ impl<'a> Layout<'a> {
             column_count: self.session_layout.column_count[start..end].iter(),
             fold: self.session_layout.fold_column[start..end].iter(),
             scale: self.session_layout.scale[start..end].iter(),
-            text: self.text.as_lines()[start..end].iter(),
+            text: self.text.as_lines().range(start..end),
             indent_state: self.document_layout.indent_state[start..end].iter(),
             tokens: self.document_layout.tokens[start..end].iter(),
             inline_inlays: self.document_layout.inline_inlays[start..end].iter(),
@@ -120,7 +120,7 @@ pub struct Lines<'a> {
     column_count: Iter<'a, Option<usize>>,
     fold: Iter<'a, usize>,
     scale: Iter<'a, f64>,
-    text: Iter<'a, String>,
+    text: text::Iter<'a>,
     indent_state: Iter<'a, Option<IndentState>>,
     tokens: Iter<'a, Vec<Token>>,
     inline_inlays: Iter<'a, Vec<(usize, InlineInlay)>>,
@@ -164,12 +164,17 @@ impl<'a> Line<'a> {
         self.y.unwrap()
     }
 
+    /// Returns the number of rows this line is wrapped into. A line that has not been laid out
+    /// yet, see `Session::layout_lines`, counts as a single row.
     pub fn row_count(&self) -> usize {
-        self.wrap_data.unwrap().wraps.len() + 1
+        self.wrap_data
+            .map_or(1, |wrap_data| wrap_data.wraps.len() + 1)
     }
 
+    /// Returns the number of columns of the widest row of this line. A line that has not been
+    /// laid out yet counts as one column per byte.
     pub fn column_count(&self) -> usize {
-        self.column_count.unwrap()
+        self.column_count.unwrap_or(self.text.len())
     }
 
     pub fn width(&self) -> f64 {
@@ -307,7 +312,8 @@ impl<'a> Line<'a> {
     }
 
     pub fn wrap_indent_column_count(self) -> usize {
-        self.wrap_data.unwrap().indent_column_count
+        self.wrap_data
+            .map_or(0, |wrap_data| wrap_data.indent_column_count)
     }
 
     pub fn text(&self) -> &str {
@@ -315,9 +321,10 @@ impl<'a> Line<'a> {
     }
 
     pub fn indent_column_count(&self) -> usize {
-        match self.indent_state.unwrap() {
-            IndentState::Empty(indent_column_count) => indent_column_count,
-            IndentState::NonEmpty(indent_column_count, _) => indent_column_count,
+        match self.indent_state {
+            Some(IndentState::Empty(indent_column_count)) => indent_column_count,
+            Some(IndentState::NonEmpty(indent_column_count, _)) => indent_column_count,
+            None => self.text.indent().map_or(0, |indent| indent.column_count()),
         }
     }
 
@@ -338,7 +345,9 @@ impl<'a> Line<'a> {
         WrappedElements {
             element: elements.next(),
             elements,
-            wraps: self.wrap_data.unwrap().wraps.iter(),
+            wraps: self
+                .wrap_data
+                .map_or([].iter(), |wrap_data| wrap_data.wraps.iter()),
             position: 0,
         }
     }
@@ -8,9 +8,15 @@ pub mod document;
 pub mod history;
 pub mod inlays;
 pub mod iter;
+pub mod keymap;
+pub mod languages;
 pub mod layout;
+pub mod lsp;
+mod mmap;
+pub mod regex;
 pub mod selection;
 pub mod session;
+pub mod search;
 pub mod settings;
 pub mod str;
 pub mod text;
@@ -0,0 +1,1318 @@
+//! A client for the Language Server Protocol.
+//!
+//! The client talks JSON-RPC to a server over its stdin and stdout. Messages from the server are
+//! read on a thread of their own and handled on the UI thread by `LspClient::handle_messages`,
+//! which the signal passed to the client wakes up.
+//!
+//! Positions in the protocol count UTF-16 code units, positions in a `Document` count bytes. The
+//! client keeps a copy of the text of every open document to convert between the two.
+
+use {
+    crate::{
+        decoration::{Decoration, DecorationType, RelatedLocation},
+        text::{Change, Edit, Position, Text},
+        Document,
+    },
+    makepad_widgets::{makepad_micro_serde::*, Signal},
+    std::{
+        collections::HashMap,
+        io::{self, BufRead, BufReader, Read, Write},
+        ops::Range,
+        path::{Path, PathBuf},
+        process::{Child, Command, Stdio},
+        sync::mpsc::{self, Receiver, Sender, TryRecvError},
+        thread,
+        time::Duration,
+    },
+};
+
+pub struct LspClient {
+    writer: Box<dyn Write + Send>,
+    message_receiver: Receiver<Option<JsonValue>>,
+    child: Option<Child>,
+    next_request_id: u64,
+    pending_requests: HashMap<u64, PendingRequest>,
+    // Messages other than `initialize` are held back until the server has answered it.
+    queued_messages: Option<Vec<JsonValue>>,
+    has_exited: bool,
+    sync_kind: SyncKind,
+    documents: HashMap<String, OpenDocument>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum SyncKind {
+    None,
+    Full,
+    Incremental,
+}
+
+struct OpenDocument {
+    version: u64,
+    // The text as the server knows it.
+    text: Text,
+    edit_receiver: Receiver<Vec<Edit>>,
+}
+
+enum PendingRequest {
+    Initialize,
+    Completion(String, Position),
+    Hover(String, Position),
+    Definition(String, Position),
+    SignatureHelp(String, Position),
+    Other,
+}
+
+/// The results of requests and the notifications of the server.
+///
+/// Positions in the document the request was made for are converted to byte positions, the
+/// ranges of locations in other documents are left as the server sent them.
+#[derive(Clone, Debug)]
+pub enum LspEvent {
+    Diagnostics {
+        uri: String,
+        diagnostics: Vec<Diagnostic>,
+    },
+    Completion {
+        request_id: u64,
+        uri: String,
+        position: Position,
+        items: Vec<CompletionItem>,
+    },
+    Hover {
+        request_id: u64,
+        uri: String,
+        position: Position,
+        contents: String,
+    },
+    Definition {
+        request_id: u64,
+        uri: String,
+        position: Position,
+        locations: Vec<Location>,
+    },
+    SignatureHelp {
+        request_id: u64,
+        uri: String,
+        position: Position,
+        signature: Option<Signature>,
+    },
+    Error {
+        request_id: u64,
+        message: String,
+    },
+    /// The server exited or closed its stdout.
+    Exited,
+}
+
+/// A position as the protocol has it: a line index and a UTF-16 offset into that line.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct LspPosition {
+    pub line: usize,
+    pub character: usize,
+}
+
+impl LspPosition {
+    pub fn from_position(text: &Text, position: Position) -> Self {
+        let line = &text.as_lines()[position.line_index];
+        Self {
+            line: position.line_index,
+            character: line[..position.byte_index.min(line.len())]
+                .encode_utf16()
+                .count(),
+        }
+    }
+
+    /// Returns the byte position in `text`, clamped to the end of the text.
+    pub fn to_position(self, text: &Text) -> Position {
+        let lines = text.as_lines();
+        let line = match lines.get(self.line) {
+            Some(line) => line,
+            None => {
+                return Position {
+                    line_index: lines.len() - 1,
+                    byte_index: lines[lines.len() - 1].len(),
+                }
+            }
+        };
+        let mut character = 0;
+        let mut byte_index = line.len();
+        for (index, char) in line.char_indices() {
+            if character >= self.character {
+                byte_index = index;
+                break;
+            }
+            character += char.len_utf16();
+        }
+        Position {
+            line_index: self.line,
+            byte_index,
+        }
+    }
+
+    fn from_json(value: &JsonValue) -> Option<Self> {
+        Some(Self {
+            line: value.get("line")?.as_u64()? as usize,
+            character: value.get("character")?.as_u64()? as usize,
+        })
+    }
+
+    fn to_json(self) -> JsonValue {
+        object(vec![
+            ("line", JsonValue::U64(self.line as u64)),
+            ("character", JsonValue::U64(self.character as u64)),
+        ])
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct LspRange {
+    pub start: LspPosition,
+    pub end: LspPosition,
+}
+
+impl LspRange {
+    fn from_json(value: &JsonValue) -> Option<Self> {
+        Some(Self {
+            start: LspPosition::from_json(value.get("start")?)?,
+            end: LspPosition::from_json(value.get("end")?)?,
+        })
+    }
+
+    fn to_json(self) -> JsonValue {
+        object(vec![
+            ("start", self.start.to_json()),
+            ("end", self.end.to_json()),
+        ])
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Location {
+    pub uri: String,
+    pub range: LspRange,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DiagnosticSeverity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Diagnostic {
+    pub range: LspRange,
+    pub severity: DiagnosticSeverity,
+    pub message: String,
+    pub related_information: Vec<(Location, String)>,
+}
+
+impl Diagnostic {
+    /// Returns the decoration that shows this diagnostic in `text`.
+    pub fn to_decoration(&self, id: usize, text: &Text) -> Decoration {
+        let ty = match self.severity {
+            DiagnosticSeverity::Error => DecorationType::Error,
+            DiagnosticSeverity::Warning => DecorationType::Warning,
+            DiagnosticSeverity::Information => DecorationType::Info,
+            DiagnosticSeverity::Hint => DecorationType::Hint,
+        };
+        let mut decoration = Decoration::new(
+            id,
+            self.range.start.to_position(text),
+            self.range.end.to_position(text),
+            ty,
+        );
+        decoration.message = self.message.clone();
+        // The related locations are usually in other documents, whose text we don't have, so
+        // their UTF-16 columns are taken as byte indices.
+        decoration.related_locations = self
+            .related_information
+            .iter()
+            .map(|(location, message)| RelatedLocation {
+                path: uri_to_path(&location.uri)
+                    .map(|path| path.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| location.uri.clone()),
+                start: Position {
+                    line_index: location.range.start.line,
+                    byte_index: location.range.start.character,
+                },
+                end: Position {
+                    line_index: location.range.end.line,
+                    byte_index: location.range.end.character,
+                },
+                message: message.clone(),
+            })
+            .collect();
+        decoration
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct CompletionItem {
+    pub label: String,
+    pub detail: Option<String>,
+    /// The text that is matched against what was typed so far.
+    pub filter_text: String,
+    /// The text that replaces what was typed so far.
+    pub insert_text: String,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Signature {
+    pub label: String,
+    /// The byte range in the label of the parameter the cursor is at.
+    pub active_parameter: Option<Range<usize>>,
+}
+
+impl LspClient {
+    /// Starts a server and sends it the `initialize` request for the workspace in `root`. The
+    /// signal is set whenever a message from the server comes in.
+    pub fn spawn(command: &str, args: &[&str], root: &Path, signal: Signal) -> io::Result<Self> {
+        let mut child = Command::new(command)
+            .args(args)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let reader = child.stdout.take().unwrap();
+        let writer = child.stdin.take().unwrap();
+        let mut client = Self::new(reader, writer, root, signal);
+        client.child = Some(child);
+        Ok(client)
+    }
+
+    /// Creates a client that talks to a server through the given streams, such as a server that
+    /// runs in another thread.
+    pub fn new(
+        reader: impl Read + Send + 'static,
+        writer: impl Write + Send + 'static,
+        root: &Path,
+        signal: Signal,
+    ) -> Self {
+        let (message_sender, message_receiver) = mpsc::channel();
+        thread::spawn(move || read_messages(BufReader::new(reader), message_sender, signal));
+        let mut client = Self {
+            writer: Box::new(writer),
+            message_receiver,
+            child: None,
+            next_request_id: 0,
+            pending_requests: HashMap::new(),
+            queued_messages: None,
+            has_exited: false,
+            sync_kind: SyncKind::Full,
+            documents: HashMap::new(),
+        };
+        client.send_request(
+            "initialize",
+            object(vec![
+                ("processId", JsonValue::U64(std::process::id() as u64)),
+                ("rootUri", JsonValue::String(path_to_uri(root))),
+                ("capabilities", client_capabilities()),
+            ]),
+            PendingRequest::Initialize,
+        );
+        client.queued_messages = Some(Vec::new());
+        client
+    }
+
+    pub fn is_document_open(&self, uri: &str) -> bool {
+        self.documents.contains_key(uri)
+    }
+
+    /// Opens a document on the server. From now on the edits of the document are sent to the
+    /// server whenever the client handles its messages or makes a request.
+    pub fn open_document(&mut self, uri: &str, language_id: &str, document: &Document) {
+        let (edit_sender, edit_receiver) = mpsc::channel();
+        document.add_change_listener(edit_sender);
+        let text = document.as_text().clone();
+        self.send_notification(
+            "textDocument/didOpen",
+            object(vec![(
+                "textDocument",
+                object(vec![
+                    ("uri", JsonValue::String(uri.to_string())),
+                    ("languageId", JsonValue::String(language_id.to_string())),
+                    ("version", JsonValue::U64(0)),
+                    ("text", JsonValue::String(text.to_string())),
+                ]),
+            )]),
+        );
+        self.documents.insert(
+            uri.to_string(),
+            OpenDocument {
+                version: 0,
+                text,
+                edit_receiver,
+            },
+        );
+    }
+
+    pub fn close_document(&mut self, uri: &str) {
+        if self.documents.remove(uri).is_some() {
+            self.send_notification(
+                "textDocument/didClose",
+                object(vec![("textDocument", text_document(uri))]),
+            );
+        }
+    }
+
+    pub fn save_document(&mut self, uri: &str) {
+        if self.documents.contains_key(uri) {
+            self.sync_document(uri);
+            self.send_notification(
+                "textDocument/didSave",
+                object(vec![("textDocument", text_document(uri))]),
+            );
+        }
+    }
+
+    /// Sends the edits that were made to the open documents since the last call.
+    pub fn sync_documents(&mut self) {
+        let uris: Vec<_> = self.documents.keys().cloned().collect();
+        for uri in uris {
+            self.sync_document(&uri);
+        }
+    }
+
+    fn sync_document(&mut self, uri: &str) {
+        let document = match self.documents.get_mut(uri) {
+            Some(document) => document,
+            None => return,
+        };
+        let mut has_changed = false;
+        let mut content_changes = Vec::new();
+        while let Ok(edits) = document.edit_receiver.try_recv() {
+            for edit in edits {
+                has_changed = true;
+                if self.sync_kind == SyncKind::Incremental {
+                    content_changes.push(content_change(&document.text, &edit.change));
+                }
+                document.text.apply_change(edit.change);
+            }
+        }
+        if !has_changed || self.sync_kind == SyncKind::None {
+            return;
+        }
+        if self.sync_kind == SyncKind::Full {
+            content_changes = vec![object(vec![(
+                "text",
+                JsonValue::String(document.text.to_string()),
+            )])];
+        }
+        document.version += 1;
+        let params = object(vec![
+            (
+                "textDocument",
+                object(vec![
+                    ("uri", JsonValue::String(uri.to_string())),
+                    ("version", JsonValue::U64(document.version)),
+                ]),
+            ),
+            ("contentChanges", JsonValue::Array(content_changes)),
+        ]);
+        self.send_notification("textDocument/didChange", params);
+    }
+
+    pub fn request_completion(&mut self, uri: &str, position: Position) -> Option<u64> {
+        self.send_position_request(
+            "textDocument/completion",
+            uri,
+            position,
+            PendingRequest::Completion(uri.to_string(), position),
+        )
+    }
+
+    pub fn request_hover(&mut self, uri: &str, position: Position) -> Option<u64> {
+        self.send_position_request(
+            "textDocument/hover",
+            uri,
+            position,
+            PendingRequest::Hover(uri.to_string(), position),
+        )
+    }
+
+    pub fn request_definition(&mut self, uri: &str, position: Position) -> Option<u64> {
+        self.send_position_request(
+            "textDocument/definition",
+            uri,
+            position,
+            PendingRequest::Definition(uri.to_string(), position),
+        )
+    }
+
+    pub fn request_signature_help(&mut self, uri: &str, position: Position) -> Option<u64> {
+        self.send_position_request(
+            "textDocument/signatureHelp",
+            uri,
+            position,
+            PendingRequest::SignatureHelp(uri.to_string(), position),
+        )
+    }
+
+    // Returns `None` if the document is not open.
+    fn send_position_request(
+        &mut self,
+        method: &str,
+        uri: &str,
+        position: Position,
+        request: PendingRequest,
+    ) -> Option<u64> {
+        self.sync_document(uri);
+        let document = self.documents.get(uri)?;
+        let params = object(vec![
+            ("textDocument", text_document(uri)),
+            (
+                "position",
+                LspPosition::from_position(&document.text, position).to_json(),
+            ),
+        ]);
+        Some(self.send_request(method, params, request))
+    }
+
+    /// Sends the pending edits of the open documents, and handles the messages that came in from
+    /// the server since the last call.
+    pub fn handle_messages(&mut self) -> Vec<LspEvent> {
+        self.sync_documents();
+        let mut events = Vec::new();
+        loop {
+            match self.message_receiver.try_recv() {
+                Ok(Some(message)) => self.handle_message(message, &mut events),
+                Ok(None) | Err(TryRecvError::Disconnected) => {
+                    if !self.has_exited {
+                        self.has_exited = true;
+                        events.push(LspEvent::Exited);
+                    }
+                    break;
+                }
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+        events
+    }
+
+    fn handle_message(&mut self, message: JsonValue, events: &mut Vec<LspEvent>) {
+        let id = message.get("id").cloned();
+        match (message.get("method").and_then(|method| method.as_str()), id) {
+            // A request from the server. We don't implement any, but some servers wait for an
+            // answer.
+            (Some(method), Some(id)) => {
+                let result = match method {
+                    "workspace/configuration" => JsonValue::Array(
+                        message
+                            .path("params.items")
+                            .and_then(|items| items.as_array())
+                            .map_or(Vec::new(), |items| vec![JsonValue::Null; items.len()]),
+                    ),
+                    _ => JsonValue::Null,
+                };
+                self.send(object(vec![
+                    ("jsonrpc", JsonValue::String("2.0".to_string())),
+                    ("id", id),
+                    ("result", result),
+                ]));
+            }
+            (Some("textDocument/publishDiagnostics"), None) => {
+                let params = message.get("params").unwrap_or(&JsonValue::Null);
+                let uri = match params.get("uri").and_then(|uri| uri.as_str()) {
+                    Some(uri) => uri,
+                    None => return,
+                };
+                let diagnostics = params
+                    .get("diagnostics")
+                    .and_then(|diagnostics| diagnostics.as_array())
+                    .map_or(Vec::new(), |diagnostics| {
+                        diagnostics.iter().filter_map(parse_diagnostic).collect()
+                    });
+                events.push(LspEvent::Diagnostics {
+                    uri: uri.to_string(),
+                    diagnostics,
+                });
+            }
+            (Some(_), None) => {}
+            (None, Some(id)) => {
+                let request_id = match id.as_u64() {
+                    Some(request_id) => request_id,
+                    None => return,
+                };
+                let request = match self.pending_requests.remove(&request_id) {
+                    Some(request) => request,
+                    None => return,
+                };
+                if let Some(error) = message.get("error") {
+                    events.push(LspEvent::Error {
+                        request_id,
+                        message: error
+                            .get("message")
+                            .and_then(|message| message.as_str())
+                            .unwrap_or("")
+                            .to_string(),
+                    });
+                    return;
+                }
+                let result = message.get("result").unwrap_or(&JsonValue::Null);
+                self.handle_response(request_id, request, result, events);
+            }
+            (None, None) => {}
+        }
+    }
+
+    fn handle_response(
+        &mut self,
+        request_id: u64,
+        request: PendingRequest,
+        result: &JsonValue,
+        events: &mut Vec<LspEvent>,
+    ) {
+        match request {
+            PendingRequest::Initialize => {
+                let sync = result.path("capabilities.textDocumentSync");
+                let kind = sync
+                    .and_then(|sync| sync.as_u64().or_else(|| sync.get("change")?.as_u64()))
+                    .unwrap_or(1);
+                self.sync_kind = match kind {
+                    0 => SyncKind::None,
+                    2 => SyncKind::Incremental,
+                    _ => SyncKind::Full,
+                };
+                let queued_messages = self.queued_messages.take().unwrap_or_default();
+                self.send_notification("initialized", object(Vec::new()));
+                for message in queued_messages {
+                    self.send(message);
+                }
+            }
+            PendingRequest::Completion(uri, position) => {
+                // The result is either a list of items, or an object with the list in `items`.
+                let items = result
+                    .as_array()
+                    .or_else(|| result.get("items")?.as_array())
+                    .map_or(Vec::new(), |items| {
+                        items.iter().filter_map(parse_completion_item).collect()
+                    });
+                events.push(LspEvent::Completion {
+                    request_id,
+                    uri,
+                    position,
+                    items,
+                });
+            }
+            PendingRequest::Hover(uri, position) => {
+                let contents = result.get("contents").map_or(String::new(), hover_contents);
+                events.push(LspEvent::Hover {
+                    request_id,
+                    uri,
+                    position,
+                    contents,
+                });
+            }
+            PendingRequest::Definition(uri, position) => {
+                // The result is a location, a list of locations, or a list of location links.
+                let locations = match result {
+                    JsonValue::Array(locations) => {
+                        locations.iter().filter_map(parse_location).collect()
+                    }
+                    location => parse_location(location).into_iter().collect(),
+                };
+                events.push(LspEvent::Definition {
+                    request_id,
+                    uri,
+                    position,
+                    locations,
+                });
+            }
+            PendingRequest::SignatureHelp(uri, position) => {
+                events.push(LspEvent::SignatureHelp {
+                    request_id,
+                    uri,
+                    position,
+                    signature: parse_signature_help(result),
+                });
+            }
+            PendingRequest::Other => {}
+        }
+    }
+
+    fn send_request(&mut self, method: &str, params: JsonValue, request: PendingRequest) -> u64 {
+        let request_id = self.next_request_id;
+        self.next_request_id += 1;
+        self.pending_requests.insert(request_id, request);
+        self.send(object(vec![
+            ("jsonrpc", JsonValue::String("2.0".to_string())),
+            ("id", JsonValue::U64(request_id)),
+            ("method", JsonValue::String(method.to_string())),
+            ("params", params),
+        ]));
+        request_id
+    }
+
+    fn send_notification(&mut self, method: &str, params: JsonValue) {
+        self.send(object(vec![
+            ("jsonrpc", JsonValue::String("2.0".to_string())),
+            ("method", JsonValue::String(method.to_string())),
+            ("params", params),
+        ]));
+    }
+
+    fn send(&mut self, message: JsonValue) {
+        if let Some(queued_messages) = &mut self.queued_messages {
+            queued_messages.push(message);
+            return;
+        }
+        let body = message.serialize_json();
+        // A server that is gone shows up as `LspEvent::Exited`.
+        let _ = write!(self.writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)
+            .and_then(|_| self.writer.flush());
+    }
+}
+
+impl Drop for LspClient {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            self.queued_messages = None;
+            self.send_request("shutdown", JsonValue::Null, PendingRequest::Other);
+            self.send_notification("exit", JsonValue::Null);
+            // Give the server a moment to exit by itself.
+            thread::spawn(move || {
+                for _ in 0..20 {
+                    if let Ok(Some(_)) = child.try_wait() {
+                        return;
+                    }
+                    thread::sleep(Duration::from_millis(50));
+                }
+                let _ = child.kill();
+                let _ = child.wait();
+            });
+        }
+    }
+}
+
+/// Returns the `file://` URI of an absolute path.
+pub fn path_to_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    let path = path.to_string_lossy().replace('\\', "/");
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' | b':' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// Returns the path of a `file://` URI.
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    let mut bytes = Vec::new();
+    let mut iter = path.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    let path = String::from_utf8(bytes).ok()?;
+    // Windows paths look like `/C:/...`.
+    if path.as_bytes().get(2) == Some(&b':') {
+        return Some(PathBuf::from(&path[1..]));
+    }
+    Some(PathBuf::from(path))
+}
+
+// Reads messages until the server closes its stdout. `None` is sent when it does.
+fn read_messages(mut reader: impl BufRead, message_sender: Sender<Option<JsonValue>>, signal: Signal) {
+    loop {
+        let message = read_message(&mut reader);
+        let is_end = message.is_none();
+        if message_sender.send(message).is_err() {
+            break;
+        }
+        signal.set();
+        if is_end {
+            break;
+        }
+    }
+}
+
+fn read_message(reader: &mut impl BufRead) -> Option<JsonValue> {
+    loop {
+        let mut content_length = None;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).ok()? == 0 {
+                return None;
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse::<usize>().ok();
+                }
+            }
+        }
+        // Headers without a length are skipped, there is no way to know where their body ends.
+        let content_length = match content_length {
+            Some(content_length) => content_length,
+            None => continue,
+        };
+        // The body is read as it comes in, a corrupt length must not allocate up front.
+        let mut body = Vec::new();
+        reader
+            .by_ref()
+            .take(content_length as u64)
+            .read_to_end(&mut body)
+            .ok()?;
+        if body.len() < content_length {
+            return None;
+        }
+        if let Ok(message) = JsonValue::deserialize_json(&String::from_utf8_lossy(&body)) {
+            return Some(message);
+        }
+    }
+}
+
+fn client_capabilities() -> JsonValue {
+    let string_array =
+        |strings: &[&str]| JsonValue::Array(strings.iter().map(|s| JsonValue::String(s.to_string())).collect());
+    object(vec![
+        (
+            "general",
+            object(vec![("positionEncodings", string_array(&["utf-16"]))]),
+        ),
+        (
+            "textDocument",
+            object(vec![
+                (
+                    "synchronization",
+                    object(vec![("didSave", JsonValue::Bool(true))]),
+                ),
+                (
+                    "completion",
+                    object(vec![(
+                        "completionItem",
+                        object(vec![("snippetSupport", JsonValue::Bool(false))]),
+                    )]),
+                ),
+                (
+                    "hover",
+                    object(vec![(
+                        "contentFormat",
+                        string_array(&["plaintext", "markdown"]),
+                    )]),
+                ),
+                (
+                    "signatureHelp",
+                    object(vec![(
+                        "signatureInformation",
+                        object(vec![(
+                            "parameterInformation",
+                            object(vec![("labelOffsetSupport", JsonValue::Bool(true))]),
+                        )]),
+                    )]),
+                ),
+                ("definition", object(Vec::new())),
+                ("publishDiagnostics", object(Vec::new())),
+            ]),
+        ),
+    ])
+}
+
+fn object(members: Vec<(&str, JsonValue)>) -> JsonValue {
+    JsonValue::Object(
+        members
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect(),
+    )
+}
+
+fn text_document(uri: &str) -> JsonValue {
+    object(vec![("uri", JsonValue::String(uri.to_string()))])
+}
+
+// Turns a change into a content change of `textDocument/didChange`. `text` is the text before the
+// change is applied.
+fn content_change(text: &Text, change: &Change) -> JsonValue {
+    let (start, end, new_text) = match change {
+        Change::Insert(position, inserted_text) => (*position, *position, inserted_text.to_string()),
+        Change::Delete(start, length) => (*start, *start + *length, String::new()),
+    };
+    object(vec![
+        (
+            "range",
+            LspRange {
+                start: LspPosition::from_position(text, start),
+                end: LspPosition::from_position(text, end),
+            }
+            .to_json(),
+        ),
+        ("text", JsonValue::String(new_text)),
+    ])
+}
+
+fn parse_diagnostic(value: &JsonValue) -> Option<Diagnostic> {
+    Some(Diagnostic {
+        range: LspRange::from_json(value.get("range")?)?,
+        severity: match value.get("severity").and_then(|severity| severity.as_u64()) {
+            Some(2) => DiagnosticSeverity::Warning,
+            Some(3) => DiagnosticSeverity::Information,
+            Some(4) => DiagnosticSeverity::Hint,
+            _ => DiagnosticSeverity::Error,
+        },
+        message: value.get("message")?.as_str()?.to_string(),
+        related_information: value
+            .get("relatedInformation")
+            .and_then(|related_information| related_information.as_array())
+            .map_or(Vec::new(), |related_information| {
+                related_information
+                    .iter()
+                    .filter_map(|information| {
+                        Some((
+                            parse_location(information.get("location")?)?,
+                            information.get("message")?.as_str()?.to_string(),
+                        ))
+                    })
+                    .collect()
+            }),
+    })
+}
+
+fn parse_completion_item(value: &JsonValue) -> Option<CompletionItem> {
+    let label = value.get("label")?.as_str()?.to_string();
+    let insert_text = value
+        .path("textEdit.newText")
+        .or_else(|| value.get("insertText"))
+        .and_then(|text| text.as_str())
+        .unwrap_or(&label)
+        .to_string();
+    Some(CompletionItem {
+        detail: value
+            .get("detail")
+            .and_then(|detail| detail.as_str())
+            .map(|detail| detail.to_string()),
+        filter_text: value
+            .get("filterText")
+            .and_then(|text| text.as_str())
+            .unwrap_or(&label)
+            .to_string(),
+        label,
+        insert_text,
+    })
+}
+
+// Turns the contents of a hover into plain text. The contents are markdown, a string or a
+// `{language, value}` object, or a list of those.
+fn hover_contents(value: &JsonValue) -> String {
+    match value {
+        JsonValue::String(string) => string.clone(),
+        JsonValue::Array(values) => values
+            .iter()
+            .map(hover_contents)
+            .filter(|string| !string.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        value => {
+            let string = value
+                .get("value")
+                .and_then(|value| value.as_str())
+                .unwrap_or("");
+            if value.get("kind").and_then(|kind| kind.as_str()) == Some("markdown") {
+                // Code fences only get in the way as plain text.
+                string
+                    .lines()
+                    .filter(|line| !line.trim_start().starts_with("```"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+                    .trim()
+                    .to_string()
+            } else {
+                string.to_string()
+            }
+        }
+    }
+}
+
+fn parse_location(value: &JsonValue) -> Option<Location> {
+    match value.get("targetUri") {
+        Some(uri) => Some(Location {
+            uri: uri.as_str()?.to_string(),
+            range: LspRange::from_json(
+                value
+                    .get("targetSelectionRange")
+                    .or_else(|| value.get("targetRange"))?,
+            )?,
+        }),
+        None => Some(Location {
+            uri: value.get("uri")?.as_str()?.to_string(),
+            range: LspRange::from_json(value.get("range")?)?,
+        }),
+    }
+}
+
+fn parse_signature_help(value: &JsonValue) -> Option<Signature> {
+    let signatures = value.get("signatures")?.as_array()?;
+    let active_signature = value
+        .get("activeSignature")
+        .and_then(|index| index.as_u64())
+        .unwrap_or(0) as usize;
+    let signature = signatures.get(active_signature).or(signatures.first())?;
+    let label = signature.get("label")?.as_str()?.to_string();
+    let active_parameter = signature
+        .get("activeParameter")
+        .or_else(|| value.get("activeParameter"))
+        .and_then(|index| index.as_u64())
+        .and_then(|index| signature.get("parameters")?.at(index as usize))
+        .and_then(|parameter| match parameter.get("label")? {
+            JsonValue::String(parameter_label) => {
+                let start = label.find(parameter_label.as_str())?;
+                Some(start..start + parameter_label.len())
+            }
+            // With `labelOffsetSupport` the label is a range of UTF-16 offsets.
+            parameter_label => {
+                let start = parameter_label.at(0)?.as_u64()? as usize;
+                let end = parameter_label.at(1)?.as_u64()? as usize;
+                Some(utf16_to_byte_index(&label, start)..utf16_to_byte_index(&label, end))
+            }
+        });
+    Some(Signature {
+        label,
+        active_parameter,
+    })
+}
+
+fn utf16_to_byte_index(string: &str, utf16_index: usize) -> usize {
+    LspPosition {
+        line: 0,
+        character: utf16_index,
+    }
+    .to_position(&Text::from(string))
+    .byte_index
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{decoration::DecorationSet, text::Drift},
+        std::io::Cursor,
+    };
+
+    // One end of an in-memory pipe. Reads block until the other end writes, and return 0 once it
+    // is dropped.
+    struct PipeReader {
+        receiver: Receiver<Vec<u8>>,
+        buffer: Cursor<Vec<u8>>,
+    }
+
+    impl Read for PipeReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            while self.buffer.position() as usize == self.buffer.get_ref().len() {
+                match self.receiver.recv() {
+                    Ok(bytes) => self.buffer = Cursor::new(bytes),
+                    Err(_) => return Ok(0),
+                }
+            }
+            self.buffer.read(buf)
+        }
+    }
+
+    struct PipeWriter(Sender<Vec<u8>>);
+
+    impl Write for PipeWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0
+                .send(buf.to_vec())
+                .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn pipe() -> (PipeReader, PipeWriter) {
+        let (sender, receiver) = mpsc::channel();
+        (
+            PipeReader {
+                receiver,
+                buffer: Cursor::new(Vec::new()),
+            },
+            PipeWriter(sender),
+        )
+    }
+
+    // The server end of a connection to a client.
+    struct StubServer {
+        reader: BufReader<PipeReader>,
+        writer: PipeWriter,
+    }
+
+    impl StubServer {
+        fn receive(&mut self) -> JsonValue {
+            read_message(&mut self.reader).unwrap()
+        }
+
+        fn send(&mut self, message: &str) {
+            write!(self.writer, "Content-Length: {}\r\n\r\n{}", message.len(), message).unwrap();
+        }
+    }
+
+    fn connect() -> (LspClient, StubServer, Signal) {
+        let (client_reader, server_writer) = pipe();
+        let (server_reader, client_writer) = pipe();
+        let signal = Signal::new();
+        let client = LspClient::new(
+            client_reader,
+            client_writer,
+            Path::new("/project"),
+            signal.clone(),
+        );
+        let server = StubServer {
+            reader: BufReader::new(server_reader),
+            writer: server_writer,
+        };
+        (client, server, signal)
+    }
+
+    // Waits for the next message from the server to come in, and handles it.
+    fn handle_next_message(client: &mut LspClient, signal: &Signal) -> Vec<LspEvent> {
+        for _ in 0..1000 {
+            if signal.check_and_clear() {
+                return client.handle_messages();
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        panic!("no message from the server");
+    }
+
+    fn method(message: &JsonValue) -> &str {
+        message.get("method").and_then(|method| method.as_str()).unwrap()
+    }
+
+    #[test]
+    fn initialize_open_and_change() {
+        let (mut client, mut server, signal) = connect();
+        let request = server.receive();
+        assert_eq!(method(&request), "initialize");
+        assert_eq!(request.get("id").and_then(|id| id.as_u64()), Some(0));
+        assert_eq!(
+            request.path("params.rootUri").and_then(|uri| uri.as_str()),
+            Some("file:///project")
+        );
+
+        // Opening a document before the server has answered `initialize` holds it back.
+        let document = Document::new(Text::from("fn main() {}\n"), DecorationSet::new());
+        client.open_document("file:///project/main.rs", "rust", &document);
+        assert!(client.is_document_open("file:///project/main.rs"));
+
+        server.send(
+            r#"{"jsonrpc":"2.0","id":0,"result":{"capabilities":{"textDocumentSync":{"change":2}}}}"#,
+        );
+        assert!(handle_next_message(&mut client, &signal).is_empty());
+        assert_eq!(method(&server.receive()), "initialized");
+        let notification = server.receive();
+        assert_eq!(method(&notification), "textDocument/didOpen");
+        assert_eq!(
+            notification
+                .path("params.textDocument.text")
+                .and_then(|text| text.as_str()),
+            Some("fn main() {}\n")
+        );
+
+        // The server asked for incremental changes, ranges count UTF-16 code units.
+        document.apply_edits(vec![Edit {
+            change: Change::Insert(
+                Position {
+                    line_index: 0,
+                    byte_index: 0,
+                },
+                Text::from("/* é */ "),
+            ),
+            drift: Drift::Before,
+        }]);
+        document.apply_edits(vec![Edit {
+            change: Change::Insert(
+                Position {
+                    line_index: 0,
+                    byte_index: 9,
+                },
+                Text::from("pub "),
+            ),
+            drift: Drift::Before,
+        }]);
+        client.sync_documents();
+        let notification = server.receive();
+        assert_eq!(method(&notification), "textDocument/didChange");
+        assert_eq!(
+            notification
+                .path("params.textDocument.version")
+                .and_then(|version| version.as_u64()),
+            Some(1)
+        );
+        let changes = notification
+            .path("params.contentChanges")
+            .and_then(|changes| changes.as_array())
+            .unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(
+            changes[1].path("range.start.character").and_then(|character| character.as_u64()),
+            Some(8)
+        );
+        assert_eq!(changes[1].get("text").and_then(|text| text.as_str()), Some("pub "));
+
+        // Nothing is sent when nothing changed.
+        client.sync_documents();
+        client.close_document("file:///project/main.rs");
+        assert_eq!(method(&server.receive()), "textDocument/didClose");
+    }
+
+    #[test]
+    fn full_sync() {
+        let (mut client, mut server, signal) = connect();
+        server.receive();
+        server.send(r#"{"jsonrpc":"2.0","id":0,"result":{"capabilities":{"textDocumentSync":1}}}"#);
+        handle_next_message(&mut client, &signal);
+        assert_eq!(method(&server.receive()), "initialized");
+
+        let document = Document::new(Text::from("a\nb"), DecorationSet::new());
+        client.open_document("file:///project/a.txt", "plaintext", &document);
+        server.receive();
+        document.apply_edits(vec![Edit {
+            change: Change::Delete(
+                Position {
+                    line_index: 0,
+                    byte_index: 1,
+                },
+                Text::from("\n").length(),
+            ),
+            drift: Drift::Before,
+        }]);
+        client.save_document("file:///project/a.txt");
+        let notification = server.receive();
+        assert_eq!(method(&notification), "textDocument/didChange");
+        assert_eq!(
+            notification
+                .path("params.contentChanges")
+                .and_then(|changes| changes.as_array())
+                .map(|changes| changes.len()),
+            Some(1)
+        );
+        assert_eq!(
+            notification
+                .path("params.contentChanges")
+                .and_then(|changes| changes.at(0))
+                .and_then(|change| change.get("text"))
+                .and_then(|text| text.as_str()),
+            Some("ab")
+        );
+        assert_eq!(method(&server.receive()), "textDocument/didSave");
+    }
+
+    #[test]
+    fn diagnostics_and_exit() {
+        let (mut client, mut server, signal) = connect();
+        server.receive();
+        server.send(r#"{"jsonrpc":"2.0","id":0,"result":{"capabilities":{}}}"#);
+        handle_next_message(&mut client, &signal);
+        server.receive();
+
+        server.send(concat!(
+            r#"{"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"#,
+            r#""uri":"file:///project/main.rs","diagnostics":["#,
+            r#"{"range":{"start":{"line":0,"character":3},"end":{"line":0,"character":7}},"#,
+            r#""severity":2,"message":"unused","relatedInformation":[{"location":{"#,
+            r#""uri":"file:///project/lib.rs","range":{"start":{"line":1,"character":0},"#,
+            r#""end":{"line":1,"character":1}}},"message":"here"}]},"#,
+            r#"{"message":"no range"}]}}"#
+        ));
+        let events = handle_next_message(&mut client, &signal);
+        let (uri, diagnostics) = match &events[..] {
+            [LspEvent::Diagnostics { uri, diagnostics }] => (uri, diagnostics),
+            events => panic!("unexpected events {:?}", events),
+        };
+        assert_eq!(uri, "file:///project/main.rs");
+        // Diagnostics without a range are dropped.
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, DiagnosticSeverity::Warning);
+        assert_eq!(diagnostics[0].message, "unused");
+        assert_eq!(diagnostics[0].range.start.character, 3);
+        assert_eq!(diagnostics[0].related_information[0].0.uri, "file:///project/lib.rs");
+
+        // A request from the server gets an answer, even though it isn't implemented.
+        server.send(
+            r#"{"jsonrpc":"2.0","id":"a","method":"workspace/configuration","params":{"items":[{},{}]}}"#,
+        );
+        assert!(handle_next_message(&mut client, &signal).is_empty());
+        let response = server.receive();
+        assert_eq!(response.get("id").and_then(|id| id.as_str()), Some("a"));
+        assert_eq!(
+            response.get("result").and_then(|result| result.as_array()).map(|result| result.len()),
+            Some(2)
+        );
+
+        drop(server);
+        let events = handle_next_message(&mut client, &signal);
+        assert!(matches!(events[..], [LspEvent::Exited]));
+        assert!(client.handle_messages().is_empty());
+    }
+
+    #[test]
+    fn read_framed_messages() {
+        let mut reader = Cursor::new(
+            concat!(
+                "Content-Length: 2\r\n\r\n{}",
+                // Header names are case insensitive, and other headers are ignored.
+                "content-type: application/vscode-jsonrpc; charset=utf-8\r\n",
+                "content-length: 10\r\n\r\n{\"a\":\"é\"}",
+                // Bodies that aren't JSON are skipped.
+                "Content-Length: 3\r\n\r\nnul",
+                // So are headers without a length, and headers with a length that isn't a number.
+                "Content-Type: text/plain\r\n\r\n",
+                "Content-Length: x\r\n\r\n",
+                "Content-Length: 4\r\n\r\ntrue",
+            )
+            .as_bytes(),
+        );
+        assert_eq!(read_message(&mut reader), Some(object(Vec::new())));
+        assert_eq!(
+            read_message(&mut reader),
+            Some(object(vec![("a", JsonValue::String("é".to_string()))]))
+        );
+        assert_eq!(read_message(&mut reader), Some(JsonValue::Bool(true)));
+        assert_eq!(read_message(&mut reader), None);
+    }
+
+    #[test]
+    fn read_truncated_messages() {
+        for input in [
+            "",
+            "Content-Length: 10",
+            "Content-Length: 10\r\n",
+            "Content-Length: 10\r\n\r\n{}",
+            "Content-Length: 18446744073709551616\r\n\r\n{}",
+            "Content-Length: 1000000000000\r\n\r\n{}",
+        ] {
+            assert_eq!(read_message(&mut Cursor::new(input.as_bytes())), None);
+        }
+    }
+
+    #[test]
+    fn uris() {
+        let path = Path::new("/a b/c#d.rs");
+        assert_eq!(path_to_uri(path), "file:///a%20b/c%23d.rs");
+        assert_eq!(uri_to_path(&path_to_uri(path)).as_deref(), Some(path));
+        assert_eq!(uri_to_path("file:///a%2"), None);
+        assert_eq!(uri_to_path("file:///a%zz"), None);
+        assert_eq!(uri_to_path("http://a"), None);
+    }
+}
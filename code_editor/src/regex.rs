@@ -0,0 +1,803 @@
+use std::{fmt, ops::Range};
+
+/// A small regular expression engine for searching in the editor.
+///
+/// Supports literals, `.`, character classes with ranges and negation, the escapes `\d \w \s \D
+/// \W \S \b \B \n \t`, the anchors `^` and `$` (which match at line boundaries), capturing and
+/// non-capturing groups, alternation, and the greedy and lazy quantifiers `* + ? {n} {n,} {n,m}`.
+/// `.` doesn't match a newline.
+///
+/// Patterns are compiled to a program that is run as a Pike VM, so a search takes time linear in
+/// the length of the text for any pattern, and never recurses.
+#[derive(Clone, Debug)]
+pub struct Regex {
+    program: Vec<Inst>,
+    group_count: usize,
+    case_insensitive: bool,
+}
+
+/// The maximum number of instructions in a compiled pattern. Bounded repetitions are compiled by
+/// repeating their node, so a pattern like `(a{1000}){1000}` is rejected instead of exhausting
+/// memory.
+const MAX_PROGRAM_LEN: usize = 1 << 16;
+
+impl Regex {
+    pub fn new(pattern: &str, case_insensitive: bool) -> Result<Self, RegexError> {
+        let mut parser = Parser {
+            chars: pattern.char_indices().collect(),
+            index: 0,
+            group_count: 0,
+        };
+        let node = parser.parse_alternation()?;
+        if let Some(&(position, _)) = parser.chars.get(parser.index) {
+            return Err(RegexError::new(position, "unmatched `)`"));
+        }
+        if node.program_len() > MAX_PROGRAM_LEN {
+            return Err(RegexError::new(0, "pattern is too large"));
+        }
+        Ok(Self {
+            program: compile(&node),
+            group_count: parser.group_count,
+            case_insensitive,
+        })
+    }
+
+    /// Creates a regex that matches the given string literally.
+    pub fn literal(string: &str, case_insensitive: bool) -> Self {
+        Self {
+            program: compile(&Node::Concat(string.chars().map(Node::Char).collect())),
+            group_count: 0,
+            case_insensitive,
+        }
+    }
+
+    pub fn group_count(&self) -> usize {
+        self.group_count
+    }
+
+    /// Returns the leftmost match in `text` that starts at or after the byte index `start`.
+    pub fn find_at(&self, text: &str, start: usize) -> Option<Match> {
+        // Slots 0 and 1 hold the range of the whole match, and slots 2n and 2n + 1 that of group
+        // n.
+        let slot_count = 2 * (self.group_count + 1);
+        let mut threads = Threads::new(self.program.len());
+        let mut next_threads = Threads::new(self.program.len());
+        let mut matched = None;
+        let mut index = start;
+        self.add_thread(&mut threads, text, index, 0, vec![None; slot_count]);
+        loop {
+            let char = text[index..].chars().next();
+            let next_index = index + char.map_or(0, |char| char.len_utf8());
+            for (pc, slots) in threads.list.drain(..) {
+                match &self.program[pc] {
+                    Inst::Match => {
+                        // Threads after this one have a lower priority, so they can't match.
+                        matched = Some(slots);
+                        break;
+                    }
+                    inst => {
+                        if char.is_some_and(|char| self.is_char_match(inst, char)) {
+                            self.add_thread(&mut next_threads, text, next_index, pc + 1, slots);
+                        }
+                    }
+                }
+            }
+            if char.is_none() {
+                break;
+            }
+            index = next_index;
+            if matched.is_none() {
+                // Start a match at the next char, with a lower priority than the ones that
+                // started before it.
+                self.add_thread(&mut next_threads, text, index, 0, vec![None; slot_count]);
+            }
+            if matched.is_some() && next_threads.list.is_empty() {
+                break;
+            }
+            std::mem::swap(&mut threads, &mut next_threads);
+            next_threads.clear();
+        }
+        let slots = matched?;
+        Some(Match {
+            range: slots[0].unwrap()..slots[1].unwrap(),
+            groups: slots[2..]
+                .chunks(2)
+                .map(|slots| Some(slots[0]?..slots[1]?))
+                .collect(),
+        })
+    }
+
+    // Adds the thread at `pc` to `threads`, after following every instruction that doesn't
+    // consume a char. Threads are added in order of priority, and a thread that reaches an
+    // instruction that was already reached at this index is dropped, because the earlier one
+    // has a higher priority.
+    fn add_thread(
+        &self,
+        threads: &mut Threads,
+        text: &str,
+        index: usize,
+        pc: usize,
+        slots: Vec<Option<usize>>,
+    ) {
+        let mut stack = vec![(pc, slots)];
+        while let Some((pc, mut slots)) = stack.pop() {
+            if !threads.visit(pc) {
+                continue;
+            }
+            match self.program[pc] {
+                Inst::Jump(target) => stack.push((target, slots)),
+                Inst::Split(first, second) => {
+                    stack.push((second, slots.clone()));
+                    stack.push((first, slots));
+                }
+                Inst::Save(slot) => {
+                    slots[slot] = Some(index);
+                    stack.push((pc + 1, slots));
+                }
+                Inst::LineStart => {
+                    if index == 0 || text[..index].ends_with('\n') {
+                        stack.push((pc + 1, slots));
+                    }
+                }
+                Inst::LineEnd => {
+                    if index == text.len() || text[index..].starts_with('\n') {
+                        stack.push((pc + 1, slots));
+                    }
+                }
+                Inst::WordBoundary(is_negated) => {
+                    if is_at_word_boundary(text, index) != is_negated {
+                        stack.push((pc + 1, slots));
+                    }
+                }
+                Inst::Char(_) | Inst::Any | Inst::Class(_) | Inst::Match => {
+                    threads.list.push((pc, slots))
+                }
+            }
+        }
+    }
+
+    fn is_char_match(&self, inst: &Inst, char: char) -> bool {
+        match inst {
+            Inst::Char(expected) => {
+                char == *expected
+                    || self.case_insensitive && char.to_lowercase().eq(expected.to_lowercase())
+            }
+            Inst::Any => char != '\n',
+            Inst::Class(class) => class.contains(char, self.case_insensitive),
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Match {
+    pub range: Range<usize>,
+    /// The byte ranges of the capturing groups, starting with group 1.
+    pub groups: Vec<Option<Range<usize>>>,
+}
+
+impl Match {
+    /// Returns the byte range of the given group, where group 0 is the whole match.
+    pub fn group(&self, index: usize) -> Option<Range<usize>> {
+        if index == 0 {
+            return Some(self.range.clone());
+        }
+        self.groups.get(index - 1).cloned().flatten()
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct RegexError {
+    /// The byte index in the pattern where the error was found.
+    pub position: usize,
+    pub message: String,
+}
+
+impl RegexError {
+    fn new(position: usize, message: &str) -> Self {
+        Self {
+            position,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for RegexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.position)
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Char(char),
+    Any,
+    Class(Class),
+    LineStart,
+    LineEnd,
+    WordBoundary,
+    NotWordBoundary,
+    Group(Box<Node>, Option<usize>),
+    Concat(Vec<Node>),
+    Alternation(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+        is_greedy: bool,
+    },
+}
+
+impl Node {
+    // The number of instructions `compile` emits for this node.
+    fn program_len(&self) -> usize {
+        match self {
+            Self::Char(_)
+            | Self::Any
+            | Self::Class(_)
+            | Self::LineStart
+            | Self::LineEnd
+            | Self::WordBoundary
+            | Self::NotWordBoundary => 1,
+            Self::Group(node, None) => node.program_len(),
+            Self::Group(node, Some(_)) => node.program_len().saturating_add(2),
+            Self::Concat(nodes) => nodes
+                .iter()
+                .fold(0, |len, node| len.saturating_add(node.program_len())),
+            Self::Alternation(nodes) => nodes.iter().fold(0, |len, node| {
+                len.saturating_add(node.program_len()).saturating_add(2)
+            }),
+            Self::Repeat { node, min, max, .. } => {
+                let len = node.program_len();
+                let optional_len = match max {
+                    Some(max) => (max - min).saturating_mul(len.saturating_add(1)),
+                    None => len.saturating_add(2),
+                };
+                min.saturating_mul(len).saturating_add(optional_len)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Class {
+    items: Vec<ClassItem>,
+    is_negated: bool,
+}
+
+impl Class {
+    fn new(item: ClassItem, is_negated: bool) -> Self {
+        Self {
+            items: vec![item],
+            is_negated,
+        }
+    }
+
+    fn contains(&self, char: char, case_insensitive: bool) -> bool {
+        let contains = |char| self.items.iter().any(|item| item.contains(char));
+        let mut is_contained = contains(char);
+        if !is_contained && case_insensitive {
+            is_contained = char.to_lowercase().any(contains) || char.to_uppercase().any(contains);
+        }
+        is_contained != self.is_negated
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum ClassItem {
+    Range(char, char),
+    Digit(bool),
+    Word(bool),
+    Space(bool),
+}
+
+impl ClassItem {
+    fn contains(self, char: char) -> bool {
+        match self {
+            Self::Range(start, end) => start <= char && char <= end,
+            Self::Digit(is_negated) => char.is_ascii_digit() != is_negated,
+            Self::Word(is_negated) => is_word_char(char) != is_negated,
+            Self::Space(is_negated) => char.is_whitespace() != is_negated,
+        }
+    }
+}
+
+/// Escapes the chars in `string` that have a special meaning in a pattern.
+pub fn escape(string: &str) -> String {
+    let mut escaped = String::new();
+    for char in string.chars() {
+        if "\\.^$|?*+()[]{}".contains(char) {
+            escaped.push('\\');
+        }
+        escaped.push(char);
+    }
+    escaped
+}
+
+pub fn is_word_char(char: char) -> bool {
+    char.is_alphanumeric() || char == '_'
+}
+
+struct Parser {
+    chars: Vec<(usize, char)>,
+    index: usize,
+    group_count: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).map(|&(_, char)| char)
+    }
+
+    fn position(&self) -> usize {
+        match self.chars.get(self.index) {
+            Some(&(index, _)) => index,
+            None => self
+                .chars
+                .last()
+                .map_or(0, |&(index, char)| index + char.len_utf8()),
+        }
+    }
+
+    fn eat(&mut self, char: char) -> bool {
+        if self.peek() == Some(char) {
+            self.index += 1;
+            return true;
+        }
+        false
+    }
+
+    fn next(&mut self) -> Result<char, RegexError> {
+        match self.peek() {
+            Some(char) => {
+                self.index += 1;
+                Ok(char)
+            }
+            None => Err(RegexError::new(self.position(), "unexpected end of pattern")),
+        }
+    }
+
+    fn parse_alternation(&mut self) -> Result<Node, RegexError> {
+        let mut nodes = vec![self.parse_concat()?];
+        while self.eat('|') {
+            nodes.push(self.parse_concat()?);
+        }
+        Ok(if nodes.len() == 1 {
+            nodes.pop().unwrap()
+        } else {
+            Node::Alternation(nodes)
+        })
+    }
+
+    fn parse_concat(&mut self) -> Result<Node, RegexError> {
+        let mut nodes = Vec::new();
+        while let Some(char) = self.peek() {
+            if char == '|' || char == ')' {
+                break;
+            }
+            let atom = self.parse_atom()?;
+            nodes.push(self.parse_quantifier(atom)?);
+        }
+        Ok(Node::Concat(nodes))
+    }
+
+    fn parse_quantifier(&mut self, node: Node) -> Result<Node, RegexError> {
+        let position = self.position();
+        let (min, max) = match self.peek() {
+            Some('*') => {
+                self.index += 1;
+                (0, None)
+            }
+            Some('+') => {
+                self.index += 1;
+                (1, None)
+            }
+            Some('?') => {
+                self.index += 1;
+                (0, Some(1))
+            }
+            Some('{') => match self.parse_counts() {
+                Some(counts) => counts,
+                // Not a valid count, so the brace is a literal.
+                None => return Ok(node),
+            },
+            _ => return Ok(node),
+        };
+        if min > max.unwrap_or(min) {
+            return Err(RegexError::new(position, "invalid repetition count"));
+        }
+        match node {
+            Node::LineStart | Node::LineEnd | Node::WordBoundary | Node::NotWordBoundary => {
+                return Err(RegexError::new(position, "nothing to repeat"));
+            }
+            _ => {}
+        }
+        let is_greedy = !self.eat('?');
+        if let Some('*' | '+' | '?' | '{') = self.peek() {
+            return Err(RegexError::new(self.position(), "nothing to repeat"));
+        }
+        Ok(Node::Repeat {
+            node: Box::new(node),
+            min,
+            max,
+            is_greedy,
+        })
+    }
+
+    // Parses `{n}`, `{n,}` or `{n,m}`, and leaves the index after it.
+    fn parse_counts(&mut self) -> Option<(usize, Option<usize>)> {
+        let start = self.index;
+        let parse_number = |parser: &mut Self| {
+            let digits_start = parser.index;
+            while parser.peek().is_some_and(|char| char.is_ascii_digit()) {
+                parser.index += 1;
+            }
+            parser.chars[digits_start..parser.index]
+                .iter()
+                .map(|&(_, char)| char)
+                .collect::<String>()
+                .parse::<usize>()
+                .ok()
+        };
+        self.index += 1;
+        let counts = parse_number(self).and_then(|min| {
+            if self.eat('}') {
+                return Some((min, Some(min)));
+            }
+            if !self.eat(',') {
+                return None;
+            }
+            if self.eat('}') {
+                return Some((min, None));
+            }
+            let max = parse_number(self)?;
+            self.eat('}').then_some((min, Some(max)))
+        });
+        if counts.is_none() {
+            self.index = start;
+        }
+        counts
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, RegexError> {
+        let position = self.position();
+        Ok(match self.next()? {
+            '.' => Node::Any,
+            '^' => Node::LineStart,
+            '$' => Node::LineEnd,
+            '(' => {
+                let index = if self.eat('?') {
+                    if !self.eat(':') {
+                        return Err(RegexError::new(self.position(), "unsupported group kind"));
+                    }
+                    None
+                } else {
+                    self.group_count += 1;
+                    Some(self.group_count - 1)
+                };
+                let node = self.parse_alternation()?;
+                if !self.eat(')') {
+                    return Err(RegexError::new(position, "unclosed group"));
+                }
+                Node::Group(Box::new(node), index)
+            }
+            '[' => Node::Class(self.parse_class(position)?),
+            '\\' => match self.next()? {
+                'b' => Node::WordBoundary,
+                'B' => Node::NotWordBoundary,
+                char => match escape_class_item(char) {
+                    Some(item) => Node::Class(Class::new(item, false)),
+                    None => Node::Char(escape_char(char)),
+                },
+            },
+            '*' | '+' | '?' => return Err(RegexError::new(position, "nothing to repeat")),
+            char => Node::Char(char),
+        })
+    }
+
+    fn parse_class(&mut self, position: usize) -> Result<Class, RegexError> {
+        let is_negated = self.eat('^');
+        let mut items = Vec::new();
+        let mut is_first = true;
+        loop {
+            let char = match self.peek() {
+                Some(']') if !is_first => {
+                    self.index += 1;
+                    break;
+                }
+                Some(_) => self.next()?,
+                None => return Err(RegexError::new(position, "unclosed character class")),
+            };
+            is_first = false;
+            let start = if char == '\\' {
+                let char = self.next()?;
+                if let Some(item) = escape_class_item(char) {
+                    items.push(item);
+                    continue;
+                }
+                escape_char(char)
+            } else {
+                char
+            };
+            let is_range = self.peek() == Some('-')
+                && self
+                    .chars
+                    .get(self.index + 1)
+                    .is_some_and(|&(_, char)| char != ']');
+            if !is_range {
+                items.push(ClassItem::Range(start, start));
+                continue;
+            }
+            self.index += 1;
+            let end = match self.next()? {
+                '\\' => escape_char(self.next()?),
+                char => char,
+            };
+            if start > end {
+                return Err(RegexError::new(position, "invalid range in character class"));
+            }
+            items.push(ClassItem::Range(start, end));
+        }
+        Ok(Class { items, is_negated })
+    }
+}
+
+fn escape_class_item(char: char) -> Option<ClassItem> {
+    Some(match char {
+        'd' => ClassItem::Digit(false),
+        'D' => ClassItem::Digit(true),
+        'w' => ClassItem::Word(false),
+        'W' => ClassItem::Word(true),
+        's' => ClassItem::Space(false),
+        'S' => ClassItem::Space(true),
+        _ => return None,
+    })
+}
+
+fn escape_char(char: char) -> char {
+    match char {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        char => char,
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Inst {
+    Char(char),
+    Any,
+    Class(Class),
+    LineStart,
+    LineEnd,
+    WordBoundary(bool),
+    /// Continues at both targets, preferring the first.
+    Split(usize, usize),
+    Jump(usize),
+    Save(usize),
+    Match,
+}
+
+fn compile(node: &Node) -> Vec<Inst> {
+    let mut program = vec![Inst::Save(0)];
+    compile_node(node, &mut program);
+    program.push(Inst::Save(1));
+    program.push(Inst::Match);
+    program
+}
+
+fn compile_node(node: &Node, program: &mut Vec<Inst>) {
+    match node {
+        Node::Char(char) => program.push(Inst::Char(*char)),
+        Node::Any => program.push(Inst::Any),
+        Node::Class(class) => program.push(Inst::Class(class.clone())),
+        Node::LineStart => program.push(Inst::LineStart),
+        Node::LineEnd => program.push(Inst::LineEnd),
+        Node::WordBoundary => program.push(Inst::WordBoundary(false)),
+        Node::NotWordBoundary => program.push(Inst::WordBoundary(true)),
+        Node::Group(node, None) => compile_node(node, program),
+        Node::Group(node, Some(group_index)) => {
+            program.push(Inst::Save(2 * group_index + 2));
+            compile_node(node, program);
+            program.push(Inst::Save(2 * group_index + 3));
+        }
+        Node::Concat(nodes) => {
+            for node in nodes {
+                compile_node(node, program);
+            }
+        }
+        Node::Alternation(nodes) => {
+            let mut jumps = Vec::new();
+            for (index, node) in nodes.iter().enumerate() {
+                if index == nodes.len() - 1 {
+                    compile_node(node, program);
+                    break;
+                }
+                let split = program.len();
+                program.push(Inst::Split(split + 1, 0));
+                compile_node(node, program);
+                jumps.push(program.len());
+                program.push(Inst::Jump(0));
+                program[split] = Inst::Split(split + 1, program.len());
+            }
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Inst::Jump(end);
+            }
+        }
+        Node::Repeat {
+            node,
+            min,
+            max,
+            is_greedy,
+        } => {
+            let split = |program: &mut Vec<Inst>, at: usize, body: usize, exit: usize| {
+                program[at] = if *is_greedy {
+                    Inst::Split(body, exit)
+                } else {
+                    Inst::Split(exit, body)
+                };
+            };
+            for _ in 0..*min {
+                compile_node(node, program);
+            }
+            match max {
+                Some(max) => {
+                    let mut splits = Vec::new();
+                    for _ in *min..*max {
+                        splits.push(program.len());
+                        program.push(Inst::Match);
+                        compile_node(node, program);
+                    }
+                    let end = program.len();
+                    for at in splits {
+                        split(program, at, at + 1, end);
+                    }
+                }
+                None => {
+                    let at = program.len();
+                    program.push(Inst::Match);
+                    compile_node(node, program);
+                    program.push(Inst::Jump(at));
+                    let end = program.len();
+                    split(program, at, at + 1, end);
+                }
+            }
+        }
+    }
+}
+
+// The threads of a Pike VM at one index in the text, in order of priority.
+struct Threads {
+    list: Vec<(usize, Vec<Option<usize>>)>,
+    // The generation in which each instruction was last reached, so clearing doesn't have to
+    // touch every instruction.
+    visited: Vec<usize>,
+    generation: usize,
+}
+
+impl Threads {
+    fn new(program_len: usize) -> Self {
+        Self {
+            list: Vec::new(),
+            visited: vec![0; program_len],
+            generation: 1,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.list.clear();
+        self.generation += 1;
+    }
+
+    // Marks `pc` as reached, and returns whether it wasn't before.
+    fn visit(&mut self, pc: usize) -> bool {
+        if self.visited[pc] == self.generation {
+            return false;
+        }
+        self.visited[pc] = self.generation;
+        true
+    }
+}
+
+fn is_at_word_boundary(text: &str, index: usize) -> bool {
+    let is_word_before = text[..index]
+        .chars()
+        .next_back()
+        .is_some_and(is_word_char);
+    let is_word_after = text[index..].chars().next().is_some_and(is_word_char);
+    is_word_before != is_word_after
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(pattern: &str, text: &str) -> Option<Range<usize>> {
+        Some(Regex::new(pattern, false).unwrap().find_at(text, 0)?.range)
+    }
+
+    #[test]
+    fn finds_leftmost_first_match() {
+        assert_eq!(find("abc", "xxabcabc"), Some(2..5));
+        assert_eq!(find("a|ab", "ab"), Some(0..1));
+        assert_eq!(find("ab|a", "ab"), Some(0..2));
+        assert_eq!(find("a+", "baaa"), Some(1..4));
+        assert_eq!(find("a+?", "baaa"), Some(1..2));
+        assert_eq!(find("a{2,3}", "aaaa"), Some(0..3));
+        assert_eq!(find("a{2,3}?", "aaaa"), Some(0..2));
+        assert_eq!(find("a{2}", "a a"), None);
+        assert_eq!(find("x*", "abc"), Some(0..0));
+        assert_eq!(find("(a*)*b", "aab"), Some(0..3));
+        assert_eq!(find("<.*?>", "<a><b>"), Some(0..3));
+        assert_eq!(find("é+", "aéé"), Some(1..5));
+    }
+
+    #[test]
+    fn matches_classes_anchors_and_boundaries() {
+        assert_eq!(find("[a-c]+", "xxbcay"), Some(2..5));
+        assert_eq!(find("[^a-c]+", "abxyc"), Some(2..4));
+        assert_eq!(find(r"\d+", "ab123"), Some(2..5));
+        assert_eq!(find(r"\bfoo\b", "foobar foo"), Some(7..10));
+        assert_eq!(find(r"\Boo", "foo"), Some(1..3));
+        assert_eq!(find("^b", "ab\nb"), Some(3..4));
+        assert_eq!(find("a$", "ab\na"), Some(3..4));
+        assert_eq!(find("a.b", "a\nb"), None);
+        assert_eq!(find(r"a\nb", "a\nb"), Some(0..3));
+        assert_eq!(
+            Regex::new("HELLO", true).unwrap().find_at("say hello", 0).unwrap().range,
+            4..9
+        );
+        assert_eq!(Regex::literal("a.b", false).find_at("axb a.b", 0).unwrap().range, 4..7);
+    }
+
+    #[test]
+    fn captures_groups() {
+        let m = Regex::new("(a+)(?:-)(b+)?(c)?", false)
+            .unwrap()
+            .find_at("xaa-bb", 0)
+            .unwrap();
+        assert_eq!(m.group(0), Some(1..6));
+        assert_eq!(m.group(1), Some(1..3));
+        assert_eq!(m.group(2), Some(4..6));
+        assert_eq!(m.group(3), None);
+        let m = Regex::new("(a|b)*", false).unwrap().find_at("abba", 0).unwrap();
+        assert_eq!(m.group(1), Some(3..4));
+    }
+
+    #[test]
+    fn finds_after_start() {
+        let regex = Regex::new("a", false).unwrap();
+        assert_eq!(regex.find_at("aba", 1).unwrap().range, 2..3);
+        assert_eq!(regex.find_at("aba", 3), None);
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        for pattern in [
+            "(", ")", "(a", "[a", "*", "a**", "a{3,2}", "^*", "(?=a)", "[z-a]", r"\",
+            "(a{1000}){1000}",
+        ] {
+            assert!(Regex::new(pattern, false).is_err(), "{}", pattern);
+        }
+        // A brace that doesn't start a valid count is a literal.
+        assert_eq!(find("a{x}", "a{x}"), Some(0..4));
+    }
+
+    #[test]
+    fn matches_long_text_without_recursing() {
+        let text = "ab\n".repeat(50_000);
+        assert_eq!(find(r"(?:.|\n)*", &text), Some(0..text.len()));
+        assert_eq!(find(r"(.|\n)*$", &text), Some(0..text.len()));
+    }
+
+    #[test]
+    fn matches_pathological_patterns_in_linear_time() {
+        let text = "a".repeat(10_000);
+        assert_eq!(find("(a|a)*b", &text), None);
+        assert_eq!(find("(a*)*b", &text), None);
+        assert_eq!(find("(?:a?){30}a{30}", &"a".repeat(30)), Some(0..30));
+        let text = format!("{}b", text);
+        assert_eq!(find("(a|a)*b", &text), Some(0..text.len()));
+    }
+}
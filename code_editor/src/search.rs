@@ -0,0 +1,276 @@
+use {
+    crate::{
+        regex::{self, Regex, RegexError},
+        text::{Lines, Position},
+    },
+    std::ops::Range,
+};
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct SearchOptions {
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    pub regex: bool,
+}
+
+/// A compiled search query.
+///
+/// Matches are found in the text with its lines joined by `'\n'`, so a regex can match across
+/// lines. Matches that are empty are skipped.
+#[derive(Clone, Debug)]
+pub struct Search {
+    query: String,
+    options: SearchOptions,
+    regex: Regex,
+}
+
+impl Search {
+    pub fn new(query: &str, options: SearchOptions) -> Result<Self, RegexError> {
+        let regex = if options.regex {
+            Regex::new(query, !options.case_sensitive)?
+        } else {
+            Regex::literal(query, !options.case_sensitive)
+        };
+        Ok(Self {
+            query: query.to_string(),
+            options,
+            regex,
+        })
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn options(&self) -> SearchOptions {
+        self.options
+    }
+
+    /// Returns the start and end of every match, in order.
+    pub fn find_all(&self, lines: &Lines) -> Vec<(Position, Position)> {
+        let text = lines.join("\n");
+        let mut offsets = Offsets::new(lines);
+        self.matches(&text)
+            .map(|m| {
+                (
+                    offsets.position_at(m.range.start),
+                    offsets.position_at(m.range.end),
+                )
+            })
+            .collect()
+    }
+
+    /// Returns the start and end of every match, in order, together with the text that replaces
+    /// it. For a regex search, `$n` and `${n}` in `replace` are replaced with the text of group
+    /// `n`, and `$$` with a `$`.
+    pub fn find_all_with_replacements(
+        &self,
+        lines: &Lines,
+        replace: &str,
+    ) -> Vec<(Position, Position, String)> {
+        let text = lines.join("\n");
+        let mut offsets = Offsets::new(lines);
+        self.matches(&text)
+            .map(|m| {
+                let replacement = if self.options.regex {
+                    expand_replacement(&text, &m, replace)
+                } else {
+                    replace.to_string()
+                };
+                (
+                    offsets.position_at(m.range.start),
+                    offsets.position_at(m.range.end),
+                    replacement,
+                )
+            })
+            .collect()
+    }
+
+    fn matches<'a>(&'a self, text: &'a str) -> impl Iterator<Item = regex::Match> + 'a {
+        let mut start = 0;
+        std::iter::from_fn(move || {
+            while start <= text.len() {
+                let m = self.regex.find_at(text, start)?;
+                if m.range.is_empty() || self.options.whole_word && !is_whole_word(text, &m.range)
+                {
+                    // Try again one char after the start of this match.
+                    start = m.range.start + text[m.range.start..].chars().next()?.len_utf8();
+                    continue;
+                }
+                start = m.range.end;
+                return Some(m);
+            }
+            None
+        })
+    }
+}
+
+fn is_whole_word(text: &str, range: &Range<usize>) -> bool {
+    !text[..range.start]
+        .chars()
+        .next_back()
+        .is_some_and(regex::is_word_char)
+        && !text[range.end..]
+            .chars()
+            .next()
+            .is_some_and(regex::is_word_char)
+}
+
+fn expand_replacement(text: &str, m: &regex::Match, replace: &str) -> String {
+    let mut string = String::new();
+    let mut chars = replace.chars().peekable();
+    while let Some(char) = chars.next() {
+        if char != '$' {
+            string.push(char);
+            continue;
+        }
+        let is_braced = chars.peek() == Some(&'{');
+        let mut digits = String::new();
+        let mut lookahead = chars.clone();
+        if is_braced {
+            lookahead.next();
+        }
+        while let Some(&digit) = lookahead.peek().filter(|char| char.is_ascii_digit()) {
+            digits.push(digit);
+            lookahead.next();
+        }
+        if is_braced && lookahead.next() != Some('}') {
+            digits.clear();
+        }
+        match digits.parse::<usize>() {
+            Ok(index) => {
+                chars = lookahead;
+                if let Some(range) = m.group(index) {
+                    string.push_str(&text[range]);
+                }
+            }
+            Err(_) => {
+                if chars.peek() == Some(&'$') {
+                    chars.next();
+                }
+                string.push('$');
+            }
+        }
+    }
+    string
+}
+
+// Turns byte offsets in the joined text into positions. Offsets have to be passed in increasing
+// order.
+struct Offsets<'a> {
+    lines: &'a Lines,
+    line_index: usize,
+    line_start: usize,
+}
+
+impl<'a> Offsets<'a> {
+    fn new(lines: &'a Lines) -> Self {
+        Self {
+            lines,
+            line_index: 0,
+            line_start: 0,
+        }
+    }
+
+    fn position_at(&mut self, offset: usize) -> Position {
+        while offset > self.line_start + self.lines[self.line_index].len() {
+            self.line_start += self.lines[self.line_index].len() + 1;
+            self.line_index += 1;
+        }
+        Position {
+            line_index: self.line_index,
+            byte_index: offset - self.line_start,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::text::Text};
+
+    fn position(line_index: usize, byte_index: usize) -> Position {
+        Position {
+            line_index,
+            byte_index,
+        }
+    }
+
+    #[test]
+    fn finds_matches_across_lines() {
+        let text = Text::from("foo bar\nbar foo\nfoo");
+        let search = Search::new("foo", SearchOptions::default()).unwrap();
+        assert_eq!(
+            search.find_all(text.as_lines()),
+            vec![
+                (position(0, 0), position(0, 3)),
+                (position(1, 4), position(1, 7)),
+                (position(2, 0), position(2, 3)),
+            ]
+        );
+        let options = SearchOptions {
+            regex: true,
+            ..SearchOptions::default()
+        };
+        let search = Search::new(r"r\nb", options).unwrap();
+        assert_eq!(
+            search.find_all(text.as_lines()),
+            vec![(position(0, 6), position(1, 1))]
+        );
+    }
+
+    #[test]
+    fn respects_options() {
+        let text = Text::from("Foo foobar foo");
+        let search = Search::new("foo", SearchOptions::default()).unwrap();
+        assert_eq!(search.find_all(text.as_lines()).len(), 3);
+        let options = SearchOptions {
+            case_sensitive: true,
+            whole_word: true,
+            regex: false,
+        };
+        let search = Search::new("foo", options).unwrap();
+        assert_eq!(
+            search.find_all(text.as_lines()),
+            vec![(position(0, 11), position(0, 14))]
+        );
+        // A literal search doesn't treat special chars as a pattern.
+        let search = Search::new("(", SearchOptions::default()).unwrap();
+        assert_eq!(search.find_all(Text::from("a(b").as_lines()).len(), 1);
+        let options = SearchOptions {
+            regex: true,
+            ..SearchOptions::default()
+        };
+        assert!(Search::new("(", options).is_err());
+    }
+
+    #[test]
+    fn skips_empty_matches() {
+        let options = SearchOptions {
+            regex: true,
+            ..SearchOptions::default()
+        };
+        let search = Search::new("x*", options).unwrap();
+        assert_eq!(
+            search.find_all(Text::from("axxbx").as_lines()),
+            vec![(position(0, 1), position(0, 3)), (position(0, 4), position(0, 5))]
+        );
+    }
+
+    #[test]
+    fn expands_replacements() {
+        let options = SearchOptions {
+            regex: true,
+            ..SearchOptions::default()
+        };
+        let search = Search::new(r"(\w+)@(\w+)", options).unwrap();
+        let replacements = search.find_all_with_replacements(
+            Text::from("me@home").as_lines(),
+            "$2 ${1}x $$ $9 $",
+        );
+        assert_eq!(replacements[0].2, "home mex $  $");
+        let search = Search::new("$1", SearchOptions::default()).unwrap();
+        let replacements =
+            search.find_all_with_replacements(Text::from("a$1").as_lines(), "$0");
+        assert_eq!(replacements[0].2, "$0");
+    }
+}
@@ -2,7 +2,7 @@ use {
     crate::{
         layout::Layout,
         str::StrExt,
-        text::{Edit, Length, Position},
+        text::{Edit, Length, Lines, Position},
     },
     std::{ops, ops::Deref, slice::Iter},
 };
@@ -265,7 +265,7 @@ impl Cursor {
         self.position.byte_index == 0
     }
 
-    pub fn is_at_end_of_line(self, lines: &[String]) -> bool {
+    pub fn is_at_end_of_line(self, lines: &Lines) -> bool {
         self.position.byte_index == lines[self.position.line_index].len()
     }
 
@@ -282,7 +282,7 @@ impl Cursor {
         row == line.row_count() - 1
     }
 
-    pub fn move_left(self, lines: &[String]) -> Self {
+    pub fn move_left(self, lines: &Lines) -> Self {
         if !self.is_at_start_of_line() {
             return self.move_to_prev_grapheme(lines);
         }
@@ -292,7 +292,7 @@ impl Cursor {
         self
     }
 
-    pub fn move_right(self, lines: &[String]) -> Self {
+    pub fn move_right(self, lines: &Lines) -> Self {
         if !self.is_at_end_of_line(lines) {
             return self.move_to_next_grapheme(lines);
         }
@@ -322,7 +322,7 @@ impl Cursor {
         self.move_to_end_of_line(layout.as_text().as_lines())
     }
 
-    pub fn home(self, lines: &[String]) -> Self {
+    pub fn home(self, lines: &Lines) -> Self {
         if !self.is_at_start_of_line() {
             let indent_len = lines[self.position.line_index].indent().unwrap_or("").len();
             if self.position.byte_index <= indent_len {
@@ -341,7 +341,7 @@ impl Cursor {
         self
     }
 
-    pub fn end(self, lines: &[String]) -> Self {
+    pub fn end(self, lines: &Lines) -> Self {
         if !self.is_at_end_of_line(lines) {
             let indent_len = lines[self.position.line_index].indent().unwrap_or("").len();
             if self.position.byte_index >= indent_len {
@@ -360,7 +360,7 @@ impl Cursor {
         self
     }
 
-    pub fn move_to_end_of_line(self, lines: &[String]) -> Self {
+    pub fn move_to_end_of_line(self, lines: &Lines) -> Self {
         let mut me = self.clone();
         while !me.is_at_end_of_line(lines) {
             me = me.move_to_next_grapheme(lines);
@@ -390,7 +390,7 @@ impl Cursor {
         }
     }
 
-    pub fn move_to_file_end(self, lines: &[String]) -> Self {
+    pub fn move_to_file_end(self, lines: &Lines) -> Self {
         Self {
             position: Position {
                 line_index: lines.len() - 1,
@@ -401,7 +401,49 @@ impl Cursor {
         }
     }
 
-    pub fn move_to_prev_grapheme(self, lines: &[String]) -> Self {
+    /// Moves to the start of the word before the cursor, skipping any whitespace in between, or
+    /// to the end of the previous line if the cursor is at the start of its line.
+    pub fn move_to_prev_word(self, lines: &Lines, word_separators: &[char]) -> Self {
+        if self.is_at_start_of_line() {
+            return if self.is_at_first_line() {
+                self
+            } else {
+                self.move_to_end_of_prev_line(lines)
+            };
+        }
+        Self {
+            position: Position {
+                line_index: self.position.line_index,
+                byte_index: lines[self.position.line_index]
+                    .find_prev_word_start(self.position.byte_index, word_separators),
+            },
+            affinity: Affinity::After,
+            preferred_column_index: None,
+        }
+    }
+
+    /// Moves to the end of the word after the cursor, skipping any whitespace in between, or to
+    /// the start of the next line if the cursor is at the end of its line.
+    pub fn move_to_next_word(self, lines: &Lines, word_separators: &[char]) -> Self {
+        if self.is_at_end_of_line(lines) {
+            return if self.is_at_last_line(lines.len()) {
+                self
+            } else {
+                self.move_to_start_of_next_line()
+            };
+        }
+        Self {
+            position: Position {
+                line_index: self.position.line_index,
+                byte_index: lines[self.position.line_index]
+                    .find_next_word_end(self.position.byte_index, word_separators),
+            },
+            affinity: Affinity::Before,
+            preferred_column_index: None,
+        }
+    }
+
+    pub fn move_to_prev_grapheme(self, lines: &Lines) -> Self {
         Self {
             position: Position {
                 line_index: self.position.line_index,
@@ -416,7 +458,7 @@ impl Cursor {
         }
     }
 
-    pub fn move_to_next_grapheme(self, lines: &[String]) -> Self {
+    pub fn move_to_next_grapheme(self, lines: &Lines) -> Self {
         let line = &lines[self.position.line_index];
         Self {
             position: Position {
@@ -432,7 +474,7 @@ impl Cursor {
         }
     }
 
-    pub fn move_to_end_of_prev_line(self, lines: &[String]) -> Self {
+    pub fn move_to_end_of_prev_line(self, lines: &Lines) -> Self {
         let prev_line_index = self.position.line_index - 1;
         Self {
             position: Position {
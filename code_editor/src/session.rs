@@ -1,12 +1,15 @@
 use {
     crate::{
         char::CharExt,
-        document::Document,
+        decoration::{Decoration, DecorationSet, DecorationType, QuickFix},
+        document::{Document, Editor},
         history::EditKind,
         layout::{BlockElement, Layout, WrappedElement},
+        regex::RegexError,
+        search::{Search, SearchOptions},
         selection::{Affinity, Cursor, SelectionSet},
         str::StrExt,
-        text::{Change, Drift, Edit, Length, Position, Text},
+        text::{Change, Drift, Edit, Length, Lines, Position, Text},
         wrap,
         wrap::WrapData,
         Selection, Settings,
@@ -30,6 +33,7 @@ pub struct Session {
     selection_state: RefCell<SelectionState>,
     wrap_column: Cell<Option<usize>>,
     fold_state: RefCell<FoldState>,
+    search_state: RefCell<SearchState>,
     edit_receiver: Receiver<(Option<SelectionSet>, Vec<Edit>)>,
 }
 
@@ -63,11 +67,12 @@ impl Session {
                 folded_lines: HashSet::new(),
                 unfolding_lines: HashSet::new(),
             }),
+            search_state: RefCell::new(SearchState {
+                search: None,
+                matches: DecorationSet::new(),
+            }),
             edit_receiver,
         };
-        for line in 0..line_count {
-            session.update_wrap_data(line);
-        }
         session.update_y();
         session.document.add_session(session.id, edit_sender);
         session
@@ -113,14 +118,50 @@ impl Session {
         })
     }
 
+    pub fn search(&self) -> Ref<'_, Option<Search>> {
+        Ref::map(self.search_state.borrow(), |search_state| &search_state.search)
+    }
+
+    /// The matches of the current search, as decorations of type `DecorationType::SearchMatch`,
+    /// with their index as id.
+    pub fn search_matches(&self) -> Ref<'_, [Decoration]> {
+        Ref::map(self.search_state.borrow(), |search_state| {
+            search_state.matches.as_decorations()
+        })
+    }
+
+    /// Returns the index of the match that is selected by the last added selection, if any.
+    pub fn current_search_match_index(&self) -> Option<usize> {
+        let selection = self.selections()[self.last_added_selection_index()?];
+        self.search_matches()
+            .iter()
+            .position(|m| m.start() == selection.start() && m.end() == selection.end())
+    }
+
     pub fn set_wrap_column(&self, wrap_column: Option<usize>) {
         if self.wrap_column.get() == wrap_column {
             return;
         }
         self.wrap_column.set(wrap_column);
-        let line_count = self.document.as_text().as_lines().len();
-        for line in 0..line_count {
-            self.update_wrap_data(line);
+        let mut layout = self.layout.borrow_mut();
+        layout.y.clear();
+        layout.column_count.fill(None);
+        layout.wrap_data.fill(None);
+        drop(layout);
+        self.update_y();
+    }
+
+    /// Lays out the lines in the given range, if they weren't already. Lines are laid out
+    /// lazily, so that opening or editing a large document only costs as much as the lines that
+    /// are actually drawn. Until a line is laid out, it counts as a single row that is not
+    /// coloured yet.
+    pub fn layout_lines(&self, start: usize, end: usize) {
+        let end = end.min(self.document.as_text().as_lines().len());
+        self.document.layout_lines(end);
+        for line in start..end {
+            if self.layout.borrow().wrap_data[line].is_none() {
+                self.update_wrap_data(line);
+            }
         }
         self.update_y();
     }
@@ -128,6 +169,7 @@ impl Session {
     pub fn fold(&self) {
         let mut fold_state = self.fold_state.borrow_mut();
         let line_count = self.document().as_text().as_lines().len();
+        self.document.layout_lines(line_count);
         for line_index in 0..line_count {
             let layout = self.layout();
             let line = layout.line(line_index);
@@ -295,6 +337,40 @@ impl Session {
         });
     }
 
+    pub fn move_to_prev_word(&self, reset_anchor: bool) {
+        self.modify_selections(reset_anchor, |selection, layout| {
+            selection.update_cursor(|cursor| {
+                cursor
+                    .move_to_prev_word(layout.as_text().as_lines(), &self.settings.word_separators)
+            })
+        });
+    }
+
+    pub fn move_to_next_word(&self, reset_anchor: bool) {
+        self.modify_selections(reset_anchor, |selection, layout| {
+            selection.update_cursor(|cursor| {
+                cursor
+                    .move_to_next_word(layout.as_text().as_lines(), &self.settings.word_separators)
+            })
+        });
+    }
+
+    pub fn move_to_file_start(&self, reset_anchor: bool) {
+        self.modify_selections(reset_anchor, |selection, _| {
+            selection.update_cursor(|cursor| cursor.move_to_file_start())
+        });
+    }
+
+    pub fn move_to_file_end(&self, reset_anchor: bool) {
+        self.modify_selections(reset_anchor, |selection, layout| {
+            selection.update_cursor(|cursor| cursor.move_to_file_end(layout.as_text().as_lines()))
+        });
+    }
+
+    pub fn select_all(&self) {
+        self.set_selection(Position::zero(), Affinity::Before, SelectionMode::All);
+    }
+
     pub fn insert(&self, text: Text) {
 
         let mut edit_kind = EditKind::Insert;
@@ -304,48 +380,48 @@ impl Session {
             let mut selection_state = self.selection_state.borrow_mut();
             if char == ' ' {
                 edit_kind = EditKind::InsertSpace;
-            } else if char == '"' || char.is_opening_delimiter() {
+            } else if selection_state
+                .injected_char_stack
+                .last()
+                .map_or(false, |&last_char| last_char == char)
+            {
+                // We are inserting a single character that we automatically injected earlier, so we need
+                // to uninject it before inserting it again.
+                uninject_char = Some(selection_state.injected_char_stack.pop().unwrap());
+            } else if char.is_quote() || char.is_opening_delimiter() {
                 if selection_state
                     .selections
                     .iter()
                     .all(|selection| !selection.is_empty())
                     || selection_state.selections.iter().all(|selection| {
+                        let text = self.document.as_text();
+                        let (before, after) = text.as_lines()[selection.cursor.position.line_index]
+                            .split_at(selection.cursor.position.byte_index);
                         selection.is_empty()
-                            && match self.document.as_text().as_lines()
-                                [selection.cursor.position.line_index]
-                                [selection.cursor.position.byte_index..]
-                                .chars()
-                                .next()
-                            {
-                                Some(char) => {
-                                    char == '"'
-                                        || char.is_closing_delimiter()
-                                        || char.is_whitespace()
-                                }
-                                None => true,
-                            }
+                            && after.chars().next().map_or(true, |next_char| {
+                                next_char.is_quote()
+                                    || next_char.is_closing_delimiter()
+                                    || next_char.is_whitespace()
+                            })
+                            && !(char.is_quote()
+                                && before.chars().next_back().map_or(false, |prev_char| {
+                                    prev_char.is_alphanumeric() || prev_char == '_'
+                                }))
                     })
                 {
-                    // We are inserting either a string or opening delimiter, and either all
+                    // We are inserting either a quote or opening delimiter, and either all
                     // selections are non-empty, or all selections are empty and followed by either
-                    // a string or closing delimiter or whitespace. In this case, we automatically
-                    // inject the corresponding string or closing delimiter.
-                    let opposite_char = if char == '"' {
-                        '"'
+                    // a quote or closing delimiter or whitespace. In this case, we automatically
+                    // inject the corresponding quote or closing delimiter. Quotes are not injected
+                    // right after a word, where they are more likely to close a string.
+                    let opposite_char = if char.is_quote() {
+                        char
                     } else {
                         char.opposite_delimiter().unwrap()
                     };
                     inject_char = Some(opposite_char);
                     selection_state.injected_char_stack.push(opposite_char);
                 }
-            } else if selection_state
-                .injected_char_stack
-                .last()
-                .map_or(false, |&last_char| last_char == char)
-            {
-                // We are inserting a single character that we automatically injected earlier, so we need
-                // to uninject it before inserting it again.
-                uninject_char = Some(selection_state.injected_char_stack.pop().unwrap());
             }
             drop(selection_state);
         }
@@ -399,6 +475,42 @@ impl Session {
         );
     }
 
+    /// Replaces the identifier before every cursor, and the selection after it, with the given
+    /// completion.
+    pub fn complete(&self, text: Text) {
+        self.selection_state
+            .borrow_mut()
+            .injected_char_stack
+            .clear();
+        self.document.edit_selections(
+            self.id,
+            EditKind::Other,
+            &self.selection_state.borrow().selections,
+            &self.settings,
+            |mut editor, position, length| {
+                let start = Position {
+                    line_index: position.line_index,
+                    byte_index: editor.as_text().as_lines()[position.line_index]
+                        .find_identifier_start(position.byte_index),
+                };
+                editor.apply_edit(Edit {
+                    change: Change::Delete(
+                        start,
+                        Length {
+                            line_count: 0,
+                            byte_count: position.byte_index - start.byte_index,
+                        } + length,
+                    ),
+                    drift: Drift::Before,
+                });
+                editor.apply_edit(Edit {
+                    change: Change::Insert(start, text.clone()),
+                    drift: Drift::Before,
+                });
+            },
+        );
+    }
+
     pub fn paste(&self, text: Text) {
         self.document.edit_selections(
             self.id,
@@ -553,8 +665,11 @@ impl Session {
                     } else {
                         // There is at least one non-whitespace character before the cursor on the
                         // current line, so delete forward by a single grapheme.
-                        let byte_count =
-                            lines[position.line_index].graphemes().next().unwrap().len();
+                        let byte_count = lines[position.line_index][position.byte_index..]
+                            .graphemes()
+                            .next()
+                            .unwrap()
+                            .len();
                         editor.apply_edit(Edit {
                             change: Change::Delete(
                                 position,
@@ -578,10 +693,10 @@ impl Session {
     }
 
     pub fn backspace(&self) {
-        self.selection_state
-            .borrow_mut()
-            .injected_char_stack
-            .clear();
+        let mut selection_state = self.selection_state.borrow_mut();
+        let injected_char = selection_state.injected_char_stack.last().copied();
+        selection_state.injected_char_stack.clear();
+        drop(selection_state);
         self.document.edit_selections(
             self.id,
             EditKind::Delete,
@@ -658,16 +773,29 @@ impl Session {
                     } else {
                         // There is at least one non-whitespace character before the cursor on the
                         // current line, so delete backwards by a single grapheme.
-                        let byte_count = lines[position.line_index]
-                            .graphemes()
-                            .next_back()
-                            .unwrap()
-                            .len();
+                        let line = &lines[position.line_index];
+                        let grapheme = line[..position.byte_index].graphemes().next_back().unwrap();
+                        let start_byte_index = position.byte_index - grapheme.len();
+                        let mut byte_count = grapheme.len();
+                        if let Some(injected_char) = injected_char {
+                            // If the grapheme opens a pair of which we injected the closing
+                            // character right after the cursor, delete that as well.
+                            let opens_injected_pair =
+                                grapheme.chars().next().map_or(false, |char| {
+                                    char.is_quote() && char == injected_char
+                                        || char.opposite_delimiter() == Some(injected_char)
+                                });
+                            if opens_injected_pair
+                                && line[position.byte_index..].starts_with(injected_char)
+                            {
+                                byte_count += injected_char.len_utf8();
+                            }
+                        }
                         editor.apply_edit(Edit {
                             change: Change::Delete(
                                 Position {
                                     line_index: position.line_index,
-                                    byte_index: position.byte_index - byte_count,
+                                    byte_index: start_byte_index,
                                 },
                                 Length {
                                     line_count: 0,
@@ -688,6 +816,332 @@ impl Session {
         );
     }
 
+    /// Deletes the selections, or for the selections that are empty, everything from the start of
+    /// the word before the cursor up to the cursor.
+    pub fn delete_word_backward(&self) {
+        self.selection_state
+            .borrow_mut()
+            .injected_char_stack
+            .clear();
+        self.document.edit_selections(
+            self.id,
+            EditKind::Other,
+            &self.selection_state.borrow().selections,
+            &self.settings,
+            |mut editor, position, length| {
+                let start = if length == Length::zero() {
+                    Cursor::from(position)
+                        .move_to_prev_word(
+                            editor.as_text().as_lines(),
+                            &self.settings.word_separators,
+                        )
+                        .position
+                } else {
+                    position
+                };
+                editor.apply_edit(Edit {
+                    change: Change::Delete(start, position + length - start),
+                    drift: Drift::Before,
+                });
+            },
+        );
+    }
+
+    /// Deletes the selections, or for the selections that are empty, everything from the cursor up
+    /// to the end of the word after it.
+    pub fn delete_word_forward(&self) {
+        self.selection_state
+            .borrow_mut()
+            .injected_char_stack
+            .clear();
+        self.document.edit_selections(
+            self.id,
+            EditKind::Other,
+            &self.selection_state.borrow().selections,
+            &self.settings,
+            |mut editor, position, length| {
+                let end = if length == Length::zero() {
+                    Cursor::from(position)
+                        .move_to_next_word(
+                            editor.as_text().as_lines(),
+                            &self.settings.word_separators,
+                        )
+                        .position
+                } else {
+                    position + length
+                };
+                editor.apply_edit(Edit {
+                    change: Change::Delete(position, end - position),
+                    drift: Drift::Before,
+                });
+            },
+        );
+    }
+
+    /// Inserts a copy of the lines that the selections span above them, so that the selections
+    /// end up on the copy below.
+    pub fn duplicate_lines(&self) {
+        self.selection_state
+            .borrow_mut()
+            .injected_char_stack
+            .clear();
+        self.document.edit_line_ranges(
+            self.id,
+            EditKind::Other,
+            &self.selection_state.borrow().selections,
+            |mut editor, line_range| {
+                let start = Position {
+                    line_index: line_range.start,
+                    byte_index: 0,
+                };
+                let text = editor
+                    .as_text()
+                    .as_lines()
+                    .range(line_range)
+                    .map(|line| format!("{}\n", line))
+                    .collect::<String>();
+                editor.apply_edit(Edit {
+                    change: Change::Insert(start, text.into()),
+                    drift: Drift::Before,
+                });
+            },
+        );
+    }
+
+    /// Moves the lines that the selections span up by one line, unless that would move the first
+    /// line.
+    pub fn move_lines_up(&self) {
+        let mut selection_state = self.selection_state.borrow_mut();
+        selection_state.injected_char_stack.clear();
+        if selection_state.selections[0].line_range().start == 0 {
+            return;
+        }
+        drop(selection_state);
+        self.document.edit_line_ranges(
+            self.id,
+            EditKind::Other,
+            &self.selection_state.borrow().selections,
+            |mut editor, line_range| {
+                let lines = editor.as_text().as_lines();
+                let line_count = lines.len();
+                let prev_line = lines[line_range.start - 1].to_string();
+                editor.apply_edit(Edit {
+                    change: Change::Delete(
+                        Position {
+                            line_index: line_range.start - 1,
+                            byte_index: 0,
+                        },
+                        Length {
+                            line_count: 1,
+                            byte_count: 0,
+                        },
+                    ),
+                    drift: Drift::Before,
+                });
+                if line_range.end < line_count {
+                    // Insert the previous line above the line after the range.
+                    editor.apply_edit(Edit {
+                        change: Change::Insert(
+                            Position {
+                                line_index: line_range.end - 1,
+                                byte_index: 0,
+                            },
+                            format!("{}\n", prev_line).into(),
+                        ),
+                        drift: Drift::Before,
+                    });
+                } else {
+                    // The range ends at the last line, so insert the previous line after it.
+                    let last_line_index = line_range.end - 2;
+                    let byte_index = editor.as_text().as_lines()[last_line_index].len();
+                    editor.apply_edit(Edit {
+                        change: Change::Insert(
+                            Position {
+                                line_index: last_line_index,
+                                byte_index,
+                            },
+                            format!("\n{}", prev_line).into(),
+                        ),
+                        drift: Drift::After,
+                    });
+                }
+            },
+        );
+    }
+
+    /// Moves the lines that the selections span down by one line, unless that would move the last
+    /// line.
+    pub fn move_lines_down(&self) {
+        let mut selection_state = self.selection_state.borrow_mut();
+        selection_state.injected_char_stack.clear();
+        if selection_state.selections.last().unwrap().line_range().end
+            >= self.document.as_text().as_lines().len()
+        {
+            return;
+        }
+        drop(selection_state);
+        self.document.edit_line_ranges(
+            self.id,
+            EditKind::Other,
+            &self.selection_state.borrow().selections,
+            |mut editor, line_range| {
+                let lines = editor.as_text().as_lines();
+                let next_line = lines[line_range.end].to_string();
+                let last_line_index = line_range.end - 1;
+                let byte_index = lines[last_line_index].len();
+                editor.apply_edit(Edit {
+                    change: Change::Delete(
+                        Position {
+                            line_index: last_line_index,
+                            byte_index,
+                        },
+                        Length {
+                            line_count: 1,
+                            byte_count: next_line.len(),
+                        },
+                    ),
+                    drift: Drift::Before,
+                });
+                editor.apply_edit(Edit {
+                    change: Change::Insert(
+                        Position {
+                            line_index: line_range.start,
+                            byte_index: 0,
+                        },
+                        format!("{}\n", next_line).into(),
+                    ),
+                    drift: Drift::Before,
+                });
+            },
+        );
+    }
+
+    /// Joins the lines that the selections span into a single line, or the line of an empty
+    /// selection with the line after it. The indentation of the joined lines is replaced with a
+    /// single space.
+    pub fn join_lines(&self) {
+        self.selection_state
+            .borrow_mut()
+            .injected_char_stack
+            .clear();
+        self.document.edit_line_ranges(
+            self.id,
+            EditKind::Other,
+            &self.selection_state.borrow().selections,
+            |mut editor, line_range| {
+                let end = line_range
+                    .end
+                    .max(line_range.start + 2)
+                    .min(editor.as_text().as_lines().len());
+                // Join from the bottom up, so that the lines that are still to be joined keep
+                // their index.
+                for line_index in (line_range.start..end.saturating_sub(1)).rev() {
+                    let lines = editor.as_text().as_lines();
+                    let line = lines[line_index].trim_end();
+                    let next_line = &lines[line_index + 1];
+                    let byte_index = line.len();
+                    let byte_count = next_line.len() - next_line.trim_start().len();
+                    let insert_space = !line.is_empty() && !next_line.trim_start().is_empty();
+                    editor.apply_edit(Edit {
+                        change: Change::Delete(
+                            Position {
+                                line_index,
+                                byte_index,
+                            },
+                            Length {
+                                line_count: 1,
+                                byte_count,
+                            },
+                        ),
+                        drift: Drift::Before,
+                    });
+                    if insert_space {
+                        editor.apply_edit(Edit {
+                            change: Change::Insert(
+                                Position {
+                                    line_index,
+                                    byte_index,
+                                },
+                                Text::from(' '),
+                            ),
+                            drift: Drift::Before,
+                        });
+                    }
+                }
+            },
+        );
+    }
+
+    /// Comments out the lines that the selections span with the line comment token of the
+    /// language of the document, or uncomments them if every line that isn't blank is already
+    /// commented out. Does nothing if the language has no line comments.
+    pub fn toggle_line_comment(&self) {
+        let line_comment = match self.document.line_comment() {
+            Some(line_comment) => line_comment,
+            None => return,
+        };
+        self.selection_state
+            .borrow_mut()
+            .injected_char_stack
+            .clear();
+        let is_commented = {
+            let text = self.document.as_text();
+            let lines = text.as_lines();
+            self.selection_state
+                .borrow()
+                .selections
+                .iter()
+                .flat_map(|selection| lines.range(selection.line_range()))
+                .map(|line| line.trim_start())
+                .filter(|line| !line.is_empty())
+                .all(|line| line.starts_with(line_comment))
+        };
+        self.document.edit_linewise(
+            self.id,
+            EditKind::Other,
+            &self.selection_state.borrow().selections,
+            |mut editor, line_index| {
+                let line = &editor.as_text().as_lines()[line_index];
+                let indent_len = line.len() - line.trim_start().len();
+                if indent_len == line.len() {
+                    return;
+                }
+                if is_commented {
+                    let rest = &line[indent_len + line_comment.len()..];
+                    let byte_count = if rest.starts_with(' ') {
+                        line_comment.len() + 1
+                    } else {
+                        line_comment.len()
+                    };
+                    editor.apply_edit(Edit {
+                        change: Change::Delete(
+                            Position {
+                                line_index,
+                                byte_index: indent_len,
+                            },
+                            Length {
+                                line_count: 0,
+                                byte_count,
+                            },
+                        ),
+                        drift: Drift::Before,
+                    });
+                } else {
+                    editor.apply_edit(Edit {
+                        change: Change::Insert(
+                            Position {
+                                line_index,
+                                byte_index: indent_len,
+                            },
+                            format!("{} ", line_comment).into(),
+                        ),
+                        drift: Drift::Before,
+                    });
+                }
+            },
+        );
+    }
+
     pub fn indent(&self) {
         self.document.edit_linewise(
             self.id,
@@ -746,6 +1200,55 @@ impl Session {
         );
     }
 
+    /// Applies the replacements of a quick fix as a single undo group. Nothing is replaced if any
+    /// of them is out of bounds, which can happen when the fix was made for an older version of
+    /// the text.
+    pub fn apply_quick_fix(&self, quick_fix: &QuickFix) -> bool {
+        let mut replacements = quick_fix.replacements.clone();
+        replacements.sort_by_key(|replacement| replacement.start);
+        let text = self.document.as_text();
+        let is_in_bounds = |position: Position| {
+            text.as_lines()
+                .get(position.line_index)
+                .map_or(false, |line| line.is_char_boundary(position.byte_index))
+        };
+        if replacements.is_empty()
+            || !replacements.iter().all(|replacement| {
+                replacement.start <= replacement.end
+                    && is_in_bounds(replacement.start)
+                    && is_in_bounds(replacement.end)
+            })
+            || replacements
+                .windows(2)
+                .any(|replacements| replacements[0].end > replacements[1].start)
+        {
+            return false;
+        }
+        drop(text);
+        self.selection_state
+            .borrow_mut()
+            .injected_char_stack
+            .clear();
+        self.document.edit(
+            self.id,
+            EditKind::Other,
+            &self.selection_state.borrow().selections,
+            |mut editor| {
+                // Replace from the end, so that the positions of the replacements before are not
+                // affected.
+                for replacement in replacements.iter().rev() {
+                    replace_range(
+                        &mut editor,
+                        replacement.start,
+                        replacement.end - replacement.start,
+                        &Text::from(replacement.text.as_str()),
+                    );
+                }
+            },
+        );
+        true
+    }
+
     pub fn copy(&self) -> String {
         let mut string = String::new();
         for selection in &self.selection_state.borrow().selections {
@@ -761,6 +1264,216 @@ impl Session {
         string
     }
 
+    /// Searches the document for `query`, and returns the number of matches. The matches are
+    /// kept up to date as the document is edited, until the search is cleared. An empty query
+    /// clears the search.
+    pub fn set_search(&self, query: &str, options: SearchOptions) -> Result<usize, RegexError> {
+        if query.is_empty() {
+            self.clear_search();
+            return Ok(0);
+        }
+        self.search_state.borrow_mut().search = Some(Search::new(query, options)?);
+        self.update_search_matches();
+        Ok(self.search_state.borrow().matches.len())
+    }
+
+    pub fn clear_search(&self) {
+        let mut search_state = self.search_state.borrow_mut();
+        search_state.search = None;
+        search_state.matches.clear();
+    }
+
+    /// Selects the first match after the last added selection, wrapping around at the end of the
+    /// document.
+    pub fn find_next(&self) -> bool {
+        let position = self.selections()[self.last_added_selection_index().unwrap()].end();
+        self.find_next_from(position)
+    }
+
+    /// Selects the first match that starts at or after `position`, wrapping around at the end of
+    /// the document.
+    pub fn find_next_from(&self, position: Position) -> bool {
+        let matches = self.search_matches();
+        let next_match = matches
+            .iter()
+            .find(|m| m.start() >= position)
+            .or_else(|| matches.first())
+            .map(match_to_selection);
+        drop(matches);
+        match next_match {
+            Some(next_match) => {
+                self.select_search_match(next_match);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Selects the last match before the last added selection, wrapping around at the start of
+    /// the document.
+    pub fn find_prev(&self) -> bool {
+        let position = self.selections()[self.last_added_selection_index().unwrap()].start();
+        let matches = self.search_matches();
+        let prev_match = matches
+            .iter()
+            .rev()
+            .find(|m| m.end() <= position)
+            .or_else(|| matches.last())
+            .map(match_to_selection);
+        drop(matches);
+        match prev_match {
+            Some(prev_match) => {
+                self.select_search_match(prev_match);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replaces the selections with one selection for every match. The match at or after the
+    /// cursor becomes the last added selection.
+    pub fn select_all_matches(&self) -> bool {
+        let matches = self.search_matches();
+        if matches.is_empty() {
+            return false;
+        }
+        let mut selection_state = self.selection_state.borrow_mut();
+        let cursor_position = selection_state.selections
+            [selection_state.last_added_selection_index.unwrap()]
+        .cursor
+        .position;
+        let mut selections = SelectionSet::new();
+        let mut last_added_selection_index = None;
+        for (index, m) in matches.iter().enumerate() {
+            let selection = match_to_selection(m);
+            let selection_index = if index == 0 {
+                selections.set_selection(selection);
+                0
+            } else {
+                selections.add_selection(selection)
+            };
+            if last_added_selection_index.is_none() && m.end() >= cursor_position {
+                last_added_selection_index = Some(selection_index);
+            }
+        }
+        selection_state.mode = SelectionMode::Simple;
+        selection_state.selections = selections;
+        selection_state.last_added_selection_index = last_added_selection_index.or(Some(0));
+        selection_state.injected_char_stack.clear();
+        drop(selection_state);
+        drop(matches);
+        self.update_highlighted_delimiter_positions();
+        self.document().force_new_group();
+        true
+    }
+
+    /// Replaces the match that is selected by the last added selection, and selects the next
+    /// match. If no match is selected, only the next match is selected.
+    pub fn replace(&mut self, replace: &str) -> bool {
+        let search = match self.search().clone() {
+            Some(search) => search,
+            None => return false,
+        };
+        let selection = self.selections()[self.last_added_selection_index().unwrap()];
+        let replacement = search
+            .find_all_with_replacements(self.document.as_text().as_lines(), replace)
+            .into_iter()
+            .find(|&(start, end, _)| start == selection.start() && end == selection.end());
+        let (start, end, text) = match replacement {
+            Some(replacement) => replacement,
+            None => {
+                self.find_next();
+                return false;
+            }
+        };
+        let text = Text::from(text);
+        let mut selections = SelectionSet::new();
+        selections.set_selection(Selection {
+            cursor: Cursor::from(end),
+            anchor: start,
+        });
+        self.document.edit_selections(
+            self.id,
+            EditKind::Other,
+            &selections,
+            &self.settings,
+            |mut editor, position, length| {
+                replace_range(&mut editor, position, length, &text);
+            },
+        );
+        self.handle_changes();
+        self.find_next_from(start + text.length());
+        true
+    }
+
+    /// Replaces every match, as a single undo group. Returns the number of replaced matches.
+    pub fn replace_all(&mut self, replace: &str) -> usize {
+        let search = match self.search().clone() {
+            Some(search) => search,
+            None => return 0,
+        };
+        let replacements =
+            search.find_all_with_replacements(self.document.as_text().as_lines(), replace);
+        if replacements.is_empty() {
+            return 0;
+        }
+        let mut selections = SelectionSet::new();
+        for (index, &(start, end, _)) in replacements.iter().enumerate() {
+            let selection = Selection {
+                cursor: Cursor::from(end),
+                anchor: start,
+            };
+            if index == 0 {
+                selections.set_selection(selection);
+            } else {
+                selections.add_selection(selection);
+            }
+        }
+        let mut texts = replacements.iter().map(|(_, _, text)| Text::from(text));
+        self.document.edit_selections(
+            self.id,
+            EditKind::Other,
+            &selections,
+            &self.settings,
+            |mut editor, position, length| {
+                replace_range(&mut editor, position, length, &texts.next().unwrap());
+            },
+        );
+        self.handle_changes();
+        replacements.len()
+    }
+
+    fn select_search_match(&self, selection: Selection) {
+        let mut selection_state = self.selection_state.borrow_mut();
+        selection_state.mode = SelectionMode::Simple;
+        selection_state.selections.set_selection(selection);
+        selection_state.last_added_selection_index = Some(0);
+        selection_state.injected_char_stack.clear();
+        drop(selection_state);
+        self.update_highlighted_delimiter_positions();
+        self.document().force_new_group();
+    }
+
+    fn update_search_matches(&self) {
+        let mut search_state = self.search_state.borrow_mut();
+        let search_state = &mut *search_state;
+        search_state.matches.clear();
+        if let Some(search) = &search_state.search {
+            for (index, (start, end)) in search
+                .find_all(self.document.as_text().as_lines())
+                .into_iter()
+                .enumerate()
+            {
+                search_state.matches.add_decoration(Decoration::new(
+                    index,
+                    start,
+                    end,
+                    DecorationType::SearchMatch,
+                ));
+            }
+        }
+    }
+
     pub fn undo(&self) -> bool {
         self.selection_state
             .borrow_mut()
@@ -817,8 +1530,7 @@ impl Session {
         for edit in edits {
             match edit.change {
                 Change::Insert(point, ref text) => {
-                    self.layout.borrow_mut().column_count[point.line_index] = None;
-                    self.layout.borrow_mut().wrap_data[point.line_index] = None;
+                    self.clear_wrap_data(point.line_index);
                     let line_count = text.length().line_count;
                     if line_count > 0 {
                         let line = point.line_index + 1;
@@ -842,8 +1554,7 @@ impl Session {
                     }
                 }
                 Change::Delete(start, length) => {
-                    self.layout.borrow_mut().column_count[start.line_index] = None;
-                    self.layout.borrow_mut().wrap_data[start.line_index] = None;
+                    self.clear_wrap_data(start.line_index);
                     let line_count = length.line_count;
                     if line_count > 0 {
                         let start_line = start.line_index + 1;
@@ -866,12 +1577,6 @@ impl Session {
                 }
             }
         }
-        let line_count = self.document.as_text().as_lines().len();
-        for line in 0..line_count {
-            if self.layout.borrow().wrap_data[line].is_none() {
-                self.update_wrap_data(line);
-            }
-        }
         self.update_y();
         let mut selection_state = self.selection_state.borrow_mut();
         if let Some(selections) = selections {
@@ -886,6 +1591,9 @@ impl Session {
         }
         drop(selection_state);
         self.update_highlighted_delimiter_positions();
+        if self.search_state.borrow().search.is_some() {
+            self.update_search_matches();
+        }
     }
 
     fn update_y(&self) {
@@ -951,11 +1659,34 @@ impl Session {
             }
             None => WrapData::default(),
         };
-        self.layout.borrow_mut().wrap_data[line] = Some(wrap_data);
-        self.layout.borrow_mut().y.truncate(line + 1);
+        let mut layout = self.layout.borrow_mut();
+        // The y of the lines after this one only changes if its number of rows did.
+        if layout.wrap_data[line]
+            .as_ref()
+            .map_or(0, |wrap_data| wrap_data.wraps.len())
+            != wrap_data.wraps.len()
+        {
+            layout.y.truncate(line + 1);
+        }
+        layout.wrap_data[line] = Some(wrap_data);
+        drop(layout);
         self.update_column_count(line);
     }
 
+    // Marks the given line as not laid out, so it counts as a single row until it is laid out
+    // again.
+    fn clear_wrap_data(&self, line: usize) {
+        let mut layout = self.layout.borrow_mut();
+        if layout.wrap_data[line]
+            .as_ref()
+            .map_or(false, |wrap_data| !wrap_data.wraps.is_empty())
+        {
+            layout.y.truncate(line + 1);
+        }
+        layout.column_count[line] = None;
+        layout.wrap_data[line] = None;
+    }
+
     fn update_highlighted_delimiter_positions(&self) {
         let mut selection_state = self.selection_state.borrow_mut();
         let mut highlighted_delimiter_positions =
@@ -1014,6 +1745,12 @@ struct SelectionState {
     highlighted_delimiter_positions: HashSet<Position>,
 }
 
+#[derive(Debug)]
+struct SearchState {
+    search: Option<Search>,
+    matches: DecorationSet,
+}
+
 #[derive(Debug)]
 struct FoldState {
     folding_lines: HashSet<usize>,
@@ -1036,7 +1773,7 @@ pub fn reindent(string: &str, f: impl FnOnce(usize) -> usize) -> (usize, usize,
 
 fn grow_selection(
     selection: Selection,
-    lines: &[String],
+    lines: &Lines,
     mode: SelectionMode,
     word_separators: &[char],
 ) -> Selection {
@@ -1155,12 +1892,32 @@ fn grow_selection(
     }
 }
 
+fn match_to_selection(m: &Decoration) -> Selection {
+    Selection {
+        cursor: Cursor::from(m.end()),
+        anchor: m.start(),
+    }
+}
+
+fn replace_range(editor: &mut Editor<'_>, position: Position, length: Length, text: &Text) {
+    editor.apply_edit(Edit {
+        change: Change::Delete(position, length),
+        drift: Drift::Before,
+    });
+    if !text.is_empty() {
+        editor.apply_edit(Edit {
+            change: Change::Insert(position, text.clone()),
+            drift: Drift::Before,
+        });
+    }
+}
+
 fn new_indentation(column_count: usize) -> String {
     iter::repeat(' ').take(column_count).collect()
 }
 
 fn find_highlighted_delimiter_pair(
-    lines: &[String],
+    lines: &Lines,
     position: Position,
 ) -> Option<(Position, Position)> {
     // Cursor is before an opening delimiter
@@ -1234,7 +1991,7 @@ fn find_highlighted_delimiter_pair(
 }
 
 fn find_opening_delimiter(
-    lines: &[String],
+    lines: &Lines,
     position: Position,
     closing_delimiter: char,
 ) -> Option<Position> {
@@ -1268,7 +2025,7 @@ fn find_opening_delimiter(
 }
 
 fn find_closing_delimiter(
-    lines: &[String],
+    lines: &Lines,
     position: Position,
     opening_delimiter: char,
 ) -> Option<Position> {
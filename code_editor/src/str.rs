@@ -7,6 +7,9 @@ pub trait StrExt {
     fn prev_indent_level(&self, indent_column_count: usize) -> usize;
     fn find_next_word_boundary(&self, index: usize, word_separators: &[char]) -> usize;
     fn find_prev_word_boundary(&self, index: usize, word_separators: &[char]) -> usize;
+    fn find_next_word_end(&self, index: usize, word_separators: &[char]) -> usize;
+    fn find_prev_word_start(&self, index: usize, word_separators: &[char]) -> usize;
+    fn find_identifier_start(&self, index: usize) -> usize;
     fn indent(&self) -> Option<&str>;
     fn longest_common_prefix(&self, other: &str) -> &str;
     fn graphemes(&self) -> Graphemes<'_>;
@@ -32,24 +35,43 @@ impl StrExt for str {
     }
 
     fn find_next_word_boundary(&self, index: usize, word_separators: &[char]) -> usize {
-        if index == 0 {
-            return index;
-        }
-        let start = index;
-        self[index..]
-            .char_indices()
-            .find(|&(_, char)| word_separators.contains(&char))
-            .map(|(index, _)| start + index)
-            .unwrap_or_else(|| self.len())
+        let class = CharClass::around(self, index, word_separators);
+        find_class_end(self, index, class, word_separators)
     }
 
     fn find_prev_word_boundary(&self, index: usize, word_separators: &[char]) -> usize {
-        if index == self.len() {
-            return index;
-        }
+        let class = CharClass::around(self, index, word_separators);
+        find_class_start(self, index, class, word_separators)
+    }
+
+    fn find_next_word_end(&self, index: usize, word_separators: &[char]) -> usize {
+        let start = self.len() - self[index..].trim_start().len();
+        self[start..].chars().next().map_or(start, |char| {
+            find_class_end(
+                self,
+                start,
+                CharClass::of(char, word_separators),
+                word_separators,
+            )
+        })
+    }
+
+    fn find_prev_word_start(&self, index: usize, word_separators: &[char]) -> usize {
+        let end = self[..index].trim_end().len();
+        self[..end].chars().next_back().map_or(end, |char| {
+            find_class_start(
+                self,
+                end,
+                CharClass::of(char, word_separators),
+                word_separators,
+            )
+        })
+    }
+
+    fn find_identifier_start(&self, index: usize) -> usize {
         self[..index]
             .char_indices()
-            .rfind(|&(_, char)| word_separators.contains(&char))
+            .rfind(|&(_, char)| !(char.is_alphanumeric() || char == '_'))
             .map(|(char_index, char)| char_index + char.len_utf8())
             .unwrap_or(0)
     }
@@ -85,6 +107,66 @@ impl StrExt for str {
     }
 }
 
+// Words are runs of characters of the same class, so that `foo::bar` is made up of the words
+// `foo`, `::` and `bar`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum CharClass {
+    Whitespace,
+    Separator,
+    Word,
+}
+
+impl CharClass {
+    fn of(char: char, word_separators: &[char]) -> Self {
+        if char.is_whitespace() {
+            Self::Whitespace
+        } else if word_separators.contains(&char) {
+            Self::Separator
+        } else {
+            Self::Word
+        }
+    }
+
+    // Returns the class of the word at the given index. A word that touches the index on either
+    // side is preferred over whitespace and separators.
+    fn around(string: &str, index: usize, word_separators: &[char]) -> Self {
+        let next_class = string[index..]
+            .chars()
+            .next()
+            .map(|char| Self::of(char, word_separators));
+        let prev_class = string[..index]
+            .chars()
+            .next_back()
+            .map(|char| Self::of(char, word_separators));
+        match (prev_class, next_class) {
+            (_, Some(Self::Word)) | (Some(Self::Word), _) => Self::Word,
+            (_, Some(class)) | (Some(class), None) => class,
+            (None, None) => Self::Whitespace,
+        }
+    }
+}
+
+fn find_class_end(string: &str, index: usize, class: CharClass, word_separators: &[char]) -> usize {
+    string[index..]
+        .char_indices()
+        .find(|&(_, char)| CharClass::of(char, word_separators) != class)
+        .map(|(char_index, _)| index + char_index)
+        .unwrap_or(string.len())
+}
+
+fn find_class_start(
+    string: &str,
+    index: usize,
+    class: CharClass,
+    word_separators: &[char],
+) -> usize {
+    string[..index]
+        .char_indices()
+        .rfind(|&(_, char)| CharClass::of(char, word_separators) != class)
+        .map(|(char_index, char)| char_index + char.len_utf8())
+        .unwrap_or(0)
+}
+
 #[derive(Clone, Debug)]
 pub struct Graphemes<'a> {
     string: &'a str,
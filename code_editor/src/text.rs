@@ -1,14 +1,23 @@
-use std::{
-    cmp::Ordering,
-    fmt, io,
-    io::BufRead,
-    iter,
-    ops::{Add, AddAssign, Sub, SubAssign},
+use {
+    crate::mmap::Mmap,
+    std::{
+        cmp::Ordering,
+        fmt,
+        fs::File,
+        hash::{Hash, Hasher},
+        io,
+        io::BufRead,
+        iter, mem,
+        ops::{Add, AddAssign, Index, Range, Sub, SubAssign},
+        path::Path,
+        str,
+        sync::Arc,
+    },
 };
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Text {
-    lines: Vec<String>,
+    lines: Lines,
 }
 
 impl Text {
@@ -17,20 +26,45 @@ impl Text {
     }
 
     pub fn newline() -> Self {
-        Self {
-            lines: vec![String::new(), String::new()],
-        }
+        Self::from_lines(vec![String::new(), String::new()])
     }
 
     pub fn from_buf_reader<R>(reader: R) -> io::Result<Self>
     where
         R: BufRead,
     {
+        Ok(Self::from_lines(reader.lines().collect::<Result<_, _>>()?))
+    }
+
+    /// Creates a text that indexes the lines of the given string in place, instead of allocating
+    /// every line on its own. This makes large texts quick to create. The lines are copied out of
+    /// the string the first time the text is changed.
+    pub fn from_shared(string: String) -> Self {
+        Self {
+            lines: Lines::from_buffer(Arc::new(Buffer::Owned(string))),
+        }
+    }
+
+    /// Creates a text for the contents of a file without reading all of it up front. Where the
+    /// platform supports it, the file is memory mapped, so only the parts of it that are looked
+    /// at are paged in. Like with `from_shared`, the lines are copied out of the file the first
+    /// time the text is changed, so a mapped text is meant for a read-only document.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be changed by anyone while the text or any clone of it is alive.
+    pub unsafe fn map_file(path: impl AsRef<Path>) -> io::Result<Self> {
         Ok(Self {
-            lines: reader.lines().collect::<Result<_, _>>()?,
+            lines: Lines::from_buffer(Arc::new(Buffer::map_file(path.as_ref())?)),
         })
     }
 
+    fn from_lines(lines: Vec<String>) -> Self {
+        Self {
+            lines: Lines::from_vec(lines),
+        }
+    }
+
     pub fn is_empty(&self) -> bool {
         self.length() == Length::zero()
     }
@@ -53,7 +87,7 @@ impl Text {
         }
     }
 
-    pub fn as_lines(&self) -> &[String] {
+    pub fn as_lines(&self) -> &Lines {
         &self.lines
     }
 
@@ -66,13 +100,13 @@ impl Text {
             lines.reserve(end.line_index - start.line_index + 1);
             lines.push(self.lines[start.line_index][start.byte_index..].to_string());
             lines.extend(
-                self.lines[start.line_index + 1..end.line_index]
-                    .iter()
-                    .cloned(),
+                self.lines
+                    .range(start.line_index + 1..end.line_index)
+                    .map(|line| line.to_string()),
             );
             lines.push(self.lines[end.line_index][..end.byte_index].to_string());
         }
-        Text { lines }
+        Self::from_lines(lines)
     }
 
     pub fn apply_change(&mut self, change: Change) {
@@ -83,73 +117,70 @@ impl Text {
     }
 
     pub fn into_lines(self) -> Vec<String> {
-        self.lines
+        self.lines.to_vec()
     }
 
-    fn insert(&mut self, point: Position, mut text: Self) {
+    fn insert(&mut self, point: Position, text: Self) {
         if text.length().line_count == 0 {
-            self.lines[point.line_index].replace_range(
-                point.byte_index..point.byte_index,
-                text.lines.first().unwrap(),
-            );
+            self.lines
+                .line_mut(point.line_index)
+                .replace_range(point.byte_index..point.byte_index, &text.lines[0]);
         } else {
-            text.lines
+            let line = &self.lines[point.line_index];
+            let mut lines = text.lines.to_vec();
+            lines
                 .first_mut()
                 .unwrap()
-                .replace_range(..0, &self.lines[point.line_index][..point.byte_index]);
-            text.lines
+                .replace_range(..0, &line[..point.byte_index]);
+            lines
                 .last_mut()
                 .unwrap()
-                .push_str(&self.lines[point.line_index][point.byte_index..]);
+                .push_str(&line[point.byte_index..]);
             self.lines
-                .splice(point.line_index..point.line_index + 1, text.lines);
+                .splice(point.line_index..point.line_index + 1, lines);
         }
     }
 
     fn delete(&mut self, start: Position, length: Length) {
         let end = start + length;
         if start.line_index == end.line_index {
-            self.lines[start.line_index].replace_range(start.byte_index..end.byte_index, "");
+            self.lines
+                .line_mut(start.line_index)
+                .replace_range(start.byte_index..end.byte_index, "");
         } else {
             let mut line = self.lines[start.line_index][..start.byte_index].to_string();
             line.push_str(&self.lines[end.line_index][end.byte_index..]);
             self.lines
-                .splice(start.line_index..end.line_index + 1, iter::once(line));
+                .splice(start.line_index..end.line_index + 1, vec![line]);
         }
     }
 }
 
 impl Default for Text {
     fn default() -> Self {
-        Self {
-            lines: vec![String::new()],
-        }
+        Self::from_lines(vec![String::new()])
     }
 }
 
 impl fmt::Display for Text {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let (last_line, remaining_lines) = self.lines.split_last().unwrap();
-        for line in remaining_lines {
+        let line_count = self.lines.len();
+        for line in self.lines.range(0..line_count - 1) {
             writeln!(f, "{}", line)?;
         }
-        write!(f, "{}", last_line)
+        write!(f, "{}", &self.lines[line_count - 1])
     }
 }
 
 impl From<char> for Text {
     fn from(char: char) -> Self {
-        Self {
-            lines: vec![String::from(char)],
-        }
+        Self::from_lines(vec![String::from(char)])
     }
 }
 
 impl From<&str> for Text {
     fn from(string: &str) -> Self {
-        Self {
-            lines: string.split('\n').map(|string| string.to_owned()).collect(),
-        }
+        Self::from_lines(string.split('\n').map(|string| string.to_owned()).collect())
     }
 }
 
@@ -174,6 +205,294 @@ impl FromIterator<char> for Text {
     }
 }
 
+/// The lines of a `Text`, indexed like a slice of strings.
+///
+/// The lines are stored in chunks of up to `CHUNK_LEN` lines, so that inserting or removing
+/// lines only moves the lines in the chunks that are affected, instead of all lines after them.
+/// A text created from a single buffer indexes the lines in that buffer until it is changed.
+#[derive(Clone, Debug)]
+pub struct Lines {
+    storage: Storage,
+}
+
+#[derive(Clone, Debug)]
+enum Storage {
+    Chunks {
+        chunks: Vec<Vec<String>>,
+        // The index of the first line of every chunk, followed by the number of lines.
+        chunk_starts: Vec<usize>,
+    },
+    Buffer {
+        buffer: Arc<Buffer>,
+        // The byte index of the start of every line, followed by the length of the buffer plus
+        // one, as if it ended with a newline.
+        line_starts: Vec<usize>,
+    },
+}
+
+const CHUNK_LEN: usize = 512;
+
+impl Lines {
+    fn from_vec(lines: Vec<String>) -> Self {
+        let mut chunks = Vec::new();
+        let mut lines = lines.into_iter().peekable();
+        while lines.peek().is_some() {
+            chunks.push(lines.by_ref().take(CHUNK_LEN).collect::<Vec<_>>());
+        }
+        let mut chunk_starts = vec![0];
+        let mut chunk_start = 0;
+        for chunk in &chunks {
+            chunk_start += chunk.len();
+            chunk_starts.push(chunk_start);
+        }
+        Self {
+            storage: Storage::Chunks {
+                chunks,
+                chunk_starts,
+            },
+        }
+    }
+
+    fn from_buffer(buffer: Arc<Buffer>) -> Self {
+        let string = buffer.as_str();
+        let mut line_starts = vec![0];
+        line_starts.extend(string.match_indices('\n').map(|(index, _)| index + 1));
+        line_starts.push(string.len() + 1);
+        Self {
+            storage: Storage::Buffer {
+                buffer,
+                line_starts,
+            },
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.storage {
+            Storage::Chunks { chunk_starts, .. } => *chunk_starts.last().unwrap(),
+            Storage::Buffer { line_starts, .. } => line_starts.len() - 1,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        if index < self.len() {
+            Some(&self[index])
+        } else {
+            None
+        }
+    }
+
+    pub fn first(&self) -> Option<&str> {
+        self.get(0)
+    }
+
+    pub fn last(&self) -> Option<&str> {
+        self.get(self.len().wrapping_sub(1))
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        self.range(0..self.len())
+    }
+
+    /// Returns an iterator over the lines in the given range, like `lines[range].iter()` would
+    /// for a slice.
+    pub fn range(&self, range: Range<usize>) -> Iter<'_> {
+        assert!(range.start <= range.end && range.end <= self.len());
+        Iter { lines: self, range }
+    }
+
+    pub fn join(&self, separator: &str) -> String {
+        let mut string = String::new();
+        for (index, line) in self.iter().enumerate() {
+            if index > 0 {
+                string.push_str(separator);
+            }
+            string.push_str(line);
+        }
+        string
+    }
+
+    pub fn to_vec(&self) -> Vec<String> {
+        self.iter().map(|line| line.to_string()).collect()
+    }
+
+    fn line_mut(&mut self, index: usize) -> &mut String {
+        let (chunks, chunk_starts) = self.make_chunks();
+        let chunk_index = find_chunk(chunk_starts, index);
+        &mut chunks[chunk_index][index - chunk_starts[chunk_index]]
+    }
+
+    fn splice(&mut self, range: Range<usize>, lines: Vec<String>) {
+        let (chunks, chunk_starts) = self.make_chunks();
+        let line_count = *chunk_starts.last().unwrap();
+        let first = find_chunk(chunk_starts, range.start.min(line_count - 1));
+        let last = if range.is_empty() {
+            first
+        } else {
+            find_chunk(chunk_starts, range.end - 1)
+        };
+        let first_start = chunk_starts[first];
+        if first == last {
+            chunks[first].splice(range.start - first_start..range.end - first_start, lines);
+        } else {
+            let last_start = chunk_starts[last];
+            let mut chunk = mem::take(&mut chunks[first]);
+            chunk.truncate(range.start - first_start);
+            chunk.extend(lines);
+            chunk.extend(chunks[last].drain(range.end - last_start..));
+            chunks.splice(first..last + 1, iter::once(chunk));
+        }
+        // Keep the chunk that was changed from growing too long or staying empty.
+        if chunks[first].len() > 2 * CHUNK_LEN {
+            let chunk = mem::take(&mut chunks[first]);
+            chunks.splice(
+                first..first + 1,
+                chunk.chunks(CHUNK_LEN).map(|chunk| chunk.to_vec()),
+            );
+        } else if chunks[first].is_empty() && chunks.len() > 1 {
+            chunks.remove(first);
+        }
+        chunk_starts.truncate(first + 1);
+        let mut chunk_start = chunk_starts[first];
+        for chunk in &chunks[first..] {
+            chunk_start += chunk.len();
+            chunk_starts.push(chunk_start);
+        }
+    }
+
+    // Copies the lines out of the buffer, if they are still in one.
+    fn make_chunks(&mut self) -> (&mut Vec<Vec<String>>, &mut Vec<usize>) {
+        if let Storage::Buffer { .. } = self.storage {
+            *self = Self::from_vec(self.to_vec());
+        }
+        match &mut self.storage {
+            Storage::Chunks {
+                chunks,
+                chunk_starts,
+            } => (chunks, chunk_starts),
+            Storage::Buffer { .. } => unreachable!(),
+        }
+    }
+}
+
+fn find_chunk(chunk_starts: &[usize], line_index: usize) -> usize {
+    match chunk_starts.binary_search(&line_index) {
+        Ok(chunk_index) => chunk_index,
+        Err(chunk_index) => chunk_index - 1,
+    }
+}
+
+impl Index<usize> for Lines {
+    type Output = str;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        match &self.storage {
+            Storage::Chunks {
+                chunks,
+                chunk_starts,
+            } => {
+                let chunk_index = find_chunk(chunk_starts, index);
+                &chunks[chunk_index][index - chunk_starts[chunk_index]]
+            }
+            Storage::Buffer {
+                buffer,
+                line_starts,
+            } => &buffer.as_str()[line_starts[index]..line_starts[index + 1] - 1],
+        }
+    }
+}
+
+impl PartialEq for Lines {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl Eq for Lines {}
+
+impl Hash for Lines {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        for line in self.iter() {
+            line.hash(state);
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Lines {
+    type Item = &'a str;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Iter<'a> {
+    lines: &'a Lines,
+    range: Range<usize>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.range.next()?;
+        Some(&self.lines[index])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+}
+
+impl<'a> DoubleEndedIterator for Iter<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let index = self.range.next_back()?;
+        Some(&self.lines[index])
+    }
+}
+
+impl<'a> ExactSizeIterator for Iter<'a> {}
+
+enum Buffer {
+    Owned(String),
+    // Checked to be valid UTF-8 when it was mapped.
+    Mapped(Mmap),
+}
+
+impl Buffer {
+    fn map_file(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        match Mmap::map(&file)? {
+            Some(mmap) => {
+                if let Err(error) = str::from_utf8(mmap.as_bytes()) {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, error));
+                }
+                Ok(Self::Mapped(mmap))
+            }
+            None => Ok(Self::Owned(io::read_to_string(file)?)),
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        match self {
+            Self::Owned(string) => string,
+            Self::Mapped(mmap) => unsafe { str::from_utf8_unchecked(mmap.as_bytes()) },
+        }
+    }
+}
+
+impl fmt::Debug for Buffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Buffer({} bytes)", self.as_str().len())
+    }
+}
+
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Edit {
     pub change: Change,
@@ -1,24 +1,123 @@
-use crate::{
-    text::{Change, Text},
-    token::TokenKind,
-    Token,
+use {
+    crate::{
+        languages::{Json, Live, Markdown, Shader, Toml},
+        text::{Change, Text},
+        token::TokenKind,
+        Token,
+    },
+    std::{fmt, path::Path},
 };
 
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+/// A language the code editor can colour.
+///
+/// Languages tokenize a line at a time. The state is what carries over from the end of one line
+/// to the start of the next, such as being inside a block comment, so that after an edit only the
+/// lines whose start state changed have to be tokenized again.
+pub trait Language: fmt::Debug + 'static {
+    type State: Clone + Copy + fmt::Debug + Default + Eq;
+
+    /// Returns the kind of the token at the cursor, which is not at the end of the line, and the
+    /// state after it. The cursor has to be moved past the token.
+    fn next(&self, state: Self::State, cursor: &mut Cursor) -> (Self::State, TokenKind);
+
+    /// Returns the token that starts a line comment, or `None` if the language has no line
+    /// comments.
+    fn line_comment(&self) -> Option<&'static str> {
+        None
+    }
+}
+
+#[derive(Debug)]
 pub struct Tokenizer {
-    state: Vec<Option<(State, State)>>,
+    lines: Box<dyn LineTokenizer>,
 }
 
 impl Tokenizer {
-    pub fn new(line_count: usize) -> Self {
+    pub fn new(language: impl Language) -> Self {
         Self {
-            state: (0..line_count).map(|_| None).collect(),
+            lines: Box::new(LanguageTokenizer {
+                language,
+                state: Vec::new(),
+                end: 0,
+            }),
         }
     }
 
+    /// Returns a tokenizer for files with the given extension. Files we don't know the language of
+    /// are tokenized as Rust.
+    pub fn for_extension(extension: &str) -> Self {
+        match extension.to_ascii_lowercase().as_str() {
+            "live" => Self::new(Live),
+            "toml" => Self::new(Toml),
+            "json" => Self::new(Json),
+            "md" | "markdown" => Self::new(Markdown),
+            "glsl" | "vert" | "frag" | "comp" | "geom" | "vs" | "fs" | "metal" | "hlsl" => {
+                Self::new(Shader)
+            }
+            _ => Self::new(Rust),
+        }
+    }
+
+    pub fn for_path(path: impl AsRef<Path>) -> Self {
+        Self::for_extension(
+            path.as_ref()
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .unwrap_or(""),
+        )
+    }
+
+    pub fn line_comment(&self) -> Option<&'static str> {
+        self.lines.line_comment()
+    }
+
     pub fn apply_change(&mut self, change: &Change) {
+        self.lines.apply_change(change);
+    }
+
+    /// Tokenizes the lines before `end` that changed since they were last tokenized, either
+    /// because they were edited or because the state at their start changed.
+    pub fn update(&mut self, text: &Text, tokens: &mut [Vec<Token>], end: usize) {
+        self.lines.update(text, tokens, end);
+    }
+}
+
+impl Default for Tokenizer {
+    fn default() -> Self {
+        Self::new(Rust)
+    }
+}
+
+trait LineTokenizer: fmt::Debug {
+    fn line_comment(&self) -> Option<&'static str>;
+
+    fn apply_change(&mut self, change: &Change);
+
+    fn update(&mut self, text: &Text, tokens: &mut [Vec<Token>], end: usize);
+}
+
+// The start and end state of every line, or `None` if the line has to be tokenized again. The
+// lines before `end` are known to be up to date.
+#[derive(Debug)]
+struct LanguageTokenizer<L: Language> {
+    language: L,
+    state: Vec<Option<(L::State, L::State)>>,
+    end: usize,
+}
+
+impl<L: Language> LineTokenizer for LanguageTokenizer<L> {
+    fn line_comment(&self) -> Option<&'static str> {
+        self.language.line_comment()
+    }
+
+    fn apply_change(&mut self, change: &Change) {
+        if self.state.is_empty() {
+            // Nothing was tokenized yet, the first update sizes the state.
+            return;
+        }
         match *change {
             Change::Insert(point, ref text) => {
+                self.end = self.end.min(point.line_index);
                 self.state[point.line_index] = None;
                 let line_count = text.length().line_count;
                 if line_count > 0 {
@@ -27,6 +126,7 @@ impl Tokenizer {
                 }
             }
             Change::Delete(start, length) => {
+                self.end = self.end.min(start.line_index);
                 self.state[start.line_index] = None;
                 let line_count = length.line_count;
                 if line_count > 0 {
@@ -38,9 +138,17 @@ impl Tokenizer {
         }
     }
 
-    pub fn update(&mut self, text: &Text, tokens: &mut [Vec<Token>]) {
-        let mut state = State::default();
-        for line in 0..text.as_lines().len() {
+    fn update(&mut self, text: &Text, tokens: &mut [Vec<Token>], end: usize) {
+        // The first update sizes the state, after that changes keep it in sync with the text.
+        self.state.resize(text.as_lines().len(), None);
+        if self.end >= end {
+            return;
+        }
+        let mut state = match self.end.checked_sub(1) {
+            Some(line) => self.state[line].unwrap().1,
+            None => L::State::default(),
+        };
+        for line in self.end..end {
             match self.state[line] {
                 Some((start_state, end_state)) if state == start_state => {
                     state = end_state;
@@ -49,19 +157,43 @@ impl Tokenizer {
                     let start_state = state;
                     let mut new_tokens = Vec::new();
                     let mut cursor = Cursor::new(&text.as_lines()[line]);
-                    loop {
-                        let (next_state, token) = state.next(&mut cursor);
+                    while cursor.peek(0) != '\0' {
+                        let start = cursor.index;
+                        let (next_state, kind) = self.language.next(state, &mut cursor);
+                        let end = cursor.index;
+                        assert!(start < end);
                         state = next_state;
-                        match token {
-                            Some(token) => new_tokens.push(token),
-                            None => break,
-                        }
+                        new_tokens.push(Token {
+                            len: end - start,
+                            kind,
+                        });
                     }
                     self.state[line] = Some((start_state, state));
                     tokens[line] = new_tokens;
                 }
             }
         }
+        self.end = end;
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Rust;
+
+impl Language for Rust {
+    type State = State;
+
+    fn next(&self, state: State, cursor: &mut Cursor) -> (State, TokenKind) {
+        match state {
+            State::Initial(state) => state.next(cursor),
+            State::BlockCommentTail(state) => state.next(cursor),
+            State::DoubleQuotedStringTail(state) => state.next(cursor),
+            State::RawDoubleQuotedStringTail(state) => state.next(cursor),
+        }
+    }
+
+    fn line_comment(&self) -> Option<&'static str> {
+        Some("//")
     }
 }
 
@@ -79,30 +211,6 @@ impl Default for State {
     }
 }
 
-impl State {
-    pub fn next(self, cursor: &mut Cursor) -> (State, Option<Token>) {
-        if cursor.peek(0) == '\0' {
-            return (self, None);
-        }
-        let start = cursor.index;
-        let (next_state, kind) = match self {
-            State::Initial(state) => state.next(cursor),
-            State::BlockCommentTail(state) => state.next(cursor),
-            State::DoubleQuotedStringTail(state) => state.next(cursor),
-            State::RawDoubleQuotedStringTail(state) => state.next(cursor),
-        };
-        let end = cursor.index;
-        assert!(start < end);
-        (
-            next_state,
-            Some(Token {
-                len: end - start,
-                kind,
-            }),
-        )
-    }
-}
-
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct InitialState;
 
@@ -481,18 +589,32 @@ impl<'a> Cursor<'a> {
         Cursor { string, index: 0 }
     }
 
-    fn peek(&self, index: usize) -> char {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the part of the line before the cursor.
+    pub fn before(&self) -> &'a str {
+        &self.string[..self.index]
+    }
+
+    /// Returns the part of the line from the cursor on.
+    pub fn rest(&self) -> &'a str {
+        &self.string[self.index..]
+    }
+
+    pub fn peek(&self, index: usize) -> char {
         self.string[self.index..].chars().nth(index).unwrap_or('\0')
     }
 
-    fn skip(&mut self, count: usize) {
+    pub fn skip(&mut self, count: usize) {
         self.index = self.string[self.index..]
             .char_indices()
             .nth(count)
             .map_or(self.string.len(), |(index, _)| self.index + index);
     }
 
-    fn skip_if<P>(&mut self, predicate: P) -> bool
+    pub fn skip_if<P>(&mut self, predicate: P) -> bool
     where
         P: FnOnce(char) -> bool,
     {
@@ -504,7 +626,20 @@ impl<'a> Cursor<'a> {
         }
     }
 
-    fn skip_exponent(&mut self) -> bool {
+    /// Skips chars while they match the predicate, stopping at the end of the line.
+    pub fn skip_while<P>(&mut self, mut predicate: P)
+    where
+        P: FnMut(char) -> bool,
+    {
+        while self.peek(0) != '\0' && self.skip_if(&mut predicate) {}
+    }
+
+    /// Skips to the end of the line.
+    pub fn skip_line(&mut self) {
+        self.index = self.string.len();
+    }
+
+    pub fn skip_exponent(&mut self) -> bool {
         debug_assert!(self.peek(0) == 'E' || self.peek(0) == 'e');
         self.skip(1);
         if self.peek(0) == '+' || self.peek(0) == '-' {
@@ -513,7 +648,7 @@ impl<'a> Cursor<'a> {
         self.skip_digits(10)
     }
 
-    fn skip_digits(&mut self, radix: u32) -> bool {
+    pub fn skip_digits(&mut self, radix: u32) -> bool {
         let mut has_skip_digits = false;
         loop {
             match self.peek(0) {
@@ -530,7 +665,7 @@ impl<'a> Cursor<'a> {
         has_skip_digits
     }
 
-    fn skip_suffix(&mut self) -> bool {
+    pub fn skip_suffix(&mut self) -> bool {
         if self.peek(0).is_identifier_start() {
             self.skip(1);
             while self.skip_if(|char| char.is_identifier_continue()) {}
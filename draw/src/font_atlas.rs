@@ -18,11 +18,13 @@ pub use {
         shader::draw_trapezoid::DrawTrapezoidVector,
         makepad_vector::font::Glyph,
         makepad_vector::trapezoidator::Trapezoidator,
-        makepad_vector::geometry::{AffineTransformation, Transform, Vector},
+        makepad_vector::geometry::{AffineTransformation, Point, Rectangle, Transform, Trapezoid, Vector},
         makepad_vector::internal_iter::ExtendFromInternalIterator,
         makepad_vector::path::PathIterator,
     },
-    rustybuzz::{Direction, GlyphInfo, UnicodeBuffer},
+    rustybuzz::{Direction, GlyphInfo, GlyphPosition, UnicodeBuffer},
+    rustybuzz::ttf_parser::{colr, GlyphId, RasterImageFormat, RgbaColor},
+    makepad_zune_png::PngDecoder,
 };
 
 pub struct CxFontsAtlas {
@@ -30,9 +32,15 @@ pub struct CxFontsAtlas {
     pub path_to_font_id: HashMap<String, usize>,
     pub texture: Texture,
     pub clear_buffer: bool,
-    pub alloc: CxFontsAtlasAlloc
+    pub alloc: CxFontsAtlasAlloc,
+    // colour glyphs (emoji) can't be stored as coverage, so they go into a separate RGBA
+    // texture that is filled on the CPU
+    pub color_texture: Texture,
+    pub color_alloc: CxFontsAtlasAlloc,
 }
 
+pub const COLOR_ATLAS_SIZE: usize = 1024;
+
 #[derive(Default)]
 pub struct CxFontsAtlasAlloc {
     pub texture_size: DVec2,
@@ -44,7 +52,7 @@ pub struct CxFontsAtlasAlloc {
 }
 
 impl CxFontsAtlas {
-    pub fn new(texture: Texture) -> Self {
+    pub fn new(texture: Texture, color_texture: Texture) -> Self {
         Self {
             fonts: Vec::new(),
             path_to_font_id: HashMap::new(),
@@ -57,6 +65,15 @@ impl CxFontsAtlas {
                 ypos: 0.0,
                 hmax: 0.0,
                 todo: Vec::new(),
+            },
+            color_texture,
+            color_alloc: CxFontsAtlasAlloc {
+                full: false,
+                texture_size: DVec2 {x: COLOR_ATLAS_SIZE as f64, y: COLOR_ATLAS_SIZE as f64},
+                xpos: 0.0,
+                ypos: 0.0,
+                hmax: 0.0,
+                todo: Vec::new(),
             }
         }
     }
@@ -89,10 +106,14 @@ impl CxFontsAtlasAlloc {
     }
 }
 
+// A font family: the font at path, followed by the fonts in fallbacks which are tried in order
+// for chars that the font has no glyph for, like CJK, Arabic or emoji.
 #[derive(Clone, Live)]
 pub struct Font {
     #[rust] pub font_id: Option<usize>,
-    #[live] pub path: LiveDependency
+    #[rust] pub fallback_font_ids: Vec<usize>,
+    #[live] pub path: LiveDependency,
+    #[live] pub fallbacks: Vec<LiveDependency>,
 }
 
 #[derive(Clone)]
@@ -102,7 +123,9 @@ impl LiveHook for Font {
     fn after_apply(&mut self, cx: &mut Cx, _apply_from: ApplyFrom, _index: usize, _nodes: &[LiveNode]) {
         Cx2d::lazy_construct_font_atlas(cx);
         let atlas = cx.get_global::<CxFontsAtlasRc>().clone();
-        self.font_id = Some(atlas.0.borrow_mut().get_font_by_path(cx, self.path.as_str()));
+        let mut atlas = atlas.0.borrow_mut();
+        self.font_id = Some(atlas.get_font_by_path(cx, self.path.as_str()));
+        self.fallback_font_ids = self.fallbacks.iter().map( | path | atlas.get_font_by_path(cx, path.as_str())).collect();
     }
 }
 
@@ -146,6 +169,11 @@ impl CxFontsAtlas {
         self.alloc.ypos = 0.;
         self.alloc.hmax = 0.;
         self.clear_buffer = true;
+        // colour glyphs overwrite their whole slot, so the colour atlas needs no clearing
+        self.color_alloc.xpos = 0.;
+        self.color_alloc.ypos = 0.;
+        self.color_alloc.hmax = 0.;
+        self.color_alloc.todo.clear();
     }
     
     pub fn get_internal_font_atlas_texture_id(&self) -> Texture {
@@ -229,6 +257,7 @@ pub struct CxDrawFontsAtlas {
     pub atlas_pass: Pass,
     pub atlas_draw_list: DrawList2d,
     pub atlas_texture: Texture,
+    pub color_trapezoidator: Trapezoidator,
     pub counter: usize
 }
 
@@ -248,11 +277,248 @@ impl CxDrawFontsAtlas {
             draw_trapezoid,
             atlas_pass: Pass::new(cx),
             atlas_draw_list: DrawList2d::new(cx),
-            atlas_texture: atlas_texture
+            atlas_texture: atlas_texture,
+            color_trapezoidator: Trapezoidator::default(),
+        }
+    }
+    
+    // atlas drawing function for colour glyphs. These are rasterized on the CPU straight into the
+    // pixels of the colour atlas, with premultiplied alpha and the rows of a slot going bottom up
+    // like the ones in the glyph atlas.
+    fn draw_color_todo(&mut self, fonts_atlas: &mut CxFontsAtlas, todo: CxFontsAtlasTodo, pixels: &mut [u32]) {
+        let texture_size = fonts_atlas.color_alloc.texture_size;
+        let cxfont = fonts_atlas.fonts[todo.font_id].as_mut().unwrap();
+        let color_glyph = if let Some(color_glyph) = cxfont.get_color_glyph(todo.glyph_id) {color_glyph} else {return};
+        let atlas_page = &cxfont.atlas_pages[todo.atlas_page_id];
+        let glyphtc = atlas_page.atlas_glyphs.get(&todo.glyph_id).unwrap()[todo.subpixel_id].unwrap();
+        
+        let x = (glyphtc.t1.x as f64 * texture_size.x).round() as usize;
+        let y = (glyphtc.t1.y as f64 * texture_size.y).round() as usize;
+        let w = ((glyphtc.t2.x - glyphtc.t1.x) as f64 * texture_size.x).round() as usize;
+        let h = ((glyphtc.t2.y - glyphtc.t1.y) as f64 * texture_size.y).round() as usize;
+        if x + w > texture_size.x as usize || y + h > texture_size.y as usize {
+            return
+        }
+        
+        let units_per_em = cxfont.ttf_font.units_per_em;
+        let font_scale_pixels = atlas_page.font_size * 96.0 / (72.0 * units_per_em) * atlas_page.dpi_factor;
+        let mut slot = vec![[0.0f32; 4]; w * h];
+        
+        match color_glyph.source {
+            CxColorGlyphSource::Layers => {
+                let mut painter = ColorLayerPainter::default();
+                cxfont.owned_font_face.with_ref( | face | face.paint_color_glyph(GlyphId(todo.glyph_id as u16), 0, &mut painter));
+                let mut coverage = vec![0.0f32; w * h];
+                let mut trapezoids = Vec::new();
+                for (layer_id, color) in painter.layers {
+                    let glyph = if let Ok(glyph) = cxfont.get_glyph_by_id(layer_id) {glyph} else {continue};
+                    trapezoids.clear();
+                    let trapezoidate = self.color_trapezoidator.trapezoidate(
+                        glyph
+                            .outline
+                            .iter()
+                            .map({
+                            move | command | {
+                                command.transform(
+                                    &AffineTransformation::identity()
+                                        .translate(Vector::new(-color_glyph.bounds.p_min.x, -color_glyph.bounds.p_min.y))
+                                        .uniform_scale(font_scale_pixels)
+                                )
+                            }
+                        }).linearize(0.5),
+                    );
+                    if let Some(trapezoidate) = trapezoidate {
+                        trapezoids.extend_from_internal_iter(trapezoidate);
+                    }
+                    coverage.iter_mut().for_each( | c | *c = 0.0);
+                    for trapezoid in &trapezoids {
+                        rasterize_trapezoid(trapezoid, w, h, &mut coverage);
+                    }
+                    // we don't know the colour of the text in the shared atlas, so the
+                    // foreground is painted white
+                    let color = color.unwrap_or(RgbaColor::new(255, 255, 255, 255));
+                    let alpha = color.alpha as f32 / 255.0;
+                    let color = [
+                        color.red as f32 / 255.0 * alpha,
+                        color.green as f32 / 255.0 * alpha,
+                        color.blue as f32 / 255.0 * alpha,
+                        alpha
+                    ];
+                    for (dst, coverage) in slot.iter_mut().zip(&coverage) {
+                        let coverage = coverage.min(1.0);
+                        let src_alpha = color[3] * coverage;
+                        for i in 0..4 {
+                            dst[i] = color[i] * coverage + dst[i] * (1.0 - src_alpha);
+                        }
+                    }
+                }
+            }
+            CxColorGlyphSource::Bitmap => {
+                let image = cxfont.owned_font_face.with_ref( | face | {
+                    decode_raster_glyph(face.glyph_raster_image(GlyphId(todo.glyph_id as u16), u16::MAX)?)
+                });
+                let (image_width, image_height, image_pixels_per_em, image) = if let Some(image) = image {image} else {return};
+                // average all the image pixels that fall into a slot pixel, or take the
+                // nearest one when scaling up
+                let scale = font_scale_pixels * units_per_em / image_pixels_per_em;
+                for sy in 0..h {
+                    let iy0 = ((sy as f64 / scale) as usize).min(image_height);
+                    let iy1 = (((sy + 1) as f64 / scale).ceil() as usize).clamp(iy0 + 1, image_height.max(iy0 + 1));
+                    for sx in 0..w {
+                        let ix0 = ((sx as f64 / scale) as usize).min(image_width);
+                        let ix1 = (((sx + 1) as f64 / scale).ceil() as usize).clamp(ix0 + 1, image_width.max(ix0 + 1));
+                        let mut sum = [0.0f32; 4];
+                        let mut count = 0.0;
+                        for iy in iy0..iy1.min(image_height) {
+                            for ix in ix0..ix1.min(image_width) {
+                                // the image rows go top down
+                                let pixel = image[(image_height - 1 - iy) * image_width + ix];
+                                for (i, sum) in sum.iter_mut().enumerate() {
+                                    *sum += pixel[i];
+                                }
+                                count += 1.0;
+                            }
+                        }
+                        if count > 0.0 {
+                            slot[sy * w + sx] = sum.map( | v | v / count);
+                        }
+                    }
+                }
+            }
+        }
+        
+        for sy in 0..h {
+            for sx in 0..w {
+                let [r, g, b, a] = slot[sy * w + sx].map( | v | (v.clamp(0.0, 1.0) * 255.0).round() as u32);
+                pixels[(y + sy) * texture_size.x as usize + x + sx] = (a << 24) | (r << 16) | (g << 8) | b;
+            }
         }
     }
 }
 
+// Collects the layers of a COLR glyph, with None for layers in the foreground colour.
+#[derive(Default)]
+struct ColorLayerPainter {
+    outline: Option<usize>,
+    layers: Vec<(usize, Option<RgbaColor>)>,
+}
+
+impl colr::Painter for ColorLayerPainter {
+    fn outline(&mut self, glyph_id: GlyphId) {
+        self.outline = Some(glyph_id.0 as usize);
+    }
+    
+    fn paint_foreground(&mut self) {
+        if let Some(outline) = self.outline.take() {
+            self.layers.push((outline, None));
+        }
+    }
+    
+    fn paint_color(&mut self, color: RgbaColor) {
+        if let Some(outline) = self.outline.take() {
+            self.layers.push((outline, Some(color)));
+        }
+    }
+}
+
+// Decodes an embedded bitmap glyph into premultiplied RGBA pixels, returning its width, height
+// and pixels per em as well.
+fn decode_raster_glyph(image: rustybuzz::ttf_parser::RasterGlyphImage) -> Option<(usize, usize, f64, Vec<[f32; 4]>)> {
+    let width = image.width as usize;
+    let height = image.height as usize;
+    let pixels = match image.format {
+        RasterImageFormat::PNG => {
+            let mut decoder = PngDecoder::new(image.data);
+            let data = decoder.decode().ok()?.u8()?;
+            let (width, height) = decoder.get_dimensions()?;
+            let pixel_count = width * height;
+            if pixel_count == 0 {
+                return None
+            }
+            let components = data.len() / pixel_count;
+            let mut pixels = Vec::with_capacity(pixel_count);
+            for pixel in data.chunks_exact(components).take(pixel_count) {
+                let [r, g, b, a] = match components {
+                    1 => [pixel[0], pixel[0], pixel[0], 255],
+                    2 => [pixel[0], pixel[0], pixel[0], pixel[1]],
+                    3 => [pixel[0], pixel[1], pixel[2], 255],
+                    _ => [pixel[0], pixel[1], pixel[2], pixel[3]],
+                }.map( | v | v as f32 / 255.0);
+                pixels.push([r * a, g * a, b * a, a]);
+            }
+            return Some((width, height, image.pixels_per_em as f64, pixels))
+        }
+        RasterImageFormat::BitmapPremulBgra32 => {
+            image.data.chunks_exact(4).take(width * height).map( | pixel | {
+                [pixel[2], pixel[1], pixel[0], pixel[3]].map( | v | v as f32 / 255.0)
+            }).collect::<Vec<_>>()
+        }
+        _ => return None
+    };
+    if pixels.len() != width * height {
+        return None
+    }
+    Some((width, height, image.pixels_per_em as f64, pixels))
+}
+
+// Accumulates the exact area that a trapezoid covers of every pixel of a width x height
+// coverage buffer.
+fn rasterize_trapezoid(trapezoid: &Trapezoid, width: usize, height: usize, coverage: &mut [f32]) {
+    let [x0, x1] = trapezoid.xs.map( | x | x as f64);
+    let [lower_y0, lower_y1, upper_y0, upper_y1] = trapezoid.ys.map( | y | y as f64);
+    if x1 <= x0 {
+        return
+    }
+    let lower_at = | x: f64 | lower_y0 + (lower_y1 - lower_y0) * (x - x0) / (x1 - x0);
+    let upper_at = | x: f64 | upper_y0 + (upper_y1 - upper_y0) * (x - x0) / (x1 - x0);
+    
+    let px_min = x0.floor().max(0.0) as usize;
+    let px_max = (x1.ceil().max(0.0) as usize).min(width);
+    let py_min = lower_y0.min(lower_y1).floor().max(0.0) as usize;
+    let py_max = (upper_y0.max(upper_y1).ceil().max(0.0) as usize).min(height);
+    for px in px_min..px_max {
+        let xa = x0.max(px as f64);
+        let xb = x1.min(px as f64 + 1.0);
+        if xb <= xa {
+            continue
+        }
+        let (la, lb, ua, ub) = (lower_at(xa), lower_at(xb), upper_at(xa), upper_at(xb));
+        for py in py_min..py_max {
+            let (lo, hi) = (py as f64, py as f64 + 1.0);
+            let area = clamped_line_integral(xa, xb, ua, ub, lo, hi) - clamped_line_integral(xa, xb, la, lb, lo, hi);
+            if area > 0.0 {
+                coverage[py * width + px] += area as f32;
+            }
+        }
+    }
+}
+
+// Integrates the line from (xa, ya) to (xb, yb), clamped to [lo, hi] and relative to lo. The
+// clamped line is linear between the points where it crosses lo and hi, so each piece is
+// integrated exactly by its midpoint.
+fn clamped_line_integral(xa: f64, xb: f64, ya: f64, yb: f64, lo: f64, hi: f64) -> f64 {
+    let mut ts = [0.0, 1.0, 0.0, 0.0];
+    let mut count = 2;
+    if ya != yb {
+        for y in [lo, hi] {
+            let t = (y - ya) / (yb - ya);
+            if t > 0.0 && t < 1.0 {
+                ts[count] = t;
+                count += 1;
+            }
+        }
+    }
+    let ts = &mut ts[0..count];
+    ts.sort_by( | a, b | a.partial_cmp(b).unwrap());
+    let mut area = 0.0;
+    for pair in ts.windows(2) {
+        let t = (pair[0] + pair[1]) * 0.5;
+        let y = (ya + (yb - ya) * t).clamp(lo, hi);
+        area += (y - lo) * (pair[1] - pair[0]) * (xb - xa);
+    }
+    area
+}
+
 impl<'a> Cx2d<'a> {
     pub fn lazy_construct_font_atlas(cx: &mut Cx){
         // ok lets fetch/instance our CxFontsAtlasRc
@@ -262,7 +528,14 @@ impl<'a> Cx2d<'a> {
             let texture = draw_fonts_atlas.atlas_texture.clone();
             cx.set_global(CxDrawFontsAtlasRc(Rc::new(RefCell::new(draw_fonts_atlas))));
             
-            let fonts_atlas = CxFontsAtlas::new(texture);
+            let color_texture = Texture::new(cx);
+            color_texture.set_format(cx, TextureFormat::VecBGRAu8_32 {
+                width: COLOR_ATLAS_SIZE,
+                height: COLOR_ATLAS_SIZE,
+                data: vec![0; COLOR_ATLAS_SIZE * COLOR_ATLAS_SIZE]
+            });
+            
+            let fonts_atlas = CxFontsAtlas::new(texture, color_texture);
             cx.set_global(CxFontsAtlasRc(Rc::new(RefCell::new(fonts_atlas))));
         }
     }
@@ -316,6 +589,20 @@ impl<'a> Cx2d<'a> {
             draw_fonts_atlas.atlas_draw_list.end(self);
             self.end_pass(&draw_fonts_atlas.atlas_pass);
         }
+        if !fonts_atlas.color_alloc.todo.is_empty() {
+            // take the pixels out of the texture, draw into them and swap them back in,
+            // which also flags the texture for upload
+            let mut pixels = Vec::new();
+            fonts_atlas.color_texture.swap_vec_u32(self.cx, &mut pixels);
+            
+            let mut color_todo = Vec::new();
+            std::mem::swap(&mut fonts_atlas.color_alloc.todo, &mut color_todo);
+            for todo in color_todo {
+                draw_fonts_atlas.draw_color_todo(fonts_atlas, todo, &mut pixels);
+            }
+            
+            fonts_atlas.color_texture.swap_vec_u32(self.cx, &mut pixels);
+        }
         //println!("TOTALT TIME {}", Cx::profile_time_ns() - start);
     }
 }
@@ -325,11 +612,43 @@ pub struct CxFont {
     pub owned_font_face: crate::owned_font_face::OwnedFace,
     pub atlas_pages: Vec<CxFontAtlasPage>,
     pub shape_cache: ShapeCache,
+    pub color_glyphs: HashMap<usize, Option<CxColorGlyph>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum CxColorGlyphSource {
+    // layers of outlines with a colour each, from the COLR and CPAL tables
+    Layers,
+    // an embedded bitmap, from the CBDT or sbix tables
+    Bitmap,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct CxColorGlyph {
+    pub source: CxColorGlyphSource,
+    // in font units, like the bounds of an outline glyph
+    pub bounds: Rectangle,
 }
 
 pub struct ShapeCache {
     pub keys: VecDeque<(Direction, Rc<str>)>,
-    pub glyph_ids: HashMap<(Direction, Rc<str>), Vec<usize>>,
+    pub glyphs: HashMap<(Direction, Rc<str>), Vec<ShapedGlyph>>,
+}
+
+// A single glyph as positioned by rustybuzz. Advances and offsets are in font units, with the y
+// axis pointing up.
+#[derive(Clone, Copy, Debug)]
+pub struct ShapedGlyph {
+    pub id: usize,
+    // The index of the first char of the cluster this glyph belongs to, relative to the start of
+    // the shaped string.
+    pub cluster: usize,
+    // The number of chars in that cluster. This is more than one for ligatures, so carets can be
+    // placed inside of them.
+    pub cluster_len: usize,
+    pub x_advance: f64,
+    pub x_offset: f64,
+    pub y_offset: f64,
 }
 
 impl ShapeCache {
@@ -339,14 +658,15 @@ impl ShapeCache {
     pub fn new() -> Self {
         Self {
             keys: VecDeque::new(),
-            glyph_ids: HashMap::new(),
+            glyphs: HashMap::new(),
         }
     }
 
     // If there is an entry for the given key in the cache, returns the corresponding list of
-    // glyph indices for that key. Otherwise, uses the given UnicodeBuffer and OwnedFace to
-    // compute the list of glyph indices for the key, inserts that in the cache and then returns
-    // the corresponding list.
+    // shaped glyphs for that key. Otherwise, uses the given UnicodeBuffer and OwnedFace to
+    // compute the list of shaped glyphs for the key, inserts that in the cache and then returns
+    // the corresponding list. The glyphs are in visual order, so for right-to-left text their
+    // clusters are decreasing.
     //
     // This method takes a UnicodeBuffer by value, and then returns the same buffer by value. This
     // is necessary because rustybuzz::shape consumes the UnicodeBuffer and then returns a
@@ -356,16 +676,16 @@ impl ShapeCache {
     //
     // Note that owned_font_face should be the same as the CxFont to which this cache belongs,
     // otherwise you will not get correct results.
-    pub fn get_or_compute_glyph_ids(
+    pub fn get_or_compute_glyphs(
         &mut self, 
         key: (Direction, &str),
         mut rustybuzz_buffer: UnicodeBuffer,
         owned_font_face: &crate::owned_font_face::OwnedFace
-    ) -> (&[usize], UnicodeBuffer) {
-        if !self.glyph_ids.contains_key(&key as &dyn ShapeCacheKey) {
+    ) -> (&[ShapedGlyph], UnicodeBuffer) {
+        if !self.glyphs.contains_key(&key as &dyn ShapeCacheKey) {
             if self.keys.len() == Self::MAX_SIZE {
                 for run in self.keys.drain(..Self::MAX_SIZE / 2) {
-                    self.glyph_ids.remove(&run);
+                    self.glyphs.remove(&run);
                 }
             }
 
@@ -373,14 +693,47 @@ impl ShapeCache {
             rustybuzz_buffer.set_direction(direction);
             rustybuzz_buffer.push_str(string);
             let glyph_buffer = owned_font_face.with_ref( | face | rustybuzz::shape(face, &[], rustybuzz_buffer));
-            let glyph_ids: Vec<_> = glyph_buffer.glyph_infos().iter().map( | glyph | glyph.glyph_id as usize).collect();
+            let glyphs = Self::shaped_glyphs(string, glyph_buffer.glyph_infos(), glyph_buffer.glyph_positions());
             rustybuzz_buffer = glyph_buffer.clear();
 
             let owned_string: Rc<str> = string.into();
             self.keys.push_back((direction, owned_string.clone()));
-            self.glyph_ids.insert((direction, owned_string), glyph_ids);
+            self.glyphs.insert((direction, owned_string), glyphs);
+        }
+        (&self.glyphs[&key as &dyn ShapeCacheKey], rustybuzz_buffer)
+    }
+
+    // rustybuzz gives us clusters as byte offsets into the shaped string, whereas the text input
+    // and selection code works with char indices. A cluster runs up to the next larger cluster
+    // in the string, so we map the sorted cluster boundaries to char indices once.
+    fn shaped_glyphs(string: &str, infos: &[GlyphInfo], positions: &[GlyphPosition]) -> Vec<ShapedGlyph> {
+        let mut boundaries: Vec<usize> = infos.iter().map( | info | info.cluster as usize).collect();
+        boundaries.push(string.len());
+        boundaries.sort_unstable();
+        boundaries.dedup();
+        
+        let mut char_indices = Vec::with_capacity(boundaries.len());
+        let mut char_index = 0;
+        let mut last_boundary = 0;
+        for &boundary in &boundaries {
+            char_index += string[last_boundary..boundary].chars().count();
+            char_indices.push(char_index);
+            last_boundary = boundary;
         }
-        (&self.glyph_ids[&key as &dyn ShapeCacheKey], rustybuzz_buffer)
+        
+        infos.iter().zip(positions).map( | (info, position) | {
+            let boundary = boundaries.binary_search(&(info.cluster as usize)).unwrap();
+            let cluster = char_indices[boundary];
+            let cluster_end = char_indices.get(boundary + 1).copied().unwrap_or(cluster);
+            ShapedGlyph {
+                id: info.glyph_id as usize,
+                cluster,
+                cluster_len: cluster_end - cluster,
+                x_advance: position.x_advance as f64,
+                x_offset: position.x_offset as f64,
+                y_offset: position.y_offset as f64,
+            }
+        }).collect()
     }
 }
 
@@ -486,6 +839,7 @@ impl CxFont {
             owned_font_face,
             atlas_pages: Vec::new(),
             shape_cache: ShapeCache::new(),
+            color_glyphs: HashMap::new(),
         })
     }
     
@@ -519,4 +873,53 @@ impl CxFont {
     pub fn get_glyph_by_id(&mut self, id: usize) -> makepad_vector::ttf_parser::Result<&Glyph> {
         self.owned_font_face.with_ref(|face| self.ttf_font.get_glyph_by_id(face, id))
     }
+    
+    pub fn get_color_glyph(&mut self, id: usize) -> Option<CxColorGlyph> {
+        if let Some(color_glyph) = self.color_glyphs.get(&id) {
+            return *color_glyph
+        }
+        let glyph_id = GlyphId(id as u16);
+        let color_glyph = if self.owned_font_face.with_ref( | face | face.is_color_glyph(glyph_id)) {
+            let mut painter = ColorLayerPainter::default();
+            self.owned_font_face.with_ref( | face | face.paint_color_glyph(glyph_id, 0, &mut painter));
+            let mut bounds: Option<Rectangle> = None;
+            for (layer_id, _) in painter.layers {
+                if let Ok(glyph) = self.get_glyph_by_id(layer_id) {
+                    if glyph.outline.is_empty() {
+                        continue
+                    }
+                    let b = glyph.bounds;
+                    bounds = Some(if let Some(bounds) = bounds {
+                        Rectangle::new(
+                            Point::new(bounds.p_min.x.min(b.p_min.x), bounds.p_min.y.min(b.p_min.y)),
+                            Point::new(bounds.p_max.x.max(b.p_max.x), bounds.p_max.y.max(b.p_max.y)),
+                        )
+                    } else {b});
+                }
+            }
+            bounds.map( | bounds | CxColorGlyph {source: CxColorGlyphSource::Layers, bounds})
+        }
+        else {
+            self.owned_font_face.with_ref( | face | {
+                // use the largest strike, which is also what gets drawn into the atlas
+                let image = face.glyph_raster_image(glyph_id, u16::MAX)?;
+                if !matches!(image.format, RasterImageFormat::PNG | RasterImageFormat::BitmapPremulBgra32) {
+                    return None
+                }
+                let scale = face.units_per_em() as f64 / image.pixels_per_em as f64;
+                Some(CxColorGlyph {
+                    source: CxColorGlyphSource::Bitmap,
+                    bounds: Rectangle::new(
+                        Point::new(image.x as f64 * scale, image.y as f64 * scale),
+                        Point::new(
+                            (image.x as f64 + image.width as f64) * scale,
+                            (image.y as f64 + image.height as f64) * scale
+                        ),
+                    )
+                })
+            })
+        };
+        self.color_glyphs.insert(id, color_glyph);
+        color_glyph
+    }
 }
@@ -13,7 +13,8 @@ pub use {
         turtle::{Walk, Layout},
         draw_list_2d::{ManyInstances, DrawList2d, RedrawingApi},
         geometry::GeometryQuad2D,
-        makepad_vector::trapezoidator::Trapezoidator,
+        makepad_vector::trapezoidator::{Trapezoidator, FillRule},
+        svg::{SvgDocument, parse_svg_path, close_subpaths, path_bounds},
         makepad_vector::geometry::{AffineTransformation, Transform, Vector, Point},
         makepad_vector::internal_iter::*,
         makepad_vector::path::{PathIterator, PathCommand},
@@ -37,22 +38,7 @@ pub struct CxIconEntry {
 
 struct CxIconPathCommands {
     bounds: Rect,
-    path: Vec<PathCommand>
-}
-
-impl<'a> InternalIterator for &CxIconPathCommands {
-    type Item = PathCommand;
-    fn for_each<F>(self, f: &mut F) -> bool
-    where
-    F: FnMut(PathCommand) -> bool,
-    {
-        for item in &self.path {
-            if !f(item.clone()) {
-                return false
-            }
-        }
-        true
-    }
+    paths: Vec<(FillRule, Vec<PathCommand>)>
 }
 
 #[derive(Clone, Copy, Hash, PartialEq, Eq)]
@@ -123,42 +109,33 @@ impl CxIconAtlas {
     pub fn parse_and_cache_path(&mut self, path_hash: CxIconPathHash, path: &[u8]) -> Option<(CxIconPathHash, Rect)> {
         match parse_svg_path(path) {
             Ok(path) => {
-                let mut min = dvec2(f64::INFINITY, f64::INFINITY);
-                let mut max = dvec2(-f64::INFINITY, -f64::INFINITY);
-                fn bound(p: &Point, min: &mut DVec2, max: &mut DVec2) {
-                    if p.x < min.x {min.x = p.x}
-                    if p.y < min.y {min.y = p.y}
-                    if p.x > max.x {max.x = p.x}
-                    if p.y > max.y {max.y = p.y}
-                }
-                for cmd in &path {
-                    match cmd {
-                        PathCommand::MoveTo(p) => {bound(p, &mut min, &mut max)},
-                        PathCommand::LineTo(p) => {bound(p, &mut min, &mut max)},
-                        PathCommand::QuadraticTo(p1, p) => {
-                            bound(p1, &mut min, &mut max);
-                            bound(p, &mut min, &mut max);
-                        },
-                        PathCommand::CubicTo(p1, p2, p) => {
-                            bound(p1, &mut min, &mut max);
-                            bound(p2, &mut min, &mut max);
-                            bound(p, &mut min, &mut max);
-                        },
-                        PathCommand::Close => ()
-                    }
-                }
-                let bounds = Rect {pos: min, size: max - min};
-                self.paths.insert(path_hash, CxIconPathCommands {
-                    bounds,
-                    path
-                });
-                return Some((path_hash, bounds));
+                self.cache_paths(path_hash, vec![(FillRule::NonZero, close_subpaths(&path))])
             }
             Err(e) => {
                 log!("Error in SVG Path {}", e);
-                return None
+                None
+            }
+        }
+    }
+    
+    fn cache_paths(&mut self, path_hash: CxIconPathHash, paths: Vec<(FillRule, Vec<PathCommand>)>) -> Option<(CxIconPathHash, Rect)> {
+        let mut min = dvec2(f64::INFINITY, f64::INFINITY);
+        let mut max = dvec2(-f64::INFINITY, -f64::INFINITY);
+        for (_, path) in &paths {
+            if let Some((path_min, path_max)) = path_bounds(path) {
+                min = dvec2(min.x.min(path_min.x), min.y.min(path_min.y));
+                max = dvec2(max.x.max(path_max.x), max.y.max(path_max.y));
             }
         }
+        if min.x > max.x {
+            return None
+        }
+        let bounds = Rect {pos: min, size: max - min};
+        self.paths.insert(path_hash, CxIconPathCommands {
+            bounds,
+            paths
+        });
+        Some((path_hash, bounds))
     }
     
     pub fn get_icon_bounds(&mut self, cx: &Cx, path_str: &Rc<String>, svg_dep: &Rc<String>) -> Option<(CxIconPathHash, Rect)> {
@@ -175,29 +152,13 @@ impl CxIconAtlas {
             // lets parse the path range out of the svg file
             match cx.get_dependency(svg_dep.as_str()) {
                 Ok(data)=>{
-                    fn find_path_str(data:&[u8])->Option<&[u8]>{
-                        let pat = "path d=\"".as_bytes();
-                        'outer:for i in 0..data.len(){
-                            for j in 0..pat.len(){
-                                if data[i+j] != pat[j]{
-                                    continue 'outer;
-                                }
-                            }
-                            for k in i+pat.len()..data.len(){
-                                if data[k] == '\"' as u8{
-                                    return Some(&data[i+pat.len()..k])
-                                }
-                            }
+                    match SvgDocument::parse(&data) {
+                        Ok(document) => return self.cache_paths(path_hash, document.fill_paths()),
+                        Err(err) => {
+                            println!("Error in SVG file {}: {}", svg_dep, err);
                             return None
                         }
-                        None
-                    }
-                    if let Some(data) = find_path_str(&data){
-                        return self.parse_and_cache_path(path_hash, data)
                     }
-                    println!("No SVG path tag found in svg file {}",path_str);
-                    return None
-                    
                 }
                 Err(_err)=>{
                     println!("Error in SVG file {}: {}",path_str, _err);
@@ -289,30 +250,26 @@ impl CxIconAtlas {
 impl DrawTrapezoidVector {
     // atlas drawing function used by CxAfterDraw
     fn draw_vector(&mut self, entry: &CxIconEntry, path: &CxIconPathCommands, many: &mut ManyInstances) {
-        let trapezoids = {
-            let mut trapezoids = Vec::new();
-            //log_str(&format!("Serializing char {} {} {} {}", glyphtc.tx1 , cx.fonts_atlas.texture_size.x ,todo.subpixel_x_fract ,atlas_page.dpi_factor));
-            let trapezoidate = self.trapezoidator.trapezoidate(
-                path.map({
-                    //log!("{:?} {:?}", entry.args, entry.pos);
-                    move | cmd | {
-                        let cmd = cmd.transform(
-                            &AffineTransformation::identity()
-                                .translate(Vector::new(entry.args.translate.x, entry.args.translate.y))
-                                .uniform_scale(entry.args.scale)
-                                .translate(Vector::new(entry.pos.x + entry.args.subpixel.x, entry.pos.y + entry.args.subpixel.y))
-                        );
-                        cmd
-                    }
-                }).linearize(entry.args.linearize)
+        let transform = AffineTransformation::identity()
+            .translate(Vector::new(entry.args.translate.x, entry.args.translate.y))
+            .uniform_scale(entry.args.scale)
+            .translate(Vector::new(entry.pos.x + entry.args.subpixel.x, entry.pos.y + entry.args.subpixel.y));
+        let mut trapezoids = Vec::new();
+        for (fill_rule, commands) in &path.paths {
+            let mut commands = commands.clone();
+            for cmd in &mut commands {
+                cmd.transform_mut(&transform);
+            }
+            let trapezoidate = self.trapezoidator.trapezoidate_with_fill_rule(
+                commands.into_iter().into_internal_iter().linearize(entry.args.linearize),
+                *fill_rule
             );
             if let Some(trapezoidate) = trapezoidate {
                 trapezoids.extend_from_internal_iter(
                     trapezoidate
                 );
             }
-            trapezoids
-        };
+        }
         
         for trapezoid in trapezoids {
             self.a_xs = Vec2 {x: trapezoid.xs[0], y: trapezoid.xs[1]};
@@ -419,234 +376,3 @@ impl<'a> Cx2d<'a> {
     
     
 }
-
-fn parse_svg_path(path: &[u8]) -> Result<Vec<PathCommand>, String> {
-    #[derive(Debug)]
-    enum Cmd {
-        Unknown,
-        Move(bool),
-        Hor(bool),
-        Vert(bool),
-        Line(bool),
-        Cubic(bool),
-        Quadratic(bool),
-        Close
-    }
-    impl Default for Cmd {fn default() -> Self {Self::Unknown}}
-    
-    #[derive(Default)]
-    struct ParseState {
-        cmd: Cmd,
-        expect_nums: usize,
-        chain: bool,
-        nums: [f64; 6],
-        num_count: usize,
-        last_pt: Point,
-        out: Vec<PathCommand>,
-        num_state: Option<NumState>
-    }
-    
-    struct NumState {
-        num: f64,
-        mul: f64,
-        has_dot: bool,
-    }
-    
-    impl NumState {
-        fn new_pos(v: f64) -> Self {Self {num: v, mul: 1.0, has_dot: false}}
-        fn new_min() -> Self {Self {num: 0.0, mul: -1.0, has_dot: false}}
-        fn finalize(self) -> f64 {self.num * self.mul}
-        fn add_digit(&mut self, digit: f64) {
-            self.num *= 10.0;
-            self.num += digit;
-            if self.has_dot {
-                self.mul *= 0.1;
-            }
-        }
-    }
-    
-    impl ParseState {
-        fn next_cmd(&mut self, cmd: Cmd) -> Result<(), String> {
-            self.finalize_cmd() ?;
-            self.chain = false;
-            self.expect_nums = match cmd {
-                Cmd::Unknown => panic!(),
-                Cmd::Move(_) => 2,
-                Cmd::Hor(_) => 1,
-                Cmd::Vert(_) => 1,
-                Cmd::Line(_) => 2,
-                Cmd::Cubic(_) => 6,
-                Cmd::Quadratic(_) => 4,
-                Cmd::Close => 0
-            };
-            self.cmd = cmd;
-            Ok(())
-        }
-        
-        fn add_min(&mut self) -> Result<(), String> {
-            if self.expect_nums == self.num_count {
-                self.finalize_cmd() ?;
-            }
-            if self.expect_nums == 0 {
-                return Err(format!("Unexpected minus"));
-            }
-            self.num_state = Some(NumState::new_min());
-            Ok(())
-        }
-        
-        fn add_digit(&mut self, digit: f64) -> Result<(), String> {
-            if let Some(num_state) = &mut self.num_state {
-                num_state.add_digit(digit);
-            }
-            else {
-                if self.expect_nums == self.num_count {
-                    self.finalize_cmd() ?;
-                }
-                if self.expect_nums == 0 {
-                    return Err(format!("Unexpected digit"));
-                }
-                self.num_state = Some(NumState::new_pos(digit))
-            }
-            Ok(())
-        }
-        
-        fn add_dot(&mut self) -> Result<(), String> {
-            if let Some(num_state) = &mut self.num_state {
-                if num_state.has_dot {
-                    return Err(format!("Unexpected ."));
-                }
-                num_state.has_dot = true;
-            }
-            else {
-                return Err(format!("Unexpected ."));
-            }
-            Ok(())
-        }
-        
-        fn finalize_num(&mut self) {
-            if let Some(num_state) = self.num_state.take() {
-                self.nums[self.num_count] = num_state.finalize();
-                self.num_count += 1;
-            }
-        }
-        
-        fn whitespace(&mut self) -> Result<(), String> {
-            self.finalize_num();
-            if self.expect_nums == self.num_count {
-                self.finalize_cmd() ?;
-            }
-            Ok(())
-        }
-        
-        fn finalize_cmd(&mut self) -> Result<(), String> {
-            self.finalize_num();
-            if self.chain && self.num_count == 0 {
-                return Ok(())
-            }
-            if self.expect_nums != self.num_count {
-                return Err(format!("SVG Path command {:?} expected {} points, got {}", self.cmd, self.expect_nums, self.num_count));
-            }
-            match self.cmd {
-                Cmd::Unknown => (),
-                Cmd::Move(abs) => {
-                    if abs {
-                        self.last_pt = Point {x: self.nums[0], y: self.nums[1]};
-                    }
-                    else {
-                        self.last_pt += Vector {x: self.nums[0], y: self.nums[1]};
-                    }
-                    self.out.push(PathCommand::MoveTo(self.last_pt));
-                },
-                Cmd::Hor(abs) => {
-                    if abs {
-                        self.last_pt = Point {x: self.nums[0], y: self.last_pt.y};
-                    }
-                    else {
-                        self.last_pt += Vector {x: self.nums[0], y: 0.0};
-                    }
-                    self.out.push(PathCommand::LineTo(self.last_pt));
-                }
-                Cmd::Vert(abs) => {
-                    if abs {
-                        self.last_pt = Point {x: self.last_pt.x, y: self.nums[0]};
-                    }
-                    else {
-                        self.last_pt += Vector {x: 0.0, y: self.nums[0]};
-                    }
-                    self.out.push(PathCommand::LineTo(self.last_pt));
-                }
-                Cmd::Line(abs) => {
-                    if abs {
-                        self.last_pt = Point {x: self.nums[0], y: self.nums[1]};
-                    }
-                    else {
-                        self.last_pt += Vector {x: self.nums[0], y: self.nums[1]};
-                    }
-                    self.out.push(PathCommand::LineTo(self.last_pt));
-                },
-                Cmd::Cubic(abs) => {
-                    if abs {
-                        self.last_pt = Point {x: self.nums[4], y: self.nums[5]};
-                    }
-                    else {
-                        self.last_pt += Vector {x: self.nums[4], y: self.nums[5]};
-                    }
-                    self.out.push(PathCommand::CubicTo(
-                        Point {x: self.nums[0], y: self.nums[1]},
-                        Point {x: self.nums[2], y: self.nums[3]},
-                        
-                        self.last_pt,
-                    ))
-                },
-                Cmd::Quadratic(abs) => {
-                    if abs {
-                        self.last_pt = Point {x: self.nums[2], y: self.nums[3]};
-                    }
-                    else {
-                        self.last_pt += Vector {x: self.nums[2], y: self.nums[3]};
-                    }
-                    self.out.push(PathCommand::QuadraticTo(
-                        Point {x: self.nums[0], y: self.nums[1]},
-                        self.last_pt
-                    ));
-                }
-                Cmd::Close => {
-                    self.out.push(PathCommand::Close);
-                }
-            }
-            self.num_count = 0;
-            self.chain = true;
-            Ok(())
-        }
-    }
-    
-    let mut state = ParseState::default();
-    for i in 0..path.len() {
-        match path[i] {
-            b'M' => state.next_cmd(Cmd::Move(true)) ?,
-            b'm' => state.next_cmd(Cmd::Move(false)) ?,
-            b'Q' => state.next_cmd(Cmd::Quadratic(true)) ?,
-            b'q' => state.next_cmd(Cmd::Quadratic(false)) ?,
-            b'C' => state.next_cmd(Cmd::Cubic(true)) ?,
-            b'c' => state.next_cmd(Cmd::Cubic(false)) ?,
-            b'H' => state.next_cmd(Cmd::Hor(true)) ?,
-            b'h' => state.next_cmd(Cmd::Hor(false)) ?,
-            b'V' => state.next_cmd(Cmd::Vert(true)) ?,
-            b'v' => state.next_cmd(Cmd::Vert(false)) ?,
-            b'L' => state.next_cmd(Cmd::Line(true)) ?,
-            b'l' => state.next_cmd(Cmd::Line(false)) ?,
-            b'Z' | b'z' => state.next_cmd(Cmd::Close) ?,
-            b'-' => state.add_min() ?,
-            b'0'..=b'9' => state.add_digit((path[i] - b'0') as f64) ?,
-            b'.' => state.add_dot() ?,
-            b',' | b' ' | b'\r' | b'\n' | b'\t' => state.whitespace() ?,
-            x => {
-                return Err(format!("Unexpected character {} - {}", x, x as char))
-            }
-        }
-    }
-    state.finalize_cmd() ?;
-    
-    Ok(state.out)
-}
-
@@ -12,6 +12,8 @@ pub mod font_atlas;
 pub mod geometry;
 pub mod nav;
 pub mod icon_atlas;
+pub mod svg;
+pub mod svg_render;
 mod owned_font_face;
  
 pub use crate::{
@@ -52,6 +54,7 @@ pub use crate::{
         draw_line::DrawLine,
         draw_text::DrawText,
         draw_color::DrawColor,
+        draw_path::{DrawPath, PathStroke, FillRule, LineCap, LineJoin},
     },
     geometry::{
         GeometryGen,
@@ -68,4 +71,5 @@ pub fn live_design(cx: &mut Cx) {
     crate::geometry::geometry_gen::live_design(cx);
     crate::shader::std::live_design(cx);
     crate::shader::draw_trapezoid::live_design(cx);
+    crate::shader::draw_path::live_design(cx);
 }
\ No newline at end of file
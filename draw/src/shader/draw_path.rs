@@ -0,0 +1,417 @@
+use {
+    std::collections::HashMap,
+    crate::{
+        makepad_platform::*,
+        cx_2d::Cx2d,
+        svg::close_subpaths,
+        makepad_vector::{
+            geometry::{AffineTransformation, Point, Transform, Trapezoid},
+            internal_iter::*,
+            path::{LinePathCommand, LinePathIterator, PathCommand, PathIterator},
+            stroker::{dash, StrokeOptions, Stroker},
+            trapezoidator::{TrapezoidEdges, Trapezoidator},
+        },
+        DrawQuad
+    },
+};
+
+pub use crate::makepad_vector::{
+    stroker::{LineCap, LineJoin},
+    trapezoidator::FillRule,
+};
+
+live_design!{
+    DrawPath = {{DrawPath}} {
+
+        fn intersect_line_segment_with_vertical_line(p0: vec2, p1: vec2, x: float) -> vec2 {
+            return vec2(
+                x,
+                mix(p0.y, p1.y, (x - p0.x) / (p1.x - p0.x))
+            );
+        }
+
+        fn intersect_line_segment_with_horizontal_line(p0: vec2, p1: vec2, y: float) -> vec2 {
+            return vec2(
+                mix(p0.x, p1.x, (y - p0.y) / (p1.y - p0.y)),
+                y
+            );
+        }
+
+        fn compute_clamped_right_trapezoid_area(p0: vec2, p1: vec2, p_min: vec2, p_max: vec2) -> float {
+            let x0 = clamp(p0.x, p_min.x, p_max.x);
+            let x1 = clamp(p1.x, p_min.x, p_max.x);
+            if (p0.x < p_min.x && p_min.x < p1.x) {
+                p0 = intersect_line_segment_with_vertical_line(p0, p1, p_min.x);
+            }
+            if (p0.x < p_max.x && p_max.x < p1.x) {
+                p1 = intersect_line_segment_with_vertical_line(p0, p1, p_max.x);
+            }
+            if (p0.y < p_min.y && p_min.y < p1.y) {
+                p0 = intersect_line_segment_with_horizontal_line(p0, p1, p_min.y);
+            }
+            if (p1.y < p_min.y && p_min.y < p0.y) {
+                p1 = intersect_line_segment_with_horizontal_line(p1, p0, p_min.y);
+            }
+            if (p0.y < p_max.y && p_max.y < p1.y) {
+                p1 = intersect_line_segment_with_horizontal_line(p0, p1, p_max.y);
+            }
+            if (p1.y < p_max.y && p_max.y < p0.y) {
+                p0 = intersect_line_segment_with_horizontal_line(p1, p0, p_max.y);
+            }
+            p0 = clamp(p0, p_min, p_max);
+            p1 = clamp(p1, p_min, p_max);
+            let h0 = p_max.y - p0.y;
+            let h1 = p_max.y - p1.y;
+            let a0 = (p0.x - x0) * h0;
+            let a1 = (p1.x - p0.x) * (h0 + h1) * 0.5;
+            let a2 = (x1 - p1.x) * h1;
+            return a0 + a1 + a2;
+        }
+
+        fn pixel(self) -> vec4 {
+            // the trapezoid is in device pixels relative to the pixel aligned rect
+            let p = self.pos * self.rect_size * self.dpi_factor;
+            let p_min = p - 0.5;
+            let p_max = p + 0.5;
+            let x0 = self.path_xs.x;
+            let x1 = self.path_xs.y;
+            let width = max(x1 - x0, 0.0001);
+
+            // a pixel on an edge shared with another trapezoid is drawn only by the
+            // trapezoid that contains its center, so the shapes have no seams
+            if self.path_edges.x < 0.5 && p.x < x0 {
+                return vec4(0.);
+            }
+            if self.path_edges.y < 0.5 && p.x >= x1 {
+                return vec4(0.);
+            }
+            let t = (p.x - x0) / width;
+            if self.path_edges.z < 0.5 && p.y < mix(self.path_ys.x, self.path_ys.y, t) {
+                return vec4(0.);
+            }
+            if self.path_edges.w < 0.5 && p.y >= mix(self.path_ys.z, self.path_ys.w, t) {
+                return vec4(0.);
+            }
+
+            // edges on the outline of the path get analytic coverage, shared
+            // edges are moved to the pixel bounds
+            let xa = x0;
+            if self.path_edges.x < 0.5 {
+                xa = p_min.x;
+            }
+            let xb = x1;
+            if self.path_edges.y < 0.5 {
+                xb = p_max.x;
+            }
+            let ta = (xa - x0) / width;
+            let tb = (xb - x0) / width;
+            let lower0 = vec2(xa, p_min.y);
+            let lower1 = vec2(xb, p_min.y);
+            if self.path_edges.z > 0.5 {
+                lower0 = vec2(xa, mix(self.path_ys.x, self.path_ys.y, ta));
+                lower1 = vec2(xb, mix(self.path_ys.x, self.path_ys.y, tb));
+            }
+            let upper0 = vec2(xa, p_max.y);
+            let upper1 = vec2(xb, p_max.y);
+            if self.path_edges.w > 0.5 {
+                upper0 = vec2(xa, mix(self.path_ys.z, self.path_ys.w, ta));
+                upper1 = vec2(xb, mix(self.path_ys.z, self.path_ys.w, tb));
+            }
+            let lower_area = compute_clamped_right_trapezoid_area(lower0, lower1, p_min, p_max);
+            let upper_area = compute_clamped_right_trapezoid_area(upper0, upper1, p_min, p_max);
+            let coverage = clamp(lower_area - upper_area, 0., 1.);
+            return vec4(self.color.rgb * self.color.a * coverage, self.color.a * coverage);
+        }
+    }
+}
+
+/// How `DrawPath::stroke_abs` outlines a path. The width and dashes are in logical pixels.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PathStroke {
+    pub width: f64,
+    pub line_cap: LineCap,
+    pub line_join: LineJoin,
+    pub miter_limit: f64,
+    /// Alternating dash and gap lengths, an empty list draws a solid line
+    pub dashes: Vec<f64>,
+    pub dash_offset: f64,
+}
+
+impl Default for PathStroke {
+    fn default() -> Self {
+        Self {
+            width: 1.0,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Miter,
+            miter_limit: 4.0,
+            dashes: Vec::new(),
+            dash_offset: 0.0,
+        }
+    }
+}
+
+impl PathStroke {
+    pub fn new(width: f64) -> Self {
+        Self {
+            width,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_cap(self, line_cap: LineCap) -> Self {
+        Self {line_cap, ..self}
+    }
+
+    pub fn with_join(self, line_join: LineJoin) -> Self {
+        Self {line_join, ..self}
+    }
+
+    pub fn with_miter_limit(self, miter_limit: f64) -> Self {
+        Self {miter_limit, ..self}
+    }
+
+    pub fn with_dashes(self, dashes: &[f64], dash_offset: f64) -> Self {
+        Self {dashes: dashes.to_vec(), dash_offset, ..self}
+    }
+}
+
+/// Draws vector paths built from lines, curves and arcs, filled or stroked. The path is
+/// tessellated on the CPU into trapezoids in device pixels, and every trapezoid is drawn as
+/// an instance that computes its own anti-aliased coverage.
+#[derive(Live, LiveHook)]
+#[repr(C)]
+pub struct DrawPath {
+    #[rust] path: Vec<PathCommand>,
+    #[rust] initial_point: Option<Point>,
+    #[rust] is_closed: bool,
+    #[rust] trapezoidator: Trapezoidator,
+    #[rust] stroker: Stroker,
+    #[rust] trapezoids: Vec<(Trapezoid, TrapezoidEdges)>,
+    #[live(true)] pub antialias: bool,
+    #[deref] pub draw_super: DrawQuad,
+    #[live] pub color: Vec4,
+    #[calc] pub path_xs: Vec2,
+    #[calc] pub path_ys: Vec4,
+    #[calc] pub path_edges: Vec4,
+}
+
+// the maximum distance in device pixels between a curve and its line segments
+const TOLERANCE: f64 = 0.1;
+
+impl DrawPath {
+    pub fn clear(&mut self) {
+        self.path.clear();
+        self.initial_point = None;
+        self.is_closed = false;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.path.is_empty()
+    }
+
+    pub fn move_to(&mut self, p: DVec2) {
+        let p = Point::new(p.x, p.y);
+        self.path.push(PathCommand::MoveTo(p));
+        self.initial_point = Some(p);
+        self.is_closed = false;
+    }
+
+    pub fn line_to(&mut self, p: DVec2) {
+        self.begin_subpath(p);
+        self.path.push(PathCommand::LineTo(Point::new(p.x, p.y)));
+    }
+
+    pub fn quad_to(&mut self, p1: DVec2, p: DVec2) {
+        self.begin_subpath(p1);
+        self.path.push(PathCommand::QuadraticTo(Point::new(p1.x, p1.y), Point::new(p.x, p.y)));
+    }
+
+    pub fn cubic_to(&mut self, p1: DVec2, p2: DVec2, p: DVec2) {
+        self.begin_subpath(p1);
+        self.path.push(PathCommand::CubicTo(
+            Point::new(p1.x, p1.y),
+            Point::new(p2.x, p2.y),
+            Point::new(p.x, p.y)
+        ));
+    }
+
+    /// Adds a circular arc from `start_angle` to `end_angle` in radians, going clockwise on screen
+    /// when `end_angle` is larger. Like a canvas arc it is connected to the current point with a line.
+    pub fn arc(&mut self, center: DVec2, radius: f64, start_angle: f64, end_angle: f64) {
+        let point_at = | angle: f64 | center + dvec2(angle.cos(), angle.sin()) * radius;
+        let tangent_at = | angle: f64 | dvec2(-angle.sin(), angle.cos());
+        let start = point_at(start_angle);
+        if self.initial_point.is_some() {
+            self.line_to(start);
+        }
+        else {
+            self.move_to(start);
+        }
+        let delta = end_angle - start_angle;
+        if delta == 0.0 || !delta.is_finite() {
+            return
+        }
+        let segment_count = (delta.abs() / (std::f64::consts::PI / 2.0)).ceil().min(64.0) as usize;
+        let segment_delta = delta / segment_count as f64;
+        let k = 4.0 / 3.0 * (segment_delta / 4.0).tan() * radius;
+        for index in 0..segment_count {
+            let a0 = start_angle + segment_delta * index as f64;
+            let a1 = a0 + segment_delta;
+            let p0 = point_at(a0);
+            let p3 = point_at(a1);
+            self.cubic_to(p0 + tangent_at(a0) * k, p3 - tangent_at(a1) * k, p3);
+        }
+    }
+
+    pub fn close(&mut self) {
+        if self.initial_point.is_some() && !self.is_closed {
+            self.path.push(PathCommand::Close);
+            self.is_closed = true;
+        }
+    }
+
+    pub fn rect(&mut self, rect: Rect) {
+        self.move_to(rect.pos);
+        self.line_to(dvec2(rect.pos.x + rect.size.x, rect.pos.y));
+        self.line_to(rect.pos + rect.size);
+        self.line_to(dvec2(rect.pos.x, rect.pos.y + rect.size.y));
+        self.close();
+    }
+
+    pub fn circle(&mut self, center: DVec2, radius: f64) {
+        self.move_to(dvec2(center.x + radius, center.y));
+        self.arc(center, radius, 0.0, 2.0 * std::f64::consts::PI);
+        self.close();
+    }
+
+    /// Fills the path, in the same coordinates as `draw_abs`. Open subpaths are closed.
+    pub fn fill_abs(&mut self, cx: &mut Cx2d, fill_rule: FillRule) {
+        let dpi_factor = cx.current_dpi_factor();
+        let mut path = close_subpaths(&self.path);
+        for command in &mut path {
+            command.transform_mut(&AffineTransformation::uniform_scaling(dpi_factor));
+        }
+        self.trapezoidate(path.iter().cloned().into_internal_iter().linearize(TOLERANCE), fill_rule);
+        self.draw_trapezoids(cx, dpi_factor);
+    }
+
+    /// Strokes the path, in the same coordinates as `draw_abs`.
+    pub fn stroke_abs(&mut self, cx: &mut Cx2d, stroke: &PathStroke) {
+        let dpi_factor = cx.current_dpi_factor();
+        let mut path = self.path.clone();
+        for command in &mut path {
+            command.transform_mut(&AffineTransformation::uniform_scaling(dpi_factor));
+        }
+        let options = StrokeOptions {
+            width: stroke.width * dpi_factor,
+            line_cap: stroke.line_cap,
+            line_join: stroke.line_join,
+            miter_limit: stroke.miter_limit,
+            tolerance: TOLERANCE,
+        };
+        let mut outline = Vec::new();
+        let lines = path.iter().cloned().into_internal_iter().linearize(TOLERANCE);
+        if stroke.dashes.is_empty() {
+            self.stroker.stroke(lines, &options, &mut outline);
+        }
+        else {
+            let mut dashes = stroke.dashes.clone();
+            for length in &mut dashes {
+                *length *= dpi_factor;
+            }
+            let mut dashed: Vec<LinePathCommand> = Vec::new();
+            dash(lines, &dashes, stroke.dash_offset * dpi_factor, &mut dashed);
+            self.stroker.stroke(dashed.iter().cloned().into_internal_iter(), &options, &mut outline);
+        }
+        // the stroker outputs overlapping polygons that are merged by the non-zero rule
+        self.trapezoidate(outline.iter().cloned().into_internal_iter().linearize(TOLERANCE), FillRule::NonZero);
+        self.draw_trapezoids(cx, dpi_factor);
+    }
+
+    fn trapezoidate(&mut self, path: impl LinePathIterator, fill_rule: FillRule) {
+        self.trapezoids.clear();
+        if let Some(trapezoids) = self.trapezoidator.trapezoidate_with_fill_rule(path, fill_rule) {
+            let out = &mut self.trapezoids;
+            trapezoids.with_edges().for_each(&mut | item | {
+                out.push(item);
+                true
+            });
+        }
+    }
+
+    fn draw_trapezoids(&mut self, cx: &mut Cx2d, dpi_factor: f64) {
+        if self.trapezoids.is_empty() {
+            return
+        }
+        // a vertical side is on the outline unless a trapezoid on the other side touches its middle,
+        // this includes sides of zero height at the tips of shapes
+        let mut by_x0: HashMap<u32, Vec<usize>> = HashMap::new();
+        let mut by_x1: HashMap<u32, Vec<usize>> = HashMap::new();
+        for (index, (trapezoid, _)) in self.trapezoids.iter().enumerate() {
+            by_x0.entry(trapezoid.xs[0].to_bits()).or_default().push(index);
+            by_x1.entry(trapezoid.xs[1].to_bits()).or_default().push(index);
+        }
+        let trapezoids = std::mem::take(&mut self.trapezoids);
+        let is_outline = | neighbours: Option<&Vec<usize>>, y0: f32, y1: f32, side: usize | {
+            let y = (y0 + y1) * 0.5;
+            !neighbours.is_some_and( | neighbours | neighbours.iter().any( | index | {
+                let ys = trapezoids[*index].0.ys;
+                ys[side] <= y && y <= ys[side + 2]
+            }))
+        };
+
+        let many_instances = self.draw_super.many_instances.is_none();
+        if many_instances {
+            self.draw_super.begin_many_instances(cx);
+        }
+        for &(trapezoid, edges) in &trapezoids {
+            let xs = trapezoid.xs;
+            let ys = trapezoid.ys;
+            let left = is_outline(by_x1.get(&xs[0].to_bits()), ys[0], ys[2], 1);
+            let right = is_outline(by_x0.get(&xs[1].to_bits()), ys[1], ys[3], 0);
+
+            // snap the rect to device pixels so the shader sees pixel centers at half pixels
+            let min = dvec2(xs[0] as f64, ys[0].min(ys[1]) as f64) - dvec2(1.0, 1.0);
+            let max = dvec2(xs[1] as f64, ys[2].max(ys[3]) as f64) + dvec2(1.0, 1.0);
+            let min = dvec2(min.x.floor(), min.y.floor());
+            let max = dvec2(max.x.ceil(), max.y.ceil());
+            self.path_xs = vec2(xs[0] - min.x as f32, xs[1] - min.x as f32);
+            self.path_ys = vec4(
+                ys[0] - min.y as f32,
+                ys[1] - min.y as f32,
+                ys[2] - min.y as f32,
+                ys[3] - min.y as f32
+            );
+            self.path_edges = if self.antialias {
+                vec4(
+                    if left {1.0} else {0.0},
+                    if right {1.0} else {0.0},
+                    if edges.lower {1.0} else {0.0},
+                    if edges.upper {1.0} else {0.0}
+                )
+            }
+            else {
+                vec4(0.0, 0.0, 0.0, 0.0)
+            };
+            self.draw_abs(cx, Rect {
+                pos: min / dpi_factor,
+                size: (max - min) / dpi_factor
+            });
+        }
+        if many_instances {
+            self.draw_super.end_many_instances(cx);
+        }
+        self.trapezoids = trapezoids;
+    }
+
+    fn begin_subpath(&mut self, p: DVec2) {
+        match self.initial_point {
+            None => self.move_to(p),
+            Some(initial_point) if self.is_closed => {
+                self.path.push(PathCommand::MoveTo(initial_point));
+                self.is_closed = false;
+            }
+            _ => ()
+        }
+    }
+}
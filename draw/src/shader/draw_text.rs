@@ -2,7 +2,7 @@ use {
     crate::{
         makepad_platform::*,
         turtle::{Walk, Size, Align},
-        font_atlas::{CxFontsAtlasTodo, CxFont, CxFontsAtlas, Font},
+        font_atlas::{CxFontsAtlasTodo, CxFontsAtlas, Font, ShapedGlyph},
         draw_list_2d::ManyInstances,
         geometry::GeometryQuad2D,
         cx_2d::Cx2d
@@ -20,6 +20,7 @@ live_design!{
         uniform curve: float
         
         texture tex: texture2d
+        texture color_tex: texture2d
         
         varying tex_coord1: vec2
         varying tex_coord2: vec2
@@ -60,6 +61,10 @@ live_design!{
             return incol
         }
         fn pixel(self) -> vec4 {
+            if self.is_color > 0.5 {
+                // colour glyphs are stored premultiplied, only the alpha of the text colour applies
+                return self.blend_color(sample2d(self.color_tex, self.tex_coord1.xy) * self.get_color().a);
+            }
             let s = sample2d_rt(self.tex, self.tex_coord1.xy).x;
             s = pow(s, self.curve);
             let col = self.get_color(); 
@@ -89,10 +94,12 @@ pub enum TextWrap {
 }
 
 struct WordIterator<'a> {
-    char_iter: Option<std::str::CharIndices<'a >>,
+    char_iter: Option<std::iter::Enumerate<std::str::CharIndices<'a >>>,
+    char_advances: &'a [f64],
     eval_width: f64,
     word_width: f64,
     word_start: usize,
+    word_char_start: usize,
     last_is_whitespace: bool,
     last_char: char,
     last_index: usize,
@@ -102,38 +109,40 @@ struct WordIterator<'a> {
 struct WordIteratorItem {
     start: usize,
     end: usize,
+    char_start: usize,
     width: f64,
     with_newline: bool
 }
 
 impl<'a> WordIterator<'a> {
-    fn new(char_iter: std::str::CharIndices<'a>, eval_width: f64, font_size_total: f64) -> Self {
+    fn new(char_iter: std::str::CharIndices<'a>, char_advances: &'a [f64], eval_width: f64, font_size_total: f64) -> Self {
         Self {
             eval_width,
-            char_iter: Some(char_iter),
+            char_iter: Some(char_iter.enumerate()),
+            char_advances,
             last_is_whitespace: false,
             word_width: 0.0,
             word_start: 0,
+            word_char_start: 0,
             last_char: '\0',
             last_index: 0,
             font_size_total
         }
     }
-    fn next_word(&mut self, font: &mut CxFont) -> Option<WordIteratorItem> {
+    fn next_word(&mut self) -> Option<WordIteratorItem> {
         if let Some(char_iter) = &mut self.char_iter {
-            while let Some((i, c)) = char_iter.next() {
+            while let Some((char_index, (i, c))) = char_iter.next() {
                 self.last_index = i;
                 self.last_char = c;
                 let ret = WordIteratorItem {
                     start: self.word_start,
                     end: i,
+                    char_start: self.word_char_start,
                     width: self.word_width,
                     with_newline: false
                 };
                 
-                let adv = if let Some(glyph) = font.get_glyph(c) {
-                    glyph.horizontal_metrics.advance_width * self.font_size_total
-                }else {0.0};
+                let adv = self.char_advances.get(char_index).map_or(0.0, | adv | adv * self.font_size_total);
                 
                 if c == '\r' {
                     continue;
@@ -141,6 +150,7 @@ impl<'a> WordIterator<'a> {
                 if c == '\n' {
                     self.last_is_whitespace = false;
                     self.word_start = i;
+                    self.word_char_start = char_index;
                     self.word_width = 0.0;
                     return Some(WordIteratorItem {with_newline: true, end: i, ..ret})
                 }
@@ -150,12 +160,14 @@ impl<'a> WordIterator<'a> {
                 else if self.last_is_whitespace {
                     self.last_is_whitespace = false;
                     self.word_start = i;
+                    self.word_char_start = char_index;
                     self.word_width = adv;
                     return Some(ret);
                 }
                 // this causes a character-based split if the word doesnt fit at all
                 if self.word_width + adv >= self.eval_width {
                     self.word_start = i;
+                    self.word_char_start = char_index;
                     self.word_width = adv;
                     return Some(ret);
                 }
@@ -169,6 +181,7 @@ impl<'a> WordIterator<'a> {
             return Some(WordIteratorItem {
                 start: self.word_start,
                 end: self.last_index + char_bytes_len,
+                char_start: self.word_char_start,
                 width: self.word_width,
                 with_newline: false
             });
@@ -186,6 +199,162 @@ pub enum Overflow {
     #[live] None
 }*/
 
+// A glyph of a shaped line, with its cluster relative to the start of the line.
+struct LineGlyph {
+    font_id: usize,
+    shaped: ShapedGlyph,
+    is_rtl: bool,
+}
+
+// Shapes a line of text into glyphs in visual order. Every run is split into pieces that use
+// the first font of the family which has glyphs for them, so chars missing from the main font
+// come from its fallbacks instead of rendering as boxes.
+fn shape_line(fonts_atlas: &mut CxFontsAtlas, font: &Font, line: &str, glyphs: &mut Vec<LineGlyph>) {
+    let mut rustybuzz_buffer = rustybuzz::UnicodeBuffer::new();
+    // the font, byte range and first char index of every piece of a run
+    let mut pieces: Vec<(usize, std::ops::Range<usize>, usize)> = Vec::new();
+    
+    // This relies on the UBA ("Unicode Bidirectional Algorithm")
+    // (see http://www.unicode.org/reports/tr9/#Basic_Display_Algorithm),
+    // as implemented by `unicode_bidi`, to slice the text into substrings
+    // that can be individually shaped, then assembled visually.
+    let bidi_info = unicode_bidi::BidiInfo::new(line, None);
+    
+    for para in &bidi_info.paragraphs {
+        // Split `line` into "runs" (that differ in their LTR/RTL "level").
+        let (adjusted_levels, runs) = bidi_info.visual_runs(para, para.range.clone());
+        for run_range in runs {
+            let run_level = adjusted_levels[run_range.start];
+            let run_char_index = line[..run_range.start].chars().count();
+            
+            pieces.clear();
+            let mut after_joiner = false;
+            for (char_index, (i, c)) in (run_char_index..).zip(line[run_range.clone()].char_indices()) {
+                let i = run_range.start + i;
+                let font_id = match pieces.last() {
+                    Some(&(last_font_id, _, _)) if after_joiner
+                        || continues_cluster(c)
+                        || (c.is_whitespace() && font_has_char(fonts_atlas, last_font_id, c)) => last_font_id,
+                    _ => if let Some(font_id) = select_font(fonts_atlas, font, c) {font_id} else {return}
+                };
+                match pieces.last_mut() {
+                    Some((last_font_id, range, _)) if *last_font_id == font_id => range.end = i + c.len_utf8(),
+                    _ => pieces.push((font_id, i..i + c.len_utf8(), char_index))
+                }
+                after_joiner = c == '\u{200d}';
+            }
+            // the pieces of a right-to-left run are laid out from right to left as well
+            if run_level.is_rtl() {
+                pieces.reverse();
+            }
+            
+            for (font_id, range, char_index) in &pieces {
+                let cxfont = fonts_atlas.fonts[*font_id].as_mut().unwrap();
+                // FIXME(eddyb) UBA/`unicode_bidi` only offers a LTR/RTL distinction,
+                // even if `rustybuzz` has vertical `Direction`s as well.
+                let (shaped_glyphs, new_rustybuzz_buffer) = cxfont.shape_cache.get_or_compute_glyphs(
+                    (
+                        if run_level.is_rtl() {
+                            rustybuzz::Direction::RightToLeft
+                        } else {
+                            rustybuzz::Direction::LeftToRight
+                        },
+                        &line[range.clone()]
+                    ),
+                    rustybuzz_buffer,
+                    &cxfont.owned_font_face
+                );
+                rustybuzz_buffer = new_rustybuzz_buffer;
+                for shaped in shaped_glyphs {
+                    glyphs.push(LineGlyph {
+                        font_id: *font_id,
+                        shaped: ShapedGlyph {cluster: char_index + shaped.cluster, ..*shaped},
+                        is_rtl: run_level.is_rtl(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+// Returns the first loaded font of the family that has a glyph for c, or the main font if
+// none of them do.
+fn select_font(fonts_atlas: &CxFontsAtlas, font: &Font, c: char) -> Option<usize> {
+    let font_id = font.font_id.filter( | font_id | fonts_atlas.fonts[*font_id].is_some())?;
+    Some(
+        std::iter::once(font_id)
+            .chain(font.fallback_font_ids.iter().copied())
+            .find( | font_id | font_has_char(fonts_atlas, *font_id, c))
+            .unwrap_or(font_id)
+    )
+}
+
+fn font_has_char(fonts_atlas: &CxFontsAtlas, font_id: usize, c: char) -> bool {
+    fonts_atlas.fonts[font_id].as_ref().is_some_and( | cxfont | {
+        cxfont.owned_font_face.with_ref( | face | face.glyph_index(c).is_some())
+    })
+}
+
+// Chars that are part of the cluster of the char in front of them, which have to be shaped
+// with the same font, like combining marks and the modifiers of emoji sequences.
+fn continues_cluster(c: char) -> bool {
+    c == '\u{200d}' // zero width joiner
+        || ('\u{fe00}'..='\u{fe0f}').contains(&c) // variation selectors
+        || ('\u{1f3fb}'..='\u{1f3ff}').contains(&c) // skin tone modifiers
+        || ('\u{e0020}'..='\u{e007f}').contains(&c) // tags
+        || unicode_bidi::bidi_class(c) == unicode_bidi::BidiClass::NSM // combining marks
+}
+
+// Computes the advance of every char in text, in units of the main font. The chars of a
+// cluster that was shaped into a single glyph, like a ligature, share the advance of that
+// glyph evenly.
+fn shaped_char_advances(fonts_atlas: &mut CxFontsAtlas, font: &Font, text: &str) -> Vec<f64> {
+    let mut advances = vec![0.0; text.chars().count()];
+    let units_per_em = if let Some(cxfont) = font.font_id.and_then( | font_id | fonts_atlas.fonts[font_id].as_ref()) {
+        cxfont.ttf_font.units_per_em
+    } else {
+        return advances
+    };
+    let mut glyphs = Vec::new();
+    shape_line(fonts_atlas, font, text, &mut glyphs);
+    for glyph in glyphs {
+        let scale = units_per_em / fonts_atlas.fonts[glyph.font_id].as_ref().unwrap().ttf_font.units_per_em;
+        let len = glyph.shaped.cluster_len.max(1);
+        for advance in advances.iter_mut().skip(glyph.shaped.cluster).take(len) {
+            *advance += glyph.shaped.x_advance * scale / len as f64;
+        }
+    }
+    advances
+}
+
+// A drawn glyph as seen by the cursor and selection queries
+struct CaretGlyph {
+    x: f64,
+    y: f64,
+    advance: f64,
+    char_index: usize,
+    char_count: usize,
+    is_rtl: bool,
+}
+
+impl CaretGlyph {
+    fn char_end(&self) -> usize {
+        self.char_index + self.char_count
+    }
+    
+    // The x position of the caret at the given (fractional) char offset into the cluster. The
+    // chars of a ligature split its advance evenly.
+    fn caret_x(&self, char_offset: f64) -> f64 {
+        let t = char_offset / self.char_count as f64;
+        if self.is_rtl {
+            self.x + self.advance * (1.0 - t)
+        }
+        else {
+            self.x + self.advance * t
+        }
+    }
+}
+
 pub struct TextGeom {
     pub eval_width: f64,
     pub eval_height: f64,
@@ -217,6 +386,10 @@ pub struct DrawText {
     #[calc] pub delta: Vec2,
     #[calc] pub font_size: f32,
     #[calc] pub advance: f32,
+    #[calc] pub char_index: f32,
+    // the number of chars in the cluster of this glyph, negative for right-to-left runs
+    #[calc] pub char_count: f32,
+    #[calc] pub is_color: f32,
 }
 
 impl LiveHook for DrawText {
@@ -231,21 +404,21 @@ impl LiveHook for DrawText {
 impl DrawText {
     
     pub fn draw(&mut self, cx: &mut Cx2d, pos: DVec2, val: &str) {
-        self.draw_inner(cx, pos, val, &mut *cx.fonts_atlas_rc.clone().0.borrow_mut());
+        self.draw_inner(cx, pos, val, 0, &mut *cx.fonts_atlas_rc.clone().0.borrow_mut());
         if self.many_instances.is_some() {
             self.end_many_instances(cx)
         }
     }
     
     pub fn draw_rel(&mut self, cx: &mut Cx2d, pos: DVec2, val: &str) {
-        self.draw_inner(cx, pos + cx.turtle().origin(), val, &mut *cx.fonts_atlas_rc.clone().0.borrow_mut());
+        self.draw_inner(cx, pos + cx.turtle().origin(), val, 0, &mut *cx.fonts_atlas_rc.clone().0.borrow_mut());
         if self.many_instances.is_some() {
             self.end_many_instances(cx)
         }
     }
     
     pub fn draw_abs(&mut self, cx: &mut Cx2d, pos: DVec2, val: &str) {
-        self.draw_inner(cx, pos, val, &mut *cx.fonts_atlas_rc.clone().0.borrow_mut());
+        self.draw_inner(cx, pos, val, 0, &mut *cx.fonts_atlas_rc.clone().0.borrow_mut());
         if self.many_instances.is_some() {
             self.end_many_instances(cx)
         }
@@ -276,11 +449,14 @@ impl DrawText {
     
     pub fn update_draw_call_vars(&mut self, font_atlas: &CxFontsAtlas) {
         self.draw_vars.texture_slots[0] = Some(font_atlas.texture.clone());
+        self.draw_vars.texture_slots[1] = Some(font_atlas.color_texture.clone());
         self.draw_vars.user_uniforms[0] = self.text_style.brightness;
         self.draw_vars.user_uniforms[1] = self.text_style.curve;
     }
     
-    fn draw_inner(&mut self, cx: &mut Cx2d, pos: DVec2, chunk: &str, fonts_atlas: &mut CxFontsAtlas) {
+    // char_offset is the index of the first char of chunk in the text being drawn, so the
+    // instances can be mapped back to chars by the cursor and selection queries.
+    fn draw_inner(&mut self, cx: &mut Cx2d, pos: DVec2, chunk: &str, char_offset: usize, fonts_atlas: &mut CxFontsAtlas) {
         if !self.draw_vars.can_instance()
             || pos.x.is_nan()
             || pos.y.is_nan()
@@ -300,142 +476,125 @@ impl DrawText {
         if walk_x.is_infinite() || walk_x.is_nan() {
             return
         }
-        //let mut char_offset = char_offset;
-        
         if !self.many_instances.is_some() {
             self.begin_many_instances_internal(cx, fonts_atlas);
         }
         
-        let cxfont = fonts_atlas.fonts[font_id].as_mut().unwrap();
         let dpi_factor = cx.current_dpi_factor();
         
-        let atlas_page_id = cxfont.get_atlas_page_id(dpi_factor, self.text_style.font_size);
-        
-        let font = &mut cxfont.ttf_font;
-        let owned_font_face = &cxfont.owned_font_face;
-        
-        let font_size_logical = self.text_style.font_size * 96.0 / (72.0 * font.units_per_em);
-        let font_size_pixels = font_size_logical * dpi_factor;
-        
-        let atlas_page = &mut cxfont.atlas_pages[atlas_page_id];
-        
         let mi = if let Some(mi) = &mut self.many_instances {mi} else {return};
         let zbias_step = 0.00001;
         let mut char_depth = self.draw_depth;
         
-        let mut rustybuzz_buffer = rustybuzz::UnicodeBuffer::new();
-        
-        // This relies on the UBA ("Unicode Bidirectional Algorithm")
-        // (see http://www.unicode.org/reports/tr9/#Basic_Display_Algorithm),
-        // as implemented by `unicode_bidi`, to slice the text into substrings
-        // that can be individually shaped, then assembled visually.
-        let bidi_info = unicode_bidi::BidiInfo::new(chunk, None);
+        let mut glyphs = Vec::new();
+        shape_line(fonts_atlas, &self.text_style.font, chunk, &mut glyphs);
         
-        // NOTE(eddyb) the caller of `draw_inner` has already processed the text,
-        // such that `chunk` won't contain e.g. any `\n`.
-        if bidi_info.paragraphs.len() == 1 {
-            let runs_with_level_and_range = {
-                let para = &bidi_info.paragraphs[0];
-                // Split `chunk` into "runs" (that differ in their LTR/RTL "level").
-                let (adjusted_levels, runs) = bidi_info.visual_runs(para, para.range.clone());
-                runs.into_iter().map(move | run_range | (adjusted_levels[run_range.start], run_range))
+        for LineGlyph {font_id, shaped, is_rtl} in glyphs {
+            let glyph_id = shaped.id;
+            let cxfont = fonts_atlas.fonts[font_id].as_mut().unwrap();
+            let atlas_page_id = cxfont.get_atlas_page_id(dpi_factor, self.text_style.font_size);
+            
+            let font_size_logical = self.text_style.font_size * 96.0 / (72.0 * cxfont.ttf_font.units_per_em);
+            let font_size_pixels = font_size_logical * dpi_factor;
+            
+            let color_glyph = cxfont.get_color_glyph(glyph_id);
+            let bounds = if let Some(color_glyph) = &color_glyph {
+                color_glyph.bounds
+            }
+            else {
+                cxfont.get_glyph_by_id(glyph_id).unwrap().bounds
             };
             
-            for (run_level, run_range) in runs_with_level_and_range {
-                // FIXME(eddyb) UBA/`unicode_bidi` only offers a LTR/RTL distinction,
-                // even if `rustybuzz` has vertical `Direction`s as well.
-                let (glyph_ids, new_rustybuzz_buffer) = cxfont
-                    .shape_cache
-                    .get_or_compute_glyph_ids(
-                    (
-                            if run_level.is_rtl() {
-                                rustybuzz::Direction::RightToLeft
-                            } else {
-                                rustybuzz::Direction::LeftToRight
-                            },
-                            &bidi_info.text[run_range]
-                        ),
-                        rustybuzz_buffer,
-                        owned_font_face
-                    );
-                rustybuzz_buffer = new_rustybuzz_buffer;
-                for &glyph_id in glyph_ids {
-                    let glyph = owned_font_face.with_ref(|face| font.get_glyph_by_id(face, glyph_id).unwrap());
-                    
-                    let advance = glyph.horizontal_metrics.advance_width * font_size_logical * self.font_scale;
-                    
-                    // snap width/height to pixel granularity
-                    let w = ((glyph.bounds.p_max.x - glyph.bounds.p_min.x) * font_size_pixels).ceil() + 1.0;
-                    let h = ((glyph.bounds.p_max.y - glyph.bounds.p_min.y) * font_size_pixels).ceil() + 1.0;
-                    
-                    // this one needs pixel snapping
-                    let min_pos_x = walk_x + font_size_logical * glyph.bounds.p_min.x;
-                    let min_pos_y = pos.y - font_size_logical * glyph.bounds.p_min.y + self.text_style.font_size * self.text_style.top_drop;
-                    
-                    // compute subpixel shift
-                    let subpixel_x_fract = min_pos_x - (min_pos_x * dpi_factor).floor() / dpi_factor;
-                    let subpixel_y_fract = min_pos_y - (min_pos_y * dpi_factor).floor() / dpi_factor;
-                    // scale and snap it
-                    // only use a subpixel id for small fonts
-                    let subpixel_id = if self.text_style.font_size>32.0 {
-                        0
-                    }
-                    else { // subtle 64 index subpixel id
-                        ((subpixel_y_fract * dpi_factor * 7.0) as usize) << 3 |
-                        (subpixel_x_fract * dpi_factor * 7.0) as usize
-                    };
-                    
-                    let subpixel_map = if let Some(tc) = atlas_page.atlas_glyphs.get_mut(&glyph_id){
-                        tc
-                    }
-                    else{
-                        atlas_page.atlas_glyphs.insert(glyph_id, [None; crate::font_atlas::ATLAS_SUBPIXEL_SLOTS]);
-                        atlas_page.atlas_glyphs.get_mut(&glyph_id).unwrap()
-                    };
-                    
-                    let tc = if let Some(tc) = &subpixel_map[subpixel_id]{
-                        tc
-                    }
-                    else {
-                        // see if we can fit it
-                        // allocate slot
-                        fonts_atlas.alloc.todo.push(CxFontsAtlasTodo {
-                            subpixel_x_fract,
-                            subpixel_y_fract,
-                            font_id,
-                            atlas_page_id,
-                            glyph_id,
-                            subpixel_id
-                        });
-                        
-                        subpixel_map[subpixel_id] = Some(
-                            fonts_atlas.alloc.alloc_atlas_glyph(w, h)
-                        );
-                        subpixel_map[subpixel_id].as_ref().unwrap()
-                    };
-                    
-                    let delta_x = font_size_logical * self.font_scale * glyph.bounds.p_min.x - subpixel_x_fract;
-                    let delta_y = -font_size_logical * self.font_scale * glyph.bounds.p_min.y + self.text_style.font_size * self.font_scale * self.text_style.top_drop - subpixel_y_fract;
-                    // give the callback a chance to do things
-                    //et scaled_min_pos_x = walk_x + delta_x;
-                    //let scaled_min_pos_y = pos.y - delta_y;
-                    self.font_t1 = tc.t1;
-                    self.font_t2 = tc.t2;
-                    self.rect_pos = dvec2(walk_x + delta_x, pos.y + delta_y).into();
-                    self.rect_size = dvec2(w * self.font_scale / dpi_factor, h * self.font_scale / dpi_factor).into();
-                    self.char_depth = char_depth;
-                    self.delta.x = delta_x as f32;
-                    self.delta.y = delta_y as f32;
-                    self.font_size = self.text_style.font_size as f32;
-                    self.advance = advance as f32; //char_offset as f32;
-                    char_depth += zbias_step;
-                    mi.instances.extend_from_slice(self.draw_vars.as_slice());
-                    walk_x += advance;
-                }
+            let atlas_page = &mut cxfont.atlas_pages[atlas_page_id];
+            
+            let advance = shaped.x_advance * font_size_logical * self.font_scale;
+            let offset_x = shaped.x_offset * font_size_logical * self.font_scale;
+            let offset_y = -shaped.y_offset * font_size_logical * self.font_scale;
+            
+            // snap width/height to pixel granularity
+            let w = ((bounds.p_max.x - bounds.p_min.x) * font_size_pixels).ceil() + 1.0;
+            let h = ((bounds.p_max.y - bounds.p_min.y) * font_size_pixels).ceil() + 1.0;
+            
+            // this one needs pixel snapping
+            let min_pos_x = walk_x + offset_x + font_size_logical * bounds.p_min.x;
+            let min_pos_y = pos.y + offset_y - font_size_logical * bounds.p_min.y + self.text_style.font_size * self.text_style.top_drop;
+            
+            // compute subpixel shift, colour glyphs are rasterized once without one
+            let (subpixel_x_fract, subpixel_y_fract) = if color_glyph.is_some() {
+                (0.0, 0.0)
             }
+            else {
+                (
+                    min_pos_x - (min_pos_x * dpi_factor).floor() / dpi_factor,
+                    min_pos_y - (min_pos_y * dpi_factor).floor() / dpi_factor
+                )
+            };
+            // scale and snap it
+            // only use a subpixel id for small fonts
+            let subpixel_id = if self.text_style.font_size>32.0 || color_glyph.is_some() {
+                0
+            }
+            else { // subtle 64 index subpixel id
+                ((subpixel_y_fract * dpi_factor * 7.0) as usize) << 3 |
+                (subpixel_x_fract * dpi_factor * 7.0) as usize
+            };
+            
+            let subpixel_map = if let Some(tc) = atlas_page.atlas_glyphs.get_mut(&glyph_id){
+                tc
+            }
+            else{
+                atlas_page.atlas_glyphs.insert(glyph_id, [None; crate::font_atlas::ATLAS_SUBPIXEL_SLOTS]);
+                atlas_page.atlas_glyphs.get_mut(&glyph_id).unwrap()
+            };
+            
+            let tc = if let Some(tc) = &subpixel_map[subpixel_id]{
+                tc
+            }
+            else {
+                // see if we can fit it
+                // allocate slot
+                let alloc = if color_glyph.is_some() {
+                    &mut fonts_atlas.color_alloc
+                }
+                else {
+                    &mut fonts_atlas.alloc
+                };
+                alloc.todo.push(CxFontsAtlasTodo {
+                    subpixel_x_fract,
+                    subpixel_y_fract,
+                    font_id,
+                    atlas_page_id,
+                    glyph_id,
+                    subpixel_id
+                });
+                
+                subpixel_map[subpixel_id] = Some(
+                    alloc.alloc_atlas_glyph(w, h)
+                );
+                subpixel_map[subpixel_id].as_ref().unwrap()
+            };
+            
+            // the shaper offset is part of delta, so rect_pos - delta stays the pen position
+            let delta_x = offset_x + font_size_logical * self.font_scale * bounds.p_min.x - subpixel_x_fract;
+            let delta_y = offset_y - font_size_logical * self.font_scale * bounds.p_min.y + self.text_style.font_size * self.font_scale * self.text_style.top_drop - subpixel_y_fract;
+            self.font_t1 = tc.t1;
+            self.font_t2 = tc.t2;
+            self.rect_pos = dvec2(walk_x + delta_x, pos.y + delta_y).into();
+            self.rect_size = dvec2(w * self.font_scale / dpi_factor, h * self.font_scale / dpi_factor).into();
+            self.char_depth = char_depth;
+            self.delta.x = delta_x as f32;
+            self.delta.y = delta_y as f32;
+            self.font_size = self.text_style.font_size as f32;
+            self.advance = advance as f32;
+            self.char_index = (char_offset + shaped.cluster) as f32;
+            self.char_count = if is_rtl {-(shaped.cluster_len as f32)} else {shaped.cluster_len as f32};
+            self.is_color = if color_glyph.is_some() {1.0} else {0.0};
+            char_depth += zbias_step;
+            mi.instances.extend_from_slice(self.draw_vars.as_slice());
+            walk_x += advance;
         }
-        
     }
+    
     pub fn compute_geom(&self, cx: &Cx2d, walk: Walk, text: &str) -> Option<TextGeom> {
         self.compute_geom_inner(cx, walk, text, &mut *cx.fonts_atlas_rc.0.borrow_mut())
     }
@@ -462,35 +621,34 @@ impl DrawText {
                     0.0
                 };
                 
+                let char_advances = shaped_char_advances(fonts_atlas, &self.text_style.font, text);
                 let mut measured_width = 0.0;
                 let mut ellip_pt = None;
-                for (i, c) in text.chars().enumerate() {
+                for ((i, _), adv) in text.char_indices().zip(char_advances) {
                     
                     if measured_width + ellip_width * 3.0 < eval_width {
                         ellip_pt = Some((i, measured_width, 3));
                     }
-                    if let Some(glyph) = fonts_atlas.fonts[font_id].as_mut().unwrap().get_glyph(c) {
-                        let adv = glyph.horizontal_metrics.advance_width * font_size_logical * self.font_scale;
-                        // ok so now what.
-                        if measured_width + adv >= eval_width { // we have to drop back to ellip_pt
-                            // if we don't have an ellip_pt, set it to 0
-                            if ellip_pt.is_none() {
-                                let dots = if ellip_width * 3.0 < eval_width {3}
-                                else if ellip_width * 2.0 < eval_width {2}
-                                else if ellip_width < eval_width {1}
-                                else {0};
-                                ellip_pt = Some((0, 0.0, dots));
-                            }
-                            return Some(TextGeom {
-                                eval_width,
-                                eval_height,
-                                measured_width: ellip_pt.unwrap().1 + ellip_width,
-                                measured_height: line_height,
-                                ellip_pt
-                            })
+                    let adv = adv * font_size_logical * self.font_scale;
+                    // ok so now what.
+                    if measured_width + adv >= eval_width { // we have to drop back to ellip_pt
+                        // if we don't have an ellip_pt, set it to 0
+                        if ellip_pt.is_none() {
+                            let dots = if ellip_width * 3.0 < eval_width {3}
+                            else if ellip_width * 2.0 < eval_width {2}
+                            else if ellip_width < eval_width {1}
+                            else {0};
+                            ellip_pt = Some((0, 0.0, dots));
                         }
-                        measured_width += adv;
+                        return Some(TextGeom {
+                            eval_width,
+                            eval_height,
+                            measured_width: ellip_pt.unwrap().1 + ellip_width,
+                            measured_height: line_height,
+                            ellip_pt
+                        })
                     }
+                    measured_width += adv;
                 }
                 
                 Some(TextGeom {
@@ -506,8 +664,9 @@ impl DrawText {
                 let mut measured_width = 0.0;
                 let mut measured_height = line_height;
                 
-                let mut iter = WordIterator::new(text.char_indices(), eval_width, font_size_logical * self.font_scale);
-                while let Some(word) = iter.next_word(fonts_atlas.fonts[font_id].as_mut().unwrap()) {
+                let char_advances = shaped_char_advances(fonts_atlas, &self.text_style.font, text);
+                let mut iter = WordIterator::new(text.char_indices(), &char_advances, eval_width, font_size_logical * self.font_scale);
+                while let Some(word) = iter.next_word() {
                     if measured_width + word.width >= eval_width {
                         measured_height += line_height * self.text_style.line_spacing;
                         measured_width = word.width;
@@ -532,17 +691,15 @@ impl DrawText {
             }
             TextWrap::Line => {
                 let mut max_width = 0.0;
-                let mut measured_width = 0.0;
                 let mut measured_height = line_height;
                 
-                for c in text.chars() {
-                    if c == '\n' {
+                for (i, line) in text.split('\n').enumerate() {
+                    if i > 0 {
                         measured_height += line_height * self.text_style.line_spacing;
                     }
-                    if let Some(glyph) = fonts_atlas.fonts[font_id].as_mut().unwrap().get_glyph(c) {
-                        let adv = glyph.horizontal_metrics.advance_width * font_size_logical * self.font_scale;
-                        measured_width += adv;
-                    }
+                    let measured_width: f64 = shaped_char_advances(fonts_atlas, &self.text_style.font, line)
+                        .iter()
+                        .sum::<f64>() * font_size_logical * self.font_scale;
                     if measured_width > max_width {
                         max_width = measured_width;
                     }
@@ -596,8 +753,8 @@ impl DrawText {
                             height: Size::Fixed(height)
                         });
                         
-                        self.draw_inner(cx, rect.pos + dvec2(0.0, y_align), &text[0..ellip], fonts_atlas);
-                        self.draw_inner(cx, rect.pos + dvec2(at_x, y_align), &"..."[0..dots], fonts_atlas);
+                        self.draw_inner(cx, rect.pos + dvec2(0.0, y_align), &text[0..ellip], 0, fonts_atlas);
+                        self.draw_inner(cx, rect.pos + dvec2(at_x, y_align), &"..."[0..dots], text[0..ellip].chars().count(), fonts_atlas);
                     }
                     else { // we might have space to h-align
                         let rect = cx.walk_turtle(Walk {
@@ -613,7 +770,7 @@ impl DrawText {
                             )
                         });
                         let x_align = (geom.eval_width - geom.measured_width) * align.x;
-                        self.draw_inner(cx, rect.pos + dvec2(x_align, y_align), text, fonts_atlas);
+                        self.draw_inner(cx, rect.pos + dvec2(x_align, y_align), text, 0, fonts_atlas);
                     }
                 }
                 TextWrap::Word => {
@@ -628,13 +785,14 @@ impl DrawText {
                     });
                     let mut pos = dvec2(0.0, 0.0);
                     
-                    let mut iter = WordIterator::new(text.char_indices(), geom.eval_width, font_size_logical * self.font_scale);
-                    while let Some(word) = iter.next_word(fonts_atlas.fonts[font_id].as_mut().unwrap()) {
+                    let char_advances = shaped_char_advances(fonts_atlas, &self.text_style.font, text);
+                    let mut iter = WordIterator::new(text.char_indices(), &char_advances, geom.eval_width, font_size_logical * self.font_scale);
+                    while let Some(word) = iter.next_word() {
                         if pos.x + word.width >= geom.eval_width {
                             pos.y += line_height * self.text_style.line_spacing;
                             pos.x = 0.0;
                         }
-                        self.draw_inner(cx, rect.pos + pos, &text[word.start..word.end], fonts_atlas);
+                        self.draw_inner(cx, rect.pos + pos, &text[word.start..word.end], word.char_start, fonts_atlas);
                         pos.x += word.width;
                         
                         if word.with_newline {
@@ -654,9 +812,11 @@ impl DrawText {
                     });
                     // lets do our y alignment
                     let mut ypos = 0.0;
+                    let mut char_offset = 0;
                     for line in text.split('\n') {
-                        self.draw_inner(cx, rect.pos + dvec2(0.0, y_align + ypos), line, fonts_atlas);
+                        self.draw_inner(cx, rect.pos + dvec2(0.0, y_align + ypos), line, char_offset, fonts_atlas);
                         ypos += line_height * self.text_style.line_spacing;
+                        char_offset += line.chars().count() + 1;
                     }
                     
                 }
@@ -667,124 +827,123 @@ impl DrawText {
         }
     }
     
-    pub fn closest_offset(&self, cx: &Cx, pos: DVec2) -> Option<usize> {
+    // Reads back the pen position, advance and cluster of every glyph drawn in our area.
+    fn get_caret_glyphs(&self, cx: &Cx) -> Vec<CaretGlyph> {
         let area = &self.draw_vars.area;
         
         if !area.is_valid(cx) {
-            return None
+            return Vec::new()
         }
-
-        let line_spacing = self.get_line_spacing();
+        
         let rect_pos = area.get_read_ref(cx, live_id!(rect_pos), ShaderTy::Vec2).unwrap();
         let delta = area.get_read_ref(cx, live_id!(delta), ShaderTy::Vec2).unwrap();
         let advance = area.get_read_ref(cx, live_id!(advance), ShaderTy::Float).unwrap();
-
-        let mut last_y = None;
+        let char_index = area.get_read_ref(cx, live_id!(char_index), ShaderTy::Float).unwrap();
+        let char_count = area.get_read_ref(cx, live_id!(char_count), ShaderTy::Float).unwrap();
+        
+        let mut glyphs = Vec::with_capacity(rect_pos.repeat);
         for i in 0..rect_pos.repeat {
             let index = rect_pos.stride * i;
-            let x = rect_pos.buffer[index + 0] as f64 - delta.buffer[index + 0] as f64;
-            let y = rect_pos.buffer[index + 1] - delta.buffer[index + 1];
-            if last_y.is_none() {last_y = Some(y)}
-            let advance = advance.buffer[index + 0] as f64;
-            if i > 0 && y > last_y.unwrap() && pos.y < last_y.unwrap() as f64 + line_spacing as f64 {
-                return Some(i - 1)
-            }
-            if pos.x < x + advance * 0.5 && pos.y < y as f64 + line_spacing as f64 {
-                return Some(i)
-            }
-            last_y = Some(y)
+            let char_count = char_count.buffer[index] as isize;
+            glyphs.push(CaretGlyph {
+                x: rect_pos.buffer[index] as f64 - delta.buffer[index] as f64,
+                y: rect_pos.buffer[index + 1] as f64 - delta.buffer[index + 1] as f64,
+                advance: advance.buffer[index] as f64,
+                char_index: char_index.buffer[index] as usize,
+                char_count: char_count.unsigned_abs().max(1),
+                is_rtl: char_count < 0,
+            });
         }
-        return Some(rect_pos.repeat);
-        
+        glyphs
     }
     
-    pub fn get_selection_rects(&self, cx: &Cx, start: usize, end: usize, shift: DVec2, pad: DVec2) -> Vec<Rect> {
-        let area = &self.draw_vars.area;
-        
-        if !area.is_valid(cx) {
-            return Vec::new();
+    pub fn closest_offset(&self, cx: &Cx, pos: DVec2) -> Option<usize> {
+        if !self.draw_vars.area.is_valid(cx) {
+            return None
         }
         
-        let rect_pos = area.get_read_ref(cx, live_id!(rect_pos), ShaderTy::Vec2).unwrap();
-        let delta = area.get_read_ref(cx, live_id!(delta), ShaderTy::Vec2).unwrap();
-        let advance = area.get_read_ref(cx, live_id!(advance), ShaderTy::Float).unwrap();
+        let line_spacing = self.get_line_spacing();
+        let glyphs = self.get_caret_glyphs(cx);
         
-        if rect_pos.repeat == 0 || start >= rect_pos.repeat{
-            return Vec::new();
+        let mut last: Option<&CaretGlyph> = None;
+        for glyph in &glyphs {
+            if let Some(last) = last {
+                if glyph.y > last.y && pos.y < last.y + line_spacing {
+                    return Some(last.char_end() - 1)
+                }
+            }
+            if pos.y < glyph.y + line_spacing {
+                // walk the chars of the cluster in visual order
+                for i in 0..glyph.char_count {
+                    let i = if glyph.is_rtl {glyph.char_count - 1 - i} else {i};
+                    if pos.x < glyph.caret_x(i as f64 + 0.5) {
+                        return Some(glyph.char_index + i + if glyph.is_rtl {1} else {0})
+                    }
+                }
+            }
+            last = Some(glyph);
         }
-        // alright now we go and walk from start to end and collect our selection rects
-        
-        let index = start * rect_pos.stride;
-        let start_x = rect_pos.buffer[index + 0] - delta.buffer[index + 0]; // + advance.buffer[index + 0] * pos;
-        let start_y = rect_pos.buffer[index + 1] - delta.buffer[index + 1];
+        Some(glyphs.iter().map( | glyph | glyph.char_end()).max().unwrap_or(0))
+    }
+    
+    pub fn get_selection_rects(&self, cx: &Cx, start: usize, end: usize, shift: DVec2, pad: DVec2) -> Vec<Rect> {
         let line_spacing = self.get_line_spacing();
-        let mut last_y = start_y;
-        let mut min_x = start_x;
-        let mut last_x = start_x;
-        let mut last_advance = advance.buffer[index + 0];
         let mut out = Vec::new();
-        for index in start..end {
-            if index >= rect_pos.repeat{
-                break;
+        // the y, min_x and max_x of the selection on the current line
+        let mut line: Option<(f64, f64, f64)> = None;
+        for glyph in self.get_caret_glyphs(cx) {
+            let sel_start = start.max(glyph.char_index);
+            let sel_end = end.min(glyph.char_end());
+            if sel_start >= sel_end {
+                continue
             }
-            let index = index * rect_pos.stride;
-            let end_x = rect_pos.buffer[index + 0] - delta.buffer[index + 0];
-            let end_y = rect_pos.buffer[index + 1] - delta.buffer[index + 1];
-            last_advance = advance.buffer[index + 0];
-            if end_y > last_y { // emit rect
-                out.push(Rect {
-                    pos: dvec2(min_x as f64, last_y as f64) + shift,
-                    size: dvec2((last_x - min_x + last_advance) as f64, line_spacing) + pad
-                });
-                min_x = end_x;
-                last_y = end_y;
+            let x1 = glyph.caret_x((sel_start - glyph.char_index) as f64);
+            let x2 = glyph.caret_x((sel_end - glyph.char_index) as f64);
+            let (min_x, max_x) = (x1.min(x2), x1.max(x2));
+            match &mut line {
+                Some((y, line_min_x, line_max_x)) if *y == glyph.y => {
+                    *line_min_x = line_min_x.min(min_x);
+                    *line_max_x = line_max_x.max(max_x);
+                }
+                _ => {
+                    if let Some((y, min_x, max_x)) = line {
+                        out.push(Rect {
+                            pos: dvec2(min_x, y) + shift,
+                            size: dvec2(max_x - min_x, line_spacing) + pad
+                        });
+                    }
+                    line = Some((glyph.y, min_x, max_x));
+                }
             }
-            last_x = end_x;
         }
-        out.push(Rect {
-            pos: dvec2(min_x as f64, last_y as f64) + shift,
-            size: dvec2((last_x - min_x + last_advance) as f64, line_spacing) + pad
-        });
+        if let Some((y, min_x, max_x)) = line {
+            out.push(Rect {
+                pos: dvec2(min_x, y) + shift,
+                size: dvec2(max_x - min_x, line_spacing) + pad
+            });
+        }
         out
     }
     
-    
     pub fn get_char_count(&self, cx: &Cx) -> usize {
-        let area = &self.draw_vars.area;
-        if !area.is_valid(cx) {
-            return 0
-        }
-        let rect_pos = area.get_read_ref(cx, live_id!(rect_pos), ShaderTy::Vec2).unwrap();
-        rect_pos.repeat
+        self.get_caret_glyphs(cx).iter().map( | glyph | glyph.char_end()).max().unwrap_or(0)
     }
     
+    // pos is the fraction of the char at index to put the cursor at
     pub fn get_cursor_pos(&self, cx: &Cx, pos: f32, index: usize) -> Option<DVec2> {
-        let area = &self.draw_vars.area;
-        
-        if !area.is_valid(cx) {
-            return None
-        }
+        let glyphs = self.get_caret_glyphs(cx);
         
-        let rect_pos = area.get_read_ref(cx, live_id!(rect_pos), ShaderTy::Vec2).unwrap();
-        let delta = area.get_read_ref(cx, live_id!(delta), ShaderTy::Vec2).unwrap();
-        let advance = area.get_read_ref(cx, live_id!(advance), ShaderTy::Float).unwrap();
-        
-        if rect_pos.repeat == 0 {
-            return None
+        if let Some(glyph) = glyphs.iter().find( | glyph | index >= glyph.char_index && index < glyph.char_end()) {
+            let x = glyph.caret_x((index - glyph.char_index) as f64 + pos as f64);
+            return Some(dvec2(x, glyph.y))
         }
-        if index >= rect_pos.repeat {
-            // lets get the last one and advance
-            let index = (rect_pos.repeat - 1) * rect_pos.stride;
-            let x = rect_pos.buffer[index + 0] - delta.buffer[index + 0] + advance.buffer[index + 0];
-            let y = rect_pos.buffer[index + 1] - delta.buffer[index + 1];
-            Some(dvec2(x as f64, y as f64))
-        }
-        else {
-            let index = index * rect_pos.stride;
-            let x = rect_pos.buffer[index + 0] - delta.buffer[index + 0] + advance.buffer[index + 0] * pos;
-            let y = rect_pos.buffer[index + 1] - delta.buffer[index + 1];
-            Some(dvec2(x as f64, y as f64))
+        // chars without a glyph, like line breaks, put the cursor in front of the next glyph
+        if let Some(glyph) = glyphs.iter().filter( | glyph | glyph.char_index > index).min_by_key( | glyph | glyph.char_index) {
+            return Some(dvec2(glyph.caret_x(0.0), glyph.y))
         }
+        // otherwise we are past the end
+        let glyph = glyphs.iter().max_by_key( | glyph | glyph.char_end())?;
+        Some(dvec2(glyph.caret_x(glyph.char_count as f64), glyph.y))
     }
     
     pub fn get_line_spacing(&self) -> f64 {
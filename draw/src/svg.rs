@@ -0,0 +1,1397 @@
+//! Parses the subset of SVG that icons and illustrations exported from design tools use: paths,
+//! basic shapes, groups, `use` references, transforms, fill and stroke colours, opacity, linear
+//! and radial gradients, and the view box. Text, filters, clip paths, masks and CSS style sheets
+//! are ignored. Group opacity is applied to each shape of the group separately.
+
+use {
+    std::collections::HashMap,
+    crate::{
+        makepad_platform::*,
+        makepad_vector::{
+            geometry::{AffineTransformation, LinearTransformation, Point, Transform, Vector},
+            internal_iter::*,
+            path::{PathCommand, PathIterator},
+            stroker::{LineCap, LineJoin, StrokeOptions, Stroker},
+            trapezoidator::FillRule,
+        },
+    }
+};
+
+/// A parsed SVG document, as a flat list of shapes in painting order.
+#[derive(Clone, Debug)]
+pub struct SvgDocument {
+    pub view_box: Rect,
+    /// The intrinsic size of the document, from its `width` and `height`, or its view box.
+    pub size: DVec2,
+    pub shapes: Vec<SvgShape>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SvgShape {
+    /// The outline of the shape, in its own coordinate system.
+    pub path: Vec<PathCommand>,
+    /// Maps the coordinate system of the shape to that of the view box.
+    pub transform: AffineTransformation,
+    pub fill: Option<SvgPaint>,
+    pub fill_rule: FillRule,
+    pub stroke: Option<SvgPaint>,
+    pub stroke_options: StrokeOptions,
+    pub opacity: f64,
+}
+
+#[derive(Clone, Debug)]
+pub enum SvgPaint {
+    /// A colour with straight alpha.
+    Color(Vec4),
+    Gradient(SvgGradient),
+}
+
+#[derive(Clone, Debug)]
+pub struct SvgGradient {
+    pub kind: SvgGradientKind,
+    /// Maps the coordinate system of the gradient to that of its shape.
+    pub transform: AffineTransformation,
+    pub spread: SvgSpread,
+    /// The offsets and straight alpha colours of the stops, in increasing order of offset.
+    pub stops: Vec<(f64, Vec4)>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum SvgGradientKind {
+    Linear {start: Point, end: Point},
+    Radial {center: Point, radius: f64, focus: Point},
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SvgSpread {
+    Pad,
+    Reflect,
+    Repeat,
+}
+
+impl SvgDocument {
+    pub fn parse(data: &[u8]) -> Result<SvgDocument, String> {
+        let data = std::str::from_utf8(data).map_err( | _ | "SVG is not valid UTF-8".to_string()) ?;
+        let root = parse_xml(data) ?;
+        if root.name != "svg" {
+            return Err(format!("Expected an svg element, found {}", root.name))
+        }
+        let mut ids = HashMap::new();
+        collect_ids(&root, &mut ids);
+
+        // percentages are relative to whatever the document is drawn into, so it has no
+        // intrinsic size in that direction
+        let intrinsic_length = | name: &str | {
+            root.attribute(name).filter( | value | !value.trim_end().ends_with('%')).and_then(parse_length).filter( | length | *length > 0.0)
+        };
+        let width = intrinsic_length("width");
+        let height = intrinsic_length("height");
+        let view_box = match root.attribute("viewBox").map(parse_numbers) {
+            Some(nums) if nums.len() == 4 && nums[2] > 0.0 && nums[3] > 0.0 => Rect {
+                pos: dvec2(nums[0], nums[1]),
+                size: dvec2(nums[2], nums[3])
+            },
+            _ => Rect {
+                pos: dvec2(0.0, 0.0),
+                size: dvec2(width.unwrap_or(100.0), height.unwrap_or(100.0))
+            }
+        };
+        let size = dvec2(width.unwrap_or(view_box.size.x), height.unwrap_or(view_box.size.y));
+
+        let mut parser = SvgParser {
+            ids,
+            view_box,
+            shapes: Vec::new(),
+            use_depth: 0,
+        };
+        let style = Style::default().inherit(&root);
+        for child in &root.children {
+            parser.parse_element(child, &style);
+        }
+        Ok(SvgDocument {
+            view_box,
+            size,
+            shapes: parser.shapes
+        })
+    }
+
+    /// Returns the painted areas of all shapes in the coordinate system of the view box, with
+    /// strokes turned into outlines that are filled with the non-zero rule. Paint and opacity are
+    /// left out, which is what a single colour icon needs.
+    pub fn fill_paths(&self) -> Vec<(FillRule, Vec<PathCommand>)> {
+        let tolerance = self.view_box.size.x.max(self.view_box.size.y) * 0.001;
+        let mut stroker = Stroker::new();
+        let mut paths = Vec::new();
+        for shape in &self.shapes {
+            if shape.opacity <= 0.0 {
+                continue
+            }
+            if shape.fill.is_some() {
+                let mut path = close_subpaths(&shape.path);
+                for cmd in &mut path {
+                    cmd.transform_mut(&shape.transform);
+                }
+                paths.push((shape.fill_rule, path));
+            }
+            if shape.stroke.is_some() {
+                let mut outline = Vec::new();
+                let local_tolerance = tolerance / transform_scale(&shape.transform).max(1E-9);
+                stroker.stroke(
+                    shape.path.iter().cloned().into_internal_iter().linearize(local_tolerance),
+                    &StrokeOptions {tolerance: local_tolerance, ..shape.stroke_options},
+                    &mut outline
+                );
+                for cmd in &mut outline {
+                    cmd.transform_mut(&shape.transform);
+                }
+                paths.push((FillRule::NonZero, outline));
+            }
+        }
+        paths
+    }
+}
+
+/// The factor by which a transformation scales areas, as a length.
+pub fn transform_scale(transform: &AffineTransformation) -> f64 {
+    transform.xy.determinant().abs().sqrt()
+}
+
+struct SvgParser<'a> {
+    ids: HashMap<&'a str, &'a XmlElement>,
+    view_box: Rect,
+    shapes: Vec<SvgShape>,
+    use_depth: usize,
+}
+
+impl<'a> SvgParser<'a> {
+    fn parse_element(&mut self, element: &'a XmlElement, parent: &Style) {
+        match element.name.as_str() {
+            "g" | "a" | "svg" | "switch" => {
+                let style = parent.inherit(element);
+                if style.is_hidden {
+                    return
+                }
+                for child in &element.children {
+                    self.parse_element(child, &style);
+                }
+            }
+            "use" => {
+                // a use element is drawn like a group with the element it refers to
+                let target = element.attribute("href")
+                    .or_else( || element.attribute("xlink:href"))
+                    .and_then( | href | href.strip_prefix('#'))
+                    .and_then( | id | self.ids.get(id).cloned());
+                if let Some(target) = target {
+                    if self.use_depth > 16 {
+                        return
+                    }
+                    let mut style = parent.inherit(element);
+                    if style.is_hidden {
+                        return
+                    }
+                    let x = element.attribute("x").and_then(parse_length).unwrap_or(0.0);
+                    let y = element.attribute("y").and_then(parse_length).unwrap_or(0.0);
+                    style.transform = style.transform.compose(AffineTransformation::translation(Vector::new(x, y)));
+                    self.use_depth += 1;
+                    if target.name == "symbol" {
+                        for child in &target.children {
+                            self.parse_element(child, &style);
+                        }
+                    }
+                    else {
+                        self.parse_element(target, &style);
+                    }
+                    self.use_depth -= 1;
+                }
+            }
+            "path" | "rect" | "circle" | "ellipse" | "line" | "polyline" | "polygon" => {
+                let style = parent.inherit(element);
+                if style.is_hidden {
+                    return
+                }
+                let path = match self.shape_path(element) {
+                    Some(path) if !path.is_empty() => path,
+                    _ => return
+                };
+                let is_line = element.name == "line" || element.name == "polyline";
+                let fill = if is_line {None} else {self.paint(&style.fill, style.fill_opacity, &style, &path)};
+                let stroke = if style.stroke_width > 0.0 {
+                    self.paint(&style.stroke, style.stroke_opacity, &style, &path)
+                }
+                else {
+                    None
+                };
+                if fill.is_none() && stroke.is_none() {
+                    return
+                }
+                self.shapes.push(SvgShape {
+                    path,
+                    transform: style.transform,
+                    fill,
+                    fill_rule: style.fill_rule,
+                    stroke,
+                    stroke_options: StrokeOptions {
+                        width: style.stroke_width,
+                        line_cap: style.line_cap,
+                        line_join: style.line_join,
+                        miter_limit: style.miter_limit,
+                        ..StrokeOptions::default()
+                    },
+                    opacity: style.opacity,
+                });
+            }
+            // definitions are only drawn through references
+            _ => ()
+        }
+    }
+
+    fn length(&self, element: &XmlElement, name: &str, relative_to: f64) -> f64 {
+        element.attribute(name).and_then( | value | parse_length_relative(value, relative_to)).unwrap_or(0.0)
+    }
+
+    fn shape_path(&self, element: &XmlElement) -> Option<Vec<PathCommand>> {
+        let vw = self.view_box.size.x;
+        let vh = self.view_box.size.y;
+        let vd = ((vw * vw + vh * vh) / 2.0).sqrt();
+        let mut path = Vec::new();
+        match element.name.as_str() {
+            "path" => {
+                match parse_svg_path(element.attribute("d") ?.as_bytes()) {
+                    Ok(commands) => path = commands,
+                    Err(err) => {
+                        log!("Error in SVG path {}", err);
+                        return None
+                    }
+                }
+            }
+            "rect" => {
+                let x = self.length(element, "x", vw);
+                let y = self.length(element, "y", vh);
+                let w = self.length(element, "width", vw);
+                let h = self.length(element, "height", vh);
+                if w <= 0.0 || h <= 0.0 {
+                    return None
+                }
+                let rx = element.attribute("rx").and_then( | v | parse_length_relative(v, vw));
+                let ry = element.attribute("ry").and_then( | v | parse_length_relative(v, vh));
+                let rx = rx.or(ry).unwrap_or(0.0).max(0.0).min(w / 2.0);
+                let ry = ry.or(Some(rx)).unwrap_or(0.0).max(0.0).min(h / 2.0);
+                if rx > 0.0 && ry > 0.0 {
+                    let k = KAPPA;
+                    let p = | px: f64, py: f64 | Point::new(px, py);
+                    path.push(PathCommand::MoveTo(p(x + rx, y)));
+                    path.push(PathCommand::LineTo(p(x + w - rx, y)));
+                    path.push(PathCommand::CubicTo(p(x + w - rx + rx * k, y), p(x + w, y + ry - ry * k), p(x + w, y + ry)));
+                    path.push(PathCommand::LineTo(p(x + w, y + h - ry)));
+                    path.push(PathCommand::CubicTo(p(x + w, y + h - ry + ry * k), p(x + w - rx + rx * k, y + h), p(x + w - rx, y + h)));
+                    path.push(PathCommand::LineTo(p(x + rx, y + h)));
+                    path.push(PathCommand::CubicTo(p(x + rx - rx * k, y + h), p(x, y + h - ry + ry * k), p(x, y + h - ry)));
+                    path.push(PathCommand::LineTo(p(x, y + ry)));
+                    path.push(PathCommand::CubicTo(p(x, y + ry - ry * k), p(x + rx - rx * k, y), p(x + rx, y)));
+                    path.push(PathCommand::Close);
+                }
+                else {
+                    path.push(PathCommand::MoveTo(Point::new(x, y)));
+                    path.push(PathCommand::LineTo(Point::new(x + w, y)));
+                    path.push(PathCommand::LineTo(Point::new(x + w, y + h)));
+                    path.push(PathCommand::LineTo(Point::new(x, y + h)));
+                    path.push(PathCommand::Close);
+                }
+            }
+            "circle" => {
+                let r = self.length(element, "r", vd);
+                push_ellipse(&mut path, self.length(element, "cx", vw), self.length(element, "cy", vh), r, r);
+            }
+            "ellipse" => {
+                let rx = element.attribute("rx").and_then( | v | parse_length_relative(v, vw));
+                let ry = element.attribute("ry").and_then( | v | parse_length_relative(v, vh));
+                let rx = rx.or(ry).unwrap_or(0.0);
+                let ry = ry.unwrap_or(rx);
+                push_ellipse(&mut path, self.length(element, "cx", vw), self.length(element, "cy", vh), rx, ry);
+            }
+            "line" => {
+                path.push(PathCommand::MoveTo(Point::new(self.length(element, "x1", vw), self.length(element, "y1", vh))));
+                path.push(PathCommand::LineTo(Point::new(self.length(element, "x2", vw), self.length(element, "y2", vh))));
+            }
+            "polyline" | "polygon" => {
+                let nums = parse_numbers(element.attribute("points") ?);
+                for (index, pair) in nums.chunks_exact(2).enumerate() {
+                    let p = Point::new(pair[0], pair[1]);
+                    path.push(if index == 0 {PathCommand::MoveTo(p)} else {PathCommand::LineTo(p)});
+                }
+                if element.name == "polygon" && !path.is_empty() {
+                    path.push(PathCommand::Close);
+                }
+            }
+            _ => return None
+        }
+        Some(path)
+    }
+
+    fn paint(&self, paint: &Option<PaintRef>, opacity: f64, style: &Style, path: &[PathCommand]) -> Option<SvgPaint> {
+        match paint.as_ref() ? {
+            PaintRef::Color(color) => Some(SvgPaint::Color(with_opacity(*color, opacity))),
+            PaintRef::CurrentColor => Some(SvgPaint::Color(with_opacity(style.color, opacity))),
+            PaintRef::Url(id, fallback) => {
+                match self.ids.get(id.as_str()) {
+                    Some(element) if element.name == "linearGradient" || element.name == "radialGradient" => {
+                        self.gradient(element, opacity, path)
+                    }
+                    _ => fallback.map( | color | SvgPaint::Color(with_opacity(color, opacity)))
+                }
+            }
+        }
+    }
+
+    // Looks up an attribute of a gradient, or of the gradients it refers to.
+    fn gradient_attribute(&self, element: &'a XmlElement, name: &str) -> Option<&'a str> {
+        let mut element = element;
+        for _ in 0..16 {
+            if let Some(value) = element.attribute(name) {
+                return Some(value)
+            }
+            element = self.gradient_href(element) ?;
+        }
+        None
+    }
+
+    fn gradient_href(&self, element: &XmlElement) -> Option<&'a XmlElement> {
+        let href = element.attribute("href").or_else( || element.attribute("xlink:href")) ?;
+        self.ids.get(href.strip_prefix('#') ?).cloned()
+    }
+
+    fn gradient(&self, element: &'a XmlElement, opacity: f64, path: &[PathCommand]) -> Option<SvgPaint> {
+        // the stops come from the first gradient in the chain that has any
+        let mut stops = Vec::new();
+        let mut stops_element = element;
+        for _ in 0..16 {
+            for child in &stops_element.children {
+                if child.name != "stop" {
+                    continue
+                }
+                let offset = child.property("offset").and_then(parse_offset).unwrap_or(0.0);
+                let offset = stops.last().map_or(offset, | (last, _): &(f64, Vec4) | offset.max(*last));
+                let color = match child.property("stop-color").and_then(parse_paint) {
+                    Some(PaintRef::Color(color)) => color,
+                    _ => vec4(0.0, 0.0, 0.0, 1.0)
+                };
+                let stop_opacity = child.property("stop-opacity").and_then(parse_offset).unwrap_or(1.0);
+                stops.push((offset, with_opacity(color, stop_opacity * opacity)));
+            }
+            if !stops.is_empty() {
+                break
+            }
+            stops_element = self.gradient_href(stops_element) ?;
+        }
+        if stops.is_empty() {
+            return None
+        }
+        if stops.len() == 1 {
+            return Some(SvgPaint::Color(stops[0].1))
+        }
+
+        let user_space = self.gradient_attribute(element, "gradientUnits") == Some("userSpaceOnUse");
+        let vw = self.view_box.size.x;
+        let vh = self.view_box.size.y;
+        let vd = ((vw * vw + vh * vh) / 2.0).sqrt();
+        let coord = | name: &str, default: f64, relative_to: f64 | {
+            match self.gradient_attribute(element, name) {
+                Some(value) if user_space => parse_length_relative(value, relative_to).unwrap_or(default * relative_to),
+                Some(value) => parse_offset_unclamped(value).unwrap_or(default),
+                None if user_space => default * relative_to,
+                None => default
+            }
+        };
+        let kind = if element.name == "linearGradient" {
+            SvgGradientKind::Linear {
+                start: Point::new(coord("x1", 0.0, vw), coord("y1", 0.0, vh)),
+                end: Point::new(coord("x2", 1.0, vw), coord("y2", 0.0, vh)),
+            }
+        }
+        else {
+            let center = Point::new(coord("cx", 0.5, vw), coord("cy", 0.5, vh));
+            let focus = Point::new(
+                self.gradient_attribute(element, "fx").map_or(center.x, | _ | coord("fx", 0.5, vw)),
+                self.gradient_attribute(element, "fy").map_or(center.y, | _ | coord("fy", 0.5, vh)),
+            );
+            SvgGradientKind::Radial {
+                center,
+                radius: coord("r", 0.5, vd),
+                focus
+            }
+        };
+        let mut transform = AffineTransformation::identity();
+        if !user_space {
+            // the gradient is relative to the bounding box of the shape
+            let (min, max) = path_bounds(path) ?;
+            let size = max - min;
+            if size.x <= 0.0 || size.y <= 0.0 {
+                return Some(SvgPaint::Color(stops[stops.len() - 1].1))
+            }
+            transform = AffineTransformation::new(
+                LinearTransformation::scaling(size),
+                min.to_vector()
+            );
+        }
+        if let Some(gradient_transform) = self.gradient_attribute(element, "gradientTransform").and_then(parse_transform) {
+            transform = transform.compose(gradient_transform);
+        }
+        let spread = match self.gradient_attribute(element, "spreadMethod") {
+            Some("reflect") => SvgSpread::Reflect,
+            Some("repeat") => SvgSpread::Repeat,
+            _ => SvgSpread::Pad
+        };
+        Some(SvgPaint::Gradient(SvgGradient {
+            kind,
+            transform,
+            spread,
+            stops
+        }))
+    }
+}
+
+const KAPPA: f64 = 0.5522847498307936;
+
+fn push_ellipse(path: &mut Vec<PathCommand>, cx: f64, cy: f64, rx: f64, ry: f64) {
+    if rx <= 0.0 || ry <= 0.0 {
+        return
+    }
+    let kx = rx * KAPPA;
+    let ky = ry * KAPPA;
+    let p = | x: f64, y: f64 | Point::new(x, y);
+    path.push(PathCommand::MoveTo(p(cx + rx, cy)));
+    path.push(PathCommand::CubicTo(p(cx + rx, cy + ky), p(cx + kx, cy + ry), p(cx, cy + ry)));
+    path.push(PathCommand::CubicTo(p(cx - kx, cy + ry), p(cx - rx, cy + ky), p(cx - rx, cy)));
+    path.push(PathCommand::CubicTo(p(cx - rx, cy - ky), p(cx - kx, cy - ry), p(cx, cy - ry)));
+    path.push(PathCommand::CubicTo(p(cx + kx, cy - ry), p(cx + rx, cy - ky), p(cx + rx, cy)));
+    path.push(PathCommand::Close);
+}
+
+/// Returns a copy of a path with all its subpaths closed, which is how they are filled.
+pub fn close_subpaths(path: &[PathCommand]) -> Vec<PathCommand> {
+    let mut out = Vec::with_capacity(path.len() + 1);
+    let mut is_open = false;
+    for cmd in path {
+        match cmd {
+            PathCommand::MoveTo(_) => {
+                if is_open {
+                    out.push(PathCommand::Close);
+                }
+                is_open = true;
+            }
+            PathCommand::Close => is_open = false,
+            _ => ()
+        }
+        out.push(*cmd);
+    }
+    if is_open {
+        out.push(PathCommand::Close);
+    }
+    out
+}
+
+/// Returns the bounds of the end and control points of a path.
+pub fn path_bounds(path: &[PathCommand]) -> Option<(Point, Point)> {
+    let mut bounds: Option<(Point, Point)> = None;
+    let mut add = | p: Point | {
+        bounds = Some(match bounds {
+            Some((min, max)) => (
+                Point::new(min.x.min(p.x), min.y.min(p.y)),
+                Point::new(max.x.max(p.x), max.y.max(p.y))
+            ),
+            None => (p, p)
+        });
+    };
+    for cmd in path {
+        match *cmd {
+            PathCommand::MoveTo(p) | PathCommand::LineTo(p) => add(p),
+            PathCommand::QuadraticTo(p1, p) => {
+                add(p1);
+                add(p);
+            }
+            PathCommand::CubicTo(p1, p2, p) => {
+                add(p1);
+                add(p2);
+                add(p);
+            }
+            PathCommand::Close => ()
+        }
+    }
+    bounds
+}
+
+fn with_opacity(color: Vec4, opacity: f64) -> Vec4 {
+    vec4(color.x, color.y, color.z, color.w * opacity.clamp(0.0, 1.0) as f32)
+}
+
+// Styles
+
+#[derive(Clone, Debug)]
+enum PaintRef {
+    Color(Vec4),
+    CurrentColor,
+    /// A reference to a gradient, with the colour to use if it does not exist.
+    Url(String, Option<Vec4>),
+}
+
+#[derive(Clone, Debug)]
+struct Style {
+    fill: Option<PaintRef>,
+    fill_opacity: f64,
+    fill_rule: FillRule,
+    stroke: Option<PaintRef>,
+    stroke_opacity: f64,
+    stroke_width: f64,
+    line_cap: LineCap,
+    line_join: LineJoin,
+    miter_limit: f64,
+    color: Vec4,
+    opacity: f64,
+    transform: AffineTransformation,
+    is_hidden: bool,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            fill: Some(PaintRef::Color(vec4(0.0, 0.0, 0.0, 1.0))),
+            fill_opacity: 1.0,
+            fill_rule: FillRule::NonZero,
+            stroke: None,
+            stroke_opacity: 1.0,
+            stroke_width: 1.0,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Miter,
+            miter_limit: 4.0,
+            color: vec4(0.0, 0.0, 0.0, 1.0),
+            opacity: 1.0,
+            transform: AffineTransformation::identity(),
+            is_hidden: false,
+        }
+    }
+}
+
+impl Style {
+    // The style of an element, from its own properties and the ones it inherits from its parent.
+    fn inherit(&self, element: &XmlElement) -> Style {
+        let mut style = self.clone();
+        if let Some(color) = element.property("color").and_then(parse_color) {
+            style.color = color;
+        }
+        let resolve = | paint: PaintRef, style: &Style | match paint {
+            PaintRef::CurrentColor => PaintRef::Color(style.color),
+            paint => paint
+        };
+        if let Some(fill) = element.property("fill") {
+            style.fill = if fill.trim() == "none" {None} else {parse_paint(fill).map( | paint | resolve(paint, &style)).or(style.fill)};
+        }
+        if let Some(stroke) = element.property("stroke") {
+            style.stroke = if stroke.trim() == "none" {None} else {parse_paint(stroke).map( | paint | resolve(paint, &style)).or(style.stroke)};
+        }
+        if let Some(value) = element.property("fill-opacity").and_then(parse_offset) {
+            style.fill_opacity = value;
+        }
+        if let Some(value) = element.property("stroke-opacity").and_then(parse_offset) {
+            style.stroke_opacity = value;
+        }
+        if let Some(value) = element.property("opacity").and_then(parse_offset) {
+            style.opacity *= value;
+        }
+        match element.property("fill-rule").map(str::trim) {
+            Some("evenodd") => style.fill_rule = FillRule::EvenOdd,
+            Some("nonzero") => style.fill_rule = FillRule::NonZero,
+            _ => ()
+        }
+        if let Some(value) = element.property("stroke-width").and_then(parse_length) {
+            style.stroke_width = value;
+        }
+        match element.property("stroke-linecap").map(str::trim) {
+            Some("butt") => style.line_cap = LineCap::Butt,
+            Some("round") => style.line_cap = LineCap::Round,
+            Some("square") => style.line_cap = LineCap::Square,
+            _ => ()
+        }
+        match element.property("stroke-linejoin").map(str::trim) {
+            Some("miter") | Some("miter-clip") | Some("arcs") => style.line_join = LineJoin::Miter,
+            Some("round") => style.line_join = LineJoin::Round,
+            Some("bevel") => style.line_join = LineJoin::Bevel,
+            _ => ()
+        }
+        if let Some(value) = element.property("stroke-miterlimit").and_then(parse_number) {
+            style.miter_limit = value.max(1.0);
+        }
+        if let Some(transform) = element.attribute("transform").and_then(parse_transform) {
+            style.transform = style.transform.compose(transform);
+        }
+        if element.property("display").map(str::trim) == Some("none")
+            || matches!(element.property("visibility").map(str::trim), Some("hidden") | Some("collapse")) {
+            style.is_hidden = true;
+        }
+        style
+    }
+}
+
+// Values
+
+fn parse_number(value: &str) -> Option<f64> {
+    let mut scanner = Scanner::new(value.as_bytes());
+    let num = scanner.number() ?;
+    Some(num)
+}
+
+fn parse_numbers(value: &str) -> Vec<f64> {
+    let mut scanner = Scanner::new(value.as_bytes());
+    let mut nums = Vec::new();
+    while let Some(num) = scanner.number() {
+        nums.push(num);
+    }
+    nums
+}
+
+fn parse_length(value: &str) -> Option<f64> {
+    parse_length_relative(value, 0.0)
+}
+
+// Parses a length in user units, with percentages relative to the given length.
+fn parse_length_relative(value: &str, relative_to: f64) -> Option<f64> {
+    let value = value.trim();
+    let mut scanner = Scanner::new(value.as_bytes());
+    let num = scanner.number() ?;
+    let unit = &value[scanner.pos..];
+    Some(match unit.trim() {
+        "" | "px" => num,
+        "%" => num * relative_to / 100.0,
+        "pt" => num * 4.0 / 3.0,
+        "pc" => num * 16.0,
+        "mm" => num * 96.0 / 25.4,
+        "cm" => num * 96.0 / 2.54,
+        "in" => num * 96.0,
+        "em" => num * 16.0,
+        "ex" => num * 8.0,
+        _ => num
+    })
+}
+
+// Parses a number or a percentage as a fraction, clamped to 0..1.
+fn parse_offset(value: &str) -> Option<f64> {
+    Some(parse_offset_unclamped(value) ?.clamp(0.0, 1.0))
+}
+
+fn parse_offset_unclamped(value: &str) -> Option<f64> {
+    let value = value.trim();
+    match value.strip_suffix('%') {
+        Some(percentage) => Some(parse_number(percentage) ? / 100.0),
+        None => parse_number(value)
+    }
+}
+
+fn parse_paint(value: &str) -> Option<PaintRef> {
+    let value = value.trim();
+    if let Some(rest) = value.strip_prefix("url(") {
+        let end = rest.find(')') ?;
+        let id = rest[..end].trim().trim_matches( | c | c == '"' || c == '\'');
+        let id = id.strip_prefix('#').unwrap_or(id);
+        let fallback = match parse_paint(&rest[end + 1..]) {
+            Some(PaintRef::Color(color)) => Some(color),
+            _ => None
+        };
+        return Some(PaintRef::Url(id.to_string(), fallback))
+    }
+    if value == "currentColor" {
+        return Some(PaintRef::CurrentColor)
+    }
+    parse_color(value).map(PaintRef::Color)
+}
+
+fn parse_color(value: &str) -> Option<Vec4> {
+    let value = value.trim();
+    if let Some(hex) = value.strip_prefix('#') {
+        let digit = | index: usize | u8::from_str_radix(hex.get(index..index + 1) ?, 16).ok().map( | v | v as f32);
+        let byte = | index: usize | u8::from_str_radix(hex.get(index..index + 2) ?, 16).ok().map( | v | v as f32 / 255.0);
+        return match hex.len() {
+            3 => Some(vec4(digit(0) ? / 15.0, digit(1) ? / 15.0, digit(2) ? / 15.0, 1.0)),
+            4 => Some(vec4(digit(0) ? / 15.0, digit(1) ? / 15.0, digit(2) ? / 15.0, digit(3) ? / 15.0)),
+            6 => Some(vec4(byte(0) ?, byte(2) ?, byte(4) ?, 1.0)),
+            8 => Some(vec4(byte(0) ?, byte(2) ?, byte(4) ?, byte(6) ?)),
+            _ => None
+        }
+    }
+    if let Some(args) = value.strip_prefix("rgba(").or_else( || value.strip_prefix("rgb(")) {
+        let args = args.strip_suffix(')') ?;
+        let mut channels = [0.0f32, 0.0, 0.0, 1.0];
+        for (index, arg) in args.split([',', '/']).enumerate().take(4) {
+            let arg = arg.trim();
+            channels[index] = match arg.strip_suffix('%') {
+                Some(percentage) => parse_number(percentage) ? as f32 / 100.0,
+                None if index == 3 => parse_number(arg) ? as f32,
+                None => parse_number(arg) ? as f32 / 255.0,
+            }.clamp(0.0, 1.0);
+        }
+        return Some(vec4(channels[0], channels[1], channels[2], channels[3]))
+    }
+    let rgb = match value.to_lowercase().as_str() {
+        "transparent" => return Some(vec4(0.0, 0.0, 0.0, 0.0)),
+        "black" => 0x000000,
+        "white" => 0xffffff,
+        "red" => 0xff0000,
+        "lime" => 0x00ff00,
+        "green" => 0x008000,
+        "blue" => 0x0000ff,
+        "yellow" => 0xffff00,
+        "cyan" | "aqua" => 0x00ffff,
+        "magenta" | "fuchsia" => 0xff00ff,
+        "gray" | "grey" => 0x808080,
+        "silver" => 0xc0c0c0,
+        "maroon" => 0x800000,
+        "olive" => 0x808000,
+        "navy" => 0x000080,
+        "purple" => 0x800080,
+        "teal" => 0x008080,
+        "orange" => 0xffa500,
+        "pink" => 0xffc0cb,
+        "brown" => 0xa52a2a,
+        "gold" => 0xffd700,
+        "darkgray" | "darkgrey" => 0xa9a9a9,
+        "lightgray" | "lightgrey" => 0xd3d3d3,
+        _ => return None
+    };
+    Some(vec4(
+        ((rgb >> 16) & 0xff) as f32 / 255.0,
+        ((rgb >> 8) & 0xff) as f32 / 255.0,
+        (rgb & 0xff) as f32 / 255.0,
+        1.0
+    ))
+}
+
+fn parse_transform(value: &str) -> Option<AffineTransformation> {
+    let mut transform = AffineTransformation::identity();
+    let mut rest = value.trim();
+    while !rest.is_empty() {
+        let open = rest.find('(') ?;
+        let close = rest[open..].find(')').map( | i | open + i) ?;
+        let name = rest[..open].trim().trim_start_matches(',').trim();
+        let args = parse_numbers(&rest[open + 1..close]);
+        let arg = | index: usize | args.get(index).cloned();
+        let next = match name {
+            "matrix" if args.len() == 6 => AffineTransformation::new(
+                LinearTransformation::new(Vector::new(args[0], args[1]), Vector::new(args[2], args[3])),
+                Vector::new(args[4], args[5])
+            ),
+            "translate" => AffineTransformation::translation(Vector::new(arg(0) ?, arg(1).unwrap_or(0.0))),
+            "scale" => {
+                let x = arg(0) ?;
+                AffineTransformation::scaling(Vector::new(x, arg(1).unwrap_or(x)))
+            }
+            "rotate" => {
+                let (sin, cos) = arg(0) ?.to_radians().sin_cos();
+                let rotation = AffineTransformation::new(
+                    LinearTransformation::new(Vector::new(cos, sin), Vector::new(-sin, cos)),
+                    Vector::zero()
+                );
+                match (arg(1), arg(2)) {
+                    (Some(cx), Some(cy)) => AffineTransformation::translation(Vector::new(cx, cy))
+                        .compose(rotation)
+                        .compose(AffineTransformation::translation(Vector::new(-cx, -cy))),
+                    _ => rotation
+                }
+            }
+            "skewX" => AffineTransformation::new(
+                LinearTransformation::new(Vector::new(1.0, 0.0), Vector::new(arg(0) ?.to_radians().tan(), 1.0)),
+                Vector::zero()
+            ),
+            "skewY" => AffineTransformation::new(
+                LinearTransformation::new(Vector::new(1.0, arg(0) ?.to_radians().tan()), Vector::new(0.0, 1.0)),
+                Vector::zero()
+            ),
+            _ => return None
+        };
+        transform = transform.compose(next);
+        rest = rest[close + 1..].trim_start();
+    }
+    Some(transform)
+}
+
+// Paths
+
+struct Scanner<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {data, pos: 0}
+    }
+
+    fn skip_separators(&mut self) {
+        while self.pos < self.data.len() && matches!(self.data[self.pos], b' ' | b'\t' | b'\r' | b'\n' | b',') {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_separators();
+        self.data.get(self.pos).cloned()
+    }
+
+    // Reads a number like `-1.5e3`. Numbers do not need separators, so `1.5.5-2` is three numbers.
+    fn number(&mut self) -> Option<f64> {
+        self.skip_separators();
+        let start = self.pos;
+        let mut pos = self.pos;
+        let at = | pos: usize | self.data.get(pos).cloned().unwrap_or(0);
+        if at(pos) == b'+' || at(pos) == b'-' {
+            pos += 1;
+        }
+        let mut has_digits = false;
+        while at(pos).is_ascii_digit() {
+            pos += 1;
+            has_digits = true;
+        }
+        if at(pos) == b'.' {
+            pos += 1;
+            while at(pos).is_ascii_digit() {
+                pos += 1;
+                has_digits = true;
+            }
+        }
+        if !has_digits {
+            return None
+        }
+        if at(pos) == b'e' || at(pos) == b'E' {
+            let mut exp_pos = pos + 1;
+            if at(exp_pos) == b'+' || at(exp_pos) == b'-' {
+                exp_pos += 1;
+            }
+            if at(exp_pos).is_ascii_digit() {
+                pos = exp_pos;
+                while at(pos).is_ascii_digit() {
+                    pos += 1;
+                }
+            }
+        }
+        self.pos = pos;
+        std::str::from_utf8(&self.data[start..pos]).ok() ?.parse().ok()
+    }
+
+    // Reads an arc flag, which is a single digit that needs no separator.
+    fn flag(&mut self) -> Option<bool> {
+        match self.peek() ? {
+            b'0' => {
+                self.pos += 1;
+                Some(false)
+            }
+            b'1' => {
+                self.pos += 1;
+                Some(true)
+            }
+            _ => None
+        }
+    }
+}
+
+/// Parses the path data of an SVG `path` element.
+pub fn parse_svg_path(data: &[u8]) -> Result<Vec<PathCommand>, String> {
+    let mut scanner = Scanner::new(data);
+    let mut out = Vec::new();
+    let mut current = Point::origin();
+    let mut subpath_start = Point::origin();
+    // the second control point of the last curve, for the reflected control points of S and T
+    let mut last_cubic_control: Option<Point> = None;
+    let mut last_quadratic_control: Option<Point> = None;
+    let mut cmd = 0u8;
+    let mut is_open = false;
+
+    while let Some(next) = scanner.peek() {
+        if next.is_ascii_alphabetic() {
+            cmd = next;
+            scanner.pos += 1;
+        }
+        // only commands with arguments repeat, so every pass reads at least one token
+        else if cmd == 0 || cmd == b'Z' || cmd == b'z' {
+            return Err(format!("Unexpected character {}", next as char))
+        }
+        if out.is_empty() && cmd != b'M' && cmd != b'm' {
+            return Err("SVG path does not start with a move to".to_string())
+        }
+        // a drawing command after a close starts a new subpath at the start of the closed one
+        if !is_open && !matches!(cmd, b'M' | b'm' | b'Z' | b'z') {
+            out.push(PathCommand::MoveTo(subpath_start));
+        }
+        is_open = !matches!(cmd, b'Z' | b'z');
+        let is_relative = cmd.is_ascii_lowercase();
+        let base = if is_relative {current.to_vector()} else {Vector::zero()};
+        let mut num = | scanner: &mut Scanner | scanner.number().ok_or_else( || {
+            format!("SVG path command {} is missing a number", cmd as char)
+        });
+        let point = | scanner: &mut Scanner, num: &mut dyn FnMut(&mut Scanner) -> Result<f64, String> | -> Result<Point, String> {
+            let x = num(scanner) ?;
+            let y = num(scanner) ?;
+            Ok(Point::new(x, y) + base)
+        };
+        let mut cubic_control = None;
+        let mut quadratic_control = None;
+        match cmd.to_ascii_uppercase() {
+            b'M' => {
+                current = point(&mut scanner, &mut num) ?;
+                subpath_start = current;
+                out.push(PathCommand::MoveTo(current));
+                // coordinates after the first pair of a move to are line tos
+                cmd = if is_relative {b'l'} else {b'L'};
+            }
+            b'L' => {
+                current = point(&mut scanner, &mut num) ?;
+                out.push(PathCommand::LineTo(current));
+            }
+            b'H' => {
+                let x = num(&mut scanner) ?;
+                current = Point::new(x + base.x, current.y);
+                out.push(PathCommand::LineTo(current));
+            }
+            b'V' => {
+                let y = num(&mut scanner) ?;
+                current = Point::new(current.x, y + base.y);
+                out.push(PathCommand::LineTo(current));
+            }
+            b'C' => {
+                let p1 = point(&mut scanner, &mut num) ?;
+                let p2 = point(&mut scanner, &mut num) ?;
+                current = point(&mut scanner, &mut num) ?;
+                out.push(PathCommand::CubicTo(p1, p2, current));
+                cubic_control = Some(p2);
+            }
+            b'S' => {
+                let p1 = match last_cubic_control {
+                    Some(control) => current + (current - control),
+                    None => current
+                };
+                let p2 = point(&mut scanner, &mut num) ?;
+                current = point(&mut scanner, &mut num) ?;
+                out.push(PathCommand::CubicTo(p1, p2, current));
+                cubic_control = Some(p2);
+            }
+            b'Q' => {
+                let p1 = point(&mut scanner, &mut num) ?;
+                current = point(&mut scanner, &mut num) ?;
+                out.push(PathCommand::QuadraticTo(p1, current));
+                quadratic_control = Some(p1);
+            }
+            b'T' => {
+                let p1 = match last_quadratic_control {
+                    Some(control) => current + (current - control),
+                    None => current
+                };
+                current = point(&mut scanner, &mut num) ?;
+                out.push(PathCommand::QuadraticTo(p1, current));
+                quadratic_control = Some(p1);
+            }
+            b'A' => {
+                let rx = num(&mut scanner) ?;
+                let ry = num(&mut scanner) ?;
+                let angle = num(&mut scanner) ?;
+                let large_arc = scanner.flag().ok_or("SVG arc is missing its large arc flag") ?;
+                let sweep = scanner.flag().ok_or("SVG arc is missing its sweep flag") ?;
+                let end = point(&mut scanner, &mut num) ?;
+                push_arc(&mut out, current, rx, ry, angle, large_arc, sweep, end);
+                current = end;
+            }
+            b'Z' => {
+                if !matches!(out.last(), Some(PathCommand::Close)) {
+                    out.push(PathCommand::Close);
+                }
+                current = subpath_start;
+            }
+            _ => return Err(format!("Unexpected SVG path command {}", cmd as char))
+        }
+        last_cubic_control = cubic_control;
+        last_quadratic_control = quadratic_control;
+    }
+    Ok(out)
+}
+
+// Approximates an elliptical arc with cubic curves, following the SVG implementation notes.
+#[allow(clippy::too_many_arguments)]
+fn push_arc(out: &mut Vec<PathCommand>, start: Point, rx: f64, ry: f64, angle: f64, large_arc: bool, sweep: bool, end: Point) {
+    if start == end {
+        return
+    }
+    let mut rx = rx.abs();
+    let mut ry = ry.abs();
+    if rx == 0.0 || ry == 0.0 {
+        out.push(PathCommand::LineTo(end));
+        return
+    }
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let half = (start - end) / 2.0;
+    let x1 = cos * half.x + sin * half.y;
+    let y1 = -sin * half.x + cos * half.y;
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        let scale = lambda.sqrt();
+        rx *= scale;
+        ry *= scale;
+    }
+    let numerator = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let mut factor = (numerator / denominator).max(0.0).sqrt();
+    if large_arc == sweep {
+        factor = -factor;
+    }
+    let cx1 = factor * rx * y1 / ry;
+    let cy1 = -factor * ry * x1 / rx;
+    let mid = Point::new((start.x + end.x) / 2.0, (start.y + end.y) / 2.0);
+    let center = Point::new(cos * cx1 - sin * cy1 + mid.x, sin * cx1 + cos * cy1 + mid.y);
+
+    let vector_angle = | ux: f64, uy: f64, vx: f64, vy: f64 | {
+        let dot = ux * vx + uy * vy;
+        let length = (ux * ux + uy * uy).sqrt() * (vx * vx + vy * vy).sqrt();
+        let angle = (dot / length).clamp(-1.0, 1.0).acos();
+        if ux * vy - uy * vx < 0.0 {-angle} else {angle}
+    };
+    let theta1 = vector_angle(1.0, 0.0, (x1 - cx1) / rx, (y1 - cy1) / ry);
+    let mut delta = vector_angle((x1 - cx1) / rx, (y1 - cy1) / ry, (-x1 - cx1) / rx, (-y1 - cy1) / ry);
+    if !sweep && delta > 0.0 {
+        delta -= 2.0 * std::f64::consts::PI;
+    }
+    else if sweep && delta < 0.0 {
+        delta += 2.0 * std::f64::consts::PI;
+    }
+
+    let segment_count = (delta.abs() / (std::f64::consts::PI / 2.0)).ceil().max(1.0) as usize;
+    let segment_delta = delta / segment_count as f64;
+    let k = 4.0 / 3.0 * (segment_delta / 4.0).tan();
+    let point_at = | theta: f64 | {
+        let (s, c) = theta.sin_cos();
+        Point::new(
+            center.x + rx * c * cos - ry * s * sin,
+            center.y + rx * c * sin + ry * s * cos
+        )
+    };
+    let derivative_at = | theta: f64 | {
+        let (s, c) = theta.sin_cos();
+        Vector::new(-rx * s * cos - ry * c * sin, -rx * s * sin + ry * c * cos)
+    };
+    let mut theta = theta1;
+    let mut p0 = start;
+    for index in 0..segment_count {
+        let next_theta = theta + segment_delta;
+        let p3 = if index == segment_count - 1 {end} else {point_at(next_theta)};
+        let p1 = p0 + derivative_at(theta) * k;
+        let p2 = p3 - derivative_at(next_theta) * k;
+        out.push(PathCommand::CubicTo(p1, p2, p3));
+        theta = next_theta;
+        p0 = p3;
+    }
+}
+
+// XML
+
+#[derive(Clone, Debug, Default)]
+struct XmlElement {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<XmlElement>,
+}
+
+impl XmlElement {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find( | (key, _) | key == name).map( | (_, value) | value.as_str())
+    }
+
+    // Looks up a presentation property, in the style attribute first.
+    fn property(&self, name: &str) -> Option<&str> {
+        if let Some(style) = self.attribute("style") {
+            for declaration in style.split(';') {
+                if let Some((key, value)) = declaration.split_once(':') {
+                    if key.trim() == name {
+                        return Some(value.trim().trim_end_matches("!important").trim())
+                    }
+                }
+            }
+        }
+        self.attribute(name)
+    }
+}
+
+fn collect_ids<'a>(element: &'a XmlElement, ids: &mut HashMap<&'a str, &'a XmlElement>) {
+    if let Some(id) = element.attribute("id") {
+        ids.entry(id).or_insert(element);
+    }
+    for child in &element.children {
+        collect_ids(child, ids);
+    }
+}
+
+// Parses the elements and attributes of an XML document into a tree, and returns its root.
+fn parse_xml(data: &str) -> Result<XmlElement, String> {
+    let bytes = data.as_bytes();
+    let mut pos = 0;
+    let mut stack: Vec<XmlElement> = Vec::new();
+    let mut root = None;
+    let skip_to = | pos: usize, pattern: &str | -> Result<usize, String> {
+        data[pos..].find(pattern).map( | index | pos + index + pattern.len()).ok_or_else( || {
+            format!("Unterminated {} in SVG", pattern)
+        })
+    };
+    while pos < bytes.len() {
+        if bytes[pos] != b'<' {
+            pos += 1;
+            continue
+        }
+        let rest = &data[pos..];
+        if rest.starts_with("<!--") {
+            pos = skip_to(pos, "-->") ?;
+        }
+        else if rest.starts_with("<![CDATA[") {
+            pos = skip_to(pos, "]]>") ?;
+        }
+        else if rest.starts_with("<?") {
+            pos = skip_to(pos, "?>") ?;
+        }
+        else if rest.starts_with("<!") {
+            // a doctype, which can have an internal subset in brackets
+            let mut depth = 0;
+            while pos < bytes.len() {
+                match bytes[pos] {
+                    b'[' => depth += 1,
+                    b']' => depth -= 1,
+                    b'>' if depth == 0 => break,
+                    _ => ()
+                }
+                pos += 1;
+            }
+            pos += 1;
+        }
+        else if rest.starts_with("</") {
+            let end = skip_to(pos, ">") ?;
+            let name = local_name(data[pos + 2..end - 1].trim());
+            let element = stack.pop().ok_or_else( || format!("Unexpected closing tag {} in SVG", name)) ?;
+            if element.name != name {
+                return Err(format!("Closing tag {} does not match {} in SVG", name, element.name))
+            }
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(element),
+                None => {
+                    root = Some(element);
+                    break
+                }
+            }
+            pos = end;
+        }
+        else {
+            pos += 1;
+            let name_start = pos;
+            while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() && bytes[pos] != b'>' && bytes[pos] != b'/' {
+                pos += 1;
+            }
+            let mut element = XmlElement {
+                name: local_name(&data[name_start..pos]).to_string(),
+                ..XmlElement::default()
+            };
+            let mut is_self_closing = false;
+            loop {
+                while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+                    pos += 1;
+                }
+                if pos >= bytes.len() {
+                    return Err("Unterminated tag in SVG".to_string())
+                }
+                if bytes[pos] == b'>' {
+                    pos += 1;
+                    break
+                }
+                if rest_starts_with(bytes, pos, b"/>") {
+                    pos += 2;
+                    is_self_closing = true;
+                    break
+                }
+                let key_start = pos;
+                while pos < bytes.len() && bytes[pos] != b'=' && !bytes[pos].is_ascii_whitespace() && bytes[pos] != b'>' && bytes[pos] != b'/' {
+                    pos += 1;
+                }
+                let key = &data[key_start..pos];
+                while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+                    pos += 1;
+                }
+                if pos < bytes.len() && bytes[pos] == b'=' {
+                    pos += 1;
+                    while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+                        pos += 1;
+                    }
+                    let quote = *bytes.get(pos).ok_or("Unterminated attribute in SVG") ?;
+                    if quote != b'"' && quote != b'\'' {
+                        return Err(format!("Unquoted attribute {} in SVG", key))
+                    }
+                    let value_start = pos + 1;
+                    let value_end = data[value_start..].find(quote as char).map( | index | value_start + index).ok_or("Unterminated attribute in SVG") ?;
+                    element.attributes.push((key.to_string(), decode_entities(&data[value_start..value_end])));
+                    pos = value_end + 1;
+                }
+                else if key.is_empty() {
+                    return Err("Unexpected character in SVG tag".to_string())
+                }
+            }
+            if is_self_closing {
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => {
+                        root = Some(element);
+                        break
+                    }
+                }
+            }
+            else {
+                stack.push(element);
+            }
+        }
+    }
+    root.ok_or_else( || "No root element in SVG".to_string())
+}
+
+fn rest_starts_with(bytes: &[u8], pos: usize, pattern: &[u8]) -> bool {
+    bytes.len() >= pos + pattern.len() && &bytes[pos..pos + pattern.len()] == pattern
+}
+
+// Strips the namespace prefix from an element name, so that `svg:path` is a `path`.
+fn local_name(name: &str) -> &str {
+    match name.split_once(':') {
+        Some((_, local)) => local,
+        None => name
+    }
+}
+
+fn decode_entities(value: &str) -> String {
+    if !value.contains('&') {
+        return value.to_string()
+    }
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => break
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => if let Some(hex) = entity.strip_prefix("#x") {
+                u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+            }
+            else if let Some(dec) = entity.strip_prefix('#') {
+                dec.parse().ok().and_then(char::from_u32)
+            }
+            else {
+                None
+            }
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_path_commands() {
+        let path = parse_svg_path(b"M10 10 L50 10 h-10 v5 Z").unwrap();
+        assert_eq!(path, vec![
+            PathCommand::MoveTo(Point::new(10.0, 10.0)),
+            PathCommand::LineTo(Point::new(50.0, 10.0)),
+            PathCommand::LineTo(Point::new(40.0, 10.0)),
+            PathCommand::LineTo(Point::new(40.0, 15.0)),
+            PathCommand::Close,
+        ]);
+    }
+
+    #[test]
+    fn parses_implicit_and_relative_commands() {
+        let path = parse_svg_path(b"m1,2 3,4-1-1zl1 1").unwrap();
+        assert_eq!(path, vec![
+            PathCommand::MoveTo(Point::new(1.0, 2.0)),
+            PathCommand::LineTo(Point::new(4.0, 6.0)),
+            PathCommand::LineTo(Point::new(3.0, 5.0)),
+            PathCommand::Close,
+            PathCommand::MoveTo(Point::new(1.0, 2.0)),
+            PathCommand::LineTo(Point::new(2.0, 3.0)),
+        ]);
+    }
+
+    #[test]
+    fn parses_curves_and_arcs() {
+        let path = parse_svg_path(b"M0 0 C1 1 2 1 3 0 S5 -1 6 0 Q7 1 8 0 T10 0 A2 2 0 0 1 14 0").unwrap();
+        assert!(matches!(path[1], PathCommand::CubicTo(..)));
+        assert_eq!(path[2], PathCommand::CubicTo(Point::new(4.0, -1.0), Point::new(5.0, -1.0), Point::new(6.0, 0.0)));
+        assert_eq!(path[4], PathCommand::QuadraticTo(Point::new(9.0, -1.0), Point::new(10.0, 0.0)));
+        assert!(matches!(path.last(), Some(PathCommand::CubicTo(_, _, end)) if (end.x - 14.0).abs() < 1e-9));
+    }
+
+    #[test]
+    fn rejects_malformed_paths() {
+        for data in [
+            &b"M10 10 L50 10 Z 5 5"[..],
+            b"M10 10 z 1",
+            b"L10 10",
+            b"5 5",
+            b"M10",
+            b"M10 10 L",
+            b"M10 10 X 1 1",
+            b"M10 10 A1 1 0 2 0 5 5",
+            b"M10 10 L#",
+        ] {
+            assert!(parse_svg_path(data).is_err(), "{}", std::str::from_utf8(data).unwrap());
+        }
+        assert_eq!(parse_svg_path(b"").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn malformed_path_in_document_does_not_hang() {
+        let document = SvgDocument::parse(br#"<svg viewBox="0 0 64 64"><path d="M10 10 L50 10 Z 5 5"/></svg>"#).unwrap();
+        assert!(document.shapes.is_empty());
+    }
+
+    #[test]
+    fn parses_transforms() {
+        let transform = parse_transform("translate(10, 20) scale(2)").unwrap();
+        let point = Point::new(1.0, 1.0).transform(&transform);
+        assert_eq!(point, Point::new(12.0, 22.0));
+        assert!(parse_transform("").is_some());
+        for value in [
+            ")translate(1)",
+            "translate(1",
+            "translate 1)",
+            "translate()",
+            "matrix(1 0 0 1)",
+            "wobble(1)",
+            "scale(1))",
+        ] {
+            assert!(parse_transform(value).is_none(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn parses_namespaced_elements() {
+        let document = SvgDocument::parse(
+            br#"<svg:svg xmlns:svg="http://www.w3.org/2000/svg" viewBox="0 0 64 64"><svg:g><svg:path d="M0 0 L10 0 L10 10 Z"/></svg:g></svg:svg>"#
+        ).unwrap();
+        assert_eq!(document.shapes.len(), 1);
+        assert!(SvgDocument::parse(br#"<svg:svg viewBox="0 0 64 64"></svg:g>"#).is_err());
+    }
+}
@@ -0,0 +1,305 @@
+//! Rasterizes an `SvgDocument` on the CPU, for images that are decoded to a pixel buffer. Shapes
+//! are turned into trapezoids like icons are, and the coverage of each pixel is the exact area of
+//! the trapezoids that overlap it, computed the same way as the trapezoid shader does.
+
+use crate::{
+    makepad_platform::*,
+    makepad_vector::{
+        geometry::{AffineTransformation, Point, Transform, Trapezoid, Vector},
+        internal_iter::*,
+        path::{PathCommand, PathIterator},
+        stroker::{StrokeOptions, Stroker},
+        trapezoidator::{FillRule, Trapezoidator},
+    },
+    svg::{close_subpaths, transform_scale, SvgDocument, SvgGradient, SvgGradientKind, SvgPaint, SvgSpread},
+};
+
+// The maximum distance between a curve and the lines that approximate it, in pixels.
+const TOLERANCE: f64 = 0.1;
+
+impl SvgDocument {
+    /// Renders the document into a buffer of `width` by `height` pixels, each packed as
+    /// `a << 24 | r << 16 | g << 8 | b` with straight alpha. The view box is scaled to fit the
+    /// buffer and centered in it, keeping its aspect ratio.
+    pub fn render(&self, width: usize, height: usize) -> Vec<u32> {
+        if width == 0 || height == 0 {
+            return Vec::new()
+        }
+        let mut canvas = Canvas {
+            width,
+            height,
+            pixels: vec![[0.0; 4]; width * height],
+            coverage: vec![0.0; width * height],
+            trapezoidator: Trapezoidator::new(),
+            stroker: Stroker::new(),
+        };
+        let scale = (width as f64 / self.view_box.size.x).min(height as f64 / self.view_box.size.y);
+        let view_transform = AffineTransformation::translation(Vector::new(
+            (width as f64 - self.view_box.size.x * scale) / 2.0,
+            (height as f64 - self.view_box.size.y * scale) / 2.0
+        )).compose(AffineTransformation::uniform_scaling(scale)).compose(
+            AffineTransformation::translation(Vector::new(-self.view_box.pos.x, -self.view_box.pos.y))
+        );
+        for shape in &self.shapes {
+            if shape.opacity <= 0.0 {
+                continue
+            }
+            let transform = view_transform.compose(shape.transform);
+            if let Some(fill) = &shape.fill {
+                let path = close_subpaths(&shape.path);
+                canvas.fill(&path, transform, shape.fill_rule, fill, shape.opacity);
+            }
+            if let Some(stroke) = &shape.stroke {
+                canvas.stroke(&shape.path, transform, &shape.stroke_options, stroke, shape.opacity);
+            }
+        }
+        let to_byte = | value: f32 | (value * 255.0 + 0.5).clamp(0.0, 255.0) as u32;
+        let mut out = Vec::with_capacity(width * height);
+        for [r, g, b, a] in canvas.pixels {
+            if a <= 0.0 {
+                out.push(0);
+                continue
+            }
+            out.push(to_byte(a) << 24 | to_byte(r / a) << 16 | to_byte(g / a) << 8 | to_byte(b / a));
+        }
+        out
+    }
+}
+
+struct Canvas {
+    width: usize,
+    height: usize,
+    // premultiplied colours
+    pixels: Vec<[f32; 4]>,
+    coverage: Vec<f32>,
+    trapezoidator: Trapezoidator,
+    stroker: Stroker,
+}
+
+impl Canvas {
+    fn stroke(&mut self, path: &[PathCommand], transform: AffineTransformation, options: &StrokeOptions, paint: &SvgPaint, opacity: f64) {
+        // the outline is built in the coordinate system of the shape, so that non uniform
+        // transforms stretch the stroke like they should
+        let tolerance = TOLERANCE / transform_scale(&transform).max(1E-9);
+        let mut outline = Vec::new();
+        self.stroker.stroke(
+            path.iter().cloned().into_internal_iter().linearize(tolerance),
+            &StrokeOptions {tolerance, ..*options},
+            &mut outline
+        );
+        self.fill(&outline, transform, FillRule::NonZero, paint, opacity);
+    }
+
+    fn fill(&mut self, path: &[PathCommand], transform: AffineTransformation, fill_rule: FillRule, paint: &SvgPaint, opacity: f64) {
+        let mut trapezoids: Vec<Trapezoid> = Vec::new();
+        let mut path = path.to_vec();
+        for cmd in &mut path {
+            cmd.transform_mut(&transform);
+        }
+        let trapezoidate = self.trapezoidator.trapezoidate_with_fill_rule(
+            path.iter().cloned().into_internal_iter().linearize(TOLERANCE),
+            fill_rule
+        );
+        if let Some(trapezoidate) = trapezoidate {
+            trapezoids.extend_from_internal_iter(trapezoidate);
+        }
+        let (min, max) = match self.accumulate_coverage(&trapezoids) {
+            Some(bounds) => bounds,
+            None => return
+        };
+        let sampler = PaintSampler::new(paint, transform, opacity);
+        for y in min.1..max.1 {
+            for x in min.0..max.0 {
+                let index = y * self.width + x;
+                let coverage = std::mem::take(&mut self.coverage[index]).abs().min(1.0);
+                if coverage <= 0.0 {
+                    continue
+                }
+                let source = sampler.sample(Point::new(x as f64 + 0.5, y as f64 + 0.5));
+                let pixel = &mut self.pixels[index];
+                let inverse = 1.0 - source[3] * coverage;
+                for channel in 0..4 {
+                    pixel[channel] = source[channel] * coverage + pixel[channel] * inverse;
+                }
+            }
+        }
+    }
+
+    // Adds the area of the trapezoids within each pixel to the coverage buffer, and returns the
+    // range of pixels that was touched.
+    fn accumulate_coverage(&mut self, trapezoids: &[Trapezoid]) -> Option<((usize, usize), (usize, usize))> {
+        let mut min = (usize::MAX, usize::MAX);
+        let mut max = (0, 0);
+        for trapezoid in trapezoids {
+            let p0 = (trapezoid.xs[0], trapezoid.ys[0]);
+            let p1 = (trapezoid.xs[1], trapezoid.ys[1]);
+            let p2 = (trapezoid.xs[0], trapezoid.ys[2]);
+            let p3 = (trapezoid.xs[1], trapezoid.ys[3]);
+            let x0 = (trapezoid.xs[0].floor().max(0.0) as usize).min(self.width);
+            let x1 = (trapezoid.xs[1].ceil().max(0.0) as usize).min(self.width);
+            let y0 = (trapezoid.ys[0].min(trapezoid.ys[1]).floor().max(0.0) as usize).min(self.height);
+            let y1 = (trapezoid.ys[2].max(trapezoid.ys[3]).ceil().max(0.0) as usize).min(self.height);
+            if x0 >= x1 || y0 >= y1 {
+                continue
+            }
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let p_min = (x as f32, y as f32);
+                    let p_max = (x as f32 + 1.0, y as f32 + 1.0);
+                    let area = clamped_right_trapezoid_area(p0, p1, p_min, p_max)
+                        - clamped_right_trapezoid_area(p2, p3, p_min, p_max);
+                    self.coverage[y * self.width + x] += area;
+                }
+            }
+            min = (min.0.min(x0), min.1.min(y0));
+            max = (max.0.max(x1), max.1.max(y1));
+        }
+        if min.0 >= max.0 || min.1 >= max.1 {
+            return None
+        }
+        Some((min, max))
+    }
+}
+
+// The area of the part of a pixel that lies below the line from `p0` to `p1`, where `p0` lies
+// left of `p1`. This is `compute_clamped_right_trapezoid_area` from the trapezoid shader.
+fn clamped_right_trapezoid_area(mut p0: (f32, f32), mut p1: (f32, f32), p_min: (f32, f32), p_max: (f32, f32)) -> f32 {
+    let at_x = | p0: (f32, f32), p1: (f32, f32), x: f32 | (x, p0.1 + (p1.1 - p0.1) * ((x - p0.0) / (p1.0 - p0.0)));
+    let at_y = | p0: (f32, f32), p1: (f32, f32), y: f32 | (p0.0 + (p1.0 - p0.0) * ((y - p0.1) / (p1.1 - p0.1)), y);
+    let clamp = | p: (f32, f32) | (p.0.clamp(p_min.0, p_max.0), p.1.clamp(p_min.1, p_max.1));
+    let x0 = p0.0.clamp(p_min.0, p_max.0);
+    let x1 = p1.0.clamp(p_min.0, p_max.0);
+    if p0.0 < p_min.0 && p_min.0 < p1.0 {
+        p0 = at_x(p0, p1, p_min.0);
+    }
+    if p0.0 < p_max.0 && p_max.0 < p1.0 {
+        p1 = at_x(p0, p1, p_max.0);
+    }
+    if p0.1 < p_min.1 && p_min.1 < p1.1 {
+        p0 = at_y(p0, p1, p_min.1);
+    }
+    if p1.1 < p_min.1 && p_min.1 < p0.1 {
+        p1 = at_y(p1, p0, p_min.1);
+    }
+    if p0.1 < p_max.1 && p_max.1 < p1.1 {
+        p1 = at_y(p0, p1, p_max.1);
+    }
+    if p1.1 < p_max.1 && p_max.1 < p0.1 {
+        p0 = at_y(p1, p0, p_max.1);
+    }
+    let p0 = clamp(p0);
+    let p1 = clamp(p1);
+    let h0 = p_max.1 - p0.1;
+    let h1 = p_max.1 - p1.1;
+    let a0 = (p0.0 - x0) * h0;
+    let a1 = (p1.0 - p0.0) * (h0 + h1) * 0.5;
+    let a2 = (x1 - p1.0) * h1;
+    a0 + a1 + a2
+}
+
+// Computes the premultiplied colour of a paint at a pixel.
+enum PaintSampler<'a> {
+    Color([f32; 4]),
+    Gradient {
+        gradient: &'a SvgGradient,
+        // maps pixels to the coordinate system of the gradient
+        inverse: AffineTransformation,
+        opacity: f32,
+    },
+}
+
+impl<'a> PaintSampler<'a> {
+    fn new(paint: &'a SvgPaint, transform: AffineTransformation, opacity: f64) -> Self {
+        match paint {
+            SvgPaint::Color(color) => PaintSampler::Color(premultiply(*color, opacity as f32)),
+            SvgPaint::Gradient(gradient) => match transform.compose(gradient.transform).invert() {
+                Some(inverse) => PaintSampler::Gradient {
+                    gradient,
+                    inverse,
+                    opacity: opacity as f32
+                },
+                None => PaintSampler::Color(premultiply(gradient.stops[gradient.stops.len() - 1].1, opacity as f32))
+            }
+        }
+    }
+
+    fn sample(&self, pixel: Point) -> [f32; 4] {
+        match self {
+            PaintSampler::Color(color) => *color,
+            PaintSampler::Gradient {gradient, inverse, opacity} => {
+                let p = pixel.transform(inverse);
+                let t = match gradient_offset(gradient.kind, p) {
+                    Some(t) => t,
+                    None => return premultiply(gradient.stops[gradient.stops.len() - 1].1, *opacity)
+                };
+                let t = match gradient.spread {
+                    SvgSpread::Pad => t.clamp(0.0, 1.0),
+                    SvgSpread::Repeat => t - t.floor(),
+                    SvgSpread::Reflect => {
+                        let t = t.rem_euclid(2.0);
+                        if t > 1.0 {2.0 - t} else {t}
+                    }
+                };
+                premultiply(stop_color(&gradient.stops, t), *opacity)
+            }
+        }
+    }
+}
+
+fn premultiply(color: Vec4, opacity: f32) -> [f32; 4] {
+    let a = color.w * opacity;
+    [color.x * a, color.y * a, color.z * a, a]
+}
+
+// Returns where a point lies along a gradient, or `None` if the gradient is degenerate.
+fn gradient_offset(kind: SvgGradientKind, p: Point) -> Option<f64> {
+    match kind {
+        SvgGradientKind::Linear {start, end} => {
+            let d = end - start;
+            let length_squared = d.x * d.x + d.y * d.y;
+            if length_squared <= 0.0 {
+                return None
+            }
+            let e = p - start;
+            Some((e.x * d.x + e.y * d.y) / length_squared)
+        }
+        SvgGradientKind::Radial {center, radius, focus} => {
+            if radius <= 0.0 {
+                return None
+            }
+            // a focus outside of the circle is moved onto it, just inside
+            let mut cf = center - focus;
+            let distance = (cf.x * cf.x + cf.y * cf.y).sqrt();
+            if distance > radius * 0.99 {
+                cf *= radius * 0.99 / distance;
+            }
+            let focus = center - cf;
+            // find the t for which p lies on the circle around focus + cf * t with radius * t
+            let e = p - focus;
+            let a = cf.x * cf.x + cf.y * cf.y - radius * radius;
+            let b = -2.0 * (e.x * cf.x + e.y * cf.y);
+            let c = e.x * e.x + e.y * e.y;
+            let discriminant = (b * b - 4.0 * a * c).max(0.0).sqrt();
+            Some(((-b + discriminant) / (2.0 * a)).max((-b - discriminant) / (2.0 * a)))
+        }
+    }
+}
+
+fn stop_color(stops: &[(f64, Vec4)], t: f64) -> Vec4 {
+    let (first_offset, first_color) = stops[0];
+    if t <= first_offset {
+        return first_color
+    }
+    for window in stops.windows(2) {
+        let (offset0, color0) = window[0];
+        let (offset1, color1) = window[1];
+        if t <= offset1 {
+            if offset1 <= offset0 {
+                return color1
+            }
+            let s = ((t - offset0) / (offset1 - offset0)) as f32;
+            return color0 + (color1 - color0) * s
+        }
+    }
+    stops[stops.len() - 1].1
+}
@@ -39,6 +39,20 @@ impl AffineTransformation {
     pub fn translate(self, v: Vector) -> AffineTransformation {
         AffineTransformation::new(self.xy, self.z + v)
     }
+
+    /// Returns the transformation that applies `other` first, and then `self`.
+    pub fn compose(self, other: AffineTransformation) -> AffineTransformation {
+        AffineTransformation::new(
+            self.xy.compose(other.xy),
+            self.transform_vector(other.z) + self.z,
+        )
+    }
+
+    /// Returns the inverse of `self`, or `None` if `self` is not invertible.
+    pub fn invert(self) -> Option<AffineTransformation> {
+        let xy = self.xy.invert()?;
+        Some(AffineTransformation::new(xy, -xy.transform_vector(self.z)))
+    }
 }
 
 impl Transformation for AffineTransformation {
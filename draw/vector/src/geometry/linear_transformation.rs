@@ -38,6 +38,22 @@ impl LinearTransformation {
             self.transform_vector(other.y),
         )
     }
+
+    pub fn determinant(self) -> f64 {
+        self.x.cross(self.y)
+    }
+
+    /// Returns the inverse of `self`, or `None` if `self` is not invertible.
+    pub fn invert(self) -> Option<LinearTransformation> {
+        let determinant = self.determinant();
+        if determinant == 0.0 || !determinant.is_finite() {
+            return None;
+        }
+        Some(LinearTransformation::new(
+            Vector::new(self.y.y, -self.x.y) / determinant,
+            Vector::new(-self.y.x, self.x.x) / determinant,
+        ))
+    }
 }
 
 impl Transformation for LinearTransformation {
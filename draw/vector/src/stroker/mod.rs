@@ -0,0 +1,406 @@
+use crate::geometry::{Point, Vector};
+use crate::path::{LinePathCommand, LinePathIterator, PathCommand};
+use std::f64::consts::PI;
+
+/// The shape at the ends of open contours.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum LineCap {
+    #[default]
+    Butt,
+    Round,
+    Square,
+}
+
+/// The shape at the corners between line segments.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum LineJoin {
+    #[default]
+    Miter,
+    Round,
+    Bevel,
+}
+
+/// The options for stroking a path. The defaults are those of SVG.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StrokeOptions {
+    pub width: f64,
+    pub line_cap: LineCap,
+    pub line_join: LineJoin,
+    /// The maximum ratio of the length of a miter to the width of the stroke, beyond which a
+    /// miter join becomes a bevel join.
+    pub miter_limit: f64,
+    /// The maximum distance between a round join or cap and its approximation.
+    pub tolerance: f64,
+}
+
+impl Default for StrokeOptions {
+    fn default() -> StrokeOptions {
+        StrokeOptions {
+            width: 1.0,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Miter,
+            miter_limit: 4.0,
+            tolerance: 0.1,
+        }
+    }
+}
+
+/// Converts a sequence of line path commands to a set of closed polygons that together cover the
+/// stroke of the path. The polygons overlap, and all have the same orientation, so they should be
+/// filled with the non-zero fill rule.
+#[derive(Clone, Debug, Default)]
+pub struct Stroker {
+    points: Vec<Point>,
+}
+
+impl Stroker {
+    /// Creates a new stroker.
+    pub fn new() -> Stroker {
+        Stroker::default()
+    }
+
+    /// Appends the polygons that cover the stroke of the given line path to `output`.
+    pub fn stroke<P: LinePathIterator>(
+        &mut self,
+        path: P,
+        options: &StrokeOptions,
+        output: &mut Vec<PathCommand>,
+    ) {
+        let points = &mut self.points;
+        points.clear();
+        // After a contour is closed, a line to without a move to starts at its initial point.
+        let mut closed_initial_point = None;
+        path.for_each(&mut |command| {
+            match command {
+                LinePathCommand::MoveTo(p) => {
+                    stroke_contour(points, false, options, output);
+                    points.clear();
+                    closed_initial_point = None;
+                    points.push(p);
+                }
+                LinePathCommand::LineTo(p) => {
+                    if let Some(p0) = closed_initial_point.take() {
+                        points.push(p0);
+                    }
+                    if points.last() != Some(&p) {
+                        points.push(p);
+                    }
+                }
+                LinePathCommand::Close => {
+                    closed_initial_point = points.first().cloned();
+                    stroke_contour(points, true, options, output);
+                    points.clear();
+                }
+            }
+            true
+        });
+        stroke_contour(points, false, options, output);
+        points.clear();
+    }
+}
+
+/// Splits a line path into the dashes of a dash pattern, and appends them to `output` as open
+/// contours. The pattern alternates between the lengths of dashes and gaps, and is repeated twice
+/// if it has an odd number of lengths. Every contour starts at `offset` into the pattern. A pattern
+/// without a positive total length leaves the path undashed.
+pub fn dash<P: LinePathIterator>(
+    path: P,
+    pattern: &[f64],
+    offset: f64,
+    output: &mut Vec<LinePathCommand>,
+) {
+    let mut dasher = Dasher::new(pattern, offset);
+    let mut initial_point = None;
+    let mut current_point = None;
+    path.for_each(&mut |command| {
+        match command {
+            LinePathCommand::MoveTo(p) => {
+                initial_point = Some(p);
+                current_point = Some(p);
+                dasher.start(p, output);
+            }
+            LinePathCommand::LineTo(p) => {
+                let p0 = match current_point {
+                    Some(p0) => p0,
+                    None => {
+                        dasher.start(p, output);
+                        p
+                    }
+                };
+                dasher.line_to(p0, p, output);
+                current_point = Some(p);
+            }
+            LinePathCommand::Close => {
+                if let (Some(p0), Some(p)) = (current_point, initial_point) {
+                    dasher.line_to(p0, p, output);
+                    // A line to after a close starts a new contour at the initial point.
+                    dasher.start(p, output);
+                    current_point = Some(p);
+                }
+            }
+        }
+        true
+    });
+}
+
+struct Dasher<'a> {
+    pattern: &'a [f64],
+    is_odd: bool,
+    offset: f64,
+    // The index of the current dash or gap in the pattern repeated twice.
+    index: usize,
+    remaining: f64,
+}
+
+impl<'a> Dasher<'a> {
+    fn new(pattern: &'a [f64], offset: f64) -> Dasher<'a> {
+        let is_valid = pattern.iter().all(|length| length.is_finite() && *length >= 0.0)
+            && pattern.iter().sum::<f64>() > 0.0;
+        Dasher {
+            pattern: if is_valid { pattern } else { &[] },
+            is_odd: pattern.len() % 2 == 1,
+            offset,
+            index: 0,
+            remaining: 0.0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        if self.is_odd {
+            self.pattern.len() * 2
+        } else {
+            self.pattern.len()
+        }
+    }
+
+    fn is_dash(&self) -> bool {
+        self.index & 1 == 0
+    }
+
+    fn length(&self, index: usize) -> f64 {
+        self.pattern[index % self.pattern.len()]
+    }
+
+    fn start(&mut self, p: Point, output: &mut Vec<LinePathCommand>) {
+        if self.pattern.is_empty() {
+            output.push(LinePathCommand::MoveTo(p));
+            return;
+        }
+        let total_length = (0..self.len()).map(|index| self.length(index)).sum::<f64>();
+        let mut offset = self.offset.rem_euclid(total_length);
+        self.index = 0;
+        while offset > 0.0 && offset >= self.length(self.index) {
+            offset -= self.length(self.index);
+            self.index = (self.index + 1) % self.len();
+        }
+        self.remaining = self.length(self.index) - offset;
+        if self.is_dash() {
+            output.push(LinePathCommand::MoveTo(p));
+        }
+    }
+
+    fn line_to(&mut self, p0: Point, p1: Point, output: &mut Vec<LinePathCommand>) {
+        if self.pattern.is_empty() {
+            output.push(LinePathCommand::LineTo(p1));
+            return;
+        }
+        let length = (p1 - p0).length();
+        let mut position = 0.0;
+        while length - position > self.remaining {
+            position += self.remaining;
+            let p = p0.lerp(p1, position / length);
+            output.push(if self.is_dash() {
+                LinePathCommand::LineTo(p)
+            } else {
+                LinePathCommand::MoveTo(p)
+            });
+            self.index = (self.index + 1) % self.len();
+            self.remaining = self.length(self.index);
+        }
+        self.remaining -= length - position;
+        if self.is_dash() {
+            output.push(LinePathCommand::LineTo(p1));
+        }
+    }
+}
+
+fn stroke_contour(
+    points: &[Point],
+    is_closed: bool,
+    options: &StrokeOptions,
+    output: &mut Vec<PathCommand>,
+) {
+    let half_width = options.width / 2.0;
+    if points.is_empty() || half_width.is_nan() || half_width <= 0.0 {
+        return;
+    }
+    let mut points = points;
+    if is_closed && points.len() > 1 && points.first() == points.last() {
+        points = &points[..points.len() - 1];
+    }
+    if points.len() == 1 {
+        // A contour without length only has caps.
+        let p = points[0];
+        match options.line_cap {
+            LineCap::Butt => {}
+            LineCap::Round => push_circle(p, half_width, options.tolerance, output),
+            LineCap::Square => {
+                let v = Vector::new(half_width, half_width);
+                push_polygon(
+                    &[
+                        p - v,
+                        p + Vector::new(v.x, -v.y),
+                        p + v,
+                        p + Vector::new(-v.x, v.y),
+                    ],
+                    output,
+                );
+            }
+        }
+        return;
+    }
+    let segment_count = if is_closed {
+        points.len()
+    } else {
+        points.len() - 1
+    };
+    let direction = |index: usize| {
+        let p0 = points[index % points.len()];
+        let p1 = points[(index + 1) % points.len()];
+        (p1 - p0).normalize().unwrap_or(Vector::new(1.0, 0.0))
+    };
+    for index in 0..segment_count {
+        let p0 = points[index];
+        let p1 = points[(index + 1) % points.len()];
+        let normal = left_normal(direction(index)) * half_width;
+        push_polygon(&[p0 + normal, p1 + normal, p1 - normal, p0 - normal], output);
+    }
+    let join_range = if is_closed {
+        0..points.len()
+    } else {
+        1..points.len() - 1
+    };
+    for index in join_range {
+        let d0 = direction((index + points.len() - 1) % points.len());
+        let d1 = direction(index);
+        push_join(points[index], d0, d1, half_width, options, output);
+    }
+    if !is_closed {
+        let d0 = direction(0);
+        let d1 = direction(segment_count - 1);
+        push_cap(points[0], -d0, half_width, options, output);
+        push_cap(points[points.len() - 1], d1, half_width, options, output);
+    }
+}
+
+// Pushes the join at `p` between a segment with direction `d0` and one with direction `d1`.
+fn push_join(
+    p: Point,
+    d0: Vector,
+    d1: Vector,
+    half_width: f64,
+    options: &StrokeOptions,
+    output: &mut Vec<PathCommand>,
+) {
+    let cross = d0.cross(d1);
+    let dot = d0.dot(d1);
+    if cross.abs() < 1E-9 && dot > 0.0 {
+        return;
+    }
+    if options.line_join == LineJoin::Round {
+        push_circle(p, half_width, options.tolerance, output);
+        return;
+    }
+    // The join goes on the outer side of the corner.
+    let side = if cross > 0.0 { -1.0 } else { 1.0 };
+    let n0 = left_normal(d0) * side;
+    let n1 = left_normal(d1) * side;
+    let p0 = p + n0 * half_width;
+    let p1 = p + n1 * half_width;
+    if options.line_join == LineJoin::Miter {
+        let cos_half_angle = ((1.0 + dot) / 2.0).max(0.0).sqrt();
+        if cos_half_angle > 0.0 && 1.0 / cos_half_angle <= options.miter_limit {
+            if let Some(miter_direction) = (n0 + n1).normalize() {
+                let miter = p + miter_direction * (half_width / cos_half_angle);
+                push_polygon(&[p, p0, miter, p1], output);
+                return;
+            }
+        }
+    }
+    push_polygon(&[p, p0, p1], output);
+}
+
+// Pushes the cap at the end `p` of a contour that leaves it in the direction `d`.
+fn push_cap(
+    p: Point,
+    d: Vector,
+    half_width: f64,
+    options: &StrokeOptions,
+    output: &mut Vec<PathCommand>,
+) {
+    match options.line_cap {
+        LineCap::Butt => {}
+        LineCap::Round => push_circle(p, half_width, options.tolerance, output),
+        LineCap::Square => {
+            let normal = left_normal(d) * half_width;
+            let extension = d * half_width;
+            push_polygon(
+                &[
+                    p + normal,
+                    p + normal + extension,
+                    p - normal + extension,
+                    p - normal,
+                ],
+                output,
+            );
+        }
+    }
+}
+
+fn push_circle(center: Point, radius: f64, tolerance: f64, output: &mut Vec<PathCommand>) {
+    let segment_count = if tolerance < radius {
+        (PI / (1.0 - tolerance / radius).acos()).ceil().clamp(8.0, 256.0) as usize
+    } else {
+        8
+    };
+    let mut points = Vec::with_capacity(segment_count);
+    for index in 0..segment_count {
+        let angle = 2.0 * PI * index as f64 / segment_count as f64;
+        points.push(center + Vector::new(angle.cos(), angle.sin()) * radius);
+    }
+    push_polygon(&points, output);
+}
+
+// Pushes a polygon with a positive signed area, so that all polygons have the same orientation.
+fn push_polygon(points: &[Point], output: &mut Vec<PathCommand>) {
+    let mut area = 0.0;
+    for index in 0..points.len() {
+        let p0 = points[index].to_vector();
+        let p1 = points[(index + 1) % points.len()].to_vector();
+        area += p0.cross(p1);
+    }
+    if area == 0.0 || !area.is_finite() {
+        return;
+    }
+    let mut push = |index: usize, p: Point| {
+        output.push(if index == 0 {
+            PathCommand::MoveTo(p)
+        } else {
+            PathCommand::LineTo(p)
+        });
+    };
+    if area > 0.0 {
+        for (index, &p) in points.iter().enumerate() {
+            push(index, p);
+        }
+    } else {
+        for (index, &p) in points.iter().rev().enumerate() {
+            push(index, p);
+        }
+    }
+    output.push(PathCommand::Close);
+}
+
+fn left_normal(d: Vector) -> Vector {
+    Vector::new(-d.y, d.x)
+}
@@ -6,12 +6,43 @@ use std::collections::BinaryHeap;
 use std::mem;
 use std::ops::Range;
 
+/// The rule that decides which regions of a set of contours are inside.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum FillRule {
+    /// A region is inside if its winding number is not zero.
+    #[default]
+    NonZero,
+    /// A region is inside if its winding number is odd.
+    EvenOdd,
+}
+
+impl FillRule {
+    fn is_inside(self, winding: i32) -> bool {
+        match self {
+            FillRule::NonZero => winding != 0,
+            FillRule::EvenOdd => winding % 2 != 0,
+        }
+    }
+}
+
+/// Which of the sloped edges of a trapezoid lie on the outline of the filled area. An edge that
+/// does not is shared with another trapezoid of the same area.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct TrapezoidEdges {
+    /// The edge through `ys[0]` and `ys[1]`.
+    pub lower: bool,
+    /// The edge through `ys[2]` and `ys[3]`.
+    pub upper: bool,
+}
+
 /// Converts a sequence of line path commands to a sequence of trapezoids. The line path commands
 /// should define a set of closed contours.
 #[derive(Clone, Debug, Default)]
 pub struct Trapezoidator {
     event_queue: BinaryHeap<Event>,
     active_segments: Vec<ActiveSegment>,
+    fill_rule: FillRule,
+    segments: Vec<LineSegment>,
 }
 
 impl Trapezoidator {
@@ -23,8 +54,21 @@ impl Trapezoidator {
     /// Returns an iterator over trapezoids corresponding to the given iterator over line path
     /// commands.
     pub fn trapezoidate<P: LinePathIterator>(&mut self, path: P)->Option<Trapezoidate>{
+        self.trapezoidate_with_fill_rule(path, FillRule::NonZero)
+    }
+
+    /// Returns an iterator over trapezoids corresponding to the given iterator over line path
+    /// commands, with the inside of the contours decided by the given fill rule.
+    pub fn trapezoidate_with_fill_rule<P: LinePathIterator>(
+        &mut self,
+        path: P,
+        fill_rule: FillRule,
+    ) -> Option<Trapezoidate<'_>> {
+        self.fill_rule = fill_rule;
         let mut initial_point = None;
         let mut current_point = None;
+        let segments = &mut self.segments;
+        segments.clear();
         if !path.for_each(&mut |command| {
             match command {
                 LinePathCommand::MoveTo(p) => {
@@ -34,27 +78,87 @@ impl Trapezoidator {
                 }
                 LinePathCommand::LineTo(p) => {
                     let p0 = current_point.replace(p).unwrap();
-                    if self.push_events_for_segment(LineSegment::new(p0, p)){
-                        return false
-                    }
+                    segments.push(LineSegment::new(p0, p));
                 }
                 LinePathCommand::Close => {
                     let p = initial_point.take().unwrap();
                     let p0 = current_point.replace(p).unwrap();
-                    if self.push_events_for_segment(LineSegment::new(p0, p)){
-                        return false
-                    }
+                    segments.push(LineSegment::new(p0, p));
                 }
             }
             true
         }){
             return None
         };
+        self.split_intersecting_segments();
+        for index in 0..self.segments.len() {
+            if self.push_events_for_segment(self.segments[index]) {
+                self.event_queue.clear();
+                return None;
+            }
+        }
         Some(Trapezoidate {
             trapezoidator: self,
         })
     }
 
+    // The sweep below assumes that segments only meet at their endpoints, so segments that cross
+    // each other, as in self-intersecting or overlapping contours, are split where they cross.
+    fn split_intersecting_segments(&mut self) {
+        let segments = &self.segments;
+        let min_x = |index: usize| segments[index].p0.x.min(segments[index].p1.x);
+        let max_x = |index: usize| segments[index].p0.x.max(segments[index].p1.x);
+        let mut order = Vec::with_capacity(segments.len());
+        order.extend(0..segments.len());
+        order.sort_by(|&index_0, &index_1| {
+            min_x(index_0)
+                .partial_cmp(&min_x(index_1))
+                .unwrap_or(Ordering::Equal)
+        });
+        // Only segments that overlap horizontally can intersect.
+        let mut active = Vec::new();
+        let mut splits = Vec::new();
+        for &index in &order {
+            let x = min_x(index);
+            active.retain(|&other| max_x(other) >= x);
+            for &other in &active {
+                if let Some((t, u, p)) = intersect_segments(segments[index], segments[other]) {
+                    if t > 0.0 && t < 1.0 {
+                        splits.push((index, t, p));
+                    }
+                    if u > 0.0 && u < 1.0 {
+                        splits.push((other, u, p));
+                    }
+                }
+            }
+            active.push(index);
+        }
+        if splits.is_empty() {
+            return;
+        }
+        splits.sort_by(|split_0, split_1| {
+            (split_0.0, split_0.1)
+                .partial_cmp(&(split_1.0, split_1.1))
+                .unwrap_or(Ordering::Equal)
+        });
+        let mut split_segments = Vec::with_capacity(segments.len() + splits.len());
+        let mut splits = splits.into_iter().peekable();
+        for (index, segment) in segments.iter().enumerate() {
+            let mut p0 = segment.p0;
+            while let Some(&(_, _, p)) = splits.peek().filter(|split| split.0 == index) {
+                splits.next();
+                if p != p0 {
+                    split_segments.push(LineSegment::new(p0, p));
+                    p0 = p;
+                }
+            }
+            if segment.p1 != p0 {
+                split_segments.push(LineSegment::new(p0, segment.p1));
+            }
+        }
+        self.segments = split_segments;
+    }
+
     fn push_events_for_segment(&mut self, segment: LineSegment)->bool {
         let (winding, p0, p1) = match segment.p0.partial_cmp(&segment.p1) {
             None => return true,
@@ -102,14 +206,14 @@ impl Trapezoidator {
         f: &mut F,
     ) -> bool
     where
-        F: FnMut(Trapezoid) -> bool,
+        F: FnMut(Trapezoid, TrapezoidEdges) -> bool,
     {
         let mut incident_segment_range = self.find_incident_segment_range(point);
-        if let Some(trapezoid_segment) =
-            self.find_lower_trapezoid_segment(point, incident_segment_range.start)
-        {
-            trapezoid_segments.push(trapezoid_segment);
-        }
+        self.find_lower_trapezoid_segments(
+            point,
+            incident_segment_range.start,
+            trapezoid_segments,
+        );
         self.remove_incident_segments(
             point,
             &mut incident_segment_range,
@@ -145,23 +249,34 @@ impl Trapezoidator {
         }
     }
 
-    fn find_lower_trapezoid_segment(
+    fn find_lower_trapezoid_segments(
         &mut self,
         point: Point,
         incident_segment_start: usize,
-    ) -> Option<ActiveSegment> {
-        if 0 == incident_segment_start
-            || !self.active_segments[incident_segment_start - 1]
+        trapezoid_segments: &mut Vec<ActiveSegment>,
+    ) {
+        // Splitting a segment ends the trapezoids on both sides of it, so if the region below the
+        // segment is inside as well, the segment below it is split too, and so on.
+        let mut lower_segment_start = incident_segment_start;
+        while 0 < lower_segment_start
+            && self.active_segments[lower_segment_start - 1]
                 .upper_region
                 .is_inside
         {
-            return None;
+            lower_segment_start -= 1;
         }
-        let intersection = self.active_segments[incident_segment_start - 1]
-            .segment
-            .intersect_with_vertical_line(point.x)
-            .unwrap();
-        self.active_segments[incident_segment_start - 1].split_front_mut(intersection)
+        let first_trapezoid_segment = trapezoid_segments.len();
+        for index in (lower_segment_start..incident_segment_start).rev() {
+            let intersection = self.active_segments[index]
+                .segment
+                .intersect_with_vertical_line(point.x)
+                .unwrap();
+            match self.active_segments[index].split_front_mut(intersection) {
+                Some(trapezoid_segment) => trapezoid_segments.push(trapezoid_segment),
+                None => break,
+            }
+        }
+        trapezoid_segments[first_trapezoid_segment..].reverse();
     }
 
     fn remove_incident_segments(
@@ -220,13 +335,14 @@ impl Trapezoidator {
         } else {
             self.active_segments[incident_segment_range.end - 1].upper_region
         };
+        let fill_rule = self.fill_rule;
         self.active_segments.splice(
             incident_segment_range.end..incident_segment_range.end,
             Iterator::map(right_segments.iter(), |right_segment| {
                 let upper_region = {
                     let winding = lower_region.winding + right_segment.winding;
                     Region {
-                        is_inside: winding != 0,
+                        is_inside: fill_rule.is_inside(winding),
                         winding,
                     }
                 };
@@ -271,7 +387,7 @@ impl Trapezoidator {
 
     fn generate_trapezoids<F>(&self, trapezoid_segments: &[ActiveSegment], f: &mut F) -> bool
     where
-        F: FnMut(Trapezoid) -> bool,
+        F: FnMut(Trapezoid, TrapezoidEdges) -> bool,
     {
         for trapezoid_segment_pair in trapezoid_segments.windows(2) {
             if !trapezoid_segment_pair[0].upper_region.is_inside {
@@ -279,6 +395,12 @@ impl Trapezoidator {
             }
             let lower_segment = trapezoid_segment_pair[0].segment;
             let upper_segment = trapezoid_segment_pair[1].segment;
+            let lower_winding = trapezoid_segment_pair[0].upper_region.winding
+                - trapezoid_segment_pair[0].winding;
+            let edges = TrapezoidEdges {
+                lower: !self.fill_rule.is_inside(lower_winding),
+                upper: !trapezoid_segment_pair[1].upper_region.is_inside,
+            };
             if !f(Trapezoid {
                 xs: [lower_segment.p0.x as f32, lower_segment.p1.x as f32],
                 ys: [
@@ -287,7 +409,7 @@ impl Trapezoidator {
                     upper_segment.p0.y as f32,
                     upper_segment.p1.y as f32,
                 ],
-            }) {
+            }, edges) {
                 return false;
             }
         }
@@ -301,12 +423,41 @@ pub struct Trapezoidate<'a> {
     trapezoidator: &'a mut Trapezoidator,
 }
 
+impl<'a> Trapezoidate<'a> {
+    /// Returns an iterator over the trapezoids together with which of their edges lie on the
+    /// outline of the filled area.
+    pub fn with_edges(self) -> TrapezoidateWithEdges<'a> {
+        TrapezoidateWithEdges {
+            trapezoidator: self.trapezoidator,
+        }
+    }
+}
+
 impl<'a> InternalIterator for Trapezoidate<'a> {
     type Item = Trapezoid;
 
     fn for_each<F>(self, f: &mut F) -> bool
     where
         F: FnMut(Trapezoid) -> bool,
+    {
+        self.with_edges()
+            .for_each(&mut |(trapezoid, _)| f(trapezoid))
+    }
+}
+
+/// An iterator over trapezoids and their edges corresponding to the given iterator over line path
+/// commands.
+#[derive(Debug)]
+pub struct TrapezoidateWithEdges<'a> {
+    trapezoidator: &'a mut Trapezoidator,
+}
+
+impl<'a> InternalIterator for TrapezoidateWithEdges<'a> {
+    type Item = (Trapezoid, TrapezoidEdges);
+
+    fn for_each<F>(self, f: &mut F) -> bool
+    where
+        F: FnMut((Trapezoid, TrapezoidEdges)) -> bool,
     {
         let mut right_segments = Vec::new();
         let mut trapezoid_segments = Vec::new();
@@ -315,7 +466,7 @@ impl<'a> InternalIterator for Trapezoidate<'a> {
                 point,
                 &mut right_segments,
                 &mut trapezoid_segments,
-                f,
+                &mut |trapezoid, edges| f((trapezoid, edges)),
             );
             right_segments.clear();
             trapezoid_segments.clear();
@@ -327,6 +478,36 @@ impl<'a> InternalIterator for Trapezoidate<'a> {
     }
 }
 
+// Returns where two segments intersect, as the parameters along both of them and the point, or
+// None if they don't intersect or are parallel. An intersection at an endpoint of either segment
+// is that endpoint exactly.
+fn intersect_segments(segment_0: LineSegment, segment_1: LineSegment) -> Option<(f64, f64, Point)> {
+    let r = segment_0.p1 - segment_0.p0;
+    let s = segment_1.p1 - segment_1.p0;
+    let denominator = r.cross(s);
+    if denominator == 0.0 || !denominator.is_finite() {
+        return None;
+    }
+    let v = segment_1.p0 - segment_0.p0;
+    let t = v.cross(s) / denominator;
+    let u = v.cross(r) / denominator;
+    if !(0.0..=1.0).contains(&t) || !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let p = if u == 0.0 {
+        segment_1.p0
+    } else if u == 1.0 {
+        segment_1.p1
+    } else if t == 0.0 {
+        segment_0.p0
+    } else if t == 1.0 {
+        segment_0.p1
+    } else {
+        segment_0.p0 + r * t
+    };
+    Some((t, u, p))
+}
+
 #[derive(Clone, Copy, Debug)]
 struct Event {
     point: Point,
@@ -60,6 +60,9 @@ app_main!(App);
 
 pub struct App {
     #[live] ui: WidgetRef,
+    // the reply so far, and the part of the event stream that isn't a whole event yet
+    #[rust] reply: String,
+    #[rust] stream_buffer: String,
 }
 
 impl LiveHook for App {
@@ -71,7 +74,8 @@ impl LiveHook for App {
 impl App {
     // This performs and event-based http request: it has no relationship with the response.
     // The response will be received and processed by AppMain's handle_event.
-    fn send_message(cx: &mut Cx, message: String) {
+    // The reply streams in as server-sent events, one per token.
+    fn send_message(&mut self, cx: &mut Cx, message: String) {
         let completion_url = format!("{}/chat/completions", OPENAI_BASE_URL);
         let request_id = live_id!(SendChatMessage);
         let mut request = HttpRequest::new(completion_url, HttpMethod::POST);
@@ -82,11 +86,37 @@ impl App {
         request.set_json_body(ChatPrompt {
             messages: vec![Message {content: message, role: "user".to_string()}],
             model: "gpt-3.5-turbo".to_string(),
-            max_tokens: 100
+            max_tokens: 100,
+            stream: true
         });
+        request.set_is_streaming();
+        request.set_timeout(30.0);
         
+        // a new question replaces the answer that may still be coming in
+        cx.cancel_http_request(request_id);
+        self.reply.clear();
+        self.stream_buffer.clear();
         cx.http_request(request_id, request);
     }
+    
+    // Takes the tokens out of the whole events in the buffer
+    fn handle_stream_chunk(&mut self, chunk: &[u8]) {
+        self.stream_buffer.push_str(&String::from_utf8_lossy(chunk));
+        while let Some(end) = self.stream_buffer.find("\n\n") {
+            let event: String = self.stream_buffer.drain(..end + 2).collect();
+            for data in event.lines().filter_map( | line | line.strip_prefix("data:")) {
+                let data = data.trim();
+                if data == "[DONE]" {
+                    continue
+                }
+                if let Ok(value) = JsonValue::deserialize_json(data) {
+                    if let Some(content) = value.path("choices.0.delta.content").and_then( | c | c.as_str()) {
+                        self.reply.push_str(content);
+                    }
+                }
+            }
+        }
+    }
 }
 
 impl AppMain for App {
@@ -96,20 +126,32 @@ impl AppMain for App {
         }
         
         for event in event.network_responses() {
+            if event.request_id != live_id!(SendChatMessage) {
+                continue
+            }
             match &event.response {
+                NetworkResponse::HttpStreamResponse(response) => {
+                    if response.status_code != 200 {
+                        let label = self.ui.label(id!(message_label));
+                        label.set_text_and_redraw(cx, "Failed to connect with OpenAI");
+                        cx.cancel_http_request(event.request_id);
+                    }
+                }
+                NetworkResponse::HttpStreamChunk(chunk) => {
+                    self.handle_stream_chunk(chunk);
+                    let label = self.ui.label(id!(message_label));
+                    label.set_text_and_redraw(cx, &self.reply);
+                }
+                // platforms that don't stream deliver all the events at once
                 NetworkResponse::HttpResponse(response) => {
                     let label = self.ui.label(id!(message_label));
-                    match event.request_id {
-                        live_id!(SendChatMessage) => {
-                            if response.status_code == 200 {
-                                let chat_response = response.get_json_body::<ChatResponse>().unwrap();
-                                label.set_text_and_redraw(cx, &chat_response.choices[0].message.content);
-                            } else {
-                                label.set_text_and_redraw(cx, "Failed to connect with OpenAI");
-                            }
-                            label.redraw(cx);
-                        },
-                        _ => (),
+                    if response.status_code == 200 {
+                        if let Some(body) = response.get_body() {
+                            self.handle_stream_chunk(body);
+                        }
+                        label.set_text_and_redraw(cx, &self.reply);
+                    } else {
+                        label.set_text_and_redraw(cx, "Failed to connect with OpenAI");
                     }
                 }
                 NetworkResponse::HttpRequestError(error) => {
@@ -124,7 +166,7 @@ impl AppMain for App {
         
         if self.ui.button(id!(send_button)).clicked(&actions) {
             let user_prompt = self.ui.text_input(id!(message_input)).text();
-            Self::send_message(cx, user_prompt);
+            self.send_message(cx, user_prompt);
         }
     }
 }
@@ -133,7 +175,8 @@ impl AppMain for App {
 struct ChatPrompt {
     pub messages: Vec<Message>,
     pub model: String,
-    pub max_tokens: i32
+    pub max_tokens: i32,
+    pub stream: bool
 }
 
 #[derive(SerJson, DeJson)]
@@ -141,27 +184,3 @@ struct Message {
     pub content: String,
     pub role: String
 }
-
-#[derive(SerJson, DeJson)]
-struct ChatResponse {
-    pub id: String,
-    pub object: String,
-    pub created: i32,
-    pub model: String,
-    pub usage: Usage,
-    pub choices: Vec<Choice>,
-}
-
-#[derive(SerJson, DeJson)]
-pub struct Usage {
-    prompt_tokens: i32,
-    completion_tokens: i32,
-    total_tokens: i32,
-}
-
-#[derive(SerJson, DeJson)]
-struct Choice {
-    message: Message,
-    finish_reason: String,
-    index: i32,
-}
@@ -8,6 +8,7 @@ live_design!{
     import crate::vectorline::VectorLine;
     import crate::drawarc::VectorArc;  
     import crate::drawarc::VectorCornerArc;
+    import crate::pathchart::PathChart;
     
     App = {{App}} {
         ui: <Window> {
@@ -39,7 +40,7 @@ live_design!{
                 }
                         
                 graph_tabs = Tabs {
-                    tabs: [arctest, boxtest, linesoverview],
+                    tabs: [arctest, boxtest, linesoverview, pathtest],
                     selected: 1
                 }
 
@@ -48,6 +49,11 @@ live_design!{
                     kind: Line1
                 }
 
+                pathtest = Tab {
+                    name: "Path Test"
+                    kind: Line4
+                }
+
                 arctest = Tab {
                     name: "Arc Test"
                     kind: Line2
@@ -67,6 +73,11 @@ live_design!{
                     width: Fill
                    
                 }
+                Line4 = <View>{
+                    height: Fill,
+                    width: Fill,
+                    <PathChart> {}
+                }
                 Line3 = <View>{
                     height: Fill,
                     width: 400.,
@@ -324,6 +335,7 @@ impl LiveHook for App {
         crate::makepad_widgets::live_design(cx);
         crate::vectorline::live_design(cx);
         crate::drawarc::live_design(cx);
+        crate::pathchart::live_design(cx);
     }
     
     fn after_new_from_doc(&mut self, _cx: &mut Cx) {
@@ -7,4 +7,5 @@ pub use makepad_widgets::makepad_live_id;
 pub mod candlestick;
 pub mod vectorline; 
 pub mod drawarc;
+pub mod pathchart;
 pub mod app;
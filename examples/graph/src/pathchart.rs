@@ -0,0 +1,112 @@
+use crate::{makepad_draw::*, makepad_widgets::*};
+
+live_design!
+{
+    PathChart = {{PathChart}} {
+        width: Fill,
+        height: Fill,
+        padding: 20,
+        grid_color: #444,
+        area_color: #08f4,
+        line_color: #0af,
+        point_color: #fff,
+        line_width: 3
+    }
+}
+
+#[derive(Live)]
+pub struct PathChart{
+    #[walk] walk: Walk,
+    #[live] padding: f64,
+    #[live] draw_path: DrawPath,
+    #[rust] area: Area,
+    #[live(2.0)] line_width: f64,
+    #[live] grid_color: Vec4,
+    #[live] area_color: Vec4,
+    #[live] line_color: Vec4,
+    #[live] point_color: Vec4,
+}
+
+impl Widget for PathChart {
+    fn handle_widget_event_with(
+        &mut self,
+        _cx: &mut Cx,
+        _event: &Event,
+        _dispatch_action: &mut dyn FnMut(&mut Cx, WidgetActionItem),
+    ) {
+    }
+
+    fn walk(&mut self, _cx: &mut Cx) -> Walk {
+        self.walk
+    }
+
+    fn redraw(&mut self, cx: &mut Cx) {
+        self.area.redraw(cx)
+    }
+
+    fn draw_walk_widget(&mut self, cx: &mut Cx2d, walk: Walk) -> WidgetDraw {
+        self.draw_walk(cx, walk);
+        WidgetDraw::done()
+    }
+}
+
+impl LiveHook for PathChart {
+    fn before_live_design(cx: &mut Cx) {
+        register_widget!(cx, PathChart)
+    }
+}
+
+impl PathChart {
+    pub fn draw_walk(&mut self, cx: &mut Cx2d, walk: Walk) {
+        let rect = cx.walk_turtle_with_area(&mut self.area, walk);
+        let pos = rect.pos + dvec2(self.padding, self.padding);
+        let size = rect.size - dvec2(self.padding, self.padding) * 2.0;
+        if size.x <= 0.0 || size.y <= 0.0 {
+            return
+        }
+
+        // dashed grid lines
+        self.draw_path.clear();
+        for i in 0..=4 {
+            let y = pos.y + size.y * i as f64 / 4.0;
+            self.draw_path.move_to(dvec2(pos.x, y));
+            self.draw_path.line_to(dvec2(pos.x + size.x, y));
+        }
+        self.draw_path.color = self.grid_color;
+        self.draw_path.stroke_abs(cx, &PathStroke::new(1.0).with_dashes(&[4.0, 4.0], 0.0));
+
+        let points: Vec<DVec2> = (0..=24).map( | i | {
+            let t = i as f64 / 24.0;
+            let value = 0.5 + 0.3 * (t * 9.0).sin() * (1.0 - t * 0.5) + 0.1 * (t * 23.0).cos();
+            dvec2(pos.x + size.x * t, pos.y + size.y * (1.0 - value))
+        }).collect();
+
+        // the area under the data
+        self.draw_path.clear();
+        self.draw_path.move_to(dvec2(pos.x, pos.y + size.y));
+        for p in &points {
+            self.draw_path.line_to(*p);
+        }
+        self.draw_path.line_to(pos + size);
+        self.draw_path.close();
+        self.draw_path.color = self.area_color;
+        self.draw_path.fill_abs(cx, FillRule::NonZero);
+
+        // the data line and its points
+        self.draw_path.clear();
+        for p in &points {
+            self.draw_path.line_to(*p);
+        }
+        self.draw_path.color = self.line_color;
+        self.draw_path.stroke_abs(cx, &PathStroke::new(self.line_width)
+            .with_join(LineJoin::Round)
+            .with_cap(LineCap::Round));
+
+        self.draw_path.clear();
+        for p in &points {
+            self.draw_path.circle(*p, self.line_width * 1.5);
+        }
+        self.draw_path.color = self.point_color;
+        self.draw_path.fill_abs(cx, FillRule::NonZero);
+    }
+}
@@ -0,0 +1,465 @@
+use std::io::{self, prelude::*, BufReader};
+use std::net::{TcpStream, Shutdown, ToSocketAddrs};
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::utils::*;
+use crate::websocket::WebSocket;
+
+const MAX_HEADER_SIZE: usize = 64 * 1024;
+const BODY_READ_SIZE: usize = 64 * 1024;
+
+/// The parts of an http:// or https:// url a request needs, ws:// and wss:// map onto those
+#[derive(Clone, Debug, PartialEq)]
+pub struct HttpUrl {
+    pub tls: bool,
+    pub host: String,
+    pub port: u16,
+    /// the path with the query, always starting with a /
+    pub path: String,
+}
+
+impl HttpUrl {
+    pub fn parse(url: &str) -> Result<HttpUrl, String> {
+        let (tls, rest) = if let Some(rest) = url.strip_prefix("http://").or_else( || url.strip_prefix("ws://")) {
+            (false, rest)
+        }
+        else if let Some(rest) = url.strip_prefix("https://").or_else( || url.strip_prefix("wss://")) {
+            (true, rest)
+        }
+        else {
+            return Err(format!("Unsupported url {}, only http(s):// and ws(s):// are", url))
+        };
+        let rest = rest.split('#').next().unwrap_or("");
+        let (authority, path) = match rest.find(['/', '?']) {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, "/")
+        };
+        let path = if path.starts_with('?') {format!("/{}", path)} else {path.to_string()};
+        // user:password@ isn't something we send along
+        let authority = authority.rsplit('@').next().unwrap_or("");
+        let default_port = if tls {443} else {80};
+        let (host, port) = if let Some(ipv6) = authority.strip_prefix('[') {
+            let (host, port) = ipv6.split_once(']').ok_or_else( || format!("Invalid host in url {}", url)) ?;
+            (host, port.strip_prefix(':'))
+        }
+        else {
+            match authority.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None)
+            }
+        };
+        let port = match port {
+            Some(port) => port.parse().map_err( | _ | format!("Invalid port in url {}", url)) ?,
+            None => default_port
+        };
+        if host.is_empty() {
+            return Err(format!("No host in url {}", url))
+        }
+        Ok(HttpUrl {tls, host: host.to_string(), port, path})
+    }
+
+    /// The value of the Host header, which leaves out the default port
+    pub fn host_header(&self) -> String {
+        let host = if self.host.contains(':') {format!("[{}]", self.host)} else {self.host.clone()};
+        if self.port == if self.tls {443} else {80} {host} else {format!("{}:{}", host, self.port)}
+    }
+
+    /// Resolves the Location of a redirect against this url
+    pub fn join(&self, location: &str) -> String {
+        let scheme = if self.tls {"https"} else {"http"};
+        let path = self.path.split('?').next().unwrap_or("/");
+        if location.contains("://") {
+            location.to_string()
+        }
+        else if let Some(rest) = location.strip_prefix("//") {
+            format!("{}://{}", scheme, rest)
+        }
+        else if location.starts_with('/') {
+            format!("{}://{}{}", scheme, self.host_header(), location)
+        }
+        else if location.starts_with('?') {
+            format!("{}://{}{}{}", scheme, self.host_header(), path, location)
+        }
+        else {
+            let dir = &path[..path.rfind('/').map( | i | i + 1).unwrap_or(0)];
+            format!("{}://{}{}{}", scheme, self.host_header(), dir, location)
+        }
+    }
+}
+
+/// A request for the http client. Host, Content-Length and Connection are set by the client
+#[derive(Clone)]
+pub struct HttpClientRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// how long connecting, and every read and write after that, can take before the request fails
+    pub timeout: Option<Duration>,
+    /// how many redirects are followed, a redirect past that is returned as the response
+    pub max_redirects: u32,
+    /// lets another thread cancel the request
+    pub cancel: Option<HttpCancelHandle>,
+}
+
+#[derive(Clone, Debug)]
+pub struct HttpClientResponse {
+    pub status_code: u16,
+    /// in the order the server sent them, a header that is sent more than once shows up more than once
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// A websocket connection the upgrade handshake went through on, frames go over the stream
+pub struct WebSocketConnection {
+    pub stream: Box<dyn HttpStream>,
+    /// the connection underneath the stream, to shut it down from another thread
+    pub tcp_stream: TcpStream,
+    /// frames the server sent right behind the upgrade response
+    pub buffered: Vec<u8>,
+    /// the headers of the upgrade response
+    pub headers: Vec<(String, String)>,
+}
+
+/// Cancels a request from another thread by shutting down its connection. Clones cancel the same
+/// request, and compare equal to each other
+#[derive(Clone, Default)]
+pub struct HttpCancelHandle(Arc<Mutex<CancelState>>);
+
+#[derive(Default)]
+struct CancelState {
+    cancelled: bool,
+    tcp_stream: Option<TcpStream>,
+}
+
+impl PartialEq for HttpCancelHandle {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl HttpCancelHandle {
+    pub fn cancel(&self) {
+        let mut state = self.0.lock().unwrap();
+        state.cancelled = true;
+        if let Some(tcp_stream) = state.tcp_stream.take() {
+            let _ = tcp_stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.lock().unwrap().cancelled
+    }
+
+    // Keeps a handle on the connection the request is on, fails when the request was cancelled already
+    fn set_tcp_stream(&self, tcp_stream: &TcpStream) -> io::Result<()> {
+        let mut state = self.0.lock().unwrap();
+        if state.cancelled {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"))
+        }
+        state.tcp_stream = Some(tcp_stream.try_clone() ?);
+        Ok(())
+    }
+}
+
+impl HttpClientResponse {
+    /// The value of a header by case insensitive name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find( | (k, _) | k.eq_ignore_ascii_case(name)).map( | (_, v) | v.as_str())
+    }
+}
+
+impl HttpClientRequest {
+    pub fn new(method: &str, url: &str) -> Self {
+        Self {
+            method: method.to_string(),
+            url: url.to_string(),
+            headers: Vec::new(),
+            body: Vec::new(),
+            timeout: None,
+            max_redirects: 0,
+            cancel: None,
+        }
+    }
+
+    /// Sends the request over a new connection and reads the whole response, blocking until it is done.
+    /// https urls need a tls connector. progress is called as the body comes in with the bytes
+    /// loaded so far and the total, when the server said what it is
+    pub fn send(&self, tls: Option<&dyn TlsConnector>, progress: &mut dyn FnMut(u64, Option<u64>)) -> Result<HttpClientResponse, String> {
+        let mut body = Vec::new();
+        let mut response = self.send_streaming(tls, &mut | _ | (), &mut | data, total | {
+            body.extend_from_slice(data);
+            progress(body.len() as u64, total);
+        }) ?;
+        response.body = body;
+        Ok(response)
+    }
+
+    /// Sends the request and hands over the body piece by piece as it comes in, blocking until it is
+    /// done. on_head is called with the status and headers before the body starts, on_body with every
+    /// piece and the total when the server said what it is. The response returned has no body
+    pub fn send_streaming(
+        &self,
+        tls: Option<&dyn TlsConnector>,
+        on_head: &mut dyn FnMut(&HttpClientResponse),
+        on_body: &mut dyn FnMut(&[u8], Option<u64>)
+    ) -> Result<HttpClientResponse, String> {
+        let mut request = Cow::Borrowed(self);
+        let mut redirects = 0;
+        loop {
+            let url = HttpUrl::parse(&request.url) ?;
+            let (stream, _) = request.connect(&url, tls) ?;
+            let (mut reader, response) = request.send_head(&url, stream).map_err( | e | request.error(e)) ?;
+            let location = response.header("Location").filter( | _ | {
+                [301, 302, 303, 307, 308].contains(&response.status_code) && redirects < self.max_redirects
+            });
+            if let Some(location) = location {
+                redirects += 1;
+                let next_url = url.join(location);
+                let next = request.to_mut();
+                // 307 and 308 repeat the request as it was, the others turn everything but a HEAD into a GET
+                if [301, 302, 303].contains(&response.status_code) && next.method != "HEAD" && (response.status_code == 303 || next.method == "POST") {
+                    next.method = "GET".to_string();
+                    next.body.clear();
+                }
+                // credentials stay with the host they were meant for
+                if HttpUrl::parse(&next_url).map( | next_url | next_url.host != url.host).unwrap_or(true) {
+                    next.headers.retain( | (name, _) | !name.eq_ignore_ascii_case("authorization") && !name.eq_ignore_ascii_case("cookie"));
+                }
+                next.url = next_url;
+                continue
+            }
+            on_head(&response);
+            if request.method != "HEAD" && response.status_code != 204 && response.status_code != 304 {
+                let chunked = response.header("Transfer-Encoding").is_some_and( | te | te.to_ascii_lowercase().contains("chunked"));
+                let content_length = response.header("Content-Length").and_then( | len | len.trim().parse::<u64>().ok());
+                let result = if chunked {
+                    read_chunked_body(&mut reader, on_body)
+                }
+                else {
+                    read_body(&mut reader, content_length, on_body)
+                };
+                result.map_err( | e | request.error(e)) ?;
+            }
+            // a cancelled read to the end of the connection looks like a complete body
+            if self.cancel.as_ref().is_some_and( | cancel | cancel.is_cancelled()) {
+                return Err(format!("Request to {} was cancelled", request.url))
+            }
+            return Ok(response)
+        }
+    }
+
+    /// Opens a websocket on the url of the request, blocking until the upgrade handshake is done.
+    /// The method and body of the request are ignored, the headers are sent along with the upgrade.
+    /// We don't offer permessage-deflate, so the server sends everything uncompressed
+    pub fn open_web_socket(&self, tls: Option<&dyn TlsConnector>) -> Result<WebSocketConnection, String> {
+        let url = HttpUrl::parse(&self.url) ?;
+        let (mut stream, tcp_stream) = self.connect(&url, tls) ?;
+        let key = WebSocket::create_key();
+        let mut head = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n",
+            url.path,
+            url.host_header(),
+            key
+        );
+        for (name, value) in &self.headers {
+            let name_lc = name.to_ascii_lowercase();
+            if ["host", "content-length", "connection", "upgrade"].contains(&name_lc.as_str()) || name_lc.starts_with("sec-websocket-") && name_lc != "sec-websocket-protocol" {
+                continue
+            }
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes()).and_then( | _ | stream.flush()).map_err( | e | format!("Websocket upgrade of {} failed {}", self.url, e)) ?;
+        
+        let mut reader = BufReader::new(stream);
+        let (status_code, headers) = read_response_head(&mut reader).map_err( | e | format!("Websocket upgrade of {} failed {}", self.url, e)) ?;
+        let header = | name: &str | headers.iter().find( | (k, _) | k.eq_ignore_ascii_case(name)).map( | (_, v) | v.as_str());
+        if status_code != 101 {
+            return Err(format!("Server refused the websocket upgrade of {} with status {}", self.url, status_code))
+        }
+        if !header("Upgrade").is_some_and( | upgrade | upgrade.eq_ignore_ascii_case("websocket")) {
+            return Err(format!("Server didn't upgrade {} to a websocket", self.url))
+        }
+        if header("Sec-WebSocket-Accept") != Some(WebSocket::accept_key(&key).as_str()) {
+            return Err(format!("Server answered the websocket upgrade of {} with the wrong Sec-WebSocket-Accept", self.url))
+        }
+        if header("Sec-WebSocket-Extensions").is_some() {
+            return Err(format!("Server enabled websocket extensions on {} we didn't ask for", self.url))
+        }
+        let buffered = reader.buffer().to_vec();
+        // from here on the websocket keeps the connection alive itself
+        let _ = tcp_stream.set_read_timeout(None);
+        Ok(WebSocketConnection {
+            stream: reader.into_inner(),
+            tcp_stream,
+            buffered,
+            headers
+        })
+    }
+
+    // Opens the connection, with TLS running on it for https. Also returns a handle on the tcp stream underneath
+    fn connect(&self, url: &HttpUrl, tls: Option<&dyn TlsConnector>) -> Result<(Box<dyn HttpStream>, TcpStream), String> {
+        let tls = if url.tls {
+            Some(tls.ok_or_else( || format!("Cannot request {} without a TLS connector", self.url)) ?)
+        } else {None};
+        let connect_error = | e: io::Error | format!("Cannot connect to {}:{} {}", url.host, url.port, describe_error(&e));
+        let tcp_stream = match self.timeout {
+            Some(timeout) => {
+                let mut last_error = io::Error::new(io::ErrorKind::NotFound, "no address found");
+                let mut tcp_stream = None;
+                for addr in (url.host.as_str(), url.port).to_socket_addrs().map_err(connect_error) ? {
+                    match TcpStream::connect_timeout(&addr, timeout) {
+                        Ok(stream) => {
+                            tcp_stream = Some(stream);
+                            break
+                        }
+                        Err(e) => last_error = e
+                    }
+                }
+                tcp_stream.ok_or(last_error).map_err(connect_error) ?
+            }
+            None => TcpStream::connect((url.host.as_str(), url.port)).map_err(connect_error) ?
+        };
+        let _ = tcp_stream.set_nodelay(true);
+        let _ = tcp_stream.set_read_timeout(self.timeout);
+        let _ = tcp_stream.set_write_timeout(self.timeout);
+        if let Some(cancel) = &self.cancel {
+            cancel.set_tcp_stream(&tcp_stream).map_err( | e | self.error(e)) ?;
+        }
+        let shutdown_stream = tcp_stream.try_clone().map_err(connect_error) ?;
+        let stream: Box<dyn HttpStream> = match tls {
+            Some(tls) => tls.connect(&url.host, tcp_stream).map_err( | e | format!("TLS handshake with {} failed {}", url.host, describe_error(&e))) ?,
+            None => Box::new(tcp_stream)
+        };
+        Ok((stream, shutdown_stream))
+    }
+
+    // Writes the request and reads the head of the response
+    fn send_head(&self, url: &HttpUrl, mut stream: Box<dyn HttpStream>) -> io::Result<(BufReader<Box<dyn HttpStream>>, HttpClientResponse)> {
+        let mut head = format!("{} {} HTTP/1.1\r\nHost: {}\r\n", self.method, url.path, url.host_header());
+        for (name, value) in &self.headers {
+            if ["host", "content-length", "connection"].iter().any( | h | name.eq_ignore_ascii_case(h)) {
+                continue
+            }
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        if !self.body.is_empty() || ["POST", "PUT", "PATCH"].contains(&self.method.as_str()) {
+            head.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        }
+        head.push_str("Connection: close\r\n\r\n");
+        stream.write_all(head.as_bytes()) ?;
+        stream.write_all(&self.body) ?;
+        stream.flush() ?;
+
+        let mut reader = BufReader::new(stream);
+        // skip over 100 Continue and friends
+        let (status_code, headers) = loop {
+            let (status_code, headers) = read_response_head(&mut reader) ?;
+            if !(100..200).contains(&status_code) || status_code == 101 {
+                break (status_code, headers)
+            }
+        };
+        Ok((reader, HttpClientResponse {status_code, headers, body: Vec::new()}))
+    }
+
+    fn error(&self, e: io::Error) -> String {
+        if self.cancel.as_ref().is_some_and( | cancel | cancel.is_cancelled()) {
+            return format!("Request to {} was cancelled", self.url)
+        }
+        format!("Request to {} failed {}", self.url, describe_error(&e))
+    }
+}
+
+fn describe_error(e: &io::Error) -> String {
+    match e.kind() {
+        // what a read or write timeout shows up as
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => "timed out".to_string(),
+        _ => e.to_string()
+    }
+}
+
+fn read_line<R: BufRead>(reader: &mut R, limit: &mut usize) -> io::Result<String> {
+    let mut line = Vec::new();
+    reader.by_ref().take(*limit as u64).read_until(b'\n', &mut line) ?;
+    if !line.ends_with(b"\n") {
+        return Err(if line.len() >= *limit {
+            io::Error::new(io::ErrorKind::InvalidData, "response header too large")
+        } else {
+            io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed in the response header")
+        })
+    }
+    *limit -= line.len();
+    Ok(String::from_utf8_lossy(&line).trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn read_response_head<R: BufRead>(reader: &mut R) -> io::Result<(u16, Vec<(String, String)>)> {
+    let mut limit = MAX_HEADER_SIZE;
+    let status_line = read_line(reader, &mut limit) ?;
+    let mut parts = status_line.split(' ');
+    let status_code = match (parts.next(), parts.next()) {
+        (Some(version), Some(code)) if version.starts_with("HTTP/") => code.parse().ok(),
+        _ => None
+    }.ok_or_else( || io::Error::new(io::ErrorKind::InvalidData, format!("invalid status line {}", status_line))) ?;
+    let mut headers = Vec::new();
+    loop {
+        let line = read_line(reader, &mut limit) ?;
+        if line.is_empty() {
+            break
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    Ok((status_code, headers))
+}
+
+fn read_body<R: Read>(reader: &mut R, content_length: Option<u64>, on_body: &mut dyn FnMut(&[u8], Option<u64>)) -> io::Result<()> {
+    let mut loaded = 0;
+    let mut buf = vec![0u8; BODY_READ_SIZE];
+    loop {
+        let want = match content_length {
+            Some(len) if loaded >= len => break,
+            Some(len) => (len - loaded).min(BODY_READ_SIZE as u64) as usize,
+            None => BODY_READ_SIZE
+        };
+        let read = reader.read(&mut buf[..want]) ?;
+        if read == 0 {
+            if content_length.is_some() {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed before the body was complete"))
+            }
+            break
+        }
+        loaded += read as u64;
+        on_body(&buf[..read], content_length);
+    }
+    Ok(())
+}
+
+fn read_chunked_body<R: BufRead>(reader: &mut R, on_body: &mut dyn FnMut(&[u8], Option<u64>)) -> io::Result<()> {
+    let mut chunk = Vec::new();
+    loop {
+        let mut limit = MAX_HEADER_SIZE;
+        let size_line = read_line(reader, &mut limit) ?;
+        let size = size_line.split(';').next().unwrap_or("").trim();
+        let size = usize::from_str_radix(size, 16).map_err( | _ | io::Error::new(io::ErrorKind::InvalidData, format!("invalid chunk size {}", size_line))) ?;
+        if size == 0 {
+            // the trailer, if any, ends with an empty line
+            while !read_line(reader, &mut limit) ?.is_empty() {}
+            return Ok(())
+        }
+        // big chunks are handed over in pieces, so a stream of them doesn't stall
+        let mut left = size;
+        while left > 0 {
+            chunk.resize(left.min(BODY_READ_SIZE), 0);
+            reader.read_exact(&mut chunk) ?;
+            left -= chunk.len();
+            on_body(&chunk, None);
+        }
+        if !read_line(reader, &mut limit) ?.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "chunk is longer than its size"))
+        }
+    }
+}
@@ -1,4 +1,7 @@
- pub mod digest;
- pub mod utils;
- pub mod server;
- pub mod websocket;
+pub mod digest;
+pub mod utils;
+pub mod server;
+pub mod client;
+pub mod router;
+pub mod static_files;
+pub mod websocket;
@@ -0,0 +1,164 @@
+use crate::server::*;
+use crate::static_files::StaticFiles;
+use crate::utils::*;
+
+/// What a route handler gets to see of a request
+pub struct HttpRouteRequest<'a> {
+    pub headers: &'a HttpServerHeaders,
+    pub body: &'a [u8],
+    /// the :name and *name captures of the route pattern, percent decoded
+    pub params: Vec<(String, String)>,
+    pub query: Vec<(String, String)>,
+}
+
+impl<'a> HttpRouteRequest<'a> {
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find( | (k, _) | k == name).map( | (_, v) | v.as_str())
+    }
+
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.query.iter().find( | (k, _) | k == name).map( | (_, v) | v.as_str())
+    }
+}
+
+type HttpRouteHandler = Box<dyn FnMut(&HttpRouteRequest) -> HttpServerResponse + Send>;
+
+enum RouteSegment {
+    Exact(String),
+    Capture(String),
+    Rest(String),
+}
+
+struct HttpRoute {
+    verb: String,
+    segments: Vec<RouteSegment>,
+    handler: HttpRouteHandler,
+}
+
+impl HttpRoute {
+    fn match_path(&self, path: &str) -> Option<Vec<(String, String)>> {
+        let mut params = Vec::new();
+        let mut rest = Some(path.strip_prefix('/') ?);
+        for segment in &self.segments {
+            if let RouteSegment::Rest(name) = segment {
+                params.push((name.clone(), percent_decode(rest.unwrap_or(""), false)));
+                return Some(params)
+            }
+            let mut split = rest ?.splitn(2, '/');
+            let part = split.next().unwrap_or("");
+            rest = split.next();
+            match segment {
+                RouteSegment::Exact(exact) => if exact != part {
+                    return None
+                }
+                RouteSegment::Capture(name) => {
+                    if part.is_empty() {
+                        return None
+                    }
+                    params.push((name.clone(), percent_decode(part, false)));
+                }
+                RouteSegment::Rest(_) => ()
+            }
+        }
+        if rest.is_none() {Some(params)} else {None}
+    }
+}
+
+/// Dispatches the requests coming out of the http server to handlers by verb and path.
+/// Patterns are paths where :name captures a segment and a trailing *name captures the
+/// rest of the path, like "/build/:id/*file". Routes are tried in the order they were added,
+/// and GET routes answer HEAD as well.
+/// ```ignore
+/// let mut router = HttpRouter::default();
+/// router.get("/api/build/:id", | req | HttpServerResponse::new(200, "text/plain", req.param("id").unwrap().into()));
+/// router.static_files("/", StaticFiles::new("./"));
+/// while let Ok(request) = rx_request.recv() {
+///     if let Some(request) = router.handle(request) {
+///         // the websocket messages end up here
+///     }
+/// }
+/// ```
+#[derive(Default)]
+pub struct HttpRouter {
+    routes: Vec<HttpRoute>,
+}
+
+impl HttpRouter {
+    pub fn route<F>(&mut self, verb: &str, pattern: &str, handler: F) where F: FnMut(&HttpRouteRequest) -> HttpServerResponse + Send + 'static {
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+        let mut segments = Vec::new();
+        let mut parts = pattern.split('/').peekable();
+        while let Some(part) = parts.next() {
+            segments.push(if let Some(name) = part.strip_prefix(':') {
+                RouteSegment::Capture(name.to_string())
+            }
+            else if let Some(name) = part.strip_prefix('*') {
+                if parts.peek().is_some() {
+                    panic!("A *{} capture has to end the route pattern", name);
+                }
+                RouteSegment::Rest(name.to_string())
+            }
+            else {
+                RouteSegment::Exact(part.to_string())
+            });
+        }
+        self.routes.push(HttpRoute {
+            verb: verb.to_string(),
+            segments,
+            handler: Box::new(handler)
+        });
+    }
+
+    pub fn get<F>(&mut self, pattern: &str, handler: F) where F: FnMut(&HttpRouteRequest) -> HttpServerResponse + Send + 'static {
+        self.route("GET", pattern, handler)
+    }
+
+    pub fn post<F>(&mut self, pattern: &str, handler: F) where F: FnMut(&HttpRouteRequest) -> HttpServerResponse + Send + 'static {
+        self.route("POST", pattern, handler)
+    }
+
+    pub fn put<F>(&mut self, pattern: &str, handler: F) where F: FnMut(&HttpRouteRequest) -> HttpServerResponse + Send + 'static {
+        self.route("PUT", pattern, handler)
+    }
+
+    pub fn delete<F>(&mut self, pattern: &str, handler: F) where F: FnMut(&HttpRouteRequest) -> HttpServerResponse + Send + 'static {
+        self.route("DELETE", pattern, handler)
+    }
+
+    /// Serves the files of a directory under a path prefix like "/makepad/"
+    pub fn static_files(&mut self, prefix: &str, files: StaticFiles) {
+        let pattern = format!("{}/*path", prefix.strip_suffix('/').unwrap_or(prefix));
+        self.get(&pattern, move | req | files.serve(req.headers, req.param("path").unwrap_or("")))
+    }
+
+    /// Answers the requests a route matches and hands back the rest, like the websocket
+    /// messages. Requests no route matches are answered with a 404, or a 405 when only the verb differs
+    pub fn handle(&mut self, request: HttpServerRequest) -> Option<HttpServerRequest> {
+        let (headers, body, response) = match request {
+            HttpServerRequest::Get {headers, response_sender} => (headers, Vec::new(), response_sender),
+            HttpServerRequest::Post {headers, body, response} |
+            HttpServerRequest::Put {headers, body, response} => (headers, body, response),
+            HttpServerRequest::Delete {headers, response} => (headers, Vec::new(), response),
+            request => return Some(request)
+        };
+        let verb = if headers.verb == "HEAD" {"GET"} else {headers.verb.as_str()};
+        for route in &mut self.routes {
+            if route.verb != verb {
+                continue
+            }
+            if let Some(params) = route.match_path(&headers.path) {
+                let req = HttpRouteRequest {
+                    headers: &headers,
+                    body: &body,
+                    params,
+                    query: headers.search.as_deref().map(parse_query).unwrap_or_default(),
+                };
+                let _ = response.send((route.handler)(&req));
+                return None
+            }
+        }
+        let status = if self.routes.iter().any( | route | route.match_path(&headers.path).is_some()) {405} else {404};
+        let _ = response.send(HttpServerResponse::status(status));
+        None
+    }
+}
@@ -1,39 +1,99 @@
 // this webserver is serving our site. Why? WHYYY. Because it was fun to write. And MUCH faster and MUCH simpler than anything else imaginable.
 
 use std::net::{TcpListener, TcpStream, SocketAddr, Shutdown};
-use std::io::prelude::*;
-use std::sync::{mpsc, mpsc::{RecvTimeoutError}};
-use std::time::Duration;
+use std::io::{ErrorKind, prelude::*};
+use std::sync::{Arc, Mutex, mpsc, mpsc::{RecvTimeoutError}, atomic::{AtomicUsize, Ordering}};
+use std::time::{Duration, Instant};
 
-use crate::websocket::{PONG_MESSAGE, WebSocket, WebSocketMessage, MessageFormat, MessageHeader, PING_MESSAGE};
+use crate::websocket::{WebSocket, WebSocketMessage, MessageFormat, MessageHeader, CLOSE_NO_STATUS};
 use crate::utils::*;
 
+// how long an idle keep-alive connection waits for its next request, also bounds slow reads
+const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_HEADER_SIZE: usize = 64 * 1024;
+const RESPONSE_CHUNK_SIZE: usize = 64 * 1024;
+const WEB_SOCKET_PING_INTERVAL: Duration = Duration::from_secs(2);
+// a websocket that hasn't sent anything, pongs included, for this long is dropped
+const WEB_SOCKET_TIMEOUT: Duration = Duration::from_secs(10);
+// how long we wait for the other side to answer our close frame
+const WEB_SOCKET_CLOSE_TIMEOUT: Duration = Duration::from_secs(2);
+// smaller messages aren't worth compressing
+const WEB_SOCKET_DEFLATE_MIN: usize = 128;
+
 #[derive(Clone)]
 pub struct HttpServer {
     pub listen_address: SocketAddr,
     pub request: mpsc::Sender<HttpServerRequest>,
-    pub post_max_size: u64
+    pub post_max_size: u64,
+    /// the threads serving http requests, websockets get a thread of their own after the upgrade
+    pub worker_threads: usize,
+    /// connections beyond this many open ones are answered with a 503
+    pub max_connections: usize,
+    /// terminates TLS on every connection when set
+    pub tls: Option<Arc<dyn TlsAcceptor>>,
 }
 
+/// A response to a Get or Post. When the header has no Content-Length the body is sent
+/// with chunked transfer encoding, so the connection can be kept alive either way
 pub struct HttpServerResponse {
     pub header: String,
     pub body: Vec<u8>
 }
 
+impl HttpServerResponse {
+    /// A response with a status and content type, the Content-Length is filled in
+    pub fn new(status: usize, content_type: &str, body: Vec<u8>) -> Self {
+        Self {
+            header: format!(
+                "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n",
+                status,
+                status_text(status),
+                content_type,
+                body.len()
+            ),
+            body
+        }
+    }
+    
+    /// An empty response that only has a status, like a 404
+    pub fn status(status: usize) -> Self {
+        Self {
+            header: format!("HTTP/1.1 {} {}\r\nContent-Length: 0\r\n\r\n", status, status_text(status)),
+            body: Vec::new()
+        }
+    }
+}
+
+/// What can be sent down a websocket through its response_sender
+pub enum WebSocketReply {
+    Binary(Vec<u8>),
+    Text(String),
+    /// Starts the close handshake with a close code and reason
+    Close(u16, String),
+    /// Pings are answered by the server itself
+    Pong(Vec<u8>),
+}
+
 pub enum HttpServerRequest {
     ConnectWebSocket {
         web_socket_id: u64,
         headers:HttpServerHeaders,
-        response_sender: mpsc::Sender<Vec<u8 >>,
+        response_sender: mpsc::Sender<WebSocketReply>,
     },
     DisconnectWebSocket {
         web_socket_id: u64,
     },
     BinaryMessage {
         web_socket_id: u64,
-        response_sender: mpsc::Sender<Vec<u8 >>,
+        response_sender: mpsc::Sender<WebSocketReply>,
         data: Vec<u8>
     },
+    TextMessage {
+        web_socket_id: u64,
+        response_sender: mpsc::Sender<WebSocketReply>,
+        text: String
+    },
+    /// HEAD requests arrive as a Get with headers.verb set to HEAD, the server leaves out the body
     Get {
         headers: HttpServerHeaders,
         response_sender: mpsc::Sender<HttpServerResponse>,
@@ -42,6 +102,15 @@ pub enum HttpServerRequest {
         headers: HttpServerHeaders,
         body: Vec<u8>,
         response: mpsc::Sender<HttpServerResponse>,
+    },
+    Put {
+        headers: HttpServerHeaders,
+        body: Vec<u8>,
+        response: mpsc::Sender<HttpServerResponse>,
+    },
+    Delete {
+        headers: HttpServerHeaders,
+        response: mpsc::Sender<HttpServerResponse>,
     }
 }
 
@@ -51,195 +120,493 @@ pub fn start_http_server(
     
     let listener = if let Ok(listener) = TcpListener::bind(http_server.listen_address) {listener} else {println!("Cannot bind http server port"); return None};
     
+    let pool = Arc::new(ConnectionPool::default());
+    let (tx_connection, rx_connection) = mpsc::channel::<(TcpStream, ConnectionSlot, u64)> ();
+    let rx_connection = Arc::new(Mutex::new(rx_connection));
+    
+    for _ in 0..http_server.worker_threads.max(1) {
+        let http_server = http_server.clone();
+        let rx_connection = rx_connection.clone();
+        let pool = pool.clone();
+        std::thread::spawn(move || loop {
+            let next = rx_connection.lock().unwrap().recv();
+            let (tcp_stream, slot, connection_id) = if let Ok(next) = next {next} else {return};
+            pool.queued.fetch_sub(1, Ordering::SeqCst);
+            // a panicking connection shouldn't take the worker down with it
+            let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe( || {
+                handle_connection(&http_server, &pool, tcp_stream, slot, connection_id)
+            }));
+        });
+    }
+    
     let listen_thread = {
         std::thread::spawn(move || {
             let mut connection_counter = 0u64;
             for tcp_stream in listener.incoming() {
-                let mut tcp_stream = if let Ok(tcp_stream) = tcp_stream {
+                let tcp_stream = if let Ok(tcp_stream) = tcp_stream {
                     tcp_stream
                 }
                 else {
                     println!("Incoming stream failure");
                     continue
                 };
-                let http_server = http_server.clone();
+                if pool.open.load(Ordering::SeqCst) >= http_server.max_connections {
+                    http_error_out(tcp_stream, 503);
+                    continue
+                }
                 connection_counter += 1;
-                let _read_thread = std::thread::spawn(move || {
-                    
-                    let headers = HttpServerHeaders::from_tcp_stream(&mut tcp_stream);
-                    if headers.is_none() {
-                        return http_error_out(tcp_stream, 500);
-                    }
-                    let headers = headers.unwrap();
-                    
-                    if headers.sec_websocket_key.is_some() {
-                        return handle_web_socket(http_server, tcp_stream, headers, connection_counter);
-                    }
-                    if headers.verb == "POST" {
-                        return handle_post(http_server, tcp_stream, headers);
-                    }
-                    if headers.verb == "GET" {
-                        return handle_get(http_server, tcp_stream, headers);
-                    }
-                    http_error_out(tcp_stream, 500)
-                });
+                pool.open.fetch_add(1, Ordering::SeqCst);
+                pool.queued.fetch_add(1, Ordering::SeqCst);
+                let slot = ConnectionSlot(pool.clone());
+                if tx_connection.send((tcp_stream, slot, connection_counter)).is_err() {
+                    return
+                }
             }
         })
     };
     Some(listen_thread)
 }
 
-fn handle_post(http_server: HttpServer, mut tcp_stream: TcpStream, headers: HttpServerHeaders) {
-    // we have to have a content-length or bust
-    if headers.content_length.is_none() {
-        return http_error_out(tcp_stream, 500);
+#[derive(Default)]
+struct ConnectionPool {
+    open: AtomicUsize,
+    // accepted connections no worker has picked up yet
+    queued: AtomicUsize,
+}
+
+// holds one of the max_connections until the connection is closed
+struct ConnectionSlot(Arc<ConnectionPool>);
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.open.fetch_sub(1, Ordering::SeqCst);
     }
-    let content_length = headers.content_length.unwrap();
-    if content_length > http_server.post_max_size {
-        return http_error_out(tcp_stream, 500);
+}
+
+struct HttpConnection {
+    // the socket underneath the stream, for timeouts and shutdown
+    tcp_stream: TcpStream,
+    stream: Box<dyn HttpStream>,
+    // bytes read past the end of the last request
+    buf: Vec<u8>,
+}
+
+impl HttpConnection {
+    fn fill_buf(&mut self) -> bool {
+        let mut data = [0u8; 8192];
+        match self.stream.read(&mut data) {
+            Ok(0) | Err(_) => false,
+            Ok(n) => {
+                self.buf.extend_from_slice(&data[0..n]);
+                true
+            }
+        }
     }
-    let bytes_total = content_length as usize;
-    let mut body = Vec::new();
-    body.resize(bytes_total, 0u8);
     
-    let mut bytes_left = bytes_total;
-    while bytes_left > 0 {
-        let buf = &mut body[(bytes_total - bytes_left)..bytes_total];
-        let bytes_read = tcp_stream.read(buf);
-        if bytes_read.is_err() {
-            return http_error_out(tcp_stream, 500);
+    // waits for the next request on an idle connection. If other connections are queued up
+    // for a worker we stop waiting and close, so idle keep-alives can't starve them
+    fn wait_for_request(&mut self, pool: &ConnectionPool, first: bool) -> bool {
+        if !self.buf.is_empty() {
+            return true
+        }
+        let start = Instant::now();
+        let _ = self.tcp_stream.set_read_timeout(Some(Duration::from_millis(100)));
+        let ready = loop {
+            match self.tcp_stream.peek(&mut [0u8; 1]) {
+                Ok(n) => break n > 0,
+                Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => (),
+                Err(_) => break false
+            }
+            if start.elapsed() > KEEP_ALIVE_TIMEOUT || !first && pool.queued.load(Ordering::SeqCst) > 0 {
+                break false
+            }
+        };
+        let _ = self.tcp_stream.set_read_timeout(Some(KEEP_ALIVE_TIMEOUT));
+        ready
+    }
+    
+    // None when the connection closed, Some(None) when the headers don't parse
+    fn read_headers(&mut self) -> Option<Option<HttpServerHeaders >> {
+        let mut searched = 0;
+        loop {
+            if let Some(pos) = self.buf[searched..].windows(4).position( | w | w == b"\r\n\r\n") {
+                let end = searched + pos + 4;
+                let addr = self.tcp_stream.peer_addr().ok() ?;
+                let headers = HttpServerHeaders::from_bytes(addr, &self.buf[0..end]);
+                self.buf.drain(0..end);
+                return Some(headers)
+            }
+            if self.buf.len() > MAX_HEADER_SIZE {
+                return Some(None)
+            }
+            searched = self.buf.len().saturating_sub(3);
+            if !self.fill_buf() {
+                return None
+            }
         }
-        let bytes_read = bytes_read.unwrap();
-        if bytes_read == 0 {
-            return http_error_out(tcp_stream, 500);
+    }
+    
+    fn read_body(&mut self, len: usize) -> Option<Vec<u8>> {
+        while self.buf.len() < len {
+            if !self.fill_buf() {
+                return None
+            }
         }
-        bytes_left -= bytes_read;
+        Some(self.buf.drain(0..len).collect())
     }
     
-    let (tx_socket, rx_socket) = mpsc::channel::<HttpServerResponse> ();
-    if http_server.request.send(HttpServerRequest::Post {
-        headers,
-        body,
-        response: tx_socket
-    }).is_err() {
-        return http_error_out(tcp_stream, 500);
+    fn error_out(&mut self, code: usize) {
+        let header = format!("HTTP/1.1 {} {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", code, status_text(code));
+        write_bytes_to_tcp_stream_no_error(&mut self.stream, header.as_bytes());
+        let _ = self.stream.flush();
+    }
+}
+
+fn handle_connection(http_server: &HttpServer, pool: &ConnectionPool, tcp_stream: TcpStream, slot: ConnectionSlot, connection_id: u64) {
+    let _ = tcp_stream.set_read_timeout(Some(KEEP_ALIVE_TIMEOUT));
+    let stream = tcp_stream.try_clone().and_then( | s | {
+        if let Some(tls) = &http_server.tls {
+            tls.accept(s)
+        }
+        else {
+            Ok(Box::new(s) as Box<dyn HttpStream>)
+        }
+    });
+    let stream = if let Ok(stream) = stream {stream} else {
+        let _ = tcp_stream.shutdown(Shutdown::Both);
+        return
     };
+    let mut conn = HttpConnection {tcp_stream, stream, buf: Vec::new()};
+    let mut first = true;
     
-    if let Ok(response) = rx_socket.recv() {
-        write_bytes_to_tcp_stream_no_error(&mut tcp_stream, response.header.as_bytes());
-        write_bytes_to_tcp_stream_no_error(&mut tcp_stream, &response.body);
+    while conn.wait_for_request(pool, first) {
+        first = false;
+        let headers = match conn.read_headers() {
+            Some(Some(headers)) => headers,
+            Some(None) => {
+                conn.error_out(400);
+                break
+            }
+            None => break
+        };
+        
+        if headers.sec_websocket_key.is_some() {
+            return handle_web_socket(http_server, conn, headers, connection_id, slot);
+        }
+        let keep_alive = headers.keep_alive;
+        let head_only = headers.verb == "HEAD";
+        let response = match headers.verb.as_str() {
+            "GET" | "HEAD" => handle_get(http_server, headers),
+            "POST" | "PUT" | "DELETE" => handle_body(http_server, &mut conn, headers),
+            _ => Err(405)
+        };
+        match response {
+            Ok(response) => if !write_response(&mut conn.stream, response, keep_alive, head_only) {
+                break
+            }
+            Err(code) => {
+                conn.error_out(code);
+                break
+            }
+        }
     }
-    let _ = tcp_stream.shutdown(Shutdown::Both);
+    let _ = conn.tcp_stream.shutdown(Shutdown::Both);
 }
 
-fn handle_web_socket(http_server: HttpServer, mut tcp_stream: TcpStream, headers: HttpServerHeaders, web_socket_id: u64) {
-    let upgrade_response = WebSocket::create_upgrade_response(headers.sec_websocket_key.as_ref().unwrap());
+// writes the response and returns if the connection can take another request
+fn write_response(stream: &mut Box<dyn HttpStream>, response: HttpServerResponse, keep_alive: bool, head_only: bool) -> bool {
+    let (out, close) = frame_response(response, keep_alive, head_only);
+    if write_bytes_to_tcp_stream_no_error(stream, &out) || stream.flush().is_err() {
+        return false
+    }
+    !close
+}
 
-    write_bytes_to_tcp_stream_no_error(&mut tcp_stream, upgrade_response.as_bytes());
-    
-    let mut write_tcp_stream = tcp_stream.try_clone().unwrap();
-    let (tx_socket, rx_socket) = mpsc::channel::<Vec<u8 >> ();
+// the bytes of a response with its body framed, and if the connection has to close after it
+fn frame_response(response: HttpServerResponse, keep_alive: bool, head_only: bool) -> (Vec<u8>, bool) {
+    let head = if let Some(head) = response.header.strip_suffix("\r\n\r\n") {head} else {
+        // not a header we can add to, send it as is and close to end the body
+        let mut out = response.header.into_bytes();
+        out.extend_from_slice(&response.body);
+        return (out, true)
+    };
+    // 1xx, 204 and 304 responses never have a body, so they get no framing either
+    let status = head.split(' ').nth(1).and_then( | status | status.parse::<usize>().ok()).unwrap_or(200);
+    let no_body = (100..200).contains(&status) || status == 204 || status == 304;
+    let mut has_length = false;
+    let mut chunked = false;
+    let mut has_connection = false;
+    let mut close = !keep_alive;
+    for line in head.split("\r\n").skip(1) {
+        let line = line.to_ascii_lowercase();
+        if line.starts_with("content-length:") {
+            has_length = true;
+        }
+        else if line.starts_with("transfer-encoding:") {
+            chunked = line.contains("chunked");
+        }
+        else if line.starts_with("connection:") {
+            has_connection = true;
+            close |= line.contains("close");
+        }
+    }
+    let mut out = head.as_bytes().to_vec();
+    out.extend_from_slice(b"\r\n");
+    if !has_length && !chunked && !no_body {
+        out.extend_from_slice(b"Transfer-Encoding: chunked\r\n");
+    }
+    if close && !has_connection {
+        out.extend_from_slice(b"Connection: close\r\n");
+    }
+    out.extend_from_slice(b"\r\n");
     
-    let _write_thread = std::thread::spawn(move || {
-        // xx
-        loop{
-            match rx_socket.recv_timeout(Duration::from_millis(2000)){
-                Ok(data)=>{
-                    if data.is_empty(){
-                        break
-                    }
-                    let header = MessageHeader::from_len(data.len(), MessageFormat::Binary, false);
-                    write_bytes_to_tcp_stream_no_error(&mut write_tcp_stream, header.as_slice());
-                    write_bytes_to_tcp_stream_no_error(&mut write_tcp_stream, &data);
-                },
-                Err(RecvTimeoutError::Timeout)=>{ 
-                    write_bytes_to_tcp_stream_no_error(&mut write_tcp_stream, &PING_MESSAGE);
-                }
-                Err(RecvTimeoutError::Disconnected)=>{
-                    break
-                }
+    if !head_only && !no_body { // HEAD gets the same headers as a GET, without the body
+        if has_length || chunked { // a body that is already framed goes out as is
+            out.extend_from_slice(&response.body);
+        }
+        else {
+            for chunk in response.body.chunks(RESPONSE_CHUNK_SIZE) {
+                out.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+                out.extend_from_slice(chunk);
+                out.extend_from_slice(b"\r\n");
             }
+            out.extend_from_slice(b"0\r\n\r\n");
         }
-        let _ = write_tcp_stream.shutdown(Shutdown::Both);
-    });
-    
-    if http_server.request.send(HttpServerRequest::ConnectWebSocket {
-        headers,
-        web_socket_id,
-        response_sender: tx_socket.clone()
-    }).is_err() {
-        let _ = tcp_stream.shutdown(Shutdown::Both);
-        return
+    }
+    (out, close)
+}
+
+// POST, PUT and DELETE, the latter may leave out the body
+fn handle_body(http_server: &HttpServer, conn: &mut HttpConnection, headers: HttpServerHeaders) -> Result<HttpServerResponse, usize> {
+    let body = if let Some(content_length) = headers.content_length {
+        if content_length > http_server.post_max_size {
+            return Err(413);
+        }
+        conn.read_body(content_length as usize).ok_or(400usize) ?
+    }
+    else if headers.verb == "DELETE" {
+        Vec::new()
+    }
+    else { // we have to have a content-length or bust
+        return Err(411)
     };
     
+    let (tx_socket, rx_socket) = mpsc::channel::<HttpServerResponse> ();
+    let request = match headers.verb.as_str() {
+        "POST" => HttpServerRequest::Post {headers, body, response: tx_socket},
+        "PUT" => HttpServerRequest::Put {headers, body, response: tx_socket},
+        _ => HttpServerRequest::Delete {headers, response: tx_socket},
+    };
+    if http_server.request.send(request).is_err() {
+        return Err(500);
+    };
+    // a dropped sender means nobody handled it
+    rx_socket.recv().map_err( | _ | 404)
+}
+
+fn handle_web_socket(http_server: &HttpServer, mut conn: HttpConnection, headers: HttpServerHeaders, web_socket_id: u64, slot: ConnectionSlot) {
+    let (write_stream, shutdown_stream) = match (conn.stream.try_clone_stream(), conn.tcp_stream.try_clone()) {
+        (Ok(write_stream), Ok(shutdown_stream)) => (write_stream, shutdown_stream),
+        _ => return conn.error_out(500)
+    };
     let mut web_socket = WebSocket::new();
-    loop {
+    let extensions = headers.header("Sec-WebSocket-Extensions").and_then( | offers | web_socket.negotiate_deflate(offers));
+    let deflate = extensions.is_some();
+    let upgrade_response = WebSocket::create_upgrade_response_with_extensions(
+        headers.sec_websocket_key.as_ref().unwrap(),
+        extensions.as_deref()
+    );
+    
+    write_bytes_to_tcp_stream_no_error(&mut conn.stream, upgrade_response.as_bytes());
+    let _ = conn.stream.flush();
+    let _ = conn.tcp_stream.set_read_timeout(None);
+    
+    let http_server = http_server.clone();
+    // websockets live long, they get their own threads instead of holding on to a worker
+    std::thread::spawn(move || {
+        let _slot = slot;
+        let last_seen = Arc::new(Mutex::new(Instant::now()));
+        let (tx_socket, rx_socket) = mpsc::channel::<WebSocketReply> ();
+        
+        let _write_thread = std::thread::spawn({
+            let last_seen = last_seen.clone();
+            move || web_socket_write_loop(write_stream, shutdown_stream, rx_socket, last_seen, deflate)
+        });
+        
+        if http_server.request.send(HttpServerRequest::ConnectWebSocket {
+            headers,
+            web_socket_id,
+            response_sender: tx_socket.clone()
+        }).is_err() {
+            let _ = conn.tcp_stream.shutdown(Shutdown::Both);
+            return
+        };
+        
+        // frames the client sent right behind the upgrade request
+        let mut buffered = std::mem::take(&mut conn.buf);
         let mut data = [0u8; 65535];
-        match tcp_stream.read(&mut data) {
-            Ok(n) => {
-                if n == 0 {
-                    let _ = tcp_stream.shutdown(Shutdown::Both);
-                    let _ = tx_socket.send(Vec::new());
-                    break 
+        loop {
+            let read = if buffered.is_empty() {
+                conn.stream.read(&mut data)
+            }
+            else {
+                let n = buffered.len().min(data.len());
+                data[0..n].copy_from_slice(&buffered[0..n]);
+                buffered.drain(0..n);
+                Ok(n)
+            };
+            let n = match read {
+                Ok(n) if n > 0 => n,
+                _ => {
+                    let _ = conn.tcp_stream.shutdown(Shutdown::Both);
+                    break
                 }
-                web_socket.parse(&data[0..n], | result | {
-                    match result {
-                        Ok(WebSocketMessage::Ping(_)) => {
-                            let _ = tx_socket.send(PONG_MESSAGE.to_vec());
-                        },
-                        Ok(WebSocketMessage::Pong(_)) => {
-                        },
-                        Ok(WebSocketMessage::Text(_text)) => {
-                        }
-                        Ok(WebSocketMessage::Binary(data)) => {
-                            if http_server.request.send(HttpServerRequest::BinaryMessage {
-                                web_socket_id,
-                                response_sender: tx_socket.clone(),
-                                data: data.to_vec(),
-                            }).is_err() {
-                                eprintln!("Websocket message deserialize error");
-                                let _ = tcp_stream.shutdown(Shutdown::Both);
-                                let _ = tx_socket.send(Vec::new());
-                            };
-                        },
-                        Ok(WebSocketMessage::Close) => {
-                            let _ = tcp_stream.shutdown(Shutdown::Both);
-                        }
-                        Err(e) => {
-                            eprintln!("Websocket error {:?}", e);
-                            let _ = tcp_stream.shutdown(Shutdown::Both);
-                            let _ = tx_socket.send(Vec::new());
-                        }
+            };
+            *last_seen.lock().unwrap() = Instant::now();
+            let mut closed = false;
+            web_socket.parse(&data[0..n], | result | {
+                match result {
+                    Ok(WebSocketMessage::Ping(data)) => {
+                        let _ = tx_socket.send(WebSocketReply::Pong(data.to_vec()));
+                    },
+                    Ok(WebSocketMessage::Pong(_)) => {
+                    },
+                    Ok(WebSocketMessage::Text(text)) => {
+                        let _ = http_server.request.send(HttpServerRequest::TextMessage {
+                            web_socket_id,
+                            response_sender: tx_socket.clone(),
+                            text: text.to_string(),
+                        });
                     }
-                });
+                    Ok(WebSocketMessage::Binary(data)) => {
+                        let _ = http_server.request.send(HttpServerRequest::BinaryMessage {
+                            web_socket_id,
+                            response_sender: tx_socket.clone(),
+                            data: data.to_vec(),
+                        });
+                    },
+                    Ok(WebSocketMessage::Close(code, _reason)) => {
+                        // answer with the same code, the client closes the connection after
+                        let _ = tx_socket.send(WebSocketReply::Close(code, String::new()));
+                    }
+                    Err(e) => {
+                        eprintln!("Websocket error {:?}", e);
+                        let _ = tx_socket.send(WebSocketReply::Close(e.close_code(), String::new()));
+                        closed = true;
+                    }
+                }
+            });
+            if closed {
+                break
+            }
+        }
+        
+        let _ =  http_server.request.send(HttpServerRequest::DisconnectWebSocket {
+            web_socket_id,
+        });
+    });
+}
+
+fn web_socket_write_loop(mut write_stream: Box<dyn HttpStream>, shutdown_stream: TcpStream, rx_socket: mpsc::Receiver<WebSocketReply>, last_seen: Arc<Mutex<Instant >>, deflate: bool) {
+    let mut closing: Option<Instant> = None;
+    loop {
+        let frame = match rx_socket.recv_timeout(WEB_SOCKET_PING_INTERVAL) {
+            Ok(_) if closing.is_some() => continue,
+            Ok(WebSocketReply::Close(code, reason)) => {
+                closing = Some(Instant::now());
+                let mut data = Vec::new();
+                if code != CLOSE_NO_STATUS {
+                    data.extend_from_slice(&code.to_be_bytes());
+                    data.extend_from_slice(reason.as_bytes());
+                }
+                web_socket_frame(MessageFormat::Close, &data, false)
             }
-            Err(_) => {
-                println!("Websocket closed");
-                let _ = tcp_stream.shutdown(Shutdown::Both);
-                let _ = tx_socket.send(Vec::new());
-                break;
+            Ok(WebSocketReply::Pong(data)) => web_socket_frame(MessageFormat::Pong, &data, false),
+            Ok(WebSocketReply::Text(text)) => web_socket_frame(MessageFormat::Text, text.as_bytes(), deflate),
+            Ok(WebSocketReply::Binary(data)) => web_socket_frame(MessageFormat::Binary, &data, deflate),
+            Err(RecvTimeoutError::Timeout) => {
+                if let Some(closing) = closing {
+                    if closing.elapsed() > WEB_SOCKET_CLOSE_TIMEOUT {
+                        break
+                    }
+                    continue
+                }
+                if last_seen.lock().unwrap().elapsed() > WEB_SOCKET_TIMEOUT {
+                    break
+                }
+                web_socket_frame(MessageFormat::Ping, &[], false)
             }
+            Err(RecvTimeoutError::Disconnected) => break
+        };
+        if write_bytes_to_tcp_stream_no_error(&mut write_stream, &frame) || write_stream.flush().is_err() {
+            break
         }
     }
-    
-    let _ =  http_server.request.send(HttpServerRequest::DisconnectWebSocket {
-        web_socket_id,
-    });
+    let _ = shutdown_stream.shutdown(Shutdown::Both);
 }
 
-fn handle_get(http_server: HttpServer, mut tcp_stream: TcpStream, headers: HttpServerHeaders) {
-    // send our channel the post
+fn web_socket_frame(format: MessageFormat, data: &[u8], deflate: bool) -> Vec<u8> {
+    if deflate && data.len() >= WEB_SOCKET_DEFLATE_MIN {
+        let data = WebSocket::deflate_message(data);
+        let header = MessageHeader::from_len(data.len(), format, false).compressed();
+        return WebSocket::build_message(header, &data)
+    }
+    WebSocket::build_message(MessageHeader::from_len(data.len(), format, false), data)
+}
+
+fn handle_get(http_server: &HttpServer, headers: HttpServerHeaders) -> Result<HttpServerResponse, usize> {
+    // send our channel the request
     let (tx_socket, rx_socket) = mpsc::channel::<HttpServerResponse> ();
     if http_server.request.send(HttpServerRequest::Get {
         headers,
         response_sender: tx_socket
     }).is_err() {
-        return http_error_out(tcp_stream, 500);
+        return Err(500);
     };
-    
-    if let Ok(response) = rx_socket.recv() {
-        write_bytes_to_tcp_stream_no_error(&mut tcp_stream, response.header.as_bytes());
-        write_bytes_to_tcp_stream_no_error(&mut tcp_stream, &response.body);
+    rx_socket.recv().map_err( | _ | 404)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(header: &str, body: &[u8], head_only: bool) -> (String, bool) {
+        let response = HttpServerResponse {header: header.to_string(), body: body.to_vec()};
+        let (out, close) = frame_response(response, true, head_only);
+        (String::from_utf8(out).unwrap(), close)
+    }
+
+    #[test]
+    fn chunks_unframed_bodies() {
+        let (out, close) = frame("HTTP/1.1 200 OK\r\n\r\n", b"hello", false);
+        assert_eq!(out, "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n");
+        assert!(!close);
+        let (out, _) = frame("HTTP/1.1 200 OK\r\n\r\n", b"hello", true);
+        assert_eq!(out, "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n");
     }
-    let _ = tcp_stream.shutdown(Shutdown::Both);
-}
\ No newline at end of file
+
+    #[test]
+    fn sends_framed_bodies_as_is() {
+        let (out, _) = frame("HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n", b"hello", false);
+        assert_eq!(out, "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello");
+        let (out, close) = frame("HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 0\r\n\r\n", b"", false);
+        assert_eq!(out, "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 0\r\n\r\n");
+        assert!(close);
+    }
+
+    #[test]
+    fn bodyless_statuses_get_no_framing() {
+        for header in ["HTTP/1.1 304 Not Modified\r\nETag: \"1\"\r\n\r\n", "HTTP/1.1 204 No Content\r\n\r\n", "HTTP/1.1 101 Switching Protocols\r\n\r\n"] {
+            let (out, close) = frame(header, b"", false);
+            assert_eq!(out, header);
+            assert!(!close);
+        }
+    }
+
+    #[test]
+    fn closes_after_unframable_header() {
+        let (out, close) = frame("HTTP/1.1 200 OK\r\n", b"body", false);
+        assert_eq!(out, "HTTP/1.1 200 OK\r\nbody");
+        assert!(close);
+    }
+}
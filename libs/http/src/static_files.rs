@@ -0,0 +1,319 @@
+use std::fs::File;
+use std::io::{prelude::*, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::server::HttpServerResponse;
+use crate::utils::*;
+
+/// The files a wasm build needs, for servers that shouldn't hand out everything under their root
+pub const WEB_EXTENSIONS: &[&str] = &["html", "wasm", "css", "js", "ttf", "png", "jpg", "svg"];
+
+/// Serves the files under a directory, with ETag revalidation, single byte Range requests
+/// and precompressed .br/.gz siblings for clients that accept them
+pub struct StaticFiles {
+    pub root: PathBuf,
+    /// added to every response, like the cross origin isolation headers wasm threads need.
+    /// Each line ends in \r\n
+    pub headers: String,
+    /// when set, only files with one of these extensions are served, anything else is a 404
+    pub extensions: Option<&'static [&'static str]>,
+}
+
+impl StaticFiles {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {root: root.into(), headers: String::new(), extensions: None}
+    }
+
+    /// Answers a GET or HEAD for path, which is relative to the root
+    pub fn serve(&self, headers: &HttpServerHeaders, path: &str) -> HttpServerResponse {
+        let path = percent_decode(path, false);
+        // no way out of the root
+        if path.starts_with('/') || path.contains('\\') || path.split('/').any( | p | p == "..") {
+            return HttpServerResponse::status(403)
+        }
+        let mut file_path = self.root.join(&path);
+        if file_path.is_dir() {
+            file_path = file_path.join("index.html");
+        }
+        if let Some(extensions) = self.extensions {
+            let ext = file_path.extension().and_then( | ext | ext.to_str()).unwrap_or("").to_ascii_lowercase();
+            if !extensions.contains(&ext.as_str()) {
+                return HttpServerResponse::status(404)
+            }
+        }
+        let metadata = if let Ok(metadata) = file_path.metadata() {metadata} else {
+            return HttpServerResponse::status(404)
+        };
+        if !metadata.is_file() {
+            return HttpServerResponse::status(404)
+        }
+        let modified = metadata.modified().ok().and_then( | m | m.duration_since(UNIX_EPOCH).ok()).unwrap_or_default();
+        let etag = format!("\"{:x}-{:x}\"", metadata.len(), modified.as_nanos());
+
+        if let Some(if_none_match) = headers.header("If-None-Match") {
+            if if_none_match.split(',').any( | tag | {
+                let tag = tag.trim();
+                tag == "*" || tag.trim_start_matches("W/") == etag
+            }) {
+                return HttpServerResponse {
+                    header: format!("HTTP/1.1 304 Not Modified\r\nETag: {}\r\n{}\r\n", etag, self.headers),
+                    body: Vec::new()
+                }
+            }
+        }
+
+        let content_type = mime_type(&file_path.to_string_lossy());
+        let len = metadata.len();
+        let head = headers.verb == "HEAD";
+
+        // ranges are served from the file itself, otherwise a precompressed sibling is preferred
+        if let Some(range) = headers.header("Range") {
+            let (start, end) = match parse_range(range, len) {
+                Some(Some(range)) => range,
+                Some(None) => return HttpServerResponse {
+                    header: format!("HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */{}\r\nContent-Length: 0\r\n\r\n", len),
+                    body: Vec::new()
+                },
+                None => (0, len), // not a range we support, the whole file it is
+            };
+            if start != 0 || end != len {
+                let body = if head {Vec::new()} else {
+                    match read_file_range(&file_path, start, end) {
+                        Some(body) => body,
+                        None => return HttpServerResponse::status(500)
+                    }
+                };
+                return HttpServerResponse {
+                    header: format!(
+                        "HTTP/1.1 206 Partial Content\r\n\
+                        Content-Type: {}\r\n\
+                        Content-Range: bytes {}-{}/{}\r\n\
+                        Content-Length: {}\r\n\
+                        Accept-Ranges: bytes\r\n\
+                        ETag: {}\r\n\
+                        {}\r\n",
+                        content_type,
+                        start,
+                        end - 1,
+                        len,
+                        end - start,
+                        etag,
+                        self.headers
+                    ),
+                    body
+                }
+            }
+        }
+
+        let accept_encoding = headers.accept_encoding.as_deref().unwrap_or("");
+        let mut send_path = file_path.clone();
+        let mut send_len = len;
+        let mut content_encoding = "";
+        for (encoding, ext) in [("br", "br"), ("gzip", "gz")] {
+            if !accept_encoding.split(',').any( | e | e.trim().split(';').next() == Some(encoding)) {
+                continue
+            }
+            let mut compressed = file_path.clone().into_os_string();
+            compressed.push(".");
+            compressed.push(ext);
+            let compressed = PathBuf::from(compressed);
+            if let Ok(metadata) = compressed.metadata() {
+                if metadata.is_file() {
+                    send_path = compressed;
+                    send_len = metadata.len();
+                    content_encoding = encoding;
+                    break
+                }
+            }
+        }
+        let body = if head {Vec::new()} else {
+            match read_file_range(&send_path, 0, send_len) {
+                Some(body) => body,
+                None => return HttpServerResponse::status(500)
+            }
+        };
+        let content_encoding = if content_encoding.is_empty() {String::new()} else {
+            format!("Content-Encoding: {}\r\n", content_encoding)
+        };
+        HttpServerResponse {
+            header: format!(
+                "HTTP/1.1 200 OK\r\n\
+                Content-Type: {}\r\n\
+                {}\
+                Content-Length: {}\r\n\
+                Accept-Ranges: bytes\r\n\
+                Vary: Accept-Encoding\r\n\
+                ETag: {}\r\n\
+                {}\r\n",
+                content_type,
+                content_encoding,
+                send_len,
+                etag,
+                self.headers
+            ),
+            body
+        }
+    }
+}
+
+fn read_file_range(path: &Path, start: u64, end: u64) -> Option<Vec<u8>> {
+    let mut file = File::open(path).ok() ?;
+    file.seek(SeekFrom::Start(start)).ok() ?;
+    let mut body = Vec::with_capacity((end - start) as usize);
+    file.take(end - start).read_to_end(&mut body).ok() ?;
+    if body.len() as u64 != end - start {
+        return None
+    }
+    Some(body)
+}
+
+/// Parses a Range header into a start..end byte range. None for ranges we don't do,
+/// like multiple ranges or other units, Some(None) when it can't be satisfied
+fn parse_range(range: &str, len: u64) -> Option<Option<(u64, u64)>> {
+    let range = range.trim().strip_prefix("bytes=") ?;
+    if range.contains(',') {
+        return None
+    }
+    let (start, end) = range.split_once('-') ?;
+    let (start, end) = (start.trim(), end.trim());
+    let (start, end) = if start.is_empty() { // the last n bytes
+        let suffix: u64 = end.parse().ok() ?;
+        (len.saturating_sub(suffix), len)
+    }
+    else {
+        let start: u64 = start.parse().ok() ?;
+        let end = if end.is_empty() {len} else {(end.parse::<u64>().ok() ? + 1).min(len)};
+        (start, end)
+    };
+    if start >= end {
+        return Some(None)
+    }
+    Some(Some((start, end)))
+}
+
+/// The content type for a file by its extension
+pub fn mime_type(path: &str) -> &'static str {
+    let ext = path.rsplit_once('.').map( | (_, ext) | ext.to_ascii_lowercase()).unwrap_or_default();
+    match ext.as_str() {
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" | "mjs" => "text/javascript",
+        "json" => "application/json",
+        "wasm" => "application/wasm",
+        "txt" | "md" => "text/plain",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "ico" => "image/x-icon",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+
+    fn request(path: &str, extra: &[&str]) -> HttpServerHeaders {
+        let mut lines = vec![format!("GET /{} HTTP/1.1", path)];
+        lines.extend(extra.iter().map( | line | line.to_string()));
+        HttpServerHeaders {
+            addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+            lines,
+            verb: "GET".to_string(),
+            path: format!("/{}", path),
+            path_no_slash: path.to_string(),
+            search: None,
+            content_length: None,
+            accept_encoding: None,
+            sec_websocket_key: None,
+            keep_alive: true,
+        }
+    }
+
+    fn status(response: &HttpServerResponse) -> &str {
+        response.header.split(' ').nth(1).unwrap()
+    }
+
+    fn root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("makepad_static_files_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::write(root.join("index.html"), "<html></html>").unwrap();
+        std::fs::write(root.join("app.wasm"), "0123456789").unwrap();
+        std::fs::write(root.join("secret.key"), "key").unwrap();
+        root
+    }
+
+    #[test]
+    fn serves_files_and_ranges() {
+        let root = root("ranges");
+        let files = StaticFiles::new(&root);
+        let response = files.serve(&request("", &[]), "");
+        assert_eq!(status(&response), "200");
+        assert_eq!(response.body, b"<html></html>");
+        let response = files.serve(&request("app.wasm", &["Range: bytes=2-4"]), "app.wasm");
+        assert_eq!(status(&response), "206");
+        assert_eq!(response.body, b"234");
+        let response = files.serve(&request("app.wasm", &["Range: bytes=20-"]), "app.wasm");
+        assert_eq!(status(&response), "416");
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn revalidates_with_etag() {
+        let root = root("etag");
+        let files = StaticFiles::new(&root);
+        let response = files.serve(&request("app.wasm", &[]), "app.wasm");
+        let etag = response.header.lines().find_map( | line | line.strip_prefix("ETag: ")).unwrap().to_string();
+        let if_none_match = format!("If-None-Match: {}", etag);
+        let response = files.serve(&request("app.wasm", &[&if_none_match]), "app.wasm");
+        assert_eq!(status(&response), "304");
+        assert!(response.body.is_empty());
+        let response = files.serve(&request("app.wasm", &["If-None-Match: \"other\""]), "app.wasm");
+        assert_eq!(status(&response), "200");
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn rejects_paths_outside_the_root_and_other_extensions() {
+        let root = root("reject");
+        let files = StaticFiles::new(&root);
+        for path in ["../etc/passwd", "sub/../../x", "/etc/passwd", "sub\\x", "%2e%2e/x"] {
+            assert_eq!(status(&files.serve(&request(path, &[]), path)), "403", "{}", path);
+        }
+        assert_eq!(status(&files.serve(&request("missing.html", &[]), "missing.html")), "404");
+        assert_eq!(status(&files.serve(&request("secret.key", &[]), "secret.key")), "200");
+        let files = StaticFiles {extensions: Some(WEB_EXTENSIONS), ..StaticFiles::new(&root)};
+        assert_eq!(status(&files.serve(&request("secret.key", &[]), "secret.key")), "404");
+        assert_eq!(status(&files.serve(&request("app.wasm", &[]), "app.wasm")), "200");
+        assert_eq!(status(&files.serve(&request("", &[]), "")), "200");
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(parse_range("bytes=0-9", 100), Some(Some((0, 10))));
+        assert_eq!(parse_range("bytes=90-", 100), Some(Some((90, 100))));
+        assert_eq!(parse_range("bytes=-10", 100), Some(Some((90, 100))));
+        assert_eq!(parse_range("bytes=50-500", 100), Some(Some((50, 100))));
+        assert_eq!(parse_range("bytes=100-", 100), Some(None));
+        assert_eq!(parse_range("bytes=0-1,5-6", 100), None);
+        assert_eq!(parse_range("items=0-1", 100), None);
+        assert_eq!(parse_range("bytes=x-1", 100), None);
+    }
+}
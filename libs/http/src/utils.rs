@@ -1,9 +1,37 @@
 use std::net::{TcpStream, Shutdown, SocketAddr};
+use std::io;
 use std::io::Write;
 use std::io::BufReader;
 use std::io::prelude::*;
 
-pub fn write_bytes_to_tcp_stream_no_error(tcp_stream: &mut TcpStream, bytes: &[u8]) -> bool {
+/// A connection the server reads requests from and writes responses to,
+/// either the TcpStream itself or a TLS session running on top of it
+pub trait HttpStream: Read + Write + Send {
+    /// A second handle on the same connection, websockets write to it from their own thread
+    fn try_clone_stream(&self) -> io::Result<Box<dyn HttpStream>>;
+}
+
+impl HttpStream for TcpStream {
+    fn try_clone_stream(&self) -> io::Result<Box<dyn HttpStream>> {
+        Ok(Box::new(self.try_clone() ?))
+    }
+}
+
+/// Terminates TLS for the http server. Implement this on top of the TLS library of your choice
+/// and set it as HttpServer::tls
+pub trait TlsAcceptor: Send + Sync {
+    /// Runs the handshake on a freshly accepted connection
+    fn accept(&self, tcp_stream: TcpStream) -> io::Result<Box<dyn HttpStream>>;
+}
+
+/// Runs TLS on top of the connections of the http client. Implement this on top of the TLS library
+/// of your choice, plain http works without one
+pub trait TlsConnector: Send + Sync {
+    /// Runs the handshake on a freshly opened connection, host is what the certificate has to match
+    fn connect(&self, host: &str, tcp_stream: TcpStream) -> io::Result<Box<dyn HttpStream>>;
+}
+
+pub fn write_bytes_to_tcp_stream_no_error<W: Write + ?Sized>(tcp_stream: &mut W, bytes: &[u8]) -> bool {
     let bytes_total = bytes.len();
     let mut bytes_left = bytes_total;
     while bytes_left > 0 {
@@ -22,10 +50,65 @@ pub fn write_bytes_to_tcp_stream_no_error(tcp_stream: &mut TcpStream, bytes: &[u
 }
 
 pub fn http_error_out(mut tcp_stream: TcpStream, code: usize) {
-    write_bytes_to_tcp_stream_no_error(&mut tcp_stream, format!("HTTP/1.1 {}\r\n\r\n", code).as_bytes());
+    write_bytes_to_tcp_stream_no_error(&mut tcp_stream, format!("HTTP/1.1 {} {}\r\n\r\n", code, status_text(code)).as_bytes());
     let _ = tcp_stream.shutdown(Shutdown::Both);
 }
 
+pub fn status_text(code: usize) -> &'static str {
+    match code {
+        101 => "Switching Protocols",
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        206 => "Partial Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        416 => "Range Not Satisfiable",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        503 => "Service Unavailable",
+        _ => ""
+    }
+}
+
+/// Decodes %XX escapes, and + as a space when plus_as_space is set like in query strings
+pub fn percent_decode(inp: &str, plus_as_space: bool) -> String {
+    let bytes = inp.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                let hex = | c: u8 | (c as char).to_digit(16);
+                if let (Some(hi), Some(lo)) = (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                    out.push((hi * 16 + lo) as u8);
+                    i += 3;
+                    continue
+                }
+                out.push(b'%');
+            }
+            b'+' if plus_as_space => out.push(b' '),
+            c => out.push(c)
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Splits a query string like ?a=1&b=two into decoded key value pairs
+pub fn parse_query(search: &str) -> Vec<(String, String)> {
+    search.trim_start_matches('?').split('&').filter( | s | !s.is_empty()).map( | pair | {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        (percent_decode(key, true), percent_decode(value, true))
+    }).collect()
+}
 
 pub fn split_header_line<'a>(inp: &'a str, what: &str) -> Option<&'a str> {
     let mut what_lc = what.to_string();
@@ -68,67 +151,98 @@ pub struct HttpServerHeaders {
     pub search: Option<String>,
     pub content_length: Option<u64>,
     pub accept_encoding: Option<String>,
-    pub sec_websocket_key: Option<String>
+    pub sec_websocket_key: Option<String>,
+    /// if the connection stays open after the response, HTTP/1.1 does unless asked not to
+    pub keep_alive: bool,
 }
 
 impl HttpServerHeaders {
+    /// The value of a header by case insensitive name, like header("if-none-match")
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.lines.iter().skip(1).find_map( | line | {
+            let (key, value) = line.split_once(':') ?;
+            if key.trim().eq_ignore_ascii_case(name) {Some(value.trim())} else {None}
+        })
+    }
+    
     pub fn from_tcp_stream(tcp_stream: &mut TcpStream) -> Option<HttpServerHeaders> {
         let addr = tcp_stream.peer_addr().unwrap();
         let mut reader = BufReader::new(tcp_stream);
         
         let mut lines = Vec::new();
-        let mut content_length = None;
-        let mut accept_encoding = None;
-        let mut sec_websocket_key = None;
         let mut line = String::new();
         
         while reader.read_line(&mut line).is_ok() { // TODO replace this with a non-line read
             if line == "\r\n" { // the newline
                 break;
             }
-            if let Some(v) = split_header_line(&line, "Content-Length: ") {
+            if line.len() > 4096 || lines.len() > 4096 { // some overflow protection
+                return None
+            }
+            lines.push(line.clone());
+            line.clear();
+        }
+        Self::from_lines(addr, lines)
+    }
+    
+    /// Parses a header block that ends in an empty line, like the server reads them off a connection
+    pub fn from_bytes(addr: SocketAddr, bytes: &[u8]) -> Option<HttpServerHeaders> {
+        let text = std::str::from_utf8(bytes).ok() ?;
+        let mut lines = Vec::new();
+        for line in text.split_inclusive("\r\n") {
+            if line == "\r\n" {
+                break;
+            }
+            if line.len() > 4096 || lines.len() > 4096 {
+                return None
+            }
+            lines.push(line.to_string());
+        }
+        Self::from_lines(addr, lines)
+    }
+    
+    fn from_lines(addr: SocketAddr, lines: Vec<String>) -> Option<HttpServerHeaders> {
+        let mut content_length = None;
+        let mut accept_encoding = None;
+        let mut sec_websocket_key = None;
+        let mut connection = None;
+        for line in &lines {
+            if !line.ends_with("\r\n") {
+                return None
+            }
+            if let Some(v) = split_header_line(line, "Content-Length: ") {
                 content_length = Some(if let Ok(v) = v.parse() {v} else {
                     return None
                 });
             }
-            if let Some(v) = split_header_line(&line, "Accept-Encoding: ") {
+            if let Some(v) = split_header_line(line, "Accept-Encoding: ") {
                 accept_encoding = Some(v.to_string());
             }
-            if let Some(v) = split_header_line(&line, "sec-websocket-key: ") {
+            if let Some(v) = split_header_line(line, "sec-websocket-key: ") {
                 sec_websocket_key = Some(v.to_string());
             }
-            if line.len() > 4096 || lines.len() > 4096 { // some overflow protection
-                return None
+            if let Some(v) = split_header_line(line, "Connection: ") {
+                connection = Some(v.to_ascii_lowercase());
             }
-            lines.push(line.clone());
-            line.clear();
         }
         if lines.len() <2 {
             return None;
         }
-        let verb;
-        let path;
-        if let Some(v) = split_header_line(&lines[0], "GET ") {
-            verb = "GET";
-            path = parse_url_path(v)
-        }
-        else if let Some(v) = split_header_line(&lines[0], "POST ") {
-            verb = "POST";
-            path = parse_url_path(v)
-        }
-        else if let Some(v) = split_header_line(&lines[0], "PUT ") {
-            verb = "PUT";
-            path = parse_url_path(v)
+        let (verb, path) = ["GET", "POST", "PUT", "DELETE", "HEAD"].iter().find_map( | verb | {
+            split_header_line(&lines[0], &format!("{} ", verb)).map( | v | (*verb, parse_url_path(v)))
+        }) ?;
+        path.as_ref() ?;
+        let path = path.unwrap();
+        if !path.0.starts_with('/') {
+            return None
         }
-        else if let Some(v) = split_header_line(&lines[0], "DELETE ") {
-            verb = "DELETE";
-            path = parse_url_path(v)
+        
+        let keep_alive = if lines[0].ends_with("HTTP/1.0\r\n") {
+            connection.as_deref().is_some_and( | c | c.contains("keep-alive"))
         }
         else {
-            return None
-        }
-        path.as_ref() ?;
-        let path = path.unwrap();
+            !connection.as_deref().is_some_and( | c | c.contains("close"))
+        };
         
         Some(HttpServerHeaders {
             addr,
@@ -139,7 +253,8 @@ impl HttpServerHeaders {
             lines,
             content_length,
             accept_encoding,
-            sec_websocket_key
+            sec_websocket_key,
+            keep_alive
         })
     }
 }
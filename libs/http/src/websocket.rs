@@ -1,6 +1,11 @@
 use std::convert::TryInto;
 use crate::digest::{Sha1, base64_encode};
 use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use makepad_miniz::{DataFormat, MZFlush, MZError};
+use makepad_miniz::deflate::{core::{CompressorOxide, create_comp_flags_from_zip_params}, stream::deflate};
+use makepad_miniz::inflate::stream::{InflateState, inflate};
 
 #[derive(Debug, PartialEq)]
 enum State {
@@ -25,6 +30,22 @@ impl State {
     }
 }
 
+const OPCODE_CONTINUATION: u8 = 0;
+const OPCODE_TEXT: u8 = 1;
+const OPCODE_BINARY: u8 = 2;
+const OPCODE_CLOSE: u8 = 8;
+const OPCODE_PING: u8 = 9;
+const OPCODE_PONG: u8 = 10;
+
+/// The close code for a close frame that didn't carry one
+pub const CLOSE_NO_STATUS: u16 = 1005;
+pub const CLOSE_NORMAL: u16 = 1000;
+pub const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+/// The close code for a message that is larger than the max message size
+pub const CLOSE_MESSAGE_TOO_BIG: u16 = 1009;
+/// The close code for a connection that ended without a close frame, never sent over the wire
+pub const CLOSE_ABNORMAL: u16 = 1006;
+
 pub struct WebSocket {
     head: [u8; 8],
     head_expected: usize,
@@ -33,26 +54,60 @@ pub struct WebSocket {
     data_len: usize,
     input_read: usize,
     mask_counter: usize,
-    is_ping: bool,
-    is_pong: bool,
-    is_partial: bool,
-    is_text: bool,
+    opcode: u8,
+    is_final: bool,
+    is_compressed: bool,
     is_masked: bool,
+    // a fragmented message that is still coming in
+    message: Vec<u8>,
+    message_opcode: Option<u8>,
+    message_compressed: bool,
+    // set when permessage-deflate was negotiated
+    inflate: Option<Box<InflateState>>,
+    // the most a message can hold, after reassembling its frames and inflating it
+    max_message_size: usize,
     state: State
 }
 
+/// The max message size of a new parser
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Cuts a close reason short to what fits in a close frame next to the code
+pub fn close_reason(reason: &str) -> &str {
+    // control frames carry at most 125 bytes, the code takes 2 of them
+    let mut len = reason.len().min(123);
+    while !reason.is_char_boundary(len) {
+        len -= 1;
+    }
+    &reason[..len]
+}
+
 pub enum WebSocketMessage<'a> {
     Ping(&'a [u8]),
     Pong(&'a [u8]),
     Text(&'a str),
     Binary(&'a [u8]),
-    Close
+    /// The close code, CLOSE_NO_STATUS if there was none, and the reason
+    Close(u16, &'a str)
 }
 
 #[derive(Debug)]
 pub enum WebSocketError<'a> {
     OpcodeNotSupported(u8),
     TextNotUTF8(&'a [u8]),
+    ProtocolError(&'static str),
+    DecompressionFailed,
+    MessageTooBig,
+}
+
+impl WebSocketError<'_> {
+    /// The code to close the connection with after this error
+    pub fn close_code(&self) -> u16 {
+        match self {
+            WebSocketError::MessageTooBig => CLOSE_MESSAGE_TOO_BIG,
+            _ => CLOSE_PROTOCOL_ERROR
+        }
+    }
 }
 
 pub const PING_MESSAGE:[u8;2] = [128 | 9,0];
@@ -60,7 +115,10 @@ pub const PONG_MESSAGE:[u8;2] = [128 | 10,0];
 
 pub enum MessageFormat {
     Binary,
-    Text
+    Text,
+    Close,
+    Ping,
+    Pong
 }
 
 pub struct MessageHeader {
@@ -74,10 +132,13 @@ impl MessageHeader {
     pub fn from_len(len: usize, format: MessageFormat, masked: bool)->Self{
         let mut data = [0u8;14];
         
-        match format {
-            MessageFormat::Binary => data[0] = 128 | 2,
-            MessageFormat::Text => data[0] = 128 | 1,
-        }
+        data[0] = 128 | match format {
+            MessageFormat::Binary => OPCODE_BINARY,
+            MessageFormat::Text => OPCODE_TEXT,
+            MessageFormat::Close => OPCODE_CLOSE,
+            MessageFormat::Ping => OPCODE_PING,
+            MessageFormat::Pong => OPCODE_PONG,
+        };
 
         if masked {
             data[1] = 128;
@@ -117,18 +178,20 @@ impl MessageHeader {
         }
     }
     
+    /// Marks the message as compressed with permessage-deflate
+    pub fn compressed(mut self)->Self{
+        self.data[0] |= 64;
+        self
+    }
+    
     pub fn as_slice(&self)->&[u8]{
         &self.data[0..self.len]
     }
 
+    /// The masking key, the last 4 bytes of a masked header
     pub fn mask(&mut self)->Option<&[u8]> {
         if self.masked {
-            match self.len {
-                6 => Some(&self.data[2..6]),
-                10 => Some(&self.data[6..10]),
-                14 => Some(&self.data[10..14]),
-                _ => None
-            }
+            Some(&self.data[self.len - 4..self.len])
         } else {
             None
         }
@@ -151,27 +214,152 @@ impl WebSocket {
             data_len: 0,
             input_read: 0,
             mask_counter: 0,
-            is_ping: false,
-            is_pong: false,
+            opcode: 0,
+            is_final: false,
+            is_compressed: false,
             is_masked: false,
-            is_partial: false,
-            is_text: false,
+            message: Vec::new(),
+            message_opcode: None,
+            message_compressed: false,
+            inflate: None,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
             state: State::Opcode
         }
     }
     
+    /// Sets the most a message can hold, larger messages are a MessageTooBig error
+    pub fn set_max_message_size(&mut self, max_message_size: usize) {
+        self.max_message_size = max_message_size;
+    }
+    
     pub fn create_upgrade_response(key: &str) -> String {
+        Self::create_upgrade_response_with_extensions(key, None)
+    }
+    
+    /// A fresh Sec-WebSocket-Key for a client to send with its upgrade request
+    pub fn create_key() -> String {
+        let mut bytes = Vec::with_capacity(16);
+        for _ in 0..2 {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u128(SystemTime::now().duration_since(UNIX_EPOCH).map( | d | d.as_nanos()).unwrap_or(0));
+            bytes.extend_from_slice(&hasher.finish().to_le_bytes());
+        }
+        base64_encode(&bytes)
+    }
+    
+    /// The Sec-WebSocket-Accept that answers a Sec-WebSocket-Key
+    pub fn accept_key(key: &str) -> String {
         let to_hash = format!("{}258EAFA5-E914-47DA-95CA-C5AB0DC85B11", key);
         let mut sha1 = Sha1::new();
         sha1.update(to_hash.as_bytes());
         let out_bytes = sha1.finalise();
-        let base64 = base64_encode(&out_bytes);
+        base64_encode(&out_bytes)
+    }
+    
+    /// The upgrade response, with the Sec-WebSocket-Extensions that negotiate_deflate agreed on
+    pub fn create_upgrade_response_with_extensions(key: &str, extensions: Option<&str>) -> String {
+        let base64 = Self::accept_key(key);
+        let extensions = if let Some(extensions) = extensions {
+            format!("Sec-WebSocket-Extensions: {}\r\n", extensions)
+        }
+        else {
+            String::new()
+        };
         let response_ack = format!(
-            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
-            base64
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n{}\r\n",
+            base64,
+            extensions
         );
         response_ack
     }
+    
+    /// Looks for a permessage-deflate offer in a Sec-WebSocket-Extensions header we can accept, and turns
+    /// on decompression for this parser if there is one. Returns the extension to put in the upgrade response.
+    /// We don't keep a compression context between the messages we send, so we always answer with
+    /// server_no_context_takeover and compress with deflate_message
+    pub fn negotiate_deflate(&mut self, offers: &str) -> Option<String> {
+        for offer in offers.split(',') {
+            let mut params = offer.split(';').map( | p | p.trim());
+            if params.next() != Some("permessage-deflate") {
+                continue
+            }
+            // our compressor always uses the full 32k window
+            let acceptable = params.all( | param | {
+                match param.split_once('=').map( | (k, v) | (k.trim(), v.trim().trim_matches('"'))) {
+                    Some(("server_max_window_bits", bits)) => bits == "15",
+                    Some(("client_max_window_bits", _)) => true,
+                    None => matches!(param, "server_no_context_takeover" | "client_no_context_takeover" | "client_max_window_bits"),
+                    _ => false
+                }
+            });
+            if acceptable {
+                self.inflate = Some(InflateState::new_boxed(DataFormat::Raw));
+                return Some("permessage-deflate; server_no_context_takeover".to_string())
+            }
+        }
+        None
+    }
+    
+    /// Compresses a message payload for permessage-deflate, send it with a compressed() header
+    pub fn deflate_message(data: &[u8]) -> Vec<u8> {
+        let mut compressor = CompressorOxide::new(create_comp_flags_from_zip_params(6, -15, 0));
+        let mut out = vec![0u8; data.len() / 2 + 64];
+        let mut read = 0;
+        let mut written = 0;
+        loop {
+            let result = deflate(&mut compressor, &data[read..], &mut out[written..], MZFlush::Sync);
+            read += result.bytes_consumed;
+            written += result.bytes_written;
+            if written < out.len() && read == data.len() || result.status.is_err() && result.status != Err(MZError::Buf) {
+                break
+            }
+            if written == out.len() {
+                out.resize(out.len() * 2, 0);
+            }
+        }
+        out.truncate(written);
+        // the sync flush ends in an empty block the receiver adds back
+        if out.ends_with(&[0, 0, 0xff, 0xff]) {
+            out.truncate(written - 4);
+        }
+        out
+    }
+    
+    fn inflate_message(state: &mut InflateState, data: &[u8], max_len: usize) -> Result<Vec<u8>, WebSocketError<'static>> {
+        let mut input = data.to_vec();
+        input.extend_from_slice(&[0, 0, 0xff, 0xff]);
+        // one byte more than max_len, so a message that is too big fills it up
+        let out_max = max_len.saturating_add(1);
+        let mut out = vec![0u8; (data.len() * 4 + 64).min(out_max)];
+        let mut read = 0;
+        let mut written = 0;
+        loop {
+            let result = inflate(state, &input[read..], &mut out[written..], MZFlush::None);
+            read += result.bytes_consumed;
+            written += result.bytes_written;
+            match result.status {
+                Err(MZError::Buf) | Ok(_) => (),
+                Err(_) => return Err(WebSocketError::DecompressionFailed)
+            }
+            if read == input.len() && written < out.len() {
+                break
+            }
+            if written == out.len() {
+                if written == out_max {
+                    state.reset(DataFormat::Raw);
+                    return Err(WebSocketError::MessageTooBig)
+                }
+                out.resize((out.len() * 2).min(out_max), 0);
+            }
+            else if result.bytes_consumed == 0 && result.bytes_written == 0 {
+                // a message that ended in a final block, the next one starts a new stream
+                state.reset(DataFormat::Raw);
+                break
+            }
+        }
+        out.truncate(written);
+        Ok(out)
+    }
 
     pub fn build_message(mut header: MessageHeader, data: &[u8])->Vec<u8>{
         let mut frame = header.as_slice().to_vec();
@@ -205,10 +393,8 @@ impl WebSocket {
                 self.data.clear();
             }
             State::Opcode => {
-                self.is_ping = false;
-                self.is_pong = false;
-                self.is_partial = false;
-                self.is_text = false;
+                self.is_final = false;
+                self.is_compressed = false;
                 self.is_masked = false;
             },
             _ => ()
@@ -227,28 +413,25 @@ impl WebSocket {
                     if self.parse_head(input) {
                         break;
                     }
-                    let opcode = self.head[0] & 15;
-                    if opcode <= 2 {
-                        self.is_partial = (self.head[0] & 128) != 0;
-                        self.is_text = opcode == 1;
-                        self.to_state(State::Len1);
-                    }
-                    else if opcode == 8 {
-                        result(Ok(WebSocketMessage::Close));
+                    self.opcode = self.head[0] & 15;
+                    self.is_final = (self.head[0] & 128) != 0;
+                    self.is_compressed = (self.head[0] & 64) != 0;
+                    if self.head[0] & 48 != 0 || self.is_compressed && self.inflate.is_none() {
+                        result(Err(WebSocketError::ProtocolError("Reserved bits set")));
                         break;
                     }
-                    else if opcode == 9 {
-                        self.is_ping = true;
-                        self.to_state(State::Len1);
-                    }
-                    else if opcode == 10 {
-                        self.is_pong = true;
-                        self.to_state(State::Len1);
-                    }
-                    else {
-                        result(Err(WebSocketError::OpcodeNotSupported(opcode)));
-                        break;
+                    match self.opcode {
+                        OPCODE_CONTINUATION | OPCODE_TEXT | OPCODE_BINARY => (),
+                        OPCODE_CLOSE | OPCODE_PING | OPCODE_PONG => if !self.is_final || self.is_compressed {
+                            result(Err(WebSocketError::ProtocolError("Fragmented or compressed control frame")));
+                            break;
+                        }
+                        opcode => {
+                            result(Err(WebSocketError::OpcodeNotSupported(opcode)));
+                            break;
+                        }
                     }
+                    self.to_state(State::Len1);
                 },
                 State::Len1 => {
                     if self.parse_head(input) {
@@ -265,6 +448,10 @@ impl WebSocket {
                             self.to_state(State::Mask);
                         }
                     }
+                    else if self.opcode >= OPCODE_CLOSE {
+                        result(Err(WebSocketError::ProtocolError("Control frame too long")));
+                        break;
+                    }
                     else if len_type == 126 {
                         self.to_state(State::Len2);
                     }
@@ -320,35 +507,111 @@ impl WebSocket {
                             self.input_read += 1;
                         }
                     }
+                    // frames of a message that is too big are turned down before their data comes in
+                    if self.opcode < OPCODE_CLOSE && self.message.len().saturating_add(self.data_len) > self.max_message_size {
+                        result(Err(WebSocketError::MessageTooBig));
+                        self.message_opcode = None;
+                        self.message.clear();
+                        self.to_state(State::Opcode);
+                        break;
+                    }
                     if self.data.len() < self.data_len { // not enough data yet
                         break;
                     }
-                    else {
-                        if self.is_ping {
-                            result(Ok(WebSocketMessage::Ping(&self.data)));
-                        }
-                        else if self.is_pong {
-                            result(Ok(WebSocketMessage::Pong(&self.data)));
-                        }
-                        else if self.is_text{
-                            if let Ok(text) = std::str::from_utf8(&self.data){
-                                result(Ok(WebSocketMessage::Text(text)));
-                            }
-                            else{
-                                result(Err(WebSocketError::TextNotUTF8(&self.data)))
-                            }
-                        }
-                        else{
-                            result(Ok(WebSocketMessage::Binary(&self.data)));
-                        }
-                        
+                    if !self.frame_done(&mut result) {
+                        self.message_opcode = None;
+                        self.message.clear();
                         self.to_state(State::Opcode);
+                        break;
                     }
+                    self.to_state(State::Opcode);
                 },
             }
         }
     }
     
+    // handles a complete frame, returns false on errors
+    fn frame_done<F>(&mut self, result: &mut F) -> bool where F: FnMut(Result<WebSocketMessage, WebSocketError>){
+        match self.opcode {
+            OPCODE_PING => result(Ok(WebSocketMessage::Ping(&self.data))),
+            OPCODE_PONG => result(Ok(WebSocketMessage::Pong(&self.data))),
+            OPCODE_CLOSE => {
+                if self.data.len() >= 2 {
+                    let code = u16::from_be_bytes([self.data[0], self.data[1]]);
+                    let reason = std::str::from_utf8(&self.data[2..]).unwrap_or("");
+                    result(Ok(WebSocketMessage::Close(code, reason)));
+                }
+                else {
+                    result(Ok(WebSocketMessage::Close(CLOSE_NO_STATUS, "")));
+                }
+            }
+            OPCODE_CONTINUATION => {
+                if self.message_opcode.is_none() {
+                    result(Err(WebSocketError::ProtocolError("Continuation frame without a message")));
+                    return false
+                }
+                self.message.extend_from_slice(&self.data);
+                if self.is_final {
+                    let opcode = self.message_opcode.take().unwrap();
+                    let message = std::mem::take(&mut self.message);
+                    return self.message_done(opcode, self.message_compressed, &message, result)
+                }
+            }
+            opcode => {
+                if self.message_opcode.is_some() {
+                    result(Err(WebSocketError::ProtocolError("New message before the last one ended")));
+                    return false
+                }
+                if self.is_final {
+                    let data = std::mem::take(&mut self.data);
+                    let ok = self.message_done(opcode, self.is_compressed, &data, result);
+                    self.data = data;
+                    return ok
+                }
+                self.message_opcode = Some(opcode);
+                self.message_compressed = self.is_compressed;
+                self.message.extend_from_slice(&self.data);
+            }
+        }
+        true
+    }
+    
+    fn message_done<F>(&mut self, opcode: u8, compressed: bool, data: &[u8], result: &mut F) -> bool where F: FnMut(Result<WebSocketMessage, WebSocketError>){
+        let inflated;
+        let data = if compressed {
+            let max_len = self.max_message_size;
+            match self.inflate.as_mut().map( | inflate | Self::inflate_message(inflate, data, max_len)) {
+                Some(Ok(data)) => {
+                    inflated = data;
+                    &inflated
+                }
+                Some(Err(err)) => {
+                    result(Err(err));
+                    return false
+                }
+                None => {
+                    result(Err(WebSocketError::DecompressionFailed));
+                    return false
+                }
+            }
+        }
+        else {
+            data
+        };
+        if opcode == OPCODE_TEXT {
+            if let Ok(text) = std::str::from_utf8(data) {
+                result(Ok(WebSocketMessage::Text(text)));
+            }
+            else {
+                result(Err(WebSocketError::TextNotUTF8(data)));
+                return false
+            }
+        }
+        else {
+            result(Ok(WebSocketMessage::Binary(data)));
+        }
+        true
+    }
 }
 
 impl Default for WebSocket {
@@ -357,3 +620,223 @@ impl Default for WebSocket {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_all(web_socket: &mut WebSocket, input: &[u8]) -> Vec<Result<Vec<u8>, String>> {
+        let mut out = Vec::new();
+        web_socket.parse(input, | result | out.push(match result {
+            Ok(WebSocketMessage::Text(text)) => Ok(text.as_bytes().to_vec()),
+            Ok(WebSocketMessage::Binary(data)) => Ok(data.to_vec()),
+            Ok(WebSocketMessage::Ping(data)) => Ok(data.to_vec()),
+            Ok(WebSocketMessage::Pong(data)) => Ok(data.to_vec()),
+            Ok(WebSocketMessage::Close(code, reason)) => Ok(format!("{} {}", code, reason).into_bytes()),
+            Err(err) => Err(format!("{:?}", err)),
+        }));
+        out
+    }
+
+    fn payload(len: usize) -> Vec<u8> {
+        (0..len).map( | i | (i * 7 + 3) as u8).collect()
+    }
+
+    #[test]
+    fn header_lengths() {
+        for (len, header_len) in [(0, 2), (125, 2), (126, 4), (65535, 4), (65536, 10)] {
+            assert_eq!(MessageHeader::from_len(len, MessageFormat::Binary, false).as_slice().len(), header_len);
+            let mut header = MessageHeader::from_len(len, MessageFormat::Binary, true);
+            assert_eq!(header.as_slice().len(), header_len + 4);
+            assert_eq!(header.as_slice()[1] & 128, 128);
+            let mask = header.mask().unwrap().to_vec();
+            assert_eq!(mask, header.as_slice()[header_len..].to_vec());
+        }
+    }
+
+    #[test]
+    fn masked_round_trip() {
+        // one payload for each of the 7 bit, 16 bit and 64 bit length encodings
+        for len in [10, 200, 70_000] {
+            let data = payload(len);
+            let frame = WebSocket::build_message(MessageHeader::from_len(len, MessageFormat::Binary, true), &data);
+            assert_eq!(parse_all(&mut WebSocket::new(), &frame), vec![Ok(data.clone())]);
+        }
+    }
+
+    #[test]
+    fn unmasked_round_trip() {
+        for len in [0, 125, 126, 65535, 65536] {
+            let data = payload(len);
+            let frame = WebSocket::build_message(MessageHeader::from_len(len, MessageFormat::Binary, false), &data);
+            assert_eq!(frame.len(), MessageHeader::from_len(len, MessageFormat::Binary, false).as_slice().len() + len);
+            assert_eq!(parse_all(&mut WebSocket::new(), &frame), vec![Ok(data)]);
+        }
+    }
+
+    #[test]
+    fn round_trip_split_input() {
+        let data = payload(300);
+        let mut input = WebSocket::build_message(MessageHeader::from_len(300, MessageFormat::Binary, true), &data);
+        input.extend(WebSocket::build_message(MessageHeader::from_len(5, MessageFormat::Text, true), b"hello"));
+        // feed the frames one byte at a time, so every state has to wait for more input
+        let mut web_socket = WebSocket::new();
+        let mut out = Vec::new();
+        for byte in &input {
+            out.extend(parse_all(&mut web_socket, std::slice::from_ref(byte)));
+        }
+        assert_eq!(out, vec![Ok(data), Ok(b"hello".to_vec())]);
+    }
+
+    // a frame with any first byte, for fragments and broken frames
+    fn frame(first_byte: u8, data: &[u8]) -> Vec<u8> {
+        let mut frame = WebSocket::build_message(MessageHeader::from_len(data.len(), MessageFormat::Binary, true), data);
+        frame[0] = first_byte;
+        frame
+    }
+
+    fn deflate_web_socket() -> WebSocket {
+        let mut web_socket = WebSocket::new();
+        assert!(web_socket.negotiate_deflate("permessage-deflate; client_max_window_bits").is_some());
+        web_socket
+    }
+
+    fn compressed_frame(data: &[u8]) -> Vec<u8> {
+        let compressed = WebSocket::deflate_message(data);
+        WebSocket::build_message(MessageHeader::from_len(compressed.len(), MessageFormat::Binary, true).compressed(), &compressed)
+    }
+
+    #[test]
+    fn control_frames() {
+        let mut input = frame(128 | OPCODE_PING, b"ping");
+        input.extend(frame(128 | OPCODE_PONG, b""));
+        input.extend(frame(128 | OPCODE_CLOSE, &[3, 232, b'b', b'y', b'e']));
+        input.extend(frame(128 | OPCODE_CLOSE, &[]));
+        assert_eq!(parse_all(&mut WebSocket::new(), &input), vec![
+            Ok(b"ping".to_vec()),
+            Ok(Vec::new()),
+            Ok(b"1000 bye".to_vec()),
+            Ok(b"1005 ".to_vec())
+        ]);
+    }
+
+    #[test]
+    fn fragmented_messages() {
+        let mut input = frame(OPCODE_TEXT, b"he");
+        // control frames can come in between the fragments
+        input.extend(frame(128 | OPCODE_PING, b"p"));
+        input.extend(frame(OPCODE_CONTINUATION, b"ll"));
+        input.extend(frame(128 | OPCODE_CONTINUATION, b"o"));
+        input.extend(frame(128 | OPCODE_BINARY, b"next"));
+        assert_eq!(parse_all(&mut WebSocket::new(), &input), vec![Ok(b"p".to_vec()), Ok(b"hello".to_vec()), Ok(b"next".to_vec())]);
+    }
+
+    #[test]
+    fn deflate_round_trip() {
+        let mut web_socket = deflate_web_socket();
+        let text = "hello hello hello hello".repeat(1000);
+        for data in [text.as_bytes().to_vec(), Vec::new(), payload(70_000)] {
+            assert_eq!(parse_all(&mut web_socket, &compressed_frame(&data)), vec![Ok(data)]);
+        }
+        // a compressed message in fragments, only the first one has the compressed bit
+        let compressed = WebSocket::deflate_message(b"fragmented");
+        let mut input = frame(64 | OPCODE_TEXT, &compressed[..3]);
+        input.extend(frame(128 | OPCODE_CONTINUATION, &compressed[3..]));
+        assert_eq!(parse_all(&mut web_socket, &input), vec![Ok(b"fragmented".to_vec())]);
+    }
+
+    #[test]
+    fn reject_invalid_frames() {
+        for (web_socket, input, error) in [
+            (WebSocket::new(), frame(128 | 32 | OPCODE_BINARY, b""), "ProtocolError(\"Reserved bits set\")"),
+            // compression that wasn't negotiated
+            (WebSocket::new(), frame(128 | 64 | OPCODE_BINARY, b""), "ProtocolError(\"Reserved bits set\")"),
+            (WebSocket::new(), frame(128 | 3, b""), "OpcodeNotSupported(3)"),
+            (WebSocket::new(), frame(OPCODE_PING, b""), "ProtocolError(\"Fragmented or compressed control frame\")"),
+            (deflate_web_socket(), frame(128 | 64 | OPCODE_PING, b""), "ProtocolError(\"Fragmented or compressed control frame\")"),
+            (WebSocket::new(), frame(128 | OPCODE_PING, &[0; 126]), "ProtocolError(\"Control frame too long\")"),
+            (WebSocket::new(), frame(128 | OPCODE_CONTINUATION, b""), "ProtocolError(\"Continuation frame without a message\")"),
+            (WebSocket::new(), [frame(OPCODE_TEXT, b"a"), frame(128 | OPCODE_TEXT, b"b")].concat(), "ProtocolError(\"New message before the last one ended\")"),
+            (WebSocket::new(), frame(128 | OPCODE_TEXT, &[0xc3]), "TextNotUTF8([195])"),
+            (deflate_web_socket(), frame(128 | 64 | OPCODE_BINARY, &[0xff; 8]), "DecompressionFailed"),
+        ] {
+            let mut web_socket = web_socket;
+            assert_eq!(parse_all(&mut web_socket, &input), vec![Err(error.to_string())]);
+        }
+    }
+
+    #[test]
+    fn max_message_size() {
+        let mut web_socket = WebSocket::new();
+        web_socket.set_max_message_size(100);
+        assert_eq!(parse_all(&mut web_socket, &frame(128 | OPCODE_BINARY, &payload(100))), vec![Ok(payload(100))]);
+        assert_eq!(parse_all(&mut web_socket, &frame(128 | OPCODE_BINARY, &payload(101))), vec![Err("MessageTooBig".to_string())]);
+        
+        // a frame that claims to be huge is turned down from its header
+        let mut web_socket = WebSocket::new();
+        let mut input = vec![128 | OPCODE_BINARY, 127];
+        input.extend_from_slice(&(1u64 << 62).to_be_bytes());
+        assert_eq!(parse_all(&mut web_socket, &input), vec![Err("MessageTooBig".to_string())]);
+        
+        // fragments that only add up to too much
+        let mut web_socket = WebSocket::new();
+        web_socket.set_max_message_size(100);
+        let mut input = frame(OPCODE_BINARY, &payload(60));
+        input.extend(frame(OPCODE_CONTINUATION, &payload(40)));
+        input.extend(frame(OPCODE_CONTINUATION, &payload(1)));
+        assert_eq!(parse_all(&mut web_socket, &input), vec![Err("MessageTooBig".to_string())]);
+        
+        // a small compressed frame that inflates to too much
+        for (len, max_message_size) in [(100_000, 99_999), (1000, 100), (1000, 0)] {
+            let mut web_socket = deflate_web_socket();
+            web_socket.set_max_message_size(max_message_size);
+            assert_eq!(parse_all(&mut web_socket, &compressed_frame(&vec![0; len])), vec![Err("MessageTooBig".to_string())]);
+            assert_eq!(parse_all(&mut deflate_web_socket(), &compressed_frame(&vec![0; len])), vec![Ok(vec![0; len])]);
+        }
+        
+        assert_eq!(WebSocketError::MessageTooBig.close_code(), CLOSE_MESSAGE_TOO_BIG);
+        assert_eq!(WebSocketError::DecompressionFailed.close_code(), CLOSE_PROTOCOL_ERROR);
+    }
+
+    #[test]
+    fn close_reasons_fit_in_a_frame() {
+        assert_eq!(close_reason("bye"), "bye");
+        assert_eq!(close_reason(&"a".repeat(200)).len(), 123);
+        // cut before a char that doesn't fit whole
+        let reason = format!("{}é", "a".repeat(122));
+        assert_eq!(close_reason(&reason), "a".repeat(122));
+        let mut payload = 1000u16.to_be_bytes().to_vec();
+        payload.extend_from_slice(close_reason(&"é".repeat(100)).as_bytes());
+        let frame = WebSocket::build_message(MessageHeader::from_len(payload.len(), MessageFormat::Close, true), &payload);
+        assert_eq!(parse_all(&mut WebSocket::new(), &frame), vec![Ok(format!("1000 {}", "é".repeat(61)).into_bytes())]);
+    }
+
+    #[test]
+    fn random_input_doesnt_panic() {
+        let mut seed = 1u64;
+        let mut random = | | {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize
+        };
+        let valid = [
+            frame(OPCODE_TEXT, b"hel"),
+            frame(128 | OPCODE_CONTINUATION, b"lo"),
+            frame(128 | OPCODE_CLOSE, &[3, 232]),
+            compressed_frame(&payload(500)),
+        ].concat();
+        for _ in 0..2000 {
+            let mut input = valid.clone();
+            for _ in 0..random() % 8 {
+                let i = random() % input.len();
+                input[i] = random() as u8;
+            }
+            let mut web_socket = deflate_web_socket();
+            web_socket.set_max_message_size(10_000);
+            // split at random, and keep parsing after errors like a careless caller would
+            let split = random() % input.len();
+            parse_all(&mut web_socket, &input[..split]);
+            parse_all(&mut web_socket, &input[split..]);
+            let noise: Vec<u8> = (0..random() % 64).map( | _ | random() as u8).collect();
+            parse_all(&mut web_socket, &noise);
+        }
+    }
+}
@@ -23,6 +23,22 @@ fn from_le32(src: &[u8]) -> u32 {
     ((src[3] as u32) << 24) | ((src[2] as u32) << 16) | ((src[1] as u32) << 8) | (src[0] as u32)
 }
 
+// Reads a 16 bit value from compressed data, which has no size to check against up front.
+fn read_le16(src: &[u8],sp: usize) -> Result<u16,String> {
+    match src.get(sp..sp + 2) {
+        Some(d) => Ok(from_le16(d)),
+        None => Err("Truncated BMP".to_string()),
+    }
+}
+
+// The offset of the pixel at x,y of an image, if the run of count pixels from there is inside it.
+fn run_offset(x: usize,y: isize,count: usize,width: usize,height: usize) -> Result<usize,String> {
+    if (y < 0) || (y as usize >= height) || (x + count > width) {
+        return Err("Invalid BMP".to_string());
+    }
+    Ok((y as usize) * width + x)
+}
+
 struct Component {
     mask: u32,
     shift: u32,
@@ -80,7 +96,7 @@ impl Component {
     }
 }
 
-pub fn decode_pixels(dst: &mut [u32],src: &[u8],width: usize,height: usize,bottom_up: bool,itype: u16,palette: &[u32; 256],redmask: u32,greenmask: u32,bluemask: u32,alphamask: u32) {
+pub fn decode_pixels(dst: &mut [u32],src: &[u8],width: usize,height: usize,bottom_up: bool,itype: u16,palette: &[u32; 256],redmask: u32,greenmask: u32,bluemask: u32,alphamask: u32) -> Result<(),String> {
     let red = Component::new(redmask);
     let green = Component::new(greenmask);
     let blue = Component::new(bluemask);
@@ -172,70 +188,53 @@ pub fn decode_pixels(dst: &mut [u32],src: &[u8],width: usize,height: usize,botto
         },
         TYPE_C4_RLE => {
             let mut x = 0usize;
+            let mut y = y as isize;
             while sp < src.len() {
-                let code: u16 = from_le16(&src[sp..sp+2]);
+                let code: u16 = read_le16(src,sp)?;
                 sp += 2;
                 match code {
                     0x0000 => {
                         x = 0;
-                        y = ((y as isize) + dy) as usize;
+                        y += dy;
                     },
                     0x0100 => {
                         break;
                     },
                     0x0200 => {
-                        x += src[sp] as usize;
-                        y = ((y as isize) + (src[sp + 1] as isize) * dy) as usize;
+                        let delta = read_le16(src,sp)?;
+                        x += (delta & 255) as usize;
+                        y += ((delta >> 8) as isize) * dy;
                         sp += 2;
                     },
                     _ => {
                         if (code & 255) != 0 {
                             let count = code & 255;
-                            if x + (count as usize) > width {
-                                break;
-                            }
+                            let dp = run_offset(x,y,count as usize,width,height)?;
                             let c0 = palette[(code >> 12) as usize];
                             let c1 = palette[((code >> 8) & 15) as usize];
-                            for _i in 0..count / 2 {
-                                dst[y * width + x] = c0;
-                                dst[y * width + x + 1] = c1;
-                                x += 2;
-                            }
-                            if (count & 1) != 0 {
-                                dst[y * width + x] = c0;
-                                x += 1;
+                            for i in 0..count as usize {
+                                dst[dp + i] = if (i & 1) == 0 { c0 } else { c1 };
                             }
+                            x += count as usize;
                         }
                         else {
                             let count = code >> 8;
-                            if x + (count as usize) > width {
-                                break;
-                            }
-                            for _i in 0..count / 4 {
-                                let c = from_le16(&src[sp..sp+2]);
-                                sp += 2;
-                                dst[y * width + x] = palette[((c >> 4) & 15) as usize];
-                                dst[y * width + x + 1] = palette[(c & 15) as usize];
-                                dst[y * width + x + 2] = palette[(c >> 12) as usize];
-                                dst[y * width + x + 3] = palette[((c >> 8) & 15) as usize];
-                                x += 4;
-                            }
-                            if (count & 3) != 0 {
-                                let c = from_le16(&src[sp..sp+2]);
-                                sp += 2;
-                                if (count & 3) >= 1 {
-                                    dst[y * width + x] = palette[((c >> 4) & 15) as usize];
-                                    x += 1;
-                                }
-                                if (count & 3) >= 2 {
-                                    dst[y * width + x] = palette[(c & 15) as usize];
-                                    x += 1;
-                                }
-                                if (count & 3) >= 3 {
-                                    dst[y * width + x] = palette[(c >> 12) as usize];
-                                    x += 1;
+                            let dp = run_offset(x,y,count as usize,width,height)?;
+                            let mut c = 0u16;
+                            for i in 0..count as usize {
+                                if (i & 3) == 0 {
+                                    c = read_le16(src,sp)?;
+                                    sp += 2;
                                 }
+                                let d = match i & 3 {
+                                    0 => (c >> 4) & 15,
+                                    1 => c & 15,
+                                    2 => c >> 12,
+                                    _ => (c >> 8) & 15,
+                                };
+                                dst[dp + i] = palette[d as usize];
                             }
+                            x += count as usize;
                         }
                     }
                 }
@@ -258,56 +257,51 @@ pub fn decode_pixels(dst: &mut [u32],src: &[u8],width: usize,height: usize,botto
         },
         TYPE_C8_RLE => {
             let mut x = 0usize;
+            let mut y = y as isize;
             while sp < src.len() {
-                let code: u16 = from_le16(&src[sp..sp+2]);
+                let code: u16 = read_le16(src,sp)?;
                 sp += 2;
                 match code {
                     0x0000 => {
                         x = 0;
-                        y = ((y as isize) + dy) as usize;
+                        y += dy;
                     },
                     0x0100 => {
                         break;
                     },
                     0x0200 => {
-                        x += src[sp] as usize;
-                        y = ((y as isize) + (src[sp + 1] as isize) * dy) as usize;
+                        let delta = read_le16(src,sp)?;
+                        x += (delta & 255) as usize;
+                        y += ((delta >> 8) as isize) * dy;
                         sp += 2;
                     },
                     _ => {
                         if (code & 255) != 0 {
-                            let count = code & 255;
-                            if x + count as usize > width {
-                                break;
-                            }
+                            let count = (code & 255) as usize;
+                            let dp = run_offset(x,y,count,width,height)?;
                             let c = palette[(code >> 8) as usize];
-                            for _i in 0..count {
-                                dst[y * width + x] = c;
-                                x += 1;
+                            for i in 0..count {
+                                dst[dp + i] = c;
                             }
+                            x += count;
                         }
                         else {
-                            let count = code >> 8;
-                            if x + count as usize > width {
-                                break;
-                            }
-                            for _i in 0..count / 2 {
-                                let c = from_le16(&src[sp..sp + 2]);
-                                sp += 2;
-                                dst[y * width + x] = palette[(c & 255) as usize];
-                                dst[y * width + x + 1] = palette[(c >> 8) as usize];
-                                x += 2;
-                            }
-                            if (count & 1) != 0 {
-                                let c = from_le16(&src[sp..sp + 2]);
-                                sp += 2;
-                                dst[y * width + x] = palette[(c & 255) as usize];
-                                x += 1;
+                            let count = (code >> 8) as usize;
+                            let dp = run_offset(x,y,count,width,height)?;
+                            // the pixels are padded to a 16 bit boundary
+                            let pixels = match src.get(sp..sp + ((count + 1) & !1)) {
+                                Some(pixels) => pixels,
+                                None => { return Err("Truncated BMP".to_string()); },
+                            };
+                            for i in 0..count {
+                                dst[dp + i] = palette[pixels[i] as usize];
                             }
+                            sp += pixels.len();
+                            x += count;
                         }
                     },
                 }
-			}
+            }
         },
         TYPE_A1RGB5 => {
             for _l in 0..height {
@@ -405,9 +399,13 @@ pub fn decode_pixels(dst: &mut [u32],src: &[u8],width: usize,height: usize,botto
         },
         _ => { },
     }
+    Ok(())
 }
 
 pub fn test(src: &[u8]) -> Option<(usize,usize)> {
+    if src.len() < 30 {
+        return None;
+    }
     let tag = from_le16(&src[0..2]);
     if (tag == 0x4D42) ||   // BM (Windows BMP)
         (tag == 0x4142) ||  // BA (OS/2 bitmap)
@@ -418,7 +416,7 @@ pub fn test(src: &[u8]) -> Option<(usize,usize)> {
         let filesize = from_le32(&src[2..6]);
         let offset = from_le32(&src[10..14]);
         let headersize = from_le32(&src[14..18]);
-        if (headersize > filesize) || (offset > filesize) || (headersize > offset) || (filesize != src.len() as u32) {
+        if (offset > filesize) || (headersize.saturating_add(14) > offset) || (filesize != src.len() as u32) {
             return None;
         }
         if (headersize != 12) &&
@@ -498,6 +496,9 @@ pub fn test(src: &[u8]) -> Option<(usize,usize)> {
 }
 
 pub fn decode(src: &[u8]) -> Result<ImageBuffer,String> {
+    if test(src).is_none() {
+        return Err("Invalid BMP".to_string());
+    }
     let tag = from_le16(&src[0..2]);
     if (tag != 0x4D42) &&
         (tag != 0x4142) &&
@@ -510,7 +511,8 @@ pub fn decode(src: &[u8]) -> Result<ImageBuffer,String> {
     let filesize = from_le32(&src[2..6]);
     let offset = from_le32(&src[10..14]);
     let headersize = from_le32(&src[14..18]);
-    if (headersize > filesize) || (offset > filesize) || (headersize > offset) || (filesize != src.len() as u32) {
+    // the header has to end before the pixels start, and the pixels inside the file
+    if (offset > filesize) || (headersize.saturating_add(14) > offset) || (filesize != src.len() as u32) {
         return Err("Invalid BMP".to_string());
     }
     if (headersize != 12) &&
@@ -604,15 +606,21 @@ pub fn decode(src: &[u8]) -> Result<ImageBuffer,String> {
                 } else if colors > 256 {
                     return Err("Invalid BMP".to_string());
                 }
-                for i in 0..colors {
-                    let sp = (14 + headersize + i * 4) as usize;
-                    let b = src[sp];
-                    let g = src[sp + 1];
-                    let r = src[sp + 2];
-                    palette[i as usize] = 0xFF000000 | ((r as u32) << 16) | ((g as u32) << 8) | (b as u32);
+                // the palette sits between the header and the pixels
+                let start = (14 + headersize) as usize;
+                let entries = match src.get(start..start + (colors as usize) * 4) {
+                    Some(entries) if start + (colors as usize) * 4 <= offset as usize => entries,
+                    _ => { return Err("Invalid BMP".to_string()); },
+                };
+                for (i,entry) in entries.chunks_exact(4).enumerate() {
+                    palette[i] = 0xFF000000 | ((entry[2] as u32) << 16) | ((entry[1] as u32) << 8) | (entry[0] as u32);
                 }
             },
             TYPE_B16 | TYPE_B32 => {
+                // the masks follow a 40 byte header, and are part of the longer ones
+                if src.len() < 70 {
+                    return Err("Invalid BMP".to_string());
+                }
                 redmask = from_le32(&src[54..58]);
                 greenmask = from_le32(&src[58..62]);
                 bluemask = from_le32(&src[62..66]);
@@ -630,7 +638,7 @@ pub fn decode(src: &[u8]) -> Result<ImageBuffer,String> {
         }
     }
     let mut image = ImageBuffer::new(width,height);
-    decode_pixels(&mut image.data,&src[offset as usize..],width,height,bottom_up,itype,&palette,redmask,greenmask,bluemask,alphamask);
+    decode_pixels(&mut image.data,&src[offset as usize..],width,height,bottom_up,itype,&palette,redmask,greenmask,bluemask,alphamask)?;
     Ok(image)
 }
 
@@ -638,6 +646,7 @@ trait WriteTypes {
     fn push16(&mut self,d: u16);
     fn push16b(&mut self,d: u16);
     fn push32(&mut self,d: u32);
+    #[allow(dead_code)]
     fn push32b(&mut self,d: u32);
 }
 
@@ -718,3 +727,145 @@ pub fn encode(image: &ImageBuffer) -> Result<Vec<u8>,String> {
     }
     Ok(dst)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A BMP with a 40 byte header, for the given pixel type and data.
+    fn bmp(width: i32,height: i32,bpp: u16,compression: u32,palette: &[u32],pixels: &[u8]) -> Vec<u8> {
+        let offset = 14 + 40 + palette.len() * 4;
+        let mut dst: Vec<u8> = Vec::new();
+        dst.push16b(0x424D);
+        dst.push32((offset + pixels.len()) as u32);
+        dst.push32(0);
+        dst.push32(offset as u32);
+        dst.push32(40);
+        dst.push32(width as u32);
+        dst.push32(height as u32);
+        dst.push16(1);
+        dst.push16(bpp);
+        dst.push32(compression);
+        dst.push32(pixels.len() as u32);
+        dst.push32(1);
+        dst.push32(1);
+        dst.push32(palette.len() as u32);
+        dst.push32(0);
+        for color in palette {
+            dst.push32(*color);
+        }
+        dst.extend_from_slice(pixels);
+        dst
+    }
+
+    const PALETTE: [u32; 2] = [0xFF000000,0xFFFFFFFF];
+
+    fn rle8() -> Vec<u8> {
+        bmp(4,2,8,1,&PALETTE,&[
+            // bottom line: a run of 4, then the end of the line
+            0x04,0x01,0x00,0x00,
+            // top line: 3 literal pixels padded to 16 bits, then a run of 1
+            0x00,0x03,0x00,0x01,0x00,0x00,0x01,0x01,
+            // end of the image
+            0x00,0x01,
+        ])
+    }
+
+    fn rle4() -> Vec<u8> {
+        bmp(5,1,4,2,&PALETTE,&[
+            // a run of 2 alternating colors, then 3 literal pixels
+            0x02,0x10,0x00,0x03,0x10,0x10,
+            0x00,0x01,
+        ])
+    }
+
+    // Calls decode on every prefix of a file, and on the file with each byte changed, with the
+    // file size in the header kept right so the data gets past the first checks.
+    fn decode_corrupted(src: &[u8]) {
+        let mut files = Vec::new();
+        for len in 0..src.len() {
+            files.push(src[..len].to_vec());
+        }
+        for i in 0..src.len() {
+            for value in [0x00,0x01,0x7F,0x80,0xFF,src[i] ^ 0x55] {
+                let mut file = src.to_vec();
+                file[i] = value;
+                files.push(file);
+            }
+        }
+        for mut file in files {
+            if (6..=src.len()).contains(&file.len()) {
+                let len = file.len() as u32;
+                file[2..6].copy_from_slice(&len.to_le_bytes());
+            }
+            let _ = decode(&file);
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut image = ImageBuffer::new(3,2);
+        image.data = vec![0xFF102030,0x80405060,0x00708090,0xFFFFFFFF,0x01020304,0xFF000000];
+        let decoded = decode(&encode(&image).unwrap()).unwrap();
+        assert_eq!((decoded.width,decoded.height),(3,2));
+        assert_eq!(decoded.data,image.data);
+    }
+
+    #[test]
+    fn uncompressed() {
+        // 2x2 with 24 bits per pixel, lines padded to 8 bytes, bottom line first
+        let src = bmp(2,2,24,0,&[],&[
+            0x01,0x02,0x03,0x04,0x05,0x06,0,0,
+            0x07,0x08,0x09,0x0A,0x0B,0x0C,0,0,
+        ]);
+        assert_eq!(test(&src),Some((2,2)));
+        let image = decode(&src).unwrap();
+        assert_eq!(image.data,vec![0xFF090807,0xFF0C0B0A,0xFF030201,0xFF060504]);
+        let image = decode(&bmp(2,1,1,0,&PALETTE,&[0x40,0,0,0])).unwrap();
+        assert_eq!(image.data,vec![0xFF000000,0xFFFFFFFF]);
+    }
+
+    #[test]
+    fn run_length_encoded() {
+        let image = decode(&rle8()).unwrap();
+        assert_eq!(image.data,vec![
+            0xFF000000,0xFFFFFFFF,0xFF000000,0xFFFFFFFF,
+            0xFFFFFFFF,0xFFFFFFFF,0xFFFFFFFF,0xFFFFFFFF,
+        ]);
+        let image = decode(&rle4()).unwrap();
+        assert_eq!(image.data,vec![0xFFFFFFFF,0xFF000000,0xFFFFFFFF,0xFF000000,0xFFFFFFFF]);
+    }
+
+    #[test]
+    fn reject_invalid_files() {
+        let mut files = Vec::new();
+        // the header runs into the pixels
+        let mut src = bmp(2,2,24,0,&[],&[0; 16]);
+        src[10..14].copy_from_slice(&20u32.to_le_bytes());
+        files.push(src);
+        // more colors than fit before the pixels
+        let mut src = bmp(2,1,8,0,&PALETTE,&[0; 4]);
+        src[46..50].copy_from_slice(&200u32.to_le_bytes());
+        files.push(src);
+        // runs past the end of a line, and past the top of the image
+        files.push(bmp(4,2,8,1,&PALETTE,&[0x05,0x01,0x00,0x01]));
+        files.push(bmp(4,2,8,1,&PALETTE,&[0x00,0x02,0x00,0x05,0x01,0x01,0x00,0x01]));
+        // literal pixels past the end of the data
+        files.push(bmp(4,2,8,1,&PALETTE,&[0x00,0x04,0x00,0x01]));
+        // bit fields without room for the masks
+        files.push(bmp(2,1,32,3,&[],&[0; 8]));
+        for src in files {
+            assert!(decode(&src).is_err());
+        }
+    }
+
+    #[test]
+    fn corrupted_files_dont_panic() {
+        let mut image = ImageBuffer::new(2,2);
+        image.data = vec![1,2,3,4];
+        decode_corrupted(&encode(&image).unwrap());
+        decode_corrupted(&rle8());
+        decode_corrupted(&rle4());
+        decode_corrupted(&bmp(3,2,4,0,&PALETTE,&[0x01,0x10,0,0,0x10,0x01,0,0]));
+    }
+}
@@ -0,0 +1,364 @@
+// image_formats::gif
+
+use crate::ImageBuffer;
+
+const MAX_CODES: usize = 4096;
+
+fn from_le16(src: &[u8]) -> u16 {
+    ((src[1] as u16) << 8) | (src[0] as u16)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GifDisposal {
+    /// leave the frame in place
+    None,
+    /// clear the frame area to transparent before the next frame
+    Background,
+    /// restore the frame area to what it was before this frame
+    Previous,
+}
+
+/// A single frame as stored in the file, positioned within the logical screen.
+/// Pixels are ARGB, transparent pixels are 0 and should not overwrite the canvas.
+pub struct GifFrame {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    /// delay in hundredths of a second
+    pub delay: u16,
+    pub disposal: GifDisposal,
+    pub data: Vec<u32>,
+}
+
+pub struct Gif {
+    pub width: usize,
+    pub height: usize,
+    /// NETSCAPE2.0 loop count, None if the animation plays once, Some(0) loops forever
+    pub loop_count: Option<u16>,
+    pub frames: Vec<GifFrame>,
+}
+
+struct Reader<'a> {
+    src: &'a [u8],
+    sp: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn need(&self, n: usize) -> Result<(), String> {
+        if self.sp + n > self.src.len() {
+            return Err("Invalid GIF: unexpected end of data".to_string());
+        }
+        Ok(())
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        self.need(1) ?;
+        self.sp += 1;
+        Ok(self.src[self.sp - 1])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        self.need(2) ?;
+        self.sp += 2;
+        Ok(from_le16(&self.src[self.sp - 2..self.sp]))
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+        self.need(n) ?;
+        self.sp += n;
+        Ok(&self.src[self.sp - n..self.sp])
+    }
+
+    fn palette(&mut self, size: usize) -> Result<Vec<u32>, String> {
+        let data = self.bytes(size * 3) ?;
+        Ok((0..size).map( | i | {
+            0xFF000000 | ((data[i * 3] as u32) << 16) | ((data[i * 3 + 1] as u32) << 8) | (data[i * 3 + 2] as u32)
+        }).collect())
+    }
+
+    fn sub_blocks(&mut self) -> Result<Vec<u8>, String> {
+        let mut out = Vec::new();
+        loop {
+            let len = match self.u8() {
+                Ok(len) => len as usize,
+                Err(_) => return Ok(out)
+            };
+            if len == 0 {
+                return Ok(out);
+            }
+            if self.need(len).is_err() {
+                // truncated file, hand back what we have so a partial frame can still be shown
+                out.extend_from_slice(&self.src[self.sp..]);
+                self.sp = self.src.len();
+                return Ok(out);
+            }
+            out.extend_from_slice(self.bytes(len) ?);
+        }
+    }
+
+    fn skip_sub_blocks(&mut self) -> Result<(), String> {
+        loop {
+            let len = self.u8() ? as usize;
+            if len == 0 {
+                return Ok(());
+            }
+            self.bytes(len) ?;
+        }
+    }
+}
+
+fn decode_lzw(src: &[u8], min_code_size: u8, pixels: usize) -> Result<Vec<u8>, String> {
+    if min_code_size < 2 || min_code_size > 11 {
+        return Err("Invalid GIF: bad LZW code size".to_string());
+    }
+    let clear = 1usize << min_code_size;
+    let end = clear + 1;
+
+    let mut prefix = vec![0u16; MAX_CODES];
+    let mut suffix = vec![0u8; MAX_CODES];
+    let mut first = vec![0u8; MAX_CODES];
+    let mut length = vec![0u16; MAX_CODES];
+    for i in 0..clear {
+        suffix[i] = i as u8;
+        first[i] = i as u8;
+        length[i] = 1;
+    }
+
+    let mut out = Vec::with_capacity(pixels);
+    let mut code_size = min_code_size as usize + 1;
+    let mut next_code = clear + 2;
+    let mut prev: Option<usize> = None;
+    let mut bits = 0u32;
+    let mut nbits = 0usize;
+    let mut sp = 0;
+
+    while out.len() < pixels {
+        while nbits < code_size {
+            if sp >= src.len() {
+                // truncated streams are common, keep what we have
+                out.resize(pixels, 0);
+                return Ok(out);
+            }
+            bits |= (src[sp] as u32) << nbits;
+            sp += 1;
+            nbits += 8;
+        }
+        let code = (bits & ((1 << code_size) - 1)) as usize;
+        bits >>= code_size;
+        nbits -= code_size;
+
+        if code == clear {
+            code_size = min_code_size as usize + 1;
+            next_code = clear + 2;
+            prev = None;
+            continue;
+        }
+        if code == end {
+            break;
+        }
+        let prev_code = match prev {
+            None => {
+                if code >= clear {
+                    return Err("Invalid GIF: bad first LZW code".to_string());
+                }
+                out.push(code as u8);
+                prev = Some(code);
+                continue;
+            }
+            Some(prev_code) => prev_code
+        };
+        let entry = if code < next_code {
+            code
+        }
+        else if code == next_code {
+            prev_code
+        }
+        else {
+            return Err("Invalid GIF: LZW code out of range".to_string());
+        };
+        // write out the string for entry, back to front
+        let start = out.len();
+        let len = length[entry] as usize;
+        out.resize(start + len, 0);
+        let mut c = entry;
+        for i in (0..len).rev() {
+            out[start + i] = suffix[c];
+            c = prefix[c] as usize;
+        }
+        if code == next_code {
+            out.push(first[prev_code]);
+        }
+        if next_code < MAX_CODES {
+            prefix[next_code] = prev_code as u16;
+            suffix[next_code] = first[entry];
+            first[next_code] = first[prev_code];
+            length[next_code] = length[prev_code] + 1;
+            next_code += 1;
+            if next_code == (1 << code_size) && code_size < 12 {
+                code_size += 1;
+            }
+        }
+        prev = Some(code);
+    }
+    out.resize(pixels, 0);
+    Ok(out)
+}
+
+fn deinterlace(src: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let mut dst = vec![0u8; width * height];
+    let mut sy = 0;
+    for (start, step) in [(0, 8), (4, 8), (2, 4), (1, 2)] {
+        let mut y = start;
+        while y < height {
+            dst[y * width..(y + 1) * width].copy_from_slice(&src[sy * width..(sy + 1) * width]);
+            sy += 1;
+            y += step;
+        }
+    }
+    dst
+}
+
+pub fn test(src: &[u8]) -> Option<(usize, usize)> {
+    if src.len() < 10 || (&src[0..6] != b"GIF87a" && &src[0..6] != b"GIF89a") {
+        return None;
+    }
+    let width = from_le16(&src[6..8]) as usize;
+    let height = from_le16(&src[8..10]) as usize;
+    if width == 0 || height == 0 {
+        return None;
+    }
+    Some((width, height))
+}
+
+/// Decodes all frames of a (possibly animated) GIF
+pub fn decode_frames(src: &[u8]) -> Result<Gif, String> {
+    let (width, height) = match test(src) {
+        Some(size) => size,
+        None => return Err("Invalid GIF".to_string())
+    };
+    let mut r = Reader {src, sp: 10};
+    let flags = r.u8() ?;
+    let _background = r.u8() ?;
+    let _aspect = r.u8() ?;
+    let global_palette = if (flags & 0x80) != 0 {
+        r.palette(2 << (flags & 7)) ?
+    }
+    else {
+        Vec::new()
+    };
+
+    let mut gif = Gif {
+        width,
+        height,
+        loop_count: None,
+        frames: Vec::new(),
+    };
+    let mut delay = 0;
+    let mut disposal = GifDisposal::None;
+    let mut transparent = None;
+
+    loop {
+        let block = match r.u8() {
+            Ok(block) => block,
+            // missing trailer, but we might have frames
+            Err(_) if gif.frames.len() > 0 => break,
+            Err(err) => return Err(err)
+        };
+        match block {
+            0x21 => {
+                let label = r.u8() ?;
+                match label {
+                    0xF9 => {
+                        let data = r.sub_blocks() ?;
+                        if data.len() >= 4 {
+                            disposal = match (data[0] >> 2) & 7 {
+                                2 => GifDisposal::Background,
+                                3 => GifDisposal::Previous,
+                                _ => GifDisposal::None
+                            };
+                            delay = from_le16(&data[1..3]);
+                            transparent = if (data[0] & 1) != 0 {Some(data[3] as usize)} else {None};
+                        }
+                    }
+                    0xFF => {
+                        let ident = r.sub_blocks() ?;
+                        if ident.starts_with(b"NETSCAPE2.0") || ident.starts_with(b"ANIMEXTS1.0") {
+                            let data = &ident[11..];
+                            if data.len() >= 3 && data[0] == 1 {
+                                gif.loop_count = Some(from_le16(&data[1..3]));
+                            }
+                        }
+                    }
+                    _ => r.skip_sub_blocks() ?
+                }
+            }
+            0x2C => {
+                let x = r.u16() ? as usize;
+                let y = r.u16() ? as usize;
+                let fw = r.u16() ? as usize;
+                let fh = r.u16() ? as usize;
+                let flags = r.u8() ?;
+                let local_palette = if (flags & 0x80) != 0 {
+                    Some(r.palette(2 << (flags & 7)) ?)
+                }
+                else {
+                    None
+                };
+                let palette = local_palette.as_ref().unwrap_or(&global_palette);
+                let min_code_size = r.u8() ?;
+                let lzw = r.sub_blocks() ?;
+                let mut indices = decode_lzw(&lzw, min_code_size, fw * fh) ?;
+                if (flags & 0x40) != 0 {
+                    indices = deinterlace(&indices, fw, fh);
+                }
+                let data = indices.iter().map( | index | {
+                    let index = *index as usize;
+                    if Some(index) == transparent {
+                        0
+                    }
+                    else {
+                        palette.get(index).cloned().unwrap_or(0xFF000000)
+                    }
+                }).collect();
+                gif.frames.push(GifFrame {
+                    x,
+                    y,
+                    width: fw,
+                    height: fh,
+                    delay,
+                    disposal,
+                    data,
+                });
+                // graphic control extensions only apply to the next image
+                delay = 0;
+                disposal = GifDisposal::None;
+                transparent = None;
+            }
+            0x3B => break,
+            _ => {
+                if gif.frames.len() > 0 {
+                    break;
+                }
+                return Err("Invalid GIF: unknown block".to_string());
+            }
+        }
+    }
+    if gif.frames.len() == 0 {
+        return Err("Invalid GIF: no image data".to_string());
+    }
+    Ok(gif)
+}
+
+/// Decodes the first frame of a GIF
+pub fn decode(src: &[u8]) -> Result<ImageBuffer, String> {
+    let gif = decode_frames(src) ?;
+    let mut image = ImageBuffer::new(gif.width, gif.height);
+    let frame = &gif.frames[0];
+    for y in 0..frame.height.min(gif.height.saturating_sub(frame.y)) {
+        for x in 0..frame.width.min(gif.width.saturating_sub(frame.x)) {
+            image.data[(frame.y + y) * gif.width + frame.x + x] = frame.data[y * frame.width + x];
+        }
+    }
+    Ok(image)
+}
@@ -4,7 +4,11 @@
 
 mod image;
 pub use image::*;
-//pub mod bmp;
+pub mod bmp;
 //pub mod png;
 pub mod jpeg;
+pub mod gif;
+pub mod qoi;
+mod vp8;
+pub mod webp;
 
@@ -0,0 +1,178 @@
+// image_formats::qoi
+
+use crate::ImageBuffer;
+
+const QOI_OP_INDEX: u8 = 0x00;
+const QOI_OP_DIFF: u8 = 0x40;
+const QOI_OP_LUMA: u8 = 0x80;
+const QOI_OP_RUN: u8 = 0xC0;
+const QOI_OP_RGB: u8 = 0xFE;
+const QOI_OP_RGBA: u8 = 0xFF;
+const QOI_MASK_2: u8 = 0xC0;
+
+const HEADER_SIZE: usize = 14;
+const MAX_PIXELS: usize = 400_000_000;
+
+fn from_be32(src: &[u8]) -> u32 {
+    ((src[0] as u32) << 24) | ((src[1] as u32) << 16) | ((src[2] as u32) << 8) | (src[3] as u32)
+}
+
+fn hash(r: u8, g: u8, b: u8, a: u8) -> usize {
+    (r as usize * 3 + g as usize * 5 + b as usize * 7 + a as usize * 11) % 64
+}
+
+pub fn test(src: &[u8]) -> Option<(usize, usize)> {
+    if src.len() < HEADER_SIZE || &src[0..4] != b"qoif" {
+        return None;
+    }
+    let width = from_be32(&src[4..8]) as usize;
+    let height = from_be32(&src[8..12]) as usize;
+    let channels = src[12];
+    if width == 0 || height == 0 || width * height > MAX_PIXELS || (channels != 3 && channels != 4) {
+        return None;
+    }
+    Some((width, height))
+}
+
+pub fn decode(src: &[u8]) -> Result<ImageBuffer, String> {
+    let (width, height) = match test(src) {
+        Some(size) => size,
+        None => return Err("Invalid QOI".to_string())
+    };
+    let mut image = ImageBuffer::new(width, height);
+    let mut index = [[0u8; 4]; 64];
+    let (mut r, mut g, mut b, mut a) = (0u8, 0u8, 0u8, 255u8);
+    let mut run = 0;
+    let mut sp = HEADER_SIZE;
+    for dst in image.data.iter_mut() {
+        if run > 0 {
+            run -= 1;
+        }
+        else {
+            if sp >= src.len() {
+                return Err("Invalid QOI: unexpected end of data".to_string());
+            }
+            let b1 = src[sp];
+            sp += 1;
+            if b1 == QOI_OP_RGB {
+                if sp + 3 > src.len() {
+                    return Err("Invalid QOI: unexpected end of data".to_string());
+                }
+                r = src[sp];
+                g = src[sp + 1];
+                b = src[sp + 2];
+                sp += 3;
+            }
+            else if b1 == QOI_OP_RGBA {
+                if sp + 4 > src.len() {
+                    return Err("Invalid QOI: unexpected end of data".to_string());
+                }
+                r = src[sp];
+                g = src[sp + 1];
+                b = src[sp + 2];
+                a = src[sp + 3];
+                sp += 4;
+            }
+            else {
+                match b1 & QOI_MASK_2 {
+                    QOI_OP_INDEX => {
+                        let c = index[b1 as usize];
+                        r = c[0];
+                        g = c[1];
+                        b = c[2];
+                        a = c[3];
+                    }
+                    QOI_OP_DIFF => {
+                        r = r.wrapping_add(((b1 >> 4) & 3).wrapping_sub(2));
+                        g = g.wrapping_add(((b1 >> 2) & 3).wrapping_sub(2));
+                        b = b.wrapping_add((b1 & 3).wrapping_sub(2));
+                    }
+                    QOI_OP_LUMA => {
+                        if sp >= src.len() {
+                            return Err("Invalid QOI: unexpected end of data".to_string());
+                        }
+                        let b2 = src[sp];
+                        sp += 1;
+                        let vg = (b1 & 0x3F).wrapping_sub(32);
+                        r = r.wrapping_add(vg.wrapping_sub(8).wrapping_add((b2 >> 4) & 0x0F));
+                        g = g.wrapping_add(vg);
+                        b = b.wrapping_add(vg.wrapping_sub(8).wrapping_add(b2 & 0x0F));
+                    }
+                    QOI_OP_RUN => {
+                        run = (b1 & 0x3F) as usize;
+                    }
+                    _ => unreachable!()
+                }
+            }
+            index[hash(r, g, b, a)] = [r, g, b, a];
+        }
+        *dst = ((a as u32) << 24) | ((r as u32) << 16) | ((g as u32) << 8) | (b as u32);
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn qoi(width: u32, height: u32, ops: &[u8]) -> Vec<u8> {
+        let mut src = b"qoif".to_vec();
+        src.extend_from_slice(&width.to_be_bytes());
+        src.extend_from_slice(&height.to_be_bytes());
+        src.extend_from_slice(&[4, 0]);
+        src.extend_from_slice(ops);
+        src.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+        src
+    }
+
+    fn image() -> Vec<u8> {
+        qoi(3, 2, &[
+            QOI_OP_RGBA, 10, 20, 30, 40,
+            // the same pixel once more
+            QOI_OP_RUN,
+            // r + 1, g + 0, b - 1
+            QOI_OP_DIFF | (3 << 4) | (2 << 2) | 1,
+            // g + 8, r and b + 7
+            QOI_OP_LUMA | 40, 0x77,
+            QOI_OP_RGB, 1, 2, 3,
+            QOI_OP_INDEX | hash(10, 20, 30, 40) as u8,
+        ])
+    }
+
+    #[test]
+    fn decode_ops() {
+        let src = image();
+        assert_eq!(test(&src), Some((3, 2)));
+        let image = decode(&src).unwrap();
+        assert_eq!(image.data, vec![
+            0x280A141E, 0x280A141E, 0x280B141D,
+            0x28121C24, 0x28010203, 0x280A141E,
+        ]);
+    }
+
+    #[test]
+    fn reject_invalid_files() {
+        let mut src = image();
+        src[12] = 5;
+        assert!(decode(&src).is_err());
+        assert!(decode(&qoi(0, 2, &[])).is_err());
+        assert!(decode(&qoi(100_000, 100_000, &[])).is_err());
+        // the data ends before the last pixel
+        assert!(decode(&qoi(3, 3, &[QOI_OP_RGB, 1, 2, 3])[..18]).is_err());
+    }
+
+    #[test]
+    fn corrupted_files_dont_panic() {
+        let src = image();
+        for len in 0..src.len() {
+            let _ = decode(&src[..len]);
+        }
+        for i in 0..src.len() {
+            for value in [0x00, 0x01, 0x7F, 0x80, 0xFF, src[i] ^ 0x55] {
+                let mut file = src.clone();
+                file[i] = value;
+                let _ = decode(&file);
+            }
+        }
+    }
+}
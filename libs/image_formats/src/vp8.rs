@@ -0,0 +1,1609 @@
+// image_formats::vp8
+// lossy VP8 key frame decoder for WebP, see RFC 6386
+
+const DC_PRED: u8 = 0;
+const V_PRED: u8 = 1;
+const H_PRED: u8 = 2;
+const TM_PRED: u8 = 3;
+const B_PRED: u8 = 4;
+
+const B_DC_PRED: u8 = 0;
+const B_TM_PRED: u8 = 1;
+const B_VE_PRED: u8 = 2;
+const B_HE_PRED: u8 = 3;
+const B_LD_PRED: u8 = 4;
+const B_RD_PRED: u8 = 5;
+const B_VR_PRED: u8 = 6;
+const B_VL_PRED: u8 = 7;
+const B_HD_PRED: u8 = 8;
+const B_HU_PRED: u8 = 9;
+
+const YMODE_TREE: [i8; 8] = [-(B_PRED as i8), 2, 4, 6, -(DC_PRED as i8), -(V_PRED as i8), -(H_PRED as i8), -(TM_PRED as i8)];
+const YMODE_PROBS: [u8; 4] = [145, 156, 163, 128];
+
+const UV_MODE_TREE: [i8; 6] = [-(DC_PRED as i8), 2, -(V_PRED as i8), 4, -(H_PRED as i8), -(TM_PRED as i8)];
+const UV_MODE_PROBS: [u8; 3] = [142, 114, 183];
+
+const BMODE_TREE: [i8; 18] = [
+    -(B_DC_PRED as i8), 2,
+    -(B_TM_PRED as i8), 4,
+    -(B_VE_PRED as i8), 6,
+    8, 12,
+    -(B_HE_PRED as i8), 10,
+    -(B_RD_PRED as i8), -(B_VR_PRED as i8),
+    -(B_LD_PRED as i8), 14,
+    -(B_VL_PRED as i8), 16,
+    -(B_HD_PRED as i8), -(B_HU_PRED as i8),
+];
+
+const COEFF_BANDS: [usize; 17] = [0, 1, 2, 3, 6, 4, 5, 6, 6, 6, 6, 6, 6, 6, 6, 7, 0];
+const ZIGZAG: [usize; 16] = [0, 1, 4, 8, 5, 2, 3, 6, 9, 12, 13, 10, 7, 11, 14, 15];
+
+const CAT3: [u8; 3] = [173, 148, 140];
+const CAT4: [u8; 4] = [176, 155, 140, 135];
+const CAT5: [u8; 5] = [180, 157, 141, 134, 130];
+const CAT6: [u8; 11] = [254, 254, 243, 230, 196, 177, 153, 140, 133, 130, 129];
+
+// scratch buffer layout for reconstructing a macroblock, borders included
+const BPS: usize = 32;
+const Y_OFF: usize = BPS + 8;
+const UV_OFF: usize = BPS + 8;
+
+/// A decoded VP8 frame, planes are padded to whole macroblocks
+pub struct Vp8Frame {
+    pub width: usize,
+    pub height: usize,
+    pub y_stride: usize,
+    pub uv_stride: usize,
+    pub y: Vec<u8>,
+    pub u: Vec<u8>,
+    pub v: Vec<u8>,
+}
+
+struct BoolDecoder<'a> {
+    src: &'a [u8],
+    sp: usize,
+    value: u32,
+    range: u32,
+    bit_count: u32,
+}
+
+impl<'a> BoolDecoder<'a> {
+    fn new(src: &'a [u8]) -> BoolDecoder<'a> {
+        let mut bd = BoolDecoder {src, sp: 0, value: 0, range: 255, bit_count: 0};
+        bd.value = ((bd.next_byte() as u32) << 8) | (bd.next_byte() as u32);
+        bd
+    }
+
+    fn next_byte(&mut self) -> u8 {
+        // reading past the end yields zeros, like the reference decoder
+        let byte = self.src.get(self.sp).cloned().unwrap_or(0);
+        self.sp += 1;
+        byte
+    }
+
+    fn read_bool(&mut self, prob: u8) -> bool {
+        let split = 1 + (((self.range - 1) * prob as u32) >> 8);
+        let big_split = split << 8;
+        let bit = if self.value >= big_split {
+            self.range -= split;
+            self.value -= big_split;
+            true
+        }
+        else {
+            self.range = split;
+            false
+        };
+        while self.range < 128 {
+            self.value <<= 1;
+            self.range <<= 1;
+            self.bit_count += 1;
+            if self.bit_count == 8 {
+                self.bit_count = 0;
+                self.value |= self.next_byte() as u32;
+            }
+        }
+        bit
+    }
+
+    fn read_flag(&mut self) -> bool {
+        self.read_bool(128)
+    }
+
+    fn read_literal(&mut self, bits: usize) -> u32 {
+        let mut value = 0;
+        for _ in 0..bits {
+            value = (value << 1) | self.read_flag() as u32;
+        }
+        value
+    }
+
+    fn read_signed(&mut self, bits: usize) -> i32 {
+        let value = self.read_literal(bits) as i32;
+        if self.read_flag() {-value} else {value}
+    }
+
+    fn read_optional_signed(&mut self, bits: usize) -> i32 {
+        if self.read_flag() {self.read_signed(bits)} else {0}
+    }
+
+    fn read_tree(&mut self, tree: &[i8], probs: &[u8]) -> u8 {
+        let mut i = 0;
+        loop {
+            let next = tree[i + self.read_bool(probs[i >> 1]) as usize];
+            if next <= 0 {
+                return (-next) as u8;
+            }
+            i = next as usize;
+        }
+    }
+
+    fn overrun(&self) -> bool {
+        self.sp > self.src.len() + 2
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Quant {
+    y1: [i32; 2],
+    y2: [i32; 2],
+    uv: [i32; 2],
+}
+
+#[derive(Clone, Copy, Default)]
+struct FilterInfo {
+    limit: i32,
+    ilevel: i32,
+    hev_thresh: i32,
+    inner: bool,
+}
+
+#[derive(Clone, Copy, Default)]
+struct NonZero {
+    y: [u8; 4],
+    u: [u8; 2],
+    v: [u8; 2],
+    dc: u8,
+}
+
+struct MacroBlock {
+    segment: usize,
+    skip: bool,
+    ymode: u8,
+    bmodes: [u8; 16],
+    uvmode: u8,
+}
+
+fn clip8(v: i32) -> u8 {
+    v.clamp(0, 255) as u8
+}
+
+fn avg2(a: u8, b: u8) -> u8 {
+    ((a as u32 + b as u32 + 1) >> 1) as u8
+}
+
+fn avg3(a: u8, b: u8, c: u8) -> u8 {
+    ((a as u32 + 2 * b as u32 + c as u32 + 2) >> 2) as u8
+}
+
+fn transform_wht(src: &[i32; 16], dst: &mut [i32]) {
+    let mut tmp = [0i32; 16];
+    for i in 0..4 {
+        let a0 = src[i] + src[12 + i];
+        let a1 = src[4 + i] + src[8 + i];
+        let a2 = src[4 + i] - src[8 + i];
+        let a3 = src[i] - src[12 + i];
+        tmp[i] = a0 + a1;
+        tmp[8 + i] = a0 - a1;
+        tmp[4 + i] = a3 + a2;
+        tmp[12 + i] = a3 - a2;
+    }
+    for i in 0..4 {
+        let dc = tmp[i * 4] + 3;
+        let a0 = dc + tmp[3 + i * 4];
+        let a1 = tmp[1 + i * 4] + tmp[2 + i * 4];
+        let a2 = tmp[1 + i * 4] - tmp[2 + i * 4];
+        let a3 = dc - tmp[3 + i * 4];
+        dst[(i * 4) * 16] = ((a0 + a1) >> 3) as i16 as i32;
+        dst[(i * 4 + 1) * 16] = ((a3 + a2) >> 3) as i16 as i32;
+        dst[(i * 4 + 2) * 16] = ((a0 - a1) >> 3) as i16 as i32;
+        dst[(i * 4 + 3) * 16] = ((a3 - a2) >> 3) as i16 as i32;
+    }
+}
+
+fn mul1(a: i32) -> i32 {
+    (a.wrapping_mul(20091) >> 16) + a
+}
+
+fn mul2(a: i32) -> i32 {
+    a.wrapping_mul(35468) >> 16
+}
+
+/// inverse DCT of one 4x4 block, added to the prediction in dst
+fn transform_add(src: &[i32], dst: &mut [u8], off: usize, stride: usize) {
+    let mut tmp = [0i32; 16];
+    for i in 0..4 {
+        let a = src[i] + src[8 + i];
+        let b = src[i] - src[8 + i];
+        let c = mul2(src[4 + i]) - mul1(src[12 + i]);
+        let d = mul1(src[4 + i]) + mul2(src[12 + i]);
+        tmp[i * 4] = a + d;
+        tmp[i * 4 + 1] = b + c;
+        tmp[i * 4 + 2] = b - c;
+        tmp[i * 4 + 3] = a - d;
+    }
+    for i in 0..4 {
+        let dc = tmp[i] + 4;
+        let a = dc + tmp[8 + i];
+        let b = dc - tmp[8 + i];
+        let c = mul2(tmp[4 + i]) - mul1(tmp[12 + i]);
+        let d = mul1(tmp[4 + i]) + mul2(tmp[12 + i]);
+        let row = off + i * stride;
+        for (x, v) in [a + d, b + c, b - c, a - d].iter().enumerate() {
+            dst[row + x] = clip8(dst[row + x] as i32 + (v >> 3));
+        }
+    }
+}
+
+fn predict_dc(buf: &mut [u8], off: usize, size: usize, shift: u32, top: bool, left: bool) {
+    let mut sum = 0u32;
+    if top {
+        for i in 0..size {
+            sum += buf[off - BPS + i] as u32;
+        }
+    }
+    if left {
+        for i in 0..size {
+            sum += buf[off + i * BPS - 1] as u32;
+        }
+    }
+    let dc = match (top, left) {
+        (true, true) => (sum + (1 << (shift - 1))) >> shift,
+        (false, false) => 0x80,
+        _ => (sum + (1 << (shift - 2))) >> (shift - 1),
+    } as u8;
+    for y in 0..size {
+        for x in 0..size {
+            buf[off + y * BPS + x] = dc;
+        }
+    }
+}
+
+fn predict_tm(buf: &mut [u8], off: usize, size: usize) {
+    let top_left = buf[off - BPS - 1] as i32;
+    for y in 0..size {
+        let left = buf[off + y * BPS - 1] as i32;
+        for x in 0..size {
+            let top = buf[off - BPS + x] as i32;
+            buf[off + y * BPS + x] = clip8(top + left - top_left);
+        }
+    }
+}
+
+fn predict_block(buf: &mut [u8], off: usize, size: usize, mode: u8, mb_x: usize, mb_y: usize) {
+    match mode {
+        DC_PRED => predict_dc(buf, off, size, if size == 16 {5} else {4}, mb_y > 0, mb_x > 0),
+        V_PRED => {
+            for y in 0..size {
+                buf.copy_within(off - BPS..off - BPS + size, off + y * BPS);
+            }
+        }
+        H_PRED => {
+            for y in 0..size {
+                let left = buf[off + y * BPS - 1];
+                buf[off + y * BPS..off + y * BPS + size].fill(left);
+            }
+        }
+        _ => predict_tm(buf, off, size),
+    }
+}
+
+fn predict_subblock(buf: &mut [u8], off: usize, mode: u8) {
+    let top = |i: usize| buf[off - BPS + i];
+    let (a, b, c, d) = (top(0), top(1), top(2), top(3));
+    let (e, f, g, h) = (top(4), top(5), top(6), top(7));
+    let x = buf[off - BPS - 1];
+    let (i, j, k, l) = (buf[off - 1], buf[off + BPS - 1], buf[off + 2 * BPS - 1], buf[off + 3 * BPS - 1]);
+    let mut out = [[0u8; 4]; 4];
+    match mode {
+        B_DC_PRED => {
+            let mut dc = 4u32;
+            for v in [a, b, c, d, i, j, k, l] {
+                dc += v as u32;
+            }
+            out = [[(dc >> 3) as u8; 4]; 4];
+        }
+        B_TM_PRED => {
+            for (y, left) in [i, j, k, l].iter().enumerate() {
+                for (xx, top) in [a, b, c, d].iter().enumerate() {
+                    out[y][xx] = clip8(*top as i32 + *left as i32 - x as i32);
+                }
+            }
+        }
+        B_VE_PRED => {
+            out = [[avg3(x, a, b), avg3(a, b, c), avg3(b, c, d), avg3(c, d, e)]; 4];
+        }
+        B_HE_PRED => {
+            out[0] = [avg3(x, i, j); 4];
+            out[1] = [avg3(i, j, k); 4];
+            out[2] = [avg3(j, k, l); 4];
+            out[3] = [avg3(k, l, l); 4];
+        }
+        B_LD_PRED => {
+            let v = [avg3(a, b, c), avg3(b, c, d), avg3(c, d, e), avg3(d, e, f), avg3(e, f, g), avg3(f, g, h), avg3(g, h, h)];
+            for y in 0..4 {
+                for xx in 0..4 {
+                    out[y][xx] = v[xx + y];
+                }
+            }
+        }
+        B_RD_PRED => {
+            let v = [avg3(j, k, l), avg3(i, j, k), avg3(x, i, j), avg3(a, x, i), avg3(b, a, x), avg3(c, b, a), avg3(d, c, b)];
+            for y in 0..4 {
+                for xx in 0..4 {
+                    out[y][xx] = v[3 - y + xx];
+                }
+            }
+        }
+        B_VR_PRED => {
+            out[0] = [avg2(x, a), avg2(a, b), avg2(b, c), avg2(c, d)];
+            out[1] = [avg3(i, x, a), avg3(x, a, b), avg3(a, b, c), avg3(b, c, d)];
+            out[2] = [avg3(j, i, x), avg2(x, a), avg2(a, b), avg2(b, c)];
+            out[3] = [avg3(k, j, i), avg3(i, x, a), avg3(x, a, b), avg3(a, b, c)];
+        }
+        B_VL_PRED => {
+            out[0] = [avg2(a, b), avg2(b, c), avg2(c, d), avg2(d, e)];
+            out[1] = [avg3(a, b, c), avg3(b, c, d), avg3(c, d, e), avg3(d, e, f)];
+            out[2] = [avg2(b, c), avg2(c, d), avg2(d, e), avg3(e, f, g)];
+            out[3] = [avg3(b, c, d), avg3(c, d, e), avg3(d, e, f), avg3(f, g, h)];
+        }
+        B_HD_PRED => {
+            out[0] = [avg2(i, x), avg3(i, x, a), avg3(x, a, b), avg3(a, b, c)];
+            out[1] = [avg2(j, i), avg3(j, i, x), avg2(i, x), avg3(i, x, a)];
+            out[2] = [avg2(k, j), avg3(k, j, i), avg2(j, i), avg3(j, i, x)];
+            out[3] = [avg2(l, k), avg3(l, k, j), avg2(k, j), avg3(k, j, i)];
+        }
+        _ => {
+            out[0] = [avg2(i, j), avg3(i, j, k), avg2(j, k), avg3(j, k, l)];
+            out[1] = [avg2(j, k), avg3(j, k, l), avg2(k, l), avg3(k, l, l)];
+            out[2] = [avg2(k, l), avg3(k, l, l), l, l];
+            out[3] = [l; 4];
+        }
+    }
+    for (y, row) in out.iter().enumerate() {
+        buf[off + y * BPS..off + y * BPS + 4].copy_from_slice(row);
+    }
+}
+
+fn needs_filter(p: &[u8], i: usize, step: usize, t: i32) -> bool {
+    let p1 = p[i - 2 * step] as i32;
+    let p0 = p[i - step] as i32;
+    let q0 = p[i] as i32;
+    let q1 = p[i + step] as i32;
+    4 * (p0 - q0).abs() + (p1 - q1).abs() <= t
+}
+
+fn needs_filter2(p: &[u8], i: usize, step: usize, t: i32, it: i32) -> bool {
+    let v = |k: isize| p[(i as isize + k * step as isize) as usize] as i32;
+    let (p3, p2, p1, p0) = (v(-4), v(-3), v(-2), v(-1));
+    let (q0, q1, q2, q3) = (v(0), v(1), v(2), v(3));
+    if 4 * (p0 - q0).abs() + (p1 - q1).abs() > t {
+        return false;
+    }
+    (p3 - p2).abs() <= it && (p2 - p1).abs() <= it && (p1 - p0).abs() <= it
+        && (q3 - q2).abs() <= it && (q2 - q1).abs() <= it && (q1 - q0).abs() <= it
+}
+
+fn hev(p: &[u8], i: usize, step: usize, thresh: i32) -> bool {
+    let p1 = p[i - 2 * step] as i32;
+    let p0 = p[i - step] as i32;
+    let q0 = p[i] as i32;
+    let q1 = p[i + step] as i32;
+    (p1 - p0).abs() > thresh || (q1 - q0).abs() > thresh
+}
+
+fn sclip1(v: i32) -> i32 {
+    v.clamp(-128, 127)
+}
+
+fn sclip2(v: i32) -> i32 {
+    v.clamp(-16, 15)
+}
+
+fn do_filter2(p: &mut [u8], i: usize, step: usize) {
+    let p1 = p[i - 2 * step] as i32;
+    let p0 = p[i - step] as i32;
+    let q0 = p[i] as i32;
+    let q1 = p[i + step] as i32;
+    let a = 3 * (q0 - p0) + sclip1(p1 - q1);
+    let a1 = sclip2((a + 4) >> 3);
+    let a2 = sclip2((a + 3) >> 3);
+    p[i - step] = clip8(p0 + a2);
+    p[i] = clip8(q0 - a1);
+}
+
+fn do_filter4(p: &mut [u8], i: usize, step: usize) {
+    let p1 = p[i - 2 * step] as i32;
+    let p0 = p[i - step] as i32;
+    let q0 = p[i] as i32;
+    let q1 = p[i + step] as i32;
+    let a = 3 * (q0 - p0);
+    let a1 = sclip2((a + 4) >> 3);
+    let a2 = sclip2((a + 3) >> 3);
+    let a3 = (a1 + 1) >> 1;
+    p[i - 2 * step] = clip8(p1 + a3);
+    p[i - step] = clip8(p0 + a2);
+    p[i] = clip8(q0 - a1);
+    p[i + step] = clip8(q1 - a3);
+}
+
+fn do_filter6(p: &mut [u8], i: usize, step: usize) {
+    let p2 = p[i - 3 * step] as i32;
+    let p1 = p[i - 2 * step] as i32;
+    let p0 = p[i - step] as i32;
+    let q0 = p[i] as i32;
+    let q1 = p[i + step] as i32;
+    let q2 = p[i + 2 * step] as i32;
+    let a = sclip1(3 * (q0 - p0) + sclip1(p1 - q1));
+    let a1 = (27 * a + 63) >> 7;
+    let a2 = (18 * a + 63) >> 7;
+    let a3 = (9 * a + 63) >> 7;
+    p[i - 3 * step] = clip8(p2 + a3);
+    p[i - 2 * step] = clip8(p1 + a2);
+    p[i - step] = clip8(p0 + a1);
+    p[i] = clip8(q0 - a1);
+    p[i + step] = clip8(q1 - a2);
+    p[i + 2 * step] = clip8(q2 - a3);
+}
+
+fn simple_filter(p: &mut [u8], mut i: usize, hstep: usize, vstep: usize, thresh: i32) {
+    let thresh2 = 2 * thresh + 1;
+    for _ in 0..16 {
+        if needs_filter(p, i, hstep, thresh2) {
+            do_filter2(p, i, hstep);
+        }
+        i += vstep;
+    }
+}
+
+fn complex_filter(p: &mut [u8], mut i: usize, hstep: usize, vstep: usize, size: usize, edge: bool, f: &FilterInfo, thresh: i32) {
+    let thresh2 = 2 * thresh + 1;
+    for _ in 0..size {
+        if needs_filter2(p, i, hstep, thresh2, f.ilevel) {
+            if hev(p, i, hstep, f.hev_thresh) {
+                do_filter2(p, i, hstep);
+            }
+            else if edge {
+                do_filter6(p, i, hstep);
+            }
+            else {
+                do_filter4(p, i, hstep);
+            }
+        }
+        i += vstep;
+    }
+}
+
+struct Vp8Decoder<'a> {
+    header: BoolDecoder<'a>,
+    partitions: Vec<BoolDecoder<'a>>,
+    width: usize,
+    height: usize,
+    mb_w: usize,
+    mb_h: usize,
+    update_mb_segment_map: bool,
+    segment_probs: [u8; 3],
+    quant: [Quant; 4],
+    filter_type: u8,
+    filter_info: [[FilterInfo; 2]; 4],
+    coeff_probs: [[[[u8; 11]; 3]; 8]; 4],
+    skip_prob: Option<u8>,
+}
+
+impl<'a> Vp8Decoder<'a> {
+    fn new(src: &'a [u8]) -> Result<Vp8Decoder<'a>, String> {
+        let (width, height) = match test(src) {
+            Some(size) => size,
+            None => return Err("Invalid VP8: not a key frame".to_string())
+        };
+        let first_part_size = ((src[0] as usize) | ((src[1] as usize) << 8) | ((src[2] as usize) << 16)) >> 5;
+        if 10 + first_part_size > src.len() {
+            return Err("Invalid VP8: truncated header partition".to_string());
+        }
+        let mut bd = BoolDecoder::new(&src[10..10 + first_part_size]);
+        let _color_space = bd.read_flag();
+        let _clamping = bd.read_flag();
+
+        let mut use_segment = false;
+        let mut update_mb_segment_map = false;
+        let mut absolute_delta = false;
+        let mut segment_quant = [0i32; 4];
+        let mut segment_filter = [0i32; 4];
+        let mut segment_probs = [255u8; 3];
+        if bd.read_flag() {
+            use_segment = true;
+            update_mb_segment_map = bd.read_flag();
+            if bd.read_flag() {
+                absolute_delta = bd.read_flag();
+                for q in segment_quant.iter_mut() {
+                    *q = bd.read_optional_signed(7);
+                }
+                for f in segment_filter.iter_mut() {
+                    *f = bd.read_optional_signed(6);
+                }
+            }
+            if update_mb_segment_map {
+                for p in segment_probs.iter_mut() {
+                    *p = if bd.read_flag() {bd.read_literal(8) as u8} else {255};
+                }
+            }
+        }
+
+        let simple = bd.read_flag();
+        let level = bd.read_literal(6) as i32;
+        let sharpness = bd.read_literal(3) as i32;
+        let mut ref_lf_delta = 0;
+        let mut mode_lf_delta = 0;
+        if bd.read_flag() && bd.read_flag() {
+            for i in 0..4 {
+                let delta = bd.read_optional_signed(6);
+                if i == 0 {
+                    ref_lf_delta = delta;
+                }
+            }
+            for i in 0..4 {
+                let delta = bd.read_optional_signed(6);
+                if i == 0 {
+                    mode_lf_delta = delta;
+                }
+            }
+        }
+        let use_lf_delta = ref_lf_delta != 0 || mode_lf_delta != 0;
+
+        let num_partitions = 1 << bd.read_literal(2);
+        let mut partitions = Vec::with_capacity(num_partitions);
+        let sizes_start = 10 + first_part_size;
+        let mut part_start = sizes_start + 3 * (num_partitions - 1);
+        if part_start > src.len() {
+            return Err("Invalid VP8: truncated partitions".to_string());
+        }
+        for p in 0..num_partitions {
+            let part_end = if p + 1 < num_partitions {
+                let s = &src[sizes_start + p * 3..];
+                (part_start + ((s[0] as usize) | ((s[1] as usize) << 8) | ((s[2] as usize) << 16))).min(src.len())
+            }
+            else {
+                src.len()
+            };
+            partitions.push(BoolDecoder::new(&src[part_start..part_end]));
+            part_start = part_end;
+        }
+
+        let base_q = bd.read_literal(7) as i32;
+        let y1_dc = bd.read_optional_signed(4);
+        let y2_dc = bd.read_optional_signed(4);
+        let y2_ac = bd.read_optional_signed(4);
+        let uv_dc = bd.read_optional_signed(4);
+        let uv_ac = bd.read_optional_signed(4);
+        let mut quant = [Quant::default(); 4];
+        for (s, quant) in quant.iter_mut().enumerate() {
+            let mut q = base_q;
+            if use_segment {
+                q = segment_quant[s];
+                if !absolute_delta {
+                    q += base_q;
+                }
+            }
+            let dc = |i: i32, max: i32| DC_QUANT[(q + i).clamp(0, max) as usize] as i32;
+            let ac = |i: i32| AC_QUANT[(q + i).clamp(0, 127) as usize] as i32;
+            quant.y1 = [dc(y1_dc, 127), ac(0)];
+            quant.y2 = [dc(y2_dc, 127) * 2, ((ac(y2_ac) * 101581) >> 16).max(8)];
+            quant.uv = [dc(uv_dc, 117), ac(uv_ac)];
+        }
+
+        let _refresh_entropy_probs = bd.read_flag();
+        let mut coeff_probs = COEFF_PROBS;
+        for t in 0..4 {
+            for b in 0..8 {
+                for c in 0..3 {
+                    for p in 0..11 {
+                        if bd.read_bool(COEFF_UPDATE_PROBS[t][b][c][p]) {
+                            coeff_probs[t][b][c][p] = bd.read_literal(8) as u8;
+                        }
+                    }
+                }
+            }
+        }
+        let skip_prob = if bd.read_flag() {Some(bd.read_literal(8) as u8)} else {None};
+
+        let filter_type = if level == 0 {0} else if simple {1} else {2};
+        let mut filter_info = [[FilterInfo::default(); 2]; 4];
+        for (s, info) in filter_info.iter_mut().enumerate() {
+            let mut base_level = level;
+            if use_segment {
+                base_level = segment_filter[s];
+                if !absolute_delta {
+                    base_level += level;
+                }
+            }
+            for (i4x4, info) in info.iter_mut().enumerate() {
+                let mut level = base_level;
+                if use_lf_delta {
+                    level += ref_lf_delta;
+                    if i4x4 == 1 {
+                        level += mode_lf_delta;
+                    }
+                }
+                let level = level.clamp(0, 63);
+                if level > 0 {
+                    let mut ilevel = level;
+                    if sharpness > 0 {
+                        ilevel >>= if sharpness > 4 {2} else {1};
+                        ilevel = ilevel.min(9 - sharpness);
+                    }
+                    let ilevel = ilevel.max(1);
+                    *info = FilterInfo {
+                        limit: 2 * level + ilevel,
+                        ilevel,
+                        hev_thresh: if level >= 40 {2} else if level >= 15 {1} else {0},
+                        inner: i4x4 == 1,
+                    };
+                }
+                else {
+                    info.inner = i4x4 == 1;
+                }
+            }
+        }
+
+        Ok(Vp8Decoder {
+            header: bd,
+            partitions,
+            width,
+            height,
+            mb_w: (width + 15) / 16,
+            mb_h: (height + 15) / 16,
+            update_mb_segment_map,
+            segment_probs,
+            quant,
+            filter_type,
+            filter_info,
+            coeff_probs,
+            skip_prob,
+        })
+    }
+
+    fn parse_macroblock(&mut self, top_modes: &mut [u8], left_modes: &mut [u8; 4]) -> MacroBlock {
+        let bd = &mut self.header;
+        let segment = if self.update_mb_segment_map {
+            let p = &self.segment_probs;
+            if bd.read_bool(p[0]) {2 + bd.read_bool(p[2]) as usize} else {bd.read_bool(p[1]) as usize}
+        }
+        else {
+            0
+        };
+        let skip = match self.skip_prob {
+            Some(prob) => bd.read_bool(prob),
+            None => false
+        };
+        let ymode = bd.read_tree(&YMODE_TREE, &YMODE_PROBS);
+        let mut bmodes = [0u8; 16];
+        if ymode == B_PRED {
+            for y in 0..4 {
+                let mut left = left_modes[y];
+                for x in 0..4 {
+                    let mode = bd.read_tree(&BMODE_TREE, &KF_BMODE_PROBS[top_modes[x] as usize][left as usize]);
+                    bmodes[y * 4 + x] = mode;
+                    top_modes[x] = mode;
+                    left = mode;
+                }
+                left_modes[y] = left;
+            }
+        }
+        else {
+            let mode = match ymode {
+                DC_PRED => B_DC_PRED,
+                V_PRED => B_VE_PRED,
+                H_PRED => B_HE_PRED,
+                _ => B_TM_PRED,
+            };
+            top_modes.fill(mode);
+            left_modes.fill(mode);
+        }
+        let uvmode = bd.read_tree(&UV_MODE_TREE, &UV_MODE_PROBS);
+        MacroBlock {segment, skip, ymode, bmodes, uvmode}
+    }
+
+    fn read_coeffs(bd: &mut BoolDecoder, probs: &[[[u8; 11]; 3]; 8], ctx: usize, dq: [i32; 2], first: usize, out: &mut [i32]) -> usize {
+        let mut n = first;
+        let mut p = &probs[COEFF_BANDS[n]][ctx];
+        while n < 16 {
+            if !bd.read_bool(p[0]) {
+                return n;
+            }
+            while !bd.read_bool(p[1]) {
+                n += 1;
+                if n == 16 {
+                    return 16;
+                }
+                p = &probs[COEFF_BANDS[n]][0];
+            }
+            let (v, next_ctx) = if !bd.read_bool(p[2]) {
+                (1, 1)
+            }
+            else {
+                let v = if !bd.read_bool(p[3]) {
+                    if !bd.read_bool(p[4]) {2} else {3 + bd.read_bool(p[5]) as i32}
+                }
+                else if !bd.read_bool(p[6]) {
+                    if !bd.read_bool(p[7]) {
+                        5 + bd.read_bool(159) as i32
+                    }
+                    else {
+                        7 + 2 * bd.read_bool(165) as i32 + bd.read_bool(145) as i32
+                    }
+                }
+                else {
+                    let bit1 = bd.read_bool(p[8]) as usize;
+                    let bit0 = bd.read_bool(p[9 + bit1]) as usize;
+                    let cat = 2 * bit1 + bit0;
+                    let table: &[u8] = match cat {
+                        0 => &CAT3,
+                        1 => &CAT4,
+                        2 => &CAT5,
+                        _ => &CAT6,
+                    };
+                    let mut v = 0;
+                    for prob in table {
+                        v += v + bd.read_bool(*prob) as i32;
+                    }
+                    v + 3 + (8 << cat)
+                };
+                (v, 2)
+            };
+            let v = if bd.read_flag() {-v} else {v};
+            // coefficients are 16 bit, like in libvpx and libwebp, which keeps the transforms from
+            // overflowing on corrupt data
+            out[ZIGZAG[n]] = (v * dq[(n > 0) as usize]) as i16 as i32;
+            n += 1;
+            p = &probs[COEFF_BANDS[n]][next_ctx];
+        }
+        16
+    }
+
+    /// Reads the residuals of a macroblock, returns true if any coefficient is non-zero
+    fn parse_residuals(&mut self, mb: &MacroBlock, part: usize, top: &mut NonZero, left: &mut NonZero, coeffs: &mut [i32; 384]) -> bool {
+        let q = self.quant[mb.segment];
+        let probs = &self.coeff_probs;
+        let bd = &mut self.partitions[part];
+        let mut non_zero = false;
+        let (first, y_type) = if mb.ymode != B_PRED {
+            let mut dc = [0i32; 16];
+            let ctx = (top.dc + left.dc) as usize;
+            let nz = Self::read_coeffs(bd, &probs[1], ctx, q.y2, 0, &mut dc);
+            top.dc = (nz > 0) as u8;
+            left.dc = top.dc;
+            if nz > 0 {
+                transform_wht(&dc, &mut coeffs[..]);
+            }
+            (1, 0)
+        }
+        else {
+            (0, 3)
+        };
+        for y in 0..4 {
+            let mut l = left.y[y];
+            for x in 0..4 {
+                let ctx = (l + top.y[x]) as usize;
+                let block = &mut coeffs[(y * 4 + x) * 16..(y * 4 + x + 1) * 16];
+                let nz = Self::read_coeffs(bd, &probs[y_type], ctx, q.y1, first, block) > first;
+                l = nz as u8;
+                top.y[x] = l;
+                non_zero |= nz || block[0] != 0;
+            }
+            left.y[y] = l;
+        }
+        for (plane, base) in [(0, 256), (1, 320)] {
+            let (top_nz, left_nz) = if plane == 0 {(&mut top.u, &mut left.u)} else {(&mut top.v, &mut left.v)};
+            for y in 0..2 {
+                let mut l = left_nz[y];
+                for x in 0..2 {
+                    let ctx = (l + top_nz[x]) as usize;
+                    let off = base + (y * 2 + x) * 16;
+                    let nz = Self::read_coeffs(bd, &probs[2], ctx, q.uv, 0, &mut coeffs[off..off + 16]) > 0;
+                    l = nz as u8;
+                    top_nz[x] = l;
+                    non_zero |= nz;
+                }
+                left_nz[y] = l;
+            }
+        }
+        non_zero
+    }
+
+    fn decode(mut self) -> Result<Vp8Frame, String> {
+        let y_stride = self.mb_w * 16;
+        let uv_stride = self.mb_w * 8;
+        let mut frame = Vp8Frame {
+            width: self.width,
+            height: self.height,
+            y_stride,
+            uv_stride,
+            y: vec![0u8; y_stride * self.mb_h * 16],
+            u: vec![0u8; uv_stride * self.mb_h * 8],
+            v: vec![0u8; uv_stride * self.mb_h * 8],
+        };
+        let mut filter_infos = vec![FilterInfo::default(); self.mb_w * self.mb_h];
+
+        let mut top_modes = vec![B_DC_PRED; self.mb_w * 4];
+        let mut top_nz = vec![NonZero::default(); self.mb_w];
+        // unfiltered bottom rows of the previous macroblock row, used for prediction
+        let mut top_y = vec![0u8; y_stride];
+        let mut top_u = vec![0u8; uv_stride];
+        let mut top_v = vec![0u8; uv_stride];
+
+        let mut ybuf = [0u8; BPS * 17];
+        let mut ubuf = [0u8; BPS * 9];
+        let mut vbuf = [0u8; BPS * 9];
+        let mut coeffs = [0i32; 384];
+
+        for mb_y in 0..self.mb_h {
+            let mut left_modes = [B_DC_PRED; 4];
+            let mut left_nz = NonZero::default();
+            for j in 0..16 {
+                ybuf[Y_OFF + j * BPS - 1] = 129;
+            }
+            for j in 0..8 {
+                ubuf[UV_OFF + j * BPS - 1] = 129;
+                vbuf[UV_OFF + j * BPS - 1] = 129;
+            }
+            if mb_y > 0 {
+                ybuf[Y_OFF - BPS - 1] = 129;
+                ubuf[UV_OFF - BPS - 1] = 129;
+                vbuf[UV_OFF - BPS - 1] = 129;
+            }
+            else {
+                ybuf[Y_OFF - BPS - 1..Y_OFF - BPS + 20].fill(127);
+                ubuf[UV_OFF - BPS - 1..UV_OFF - BPS + 8].fill(127);
+                vbuf[UV_OFF - BPS - 1..UV_OFF - BPS + 8].fill(127);
+            }
+            let part = mb_y & (self.partitions.len() - 1);
+
+            for mb_x in 0..self.mb_w {
+                let mb = self.parse_macroblock(&mut top_modes[mb_x * 4..mb_x * 4 + 4], &mut left_modes);
+                coeffs.fill(0);
+                let non_zero = if !mb.skip {
+                    self.parse_residuals(&mb, part, &mut top_nz[mb_x], &mut left_nz, &mut coeffs)
+                }
+                else {
+                    left_nz.y = [0; 4];
+                    left_nz.u = [0; 2];
+                    left_nz.v = [0; 2];
+                    let top = &mut top_nz[mb_x];
+                    top.y = [0; 4];
+                    top.u = [0; 2];
+                    top.v = [0; 2];
+                    if mb.ymode != B_PRED {
+                        left_nz.dc = 0;
+                        top.dc = 0;
+                    }
+                    false
+                };
+                if self.filter_type > 0 {
+                    let mut info = self.filter_info[mb.segment][(mb.ymode == B_PRED) as usize];
+                    info.inner |= non_zero;
+                    filter_infos[mb_y * self.mb_w + mb_x] = info;
+                }
+
+                // rotate in the left samples of the previous macroblock
+                if mb_x > 0 {
+                    for j in 0..17 {
+                        ybuf[j * BPS + 7] = ybuf[j * BPS + 8 + 15];
+                    }
+                    for j in 0..9 {
+                        ubuf[j * BPS + 7] = ubuf[j * BPS + 8 + 7];
+                        vbuf[j * BPS + 7] = vbuf[j * BPS + 8 + 7];
+                    }
+                }
+                if mb_y > 0 {
+                    ybuf[Y_OFF - BPS..Y_OFF - BPS + 16].copy_from_slice(&top_y[mb_x * 16..mb_x * 16 + 16]);
+                    ubuf[UV_OFF - BPS..UV_OFF - BPS + 8].copy_from_slice(&top_u[mb_x * 8..mb_x * 8 + 8]);
+                    vbuf[UV_OFF - BPS..UV_OFF - BPS + 8].copy_from_slice(&top_v[mb_x * 8..mb_x * 8 + 8]);
+                }
+
+                if mb.ymode == B_PRED {
+                    let top_right = Y_OFF - BPS + 16;
+                    if mb_y > 0 {
+                        if mb_x + 1 >= self.mb_w {
+                            ybuf[top_right..top_right + 4].fill(top_y[mb_x * 16 + 15]);
+                        }
+                        else {
+                            ybuf[top_right..top_right + 4].copy_from_slice(&top_y[mb_x * 16 + 16..mb_x * 16 + 20]);
+                        }
+                    }
+                    // blocks on the right edge use the top right pixels of the macroblock
+                    for k in 1..4 {
+                        ybuf.copy_within(top_right..top_right + 4, top_right + k * 4 * BPS);
+                    }
+                    for n in 0..16 {
+                        let off = Y_OFF + (n >> 2) * 4 * BPS + (n & 3) * 4;
+                        predict_subblock(&mut ybuf, off, mb.bmodes[n]);
+                        transform_add(&coeffs[n * 16..n * 16 + 16], &mut ybuf, off, BPS);
+                    }
+                }
+                else {
+                    predict_block(&mut ybuf, Y_OFF, 16, mb.ymode, mb_x, mb_y);
+                    for n in 0..16 {
+                        let off = Y_OFF + (n >> 2) * 4 * BPS + (n & 3) * 4;
+                        transform_add(&coeffs[n * 16..n * 16 + 16], &mut ybuf, off, BPS);
+                    }
+                }
+                predict_block(&mut ubuf, UV_OFF, 8, mb.uvmode, mb_x, mb_y);
+                predict_block(&mut vbuf, UV_OFF, 8, mb.uvmode, mb_x, mb_y);
+                for n in 0..4 {
+                    let off = UV_OFF + (n >> 1) * 4 * BPS + (n & 1) * 4;
+                    transform_add(&coeffs[256 + n * 16..256 + n * 16 + 16], &mut ubuf, off, BPS);
+                    transform_add(&coeffs[320 + n * 16..320 + n * 16 + 16], &mut vbuf, off, BPS);
+                }
+
+                top_y[mb_x * 16..mb_x * 16 + 16].copy_from_slice(&ybuf[Y_OFF + 15 * BPS..Y_OFF + 15 * BPS + 16]);
+                top_u[mb_x * 8..mb_x * 8 + 8].copy_from_slice(&ubuf[UV_OFF + 7 * BPS..UV_OFF + 7 * BPS + 8]);
+                top_v[mb_x * 8..mb_x * 8 + 8].copy_from_slice(&vbuf[UV_OFF + 7 * BPS..UV_OFF + 7 * BPS + 8]);
+                for j in 0..16 {
+                    let dst = (mb_y * 16 + j) * y_stride + mb_x * 16;
+                    frame.y[dst..dst + 16].copy_from_slice(&ybuf[Y_OFF + j * BPS..Y_OFF + j * BPS + 16]);
+                }
+                for j in 0..8 {
+                    let dst = (mb_y * 8 + j) * uv_stride + mb_x * 8;
+                    frame.u[dst..dst + 8].copy_from_slice(&ubuf[UV_OFF + j * BPS..UV_OFF + j * BPS + 8]);
+                    frame.v[dst..dst + 8].copy_from_slice(&vbuf[UV_OFF + j * BPS..UV_OFF + j * BPS + 8]);
+                }
+            }
+            if self.header.overrun() || self.partitions[part].overrun() {
+                return Err("Invalid VP8: unexpected end of data".to_string());
+            }
+        }
+
+        if self.filter_type > 0 {
+            for mb_y in 0..self.mb_h {
+                for mb_x in 0..self.mb_w {
+                    self.filter_macroblock(&mut frame, &filter_infos[mb_y * self.mb_w + mb_x], mb_x, mb_y);
+                }
+            }
+        }
+        Ok(frame)
+    }
+
+    fn filter_macroblock(&self, frame: &mut Vp8Frame, f: &FilterInfo, mb_x: usize, mb_y: usize) {
+        if f.limit == 0 {
+            return;
+        }
+        let ys = frame.y_stride;
+        let uvs = frame.uv_stride;
+        let y = mb_y * 16 * ys + mb_x * 16;
+        let uv = mb_y * 8 * uvs + mb_x * 8;
+        if self.filter_type == 1 {
+            if mb_x > 0 {
+                simple_filter(&mut frame.y, y, 1, ys, f.limit + 4);
+            }
+            if f.inner {
+                for k in 1..4 {
+                    simple_filter(&mut frame.y, y + 4 * k, 1, ys, f.limit);
+                }
+            }
+            if mb_y > 0 {
+                simple_filter(&mut frame.y, y, ys, 1, f.limit + 4);
+            }
+            if f.inner {
+                for k in 1..4 {
+                    simple_filter(&mut frame.y, y + 4 * k * ys, ys, 1, f.limit);
+                }
+            }
+        }
+        else {
+            if mb_x > 0 {
+                complex_filter(&mut frame.y, y, 1, ys, 16, true, f, f.limit + 4);
+                complex_filter(&mut frame.u, uv, 1, uvs, 8, true, f, f.limit + 4);
+                complex_filter(&mut frame.v, uv, 1, uvs, 8, true, f, f.limit + 4);
+            }
+            if f.inner {
+                for k in 1..4 {
+                    complex_filter(&mut frame.y, y + 4 * k, 1, ys, 16, false, f, f.limit);
+                }
+                complex_filter(&mut frame.u, uv + 4, 1, uvs, 8, false, f, f.limit);
+                complex_filter(&mut frame.v, uv + 4, 1, uvs, 8, false, f, f.limit);
+            }
+            if mb_y > 0 {
+                complex_filter(&mut frame.y, y, ys, 1, 16, true, f, f.limit + 4);
+                complex_filter(&mut frame.u, uv, uvs, 1, 8, true, f, f.limit + 4);
+                complex_filter(&mut frame.v, uv, uvs, 1, 8, true, f, f.limit + 4);
+            }
+            if f.inner {
+                for k in 1..4 {
+                    complex_filter(&mut frame.y, y + 4 * k * ys, ys, 1, 16, false, f, f.limit);
+                }
+                complex_filter(&mut frame.u, uv + 4 * uvs, uvs, 1, 8, false, f, f.limit);
+                complex_filter(&mut frame.v, uv + 4 * uvs, uvs, 1, 8, false, f, f.limit);
+            }
+        }
+    }
+}
+
+fn mult_hi(v: i32, coeff: i32) -> i32 {
+    (v * coeff) >> 8
+}
+
+fn yuv_clip(v: i32) -> u32 {
+    if (v & !16383) == 0 {(v >> 6) as u32} else if v < 0 {0} else {255}
+}
+
+fn yuv_to_argb(y: u8, u: u8, v: u8) -> u32 {
+    let (y, u, v) = (y as i32, u as i32, v as i32);
+    let r = yuv_clip(mult_hi(y, 19077) + mult_hi(v, 26149) - 14234);
+    let g = yuv_clip(mult_hi(y, 19077) - mult_hi(u, 6419) - mult_hi(v, 13320) + 8708);
+    let b = yuv_clip(mult_hi(y, 19077) + mult_hi(u, 33050) - 17685);
+    0xFF000000 | (r << 16) | (g << 8) | b
+}
+
+impl Vp8Frame {
+    /// Converts the frame to ARGB with 'fancy' chroma upsampling
+    pub fn to_argb(&self) -> Vec<u32> {
+        let (w, h) = (self.width, self.height);
+        let uv_h = (h + 1) / 2;
+        let mut out = vec![0u32; w * h];
+        for y in 0..h {
+            // the chroma row nearest to this luma row is weighted 3:1 against the other neighbour
+            let (near, far) = if y == 0 {
+                (0, 0)
+            }
+            else if y & 1 == 1 {
+                let k = (y + 1) / 2;
+                (k - 1, if k < uv_h {k} else {k - 1})
+            }
+            else {
+                (y / 2, y / 2 - 1)
+            };
+            self.upsample_row(y, near, far, &mut out[y * w..(y + 1) * w]);
+        }
+        out
+    }
+
+    fn upsample_row(&self, y: usize, near: usize, far: usize, dst: &mut [u32]) {
+        let w = self.width;
+        let luma = &self.y[y * self.y_stride..];
+        let s = self.uv_stride;
+        let sample = |x: usize| {
+            [
+                (self.u[near * s + x] as u32, self.u[far * s + x] as u32),
+                (self.v[near * s + x] as u32, self.v[far * s + x] as u32),
+            ]
+        };
+        let mut left = sample(0);
+        let edge = |c: (u32, u32)| (3 * c.0 + c.1 + 2) >> 2;
+        dst[0] = yuv_to_argb(luma[0], edge(left[0]) as u8, edge(left[1]) as u8);
+        for x in 1..=(w - 1) >> 1 {
+            let cur = sample(x);
+            let mut uv0 = [0u32; 2];
+            let mut uv1 = [0u32; 2];
+            for c in 0..2 {
+                let (nl, fl) = left[c];
+                let (nr, fr) = cur[c];
+                let avg = nl + nr + fl + fr + 8;
+                let diag_a = (avg + 2 * (nr + fl)) >> 3;
+                let diag_b = (avg + 2 * (nl + fr)) >> 3;
+                uv0[c] = (diag_a + nl) >> 1;
+                uv1[c] = (diag_b + nr) >> 1;
+            }
+            dst[2 * x - 1] = yuv_to_argb(luma[2 * x - 1], uv0[0] as u8, uv0[1] as u8);
+            dst[2 * x] = yuv_to_argb(luma[2 * x], uv1[0] as u8, uv1[1] as u8);
+            left = cur;
+        }
+        if w & 1 == 0 {
+            dst[w - 1] = yuv_to_argb(luma[w - 1], edge(left[0]) as u8, edge(left[1]) as u8);
+        }
+    }
+}
+
+/// Checks for a VP8 key frame, returns its size
+pub fn test(src: &[u8]) -> Option<(usize, usize)> {
+    if src.len() < 10 || (src[0] & 1) != 0 || src[3..6] != [0x9D, 0x01, 0x2A] {
+        return None;
+    }
+    let width = (((src[7] as usize) << 8) | (src[6] as usize)) & 0x3FFF;
+    let height = (((src[9] as usize) << 8) | (src[8] as usize)) & 0x3FFF;
+    if width == 0 || height == 0 {
+        return None;
+    }
+    Some((width, height))
+}
+
+/// Decodes the contents of a "VP8 " chunk
+pub fn decode(src: &[u8]) -> Result<Vp8Frame, String> {
+    Vp8Decoder::new(src) ?.decode()
+}
+
+const DC_QUANT: [i16; 128] = [
+    4, 5, 6, 7, 8, 9, 10, 10, 11, 12, 13, 14, 15, 16, 17, 17,
+    18, 19, 20, 20, 21, 21, 22, 22, 23, 23, 24, 25, 25, 26, 27, 28,
+    29, 30, 31, 32, 33, 34, 35, 36, 37, 37, 38, 39, 40, 41, 42, 43,
+    44, 45, 46, 46, 47, 48, 49, 50, 51, 52, 53, 54, 55, 56, 57, 58,
+    59, 60, 61, 62, 63, 64, 65, 66, 67, 68, 69, 70, 71, 72, 73, 74,
+    75, 76, 76, 77, 78, 79, 80, 81, 82, 83, 84, 85, 86, 87, 88, 89,
+    91, 93, 95, 96, 98, 100, 101, 102, 104, 106, 108, 110, 112, 114, 116, 118,
+    122, 124, 126, 128, 130, 132, 134, 136, 138, 140, 143, 145, 148, 151, 154, 157,
+];
+
+const AC_QUANT: [i16; 128] = [
+    4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19,
+    20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35,
+    36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48, 49, 50, 51,
+    52, 53, 54, 55, 56, 57, 58, 60, 62, 64, 66, 68, 70, 72, 74, 76,
+    78, 80, 82, 84, 86, 88, 90, 92, 94, 96, 98, 100, 102, 104, 106, 108,
+    110, 112, 114, 116, 119, 122, 125, 128, 131, 134, 137, 140, 143, 146, 149, 152,
+    155, 158, 161, 164, 167, 170, 173, 177, 181, 185, 189, 193, 197, 201, 205, 209,
+    213, 217, 221, 225, 229, 234, 239, 245, 249, 254, 259, 264, 269, 274, 279, 284,
+];
+
+const COEFF_UPDATE_PROBS: [[[[u8; 11]; 3]; 8]; 4] = [
+    [
+        [
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [176, 246, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [223, 241, 252, 255, 255, 255, 255, 255, 255, 255, 255],
+            [249, 253, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 244, 252, 255, 255, 255, 255, 255, 255, 255, 255],
+            [234, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [253, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 246, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [239, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [254, 255, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 248, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [251, 255, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [251, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [254, 255, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 254, 253, 255, 254, 255, 255, 255, 255, 255, 255],
+            [250, 255, 254, 255, 254, 255, 255, 255, 255, 255, 255],
+            [254, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+    ],
+    [
+        [
+            [217, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [225, 252, 241, 253, 255, 255, 254, 255, 255, 255, 255],
+            [234, 250, 241, 250, 253, 255, 253, 254, 255, 255, 255],
+        ],
+        [
+            [255, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [223, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [238, 253, 254, 254, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 248, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [249, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 253, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [247, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [252, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [253, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 254, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+            [250, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [254, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+    ],
+    [
+        [
+            [186, 251, 250, 255, 255, 255, 255, 255, 255, 255, 255],
+            [234, 251, 244, 254, 255, 255, 255, 255, 255, 255, 255],
+            [251, 251, 243, 253, 254, 255, 254, 255, 255, 255, 255],
+        ],
+        [
+            [255, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [236, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [251, 253, 253, 254, 254, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [254, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [254, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [254, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [254, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+    ],
+    [
+        [
+            [248, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [250, 254, 252, 254, 255, 255, 255, 255, 255, 255, 255],
+            [248, 254, 249, 253, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 253, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+            [246, 253, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+            [252, 254, 251, 254, 254, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 254, 252, 255, 255, 255, 255, 255, 255, 255, 255],
+            [248, 254, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+            [253, 255, 254, 254, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 251, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [245, 251, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [253, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 251, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+            [252, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 252, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [249, 255, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 255, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+            [250, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [254, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+    ],
+];
+
+const COEFF_PROBS: [[[[u8; 11]; 3]; 8]; 4] = [
+    [
+        [
+            [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128],
+            [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128],
+            [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128],
+        ],
+        [
+            [253, 136, 254, 255, 228, 219, 128, 128, 128, 128, 128],
+            [189, 129, 242, 255, 227, 213, 255, 219, 128, 128, 128],
+            [106, 126, 227, 252, 214, 209, 255, 255, 128, 128, 128],
+        ],
+        [
+            [1, 98, 248, 255, 236, 226, 255, 255, 128, 128, 128],
+            [181, 133, 238, 254, 221, 234, 255, 154, 128, 128, 128],
+            [78, 134, 202, 247, 198, 180, 255, 219, 128, 128, 128],
+        ],
+        [
+            [1, 185, 249, 255, 243, 255, 128, 128, 128, 128, 128],
+            [184, 150, 247, 255, 236, 224, 128, 128, 128, 128, 128],
+            [77, 110, 216, 255, 236, 230, 128, 128, 128, 128, 128],
+        ],
+        [
+            [1, 101, 251, 255, 241, 255, 128, 128, 128, 128, 128],
+            [170, 139, 241, 252, 236, 209, 255, 255, 128, 128, 128],
+            [37, 116, 196, 243, 228, 255, 255, 255, 128, 128, 128],
+        ],
+        [
+            [1, 204, 254, 255, 245, 255, 128, 128, 128, 128, 128],
+            [207, 160, 250, 255, 238, 128, 128, 128, 128, 128, 128],
+            [102, 103, 231, 255, 211, 171, 128, 128, 128, 128, 128],
+        ],
+        [
+            [1, 152, 252, 255, 240, 255, 128, 128, 128, 128, 128],
+            [177, 135, 243, 255, 234, 225, 128, 128, 128, 128, 128],
+            [80, 129, 211, 255, 194, 224, 128, 128, 128, 128, 128],
+        ],
+        [
+            [1, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+            [246, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+            [255, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128],
+        ],
+    ],
+    [
+        [
+            [198, 35, 237, 223, 193, 187, 162, 160, 145, 155, 62],
+            [131, 45, 198, 221, 172, 176, 220, 157, 252, 221, 1],
+            [68, 47, 146, 208, 149, 167, 221, 162, 255, 223, 128],
+        ],
+        [
+            [1, 149, 241, 255, 221, 224, 255, 255, 128, 128, 128],
+            [184, 141, 234, 253, 222, 220, 255, 199, 128, 128, 128],
+            [81, 99, 181, 242, 176, 190, 249, 202, 255, 255, 128],
+        ],
+        [
+            [1, 129, 232, 253, 214, 197, 242, 196, 255, 255, 128],
+            [99, 121, 210, 250, 201, 198, 255, 202, 128, 128, 128],
+            [23, 91, 163, 242, 170, 187, 247, 210, 255, 255, 128],
+        ],
+        [
+            [1, 200, 246, 255, 234, 255, 128, 128, 128, 128, 128],
+            [109, 178, 241, 255, 231, 245, 255, 255, 128, 128, 128],
+            [44, 130, 201, 253, 205, 192, 255, 255, 128, 128, 128],
+        ],
+        [
+            [1, 132, 239, 251, 219, 209, 255, 165, 128, 128, 128],
+            [94, 136, 225, 251, 218, 190, 255, 255, 128, 128, 128],
+            [22, 100, 174, 245, 186, 161, 255, 199, 128, 128, 128],
+        ],
+        [
+            [1, 182, 249, 255, 232, 235, 128, 128, 128, 128, 128],
+            [124, 143, 241, 255, 227, 234, 128, 128, 128, 128, 128],
+            [35, 77, 181, 251, 193, 211, 255, 205, 128, 128, 128],
+        ],
+        [
+            [1, 157, 247, 255, 236, 231, 255, 255, 128, 128, 128],
+            [121, 141, 235, 255, 225, 227, 255, 255, 128, 128, 128],
+            [45, 99, 188, 251, 195, 217, 255, 224, 128, 128, 128],
+        ],
+        [
+            [1, 1, 251, 255, 213, 255, 128, 128, 128, 128, 128],
+            [203, 1, 248, 255, 255, 128, 128, 128, 128, 128, 128],
+            [137, 1, 177, 255, 224, 255, 128, 128, 128, 128, 128],
+        ],
+    ],
+    [
+        [
+            [253, 9, 248, 251, 207, 208, 255, 192, 128, 128, 128],
+            [175, 13, 224, 243, 193, 185, 249, 198, 255, 255, 128],
+            [73, 17, 171, 221, 161, 179, 236, 167, 255, 234, 128],
+        ],
+        [
+            [1, 95, 247, 253, 212, 183, 255, 255, 128, 128, 128],
+            [239, 90, 244, 250, 211, 209, 255, 255, 128, 128, 128],
+            [155, 77, 195, 248, 188, 195, 255, 255, 128, 128, 128],
+        ],
+        [
+            [1, 24, 239, 251, 218, 219, 255, 205, 128, 128, 128],
+            [201, 51, 219, 255, 196, 186, 128, 128, 128, 128, 128],
+            [69, 46, 190, 239, 201, 218, 255, 228, 128, 128, 128],
+        ],
+        [
+            [1, 191, 251, 255, 255, 128, 128, 128, 128, 128, 128],
+            [223, 165, 249, 255, 213, 255, 128, 128, 128, 128, 128],
+            [141, 124, 248, 255, 255, 128, 128, 128, 128, 128, 128],
+        ],
+        [
+            [1, 16, 248, 255, 255, 128, 128, 128, 128, 128, 128],
+            [190, 36, 230, 255, 236, 255, 128, 128, 128, 128, 128],
+            [149, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+        ],
+        [
+            [1, 226, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+            [247, 192, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+            [240, 128, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+        ],
+        [
+            [1, 134, 252, 255, 255, 128, 128, 128, 128, 128, 128],
+            [213, 62, 250, 255, 255, 128, 128, 128, 128, 128, 128],
+            [55, 93, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+        ],
+        [
+            [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128],
+            [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128],
+            [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128],
+        ],
+    ],
+    [
+        [
+            [202, 24, 213, 235, 186, 191, 220, 160, 240, 175, 255],
+            [126, 38, 182, 232, 169, 184, 228, 174, 255, 187, 128],
+            [61, 46, 138, 219, 151, 178, 240, 170, 255, 216, 128],
+        ],
+        [
+            [1, 112, 230, 250, 199, 191, 247, 159, 255, 255, 128],
+            [166, 109, 228, 252, 211, 215, 255, 174, 128, 128, 128],
+            [39, 77, 162, 232, 172, 180, 245, 178, 255, 255, 128],
+        ],
+        [
+            [1, 52, 220, 246, 198, 199, 249, 220, 255, 255, 128],
+            [124, 74, 191, 243, 183, 193, 250, 221, 255, 255, 128],
+            [24, 71, 130, 219, 154, 170, 243, 182, 255, 255, 128],
+        ],
+        [
+            [1, 182, 225, 249, 219, 240, 255, 224, 128, 128, 128],
+            [149, 150, 226, 252, 216, 205, 255, 171, 128, 128, 128],
+            [28, 108, 170, 242, 183, 194, 254, 223, 255, 255, 128],
+        ],
+        [
+            [1, 81, 230, 252, 204, 203, 255, 192, 128, 128, 128],
+            [123, 102, 209, 247, 188, 196, 255, 233, 128, 128, 128],
+            [20, 95, 153, 243, 164, 173, 255, 203, 128, 128, 128],
+        ],
+        [
+            [1, 222, 248, 255, 216, 213, 128, 128, 128, 128, 128],
+            [168, 175, 246, 252, 235, 205, 255, 255, 128, 128, 128],
+            [47, 116, 215, 255, 211, 212, 255, 255, 128, 128, 128],
+        ],
+        [
+            [1, 121, 236, 253, 212, 214, 255, 255, 128, 128, 128],
+            [141, 84, 213, 252, 201, 202, 255, 219, 128, 128, 128],
+            [42, 80, 160, 240, 162, 185, 255, 205, 128, 128, 128],
+        ],
+        [
+            [1, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+            [244, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+            [238, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+        ],
+    ],
+];
+
+const KF_BMODE_PROBS: [[[u8; 9]; 10]; 10] = [
+    [
+        [231, 120, 48, 89, 115, 113, 120, 152, 112],
+        [152, 179, 64, 126, 170, 118, 46, 70, 95],
+        [175, 69, 143, 80, 85, 82, 72, 155, 103],
+        [56, 58, 10, 171, 218, 189, 17, 13, 152],
+        [144, 71, 10, 38, 171, 213, 144, 34, 26],
+        [114, 26, 17, 163, 44, 195, 21, 10, 173],
+        [121, 24, 80, 195, 26, 62, 44, 64, 85],
+        [170, 46, 55, 19, 136, 160, 33, 206, 71],
+        [63, 20, 8, 114, 114, 208, 12, 9, 226],
+        [81, 40, 11, 96, 182, 84, 29, 16, 36],
+    ],
+    [
+        [134, 183, 89, 137, 98, 101, 106, 165, 148],
+        [72, 187, 100, 130, 157, 111, 32, 75, 80],
+        [66, 102, 167, 99, 74, 62, 40, 234, 128],
+        [41, 53, 9, 178, 241, 141, 26, 8, 107],
+        [104, 79, 12, 27, 217, 255, 87, 17, 7],
+        [74, 43, 26, 146, 73, 166, 49, 23, 157],
+        [65, 38, 105, 160, 51, 52, 31, 115, 128],
+        [87, 68, 71, 44, 114, 51, 15, 186, 23],
+        [47, 41, 14, 110, 182, 183, 21, 17, 194],
+        [66, 45, 25, 102, 197, 189, 23, 18, 22],
+    ],
+    [
+        [88, 88, 147, 150, 42, 46, 45, 196, 205],
+        [43, 97, 183, 117, 85, 38, 35, 179, 61],
+        [39, 53, 200, 87, 26, 21, 43, 232, 171],
+        [56, 34, 51, 104, 114, 102, 29, 93, 77],
+        [107, 54, 32, 26, 51, 1, 81, 43, 31],
+        [39, 28, 85, 171, 58, 165, 90, 98, 64],
+        [34, 22, 116, 206, 23, 34, 43, 166, 73],
+        [68, 25, 106, 22, 64, 171, 36, 225, 114],
+        [34, 19, 21, 102, 132, 188, 16, 76, 124],
+        [62, 18, 78, 95, 85, 57, 50, 48, 51],
+    ],
+    [
+        [193, 101, 35, 159, 215, 111, 89, 46, 111],
+        [60, 148, 31, 172, 219, 228, 21, 18, 111],
+        [112, 113, 77, 85, 179, 255, 38, 120, 114],
+        [40, 42, 1, 196, 245, 209, 10, 25, 109],
+        [100, 80, 8, 43, 154, 1, 51, 26, 71],
+        [88, 43, 29, 140, 166, 213, 37, 43, 154],
+        [61, 63, 30, 155, 67, 45, 68, 1, 209],
+        [142, 78, 78, 16, 255, 128, 34, 197, 171],
+        [41, 40, 5, 102, 211, 183, 4, 1, 221],
+        [51, 50, 17, 168, 209, 192, 23, 25, 82],
+    ],
+    [
+        [125, 98, 42, 88, 104, 85, 117, 175, 82],
+        [95, 84, 53, 89, 128, 100, 113, 101, 45],
+        [75, 79, 123, 47, 51, 128, 81, 171, 1],
+        [57, 17, 5, 71, 102, 57, 53, 41, 49],
+        [115, 21, 2, 10, 102, 255, 166, 23, 6],
+        [38, 33, 13, 121, 57, 73, 26, 1, 85],
+        [41, 10, 67, 138, 77, 110, 90, 47, 114],
+        [101, 29, 16, 10, 85, 128, 101, 196, 26],
+        [57, 18, 10, 102, 102, 213, 34, 20, 43],
+        [117, 20, 15, 36, 163, 128, 68, 1, 26],
+    ],
+    [
+        [138, 31, 36, 171, 27, 166, 38, 44, 229],
+        [67, 87, 58, 169, 82, 115, 26, 59, 179],
+        [63, 59, 90, 180, 59, 166, 93, 73, 154],
+        [40, 40, 21, 116, 143, 209, 34, 39, 175],
+        [57, 46, 22, 24, 128, 1, 54, 17, 37],
+        [47, 15, 16, 183, 34, 223, 49, 45, 183],
+        [46, 17, 33, 183, 6, 98, 15, 32, 183],
+        [65, 32, 73, 115, 28, 128, 23, 128, 205],
+        [40, 3, 9, 115, 51, 192, 18, 6, 223],
+        [87, 37, 9, 115, 59, 77, 64, 21, 47],
+    ],
+    [
+        [104, 55, 44, 218, 9, 54, 53, 130, 226],
+        [64, 90, 70, 205, 40, 41, 23, 26, 57],
+        [54, 57, 112, 184, 5, 41, 38, 166, 213],
+        [30, 34, 26, 133, 152, 116, 10, 32, 134],
+        [75, 32, 12, 51, 192, 255, 160, 43, 51],
+        [39, 19, 53, 221, 26, 114, 32, 73, 255],
+        [31, 9, 65, 234, 2, 15, 1, 118, 73],
+        [88, 31, 35, 67, 102, 85, 55, 186, 85],
+        [56, 21, 23, 111, 59, 205, 45, 37, 192],
+        [55, 38, 70, 124, 73, 102, 1, 34, 98],
+    ],
+    [
+        [102, 61, 71, 37, 34, 53, 31, 243, 192],
+        [69, 60, 71, 38, 73, 119, 28, 222, 37],
+        [68, 45, 128, 34, 1, 47, 11, 245, 171],
+        [62, 17, 19, 70, 146, 85, 55, 62, 70],
+        [75, 15, 9, 9, 64, 255, 184, 119, 16],
+        [37, 43, 37, 154, 100, 163, 85, 160, 1],
+        [63, 9, 92, 136, 28, 64, 32, 201, 85],
+        [86, 6, 28, 5, 64, 255, 25, 248, 1],
+        [56, 8, 17, 132, 137, 255, 55, 116, 128],
+        [58, 15, 20, 82, 135, 57, 26, 121, 40],
+    ],
+    [
+        [164, 50, 31, 137, 154, 133, 25, 35, 218],
+        [51, 103, 44, 131, 131, 123, 31, 6, 158],
+        [86, 40, 64, 135, 148, 224, 45, 183, 128],
+        [22, 26, 17, 131, 240, 154, 14, 1, 209],
+        [83, 12, 13, 54, 192, 255, 68, 47, 28],
+        [45, 16, 21, 91, 64, 222, 7, 1, 197],
+        [56, 21, 39, 155, 60, 138, 23, 102, 213],
+        [85, 26, 85, 85, 128, 128, 32, 146, 171],
+        [18, 11, 7, 63, 144, 171, 4, 4, 246],
+        [35, 27, 10, 146, 174, 171, 12, 26, 128],
+    ],
+    [
+        [190, 80, 35, 99, 180, 80, 126, 54, 45],
+        [85, 126, 47, 87, 176, 51, 41, 20, 32],
+        [101, 75, 128, 139, 118, 146, 116, 128, 85],
+        [56, 41, 15, 176, 236, 85, 37, 9, 62],
+        [146, 36, 19, 30, 171, 255, 97, 27, 20],
+        [71, 30, 17, 119, 118, 255, 17, 18, 138],
+        [101, 38, 60, 138, 55, 70, 43, 26, 142],
+        [138, 45, 61, 62, 219, 1, 81, 188, 64],
+        [32, 41, 20, 117, 151, 142, 20, 21, 163],
+        [112, 19, 12, 61, 195, 128, 48, 4, 24],
+    ],
+];
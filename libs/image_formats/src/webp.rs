@@ -0,0 +1,931 @@
+// image_formats::webp
+// RIFF container with lossy (VP8), lossless (VP8L), alpha and animation support
+
+use crate::ImageBuffer;
+use crate::vp8;
+
+const MAX_PIXELS: usize = 400_000_000;
+
+const NUM_LITERAL_CODES: usize = 256;
+const NUM_LENGTH_CODES: usize = 24;
+const NUM_DISTANCE_CODES: usize = 40;
+const CODE_LENGTH_CODES: usize = 19;
+const CODE_LENGTH_ORDER: [usize; CODE_LENGTH_CODES] = [17, 18, 0, 1, 2, 3, 4, 5, 16, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+const MAX_CODE_LENGTH: usize = 15;
+const TABLE_BITS: usize = 8;
+
+const PREDICTOR_TRANSFORM: u32 = 0;
+const CROSS_COLOR_TRANSFORM: u32 = 1;
+const SUBTRACT_GREEN_TRANSFORM: u32 = 2;
+const COLOR_INDEXING_TRANSFORM: u32 = 3;
+
+fn from_le16(src: &[u8]) -> u16 {
+    ((src[1] as u16) << 8) | (src[0] as u16)
+}
+
+fn from_le24(src: &[u8]) -> u32 {
+    ((src[2] as u32) << 16) | ((src[1] as u32) << 8) | (src[0] as u32)
+}
+
+fn from_le32(src: &[u8]) -> u32 {
+    ((src[3] as u32) << 24) | ((src[2] as u32) << 16) | ((src[1] as u32) << 8) | (src[0] as u32)
+}
+
+/// A single frame of an animated WebP, positioned within the canvas.
+pub struct WebpFrame {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    /// duration in milliseconds
+    pub duration: u32,
+    /// alpha-blend the frame onto the canvas, otherwise it replaces the area it covers
+    pub blend: bool,
+    /// clear the frame area to transparent before the next frame
+    pub dispose: bool,
+    pub data: Vec<u32>,
+}
+
+pub struct Webp {
+    pub width: usize,
+    pub height: usize,
+    /// 0 loops forever
+    pub loop_count: u16,
+    pub frames: Vec<WebpFrame>,
+}
+
+// VP8L bitstream, least significant bit first
+
+struct BitReader<'a> {
+    src: &'a [u8],
+    sp: usize,
+    bits: u64,
+    nbits: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(src: &'a [u8]) -> BitReader<'a> {
+        BitReader {src, sp: 0, bits: 0, nbits: 0}
+    }
+
+    fn fill(&mut self) {
+        while self.nbits <= 56 {
+            let byte = self.src.get(self.sp).cloned().unwrap_or(0);
+            self.bits |= (byte as u64) << self.nbits;
+            self.nbits += 8;
+            self.sp += 1;
+        }
+    }
+
+    fn peek(&mut self, n: usize) -> u32 {
+        if self.nbits < n {
+            self.fill();
+        }
+        (self.bits & ((1u64 << n) - 1)) as u32
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.bits >>= n;
+        self.nbits -= n;
+    }
+
+    fn read(&mut self, n: usize) -> u32 {
+        let value = self.peek(n);
+        self.consume(n);
+        value
+    }
+
+    fn overrun(&self) -> bool {
+        self.sp * 8 > self.src.len() * 8 + self.nbits
+    }
+}
+
+struct HuffmanCode {
+    /// symbol of a code without any bits
+    single: Option<u16>,
+    /// (symbol, length) indexed by the next TABLE_BITS bits, length 0 means the code is longer
+    table: Vec<(u16, u8)>,
+    counts: [u16; MAX_CODE_LENGTH + 1],
+    symbols: Vec<u16>,
+}
+
+impl HuffmanCode {
+    fn from_lengths(lengths: &[u8]) -> Result<HuffmanCode, String> {
+        let mut counts = [0u16; MAX_CODE_LENGTH + 1];
+        for len in lengths {
+            counts[*len as usize] += 1;
+        }
+        counts[0] = 0;
+        let used = lengths.iter().filter( | len | **len > 0).count();
+        if used == 0 {
+            return Err("Invalid WebP: empty Huffman code".to_string());
+        }
+        if used == 1 {
+            let symbol = lengths.iter().position( | len | *len > 0).unwrap() as u16;
+            return Ok(HuffmanCode {single: Some(symbol), table: Vec::new(), counts, symbols: Vec::new()});
+        }
+        let mut left = 1i32;
+        for len in 1..=MAX_CODE_LENGTH {
+            left = (left << 1) - counts[len] as i32;
+            if left < 0 {
+                return Err("Invalid WebP: oversubscribed Huffman code".to_string());
+            }
+        }
+        if left != 0 {
+            return Err("Invalid WebP: incomplete Huffman code".to_string());
+        }
+        let mut offsets = [0usize; MAX_CODE_LENGTH + 2];
+        for len in 1..=MAX_CODE_LENGTH {
+            offsets[len + 1] = offsets[len] + counts[len] as usize;
+        }
+        let mut symbols = vec![0u16; used];
+        for (symbol, len) in lengths.iter().enumerate() {
+            if *len > 0 {
+                symbols[offsets[*len as usize]] = symbol as u16;
+                offsets[*len as usize] += 1;
+            }
+        }
+        // fill the lookup table for the short codes
+        let mut table = vec![(0u16, 0u8); 1 << TABLE_BITS];
+        let mut code = 0usize;
+        let mut index = 0;
+        for len in 1..=MAX_CODE_LENGTH {
+            for _ in 0..counts[len] {
+                if len <= TABLE_BITS {
+                    let mut reversed = 0;
+                    for i in 0..len {
+                        reversed |= ((code >> i) & 1) << (len - 1 - i);
+                    }
+                    let mut i = reversed;
+                    while i < table.len() {
+                        table[i] = (symbols[index], len as u8);
+                        i += 1 << len;
+                    }
+                }
+                code += 1;
+                index += 1;
+            }
+            code <<= 1;
+        }
+        Ok(HuffmanCode {single: None, table, counts, symbols})
+    }
+
+    fn read_symbol(&self, br: &mut BitReader) -> u16 {
+        if let Some(symbol) = self.single {
+            return symbol;
+        }
+        let (symbol, len) = self.table[br.peek(TABLE_BITS) as usize];
+        if len > 0 {
+            br.consume(len as usize);
+            return symbol;
+        }
+        // canonical decoding, one bit at a time
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0i32;
+        for len in 1..=MAX_CODE_LENGTH {
+            code |= br.read(1) as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return self.symbols[(index + code - first) as usize];
+            }
+            index += count;
+            first += count;
+            first <<= 1;
+            code <<= 1;
+        }
+        0
+    }
+}
+
+fn read_huffman_code(br: &mut BitReader, alphabet_size: usize) -> Result<HuffmanCode, String> {
+    let mut lengths = vec![0u8; alphabet_size];
+    if br.read(1) == 1 {
+        // simple code with one or two symbols
+        let num_symbols = br.read(1) as usize + 1;
+        let first_bits = if br.read(1) == 1 {8} else {1};
+        let first = br.read(first_bits) as usize;
+        if first >= alphabet_size {
+            return Err("Invalid WebP: bad Huffman symbol".to_string());
+        }
+        lengths[first] = 1;
+        if num_symbols == 2 {
+            let second = br.read(8) as usize;
+            if second >= alphabet_size {
+                return Err("Invalid WebP: bad Huffman symbol".to_string());
+            }
+            lengths[second] = 1;
+        }
+        return HuffmanCode::from_lengths(&lengths);
+    }
+
+    let num_codes = br.read(4) as usize + 4;
+    let mut code_length_lengths = [0u8; CODE_LENGTH_CODES];
+    for i in 0..num_codes {
+        code_length_lengths[CODE_LENGTH_ORDER[i]] = br.read(3) as u8;
+    }
+    let code_length_code = HuffmanCode::from_lengths(&code_length_lengths) ?;
+
+    let max_symbol = if br.read(1) == 1 {
+        let length_bits = 2 + 2 * br.read(3) as usize;
+        let max_symbol = 2 + br.read(length_bits) as usize;
+        if max_symbol > alphabet_size {
+            return Err("Invalid WebP: bad Huffman max symbol".to_string());
+        }
+        max_symbol
+    }
+    else {
+        alphabet_size
+    };
+
+    let mut prev_len = 8;
+    let mut symbol = 0;
+    let mut remaining = max_symbol;
+    while symbol < alphabet_size && remaining > 0 {
+        remaining -= 1;
+        let code = code_length_code.read_symbol(br) as usize;
+        if code < 16 {
+            lengths[symbol] = code as u8;
+            symbol += 1;
+            if code != 0 {
+                prev_len = code as u8;
+            }
+        }
+        else {
+            let (extra_bits, offset, value) = match code {
+                16 => (2, 3, prev_len),
+                17 => (3, 3, 0),
+                _ => (7, 11, 0)
+            };
+            let repeat = br.read(extra_bits) as usize + offset;
+            if symbol + repeat > alphabet_size {
+                return Err("Invalid WebP: bad Huffman code lengths".to_string());
+            }
+            lengths[symbol..symbol + repeat].fill(value);
+            symbol += repeat;
+        }
+    }
+    HuffmanCode::from_lengths(&lengths)
+}
+
+fn div_round_up(value: usize, bits: u32) -> usize {
+    (value + (1 << bits) - 1) >> bits
+}
+
+fn prefix_value(br: &mut BitReader, prefix: usize) -> usize {
+    if prefix < 4 {
+        return prefix + 1;
+    }
+    let extra_bits = (prefix - 2) >> 1;
+    let offset = (2 + (prefix & 1)) << extra_bits;
+    offset + br.read(extra_bits) as usize + 1
+}
+
+fn distance(xsize: usize, code: usize) -> usize {
+    if code > 120 {
+        return code - 120;
+    }
+    let (dx, dy) = DISTANCE_MAP[code - 1];
+    let dist = dx as isize + dy as isize * xsize as isize;
+    dist.max(1) as usize
+}
+
+fn decode_image_data(br: &mut BitReader, xsize: usize, ysize: usize, is_main: bool) -> Result<Vec<u32>, String> {
+    let cache_bits = if br.read(1) == 1 {
+        let bits = br.read(4) as usize;
+        if !(1..=11).contains(&bits) {
+            return Err("Invalid WebP: bad color cache size".to_string());
+        }
+        bits
+    }
+    else {
+        0
+    };
+    let cache_size = if cache_bits > 0 {1 << cache_bits} else {0};
+
+    let mut meta_bits = 0;
+    let mut meta_width = 0;
+    let mut meta_image = Vec::new();
+    if is_main && br.read(1) == 1 {
+        meta_bits = br.read(3) + 2;
+        meta_width = div_round_up(xsize, meta_bits);
+        meta_image = decode_image_data(br, meta_width, div_round_up(ysize, meta_bits), false) ?;
+        for code in meta_image.iter_mut() {
+            *code = (*code >> 8) & 0xFFFF;
+        }
+    }
+    let num_groups = meta_image.iter().max().map_or(1, | max | *max as usize + 1);
+
+    let mut groups = Vec::with_capacity(num_groups);
+    for _ in 0..num_groups {
+        groups.push([
+            read_huffman_code(br, NUM_LITERAL_CODES + NUM_LENGTH_CODES + cache_size) ?,
+            read_huffman_code(br, NUM_LITERAL_CODES) ?,
+            read_huffman_code(br, NUM_LITERAL_CODES) ?,
+            read_huffman_code(br, NUM_LITERAL_CODES) ?,
+            read_huffman_code(br, NUM_DISTANCE_CODES) ?,
+        ]);
+    }
+
+    let total = xsize * ysize;
+    let mut data = vec![0u32; total];
+    let mut cache = vec![0u32; cache_size];
+    let cache_shift = 32 - cache_bits;
+    let mut cached = 0;
+    let mut pos = 0;
+    while pos < total {
+        let group = if meta_image.is_empty() {
+            &groups[0]
+        }
+        else {
+            let (x, y) = (pos % xsize, pos / xsize);
+            &groups[meta_image[(y >> meta_bits) * meta_width + (x >> meta_bits)] as usize]
+        };
+        let green = group[0].read_symbol(br) as usize;
+        if green < NUM_LITERAL_CODES {
+            let red = group[1].read_symbol(br) as u32;
+            let blue = group[2].read_symbol(br) as u32;
+            let alpha = group[3].read_symbol(br) as u32;
+            data[pos] = (alpha << 24) | (red << 16) | ((green as u32) << 8) | blue;
+            pos += 1;
+        }
+        else if green < NUM_LITERAL_CODES + NUM_LENGTH_CODES {
+            let length = prefix_value(br, green - NUM_LITERAL_CODES);
+            let dist_symbol = group[4].read_symbol(br) as usize;
+            let dist = distance(xsize, prefix_value(br, dist_symbol));
+            if dist > pos || pos + length > total {
+                return Err("Invalid WebP: bad backward reference".to_string());
+            }
+            for i in pos..pos + length {
+                data[i] = data[i - dist];
+            }
+            pos += length;
+        }
+        else {
+            let index = green - NUM_LITERAL_CODES - NUM_LENGTH_CODES;
+            // the cache only holds pixels decoded so far
+            while cached < pos {
+                let argb = data[cached];
+                cache[(0x1E35A7BDu32.wrapping_mul(argb) >> cache_shift) as usize] = argb;
+                cached += 1;
+            }
+            data[pos] = cache[index];
+            pos += 1;
+        }
+        if br.overrun() {
+            return Err("Invalid WebP: unexpected end of data".to_string());
+        }
+    }
+    Ok(data)
+}
+
+fn add_pixels(a: u32, b: u32) -> u32 {
+    let ag = (a & 0xFF00FF00).wrapping_add(b & 0xFF00FF00) & 0xFF00FF00;
+    let rb = (a & 0x00FF00FF).wrapping_add(b & 0x00FF00FF) & 0x00FF00FF;
+    ag | rb
+}
+
+fn average2(a: u32, b: u32) -> u32 {
+    (((a ^ b) & 0xFEFEFEFE) >> 1) + (a & b)
+}
+
+fn channel(p: u32, shift: u32) -> i32 {
+    ((p >> shift) & 0xFF) as i32
+}
+
+fn select(l: u32, t: u32, tl: u32) -> u32 {
+    let mut pl = 0;
+    let mut pt = 0;
+    for shift in [24, 16, 8, 0] {
+        let p = channel(l, shift) + channel(t, shift) - channel(tl, shift);
+        pl += (p - channel(l, shift)).abs();
+        pt += (p - channel(t, shift)).abs();
+    }
+    if pl < pt {l} else {t}
+}
+
+fn clamp_add_subtract_full(a: u32, b: u32, c: u32) -> u32 {
+    let mut out = 0;
+    for shift in [24, 16, 8, 0] {
+        let v = (channel(a, shift) + channel(b, shift) - channel(c, shift)).clamp(0, 255);
+        out |= (v as u32) << shift;
+    }
+    out
+}
+
+fn clamp_add_subtract_half(a: u32, b: u32) -> u32 {
+    let mut out = 0;
+    for shift in [24, 16, 8, 0] {
+        let (a, b) = (channel(a, shift), channel(b, shift));
+        let v = (a + (a - b) / 2).clamp(0, 255);
+        out |= (v as u32) << shift;
+    }
+    out
+}
+
+fn predict(mode: u32, l: u32, t: u32, tr: u32, tl: u32) -> u32 {
+    match mode {
+        1 => l,
+        2 => t,
+        3 => tr,
+        4 => tl,
+        5 => average2(average2(l, tr), t),
+        6 => average2(l, tl),
+        7 => average2(l, t),
+        8 => average2(tl, t),
+        9 => average2(t, tr),
+        10 => average2(average2(l, tl), average2(t, tr)),
+        11 => select(l, t, tl),
+        12 => clamp_add_subtract_full(l, t, tl),
+        13 => clamp_add_subtract_half(average2(l, t), tl),
+        _ => 0xFF000000,
+    }
+}
+
+fn color_delta(t: u32, c: u32) -> i32 {
+    ((t as u8 as i8) as i32 * (c as u8 as i8) as i32) >> 5
+}
+
+enum Transform {
+    Predictor {bits: u32, width: usize, data: Vec<u32>},
+    CrossColor {bits: u32, width: usize, data: Vec<u32>},
+    SubtractGreen,
+    ColorIndexing {bits: u32, width: usize, palette: Vec<u32>},
+}
+
+impl Transform {
+    fn apply(&self, data: Vec<u32>, height: usize) -> Vec<u32> {
+        match self {
+            Transform::Predictor {bits, width, data: modes} => {
+                let width = *width;
+                let block_width = div_round_up(width, *bits);
+                let mut data = data;
+                for y in 0..height {
+                    for x in 0..width {
+                        let i = y * width + x;
+                        let pred = if y == 0 {
+                            if x == 0 {0xFF000000} else {data[i - 1]}
+                        }
+                        else if x == 0 {
+                            data[i - width]
+                        }
+                        else {
+                            let mode = (modes[(y >> bits) * block_width + (x >> bits)] >> 8) & 0xF;
+                            predict(mode, data[i - 1], data[i - width], data[i - width + 1], data[i - width - 1])
+                        };
+                        data[i] = add_pixels(data[i], pred);
+                    }
+                }
+                data
+            }
+            Transform::CrossColor {bits, width, data: elements} => {
+                let width = *width;
+                let block_width = div_round_up(width, *bits);
+                let mut data = data;
+                for y in 0..height {
+                    for x in 0..width {
+                        let element = elements[(y >> bits) * block_width + (x >> bits)];
+                        let p = data[y * width + x];
+                        let green = (p >> 8) & 0xFF;
+                        let mut red = (p >> 16) & 0xFF;
+                        let mut blue = p & 0xFF;
+                        red = (red as i32 + color_delta(element, green)) as u32 & 0xFF;
+                        blue = (blue as i32 + color_delta(element >> 8, green) + color_delta(element >> 16, red)) as u32 & 0xFF;
+                        data[y * width + x] = (p & 0xFF00FF00) | (red << 16) | blue;
+                    }
+                }
+                data
+            }
+            Transform::SubtractGreen => {
+                data.into_iter().map( | p | {
+                    let green = (p >> 8) & 0xFF;
+                    let rb = ((p & 0x00FF00FF) + ((green << 16) | green)) & 0x00FF00FF;
+                    (p & 0xFF00FF00) | rb
+                }).collect()
+            }
+            Transform::ColorIndexing {bits, width, palette} => {
+                let width = *width;
+                let packed_width = div_round_up(width, *bits);
+                let pixels_per_byte = 1 << bits;
+                let bits_per_pixel = 8 >> bits;
+                let mask = (1 << bits_per_pixel) - 1;
+                let mut out = vec![0u32; width * height];
+                for y in 0..height {
+                    for x in 0..width {
+                        let packed = (data[y * packed_width + x / pixels_per_byte] >> 8) & 0xFF;
+                        let index = (packed >> ((x % pixels_per_byte) * bits_per_pixel)) & mask;
+                        out[y * width + x] = palette.get(index as usize).cloned().unwrap_or(0);
+                    }
+                }
+                out
+            }
+        }
+    }
+}
+
+/// Decodes a VP8L image stream (without the header) of the given size
+fn decode_vp8l_stream(br: &mut BitReader, width: usize, height: usize) -> Result<Vec<u32>, String> {
+    let mut transforms = Vec::new();
+    let mut seen = [false; 4];
+    let mut xsize = width;
+    while br.read(1) == 1 {
+        let kind = br.read(2);
+        if seen[kind as usize] {
+            return Err("Invalid WebP: repeated transform".to_string());
+        }
+        seen[kind as usize] = true;
+        match kind {
+            PREDICTOR_TRANSFORM | CROSS_COLOR_TRANSFORM => {
+                let bits = br.read(3) + 2;
+                let data = decode_image_data(br, div_round_up(xsize, bits), div_round_up(height, bits), false) ?;
+                transforms.push(if kind == PREDICTOR_TRANSFORM {
+                    Transform::Predictor {bits, width: xsize, data}
+                }
+                else {
+                    Transform::CrossColor {bits, width: xsize, data}
+                });
+            }
+            SUBTRACT_GREEN_TRANSFORM => transforms.push(Transform::SubtractGreen),
+            COLOR_INDEXING_TRANSFORM => {
+                let size = br.read(8) as usize + 1;
+                let mut palette = decode_image_data(br, size, 1, false) ?;
+                for i in 1..size {
+                    palette[i] = add_pixels(palette[i], palette[i - 1]);
+                }
+                let bits = if size <= 2 {3} else if size <= 4 {2} else if size <= 16 {1} else {0};
+                transforms.push(Transform::ColorIndexing {bits, width: xsize, palette});
+                xsize = div_round_up(xsize, bits);
+            }
+            _ => unreachable!()
+        }
+    }
+    let mut data = decode_image_data(br, xsize, height, true) ?;
+    for transform in transforms.iter().rev() {
+        data = transform.apply(data, height);
+    }
+    Ok(data)
+}
+
+fn vp8l_size(src: &[u8]) -> Option<(usize, usize)> {
+    if src.len() < 5 || src[0] != 0x2F {
+        return None;
+    }
+    let bits = from_le32(&src[1..5]);
+    if (bits >> 29) != 0 {
+        return None;
+    }
+    Some(((bits & 0x3FFF) as usize + 1, ((bits >> 14) & 0x3FFF) as usize + 1))
+}
+
+fn decode_vp8l(src: &[u8]) -> Result<(usize, usize, Vec<u32>), String> {
+    let (width, height) = match vp8l_size(src) {
+        Some(size) => size,
+        None => return Err("Invalid WebP: bad VP8L header".to_string())
+    };
+    let mut br = BitReader::new(&src[5..]);
+    let data = decode_vp8l_stream(&mut br, width, height) ?;
+    Ok((width, height, data))
+}
+
+fn decode_alpha(src: &[u8], width: usize, height: usize) -> Result<Vec<u8>, String> {
+    if src.is_empty() {
+        return Err("Invalid WebP: empty alpha chunk".to_string());
+    }
+    let compression = src[0] & 3;
+    let filtering = (src[0] >> 2) & 3;
+    let mut alpha = match compression {
+        0 => {
+            if src.len() < 1 + width * height {
+                return Err("Invalid WebP: truncated alpha".to_string());
+            }
+            src[1..1 + width * height].to_vec()
+        }
+        1 => {
+            let mut br = BitReader::new(&src[1..]);
+            decode_vp8l_stream(&mut br, width, height) ?.iter().map( | p | (p >> 8) as u8).collect()
+        }
+        _ => return Err("Invalid WebP: unknown alpha compression".to_string())
+    };
+    if filtering != 0 {
+        for y in 0..height {
+            for x in 0..width {
+                let i = y * width + x;
+                let pred = if x == 0 && y == 0 {
+                    0
+                }
+                else if y == 0 {
+                    alpha[i - 1]
+                }
+                else if x == 0 {
+                    alpha[i - width]
+                }
+                else {
+                    match filtering {
+                        1 => alpha[i - 1],
+                        2 => alpha[i - width],
+                        _ => (alpha[i - 1] as i32 + alpha[i - width] as i32 - alpha[i - width - 1] as i32).clamp(0, 255) as u8
+                    }
+                };
+                alpha[i] = alpha[i].wrapping_add(pred);
+            }
+        }
+    }
+    Ok(alpha)
+}
+
+struct Chunk<'a> {
+    id: &'a [u8],
+    data: &'a [u8],
+}
+
+fn chunks(src: &[u8]) -> Vec<Chunk<'_>> {
+    let mut chunks = Vec::new();
+    let mut sp = 0;
+    while sp + 8 <= src.len() {
+        let size = from_le32(&src[sp + 4..sp + 8]) as usize;
+        let end = (sp + 8).saturating_add(size).min(src.len());
+        chunks.push(Chunk {id: &src[sp..sp + 4], data: &src[sp + 8..end]});
+        // chunks are padded to an even size
+        sp = end.saturating_add(size & 1);
+    }
+    chunks
+}
+
+/// Decodes a frame made of an optional ALPH chunk followed by VP8 or VP8L
+fn decode_image_chunks(chunks: &[Chunk]) -> Result<(usize, usize, Vec<u32>), String> {
+    let mut alpha = None;
+    for chunk in chunks {
+        match chunk.id {
+            b"ALPH" => alpha = Some(chunk.data),
+            b"VP8L" => return decode_vp8l(chunk.data),
+            b"VP8 " => {
+                let frame = vp8::decode(chunk.data) ?;
+                let (width, height) = (frame.width, frame.height);
+                let mut data = frame.to_argb();
+                if let Some(alpha) = alpha {
+                    let alpha = decode_alpha(alpha, width, height) ?;
+                    for (p, a) in data.iter_mut().zip(alpha.iter()) {
+                        *p = (*p & 0x00FFFFFF) | ((*a as u32) << 24);
+                    }
+                }
+                return Ok((width, height, data));
+            }
+            _ => {}
+        }
+    }
+    Err("Invalid WebP: no image data".to_string())
+}
+
+pub fn test(src: &[u8]) -> Option<(usize, usize)> {
+    if src.len() < 30 || &src[0..4] != b"RIFF" || &src[8..12] != b"WEBP" {
+        return None;
+    }
+    let data = &src[20..];
+    let size = match &src[12..16] {
+        b"VP8 " => vp8::test(data),
+        b"VP8L" => vp8l_size(data),
+        b"VP8X" => Some((from_le24(&data[4..7]) as usize + 1, from_le24(&data[7..10]) as usize + 1)),
+        _ => None
+    };
+    match size {
+        Some((width, height)) if width * height <= MAX_PIXELS => Some((width, height)),
+        _ => None
+    }
+}
+
+/// Decodes all frames of a (possibly animated) WebP
+pub fn decode_frames(src: &[u8]) -> Result<Webp, String> {
+    let (width, height) = match test(src) {
+        Some(size) => size,
+        None => return Err("Invalid WebP".to_string())
+    };
+    let riff_size = from_le32(&src[4..8]) as usize;
+    let chunks = chunks(&src[12..riff_size.saturating_add(8).clamp(12, src.len())]);
+    let mut webp = Webp {
+        width,
+        height,
+        loop_count: 0,
+        frames: Vec::new(),
+    };
+    let animated = chunks.iter().any( | chunk | chunk.id == b"ANMF");
+    if !animated {
+        let (fw, fh, data) = decode_image_chunks(&chunks) ?;
+        webp.frames.push(WebpFrame {
+            x: 0,
+            y: 0,
+            width: fw,
+            height: fh,
+            duration: 0,
+            blend: false,
+            dispose: false,
+            data,
+        });
+        return Ok(webp);
+    }
+    for chunk in chunks.iter() {
+        match chunk.id {
+            b"ANIM" if chunk.data.len() >= 6 => {
+                webp.loop_count = from_le16(&chunk.data[4..6]);
+            }
+            b"ANMF" if chunk.data.len() >= 16 => {
+                let d = chunk.data;
+                let flags = d[15];
+                let (fw, fh, data) = decode_image_chunks(&self::chunks(&d[16..])) ?;
+                webp.frames.push(WebpFrame {
+                    x: from_le24(&d[0..3]) as usize * 2,
+                    y: from_le24(&d[3..6]) as usize * 2,
+                    width: fw,
+                    height: fh,
+                    duration: from_le24(&d[12..15]),
+                    blend: (flags & 2) == 0,
+                    dispose: (flags & 1) != 0,
+                    data,
+                });
+            }
+            _ => {}
+        }
+    }
+    if webp.frames.is_empty() {
+        return Err("Invalid WebP: no frames".to_string());
+    }
+    Ok(webp)
+}
+
+/// Decodes a WebP, for animations only the first frame
+pub fn decode(src: &[u8]) -> Result<ImageBuffer, String> {
+    let webp = decode_frames(src) ?;
+    let mut image = ImageBuffer::new(webp.width, webp.height);
+    let frame = &webp.frames[0];
+    for y in 0..frame.height.min(webp.height.saturating_sub(frame.y)) {
+        for x in 0..frame.width.min(webp.width.saturating_sub(frame.x)) {
+            image.data[(frame.y + y) * webp.width + frame.x + x] = frame.data[y * frame.width + x];
+        }
+    }
+    Ok(image)
+}
+
+const DISTANCE_MAP: [(i8, i8); 120] = [
+    (0, 1), (1, 0), (1, 1), (-1, 1), (0, 2), (2, 0), (1, 2), (-1, 2),
+    (2, 1), (-2, 1), (2, 2), (-2, 2), (0, 3), (3, 0), (1, 3), (-1, 3),
+    (3, 1), (-3, 1), (2, 3), (-2, 3), (3, 2), (-3, 2), (0, 4), (4, 0),
+    (1, 4), (-1, 4), (4, 1), (-4, 1), (3, 3), (-3, 3), (2, 4), (-2, 4),
+    (4, 2), (-4, 2), (0, 5), (3, 4), (-3, 4), (4, 3), (-4, 3), (5, 0),
+    (1, 5), (-1, 5), (5, 1), (-5, 1), (2, 5), (-2, 5), (5, 2), (-5, 2),
+    (4, 4), (-4, 4), (3, 5), (-3, 5), (5, 3), (-5, 3), (0, 6), (6, 0),
+    (1, 6), (-1, 6), (6, 1), (-6, 1), (2, 6), (-2, 6), (6, 2), (-6, 2),
+    (4, 5), (-4, 5), (5, 4), (-5, 4), (3, 6), (-3, 6), (6, 3), (-6, 3),
+    (0, 7), (7, 0), (1, 7), (-1, 7), (5, 5), (-5, 5), (7, 1), (-7, 1),
+    (4, 6), (-4, 6), (6, 4), (-6, 4), (2, 7), (-2, 7), (7, 2), (-7, 2),
+    (3, 7), (-3, 7), (7, 3), (-7, 3), (5, 6), (-5, 6), (6, 5), (-6, 5),
+    (8, 0), (4, 7), (-4, 7), (7, 4), (-7, 4), (8, 1), (8, 2), (6, 6),
+    (-6, 6), (8, 3), (5, 7), (-5, 7), (7, 5), (-7, 5), (8, 4), (6, 7),
+    (-6, 7), (7, 6), (-7, 6), (8, 5), (7, 7), (-7, 7), (8, 6), (8, 7),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes bits the way BitReader reads them, least significant first.
+    #[derive(Default)]
+    struct BitWriter {
+        bytes: Vec<u8>,
+        nbits: usize,
+    }
+
+    impl BitWriter {
+        fn write(&mut self, value: u32, n: usize) {
+            for i in 0..n {
+                if (self.nbits & 7) == 0 {
+                    self.bytes.push(0);
+                }
+                *self.bytes.last_mut().unwrap() |= (((value >> i) & 1) as u8) << (self.nbits % 8);
+                self.nbits += 1;
+            }
+        }
+
+        // A simple Huffman code with one or two 8 bit symbols
+        fn write_simple_code(&mut self, symbols: &[u32]) {
+            self.write(1, 1);
+            self.write(symbols.len() as u32 - 1, 1);
+            self.write(1, 1);
+            for symbol in symbols {
+                self.write(*symbol, 8);
+            }
+        }
+    }
+
+    fn riff(id: &[u8], data: &[u8]) -> Vec<u8> {
+        let padded_len = data.len() + (data.len() & 1);
+        let mut src = b"RIFF".to_vec();
+        src.extend_from_slice(&(12 + padded_len as u32).to_le_bytes());
+        src.extend_from_slice(b"WEBP");
+        src.extend_from_slice(id);
+        src.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        src.extend_from_slice(data);
+        src.resize(20 + padded_len, 0);
+        src
+    }
+
+    // A 4x2 lossless image where green is 10 or 200, picked by one bit per pixel
+    fn lossless(subtract_green: bool) -> Vec<u8> {
+        let mut bw = BitWriter::default();
+        bw.write(0x2F, 8);
+        bw.write(4 - 1, 14);
+        bw.write(2 - 1, 14);
+        bw.write(1, 1);
+        bw.write(0, 3);
+        if subtract_green {
+            bw.write(1, 1);
+            bw.write(SUBTRACT_GREEN_TRANSFORM, 2);
+        }
+        bw.write(0, 1);
+        // no color cache, no meta codes
+        bw.write(0, 1);
+        bw.write(0, 1);
+        bw.write_simple_code(&[10, 200]);
+        bw.write_simple_code(&[50]);
+        bw.write_simple_code(&[60]);
+        bw.write_simple_code(&[255]);
+        bw.write_simple_code(&[0]);
+        for bit in [0, 1, 1, 0, 1, 0, 0, 0] {
+            bw.write(bit, 1);
+        }
+        riff(b"VP8L", &bw.bytes)
+    }
+
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u8 {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (self.0 >> 56) as u8
+        }
+    }
+
+    fn decode_corrupted(src: &[u8]) {
+        for len in 0..src.len() {
+            let _ = decode(&src[..len]);
+        }
+        for i in 0..src.len() {
+            for value in [0x00, 0x01, 0x7F, 0x80, 0xFF, src[i] ^ 0x55] {
+                let mut file = src.to_vec();
+                file[i] = value;
+                let _ = decode(&file);
+            }
+        }
+    }
+
+    #[test]
+    fn decode_lossless() {
+        let src = lossless(false);
+        assert_eq!(test(&src), Some((4, 2)));
+        let image = decode(&src).unwrap();
+        let (a, b) = (0xFF320A3C, 0xFF32C83C);
+        assert_eq!(image.data, vec![a, b, b, a, b, a, a, a]);
+        // green is added back to red and blue
+        let image = decode(&lossless(true)).unwrap();
+        let (a, b) = (0xFF3C0A46, 0xFFFAC804);
+        assert_eq!(image.data, vec![a, b, b, a, b, a, a, a]);
+    }
+
+    #[test]
+    fn reject_invalid_files() {
+        let mut src = lossless(false);
+        src[8..12].copy_from_slice(b"WEBQ");
+        assert!(decode(&src).is_err());
+        let mut src = lossless(false);
+        src[20] = 0x2E;
+        assert!(decode(&src).is_err());
+        assert!(decode(&riff(b"VP8 ", &[0x10, 0x02, 0x00, 0x9D, 0x01, 0x2A, 0, 0, 16, 0])).is_err());
+        assert!(decode(&riff(b"ABCD", &[0; 16])).is_err());
+    }
+
+    #[test]
+    fn corrupted_files_dont_panic() {
+        decode_corrupted(&lossless(false));
+        decode_corrupted(&lossless(true));
+    }
+
+    #[test]
+    fn random_data_doesnt_panic() {
+        let mut rng = Rng(1);
+        for i in 0..500 {
+            let mut body: Vec<u8> = (0..64 + i % 200).map( | _ | rng.next()).collect();
+            if i % 2 == 0 {
+                // a VP8 key frame of 17x33 pixels, with a first partition inside the data
+                let first_partition = (rng.next() as usize) % body.len();
+                let tag = (first_partition << 5) | 0x10;
+                let mut frame = vec![tag as u8, (tag >> 8) as u8, (tag >> 16) as u8, 0x9D, 0x01, 0x2A, 17, 0, 33, 0];
+                frame.append(&mut body);
+                let _ = decode(&riff(b"VP8 ", &frame));
+            }
+            else {
+                // a VP8L image of 17x33 pixels
+                let mut image = vec![0x2F, 16, 0x40, 0x08, 0x00];
+                image.append(&mut body);
+                let _ = decode(&riff(b"VP8L", &image));
+            }
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use proc_macro::{TokenStream};
 use makepad_micro_proc_macro::*;
+use crate::serde_attrs::*;
 
 pub fn derive_ser_bin_impl(input: TokenStream) -> TokenStream {
     let mut parser = TokenParser::new(input);
@@ -24,7 +25,8 @@ pub fn derive_ser_bin_impl(input: TokenStream) -> TokenStream {
                 }
             }
             else if let Some(fields) = parser.eat_all_struct_fields(){ 
-                for field in fields{
+                let fields = match serde_fields(fields, None, false) {Ok(f) => f, Err(e) => return e};
+                for field in fields.iter().filter( | f | !f.skip){
                     tb.add("self .").ident(&field.name).add(". ser_bin ( s ) ;");
                 }
             }
@@ -65,12 +67,17 @@ pub fn derive_ser_bin_impl(input: TokenStream) -> TokenStream {
                         tb.add("}");
                     }
                     else if let Some(fields) = parser.eat_all_struct_fields(){ // named variant
+                        let fields = match serde_fields(fields, None, false) {Ok(f) => f, Err(e) => return e};
                         tb.add("Self ::").ident(&variant).add("{");
                         for field in fields.iter(){
-                            tb.ident(&field.name).add(",");
+                            tb.ident(&field.name);
+                            if field.skip {
+                                tb.add(": _");
+                            }
+                            tb.add(",");
                         }
                         tb.add("} => {").suf_u16(index).add(". ser_bin ( s ) ;");
-                        for field in fields{
+                        for field in fields.iter().filter( | f | !f.skip){
                             tb.ident(&field.name).add(". ser_bin ( s ) ;");
                         }
                         tb.add("}");
@@ -122,9 +129,16 @@ pub fn derive_de_bin_impl(input: TokenStream) -> TokenStream {
                 tb.add(")");
             }
             else if let Some(fields) = parser.eat_all_struct_fields(){ 
+                let fields = match serde_fields(fields, None, false) {Ok(f) => f, Err(e) => return e};
                 tb.add("{");
                 for field in fields{
-                    tb.ident(&field.name).add(": DeBin :: de_bin ( o , d ) ? ,");
+                    tb.ident(&field.name).add(":");
+                    if field.skip {
+                        tb.stream(Some(field.default_value())).add(",");
+                    }
+                    else {
+                        tb.add("DeBin :: de_bin ( o , d ) ? ,");
+                    }
                 }
                 tb.add("}");
             }
@@ -165,9 +179,16 @@ pub fn derive_de_bin_impl(input: TokenStream) -> TokenStream {
                         tb.add(")");
                     }
                     else if let Some(fields) = parser.eat_all_struct_fields(){ // named variant
+                        let fields = match serde_fields(fields, None, false) {Ok(f) => f, Err(e) => return e};
                         tb.ident(&variant).add("{");
                         for field in fields.iter(){
-                            tb.ident(&field.name).add(": DeBin :: de_bin ( o , d ) ? ,");
+                            tb.ident(&field.name).add(":");
+                            if field.skip {
+                                tb.stream(Some(field.default_value())).add(",");
+                            }
+                            else {
+                                tb.add("DeBin :: de_bin ( o , d ) ? ,");
+                            }
                         }
                         tb.add("}");
                     }
@@ -1,16 +1,160 @@
 use proc_macro::TokenStream;
 use makepad_micro_proc_macro::*;
+use crate::serde_attrs::*;
+
+// writes the fields of a struct or named variant, expects a mut first:bool for the commas
+fn ser_json_fields(tb: &mut TokenBuilder, fields: &[SerdeField], on_self: bool) {
+    for field in fields {
+        if field.skip {
+            continue;
+        }
+        if field.flatten {
+            tb.add("s . flatten ( d + 1 ,");
+            if on_self {
+                tb.add("& self .");
+            }
+            tb.ident(&field.name).add(", & mut first ) ;");
+        }
+        else if field.is_option() {
+            tb.add("if let Some ( t ) = ");
+            if on_self {
+                tb.add("& self .");
+            }
+            tb.ident(&field.name).add("{");
+            tb.add("s . field_sep ( & mut first ) ;");
+            tb.add("s . field ( d + 1 ,").string(&field.key).add(") ;");
+            tb.add("t . ser_json ( d + 1 , s ) ;");
+            tb.add("} ;");
+        }
+        else {
+            tb.add("s . field_sep ( & mut first ) ;");
+            tb.add("s . field ( d + 1 ,").string(&field.key).add(" ) ;");
+            if on_self {
+                tb.add("self .");
+            }
+            tb.ident(&field.name).add(". ser_json ( d + 1 , s ) ;");
+        }
+    }
+}
+
+fn ser_json_object(tb: &mut TokenBuilder, fields: &[SerdeField], on_self: bool) {
+    tb.add("s . st_pre ( ) ;");
+    if fields.iter().any( | f | !f.skip) {
+        tb.add("let mut first = true ;");
+        ser_json_fields(tb, fields, on_self);
+    }
+    tb.add("s . st_post ( d ) ;");
+}
+
+fn ser_json_tuple(tb: &mut TokenBuilder, len: usize) {
+    tb.add("s . out . push (").chr('[').add(") ;");
+    for i in 0..len {
+        tb.ident(&format!("n{}", i)).add(". ser_json ( d , s ) ;");
+        if i != len - 1 {
+            tb.add("s . out . push (").chr(',').add(") ;");
+        }
+    }
+    tb.add("s . out . push (").chr(']').add(") ;");
+}
+
+// adds where a value sits to the errors it returns, key is an object key or an index,
+// outer the key the containing object or array itself sits under
+fn err_path(tb: &mut TokenBuilder, key: Result<&str, usize>, outer: Option<&str>) {
+    tb.add(". map_err ( | e | e .");
+    match key {
+        Ok(key) => tb.add("in_key (").string(key).add(")"),
+        Err(index) => tb.add("in_index (").unsuf_usize(index).add(")")
+    };
+    if let Some(outer) = outer {
+        tb.add(". in_key (").string(outer).add(")");
+    }
+    tb.add(")");
+}
+
+// the field initializers of a struct or named variant, reading the _name options the parse left behind
+fn de_json_field_inits(tb: &mut TokenBuilder, fields: &[SerdeField]) {
+    for field in fields {
+        tb.ident(&field.name).add(":");
+        if field.skip {
+            tb.stream(Some(field.default_value())).add(",");
+        }
+        else if field.flatten {
+            tb.add("s . de_members ( & _flat ) ? ,");
+        }
+        else {
+            tb.add("if let Some ( t ) =").ident(&format!("_{}", field.name)).add("{ t } else {");
+            if let Some(default) = &field.default {
+                tb.stream(Some(default.clone()));
+            }
+            else if field.is_option() {
+                tb.add("None");
+            }
+            else {
+                tb.add("return Err ( s . err_nf (").string(&field.key).add(") )");
+            }
+            tb.add("} ,");
+        }
+    }
+}
+
+// parses an object from the token stream into the _name options
+fn de_json_fields(tb: &mut TokenBuilder, fields: &[SerdeField], outer: Option<&str>) {
+    let has_flatten = fields.iter().any( | f | f.flatten);
+    tb.add("s . curly_open ( i ) ? ;");
+    for field in fields.iter().filter( | f | !f.skip && !f.flatten) {
+        tb.add("let mut").ident(&format!("_{}", field.name)).add("= None ;");
+    }
+    if has_flatten {
+        tb.add("let mut _flat = Vec :: new ( ) ;");
+    }
+    tb.add("while let Some ( _ ) = s . next_str ( ) {");
+    tb.add("match s . strbuf . as_ref ( ) {");
+    for field in fields.iter().filter( | f | !f.skip && !f.flatten) {
+        // the colon reads the value token, so it can fail on the value too
+        tb.string(&field.key).add("=> { s . next_colon ( i )");
+        err_path(tb, Ok(&field.key), outer);
+        tb.add("? ;");
+        tb.ident(&format!("_{}", field.name)).add("= Some ( DeJson :: de_json ( s , i )");
+        err_path(tb, Ok(&field.key), outer);
+        tb.add("? ) ; } ,");
+    }
+    if has_flatten {
+        tb.add("_ => { let key = s . as_string ( ) ? ; s . next_colon ( i ) ? ;");
+        tb.add("let mut value = String :: new ( ) ; s . capture_value ( i , & mut value ) ? ;");
+        tb.add("_flat . push ( ( key , value ) ) ; }");
+    }
+    else {
+        tb.add("_ => return std :: result :: Result :: Err ( s . err_exp ( & s . strbuf ) )");
+    }
+    tb.add("} ; s . eat_comma_curly ( i ) ? ;");
+    tb.add("} ; s . curly_close ( i ) ? ;");
+}
+
+// reads the _name options out of members captured with capture_members, leftovers go to #[flatten]
+fn de_json_members(tb: &mut TokenBuilder, fields: &[SerdeField], outer: Option<&str>) {
+    for field in fields.iter().filter( | f | !f.skip && !f.flatten) {
+        tb.add("let").ident(&format!("_{}", field.name)).add("= match s . take_member ( & mut _flat ,").string(&field.key).add(") {");
+        tb.add("Some ( v ) => Some ( s . de_captured ( & v )");
+        err_path(tb, Ok(&field.key), outer);
+        tb.add("? ) , None => None } ;");
+    }
+    if !fields.iter().any( | f | f.flatten) {
+        tb.add("if let Some ( ( key , _ ) ) = _flat . first ( ) {");
+        tb.add("return std :: result :: Result :: Err ( s . err_exp ( key ) ) }");
+    }
+}
 
 pub fn derive_ser_json_impl(input: TokenStream) -> TokenStream {
 
     let mut parser = TokenParser::new(input);
     let mut tb = TokenBuilder::new();
-    
-    parser.eat_attributes();
+
+    let main_attrs = parser.eat_attributes();
+    let container = match container_attrs(&main_attrs) {Ok(c) => c, Err(e) => return e};
     parser.eat_ident("pub");
-    if parser.eat_ident("struct"){
-        if let Some(name) = parser.eat_any_ident(){
-            
+    if parser.eat_ident("struct") {
+        if let Some(name) = parser.eat_any_ident() {
+
             let generic = parser.eat_generic();
             let types = parser.eat_all_types();
             let where_clause = parser.eat_where_clause(Some("SerJson"));
@@ -18,136 +162,149 @@ pub fn derive_ser_json_impl(input: TokenStream) -> TokenStream {
             tb.add("impl").stream(generic.clone());
             tb.add("SerJson for").ident(&name).stream(generic).stream(where_clause);
             tb.add("{ fn ser_json ( & self , d : usize , s : & mut SerJsonState ) {");
-            
-            if let Some(types) = types{
+
+            if let Some(types) = types {
                 tb.add("s . out . push (").chr('[').add(") ;");
-                for i in 0..types.len(){
-                     tb.add("self .").unsuf_usize(i).add(". ser_json ( d , s ) ;");
-                     if i != types.len() - 1{
-                         tb.add("s . out . push (").chr(',').add(") ;");
-                     }
+                for i in 0..types.len() {
+                    tb.add("self .").unsuf_usize(i).add(". ser_json ( d , s ) ;");
+                    if i != types.len() - 1 {
+                        tb.add("s . out . push (").chr(',').add(") ;");
+                    }
                 }
                 tb.add("s . out . push (").chr(']').add(") ;");
             }
-            else if let Some(fields) = parser.eat_all_struct_fields(){
-                let fields_len = fields.len();
-                tb.add("s . st_pre ( ) ;");
+            else if let Some(fields) = parser.eat_all_struct_fields() {
                 // named struct
-                for (i, field) in fields.into_iter().enumerate() {
-                    if field.ty.into_iter().next().unwrap().to_string() == "Option"{
-                        tb.add("if let Some ( t ) = ").add("& self .").ident(&field.name).add("{");
-                        tb.add("s . field ( d + 1 ,").string(&field.name).add(") ;");
-                        tb.add("t . ser_json ( d + 1 , s ) ;");
-                        if i != fields_len - 1 {
-                            tb.add("s . conl ( ) ;");
-                        }
-                        tb.add("} ;");
-                    }
-                    else{
-                        tb.add("s . field ( d + 1 ,").string(&field.name).add(" ) ;");
-                        tb.add("self .").ident(&field.name).add(". ser_json ( d + 1 , s ) ;");
-                        if i != fields_len - 1 {
-                            tb.add("s . conl ( ) ;");
-                        }
-                    }
-                }
-                tb.add("s . st_post ( d ) ;");
+                let fields = match serde_fields(fields, Some(&container), true) {Ok(f) => f, Err(e) => return e};
+                ser_json_object(&mut tb, &fields, true);
             }
-            else{
+            else {
                 return parser.unexpected()
             }
             tb.add("} } ;");
             return tb.end();
         }
     }
-    else if parser.eat_ident("enum"){
-        if let Some(name) = parser.eat_any_ident(){
+    else if parser.eat_ident("enum") {
+        if let Some(name) = parser.eat_any_ident() {
             let generic = parser.eat_generic();
             let where_clause = parser.eat_where_clause(Some("SerJson"));
 
             tb.add("impl").stream(generic.clone());
             tb.add("SerJson for").ident(&name).stream(generic).stream(where_clause);
             tb.add("{ fn ser_json ( & self , d : usize , s : & mut SerJsonState ) {");
-            tb.add("s . out . push (").chr('{').add(") ;");
             tb.add("match self {");
-            
-            if !parser.open_brace(){
+
+            if !parser.open_brace() {
                 return parser.unexpected()
             }
 
-            while !parser.eat_eot(){
+            while !parser.eat_eot() {
                 // parse ident
-                parser.eat_attributes();
-                if let Some(variant) = parser.eat_any_ident(){
-                    if let Some(types) = parser.eat_all_types(){
-                        
+                let attrs = parser.eat_attributes();
+                if let Some(variant) = parser.eat_any_ident() {
+                    let key = match variant_key(&variant, &attrs, &container) {Ok(k) => k, Err(e) => return e};
+                    if let Some(types) = parser.eat_all_types() {
+
                         tb.add("Self ::").ident(&variant).add("(");
-                        for i in 0..types.len(){
+                        for i in 0..types.len() {
                             tb.ident(&format!("n{}", i)).add(",");
                         }
                         tb.add(") => {");
-                        tb.add("s . label (").string(&variant).add(") ;");
-                        tb.add("s . out . push (").chr(':').add(") ;");
-                        tb.add("s . out . push (").chr('[').add(") ;");
-                        
-                        for i in 0..types.len(){
-                            tb.ident(&format!("n{}", i)).add(". ser_json ( d , s ) ;");
-                            if i != types.len() - 1{
-                                tb.add("s . out . push (").chr(',').add(") ;");
+                        if let Some(tag) = &container.tag {
+                            tb.add("s . st_pre ( ) ;");
+                            tb.add("s . field ( d + 1 ,").string(tag).add(") ;");
+                            tb.add("s . label (").string(&key).add(") ;");
+                            if let Some(content) = &container.content {
+                                tb.add("s . conl ( ) ;");
+                                tb.add("s . field ( d + 1 ,").string(content).add(") ;");
+                                if types.len() == 1 {
+                                    tb.add("n0 . ser_json ( d + 1 , s ) ;");
+                                }
+                                else {
+                                    ser_json_tuple(&mut tb, types.len());
+                                }
                             }
+                            else if types.len() == 1 {
+                                tb.add("let mut first = false ;");
+                                tb.add("s . flatten ( d + 1 , n0 , & mut first ) ;");
+                            }
+                            else {
+                                return error("Internally tagged enums only support unit, named and single field variants")
+                            }
+                            tb.add("s . st_post ( d ) ;");
+                        }
+                        else {
+                            tb.add("s . out . push (").chr('{').add(") ;");
+                            tb.add("s . label (").string(&key).add(") ;");
+                            tb.add("s . out . push (").chr(':').add(") ;");
+                            ser_json_tuple(&mut tb, types.len());
+                            tb.add("s . out . push (").chr('}').add(") ;");
                         }
-                        tb.add("s . out . push (").chr(']').add(") ;");
                         tb.add("}");
                     }
-                    else if let Some(fields) = parser.eat_all_struct_fields(){ // named variant
+                    else if let Some(fields) = parser.eat_all_struct_fields() { // named variant
+                        let fields = match serde_fields(fields, None, true) {Ok(f) => f, Err(e) => return e};
                         tb.add("Self ::").ident(&variant).add("{");
-                        for field in fields.iter(){
-                            tb.ident(&field.name).add(",");
+                        for field in fields.iter() {
+                            tb.ident(&field.name);
+                            if field.skip {
+                                tb.add(": _");
+                            }
+                            tb.add(",");
                         }
                         tb.add("} => {");
-                        
-                        tb.add("s . label (").string(&variant).add(") ;");
-                        tb.add("s . out . push (").chr(':').add(") ;");
-                        tb.add("s . st_pre ( ) ;");
 
-                        let fields_len = fields.len();
-                        for (i, field) in fields.into_iter().enumerate() {
-                            let field_strip = if let Some(v) = field.name.strip_prefix("_"){v}else{&field.name};
-                            if field.ty.into_iter().next().unwrap().to_string() == "Option"{
-                                tb.add("if let Some ( t ) = ").ident(&field.name).add("{");
-                                tb.add("s . field ( d + 1 ,").string(&field_strip).add(") ;");
-                                tb.add("t . ser_json ( d + 1 , s ) ;");
-                                if i != fields_len - 1 {
-                                    tb.add("s . conl ( ) ;");
-                                }
-                                tb.add("} ;");
+                        if let Some(tag) = &container.tag {
+                            tb.add("s . st_pre ( ) ;");
+                            tb.add("s . field ( d + 1 ,").string(tag).add(") ;");
+                            tb.add("s . label (").string(&key).add(") ;");
+                            if let Some(content) = &container.content {
+                                tb.add("s . conl ( ) ;");
+                                tb.add("s . field ( d + 1 ,").string(content).add(") ;");
+                                ser_json_object(&mut tb, &fields, false);
                             }
-                            else{
-                                tb.add("s . field ( d + 1 ,").string(&field_strip).add(" ) ;");
-                                tb.ident(&field.name).add(". ser_json ( d + 1 , s ) ;");
-                                if i != fields_len - 1 {
-                                    tb.add("s . conl ( ) ;");
-                                }
+                            else if fields.iter().any( | f | !f.skip) {
+                                tb.add("let mut first = false ;");
+                                ser_json_fields(&mut tb, &fields, false);
                             }
+                            tb.add("s . st_post ( d ) ;");
                         }
-                        tb.add("s . st_post ( d ) ; }");
+                        else {
+                            tb.add("s . out . push (").chr('{').add(") ;");
+                            tb.add("s . label (").string(&key).add(") ;");
+                            tb.add("s . out . push (").chr(':').add(") ;");
+                            ser_json_object(&mut tb, &fields, false);
+                            tb.add("s . out . push (").chr('}').add(") ;");
+                        }
+                        tb.add("}");
                     }
-                    else if parser.is_punct_alone(',') || parser.is_eot(){ // bare variant
+                    else if parser.is_punct_alone(',') || parser.is_eot() { // bare variant
                         tb.add("Self ::").ident(&variant).add("=> {");
-                        tb.add("s . label (").string(&variant).add(") ;");
-                        tb.add("s . out . push_str (").string(":[]").add(") ; }");
+                        if let Some(tag) = &container.tag {
+                            tb.add("s . st_pre ( ) ;");
+                            tb.add("s . field ( d + 1 ,").string(tag).add(") ;");
+                            tb.add("s . label (").string(&key).add(") ;");
+                            tb.add("s . st_post ( d ) ;");
+                        }
+                        else {
+                            tb.add("s . out . push (").chr('{').add(") ;");
+                            tb.add("s . label (").string(&key).add(") ;");
+                            tb.add("s . out . push_str (").string(":[]").add(") ;");
+                            tb.add("s . out . push (").chr('}').add(") ;");
+                        }
+                        tb.add("}");
                     }
-                    else{
+                    else {
                         return parser.unexpected();
                     }
                     parser.eat_punct_alone(',');
                 }
-                else{
+                else {
                     return parser.unexpected()
                 }
             }
             tb.add("}");
-            tb.add("s . out . push (").chr('}').add(") ;");
             tb.add("} } ;");
             return tb.end();
         }
@@ -158,11 +315,12 @@ pub fn derive_ser_json_impl(input: TokenStream) -> TokenStream {
 pub fn derive_de_json_impl(input: TokenStream) -> TokenStream {
     let mut parser = TokenParser::new(input);
     let mut tb = TokenBuilder::new();
-    
-    parser.eat_attributes();
+
+    let main_attrs = parser.eat_attributes();
+    let container = match container_attrs(&main_attrs) {Ok(c) => c, Err(e) => return e};
     parser.eat_ident("pub");
-    if parser.eat_ident("struct"){
-        if let Some(name) = parser.eat_any_ident(){
+    if parser.eat_ident("struct") {
+        if let Some(name) = parser.eat_any_ident() {
             let generic = parser.eat_generic();
             let types = parser.eat_all_types();
             let where_clause = parser.eat_where_clause(Some("DeJson"));
@@ -172,58 +330,36 @@ pub fn derive_de_json_impl(input: TokenStream) -> TokenStream {
             tb.add("{ fn de_json ( s : &  mut  DeJsonState , i : & mut std :: str :: Chars )");
             tb.add("-> std :: result :: Result < Self ,  DeJsonErr > { ");
 
-            if let Some(types) = types{
+            if let Some(types) = types {
                 tb.add("s . block_open ( i ) ? ;");
                 tb.add("let r = Self");
                 tb.add("(");
-                for _ in 0..types.len(){
-                     tb.add("{ let r = DeJson :: de_json ( s , i ) ? ; s . eat_comma_block ( i ) ? ; r } ,");
+                for index in 0..types.len() {
+                    tb.add("{ let r = DeJson :: de_json ( s , i )");
+                    err_path(&mut tb, Err(index), None);
+                    tb.add("? ; s . eat_comma_block ( i ) ? ; r } ,");
                 }
                 tb.add(") ;");
                 tb.add("s . block_close ( i ) ? ;");
                 tb.add("std :: result :: Result :: Ok ( r )");
             }
-            else if let Some(fields) = parser.eat_all_struct_fields(){ 
-                tb.add("s . curly_open ( i ) ? ;");
-                for field in &fields{
-                    tb.add("let mut").ident(&format!("_{}",field.name)).add("= None ;");
-                }
-                tb.add("while let Some ( _ ) = s . next_str ( ) {");
-                tb.add("match s . strbuf . as_ref ( ) {");
-                for field in &fields{
-                    let field_strip = if let Some(v) = field.name.strip_prefix("_"){v}else{&field.name};
-                    tb.string(&field_strip).add("=> { s . next_colon ( i ) ? ;");
-                    tb.ident(&format!("_{}",field.name)).add("= Some (DeJson :: de_json ( s , i ) ? ) ; } ,");
-                }
-                tb.add("_ => return std :: result :: Result :: Err ( s . err_exp ( & s . strbuf ) )");
-                tb.add("} ; s . eat_comma_curly ( i ) ? ;");
-                tb.add("} ; s . curly_close ( i ) ? ;");
-                
+            else if let Some(fields) = parser.eat_all_struct_fields() {
+                let fields = match serde_fields(fields, Some(&container), true) {Ok(f) => f, Err(e) => return e};
+                de_json_fields(&mut tb, &fields, None);
                 tb.add("std :: result :: Result :: Ok ( Self {");
-                for field in fields{
-                    tb.ident(&field.name).add(":");
-                    if field.ty.into_iter().next().unwrap().to_string() == "Option"{
-                        tb.add("if let Some ( t ) =").ident(&format!("_{}",field.name));
-                        tb.add("{ t } else { None } ,");
-                    }
-                    else{
-                        tb.add("if let Some ( t ) =").ident(&format!("_{}",field.name));
-                        tb.add("{ t } else { return Err ( s . err_nf (");
-                        tb.string(&field.name).add(") ) } ,");
-                    }
-                }
+                de_json_field_inits(&mut tb, &fields);
                 tb.add("} )");
             }
-            else{
+            else {
                 return parser.unexpected()
             }
-            tb.add("} } ;"); 
+            tb.add("} } ;");
             return tb.end();
         }
     }
-    else if parser.eat_ident("enum"){
-        
-        if let Some(name) = parser.eat_any_ident(){
+    else if parser.eat_ident("enum") {
+
+        if let Some(name) = parser.eat_any_ident() {
             let generic = parser.eat_generic();
             let where_clause = parser.eat_where_clause(Some("DeJson"));
 
@@ -231,75 +367,121 @@ pub fn derive_de_json_impl(input: TokenStream) -> TokenStream {
             tb.add("DeJson for").ident(&name).stream(generic).stream(where_clause);
             tb.add("{ fn de_json ( s : & mut  DeJsonState , i : & mut std :: str :: Chars )");
             tb.add("-> std :: result :: Result < Self , DeJsonErr > { ");
-            tb.add("s . curly_open ( i ) ? ;");
-            tb.add("let _ = s . string ( i ) ? ;");
-            tb.add("s . colon ( i ) ? ;");
-            tb.add("let r = std :: result :: Result :: Ok ( match s . strbuf . as_ref ( ) {");
-            
-            if !parser.open_brace(){
+            if let Some(tag) = &container.tag {
+                // tagged enums are read as a whole so the tag can come in any position
+                tb.add("let mut _flat = s . capture_members ( i ) ? ;");
+                tb.add("let tag : String = s . take_tag ( & mut _flat ,").string(tag).add(") ? ;");
+                if let Some(content) = &container.content {
+                    tb.add("let _content = s . take_member ( & mut _flat ,").string(content).add(") ;");
+                    tb.add("if let Some ( ( key , _ ) ) = _flat . first ( ) {");
+                    tb.add("return std :: result :: Result :: Err ( s . err_exp ( key ) ) }");
+                }
+                tb.add("std :: result :: Result :: Ok ( match tag . as_ref ( ) {");
+            }
+            else {
+                tb.add("s . curly_open ( i ) ? ;");
+                tb.add("let _ = s . string ( i ) ? ;");
+                tb.add("s . colon ( i ) ? ;");
+                tb.add("let r = std :: result :: Result :: Ok ( match s . strbuf . as_ref ( ) {");
+            }
+
+            if !parser.open_brace() {
                 return parser.unexpected()
             }
-            while !parser.eat_eot(){
+            while !parser.eat_eot() {
                 // parse ident
-                parser.eat_attributes();
-                if let Some(variant) = parser.eat_any_ident(){
-                    tb.string(&variant).add("=> {");
-                    if let Some(types) = parser.eat_all_types(){
-                        
-                        tb.add("s . block_open ( i ) ? ;");
-                        tb.add("let r = Self ::").ident(&variant).add("(");
-                        for _ in 0..types.len(){
-                            tb.add("{ let r = DeJson :: de_json ( s , i ) ? ; s . eat_comma_block ( i ) ? ; r } ,");
+                let attrs = parser.eat_attributes();
+                if let Some(variant) = parser.eat_any_ident() {
+                    let key = match variant_key(&variant, &attrs, &container) {Ok(k) => k, Err(e) => return e};
+                    tb.string(&key).add("=> {");
+                    if let Some(types) = parser.eat_all_types() {
+                        if let Some(content) = &container.content {
+                            tb.add("let content = if let Some ( content ) = _content { content } else {");
+                            tb.add("return std :: result :: Result :: Err ( s . err_nf (").string(content).add(") ) } ;");
+                            if types.len() == 1 {
+                                tb.add("Self ::").ident(&variant).add("( s . de_captured ( & content ) . map_err ( | e | e . in_key (").string(content).add(") ) ? )");
+                            }
+                            else {
+                                tb.add("let r : (");
+                                for ty in types.iter() {
+                                    tb.stream(Some(ty.clone())).add(",");
+                                }
+                                tb.add(") = s . de_captured ( & content ) . map_err ( | e | e . in_key (").string(content).add(") ) ? ;");
+                                tb.add("Self ::").ident(&variant).add("(");
+                                for i in 0..types.len() {
+                                    tb.add("r .").unsuf_usize(i).add(",");
+                                }
+                                tb.add(")");
+                            }
+                        }
+                        else if container.tag.is_some() {
+                            if types.len() != 1 {
+                                return error("Internally tagged enums only support unit, named and single field variants")
+                            }
+                            tb.add("Self ::").ident(&variant).add("( s . de_members ( & _flat ) ? )");
+                        }
+                        else {
+                            tb.add("s . block_open ( i ) ? ;");
+                            tb.add("let r = Self ::").ident(&variant).add("(");
+                            for index in 0..types.len() {
+                                tb.add("{ let r = DeJson :: de_json ( s , i )");
+                                err_path(&mut tb, Err(index), Some(&key));
+                                tb.add("? ; s . eat_comma_block ( i ) ? ; r } ,");
+                            }
+                            tb.add(") ;");
+                            tb.add("s . block_close ( i ) ? ; r");
                         }
-                        tb.add(") ;");
-                        tb.add("s . block_close ( i ) ? ; r");
                     }
-                    else if let Some(fields) = parser.eat_all_struct_fields(){ // named variant
-                        tb.add("s . curly_open ( i ) ? ;");
-                        for field in &fields{
-                            tb.add("let mut").ident(&format!("_{}",field.name)).add("= None ;");
+                    else if let Some(fields) = parser.eat_all_struct_fields() { // named variant
+                        let fields = match serde_fields(fields, None, true) {Ok(f) => f, Err(e) => return e};
+                        if let Some(content) = &container.content {
+                            tb.add("let content = if let Some ( content ) = _content { content } else {");
+                            tb.add("return std :: result :: Result :: Err ( s . err_nf (").string(content).add(") ) } ;");
+                            tb.add("let mut _flat = s . members_of ( & content ) ? ;");
+                            de_json_members(&mut tb, &fields, Some(content));
                         }
-                        tb.add("while let Some ( _ ) = s . next_str ( ) {");
-                        tb.add("match s . strbuf . as_ref ( ) {");
-                        for field in &fields{
-                            tb.string(&field.name).add("=> { s . next_colon ( i ) ? ;");
-                            tb.ident(&format!("_{}",field.name)).add("= Some (DeJson :: de_json ( s , i ) ? ) ; } ,");
+                        else if container.tag.is_some() {
+                            de_json_members(&mut tb, &fields, None);
                         }
-                        tb.add("_ => return std :: result :: Result :: Err ( s . err_exp ( & s . strbuf ) )");
-                        tb.add("} s . eat_comma_curly ( i ) ? ;");
-                        tb.add("} s . curly_close ( i ) ? ;");
-                        
-                        tb.add("Self ::").ident(&variant).add("{");
-                        for field in fields{
-                            tb.ident(&field.name).add(":");
-                            if field.ty.into_iter().next().unwrap().to_string() == "Option"{
-                                tb.add("if let Some ( t ) =").ident(&format!("_{}",field.name));
-                                tb.add("{ t } else { None } ,");
-                            }
-                            else{
-                                tb.add("if let Some ( t ) =").ident(&format!("_{}",field.name));
-                                tb.add("{ t } else { return Err ( s . err_nf (");
-                                tb.string(&field.name).add(") ) } ,");
-                            }
+                        else {
+                            de_json_fields(&mut tb, &fields, Some(&key));
                         }
+                        tb.add("Self ::").ident(&variant).add("{");
+                        de_json_field_inits(&mut tb, &fields);
                         tb.add("}");
                     }
-                    else if parser.is_punct_alone(',') || parser.is_eot(){ // bare variant
-                        tb.add("s . block_open ( i ) ? ; s . block_close ( i ) ? ; Self ::").ident(&variant);
+                    else if parser.is_punct_alone(',') || parser.is_eot() { // bare variant
+                        if container.content.is_some() {
+                            tb.add("Self ::").ident(&variant);
+                        }
+                        else if container.tag.is_some() {
+                            tb.add("if let Some ( ( key , _ ) ) = _flat . first ( ) {");
+                            tb.add("return std :: result :: Result :: Err ( s . err_exp ( key ) ) }");
+                            tb.add("Self ::").ident(&variant);
+                        }
+                        else {
+                            tb.add("s . block_open ( i ) ? ; s . block_close ( i ) ? ; Self ::").ident(&variant);
+                        }
                     }
-                    else{
+                    else {
                         return parser.unexpected();
                     }
-                    
+
                     tb.add("}");
                     parser.eat_punct_alone(',');
                 }
-                else{
+                else {
                     return parser.unexpected()
                 }
             }
-            tb.add("_ => return std :: result :: Result :: Err ( s . err_exp ( & s . strbuf ) )");
-            tb.add("} ) ; s . curly_close ( i ) ? ; r } }");
+            if container.tag.is_some() {
+                tb.add("_ => return std :: result :: Result :: Err ( s . err_enum ( & tag ) )");
+                tb.add("} ) } }");
+            }
+            else {
+                tb.add("_ => return std :: result :: Result :: Err ( s . err_exp ( & s . strbuf ) )");
+                tb.add("} ) ; s . curly_close ( i ) ? ; r } }");
+            }
             return tb.end();
         }
     }
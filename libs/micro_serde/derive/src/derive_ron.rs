@@ -1,13 +1,71 @@
  
 use proc_macro::{TokenStream};
 use makepad_micro_proc_macro::*;
+use crate::serde_attrs::*;
+
+// adds where a value sits to the errors it returns, outer is the enum variant the fields are in
+fn err_path(tb: &mut TokenBuilder, key: Result<&str, usize>, outer: Option<&str>) {
+    tb.add(". map_err ( | e | e .");
+    match key {
+        Ok(key) => tb.add("in_key (").string(key).add(")"),
+        Err(index) => tb.add("in_index (").unsuf_usize(index).add(")")
+    };
+    if let Some(outer) = outer {
+        tb.add(". in_key (").string(outer).add(")");
+    }
+    tb.add(")");
+}
+
+// parses named fields into the _name options
+fn de_ron_fields(tb: &mut TokenBuilder, fields: &[SerdeField], outer: Option<&str>) {
+    tb.add("s . paren_open ( i ) ? ;");
+    for field in fields.iter().filter( | f | !f.skip) {
+        tb.add("let mut").ident(&format!("_{}",field.name)).add("= None ;");
+    }
+    tb.add("while let Some ( _ ) = s . next_ident ( ) {");
+    tb.add("match s . identbuf . as_ref ( ) {");
+    for field in fields.iter().filter( | f | !f.skip) {
+        // the colon reads the value token, so it can fail on the value too
+        tb.string(&field.key).add("=> { s . next_colon ( i )");
+        err_path(tb, Ok(&field.key), outer);
+        tb.add("? ;");
+        tb.ident(&format!("_{}",field.name)).add("= Some ( DeRon :: de_ron ( s , i )");
+        err_path(tb, Ok(&field.key), outer);
+        tb.add("? ) ; } ,");
+    }
+    tb.add("_ => return std :: result :: Result :: Err ( s . err_exp ( & s . identbuf ) )");
+    tb.add("} ; s . eat_comma_paren ( i ) ? ;");
+    tb.add("} ; s . paren_close ( i ) ? ;");
+}
+
+fn de_ron_field_inits(tb: &mut TokenBuilder, fields: &[SerdeField]) {
+    for field in fields {
+        tb.ident(&field.name).add(":");
+        if field.skip {
+            tb.stream(Some(field.default_value())).add(",");
+            continue;
+        }
+        tb.add("if let Some ( t ) =").ident(&format!("_{}",field.name)).add("{ t } else {");
+        if let Some(default) = &field.default {
+            tb.stream(Some(default.clone()));
+        }
+        else if field.is_option() {
+            tb.add("None");
+        }
+        else {
+            tb.add("return Err ( s . err_nf (").string(&field.key).add(") )");
+        }
+        tb.add("} ,");
+    }
+}
 
 pub fn derive_ser_ron_impl(input: TokenStream) -> TokenStream {
 
     let mut parser = TokenParser::new(input);
     let mut tb = TokenBuilder::new();
     
-    parser.eat_attributes();
+    let main_attrs = parser.eat_attributes();
+    let container = match container_attrs(&main_attrs) {Ok(c) => c, Err(e) => return e};
     parser.eat_ident("pub");
     if parser.eat_ident("struct"){
         if let Some(name) = parser.eat_any_ident(){
@@ -32,16 +90,17 @@ pub fn derive_ser_ron_impl(input: TokenStream) -> TokenStream {
                 tb.add("s.out.push(").chr(')').add(");");
             }
             else if let Some(fields) = parser.eat_all_struct_fields(){ 
+                let fields = match serde_fields(fields, Some(&container), false) {Ok(f) => f, Err(e) => return e};
                 tb.add("s.st_pre( ) ;");
                 // named struct
-                for field in fields{
-                    if field.ty.into_iter().next().unwrap().to_string() == "Option"{
+                for field in fields.iter().filter( | f | !f.skip){
+                    if field.is_option(){
                         tb.add("if let Some ( t ) = ").add("& self .").ident(&field.name).add("{");
-                        tb.add("s.field ( d + 1 ,").string(&field.name).add(") ;");
+                        tb.add("s.field ( d + 1 ,").string(&field.key).add(") ;");
                         tb.add("t.ser_ron ( d + 1 , s ) ; s . conl ( ) ; } ;");
                     }
                     else{
-                        tb.add("s . field ( d + 1 ,").string(&field.name).add(" ) ;");
+                        tb.add("s . field ( d + 1 ,").string(&field.key).add(" ) ;");
                         tb.add("self .").ident(&field.name).add(". ser_ron ( d + 1 , s ) ; s . conl ( ) ;");
                     }
                 }
@@ -70,8 +129,9 @@ pub fn derive_ser_ron_impl(input: TokenStream) -> TokenStream {
 
             while !parser.eat_eot(){
                 // parse ident
-                parser.eat_attributes();
+                let attrs = parser.eat_attributes();
                 if let Some(variant) = parser.eat_any_ident(){
+                    let key = match variant_key(&variant, &attrs, &container) {Ok(k) => k, Err(e) => return e};
                     if let Some(types) = parser.eat_all_types(){
                         
                         tb.add("Self ::").ident(&variant).add("(");
@@ -79,7 +139,7 @@ pub fn derive_ser_ron_impl(input: TokenStream) -> TokenStream {
                             tb.ident(&format!("n{}", i)).add(",");
                         }
                         tb.add(") => {");
-                        tb.add("s . out . push_str (").string(&variant).add(") ;");
+                        tb.add("s . out . push_str (").string(&key).add(") ;");
                         tb.add("s . out . push (").chr('(').add(") ;");
                         
                         for i in 0..types.len(){
@@ -92,23 +152,28 @@ pub fn derive_ser_ron_impl(input: TokenStream) -> TokenStream {
                         tb.add("}");
                     }
                     else if let Some(fields) = parser.eat_all_struct_fields(){ // named variant
+                        let fields = match serde_fields(fields, None, false) {Ok(f) => f, Err(e) => return e};
                         tb.add("Self ::").ident(&variant).add("{");
                         for field in fields.iter(){
-                            tb.ident(&field.name).add(",");
+                            tb.ident(&field.name);
+                            if field.skip {
+                                tb.add(": _");
+                            }
+                            tb.add(",");
                         }
                         tb.add("} => {");
                         
-                        tb.add("s . out . push_str (").string(&variant).add(") ;");
+                        tb.add("s . out . push_str (").string(&key).add(") ;");
                         tb.add("s . st_pre ( ) ;");
                         
-                        for field in fields{
-                            if field.ty.into_iter().next().unwrap().to_string() == "Option"{
+                        for field in fields.iter().filter( | f | !f.skip){
+                            if field.is_option(){
                                 tb.add("if ").ident(&field.name).add(". is_some ( ) {");
-                                tb.add("s . field ( d + 1 ,").string(&field.name).add(") ;");
+                                tb.add("s . field ( d + 1 ,").string(&field.key).add(") ;");
                                 tb.ident(&field.name).add(" . ser_ron ( d + 1 , s ) ; s . conl ( ) ; } ;");
                             }
                             else{
-                                tb.add("s . field ( d + 1 ,").string(&field.name).add(" ) ;");
+                                tb.add("s . field ( d + 1 ,").string(&field.key).add(" ) ;");
                                 tb.ident(&field.name).add(". ser_ron ( d + 1 , s ) ; s . conl ( ) ;");
                             }
                         }
@@ -116,7 +181,7 @@ pub fn derive_ser_ron_impl(input: TokenStream) -> TokenStream {
                     }
                     else if parser.is_punct_alone(',') || parser.is_eot(){ // bare variant
                         tb.add("Self ::").ident(&variant).add("=> {");
-                        tb.add("s . out . push_str (").string(&variant).add(") ; }");
+                        tb.add("s . out . push_str (").string(&key).add(") ; }");
                     }
                     else{
                         return parser.unexpected();
@@ -138,7 +203,8 @@ pub fn derive_ser_ron_impl(input: TokenStream) -> TokenStream {
 pub fn derive_de_ron_impl(input: TokenStream) -> TokenStream {
     let mut parser = TokenParser::new(input);
     let mut tb = TokenBuilder::new();
-    parser.eat_attributes();
+    let main_attrs = parser.eat_attributes();
+    let container = match container_attrs(&main_attrs) {Ok(c) => c, Err(e) => return e};
     parser.eat_ident("pub");
     if parser.eat_ident("struct"){
         if let Some(name) = parser.eat_any_ident(){
@@ -155,41 +221,20 @@ pub fn derive_de_ron_impl(input: TokenStream) -> TokenStream {
                 tb.add("s . paren_open ( i ) ? ;");
                 tb.add("let r = Self");
                 tb.add("(");
-                for _ in 0..types.len(){
-                     tb.add("{ let r = DeRon :: de_ron ( s , i ) ? ; s . eat_comma_paren ( i ) ? ; r } ,");
+                for index in 0..types.len(){
+                     tb.add("{ let r = DeRon :: de_ron ( s , i )");
+                     err_path(&mut tb, Err(index), None);
+                     tb.add("? ; s . eat_comma_paren ( i ) ? ; r } ,");
                 }
                 tb.add(") ;");
                 tb.add("s . paren_close ( i ) ? ;");
                 tb.add("std :: result :: Result :: Ok ( r ) ");
             }
             else if let Some(fields) = parser.eat_all_struct_fields(){ 
-                tb.add("s . paren_open ( i ) ? ;");
-                for field in &fields{
-                    tb.add("let mut").ident(&format!("_{}",field.name)).add("= None ;");
-                }
-                tb.add("while let Some ( _ ) = s . next_ident ( ) {");
-                tb.add("match s . identbuf . as_ref ( ) {");
-                for field in &fields{
-                    tb.string(&field.name).add("=> { s . next_colon ( i ) ? ;");
-                    tb.ident(&format!("_{}",field.name)).add("= Some ( DeRon :: de_ron ( s , i ) ? ) ; } ,");
-                }
-                tb.add("_ => return std :: result :: Result :: Err ( s . err_exp ( & s . identbuf ) )");
-                tb.add("} ; s . eat_comma_paren ( i ) ? ;");
-                tb.add("} ; s . paren_close ( i ) ? ;");
-                
+                let fields = match serde_fields(fields, Some(&container), false) {Ok(f) => f, Err(e) => return e};
+                de_ron_fields(&mut tb, &fields, None);
                 tb.add("std :: result :: Result :: Ok ( Self {");
-                for field in fields{
-                    tb.ident(&field.name).add(":");
-                    if field.ty.into_iter().next().unwrap().to_string() == "Option"{
-                        tb.add("if let Some ( t ) =").ident(&format!("_{}",field.name));
-                        tb.add("{ t } else { None } ,");
-                    }
-                    else{
-                        tb.add("if let Some ( t ) =").ident(&format!("_{}",field.name));
-                        tb.add("{ t } else { return Err ( s . err_nf (");
-                        tb.string(&field.name).add(") ) } ,");
-                    }
-                }
+                de_ron_field_inits(&mut tb, &fields);
                 tb.add("} )");
             }
             else{
@@ -217,47 +262,27 @@ pub fn derive_de_ron_impl(input: TokenStream) -> TokenStream {
             }
             while !parser.eat_eot(){
                 // parse ident
-                parser.eat_attributes();
+                let attrs = parser.eat_attributes();
                 if let Some(variant) = parser.eat_any_ident(){
-                    tb.string(&variant).add("=> {");
+                    let key = match variant_key(&variant, &attrs, &container) {Ok(k) => k, Err(e) => return e};
+                    tb.string(&key).add("=> {");
                     if let Some(types) = parser.eat_all_types(){
                         
                         tb.add("s . paren_open ( i ) ? ;");
                         tb.add("let r = Self ::").ident(&variant).add("(");
-                        for _ in 0..types.len(){
-                            tb.add("{ let r = DeRon :: de_ron ( s , i ) ? ; s . eat_comma_paren ( i ) ? ; r } ,");
+                        for index in 0..types.len(){
+                            tb.add("{ let r = DeRon :: de_ron ( s , i )");
+                            err_path(&mut tb, Err(index), Some(&key));
+                            tb.add("? ; s . eat_comma_paren ( i ) ? ; r } ,");
                         }
                         tb.add(") ;");
                         tb.add("s . paren_close ( i ) ? ; r");
                     }
                     else if let Some(fields) = parser.eat_all_struct_fields(){ // named variant
-                        tb.add("s . paren_open ( i ) ? ;");
-                        for field in &fields{
-                            tb.add("let mut").ident(&format!("_{}",field.name)).add("= None ;");
-                        }
-                        tb.add("while let Some ( _ ) = s . next_ident ( ) {");
-                        tb.add("match s . identbuf . as_ref ( ) {");
-                        for field in &fields{
-                            tb.string(&field.name).add("=> { s . next_colon ( i ) ? ;");
-                            tb.ident(&format!("_{}",field.name)).add("= Some ( DeRon :: de_ron ( s , i ) ? ) ; } ,");
-                        }
-                        tb.add("_ => return std :: result :: Result :: Err ( s . err_exp ( & s . strbuf ) )");
-                        tb.add("} ; s . eat_comma_paren ( i ) ? ;");
-                        tb.add("} ; s . paren_close ( i ) ? ;");
-                        
+                        let fields = match serde_fields(fields, None, false) {Ok(f) => f, Err(e) => return e};
+                        de_ron_fields(&mut tb, &fields, Some(&key));
                         tb.add("Self ::").ident(&variant).add("{");
-                        for field in fields{
-                            tb.ident(&field.name).add(":");
-                            if field.ty.into_iter().next().unwrap().to_string() == "Option"{
-                                tb.add("if let Some ( t ) =").ident(&format!("_{}",field.name));
-                                tb.add("{ t } else { None } ,");
-                            }
-                            else{
-                                tb.add("if let Some ( t ) =").ident(&format!("_{}",field.name));
-                                tb.add("{ t } else { return Err ( s . err_nf (");
-                                tb.string(&field.name).add(") ) } ,");
-                            }
-                        }
+                        de_ron_field_inits(&mut tb, &fields);
                         tb.add("}");
                     }
                     else if parser.is_punct_alone(',') || parser.is_eot(){ // bare variant
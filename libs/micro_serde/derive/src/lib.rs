@@ -1,6 +1,8 @@
 extern crate proc_macro;
 use proc_macro::{TokenStream};
 
+mod serde_attrs;
+
 mod derive_bin;
 use crate::derive_bin::*;
 
@@ -10,34 +12,52 @@ use crate::derive_ron::*;
 mod derive_json;
 use crate::derive_json::*;
 
-#[proc_macro_derive(SerBin)]
+// The derives understand these attributes:
+//
+// on fields
+//   #[rename = "name"]         the name used on the wire (json and ron)
+//   #[default]                 a missing field becomes Default::default()
+//   #[default(expr)]           a missing field becomes expr
+//   #[default = "path::to_fn"] a missing field becomes path::to_fn()
+//   #[skip]                    never written, always read as its default
+//   #[flatten]                 json only, the fields of the value are written into the parent object and
+//                              the keys the parent doesn't know are read back into it
+// on variants
+//   #[rename = "name"]
+// on structs and enums
+//   #[rename_all = "camelCase"] renames all fields or variants, also snake_case, PascalCase, lowercase,
+//                               UPPERCASE, SCREAMING_SNAKE_CASE, kebab-case, SCREAMING-KEBAB-CASE
+// on enums, json only
+//   #[tag = "type"]            internally tagged: {"type":"Variant", ..fields}
+//   #[tag = "t"] #[content = "c"] adjacently tagged: {"t":"Variant","c":value}
+
+#[proc_macro_derive(SerBin, attributes(rename, rename_all, default, skip, flatten, tag, content))]
 pub fn derive_ser_bin(input: TokenStream) -> TokenStream {
     derive_ser_bin_impl(input)
 }
 
-#[proc_macro_derive(DeBin)]
+#[proc_macro_derive(DeBin, attributes(rename, rename_all, default, skip, flatten, tag, content))]
 pub fn derive_de_bin(input: TokenStream) -> TokenStream {
     derive_de_bin_impl(input)
 }
 
-#[proc_macro_derive(SerJson)]
+#[proc_macro_derive(SerJson, attributes(rename, rename_all, default, skip, flatten, tag, content))]
 pub fn derive_ser_json(input: TokenStream) -> TokenStream {
     derive_ser_json_impl(input)
 }
 
-#[proc_macro_derive(DeJson)]
+#[proc_macro_derive(DeJson, attributes(rename, rename_all, default, skip, flatten, tag, content))]
 pub fn derive_de_json(input: TokenStream) -> TokenStream {
     derive_de_json_impl(input)
 }
 
 
-#[proc_macro_derive(SerRon)]
+#[proc_macro_derive(SerRon, attributes(rename, rename_all, default, skip, flatten, tag, content))]
 pub fn derive_ser_ron(input: TokenStream) -> TokenStream {
     derive_ser_ron_impl(input)
 }
 
-#[proc_macro_derive(DeRon)]
+#[proc_macro_derive(DeRon, attributes(rename, rename_all, default, skip, flatten, tag, content))]
 pub fn derive_de_ron(input: TokenStream) -> TokenStream {
     derive_de_ron_impl(input)
 }
-
@@ -0,0 +1,175 @@
+use proc_macro::TokenStream;
+use makepad_micro_proc_macro::*;
+
+// the attributes shared by all the derives, see lib.rs for what they do
+
+pub struct ContainerAttrs {
+    pub rename_all: Option<String>,
+    pub tag: Option<String>,
+    pub content: Option<String>,
+}
+
+pub struct SerdeField {
+    pub name: String,
+    pub ty: TokenStream,
+    pub key: String,
+    pub default: Option<TokenStream>,
+    pub skip: bool,
+    pub flatten: bool,
+}
+
+impl SerdeField {
+    pub fn is_option(&self) -> bool {
+        self.ty.clone().into_iter().next().map(|t| t.to_string() == "Option").unwrap_or(false)
+    }
+
+    // the value used when the field is skipped
+    pub fn default_value(&self) -> TokenStream {
+        if let Some(default) = &self.default {
+            default.clone()
+        }
+        else {
+            let mut tb = TokenBuilder::new();
+            tb.add("Default :: default ( )");
+            tb.end()
+        }
+    }
+}
+
+fn attr_string(attr: &Attribute) -> Result<String, TokenStream> {
+    if let Some(args) = &attr.args {
+        let lit = args.to_string();
+        if let Some(value) = lit.strip_prefix('"').and_then( | v | v.strip_suffix('"')) {
+            return Ok(value.to_string())
+        }
+    }
+    Err(error(&format!("#[{}] expects a string literal, like #[{} = \"name\"]", attr.name, attr.name)))
+}
+
+pub fn container_attrs(attrs: &[Attribute]) -> Result<ContainerAttrs, TokenStream> {
+    let mut out = ContainerAttrs {rename_all: None, tag: None, content: None};
+    for attr in attrs {
+        match attr.name.as_ref() {
+            "rename_all" => {
+                let rule = attr_string(attr) ?;
+                rename_case("", &rule) ?;
+                out.rename_all = Some(rule);
+            }
+            "tag" => out.tag = Some(attr_string(attr) ?),
+            "content" => out.content = Some(attr_string(attr) ?),
+            _ => ()
+        }
+    }
+    if out.content.is_some() && out.tag.is_none() {
+        return Err(error("#[content] needs a #[tag] as well"))
+    }
+    Ok(out)
+}
+
+/// Splits an identifier into lowercase words on underscores and case changes
+fn split_words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    for part in name.split('_') {
+        let mut word = String::new();
+        let mut last_lower = false;
+        for c in part.chars() {
+            if c.is_uppercase() && last_lower && !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            last_lower = c.is_lowercase() || c.is_numeric();
+            word.extend(c.to_lowercase());
+        }
+        if !word.is_empty() {
+            words.push(word);
+        }
+    }
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    if let Some(first) = chars.next() {
+        first.to_uppercase().chain(chars).collect()
+    }
+    else {
+        String::new()
+    }
+}
+
+pub fn rename_case(name: &str, rule: &str) -> Result<String, TokenStream> {
+    let words = split_words(name);
+    Ok(match rule {
+        "lowercase" => name.to_lowercase(),
+        "UPPERCASE" => name.to_uppercase(),
+        "PascalCase" => words.iter().map( | w | capitalize(w)).collect(),
+        "camelCase" => words.iter().enumerate().map( | (i, w) | if i == 0 {w.clone()} else {capitalize(w)}).collect(),
+        "snake_case" => words.join("_"),
+        "SCREAMING_SNAKE_CASE" => words.join("_").to_uppercase(),
+        "kebab-case" => words.join("-"),
+        "SCREAMING-KEBAB-CASE" => words.join("-").to_uppercase(),
+        _ => return Err(error(&format!("Unknown rename_all rule \"{}\", use one of lowercase, UPPERCASE, PascalCase, camelCase, snake_case, SCREAMING_SNAKE_CASE, kebab-case or SCREAMING-KEBAB-CASE", rule)))
+    })
+}
+
+/// The name an enum variant is written as
+pub fn variant_key(variant: &str, attrs: &[Attribute], container: &ContainerAttrs) -> Result<String, TokenStream> {
+    if let Some(attr) = attrs.iter().find( | a | a.name == "rename") {
+        return attr_string(attr)
+    }
+    if let Some(rule) = &container.rename_all {
+        return rename_case(variant, rule)
+    }
+    Ok(variant.to_string())
+}
+
+/// Reads the field attributes. strip_underscore drops a leading _ from the key, which json uses so
+/// fields like _type can be written as "type"
+pub fn serde_fields(fields: Vec<StructField>, container: Option<&ContainerAttrs>, strip_underscore: bool) -> Result<Vec<SerdeField>, TokenStream> {
+    let mut out = Vec::new();
+    for field in fields {
+        let mut key = None;
+        let mut default = None;
+        let mut skip = false;
+        let mut flatten = false;
+        for attr in &field.attrs {
+            match attr.name.as_ref() {
+                "rename" => key = Some(attr_string(attr) ?),
+                "default" => {
+                    default = Some(if let Some(args) = &attr.args {
+                        // #[default = "path::to_fn"] calls a function, #[default(expr)] uses the expression
+                        if let Ok(path) = attr_string(attr) {
+                            let mut tb = TokenBuilder::new();
+                            tb.add(&path).add("( )");
+                            tb.end()
+                        }
+                        else {
+                            args.clone()
+                        }
+                    }
+                    else {
+                        let mut tb = TokenBuilder::new();
+                        tb.add("Default :: default ( )");
+                        tb.end()
+                    })
+                }
+                "skip" => skip = true,
+                "flatten" => flatten = true,
+                _ => ()
+            }
+        }
+        let key = if let Some(key) = key {
+            key
+        }
+        else {
+            let name = if strip_underscore {field.name.strip_prefix('_').unwrap_or(&field.name)} else {&field.name};
+            if let Some(rule) = container.and_then( | c | c.rename_all.as_ref()) {
+                rename_case(name, rule) ?
+            }
+            else {
+                name.to_string()
+            }
+        };
+        out.push(SerdeField {name: field.name, ty: field.ty, key, default, skip, flatten});
+    }
+    Ok(out)
+}
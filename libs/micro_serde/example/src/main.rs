@@ -1,6 +1,7 @@
 // lets make tinyserde dep free too!
 
 use makepad_micro_serde::*;
+use std::collections::HashMap;
 
 #[derive(SerBin, DeBin, SerJson, DeJson, SerRon, DeRon, PartialEq)]
 struct MyStruct<T> where T: Clone {
@@ -25,6 +26,46 @@ enum MyEnum<T> where T: Clone {
     Four {z: Option<u32>, w: T},
 }
 
+#[derive(SerBin, DeBin, SerJson, DeJson, SerRon, DeRon, PartialEq, Debug, Clone)]
+#[rename_all = "camelCase"]
+struct Attributed {
+    #[rename = "type"]
+    kind: String,
+    max_tokens: u32,
+    #[default(16)]
+    top_k: u32,
+    #[skip]
+    cached: Vec<u32>,
+    #[flatten]
+    extra: HashMap<String, u32>,
+}
+
+#[derive(SerJson, DeJson, PartialEq, Debug)]
+#[tag = "type"]
+#[rename_all = "snake_case"]
+enum Internal {
+    Ping,
+    TextDelta {text: String, #[default] index: u32},
+    #[rename = "done"]
+    Finished(Usage),
+}
+
+#[derive(SerJson, DeJson, PartialEq, Debug)]
+struct Usage {
+    prompt_tokens: u32,
+    total_tokens: u32,
+}
+
+#[derive(SerJson, DeJson, PartialEq, Debug)]
+#[tag = "t"]
+#[content = "c"]
+enum Adjacent {
+    Unit,
+    One(u32),
+    Pair(u32, String),
+    Named {x: u32},
+}
+
 fn main() {
     //let a = MyStruct{step1:1,step2:None};
     //let x = MyStruct2(1,2);
@@ -55,4 +96,47 @@ fn main() {
     println!("RON Output {}", ron);
     let y:MyStruct<usize> = DeRon::deserialize_ron(&ron).unwrap();
     println!("RON roundtrip equality {}", x == y);
+    
+    let x = Attributed {
+        kind: "chat".to_string(),
+        max_tokens: 10,
+        top_k: 4,
+        cached: vec![1],
+        extra: [("seed".to_string(), 3)].into_iter().collect()
+    };
+    let json = x.serialize_json();
+    println!("Attributes JSON Output {}", json);
+    let y: Attributed = DeJson::deserialize_json(&json).unwrap();
+    println!("Attributes JSON roundtrip equality {}", y == Attributed {cached: vec![], ..x.clone()});
+    let y: Attributed = DeJson::deserialize_json("{\"type\":\"chat\",\"maxTokens\":10}").unwrap();
+    println!("Attributes JSON default {}", y.top_k == 16);
+    let ron = x.serialize_ron();
+    println!("Attributes RON Output {}", ron);
+    
+    let all = vec![
+        Internal::Ping,
+        Internal::TextDelta {text: "hi".to_string(), index: 1},
+        Internal::Finished(Usage {prompt_tokens: 5, total_tokens: 7})
+    ];
+    let json = all.serialize_json();
+    println!("Internally tagged JSON Output {}", json);
+    let y: Vec<Internal> = DeJson::deserialize_json(&json).unwrap();
+    println!("Internally tagged JSON roundtrip equality {}", all == y);
+    
+    let all = vec![Adjacent::Unit, Adjacent::One(1), Adjacent::Pair(2, "two".to_string()), Adjacent::Named {x: 3}];
+    let json = all.serialize_json();
+    println!("Adjacently tagged JSON Output {}", json);
+    let y: Vec<Adjacent> = DeJson::deserialize_json(&json).unwrap();
+    println!("Adjacently tagged JSON roundtrip equality {}", all == y);
+    
+    let value: JsonValue = DeJson::deserialize_json(r#"{"choices":[{"delta":{"content":"Hi"}}]}"#).unwrap();
+    println!("JsonValue path lookup {:?}", value.path("choices.0.delta.content").and_then(|v| v.as_str()));
+    
+    let mut stream = DeJsonStream::default();
+    for chunk in ["[{\"a\":1", "0},", "{\"b\":[tr", "ue]}]"] {
+        stream.push_str(chunk);
+        while let Some(value) = stream.next_value().unwrap() {
+            println!("Streamed JsonValue {}", value.serialize_json());
+        }
+    }
 }
@@ -0,0 +1,158 @@
+use std::str::Chars;
+use crate::serde_json::*;
+
+/// A json value that isn't described by a Rust type, for schemaless payloads.
+/// Object members keep the order they were read in
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum JsonValue {
+    #[default]
+    Null,
+    Bool(bool),
+    U64(u64),
+    I64(i64),
+    F64(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// The member of an object
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        if let JsonValue::Object(members) = self {
+            return members.iter().find( | (k, _) | k == key).map( | (_, v) | v)
+        }
+        None
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut JsonValue> {
+        if let JsonValue::Object(members) = self {
+            return members.iter_mut().find( | (k, _) | k == key).map( | (_, v) | v)
+        }
+        None
+    }
+
+    /// The item of an array
+    pub fn at(&self, index: usize) -> Option<&JsonValue> {
+        if let JsonValue::Array(items) = self {
+            return items.get(index)
+        }
+        None
+    }
+
+    /// Looks up a value by a dot separated path of object keys and array indices,
+    /// like "choices.0.delta.content"
+    pub fn path(&self, path: &str) -> Option<&JsonValue> {
+        let mut value = self;
+        for step in path.split('.').filter( | s | !s.is_empty()) {
+            value = match value {
+                JsonValue::Object(_) => value.get(step) ?,
+                JsonValue::Array(_) => value.at(step.parse().ok() ?) ?,
+                _ => return None
+            };
+        }
+        Some(value)
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, JsonValue::Null)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        if let JsonValue::Bool(v) = self {Some(*v)} else {None}
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        if let JsonValue::String(v) = self {Some(v)} else {None}
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            JsonValue::U64(v) => Some(*v),
+            JsonValue::I64(v) if *v >= 0 => Some(*v as u64),
+            _ => None
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            JsonValue::I64(v) => Some(*v),
+            JsonValue::U64(v) if *v <= i64::MAX as u64 => Some(*v as i64),
+            _ => None
+        }
+    }
+
+    /// Any number as a float
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::U64(v) => Some(*v as f64),
+            JsonValue::I64(v) => Some(*v as f64),
+            JsonValue::F64(v) => Some(*v),
+            _ => None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<JsonValue>> {
+        if let JsonValue::Array(v) = self {Some(v)} else {None}
+    }
+
+    pub fn as_object(&self) -> Option<&Vec<(String, JsonValue)>> {
+        if let JsonValue::Object(v) = self {Some(v)} else {None}
+    }
+}
+
+impl SerJson for JsonValue {
+    fn ser_json(&self, d: usize, s: &mut SerJsonState) {
+        match self {
+            JsonValue::Null => s.out.push_str("null"),
+            JsonValue::Bool(v) => v.ser_json(d, s),
+            JsonValue::U64(v) => v.ser_json(d, s),
+            JsonValue::I64(v) => v.ser_json(d, s),
+            JsonValue::F64(v) => v.ser_json(d, s),
+            JsonValue::String(v) => v.ser_json(d, s),
+            JsonValue::Array(v) => v.ser_json(d, s),
+            JsonValue::Object(members) => {
+                s.st_pre();
+                let mut first = true;
+                for (key, value) in members {
+                    s.field_sep(&mut first);
+                    s.indent(d + 1);
+                    key.ser_json(d + 1, s);
+                    s.out.push(':');
+                    value.ser_json(d + 1, s);
+                }
+                s.st_post(d);
+            }
+        }
+    }
+}
+
+impl DeJson for JsonValue {
+    fn de_json(s: &mut DeJsonState, i: &mut Chars) -> Result<Self, DeJsonErr> {
+        let value = match s.tok {
+            DeJsonTok::CurlyOpen => {
+                let mut members = Vec::new();
+                s.curly_open(i) ?;
+                while s.tok != DeJsonTok::CurlyClose {
+                    let key = s.as_string() ?;
+                    s.next_colon(i).map_err( | e | e.in_key(&key)) ?;
+                    let value = DeJson::de_json(s, i).map_err( | e | e.in_key(&key)) ?;
+                    members.push((key, value));
+                    s.eat_comma_curly(i) ?;
+                }
+                s.curly_close(i) ?;
+                return Ok(JsonValue::Object(members))
+            }
+            DeJsonTok::BlockOpen => return Ok(JsonValue::Array(DeJson::de_json(s, i) ?)),
+            DeJsonTok::Str => JsonValue::String(s.as_string() ?),
+            DeJsonTok::U64(v) => JsonValue::U64(v),
+            DeJsonTok::I64(v) => JsonValue::I64(v),
+            DeJsonTok::F64(v) => JsonValue::F64(v),
+            DeJsonTok::Bool(v) => JsonValue::Bool(v),
+            DeJsonTok::Null => JsonValue::Null,
+            _ => return Err(s.err_token("json value"))
+        };
+        s.next_tok(i) ?;
+        Ok(value)
+    }
+}
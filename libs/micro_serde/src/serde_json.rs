@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 use std::hash::Hash;
 use std::str::Chars;
+use std::fmt::Write;
+use crate::json_value::JsonValue;
 
 pub struct SerJsonState {
     pub out: String
@@ -40,6 +42,42 @@ impl SerJsonState {
         self.out.push('}');
     }
     
+    /// Writes the comma before every field of an object but the first
+    pub fn field_sep(&mut self, first: &mut bool) {
+        if !*first {
+            self.conl();
+        }
+        *first = false;
+    }
+    
+    /// Writes the fields of an object valued field into the object being written, used by #[flatten]
+    pub fn flatten<T: SerJson + ?Sized>(&mut self, d: usize, value: &T, first: &mut bool) {
+        let mut inner = SerJsonState {out: String::new()};
+        value.ser_json(d, &mut inner);
+        if let Some(fields) = inner.out.strip_prefix('{').and_then( | v | v.strip_suffix('}')) {
+            if !fields.is_empty() {
+                self.field_sep(first);
+                self.out.push_str(fields);
+            }
+        }
+    }
+}
+
+fn ser_json_str(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c{
+            '\n'=>{out.push('\\');out.push('n');},
+            '\r'=>{out.push('\\');out.push('r');},
+            '\t'=>{out.push('\\');out.push('t');},
+            '\\'=>{out.push('\\');out.push('\\');},
+            '"'=>{out.push('\\');out.push('"');},
+            // other control chars, like '\0', can only be written as an escape in json
+            _ if c.is_control() =>{let _ = write!(out, "\\u{:04x}", c as u32);},
+            _=>out.push(c)
+        }
+    }
+    out.push('"');
 }
 
 pub trait SerJson {
@@ -102,21 +140,71 @@ pub struct DeJsonState {
     pub numbuf:String,
     pub identbuf:String,
     pub line: usize,
-    pub col: usize
+    pub col: usize,
+    // where the current token starts, errors are reported there
+    pub tok_line: usize,
+    pub tok_col: usize,
 }
 
 pub struct DeJsonErr{
     pub msg:String,
+    /// 1 based position of the token the error happened at
     pub line:usize,
-    pub col:usize
+    pub col:usize,
+    /// the path of the failing value in the document, like choices[0].message.content
+    pub path:String,
+    pub expected:Option<String>,
+    pub found:Option<String>,
+}
+
+impl DeJsonErr {
+    pub fn new(msg: &str) -> Self {
+        DeJsonErr {msg: msg.to_string(), line: 0, col: 0, path: String::new(), expected: None, found: None}
+    }
+    
+    /// Adds the object key the error happened under to the front of the path
+    pub fn in_key(mut self, key: &str) -> Self {
+        self.path = join_err_path(key, &self.path);
+        self
+    }
+    
+    /// Adds the array index the error happened under to the front of the path
+    pub fn in_index(mut self, index: usize) -> Self {
+        self.path = join_err_path(&format!("[{}]", index), &self.path);
+        self
+    }
+}
+
+pub(crate) fn join_err_path(outer: &str, inner: &str) -> String {
+    if inner.is_empty() {
+        outer.to_string()
+    }
+    else if inner.starts_with('[') {
+        format!("{}{}", outer, inner)
+    }
+    else {
+        format!("{}.{}", outer, inner)
+    }
+}
+
+impl std::fmt::Display for DeJsonErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.msg)?;
+        if !self.path.is_empty() {
+            write!(f, " at {}", self.path)?;
+        }
+        write!(f, " (line {} column {})", self.line, self.col)
+    }
 }
 
 impl std::fmt::Debug for DeJsonErr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Json Deserialize error: {}, line:{} col:{}", self.msg, self.line+1, self.col+1)
+        write!(f, "Json Deserialize error: {}", self)
     }
 }
 
+impl std::error::Error for DeJsonErr {}
+
 impl DeJsonState {
     pub fn next(&mut self, i: &mut Chars) {
         if let Some(c) = i.next() {
@@ -134,32 +222,105 @@ impl DeJsonState {
         }
     }
     
+    fn err(&self, msg: String, expected: Option<String>, found: Option<String>) -> DeJsonErr {
+        DeJsonErr{msg, line:self.tok_line + 1, col:self.tok_col, path:String::new(), expected, found}
+    }
+    
+    /// Describes the current token for error messages
+    pub fn tok_desc(&self) -> String {
+        match &self.tok {
+            DeJsonTok::Str => {
+                if self.strbuf.chars().count() > 32 {
+                    format!("string \"{}...\"", self.strbuf.chars().take(32).collect::<String>())
+                }
+                else {
+                    format!("string \"{}\"", self.strbuf)
+                }
+            }
+            DeJsonTok::Char(c) => format!("'{}'", c),
+            DeJsonTok::U64(v) => format!("number {}", v),
+            DeJsonTok::I64(v) => format!("number {}", v),
+            DeJsonTok::F64(v) => format!("number {}", v),
+            DeJsonTok::Bool(v) => format!("{}", v),
+            DeJsonTok::BareIdent => format!("identifier {}", self.identbuf),
+            DeJsonTok::Null => "null".to_string(),
+            DeJsonTok::Colon => "':'".to_string(),
+            DeJsonTok::CurlyOpen => "'{'".to_string(),
+            DeJsonTok::CurlyClose => "'}'".to_string(),
+            DeJsonTok::BlockOpen => "'['".to_string(),
+            DeJsonTok::BlockClose => "']'".to_string(),
+            DeJsonTok::Comma => "','".to_string(),
+            DeJsonTok::Bof => "start of input".to_string(),
+            DeJsonTok::Eof => "end of input".to_string(),
+        }
+    }
+    
     pub fn err_exp(&self, name: &str) -> DeJsonErr {
-        DeJsonErr{msg:format!("Unexpected key {}", name), line:self.line, col:self.col}
+        self.err(format!("Unexpected key {}", name), None, Some(format!("key {}", name)))
     }
     
     pub fn err_nf(&self, name: &str) -> DeJsonErr {
-        DeJsonErr{msg:format!("Key not found {}", name), line:self.line, col:self.col}
+        self.err(format!("Key not found {}", name), Some(format!("key {}", name)), None)
     }
 
     pub fn err_enum(&self, name: &str) -> DeJsonErr {
-        DeJsonErr{msg:format!("Enum not defined {}", name), line:self.line, col:self.col}
+        self.err(format!("Enum not defined {}", name), None, Some(name.to_string()))
     }
 
     pub fn err_token(&self, what:&str) -> DeJsonErr {
-        DeJsonErr{msg:format!("Unexpected token {:?} expected {} ", self.tok, what), line:self.line, col:self.col}
+        let found = self.tok_desc();
+        self.err(format!("Expected {}, found {}", what, found), Some(what.to_string()), Some(found))
     }
 
     pub fn err_range(&self, what:&str) -> DeJsonErr {
-        DeJsonErr{msg:format!("Value out of range {} ", what), line:self.line, col:self.col}
+        self.err(format!("Value out of range {}", what), None, Some(self.tok_desc()))
     }
 
     pub fn err_type(&self, what:&str) -> DeJsonErr {
-        DeJsonErr{msg:format!("Token wrong type {} ", what), line:self.line, col:self.col}
+        let found = self.tok_desc();
+        self.err(format!("Token wrong type, expected {}, found {}", what, found), Some(what.to_string()), Some(found))
     }
 
     pub fn err_parse(&self, what:&str) -> DeJsonErr {
-        DeJsonErr{msg:format!("Cannot parse {} ", what), line:self.line, col:self.col}
+        self.err(format!("Cannot parse {}", what), Some(what.to_string()), None)
+    }
+    
+    // Reads a \uXXXX escape and the low half of a surrogate pair that follows it. The current char
+    // is the 'u', after this it is the first char after the escape. Surrogates that are not part
+    // of a pair are not chars, so they are an error
+    fn hex_escape(&mut self, i: &mut Chars) -> Result<char, DeJsonErr> {
+        let mut code = self.hex_digits(i) ?;
+        if (0xd800..0xdc00).contains(&code) {
+            if self.cur != '\\' {
+                return Err(self.err_parse("surrogate pair"));
+            }
+            self.next(i);
+            if self.cur != 'u' {
+                return Err(self.err_parse("surrogate pair"));
+            }
+            let low = self.hex_digits(i) ?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err(self.err_parse("surrogate pair"));
+            }
+            code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+        }
+        match char::from_u32(code) {
+            Some(c) => Ok(c),
+            None => Err(self.err_parse("surrogate pair"))
+        }
+    }
+    
+    fn hex_digits(&mut self, i: &mut Chars) -> Result<u32, DeJsonErr> {
+        let mut code = 0;
+        for _ in 0..4 {
+            self.next(i);
+            match self.cur.to_digit(16) {
+                Some(digit) => code = code * 16 + digit,
+                None => return Err(self.err_parse("string"))
+            }
+        }
+        self.next(i);
+        Ok(code)
     }
     
     pub fn eat_comma_block(&mut self, i: &mut Chars) -> Result<(), DeJsonErr> {
@@ -323,10 +484,115 @@ impl DeJsonState {
         Err(self.err_token("string"))
     }
     
+    /// Moves past the current value and writes it back out as json
+    pub fn capture_value(&mut self, i: &mut Chars, out: &mut String) -> Result<(), DeJsonErr> {
+        match self.tok {
+            DeJsonTok::CurlyOpen => {
+                out.push('{');
+                self.next_tok(i) ?;
+                while self.tok != DeJsonTok::CurlyClose {
+                    let key = self.as_string() ?;
+                    ser_json_str(out, &key);
+                    out.push(':');
+                    self.next_colon(i) ?;
+                    self.capture_value(i, out) ?;
+                    self.eat_comma_curly(i) ?;
+                    if self.tok != DeJsonTok::CurlyClose {
+                        out.push(',');
+                    }
+                }
+                out.push('}');
+            }
+            DeJsonTok::BlockOpen => {
+                out.push('[');
+                self.next_tok(i) ?;
+                while self.tok != DeJsonTok::BlockClose {
+                    self.capture_value(i, out) ?;
+                    self.eat_comma_block(i) ?;
+                    if self.tok != DeJsonTok::BlockClose {
+                        out.push(',');
+                    }
+                }
+                out.push(']');
+            }
+            DeJsonTok::Str => ser_json_str(out, &self.strbuf),
+            DeJsonTok::U64(_) | DeJsonTok::I64(_) | DeJsonTok::F64(_) => out.push_str(&self.numbuf),
+            DeJsonTok::Bool(v) => out.push_str(if v {"true"} else {"false"}),
+            DeJsonTok::Null => out.push_str("null"),
+            _ => return Err(self.err_token("value"))
+        }
+        self.next_tok(i)
+    }
+    
+    /// Reads an object as its keys with their values as json text, so the members can be
+    /// looked at in any order. Used by tagged enums
+    pub fn capture_members(&mut self, i: &mut Chars) -> Result<Vec<(String, String)>, DeJsonErr> {
+        let mut members = Vec::new();
+        self.curly_open(i) ?;
+        while self.tok != DeJsonTok::CurlyClose {
+            let key = self.as_string() ?;
+            self.next_colon(i) ?;
+            let mut value = String::new();
+            self.capture_value(i, &mut value) ?;
+            self.eat_comma_curly(i) ?;
+            members.push((key, value));
+        }
+        self.curly_close(i) ?;
+        Ok(members)
+    }
+    
+    /// Reads the members of an object captured with capture_value
+    pub fn members_of(&self, value: &str) -> Result<Vec<(String, String)>, DeJsonErr> {
+        let mut state = DeJsonState::default();
+        let mut chars = value.chars();
+        state.next(&mut chars);
+        state.next_tok(&mut chars).and_then( | _ | state.capture_members(&mut chars))
+            .map_err( | e | DeJsonErr {line: self.tok_line + 1, col: self.tok_col, ..e})
+    }
+    
+    /// Removes a member captured with capture_members
+    pub fn take_member(&self, members: &mut Vec<(String, String)>, key: &str) -> Option<String> {
+        let index = members.iter().position( | (k, _) | k == key) ?;
+        Some(members.remove(index).1)
+    }
+    
+    /// Removes the string valued tag member of a tagged enum
+    pub fn take_tag(&self, members: &mut Vec<(String, String)>, key: &str) -> Result<String, DeJsonErr> {
+        if let Some(value) = self.take_member(members, key) {
+            self.de_captured(&value)
+        }
+        else {
+            Err(self.err_nf(key))
+        }
+    }
+    
+    /// Deserializes a value captured with capture_value, errors are reported at the current position
+    pub fn de_captured<T: DeJson>(&self, value: &str) -> Result<T, DeJsonErr> {
+        T::deserialize_json(value).map_err( | e | DeJsonErr {line: self.tok_line + 1, col: self.tok_col, ..e})
+    }
+    
+    /// Deserializes the object made up of captured members, used by #[flatten] and tagged enums
+    pub fn de_members<T: DeJson>(&self, members: &[(String, String)]) -> Result<T, DeJsonErr> {
+        let mut out = String::new();
+        out.push('{');
+        for (index, (key, value)) in members.iter().enumerate() {
+            if index != 0 {
+                out.push(',');
+            }
+            ser_json_str(&mut out, key);
+            out.push(':');
+            out.push_str(value);
+        }
+        out.push('}');
+        self.de_captured(&out)
+    }
+    
     pub fn next_tok(&mut self, i: &mut Chars) -> Result<(), DeJsonErr> {
         while self.cur == '\n' || self.cur == '\r' || self.cur == '\t' || self.cur == ' ' {
             self.next(i);
         }
+        self.tok_line = self.line;
+        self.tok_col = self.col;
         if self.cur == '\0' {
             self.tok = DeJsonTok::Eof;
             return Ok(())
@@ -431,7 +697,7 @@ impl DeJsonState {
                     return Ok(())
                 }
                 self.tok = DeJsonTok::BareIdent;
-                Err(self.err_token(&format!("Got ##{}## needed true, false, null", self.identbuf)))
+                Err(self.err_token("true, false or null"))
             }
             '"' => {
                 self.strbuf.clear();
@@ -444,6 +710,13 @@ impl DeJsonState {
                             'r'=>self.strbuf.push('\r'),
                             't'=>self.strbuf.push('\t'),
                             '0'=>self.strbuf.push('\0'),
+                            'b'=>self.strbuf.push('\u{8}'),
+                            'f'=>self.strbuf.push('\u{c}'),
+                            'u'=>{
+                                let c = self.hex_escape(i) ?;
+                                self.strbuf.push(c);
+                                continue;
+                            }
                             '\0'=>{
                                 return Err(self.err_parse("string"));
                             },
@@ -466,7 +739,198 @@ impl DeJsonState {
                 Ok(())
             },
             _ => {
-                Err(self.err_token("tokenizer"))
+                Err(self.err(format!("Unexpected character '{}'", self.cur), None, Some(format!("'{}'", self.cur))))
+            }
+        }
+    }
+}
+
+/// Pulls json tokens out of text that arrives in pieces, like a response body or a
+/// server-sent event stream. Tokens land in state.tok / state.strbuf just like they do for DeJson,
+/// next_tok and next_value return false / None when they need more text
+#[derive(Default)]
+pub struct DeJsonStream {
+    pub state: DeJsonState,
+    buf: String,
+    pos: usize,
+    pending: Vec<u8>,
+    finished: bool,
+    // arrays and objects next_value is in the middle of, with the key an object member is waiting on
+    stack: Vec<(JsonValue, Option<String>)>,
+    expect_colon: bool,
+    // the innermost array or object just got a value, so a comma or its end has to follow
+    expect_comma: bool,
+    // the last token was a comma, so a value has to follow
+    after_comma: bool,
+}
+
+impl DeJsonStream {
+    pub fn push_str(&mut self, chunk: &str) {
+        self.compact();
+        self.buf.push_str(chunk);
+    }
+    
+    /// Adds raw bytes, a utf8 sequence split across chunks is held back until it is complete
+    pub fn push_bytes(&mut self, chunk: &[u8]) {
+        self.compact();
+        self.pending.extend_from_slice(chunk);
+        let mut start = 0;
+        loop {
+            match std::str::from_utf8(&self.pending[start..]) {
+                Ok(v) => {
+                    self.buf.push_str(v);
+                    self.pending.clear();
+                    return
+                }
+                Err(e) => {
+                    let valid = start + e.valid_up_to();
+                    self.buf.push_str(std::str::from_utf8(&self.pending[start..valid]).unwrap());
+                    if let Some(len) = e.error_len() {
+                        self.buf.push(char::REPLACEMENT_CHARACTER);
+                        start = valid + len;
+                    }
+                    else {
+                        self.pending.drain(..valid);
+                        return
+                    }
+                }
+            }
+        }
+    }
+    
+    /// Marks the end of the input, so a trailing number can complete and Eof is produced
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+    
+    fn compact(&mut self) {
+        if self.pos > 0 && self.pos * 2 > self.buf.len() {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+        }
+    }
+    
+    // where the next token ends, if all of it is in the buffer
+    fn token_end(&self) -> Option<usize> {
+        let b = self.buf.as_bytes();
+        let mut o = self.pos;
+        while o < b.len() && matches!(b[o], b' ' | b'\t' | b'\r' | b'\n') {
+            o += 1;
+        }
+        if o == b.len() {
+            return if self.finished {Some(o)} else {None}
+        }
+        match b[o] {
+            b'"' => {
+                o += 1;
+                while o < b.len() {
+                    match b[o] {
+                        b'\\' => o += 2,
+                        b'"' => return Some(o + 1),
+                        _ => o += 1
+                    }
+                }
+                None
+            }
+            b'-' | b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' | b'_' => {
+                while o < b.len() && matches!(b[o], b'-' | b'+' | b'.' | b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' | b'_') {
+                    o += 1;
+                }
+                if o == b.len() && !self.finished {None} else {Some(o)}
+            }
+            _ => Some(o + 1)
+        }
+    }
+    
+    /// Reads the next token into state, returns false when the buffer doesn't hold a whole token yet
+    pub fn next_tok(&mut self) -> Result<bool, DeJsonErr> {
+        let end = if let Some(end) = self.token_end() {end} else {return Ok(false)};
+        let mut chars = self.buf[self.pos..end].chars();
+        self.pos = end;
+        self.state.next(&mut chars);
+        self.state.next_tok(&mut chars) ?;
+        Ok(true)
+    }
+    
+    /// Reads the next whole value, returns None when it needs more text or the input has ended.
+    /// Partially read arrays and objects are kept, so this can be called again after every chunk
+    pub fn next_value(&mut self) -> Result<Option<JsonValue>, DeJsonErr> {
+        loop {
+            if !self.next_tok() ? {
+                return Ok(None)
+            }
+            if self.expect_colon {
+                if self.state.tok != DeJsonTok::Colon {
+                    return Err(self.state.err_token(":"))
+                }
+                self.expect_colon = false;
+                continue;
+            }
+            match self.state.tok {
+                DeJsonTok::Comma => {
+                    if self.stack.is_empty() || !self.expect_comma {
+                        return Err(self.state.err_token("value"))
+                    }
+                    self.expect_comma = false;
+                    self.after_comma = true;
+                    continue;
+                }
+                DeJsonTok::CurlyClose | DeJsonTok::BlockClose => if self.after_comma {
+                    return Err(self.state.err_token("value"))
+                },
+                DeJsonTok::Eof => (),
+                _ => if self.expect_comma {
+                    return Err(self.state.err_token(","))
+                }
+            }
+            self.after_comma = false;
+            let value = match self.state.tok {
+                DeJsonTok::CurlyOpen => {
+                    self.stack.push((JsonValue::Object(Vec::new()), None));
+                    continue;
+                }
+                DeJsonTok::BlockOpen => {
+                    self.stack.push((JsonValue::Array(Vec::new()), None));
+                    continue;
+                }
+                DeJsonTok::CurlyClose => match self.stack.pop() {
+                    Some((value @ JsonValue::Object(_), None)) => value,
+                    _ => return Err(self.state.err_token("value"))
+                },
+                DeJsonTok::BlockClose => match self.stack.pop() {
+                    Some((value @ JsonValue::Array(_), _)) => value,
+                    _ => return Err(self.state.err_token("value"))
+                },
+                DeJsonTok::Str => {
+                    let value = self.state.as_string() ?;
+                    if let Some((JsonValue::Object(_), key @ None)) = self.stack.last_mut() {
+                        *key = Some(value);
+                        self.expect_colon = true;
+                        continue;
+                    }
+                    JsonValue::String(value)
+                }
+                DeJsonTok::U64(v) => JsonValue::U64(v),
+                DeJsonTok::I64(v) => JsonValue::I64(v),
+                DeJsonTok::F64(v) => JsonValue::F64(v),
+                DeJsonTok::Bool(v) => JsonValue::Bool(v),
+                DeJsonTok::Null => JsonValue::Null,
+                DeJsonTok::Eof if self.stack.is_empty() => return Ok(None),
+                _ => return Err(self.state.err_token("json value"))
+            };
+            self.expect_comma = !self.stack.is_empty();
+            match self.stack.last_mut() {
+                None => return Ok(Some(value)),
+                Some((JsonValue::Array(items), _)) => items.push(value),
+                Some((JsonValue::Object(members), key)) => {
+                    if let Some(key) = key.take() {
+                        members.push((key, value));
+                    }
+                    else {
+                        return Err(self.state.err_token("key"))
+                    }
+                }
+                _ => unreachable!()
             }
         }
     }
@@ -586,19 +1050,7 @@ impl DeJson for bool {
 
 impl SerJson for String {
     fn ser_json(&self, _d: usize, s: &mut SerJsonState) {
-        s.out.push('"');
-        for c in self.chars() {
-            match c{
-                '\n'=>{s.out.push('\\');s.out.push('n');},
-                '\r'=>{s.out.push('\\');s.out.push('r');},
-                '\t'=>{s.out.push('\\');s.out.push('t');},
-                '\0'=>{s.out.push('\\');s.out.push('0');},
-                '\\'=>{s.out.push('\\');s.out.push('\\');},
-                '"'=>{s.out.push('\\');s.out.push('"');},
-                _=>s.out.push(c)
-            }
-        }
-        s.out.push('"');
+        ser_json_str(&mut s.out, self);
     }
 }
 
@@ -633,7 +1085,8 @@ impl<T> DeJson for Vec<T> where T: DeJson {
         s.block_open(i) ?;
         
         while s.tok != DeJsonTok::BlockClose {
-            out.push(DeJson::de_json(s, i) ?);
+            let index = out.len();
+            out.push(DeJson::de_json(s, i).map_err( | e | e.in_index(index)) ?);
             s.eat_comma_block(i) ?;
         }
         s.block_close(i) ?;
@@ -658,7 +1111,7 @@ impl<T> SerJson for [T] where T: SerJson {
 unsafe fn de_json_array_impl_inner<T>(top: *mut T, count: usize, s: &mut DeJsonState, i: &mut Chars) -> Result<(), DeJsonErr> where T:DeJson{
     s.block_open(i) ?;
     for c in 0..count {
-        top.add(c).write(DeJson::de_json(s, i) ?);
+        top.add(c).write(DeJson::de_json(s, i).map_err( | e | e.in_index(c)) ?);
         s.eat_comma_block(i) ?;
     }
     s.block_close(i) ?;
@@ -677,10 +1130,10 @@ impl<T, const N: usize> DeJson for [T; N] where T: DeJson {
     }
 }
 
-fn de_json_comma_block<T>(s: &mut DeJsonState, i: &mut Chars) -> Result<T, DeJsonErr> where T: DeJson {
-    let t = DeJson::de_json(s, i);
+fn de_json_comma_block<T>(s: &mut DeJsonState, i: &mut Chars, index: usize) -> Result<T, DeJsonErr> where T: DeJson {
+    let t = DeJson::de_json(s, i).map_err( | e | e.in_index(index)) ?;
     s.eat_comma_block(i) ?;
-    t
+    Ok(t)
 }
 
 impl<A, B> SerJson for (A, B) where A: SerJson,
@@ -698,7 +1151,7 @@ impl<A, B> DeJson for (A, B) where A: DeJson,
 B: DeJson {
     fn de_json(s: &mut DeJsonState, i: &mut Chars) -> Result<(A, B), DeJsonErr> {
         s.block_open(i) ?;
-        let r = (de_json_comma_block(s, i) ?, de_json_comma_block(s, i) ?);
+        let r = (de_json_comma_block(s, i, 0) ?, de_json_comma_block(s, i, 1) ?);
         s.block_close(i) ?;
         Ok(r)
     }
@@ -723,7 +1176,7 @@ B: DeJson,
 C: DeJson {
     fn de_json(s: &mut DeJsonState, i: &mut Chars) -> Result<(A, B, C), DeJsonErr> {
         s.block_open(i) ?;
-        let r = (de_json_comma_block(s, i) ?, de_json_comma_block(s, i) ?, de_json_comma_block(s, i) ?);
+        let r = (de_json_comma_block(s, i, 0) ?, de_json_comma_block(s, i, 1) ?, de_json_comma_block(s, i, 2) ?);
         s.block_close(i) ?;
         Ok(r)
     }
@@ -752,7 +1205,7 @@ C: DeJson,
 D: DeJson {
     fn de_json(s: &mut DeJsonState, i: &mut Chars) -> Result<(A, B, C, D), DeJsonErr> {
         s.block_open(i) ?;
-        let r = (de_json_comma_block(s, i) ?, de_json_comma_block(s, i) ?, de_json_comma_block(s, i) ?, de_json_comma_block(s, i) ?);
+        let r = (de_json_comma_block(s, i, 0) ?, de_json_comma_block(s, i, 1) ?, de_json_comma_block(s, i, 2) ?, de_json_comma_block(s, i, 3) ?);
         s.block_close(i) ?;
         Ok(r)
     }
@@ -784,9 +1237,12 @@ V: DeJson  {
         let mut h = HashMap::new();
         s.curly_open(i) ?;
         while s.tok != DeJsonTok::CurlyClose {
+            // string keys go into the error path
+            let key = if s.tok == DeJsonTok::Str {Some(s.strbuf.clone())} else {None};
+            let in_key = | e: DeJsonErr | if let Some(key) = &key {e.in_key(key)} else {e};
             let k = DeJson::de_json(s, i) ?;
-            s.colon(i) ?;
-            let v = DeJson::de_json(s, i) ?;
+            s.colon(i).map_err(in_key) ?;
+            let v = DeJson::de_json(s, i).map_err(in_key) ?;
             s.eat_comma_curly(i) ?;
             h.insert(k, v);
         }
@@ -806,3 +1262,107 @@ impl<T> DeJson for Box<T> where T: DeJson {
         Ok(Box::new(DeJson::de_json(s, i) ?))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn string_escapes() {
+        let s = String::deserialize_json(r#""a\"\\\/\b\f\n\r\t\u0041\u00e9\u20AC""#).unwrap();
+        assert_eq!(s, "a\"\\/\u{8}\u{c}\n\r\tAé€");
+        // a surrogate pair
+        assert_eq!(String::deserialize_json(r#""\ud83d\ude00!""#).unwrap(), "\u{1f600}!");
+        // control chars are written as escapes, and read back the same
+        let s = "\0\u{1}\u{1f}\n".to_string();
+        assert_eq!(s.serialize_json(), r#""\u0000\u0001\u001f\n""#);
+        assert_eq!(String::deserialize_json(&s.serialize_json()).unwrap(), s);
+    }
+
+    #[test]
+    fn reject_invalid_escapes() {
+        for input in [
+            // a high surrogate that is not followed by a low one
+            r#""\ud83d""#,
+            r#""\ud83dx""#,
+            r#""\ud83d\n""#,
+            r#""\ud83d\u0041""#,
+            r#""\ud83d\ud83d""#,
+            // a low surrogate on its own
+            r#""\ude00""#,
+            // too few hex digits
+            r#""\u12""#,
+            r#""\u12g4""#,
+            r#""\u"#,
+        ] {
+            assert!(String::deserialize_json(input).is_err(), "{}", input);
+        }
+    }
+    
+    // streams the input one char at a time
+    fn stream_values(input: &str) -> Result<Vec<JsonValue>, DeJsonErr> {
+        let mut stream = DeJsonStream::default();
+        let mut values = Vec::new();
+        for c in input.chars() {
+            stream.push_str(&c.to_string());
+            while let Some(value) = stream.next_value() ? {
+                values.push(value);
+            }
+        }
+        stream.finish();
+        while let Some(value) = stream.next_value() ? {
+            values.push(value);
+        }
+        Ok(values)
+    }
+    
+    #[test]
+    fn stream() {
+        let values = stream_values(r#"{"a":[1,-2,3.5],"b":{},"c":[]} "é" [true,null,[{"d":"\""}]] 7"#).unwrap();
+        assert_eq!(values.len(), 4);
+        assert_eq!(values[0].path("a.1"), Some(&JsonValue::I64(-2)));
+        assert_eq!(values[0].get("b"), Some(&JsonValue::Object(Vec::new())));
+        assert_eq!(values[1], JsonValue::String("é".to_string()));
+        assert_eq!(values[2].path("2.0.d").and_then(|v| v.as_str()), Some("\""));
+        assert_eq!(values[3], JsonValue::U64(7));
+        // utf8 split across chunks
+        let mut stream = DeJsonStream::default();
+        let bytes = "\"é\"".as_bytes();
+        stream.push_bytes(&bytes[..2]);
+        assert_eq!(stream.next_value().unwrap(), None);
+        stream.push_bytes(&bytes[2..]);
+        assert_eq!(stream.next_value().unwrap(), Some(JsonValue::String("é".to_string())));
+    }
+    
+    #[test]
+    fn reject_malformed_streams() {
+        for input in [
+            // commas that aren't between two values
+            "[1,,2]",
+            "[,1]",
+            "[1,]",
+            "{,}",
+            r#"{"a":1,,"b":2}"#,
+            r#"{"a":1,}"#,
+            ",1",
+            "1,2",
+            // values without a comma between them
+            "[1 2]",
+            r#"{"a":1 "b":2}"#,
+            // broken members
+            r#"{"a" 1}"#,
+            r#"{"a":}"#,
+            r#"{"a"}"#,
+            "{1:2}",
+            // mismatched or missing brackets
+            "[1}",
+            r#"{"a":1]"#,
+            "]",
+            "}",
+            "[1",
+            r#"{"a":"#,
+        ] {
+            assert!(stream_values(input).is_err(), "{}", input);
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::hash::Hash;
 use std::str::Chars;
+use crate::serde_json::join_err_path;
 
 pub struct SerRonState {
     pub out: String
@@ -95,21 +96,59 @@ pub struct DeRonState {
     pub numbuf: String,
     pub identbuf: String,
     pub line: usize,
-    pub col: usize
+    pub col: usize,
+    // where the current token starts, errors are reported there
+    pub tok_line: usize,
+    pub tok_col: usize,
 }
 
 pub struct DeRonErr {
     pub msg: String,
+    /// 1 based position of the token the error happened at
     pub line: usize,
-    pub col: usize
+    pub col: usize,
+    /// the path of the failing value in the document, like choices[0].message.content
+    pub path: String,
+    pub expected: Option<String>,
+    pub found: Option<String>,
+}
+
+impl DeRonErr {
+    pub fn new(msg: &str) -> Self {
+        DeRonErr {msg: msg.to_string(), line: 0, col: 0, path: String::new(), expected: None, found: None}
+    }
+    
+    /// Adds the field or key the error happened under to the front of the path
+    pub fn in_key(mut self, key: &str) -> Self {
+        self.path = join_err_path(key, &self.path);
+        self
+    }
+    
+    /// Adds the array or tuple index the error happened under to the front of the path
+    pub fn in_index(mut self, index: usize) -> Self {
+        self.path = join_err_path(&format!("[{}]", index), &self.path);
+        self
+    }
+}
+
+impl std::fmt::Display for DeRonErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.msg) ?;
+        if !self.path.is_empty() {
+            write!(f, " at {}", self.path) ?;
+        }
+        write!(f, " (line {} column {})", self.line, self.col)
+    }
 }
 
 impl std::fmt::Debug for DeRonErr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Ron Deserialize error: {}, line:{} col:{}", self.msg, self.line + 1, self.col + 1)
+        write!(f, "Ron Deserialize error: {}", self)
     }
 }
 
+impl std::error::Error for DeRonErr {}
+
 impl DeRonState {
     pub fn next(&mut self, i: &mut Chars) {
         if let Some(c) = i.next() {
@@ -119,7 +158,7 @@ impl DeRonState {
                 self.col = 0;
             }
             else {
-                self.col = 0;
+                self.col += 1;
             }
         }
         else {
@@ -127,32 +166,68 @@ impl DeRonState {
         }
     }
     
+    fn err(&self, msg: String, expected: Option<String>, found: Option<String>) -> DeRonErr {
+        DeRonErr {msg, line: self.tok_line + 1, col: self.tok_col, path: String::new(), expected, found}
+    }
+    
+    /// Describes the current token for error messages
+    pub fn tok_desc(&self) -> String {
+        match &self.tok {
+            DeRonTok::Ident => format!("identifier {}", self.identbuf),
+            DeRonTok::Str => {
+                if self.strbuf.chars().count() > 32 {
+                    format!("string \"{}...\"", self.strbuf.chars().take(32).collect::<String>())
+                }
+                else {
+                    format!("string \"{}\"", self.strbuf)
+                }
+            }
+            DeRonTok::U64(v) => format!("number {}", v),
+            DeRonTok::I64(v) => format!("number {}", v),
+            DeRonTok::F64(v) => format!("number {}", v),
+            DeRonTok::Bool(v) => format!("{}", v),
+            DeRonTok::Char(c) => format!("'{}'", c),
+            DeRonTok::Colon => "':'".to_string(),
+            DeRonTok::CurlyOpen => "'{'".to_string(),
+            DeRonTok::CurlyClose => "'}'".to_string(),
+            DeRonTok::ParenOpen => "'('".to_string(),
+            DeRonTok::ParenClose => "')'".to_string(),
+            DeRonTok::BlockOpen => "'['".to_string(),
+            DeRonTok::BlockClose => "']'".to_string(),
+            DeRonTok::Comma => "','".to_string(),
+            DeRonTok::Bof => "start of input".to_string(),
+            DeRonTok::Eof => "end of input".to_string(),
+        }
+    }
+    
     pub fn err_exp(&self, name: &str) -> DeRonErr {
-        DeRonErr {msg: format!("Unexpected key {}", name), line: self.line, col: self.col}
+        self.err(format!("Unexpected key {}", name), None, Some(format!("key {}", name)))
     }
     
     pub fn err_nf(&self, name: &str) -> DeRonErr {
-        DeRonErr {msg: format!("Key not found {}", name), line: self.line, col: self.col}
+        self.err(format!("Key not found {}", name), Some(format!("key {}", name)), None)
     }
     
     pub fn err_enum(&self, name: &str) -> DeRonErr {
-        DeRonErr {msg: format!("Enum not defined {}", name), line: self.line, col: self.col}
+        self.err(format!("Enum not defined {}", name), None, Some(name.to_string()))
     }
     
     pub fn err_token(&self, what: &str) -> DeRonErr {
-        DeRonErr {msg: format!("Unexpected token {:?} expected {} ", self.tok, what), line: self.line, col: self.col}
+        let found = self.tok_desc();
+        self.err(format!("Expected {}, found {}", what, found), Some(what.to_string()), Some(found))
     }
     
     pub fn err_range(&self, what: &str) -> DeRonErr {
-        DeRonErr {msg: format!("Value out of range {} ", what), line: self.line, col: self.col}
+        self.err(format!("Value out of range {}", what), None, Some(self.tok_desc()))
     }
     
     pub fn err_type(&self, what: &str) -> DeRonErr {
-        DeRonErr {msg: format!("Token wrong type {} ", what), line: self.line, col: self.col}
+        let found = self.tok_desc();
+        self.err(format!("Token wrong type, expected {}, found {}", what, found), Some(what.to_string()), Some(found))
     }
     
     pub fn err_parse(&self, what: &str) -> DeRonErr {
-        DeRonErr {msg: format!("Cannot parse {} ", what), line: self.line, col: self.col}
+        self.err(format!("Cannot parse {}", what), Some(what.to_string()), None)
     }
     
     pub fn eat_comma_paren(&mut self, i: &mut Chars) -> Result<(), DeRonErr> {
@@ -354,6 +429,8 @@ impl DeRonState {
             while self.cur == '\n' || self.cur == '\r' || self.cur == '\t' || self.cur == ' ' {
                 self.next(i);
             }
+            self.tok_line = self.line;
+            self.tok_col = self.col;
             match self.cur {
                 '\0' => {
                     self.tok = DeRonTok::Eof;
@@ -536,7 +613,7 @@ impl DeRonState {
                     return Ok(())
                 },
                 _ => {
-                    return Err(self.err_token("tokenizer"));
+                    return Err(self.err(format!("Unexpected character '{}'", self.cur), None, Some(format!("'{}'", self.cur))));
                 }
             }
         }
@@ -703,7 +780,8 @@ impl<T> DeRon for Vec<T> where T: DeRon {
         s.block_open(i) ?;
         
         while s.tok != DeRonTok::BlockClose {
-            out.push(DeRon::de_ron(s, i) ?);
+            let index = out.len();
+            out.push(DeRon::de_ron(s, i).map_err( | e | e.in_index(index)) ?);
             s.eat_comma_block(i) ?;
         }
         s.block_close(i) ?;
@@ -728,7 +806,7 @@ impl<T> SerRon for [T] where T: SerRon {
 unsafe fn de_ron_array_impl_inner<T>(top: *mut T, count: usize, s: &mut DeRonState, i: &mut Chars) -> Result<(), DeRonErr> where T: DeRon {
     s.paren_open(i) ?;
     for c in 0..count {
-        top.add(c).write(DeRon::de_ron(s, i) ?);
+        top.add(c).write(DeRon::de_ron(s, i).map_err( | e | e.in_index(c)) ?);
         s.eat_comma_paren(i) ?;
     }
     s.paren_close(i) ?;
@@ -747,10 +825,10 @@ impl<T, const N: usize> DeRon for [T; N] where T: DeRon {
     }
 }
 
-fn de_ron_comma_paren<T>(s: &mut DeRonState, i: &mut Chars) -> Result<T, DeRonErr> where T: DeRon {
-    let t = DeRon::de_ron(s, i);
+fn de_ron_comma_paren<T>(s: &mut DeRonState, i: &mut Chars, index: usize) -> Result<T, DeRonErr> where T: DeRon {
+    let t = DeRon::de_ron(s, i).map_err( | e | e.in_index(index)) ?;
     s.eat_comma_paren(i) ?;
-    t
+    Ok(t)
 }
 
 impl<A, B> SerRon for (A, B) where A: SerRon,
@@ -768,7 +846,7 @@ impl<A, B> DeRon for (A, B) where A: DeRon,
 B: DeRon {
     fn de_ron(s: &mut DeRonState, i: &mut Chars) -> Result<(A, B), DeRonErr> {
         s.paren_open(i) ?;
-        let r = (de_ron_comma_paren(s, i) ?, de_ron_comma_paren(s, i) ?);
+        let r = (de_ron_comma_paren(s, i, 0) ?, de_ron_comma_paren(s, i, 1) ?);
         s.paren_close(i) ?;
         Ok(r)
     }
@@ -793,7 +871,7 @@ B: DeRon,
 C: DeRon {
     fn de_ron(s: &mut DeRonState, i: &mut Chars) -> Result<(A, B, C), DeRonErr> {
         s.paren_open(i) ?;
-        let r = (de_ron_comma_paren(s, i) ?, de_ron_comma_paren(s, i) ?, de_ron_comma_paren(s, i) ?);
+        let r = (de_ron_comma_paren(s, i, 0) ?, de_ron_comma_paren(s, i, 1) ?, de_ron_comma_paren(s, i, 2) ?);
         s.paren_close(i) ?;
         Ok(r)
     }
@@ -822,7 +900,7 @@ C: DeRon,
 D: DeRon {
     fn de_ron(s: &mut DeRonState, i: &mut Chars) -> Result<(A, B, C, D), DeRonErr> {
         s.paren_open(i) ?;
-        let r = (de_ron_comma_paren(s, i) ?, de_ron_comma_paren(s, i) ?, de_ron_comma_paren(s, i) ?, de_ron_comma_paren(s, i) ?);
+        let r = (de_ron_comma_paren(s, i, 0) ?, de_ron_comma_paren(s, i, 1) ?, de_ron_comma_paren(s, i, 2) ?, de_ron_comma_paren(s, i, 3) ?);
         s.paren_close(i) ?;
         Ok(r)
     }
@@ -851,9 +929,12 @@ V: DeRon {
         let mut h = HashMap::new();
         s.curly_open(i) ?;
         while s.tok != DeRonTok::CurlyClose {
+            // string keys go into the error path
+            let key = if s.tok == DeRonTok::Str {Some(s.strbuf.clone())} else {None};
+            let in_key = | e: DeRonErr | if let Some(key) = &key {e.in_key(key)} else {e};
             let k = DeRon::de_ron(s, i) ?;
-            s.colon(i) ?;
-            let v = DeRon::de_ron(s, i) ?;
+            s.colon(i).map_err(in_key) ?;
+            let v = DeRon::de_ron(s, i).map_err(in_key) ?;
             s.eat_comma_curly(i) ?;
             h.insert(k, v);
         }
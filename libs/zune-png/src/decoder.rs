@@ -174,7 +174,8 @@ where
     pub(crate) seen_headers:    bool,
     pub(crate) seen_trns:       bool,
     pub(crate) seen_iend:       bool,
-    pub(crate) current_frame:   usize
+    pub(crate) current_frame:   usize,
+    pub(crate) default_image_is_frame: bool
 }
 
 impl<T: ZReaderTrait> PngDecoder<T> {
@@ -218,7 +219,8 @@ impl<T: ZReaderTrait> PngDecoder<T> {
             seen_headers:    false,
             seen_iend:       false,
             trns_bytes:      [0; 4],
-            current_frame:   0
+            current_frame:   0,
+            default_image_is_frame: false
         }
     }
 
@@ -313,8 +315,45 @@ impl<T: ZReaderTrait> PngDecoder<T> {
     }
 
     /// Return true if image has more frames available
+    ///
+    /// Frames of an animated image are parsed lazily, so this also
+    /// returns true while the end of the stream hasn't been seen yet
     pub fn more_frames(&self) -> bool {
-        self.frames.len() > self.current_frame
+        self.frames.len() > self.current_frame || (self.seen_headers && !self.seen_iend)
+    }
+
+    /// Return the animation control information if the image is an APNG
+    ///
+    /// # Returns
+    /// - `Some(actl)`: The number of frames and plays of the animation
+    /// - `None`: The image has no `acTL` chunk or headers weren't decoded
+    pub fn actl_info(&self) -> Option<&ActlChunk> {
+        self.actl_info.as_ref()
+    }
+
+    /// Return the frame information of the last decoded frame
+    ///
+    /// For the default image of a non-animated PNG this describes
+    /// the whole image with no delay
+    ///
+    /// # Returns
+    /// - `Some(info)`: Size, offset, delay and dispose/blend ops of the frame
+    /// - `None`: No frame has been decoded yet
+    pub fn frame_info(&self) -> Option<FrameInfo> {
+        if self.current_frame == 0 {
+            return None;
+        }
+        self.frames
+            .get(self.current_frame - 1)
+            .and_then(|frame| frame.fctl_info)
+    }
+
+    /// Return true if the default image (`IDAT`) is the first frame of the animation
+    ///
+    /// An APNG may carry a default image that is only shown by decoders
+    /// without animation support, in which case this returns false
+    pub const fn default_image_is_frame(&self) -> bool {
+        self.default_image_is_frame
     }
 
     pub(crate) fn read_chunk_header(&mut self) -> Result<PngChunk, PngDecodeErrors> {
@@ -74,6 +74,7 @@ pub enum CxOsOp {
     ShowClipboardActions(String),
 
     HttpRequest{request_id: LiveId, request:HttpRequest},
+    CancelHttpRequest{request_id: LiveId},
 
     PrepareVideoPlayback(LiveId, VideoSource, u32, bool, bool, bool),
     PauseVideoPlayback(LiveId),
@@ -419,6 +420,12 @@ impl Cx {
     pub fn http_request(&mut self, request_id: LiveId, request: HttpRequest) {
         self.platform_ops.push(CxOsOp::HttpRequest{request_id, request});
     }
+    
+    /// Aborts the requests with this id that are still underway. Responses that were on their way
+    /// already can still arrive. Linux and Apple only, the web lets the requests run
+    pub fn cancel_http_request(&mut self, request_id: LiveId) {
+        self.platform_ops.push(CxOsOp::CancelHttpRequest{request_id});
+    }
            /*
     pub fn web_socket_open(&mut self, request_id: LiveId, request: HttpRequest) {
         self.platform_ops.push(CxOsOp::WebSocketOpen{
@@ -16,6 +16,12 @@ pub enum NetworkResponse{
     HttpRequestError(String),
     HttpResponse(HttpResponse),
     HttpProgress{loaded:u32, total:u32},
+    /// The status and headers of a streaming request, the body follows as HttpStreamChunk
+    HttpStreamResponse(HttpResponse),
+    /// A piece of the body of a streaming request, in the order they arrive
+    HttpStreamChunk(Vec<u8>),
+    /// The body of a streaming request is complete
+    HttpStreamComplete,
 }
 
 pub struct NetworkResponseIter<I> {
@@ -72,6 +78,28 @@ pub struct HttpRequest {
     pub method: HttpMethod,
     pub headers: BTreeMap<String, Vec<String>>,
     pub body: Option<Vec<u8>>,
+    /// Deliver the body as it comes in, see NetworkResponse::HttpStreamResponse.
+    /// Linux and Apple only, on the web the body still arrives as one HttpResponse
+    pub is_streaming: bool,
+    /// Seconds connecting, or waiting for more of the response, can take before the request fails.
+    /// Linux and Apple only, the web uses the browser's timeout
+    pub timeout: Option<f64>,
+    /// Linux and Apple only, the web follows redirects the way the browser does
+    pub redirect_policy: HttpRedirectPolicy,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HttpRedirectPolicy {
+    /// Redirects arrive as the response
+    DontFollow,
+    /// Follows up to this many redirects
+    Follow(u32),
+}
+
+impl Default for HttpRedirectPolicy {
+    fn default() -> Self {
+        Self::Follow(10)
+    }
 }
 
 impl HttpRequest { 
@@ -81,7 +109,10 @@ impl HttpRequest {
             url,
             method,
             headers: BTreeMap::new(),
-            body: None
+            body: None,
+            is_streaming: false,
+            timeout: None,
+            redirect_policy: HttpRedirectPolicy::default(),
         }
     }
     
@@ -89,6 +120,18 @@ impl HttpRequest {
         self.metadata_id = id;
     }
     
+    pub fn set_is_streaming(&mut self){
+        self.is_streaming = true;
+    }
+    
+    pub fn set_timeout(&mut self, seconds: f64){
+        self.timeout = Some(seconds);
+    }
+    
+    pub fn set_redirect_policy(&mut self, policy: HttpRedirectPolicy){
+        self.redirect_policy = policy;
+    }
+    
     pub fn set_header(&mut self, name: String, value: String) {
         let entry = self.headers.entry(name).or_insert(Vec::new());
         entry.push(value);
@@ -163,11 +206,7 @@ impl HttpResponse {
             let json = str::from_utf8(&body).unwrap();
             DeJson::deserialize_json(&json)
         } else {
-            Err(DeJsonErr{
-                msg:"No body present".to_string(),
-                line:0,
-                col:0
-            })
+            Err(DeJsonErr::new("No body present"))
         }
     }
 
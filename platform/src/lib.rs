@@ -61,6 +61,8 @@ pub use makepad_objc_sys;
 pub use ::makepad_windows as windows;
 
 pub use makepad_futures;
+
+pub use makepad_http;
  
 pub use {
     makepad_shader_compiler,
@@ -150,6 +152,7 @@ pub use {
             HttpRequest,
             HttpResponse,
             HttpMethod,
+            HttpRedirectPolicy,
             NetworkResponse,
             NetworkResponseEvent,
             Margin,
@@ -18,7 +18,7 @@ use {
                     ios_event::IosEvent,
                     ios_app::{IosApp, init_ios_app_global,get_ios_app_global}
                 },
-                url_session::{make_http_request},
+                url_session::{make_http_request, cancel_http_request},
             },
             apple_classes::init_apple_classes_global,
             apple_media::CxAppleMedia,
@@ -308,6 +308,9 @@ impl Cx {
                 CxOsOp::HttpRequest{request_id, request} => {
                     make_http_request(request_id, request, self.os.network_response.sender.clone());
                 },
+                CxOsOp::CancelHttpRequest{request_id} => {
+                    cancel_http_request(request_id);
+                },
                 CxOsOp::ShowClipboardActions(_request) => {
                     crate::log!("Show clipboard actions not supported yet");
                 }
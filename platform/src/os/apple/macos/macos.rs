@@ -26,7 +26,7 @@ use {
                     macos_window::MacosWindow
                 },
                 apple_classes::init_apple_classes_global,
-                url_session::{make_http_request},
+                url_session::{make_http_request, cancel_http_request},
             },
             metal_xpc::start_xpc_service,
             apple_media::CxAppleMedia,
@@ -513,6 +513,9 @@ impl Cx {
                 CxOsOp::HttpRequest {request_id, request} => {
                     make_http_request(request_id, request, self.os.network_response.sender.clone());
                 },
+                CxOsOp::CancelHttpRequest {request_id} => {
+                    cancel_http_request(request_id);
+                },
                 CxOsOp::ShowClipboardActions(_request) => {
                     crate::log!("Show clipboard actions not supported yet");
                 }
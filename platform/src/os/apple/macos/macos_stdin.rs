@@ -19,7 +19,7 @@ use {
         texture::{Texture, TextureFormat},
         thread::Signal,
         os::{
-            url_session::{make_http_request},
+            url_session::{make_http_request, cancel_http_request},
             apple_sys::*,
             metal_xpc::{
                 xpc_service_proxy,
@@ -359,6 +359,9 @@ impl Cx {
                 CxOsOp::HttpRequest {request_id, request} => {
                     make_http_request(request_id, request, self.os.network_response.sender.clone());
                 },
+                CxOsOp::CancelHttpRequest {request_id} => {
+                    cancel_http_request(request_id);
+                },
                 _ => ()
                 /*
                 CxOsOp::CloseWindow(_window_id) => {},
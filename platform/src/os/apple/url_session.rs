@@ -3,11 +3,11 @@ use {
     std::{
         ptr,
         sync::mpsc::{Sender},
-        sync::Arc,
+        sync::{Arc, Mutex},
     },
     crate::{
         makepad_live_id::*,
-        makepad_objc_sys::{objc_block},
+        makepad_objc_sys::{objc_block, objc_block_invoke},
         makepad_objc_sys::runtime::{ObjcId},
         os::{
             //cocoa_app::{MacosApp, get_macos_class_global},
@@ -19,11 +19,13 @@ use {
             },
         },
         web_socket::WebSocketMessage,
+        makepad_http::websocket::{close_reason, CLOSE_NORMAL, CLOSE_NO_STATUS, CLOSE_ABNORMAL},
         event::{
             NetworkResponseEvent,
             NetworkResponse,
             HttpRequest,
-            HttpResponse
+            HttpResponse,
+            HttpRedirectPolicy,
         },
     }
 };
@@ -71,6 +73,10 @@ unsafe fn make_ns_request(request: &HttpRequest) -> ObjcId {
         let nsdata: ObjcId = msg_send![class!(NSData), dataWithBytes: body.as_ptr() length: body.len()];
         let () = msg_send![ns_request, setHTTPBody: nsdata];
     }
+    
+    if let Some(timeout) = request.timeout {
+        let () = msg_send![ns_request, setTimeoutInterval: timeout];
+    }
     ns_request
 }
 
@@ -83,14 +89,6 @@ impl OsWebSocket{
 
     pub fn send_message(&mut self, message:WebSocketMessage)->Result<(),()>{
         unsafe{
-            let rx_sender = self.rx_sender.clone();
-            let handler = objc_block!(move | error: ObjcId | {
-                if error != ptr::null_mut() {
-                    let error_str: String = nsstring_to_string(msg_send![error, localizedDescription]);
-                    rx_sender.send(WebSocketMessage::Error(error_str)).unwrap();
-                }
-            });
-                       
             let msg: ObjcId = match &message{
                 WebSocketMessage::String(data)=>{
                     let nsstring = str_to_nsstring(data);
@@ -104,9 +102,33 @@ impl OsWebSocket{
                     let () = msg_send![msg, initWithData: nsdata];
                     msg
                 }
-                _=>panic!()
+                WebSocketMessage::Close(code, reason)=>{
+                    // NSURLSession always sends a code, the ones that stand for no code become a normal close
+                    let code = match *code{
+                        CLOSE_NO_STATUS | CLOSE_ABNORMAL => CLOSE_NORMAL,
+                        code => code
+                    };
+                    let reason = close_reason(reason);
+                    let reason: ObjcId = if reason.is_empty(){
+                        nil
+                    }
+                    else{
+                        msg_send![class!(NSData), dataWithBytes: reason.as_ptr() length: reason.len()]
+                    };
+                    let () = msg_send![*Arc::as_ptr(&self.data_task), cancelWithCloseCode: code as isize reason: reason];
+                    return Ok(())
+                }
+                // only the app side of the socket has these
+                WebSocketMessage::Opened | WebSocketMessage::Closed | WebSocketMessage::Error(_)=>return Err(())
             };
-                        
+            
+            let rx_sender = self.rx_sender.clone();
+            let handler = objc_block!(move | error: ObjcId | {
+                if error != ptr::null_mut() {
+                    let error_str: String = nsstring_to_string(msg_send![error, localizedDescription]);
+                    rx_sender.send(WebSocketMessage::Error(error_str)).unwrap();
+                }
+            });
             let () = msg_send![*Arc::as_ptr(&self.data_task), sendMessage: msg completionHandler:handler];
             Ok(())
         } 
@@ -163,56 +185,211 @@ impl OsWebSocket{
 }
 
 
-pub fn make_http_request(request_id: LiveId, request: HttpRequest, networking_sender: Sender<NetworkResponseEvent>) {
-    unsafe {
-        let ns_request = make_ns_request(&request);
-                
-        // Build the NSURLSessionDataTask instance
-        let response_handler = objc_block!(move | data: ObjcId, response: ObjcId, error: ObjcId | {
-            if error != ptr::null_mut() {
-                let error_str: String = nsstring_to_string(msg_send![error, localizedDescription]);
-                let message = NetworkResponseEvent {
-                    request_id,
-                    response: NetworkResponse::HttpRequestError(error_str)
-                };
-                networking_sender.send(message).unwrap();
-                return;
+const NS_URL_SESSION_RESPONSE_ALLOW: isize = 1;
+const NS_URL_ERROR_CANCELLED: isize = -999;
+
+struct AppleHttpRequest {
+    request_id: LiveId,
+    metadata_id: LiveId,
+    is_streaming: bool,
+    is_cancelled: bool,
+    redirect_policy: HttpRedirectPolicy,
+    redirects: u32,
+    response: Option<HttpResponse>,
+    body: Vec<u8>,
+    data_task: ObjcId,
+    networking_sender: Sender<NetworkResponseEvent>,
+}
+
+impl AppleHttpRequest {
+    fn send(&self, response: NetworkResponse) {
+        if !self.is_cancelled {
+            let _ = self.networking_sender.send(NetworkResponseEvent {
+                request_id: self.request_id,
+                response
+            });
+        }
+    }
+}
+
+// the session and its delegate are shared by all requests, the delegate finds
+// the request a callback is about by its data task
+struct AppleHttpRequests {
+    session: Option<ObjcId>,
+    requests: Vec<AppleHttpRequest>,
+}
+
+unsafe impl Send for AppleHttpRequests {}
+
+static HTTP_REQUESTS: Mutex<AppleHttpRequests> = Mutex::new(AppleHttpRequests {
+    session: None,
+    requests: Vec::new(),
+});
+
+fn with_http_request<R>(data_task: ObjcId, f: impl FnOnce(&mut AppleHttpRequest) -> R) -> Option<R> {
+    let mut http_requests = HTTP_REQUESTS.lock().unwrap();
+    http_requests.requests.iter_mut().find( | request | request.data_task == data_task).map(f)
+}
+
+unsafe fn ns_response_to_http_response(metadata_id: LiveId, ns_response: ObjcId) -> HttpResponse {
+    let response_code: u16 = msg_send![ns_response, statusCode];
+    let headers: ObjcId = msg_send![ns_response, allHeaderFields];
+    let mut response = HttpResponse::new(
+        metadata_id,
+        response_code,
+        "".to_string(),
+        None,
+    );
+    
+    let key_enumerator: ObjcId = msg_send![headers, keyEnumerator];
+    let mut key: ObjcId = msg_send![key_enumerator, nextObject];
+    while key != ptr::null_mut() {
+        let value: ObjcId = msg_send![headers, objectForKey: key];
+        let key_str = nsstring_to_string(key);
+        let value_str = nsstring_to_string(value);
+        response.set_header(key_str, value_str);
+        
+        key = msg_send![key_enumerator, nextObject];
+    }
+    response
+}
+
+pub fn define_url_session_data_delegate() -> *const Class {
+    
+    extern fn will_perform_http_redirection(_this: &Object, _: Sel, _session: ObjcId, task: ObjcId, _response: ObjcId, new_request: ObjcId, completion: ObjcId) {
+        let follow = with_http_request(task, | request | match request.redirect_policy {
+            HttpRedirectPolicy::DontFollow => false,
+            HttpRedirectPolicy::Follow(max_redirects) => {
+                request.redirects += 1;
+                request.redirects <= max_redirects
             }
-                        
+        }).unwrap_or(true);
+        // a nil request turns the redirect itself into the response
+        let new_request = if follow {new_request} else {nil};
+        unsafe {objc_block_invoke!(completion, invoke((new_request): ObjcId));}
+    }
+    
+    extern fn did_receive_response(_this: &Object, _: Sel, _session: ObjcId, data_task: ObjcId, ns_response: ObjcId, completion: ObjcId) {
+        with_http_request(data_task, | request | {
+            let response = unsafe {ns_response_to_http_response(request.metadata_id, ns_response)};
+            if request.is_streaming {
+                request.send(NetworkResponse::HttpStreamResponse(response));
+            }
+            else {
+                request.response = Some(response);
+            }
+        });
+        unsafe {objc_block_invoke!(completion, invoke((NS_URL_SESSION_RESPONSE_ALLOW): isize));}
+    }
+    
+    extern fn did_receive_data(_this: &Object, _: Sel, _session: ObjcId, data_task: ObjcId, data: ObjcId) {
+        let data_bytes: &[u8] = unsafe {
             let bytes: *const u8 = msg_send![data, bytes];
             let length: usize = msg_send![data, length];
-            let data_bytes: &[u8] = std::slice::from_raw_parts(bytes, length);
-            let response_code: u16 = msg_send![response, statusCode];
-            let headers: ObjcId = msg_send![response, allHeaderFields];
-            let mut response = HttpResponse::new(
-                request.metadata_id,
-                response_code,
-                "".to_string(),
-                Some(data_bytes.to_vec()),
-            );
-                        
-            let key_enumerator: ObjcId = msg_send![headers, keyEnumerator];
-            let mut key: ObjcId = msg_send![key_enumerator, nextObject];
-            while key != ptr::null_mut() {
-                let value: ObjcId = msg_send![headers, objectForKey: key];
-                let key_str = nsstring_to_string(key);
-                let value_str = nsstring_to_string(value);
-                response.set_header(key_str, value_str);
-                                
-                key = msg_send![key_enumerator, nextObject];
+            if length == 0 {
+                return
+            }
+            std::slice::from_raw_parts(bytes, length)
+        };
+        with_http_request(data_task, | request | {
+            if request.is_streaming {
+                request.send(NetworkResponse::HttpStreamChunk(data_bytes.to_vec()));
+            }
+            else {
+                request.body.extend_from_slice(data_bytes);
             }
-                        
-            let message = NetworkResponseEvent {
-                request_id,
-                response: NetworkResponse::HttpResponse(response)
-            };
-            networking_sender.send(message).unwrap();
         });
-                
-        let session: ObjcId = msg_send![class!(NSURLSession), sharedSession];
-        let data_task: ObjcId = msg_send![session, dataTaskWithRequest: ns_request completionHandler: &response_handler];
-                
+    }
+    
+    extern fn did_complete_with_error(_this: &Object, _: Sel, _session: ObjcId, task: ObjcId, error: ObjcId) {
+        let mut request = {
+            let mut http_requests = HTTP_REQUESTS.lock().unwrap();
+            match http_requests.requests.iter().position( | request | request.data_task == task) {
+                Some(index) => http_requests.requests.remove(index),
+                None => return
+            }
+        };
+        unsafe {
+            let () = msg_send![request.data_task, release];
+            if error != ptr::null_mut() {
+                let code: isize = msg_send![error, code];
+                if code != NS_URL_ERROR_CANCELLED {
+                    let error_str: String = nsstring_to_string(msg_send![error, localizedDescription]);
+                    request.send(NetworkResponse::HttpRequestError(error_str));
+                }
+                return
+            }
+        }
+        if request.is_streaming {
+            request.send(NetworkResponse::HttpStreamComplete);
+        }
+        else if let Some(mut response) = request.response.take() {
+            response.body = Some(std::mem::take(&mut request.body));
+            request.send(NetworkResponse::HttpResponse(response));
+        }
+        else {
+            request.send(NetworkResponse::HttpRequestError("No response received".to_string()));
+        }
+    }
+    
+    let superclass = class!(NSObject);
+    let mut decl = ClassDecl::new("HttpSessionDelegate", superclass).unwrap();
+    
+    // Add callback methods
+    unsafe {
+        decl.add_method(sel!(URLSession: task: willPerformHTTPRedirection: newRequest: completionHandler:), will_perform_http_redirection as extern fn(&Object, Sel, ObjcId, ObjcId, ObjcId, ObjcId, ObjcId));
+        decl.add_method(sel!(URLSession: dataTask: didReceiveResponse: completionHandler:), did_receive_response as extern fn(&Object, Sel, ObjcId, ObjcId, ObjcId, ObjcId));
+        decl.add_method(sel!(URLSession: dataTask: didReceiveData:), did_receive_data as extern fn(&Object, Sel, ObjcId, ObjcId, ObjcId));
+        decl.add_method(sel!(URLSession: task: didCompleteWithError:), did_complete_with_error as extern fn(&Object, Sel, ObjcId, ObjcId, ObjcId));
+    }
+    
+    return decl.register();
+}
+
+pub fn make_http_request(request_id: LiveId, request: HttpRequest, networking_sender: Sender<NetworkResponseEvent>) {
+    unsafe {
+        let ns_request = make_ns_request(&request);
+        
+        let mut http_requests = HTTP_REQUESTS.lock().unwrap();
+        let session = match http_requests.session {
+            Some(session) => session,
+            None => {
+                // the delegate gets its calls on a queue of the session's own
+                let configuration: ObjcId = msg_send![class!(NSURLSessionConfiguration), defaultSessionConfiguration];
+                let delegate: ObjcId = msg_send![define_url_session_data_delegate(), new];
+                let session: ObjcId = msg_send![class!(NSURLSession), sessionWithConfiguration: configuration delegate: delegate delegateQueue: nil];
+                let () = msg_send![session, retain];
+                http_requests.session = Some(session);
+                session
+            }
+        };
+        
+        let data_task: ObjcId = msg_send![session, dataTaskWithRequest: ns_request];
+        let () = msg_send![data_task, retain];
+        http_requests.requests.push(AppleHttpRequest {
+            request_id,
+            metadata_id: request.metadata_id,
+            is_streaming: request.is_streaming,
+            is_cancelled: false,
+            redirect_policy: request.redirect_policy,
+            redirects: 0,
+            response: None,
+            body: Vec::new(),
+            data_task,
+            networking_sender,
+        });
+        
         // Run the request task
         let () = msg_send![data_task, resume];
     }
-}
\ No newline at end of file
+}
+
+pub fn cancel_http_request(request_id: LiveId) {
+    let mut http_requests = HTTP_REQUESTS.lock().unwrap();
+    for request in http_requests.requests.iter_mut() {
+        if request.request_id == request_id {
+            request.is_cancelled = true;
+            unsafe {let () = msg_send![request.data_task, cancel];}
+        }
+    }
+}
@@ -5,7 +5,7 @@ use std::sync::Arc;
 use std::sync::mpsc::{*};
 use self::super::android_jni;
 use crate::LiveId;
-use makepad_http::websocket::{MessageHeader, MessageFormat, WebSocket};
+use makepad_http::websocket::{self, MessageHeader, MessageFormat, WebSocket, CLOSE_NO_STATUS, CLOSE_ABNORMAL};
 
 pub struct OsWebSocket{
     pub sender_ref: Arc<Box<Sender<WebSocketMessage>>>,
@@ -26,7 +26,18 @@ impl OsWebSocket{
                 let header = MessageHeader::from_len(data.len(), MessageFormat::Text, true);
                 WebSocket::build_message(header, &data)
             }
-            _=>panic!()
+            WebSocketMessage::Close(code, reason)=>{
+                // the server answers with a close frame of its own and ends the connection
+                let mut data = Vec::new();
+                if *code != CLOSE_NO_STATUS && *code != CLOSE_ABNORMAL {
+                    data.extend_from_slice(&code.to_be_bytes());
+                    data.extend_from_slice(websocket::close_reason(reason).as_bytes());
+                }
+                let header = MessageHeader::from_len(data.len(), MessageFormat::Close, true);
+                WebSocket::build_message(header, &data)
+            }
+            // only the app side of the socket has these
+            WebSocketMessage::Opened | WebSocketMessage::Closed | WebSocketMessage::Error(_)=>return Err(())
         };
         unsafe {android_jni::to_java_websocket_send_message(self.request_id, frame);}
 
@@ -10,7 +10,8 @@ use {
     self::super::super::{
         gl_sys,
         select_timer::SelectTimers,
-        linux_media::CxLinuxMedia
+        linux_media::CxLinuxMedia,
+        linux_http::LinuxHttp,
     },
     crate::{
         cx_api::{CxOsOp, CxOsApi},
@@ -21,6 +22,7 @@ use {
             TimerEvent,
             Event,
             WindowGeom,
+            NetworkResponseChannel,
         },
         window::CxWindowPool,
         pass::CxPassParent,
@@ -199,6 +201,7 @@ impl Cx {
                         self.handle_media_signals();
                         self.call_event_handler(&Event::Signal);
                     }
+                    self.handle_networking_events();
                 }
                 else {
                     self.call_event_handler(&Event::Timer(e))
@@ -296,6 +299,16 @@ impl Cx {
         }
     }
     
+    fn handle_networking_events(&mut self) {
+        let mut out = Vec::new();
+        while let Ok(event) = self.os.network_response.receiver.try_recv() {
+            out.push(event);
+        }
+        if !out.is_empty() {
+            self.call_event_handler(&Event::NetworkResponses(out))
+        }
+    }
+    
     fn handle_platform_ops(&mut self, direct_app: &mut DirectApp) -> EventFlow {
         while let Some(op) = self.platform_ops.pop() {
             match op {
@@ -323,6 +336,12 @@ impl Cx {
                 CxOsOp::StopTimer(timer_id) => {
                     direct_app.timers.stop_timer(timer_id);
                 },
+                CxOsOp::HttpRequest {request_id, request} => {
+                    self.os.http.make_request(request_id, request, self.os.network_response.sender.clone());
+                },
+                CxOsOp::CancelHttpRequest {request_id} => {
+                    self.os.http.cancel_request(request_id);
+                },
                 _ => ()
             }
         }
@@ -345,5 +364,7 @@ impl CxOsApi for Cx {
 #[derive(Default)]
 pub struct CxOs {
     pub (crate) media: CxLinuxMedia,
+    pub (crate) network_response: NetworkResponseChannel,
+    pub (crate) http: LinuxHttp,
 }
 
@@ -0,0 +1,115 @@
+use {
+    std::{
+        time::Duration,
+        sync::{Arc, Mutex, mpsc::Sender},
+    },
+    crate::{
+        makepad_live_id::LiveId,
+        makepad_http::{
+            client::{HttpClientRequest, HttpClientResponse, HttpCancelHandle},
+            utils::TlsConnector,
+        },
+        event::{
+            NetworkResponseEvent,
+            NetworkResponse,
+            HttpRequest,
+            HttpResponse,
+            HttpRedirectPolicy,
+        },
+        cx::Cx,
+    },
+    super::linux_tls::default_tls_connector,
+};
+
+impl Cx {
+    /// Sets the TLS backend https requests and wss:// websockets run on. Without one they use the
+    /// OpenSSL of the system, loaded when it is first needed
+    pub fn set_tls_connector(&mut self, tls_connector: Arc<dyn TlsConnector>) {
+        *super::web_socket::TLS_CONNECTOR.lock().unwrap() = Some(tls_connector.clone());
+        self.os.http.tls_connector = Some(tls_connector);
+    }
+}
+
+/// The http requests that are underway, each runs on a thread of its own
+#[derive(Default)]
+pub struct LinuxHttp {
+    pub tls_connector: Option<Arc<dyn TlsConnector>>,
+    // the threads take their request out when it is done
+    requests: Arc<Mutex<Vec<(LiveId, HttpCancelHandle)>>>,
+}
+
+impl LinuxHttp {
+    pub fn make_request(&mut self, request_id: LiveId, request: HttpRequest, networking_sender: Sender<NetworkResponseEvent>) {
+        let mut client_request = HttpClientRequest::new(request.method.to_string(), &request.url);
+        for (name, values) in &request.headers {
+            client_request.headers.push((name.clone(), values.join(",")));
+        }
+        client_request.body = request.body.unwrap_or_default();
+        client_request.timeout = request.timeout.map(Duration::from_secs_f64);
+        client_request.max_redirects = match request.redirect_policy {
+            HttpRedirectPolicy::DontFollow => 0,
+            HttpRedirectPolicy::Follow(max_redirects) => max_redirects
+        };
+        let cancel = HttpCancelHandle::default();
+        client_request.cancel = Some(cancel.clone());
+        self.requests.lock().unwrap().push((request_id, cancel.clone()));
+
+        let tls_connector = self.tls_connector.clone().or_else(default_tls_connector);
+        let requests = self.requests.clone();
+        let metadata_id = request.metadata_id;
+        let is_streaming = request.is_streaming;
+        std::thread::spawn(move || {
+            let send = | response | {
+                if !cancel.is_cancelled() {
+                    let _ = networking_sender.send(NetworkResponseEvent {request_id, response});
+                }
+            };
+            let to_http_response = | client_response: HttpClientResponse | {
+                let mut response = HttpResponse::new(
+                    metadata_id,
+                    client_response.status_code,
+                    "".to_string(),
+                    Some(client_response.body),
+                );
+                for (name, value) in client_response.headers {
+                    response.set_header(name, value);
+                }
+                response
+            };
+            if is_streaming {
+                let result = client_request.send_streaming(tls_connector.as_deref(), &mut | head | {
+                    let mut response = to_http_response(head.clone());
+                    response.body = None;
+                    send(NetworkResponse::HttpStreamResponse(response));
+                }, &mut | data, _ | {
+                    send(NetworkResponse::HttpStreamChunk(data.to_vec()));
+                });
+                send(match result {
+                    Ok(_) => NetworkResponse::HttpStreamComplete,
+                    Err(err) => NetworkResponse::HttpRequestError(err)
+                });
+            }
+            else {
+                let result = client_request.send(tls_connector.as_deref(), &mut | loaded, total | {
+                    send(NetworkResponse::HttpProgress {
+                        loaded: loaded.min(u32::MAX as u64) as u32,
+                        total: total.unwrap_or(0).min(u32::MAX as u64) as u32
+                    });
+                });
+                send(match result {
+                    Ok(client_response) => NetworkResponse::HttpResponse(to_http_response(client_response)),
+                    Err(err) => NetworkResponse::HttpRequestError(err)
+                });
+            }
+            requests.lock().unwrap().retain( | (_, handle) | *handle != cancel);
+        });
+    }
+
+    pub fn cancel_request(&mut self, request_id: LiveId) {
+        for (id, cancel) in self.requests.lock().unwrap().iter() {
+            if *id == request_id {
+                cancel.cancel();
+            }
+        }
+    }
+}
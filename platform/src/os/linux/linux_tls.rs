@@ -0,0 +1,236 @@
+use {
+    std::{
+        ffi::{CStr, CString},
+        io::{self, Read, Write},
+        net::{IpAddr, TcpStream},
+        os::{raw::{c_int, c_void}, unix::io::AsRawFd},
+        ptr,
+        sync::{Arc, Mutex, OnceLock},
+    },
+    crate::makepad_http::utils::{HttpStream, TlsConnector},
+    super::openssl_sys::*,
+};
+
+/// The TLS connector https requests and wss:// websockets use when the app didn't set one with
+/// Cx::set_tls_connector. It runs on the OpenSSL of the system and checks certificates against its
+/// trust store. None when OpenSSL can't be loaded
+pub fn default_tls_connector() -> Option<Arc<dyn TlsConnector>> {
+    static DEFAULT: OnceLock<Option<Arc<dyn TlsConnector>>> = OnceLock::new();
+    DEFAULT.get_or_init( || {
+        OpenSslConnector::new().map( | connector | Arc::new(connector) as Arc<dyn TlsConnector>)
+    }).clone()
+}
+
+pub struct OpenSslConnector {
+    lib: Arc<LibSsl>,
+    ctx: *mut SSL_CTX,
+}
+
+// an SSL_CTX can make connections from any thread once it is set up
+unsafe impl Send for OpenSslConnector {}
+unsafe impl Sync for OpenSslConnector {}
+
+impl OpenSslConnector {
+    pub fn new() -> Option<Self> {
+        let lib = LibSsl::try_load() ?;
+        unsafe {
+            let ctx = (lib.SSL_CTX_new)((lib.TLS_client_method)());
+            if ctx.is_null() {
+                return None
+            }
+            (lib.SSL_CTX_set_default_verify_paths)(ctx);
+            (lib.SSL_CTX_set_verify)(ctx, SSL_VERIFY_PEER, None);
+            // servers often close without a close_notify, the http framing tells us if the body is complete
+            if (lib.OpenSSL_version_num)() >= 0x3000_0000 {
+                (lib.SSL_CTX_set_options)(ctx, SSL_OP_IGNORE_UNEXPECTED_EOF);
+            }
+            // a read that only finds records without data, like session tickets, returns instead of
+            // blocking for more. This way a reader never waits for data while it holds the session
+            (lib.SSL_CTX_ctrl)(ctx, SSL_CTRL_CLEAR_MODE, SSL_MODE_AUTO_RETRY, ptr::null_mut());
+            Some(Self {lib: Arc::new(lib), ctx})
+        }
+    }
+}
+
+impl Drop for OpenSslConnector {
+    fn drop(&mut self) {
+        unsafe {(self.lib.SSL_CTX_free)(self.ctx)};
+    }
+}
+
+impl TlsConnector for OpenSslConnector {
+    fn connect(&self, host: &str, tcp_stream: TcpStream) -> io::Result<Box<dyn HttpStream>> {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let c_host = CString::new(host).map_err( | _ | io::Error::new(io::ErrorKind::InvalidInput, "Host contains a nul byte")) ?;
+        let lib = &self.lib;
+        let ssl = unsafe {(lib.SSL_new)(self.ctx)};
+        if ssl.is_null() {
+            return Err(io::Error::other("Cannot create a TLS session"))
+        }
+        let read_stream = tcp_stream.try_clone() ?;
+        let fd = tcp_stream.as_raw_fd();
+        let session = Session {lib: lib.clone(), ssl, _tcp_stream: tcp_stream};
+        unsafe {
+            (lib.ERR_clear_error)();
+            (lib.SSL_set_fd)(ssl, fd);
+            if host.parse::<IpAddr>().is_ok() {
+                // no server name indication for addresses, the certificate has to name the address
+                (lib.X509_VERIFY_PARAM_set1_ip_asc)((lib.SSL_get0_param)(ssl), c_host.as_ptr());
+            }
+            else {
+                (lib.SSL_ctrl)(ssl, SSL_CTRL_SET_TLSEXT_HOSTNAME, TLSEXT_NAMETYPE_host_name, c_host.as_ptr() as *mut c_void);
+                (lib.SSL_set1_host)(ssl, c_host.as_ptr());
+            }
+            loop {
+                let ret = (lib.SSL_connect)(ssl);
+                if ret == 1 {
+                    break
+                }
+                match session.error(ret) {
+                    SslError::Retry => continue,
+                    SslError::Closed => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed during the handshake")),
+                    SslError::Io(err) => return Err(err)
+                }
+            }
+        }
+        Ok(Box::new(OpenSslStream {
+            session: Arc::new(Mutex::new(session)),
+            read_stream,
+        }))
+    }
+}
+
+struct Session {
+    lib: Arc<LibSsl>,
+    ssl: *mut SSL,
+    // the socket the session runs on, closed after the session is freed
+    _tcp_stream: TcpStream,
+}
+
+// the session is only used behind the mutex of its streams
+unsafe impl Send for Session {}
+
+enum SslError {
+    // nothing went wrong, the call has to be made again
+    Retry,
+    Closed,
+    Io(io::Error),
+}
+
+impl Session {
+    // what went wrong with a call that returned ret
+    unsafe fn error(&self, ret: c_int) -> SslError {
+        let lib = &self.lib;
+        match (lib.SSL_get_error)(self.ssl, ret) {
+            SSL_ERROR_WANT_READ | SSL_ERROR_WANT_WRITE => SslError::Retry,
+            SSL_ERROR_ZERO_RETURN => SslError::Closed,
+            SSL_ERROR_SYSCALL => {
+                let err = io::Error::last_os_error();
+                if (lib.ERR_get_error)() == 0 && err.raw_os_error() == Some(0) {
+                    // OpenSSL 1.1 reports a close without a close_notify this way
+                    SslError::Closed
+                }
+                else {
+                    SslError::Io(err)
+                }
+            }
+            SSL_ERROR_SSL => {
+                let verify_result = (lib.SSL_get_verify_result)(self.ssl);
+                let message = if verify_result != X509_V_OK {
+                    let reason = CStr::from_ptr((lib.X509_verify_cert_error_string)(verify_result));
+                    format!("Certificate verification failed: {}", reason.to_string_lossy())
+                }
+                else {
+                    let mut buf = [0u8; 256];
+                    (lib.ERR_error_string_n)((lib.ERR_get_error)(), buf.as_mut_ptr() as *mut _, buf.len());
+                    CStr::from_bytes_until_nul(&buf).map( | s | s.to_string_lossy().into_owned()).unwrap_or_default()
+                };
+                (lib.ERR_clear_error)();
+                SslError::Io(io::Error::other(message))
+            }
+            code => SslError::Io(io::Error::other(format!("TLS error {}", code)))
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        unsafe {(self.lib.SSL_free)(self.ssl)};
+    }
+}
+
+/// A TLS connection, clones share the session so websockets can read on one thread and write on another
+struct OpenSslStream {
+    session: Arc<Mutex<Session>>,
+    // a handle on the socket to wait for data on without holding the session
+    read_stream: TcpStream,
+}
+
+impl Read for OpenSslStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0)
+        }
+        let len = buf.len().min(c_int::MAX as usize) as c_int;
+        loop {
+            let has_pending = {
+                let session = self.session.lock().unwrap();
+                unsafe {(session.lib.SSL_pending)(session.ssl) > 0}
+            };
+            if !has_pending {
+                // this blocks until data arrives, the connection closes or the read timeout passes
+                self.read_stream.peek(&mut [0u8]) ?;
+            }
+            let session = self.session.lock().unwrap();
+            unsafe {
+                (session.lib.ERR_clear_error)();
+                let ret = (session.lib.SSL_read)(session.ssl, buf.as_mut_ptr() as *mut c_void, len);
+                if ret > 0 {
+                    return Ok(ret as usize)
+                }
+                match session.error(ret) {
+                    SslError::Retry => continue,
+                    SslError::Closed => return Ok(0),
+                    SslError::Io(err) => return Err(err)
+                }
+            }
+        }
+    }
+}
+
+impl Write for OpenSslStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0)
+        }
+        let len = buf.len().min(c_int::MAX as usize) as c_int;
+        let session = self.session.lock().unwrap();
+        loop {
+            unsafe {
+                (session.lib.ERR_clear_error)();
+                let ret = (session.lib.SSL_write)(session.ssl, buf.as_ptr() as *const c_void, len);
+                if ret > 0 {
+                    return Ok(ret as usize)
+                }
+                match session.error(ret) {
+                    SslError::Retry => continue,
+                    SslError::Closed => return Ok(0),
+                    SslError::Io(err) => return Err(err)
+                }
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl HttpStream for OpenSslStream {
+    fn try_clone_stream(&self) -> io::Result<Box<dyn HttpStream>> {
+        Ok(Box::new(OpenSslStream {
+            session: self.session.clone(),
+            read_stream: self.read_stream.try_clone() ?,
+        }))
+    }
+}
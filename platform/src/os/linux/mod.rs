@@ -33,6 +33,13 @@ pub mod pulse_sys;
 #[cfg(not(target_os="android"))]
 mod web_socket;
 
+#[cfg(not(target_os="android"))]
+pub mod linux_http;
+#[cfg(not(target_os="android"))]
+pub mod linux_tls;
+#[cfg(not(target_os="android"))]
+pub mod openssl_sys;
+
 #[cfg(target_os="android")]
 pub mod android;
 
@@ -0,0 +1,133 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+// The parts of OpenSSL 1.1 and 3 the default TLS connector needs. The library is loaded at
+// runtime, so apps still start on systems without it, they just can't use https
+
+use {
+    std::{
+        ffi::CString,
+        os::raw::{c_char, c_int, c_long, c_ulong, c_void},
+        ptr::NonNull,
+    },
+    super::libc_sys::{dlclose, dlopen, dlsym, RTLD_LAZY, RTLD_LOCAL},
+};
+
+pub enum SSL_METHOD {}
+pub enum SSL_CTX {}
+pub enum SSL {}
+pub enum X509_VERIFY_PARAM {}
+
+pub const SSL_VERIFY_PEER: c_int = 1;
+
+pub const SSL_ERROR_SSL: c_int = 1;
+pub const SSL_ERROR_WANT_READ: c_int = 2;
+pub const SSL_ERROR_WANT_WRITE: c_int = 3;
+pub const SSL_ERROR_SYSCALL: c_int = 5;
+pub const SSL_ERROR_ZERO_RETURN: c_int = 6;
+
+pub const SSL_CTRL_SET_TLSEXT_HOSTNAME: c_int = 55;
+pub const SSL_CTRL_CLEAR_MODE: c_int = 78;
+pub const SSL_MODE_AUTO_RETRY: c_long = 4;
+pub const TLSEXT_NAMETYPE_host_name: c_long = 0;
+/// Only exists from OpenSSL 3, where a connection closed without a close_notify is an error otherwise
+pub const SSL_OP_IGNORE_UNEXPECTED_EOF: u64 = 1 << 7;
+pub const X509_V_OK: c_long = 0;
+
+pub type SSL_verify_cb = Option<unsafe extern "C" fn(c_int, *mut c_void) -> c_int>;
+
+struct Module(NonNull<c_void>);
+
+impl Module {
+    fn load(path: &str) -> Option<Self> {
+        let path = CString::new(path).unwrap();
+        let module = unsafe {dlopen(path.as_ptr(), RTLD_LAZY | RTLD_LOCAL)};
+        NonNull::new(module).map(Module)
+    }
+
+    // symbols of libcrypto are found through libssl, it depends on it
+    fn get_symbol<F: Sized>(&self, name: &str) -> Option<F> {
+        let name = CString::new(name).unwrap();
+        let symbol = unsafe {dlsym(self.0.as_ptr(), name.as_ptr())};
+        if symbol.is_null() {
+            return None
+        }
+        Some(unsafe {std::mem::transmute_copy::<_, F>(&symbol)})
+    }
+}
+
+impl Drop for Module {
+    fn drop(&mut self) {
+        unsafe {dlclose(self.0.as_ptr())};
+    }
+}
+
+pub struct LibSsl {
+    pub OpenSSL_version_num: unsafe extern "C" fn() -> c_ulong,
+    pub TLS_client_method: unsafe extern "C" fn() -> *const SSL_METHOD,
+    pub SSL_CTX_new: unsafe extern "C" fn(*const SSL_METHOD) -> *mut SSL_CTX,
+    pub SSL_CTX_free: unsafe extern "C" fn(*mut SSL_CTX),
+    pub SSL_CTX_set_default_verify_paths: unsafe extern "C" fn(*mut SSL_CTX) -> c_int,
+    pub SSL_CTX_set_verify: unsafe extern "C" fn(*mut SSL_CTX, c_int, SSL_verify_cb),
+    pub SSL_CTX_set_options: unsafe extern "C" fn(*mut SSL_CTX, u64) -> u64,
+    pub SSL_CTX_ctrl: unsafe extern "C" fn(*mut SSL_CTX, c_int, c_long, *mut c_void) -> c_long,
+    pub SSL_new: unsafe extern "C" fn(*mut SSL_CTX) -> *mut SSL,
+    pub SSL_free: unsafe extern "C" fn(*mut SSL),
+    pub SSL_set_fd: unsafe extern "C" fn(*mut SSL, c_int) -> c_int,
+    pub SSL_ctrl: unsafe extern "C" fn(*mut SSL, c_int, c_long, *mut c_void) -> c_long,
+    pub SSL_set1_host: unsafe extern "C" fn(*mut SSL, *const c_char) -> c_int,
+    pub SSL_get0_param: unsafe extern "C" fn(*mut SSL) -> *mut X509_VERIFY_PARAM,
+    pub X509_VERIFY_PARAM_set1_ip_asc: unsafe extern "C" fn(*mut X509_VERIFY_PARAM, *const c_char) -> c_int,
+    pub SSL_connect: unsafe extern "C" fn(*mut SSL) -> c_int,
+    pub SSL_read: unsafe extern "C" fn(*mut SSL, *mut c_void, c_int) -> c_int,
+    pub SSL_write: unsafe extern "C" fn(*mut SSL, *const c_void, c_int) -> c_int,
+    pub SSL_pending: unsafe extern "C" fn(*const SSL) -> c_int,
+    pub SSL_get_error: unsafe extern "C" fn(*const SSL, c_int) -> c_int,
+    pub SSL_get_verify_result: unsafe extern "C" fn(*const SSL) -> c_long,
+    pub X509_verify_cert_error_string: unsafe extern "C" fn(c_long) -> *const c_char,
+    pub ERR_get_error: unsafe extern "C" fn() -> c_ulong,
+    pub ERR_error_string_n: unsafe extern "C" fn(c_ulong, *mut c_char, usize),
+    pub ERR_clear_error: unsafe extern "C" fn(),
+    _keep_module_alive: Module,
+}
+
+// the library and its functions can be used from any thread
+unsafe impl Send for LibSsl {}
+unsafe impl Sync for LibSsl {}
+
+impl LibSsl {
+    pub fn try_load() -> Option<LibSsl> {
+        let module = Module::load("libssl.so.3")
+            .or_else( || Module::load("libssl.so.1.1"))
+            .or_else( || Module::load("libssl.so")) ?;
+        Some(LibSsl {
+            OpenSSL_version_num: module.get_symbol("OpenSSL_version_num") ?,
+            TLS_client_method: module.get_symbol("TLS_client_method") ?,
+            SSL_CTX_new: module.get_symbol("SSL_CTX_new") ?,
+            SSL_CTX_free: module.get_symbol("SSL_CTX_free") ?,
+            SSL_CTX_set_default_verify_paths: module.get_symbol("SSL_CTX_set_default_verify_paths") ?,
+            SSL_CTX_set_verify: module.get_symbol("SSL_CTX_set_verify") ?,
+            SSL_CTX_set_options: module.get_symbol("SSL_CTX_set_options") ?,
+            SSL_CTX_ctrl: module.get_symbol("SSL_CTX_ctrl") ?,
+            SSL_new: module.get_symbol("SSL_new") ?,
+            SSL_free: module.get_symbol("SSL_free") ?,
+            SSL_set_fd: module.get_symbol("SSL_set_fd") ?,
+            SSL_ctrl: module.get_symbol("SSL_ctrl") ?,
+            SSL_set1_host: module.get_symbol("SSL_set1_host") ?,
+            SSL_get0_param: module.get_symbol("SSL_get0_param") ?,
+            X509_VERIFY_PARAM_set1_ip_asc: module.get_symbol("X509_VERIFY_PARAM_set1_ip_asc") ?,
+            SSL_connect: module.get_symbol("SSL_connect") ?,
+            SSL_read: module.get_symbol("SSL_read") ?,
+            SSL_write: module.get_symbol("SSL_write") ?,
+            SSL_pending: module.get_symbol("SSL_pending") ?,
+            SSL_get_error: module.get_symbol("SSL_get_error") ?,
+            SSL_get_verify_result: module.get_symbol("SSL_get_verify_result") ?,
+            X509_verify_cert_error_string: module.get_symbol("X509_verify_cert_error_string") ?,
+            ERR_get_error: module.get_symbol("ERR_get_error") ?,
+            ERR_error_string_n: module.get_symbol("ERR_error_string_n") ?,
+            ERR_clear_error: module.get_symbol("ERR_clear_error") ?,
+            _keep_module_alive: module,
+        })
+    }
+}
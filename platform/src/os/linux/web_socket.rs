@@ -1,16 +1,328 @@
+use {
+    std::{
+        io::{Read, Write},
+        net::Shutdown,
+        time::{Duration, Instant},
+        sync::{Arc, Mutex, mpsc::{channel, Sender, Receiver, RecvTimeoutError}},
+    },
+    crate::{
+        makepad_http::{
+            client::{HttpClientRequest, WebSocketConnection},
+            utils::{HttpStream, TlsConnector, write_bytes_to_tcp_stream_no_error},
+            websocket::{self, WebSocket, MessageHeader, MessageFormat, CLOSE_NORMAL, CLOSE_NO_STATUS, CLOSE_ABNORMAL},
+        },
+        event::HttpRequest,
+        web_socket::WebSocketMessage,
+    },
+    super::linux_tls::default_tls_connector,
+};
 
-use crate::event::HttpRequest;
-use crate::web_socket::{WebSocketMessage};
-use std::sync::mpsc::{Sender};
+const PING_INTERVAL: Duration = Duration::from_secs(5);
+// the connection counts as lost when nothing came in for this long, pings included
+const TIMEOUT: Duration = Duration::from_secs(15);
+// how long we wait for the server to answer our close frame
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(2);
+const RECONNECT_MIN: Duration = Duration::from_millis(500);
+const RECONNECT_MAX: Duration = Duration::from_secs(30);
+
+/// Set by Cx::set_tls_connector, wss:// sockets are opened with it
+pub (crate) static TLS_CONNECTOR: Mutex<Option<Arc<dyn TlsConnector>>> = Mutex::new(None);
+
+enum SocketEvent {
+    // from the app
+    Send(WebSocketMessage),
+    Close(u16, String),
+    // from the read thread of a connection, tagged with the id of that connection
+    Ping(u64, Vec<u8>),
+    PeerClosed(u64, u16, String),
+    ProtocolError(u64, u16, String),
+    ReadFailed(u64, String),
+}
+
+/// A websocket client that runs on a thread of its own. When the connection is lost it reconnects
+/// with a growing delay until the socket is dropped or the server closes it with CLOSE_NORMAL
 pub struct OsWebSocket{
+    event_sender: Sender<SocketEvent>,
 }
 
 impl OsWebSocket{
-    pub fn send_message(&mut self, _message:WebSocketMessage)->Result<(),()>{
-        todo!();
+    pub fn send_message(&mut self, message:WebSocketMessage)->Result<(),()>{
+        let event = match message{
+            WebSocketMessage::Close(code, reason) => SocketEvent::Close(code, reason),
+            // only the app side of the socket has these
+            WebSocketMessage::Opened | WebSocketMessage::Closed | WebSocketMessage::Error(_) => return Err(()),
+            message => SocketEvent::Send(message)
+        };
+        self.event_sender.send(event).map_err(|_|())
+    }
+
+    pub fn open(request: HttpRequest, rx_sender:Sender<WebSocketMessage>)->OsWebSocket{
+        let (event_sender, event_receiver) = channel();
+        std::thread::spawn({
+            let event_sender = event_sender.clone();
+            move || run_web_socket(request, rx_sender, event_sender, event_receiver)
+        });
+        OsWebSocket{
+            event_sender
+        }
+    }
+}
+
+impl Drop for OsWebSocket{
+    fn drop(&mut self){
+        let _ = self.event_sender.send(SocketEvent::Close(CLOSE_NORMAL, String::new()));
+    }
+}
+
+fn run_web_socket(request: HttpRequest, rx_sender: Sender<WebSocketMessage>, event_sender: Sender<SocketEvent>, event_receiver: Receiver<SocketEvent>) {
+    let mut client_request = HttpClientRequest::new("GET", &request.url);
+    for (name, values) in &request.headers {
+        client_request.headers.push((name.clone(), values.join(",")));
+    }
+    // messages the app sent while we were not connected
+    let mut queue = Vec::new();
+    let mut connection_id = 0;
+    let mut backoff = RECONNECT_MIN;
+    loop {
+        let tls_connector = TLS_CONNECTOR.lock().unwrap().clone().or_else(default_tls_connector);
+        match client_request.open_web_socket(tls_connector.as_deref()) {
+            Ok(connection) => {
+                connection_id += 1;
+                backoff = RECONNECT_MIN;
+                let _ = rx_sender.send(WebSocketMessage::Opened);
+                if run_connection(connection, connection_id, &mut queue, &rx_sender, &event_sender, &event_receiver) {
+                    break
+                }
+            }
+            Err(err) => {
+                let _ = rx_sender.send(WebSocketMessage::Error(err));
+            }
+        }
+        // wait before we try again, unless the socket gets closed in the meantime
+        let retry_at = Instant::now() + backoff;
+        backoff = (backoff * 2).min(RECONNECT_MAX);
+        while let Some(timeout) = retry_at.checked_duration_since(Instant::now()) {
+            match event_receiver.recv_timeout(timeout) {
+                Ok(SocketEvent::Send(message)) => queue.push(message),
+                Ok(SocketEvent::Close(..)) | Err(RecvTimeoutError::Disconnected) => {
+                    let _ = rx_sender.send(WebSocketMessage::Closed);
+                    return
+                }
+                // left over from the connection we lost
+                Ok(_) => (),
+                Err(RecvTimeoutError::Timeout) => break
+            }
+        }
+    }
+    let _ = rx_sender.send(WebSocketMessage::Closed);
+}
+
+// Writes to the connection until it ends, returns true if the socket is closed for good
+fn run_connection(
+    connection: WebSocketConnection,
+    connection_id: u64,
+    queue: &mut Vec<WebSocketMessage>,
+    rx_sender: &Sender<WebSocketMessage>,
+    event_sender: &Sender<SocketEvent>,
+    event_receiver: &Receiver<SocketEvent>
+) -> bool {
+    let WebSocketConnection {mut stream, tcp_stream, buffered, ..} = connection;
+    let read_stream = match stream.try_clone_stream() {
+        Ok(read_stream) => read_stream,
+        Err(err) => {
+            let _ = rx_sender.send(WebSocketMessage::Error(format!("Cannot read from websocket {}", err)));
+            let _ = tcp_stream.shutdown(Shutdown::Both);
+            return false
+        }
+    };
+    let last_seen = Arc::new(Mutex::new(Instant::now()));
+    std::thread::spawn({
+        let last_seen = last_seen.clone();
+        let rx_sender = rx_sender.clone();
+        let event_sender = event_sender.clone();
+        move || read_loop(read_stream, buffered, connection_id, last_seen, rx_sender, event_sender)
+    });
+
+    let mut closed_for_good = false;
+    let mut closing: Option<Instant> = None;
+    let mut queued = std::mem::take(queue).into_iter();
+    while let Some(message) = queued.next() {
+        if let Some(frame) = message_frame(&message) {
+            if write_bytes_to_tcp_stream_no_error(&mut stream, &frame) || stream.flush().is_err() {
+                // keep what we couldn't send for the next connection
+                queue.push(message);
+                queue.extend(queued);
+                let _ = rx_sender.send(WebSocketMessage::Close(CLOSE_ABNORMAL, "Connection lost".to_string()));
+                let _ = tcp_stream.shutdown(Shutdown::Both);
+                return false
+            }
+        }
+    }
+    loop {
+        let mut last_frame = false;
+        // the app message we are writing, queued again when the write fails
+        let mut unsent = None;
+        let frame = match event_receiver.recv_timeout(PING_INTERVAL) {
+            Ok(SocketEvent::Send(_)) if closing.is_some() => continue,
+            Ok(SocketEvent::Close(..)) if closing.is_some() => {
+                closed_for_good = true;
+                continue
+            }
+            Ok(SocketEvent::Send(message)) => match message_frame(&message) {
+                Some(frame) => {
+                    unsent = Some(message);
+                    frame
+                }
+                None => continue
+            },
+            Ok(SocketEvent::Close(code, reason)) => {
+                closed_for_good = true;
+                closing = Some(Instant::now());
+                close_frame(code, &reason)
+            }
+            Ok(SocketEvent::Ping(id, data)) if id == connection_id && closing.is_none() => {
+                build_frame(MessageFormat::Pong, &data)
+            }
+            Ok(SocketEvent::PeerClosed(id, code, reason)) if id == connection_id => {
+                let _ = rx_sender.send(WebSocketMessage::Close(code, reason));
+                if code == CLOSE_NORMAL {
+                    closed_for_good = true;
+                }
+                if closing.is_some() {
+                    // the answer to our close frame
+                    break
+                }
+                // answer with the same code, the server closes the connection after
+                closing = Some(Instant::now());
+                close_frame(code, "")
+            }
+            Ok(SocketEvent::ProtocolError(id, code, error)) if id == connection_id => {
+                let _ = rx_sender.send(WebSocketMessage::Close(code, error));
+                last_frame = true;
+                close_frame(code, "")
+            }
+            Ok(SocketEvent::ReadFailed(id, error)) if id == connection_id => {
+                if closing.is_none() {
+                    let _ = rx_sender.send(WebSocketMessage::Close(CLOSE_ABNORMAL, error));
+                }
+                break
+            }
+            // events of connections that are gone
+            Ok(_) => continue,
+            Err(RecvTimeoutError::Timeout) => {
+                if let Some(closing) = closing {
+                    if closing.elapsed() > CLOSE_TIMEOUT {
+                        break
+                    }
+                    continue
+                }
+                if last_seen.lock().unwrap().elapsed() > TIMEOUT {
+                    let _ = rx_sender.send(WebSocketMessage::Close(CLOSE_ABNORMAL, "Connection timed out".to_string()));
+                    break
+                }
+                build_frame(MessageFormat::Ping, &[])
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                closed_for_good = true;
+                break
+            }
+        };
+        if write_bytes_to_tcp_stream_no_error(&mut stream, &frame) || stream.flush().is_err() {
+            if closing.is_none() && !last_frame {
+                let _ = rx_sender.send(WebSocketMessage::Close(CLOSE_ABNORMAL, "Connection lost".to_string()));
+            }
+            queue.extend(unsent);
+            break
+        }
+        if last_frame {
+            break
+        }
+    }
+    // this also ends the read thread
+    let _ = tcp_stream.shutdown(Shutdown::Both);
+    closed_for_good
+}
+
+fn read_loop(
+    mut read_stream: Box<dyn HttpStream>,
+    buffered: Vec<u8>,
+    connection_id: u64,
+    last_seen: Arc<Mutex<Instant>>,
+    rx_sender: Sender<WebSocketMessage>,
+    event_sender: Sender<SocketEvent>
+) {
+    let mut web_socket = WebSocket::new();
+    let mut parse = | input: &[u8] | {
+        let mut error = None;
+        web_socket.parse(input, | result | {
+            match result {
+                Ok(websocket::WebSocketMessage::Ping(data)) => {
+                    let _ = event_sender.send(SocketEvent::Ping(connection_id, data.to_vec()));
+                }
+                Ok(websocket::WebSocketMessage::Pong(_)) => (),
+                Ok(websocket::WebSocketMessage::Text(text)) => {
+                    let _ = rx_sender.send(WebSocketMessage::String(text.to_string()));
+                }
+                Ok(websocket::WebSocketMessage::Binary(data)) => {
+                    let _ = rx_sender.send(WebSocketMessage::Binary(data.to_vec()));
+                }
+                Ok(websocket::WebSocketMessage::Close(code, reason)) => {
+                    let _ = event_sender.send(SocketEvent::PeerClosed(connection_id, code, reason.to_string()));
+                }
+                Err(err) => if error.is_none() {
+                    error = Some((err.close_code(), format!("{:?}", err)));
+                }
+            }
+        });
+        match error {
+            Some((code, error)) => {
+                let _ = event_sender.send(SocketEvent::ProtocolError(connection_id, code, error));
+                false
+            }
+            None => true
+        }
+    };
+    if !buffered.is_empty() && !parse(&buffered) {
+        return
     }
-                    
-    pub fn open(_request: HttpRequest, _rx_sender:Sender<WebSocketMessage>)->OsWebSocket{
-        todo!();
+    let mut data = [0u8; 65535];
+    loop {
+        let n = match read_stream.read(&mut data) {
+            Ok(0) => {
+                let _ = event_sender.send(SocketEvent::ReadFailed(connection_id, "Connection closed".to_string()));
+                return
+            }
+            Ok(n) => n,
+            Err(err) => {
+                let _ = event_sender.send(SocketEvent::ReadFailed(connection_id, err.to_string()));
+                return
+            }
+        };
+        *last_seen.lock().unwrap() = Instant::now();
+        if !parse(&data[0..n]) {
+            return
+        }
     }
-}
\ No newline at end of file
+}
+
+// Clients mask every frame they send
+fn build_frame(format: MessageFormat, data: &[u8]) -> Vec<u8> {
+    WebSocket::build_message(MessageHeader::from_len(data.len(), format, true), data)
+}
+
+fn close_frame(code: u16, reason: &str) -> Vec<u8> {
+    let mut data = Vec::new();
+    if code != CLOSE_NO_STATUS && code != CLOSE_ABNORMAL {
+        data.extend_from_slice(&code.to_be_bytes());
+        data.extend_from_slice(websocket::close_reason(reason).as_bytes());
+    }
+    build_frame(MessageFormat::Close, &data)
+}
+
+fn message_frame(message: &WebSocketMessage) -> Option<Vec<u8>> {
+    match message {
+        WebSocketMessage::String(text) => Some(build_frame(MessageFormat::Text, text.as_bytes())),
+        WebSocketMessage::Binary(data) => Some(build_frame(MessageFormat::Binary, data)),
+        _ => None
+    }
+}
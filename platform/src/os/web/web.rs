@@ -468,6 +468,9 @@ impl Cx {
                         body: WasmDataU8::from_vec_u8(request.body.unwrap_or(Vec::new())),
                     });
                 },
+                CxOsOp::CancelHttpRequest{request_id: _} => {
+                    crate::log!("Cancelling http requests not supported yet");
+                },
                 /*
                 CxOsOp::WebSocketOpen{request_id, request}=>{
                     let headers = request.get_headers_string();
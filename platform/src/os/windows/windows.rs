@@ -364,6 +364,9 @@ impl Cx {
                 CxOsOp::HttpRequest {request_id: _, request: _} => {
                     //todo!()
                 },
+                CxOsOp::CancelHttpRequest {request_id: _} => {
+                    crate::log!("Cancelling http requests not supported yet");
+                },
                 CxOsOp::PrepareVideoPlayback(_, _, _, _, _, _) => todo!(),
                 CxOsOp::PauseVideoPlayback(_) => todo!(),
                 CxOsOp::ResumeVideoPlayback(_) => todo!(),
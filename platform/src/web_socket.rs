@@ -44,6 +44,12 @@ pub enum WebSocketMessage{
     Error(String),
     Binary(Vec<u8>),
     String(String),
+    /// The connection is up, platforms that reconnect send it again after every reconnect.
+    /// Like Error and Closed it only comes from the socket, sending it fails
+    Opened,
+    /// The close code and reason the connection ended with. Sending it closes the socket with that code
+    Close(u16, String),
+    /// The socket is closed for good
     Closed
 }
 
@@ -89,7 +95,8 @@ impl Cx{
                         }
                         WebSocketThreadMsg::SendMessage{socket_id, message}=>{
                             if let Some(socket) = sockets.get_mut(&socket_id){
-                                socket.send_message(message).unwrap();
+                                // a socket that closed for good already told the app so
+                                let _ = socket.send_message(message);
                             }
                         }
                         WebSocketThreadMsg::AppToStudio{message}=>{
@@ -113,7 +120,7 @@ impl Cx{
                     if Instant::now().duration_since(first_time) >= collect_time{
                         // lets send it
                         if let Some(socket) = sockets.get_mut(&0){
-                            socket.send_message(WebSocketMessage::Binary(app_to_studio.serialize_bin())).unwrap();
+                            let _ = socket.send_message(WebSocketMessage::Binary(app_to_studio.serialize_bin()));
                         }
                         app_to_studio.0.clear();
                         first_message = None;
@@ -0,0 +1,448 @@
+use {
+    crate::makepad_micro_serde::{SerBin, DeBin, DeBinErr},
+    std::mem,
+};
+
+/// A change to a text, as the sequence of operations that walks over the old text from start to
+/// end. Lengths are in bytes of UTF-8, lines are separated by a '\n' byte, and every operation
+/// starts and ends on a char boundary.
+///
+/// Deltas are what clients send to the collab server, and what the server broadcasts to the other
+/// participants of a file. A delta applies to one particular revision of a file. Two deltas that
+/// apply to the same revision can be transformed against each other so that either can be applied
+/// after the other, which is how concurrent edits are merged.
+#[derive(Clone, Debug, Default, Eq, PartialEq, SerBin, DeBin)]
+pub struct Delta {
+    pub operations: Vec<Operation>,
+}
+
+/// A single operation of a `Delta`.
+#[derive(Clone, Debug, Eq, PartialEq, SerBin, DeBin)]
+pub enum Operation {
+    /// Keeps the given number of bytes of the old text.
+    Retain(usize),
+    /// Inserts the given text.
+    Insert(String),
+    /// Removes the given number of bytes of the old text.
+    Delete(usize),
+}
+
+impl Delta {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `true` if applying this delta leaves every text as it is.
+    pub fn is_identity(&self) -> bool {
+        self.operations.iter().all( | operation | matches!(operation, Operation::Retain(_)))
+    }
+
+    /// Appends a retain, merging it with a retain at the end.
+    pub fn retain(&mut self, count: usize) {
+        if count == 0 {
+            return
+        }
+        if let Some(Operation::Retain(last)) = self.operations.last_mut() {
+            *last += count;
+            return
+        }
+        self.operations.push(Operation::Retain(count));
+    }
+
+    /// Appends an insert. Inserts are kept in front of a delete at the same position, so equal
+    /// changes always end up as equal deltas.
+    pub fn insert(&mut self, text: &str) {
+        if text.is_empty() {
+            return
+        }
+        match self.operations.as_mut_slice() {
+            [.., Operation::Insert(last)] => last.push_str(text),
+            [.., Operation::Insert(last), Operation::Delete(_)] => last.push_str(text),
+            [.., Operation::Delete(_)] => {
+                let delete = self.operations.pop().unwrap();
+                self.operations.push(Operation::Insert(text.to_string()));
+                self.operations.push(delete);
+            }
+            _ => self.operations.push(Operation::Insert(text.to_string())),
+        }
+    }
+
+    /// Appends a delete, merging it with a delete at the end.
+    pub fn delete(&mut self, count: usize) {
+        if count == 0 {
+            return
+        }
+        if let Some(Operation::Delete(last)) = self.operations.last_mut() {
+            *last += count;
+            return
+        }
+        self.operations.push(Operation::Delete(count));
+    }
+
+    /// The number of bytes of the old text this delta walks over. It applies to texts of at least
+    /// this length, the rest of the text is retained.
+    pub fn input_len(&self) -> usize {
+        self.operations.iter().fold(0, | len, operation | match operation {
+            Operation::Retain(count) | Operation::Delete(count) => len.saturating_add(*count),
+            Operation::Insert(_) => len,
+        })
+    }
+
+    /// The number of bytes this delta inserts.
+    pub fn inserted_len(&self) -> usize {
+        self.operations.iter().fold(0, | len, operation | match operation {
+            Operation::Insert(text) => len.saturating_add(text.len()),
+            _ => len,
+        })
+    }
+
+    /// The number of bytes this delta removes.
+    pub fn deleted_len(&self) -> usize {
+        self.operations.iter().fold(0, | len, operation | match operation {
+            Operation::Delete(count) => len.saturating_add(*count),
+            _ => len,
+        })
+    }
+
+    /// Returns `true` if this delta can be applied to the given text, that is if it doesn't run
+    /// past the end and every operation starts and ends on a char boundary.
+    pub fn fits(&self, text: &str) -> bool {
+        let mut index = 0usize;
+        for operation in &self.operations {
+            if let Operation::Retain(count) | Operation::Delete(count) = operation {
+                index = match index.checked_add(*count) {
+                    Some(index) if text.is_char_boundary(index) => index,
+                    _ => return false
+                };
+            }
+        }
+        true
+    }
+
+    /// Drops a retain at the end, which doesn't change anything.
+    fn trimmed(mut self) -> Self {
+        if let Some(Operation::Retain(_)) = self.operations.last() {
+            self.operations.pop();
+        }
+        self
+    }
+
+    /// Applies this delta to the given text. Returns `None` if the delta doesn't fit the text,
+    /// either because it runs past the end or because it would split a char.
+    pub fn apply(&self, text: &str) -> Option<String> {
+        if !self.fits(text) {
+            return None
+        }
+        let mut output = String::with_capacity(text.len());
+        let mut index = 0;
+        for operation in &self.operations {
+            match operation {
+                Operation::Retain(count) => {
+                    output.push_str(&text[index..index + count]);
+                    index += count;
+                }
+                Operation::Insert(insert) => output.push_str(insert),
+                Operation::Delete(count) => index += count,
+            }
+        }
+        output.push_str(&text[index..]);
+        Some(output)
+    }
+
+    /// Returns the delta that has the same effect as applying this delta followed by `other`.
+    /// Returns `None` if `other` doesn't fit the text this delta creates, because it would split
+    /// a char this delta inserts.
+    pub fn compose(&self, other: &Delta) -> Option<Delta> {
+        let mut output = Delta::new();
+        let mut ops_0 = Operations::new(&self.operations);
+        let mut ops_1 = Operations::new(&other.operations);
+        loop {
+            match (ops_0.peek(), ops_1.peek()) {
+                (None, None) => break,
+                // deletes of the first delta and inserts of the second don't interact
+                (Some(Operation::Delete(count)), _) => {
+                    output.delete(*count);
+                    ops_0.next();
+                }
+                (_, Some(Operation::Insert(text))) => {
+                    output.insert(text);
+                    ops_1.next();
+                }
+                (Some(Operation::Retain(count_0)), Some(Operation::Retain(count_1))) => {
+                    let count = (*count_0).min(*count_1);
+                    output.retain(count);
+                    ops_0.advance(count);
+                    ops_1.advance(count);
+                }
+                (Some(Operation::Retain(count_0)), Some(Operation::Delete(count_1))) => {
+                    let count = (*count_0).min(*count_1);
+                    output.delete(count);
+                    ops_0.advance(count);
+                    ops_1.advance(count);
+                }
+                (Some(Operation::Insert(text)), Some(Operation::Retain(count_1))) => {
+                    let count = text.len().min(*count_1);
+                    if !text.is_char_boundary(count) {
+                        return None
+                    }
+                    output.insert(&text[..count]);
+                    ops_0.advance(count);
+                    ops_1.advance(count);
+                }
+                (Some(Operation::Insert(text)), Some(Operation::Delete(count_1))) => {
+                    // the second delta removes what the first one inserted
+                    let count = text.len().min(*count_1);
+                    if !text.is_char_boundary(count) {
+                        return None
+                    }
+                    ops_0.advance(count);
+                    ops_1.advance(count);
+                }
+                // whatever is left of the text past the end of either delta is retained
+                (Some(Operation::Retain(count)), None) | (None, Some(Operation::Retain(count))) => {
+                    output.retain(*count);
+                    ops_0.next();
+                    ops_1.next();
+                }
+                (Some(Operation::Insert(text)), None) => {
+                    output.insert(text);
+                    ops_0.next();
+                }
+                (None, Some(Operation::Delete(count))) => {
+                    output.delete(*count);
+                    ops_1.next();
+                }
+            }
+        }
+        Some(output.trimmed())
+    }
+
+    /// Transforms two deltas that apply to the same text against each other. Returns the pair
+    /// `(self', other')` such that applying `self` followed by `other'` gives the same text as
+    /// applying `other` followed by `self'`. When both deltas insert at the same position, the
+    /// insert of `self` ends up first. Both deltas have to fit the same text, see `fits`.
+    pub fn transform(&self, other: &Delta) -> (Delta, Delta) {
+        let mut output_0 = Delta::new();
+        let mut output_1 = Delta::new();
+        let mut ops_0 = Operations::new(&self.operations);
+        let mut ops_1 = Operations::new(&other.operations);
+        loop {
+            match (ops_0.peek(), ops_1.peek()) {
+                (None, None) => break,
+                (Some(Operation::Insert(text)), _) => {
+                    output_0.insert(text);
+                    output_1.retain(text.len());
+                    ops_0.next();
+                }
+                (_, Some(Operation::Insert(text))) => {
+                    output_0.retain(text.len());
+                    output_1.insert(text);
+                    ops_1.next();
+                }
+                (Some(Operation::Retain(count_0)), Some(Operation::Retain(count_1))) => {
+                    let count = (*count_0).min(*count_1);
+                    output_0.retain(count);
+                    output_1.retain(count);
+                    ops_0.advance(count);
+                    ops_1.advance(count);
+                }
+                (Some(Operation::Delete(count_0)), Some(Operation::Delete(count_1))) => {
+                    // both removed the same text, so neither has to remove it anymore
+                    let count = (*count_0).min(*count_1);
+                    ops_0.advance(count);
+                    ops_1.advance(count);
+                }
+                (Some(Operation::Delete(count_0)), Some(Operation::Retain(count_1))) => {
+                    let count = (*count_0).min(*count_1);
+                    output_0.delete(count);
+                    ops_0.advance(count);
+                    ops_1.advance(count);
+                }
+                (Some(Operation::Retain(count_0)), Some(Operation::Delete(count_1))) => {
+                    let count = (*count_0).min(*count_1);
+                    output_1.delete(count);
+                    ops_0.advance(count);
+                    ops_1.advance(count);
+                }
+                // a trailing retain is implied, so a delta that ends early keeps the rest
+                (Some(Operation::Retain(count)), None) | (None, Some(Operation::Retain(count))) => {
+                    output_0.retain(*count);
+                    output_1.retain(*count);
+                    ops_0.next();
+                    ops_1.next();
+                }
+                (Some(Operation::Delete(count)), None) => {
+                    output_0.delete(*count);
+                    ops_0.next();
+                }
+                (None, Some(Operation::Delete(count))) => {
+                    output_1.delete(*count);
+                    ops_1.next();
+                }
+            }
+        }
+        (output_0.trimmed(), output_1.trimmed())
+    }
+}
+
+// Walks over the operations of a delta, splitting them where the other delta needs it.
+struct Operations<'a> {
+    operations: &'a [Operation],
+    current: Option<Operation>,
+}
+
+impl<'a> Operations<'a> {
+    fn new(operations: &'a [Operation]) -> Self {
+        let mut ops = Self {operations, current: None};
+        ops.next();
+        ops
+    }
+
+    fn peek(&self) -> Option<&Operation> {
+        self.current.as_ref()
+    }
+
+    fn next(&mut self) {
+        let operations = self.operations;
+        self.current = operations.split_first().map( | (first, rest) | {
+            self.operations = rest;
+            first.clone()
+        });
+    }
+
+    // Consumes count bytes of the current operation
+    fn advance(&mut self, count: usize) {
+        match &mut self.current {
+            Some(Operation::Retain(left)) | Some(Operation::Delete(left)) if *left > count => *left -= count,
+            Some(Operation::Insert(text)) if text.len() > count => {
+                *text = mem::take(text).split_off(count);
+            }
+            _ => self.next(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A small generator, so the tests see many deltas without depending on a crate for it.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self, n: usize) -> usize {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((self.0 >> 33) as usize) % n
+        }
+    }
+
+    // A random delta that fits the given text.
+    fn random_delta(rng: &mut Rng, text: &str) -> Delta {
+        let inserts = ["x", "é", "\n", "漢字", "ab"];
+        let boundaries: Vec<usize> = (0..=text.len()).filter( | i | text.is_char_boundary(*i)).collect();
+        let mut delta = Delta::new();
+        let mut boundary = 0;
+        while boundary + 1 < boundaries.len() {
+            let next = (boundary + 1 + rng.next(3)).min(boundaries.len() - 1);
+            let count = boundaries[next] - boundaries[boundary];
+            match rng.next(4) {
+                0 => {
+                    delta.insert(inserts[rng.next(inserts.len())]);
+                    continue
+                }
+                1 => delta.delete(count),
+                _ => delta.retain(count),
+            }
+            boundary = next;
+        }
+        if rng.next(2) == 0 {
+            delta.insert(inserts[rng.next(inserts.len())]);
+        }
+        delta.trimmed()
+    }
+
+    const TEXT: &str = "héllo\nwörld 漢字";
+
+    #[test]
+    fn apply() {
+        let mut delta = Delta::new();
+        delta.retain(1);
+        delta.delete(2);
+        delta.insert("e");
+        assert_eq!(delta.apply(TEXT).as_deref(), Some("hello\nwörld 漢字"));
+        assert_eq!(delta.operations, vec![
+            Operation::Retain(1),
+            Operation::Insert("e".to_string()),
+            Operation::Delete(2),
+        ]);
+        assert!(Delta::new().is_identity());
+        assert_eq!(Delta::new().apply(TEXT).as_deref(), Some(TEXT));
+    }
+
+    #[test]
+    fn reject_deltas_that_dont_fit() {
+        let deltas = [
+            // past the end
+            vec![Operation::Retain(TEXT.len() + 1)],
+            vec![Operation::Retain(TEXT.len()), Operation::Delete(1)],
+            // inside the two bytes of 'é'
+            vec![Operation::Retain(2), Operation::Insert("x".to_string())],
+            vec![Operation::Delete(2)],
+            // overflowing counts
+            vec![Operation::Retain(usize::MAX), Operation::Retain(2)],
+        ];
+        for operations in deltas {
+            let delta = Delta {operations};
+            assert!(!delta.fits(TEXT), "{:?}", delta);
+            assert_eq!(delta.apply(TEXT), None, "{:?}", delta);
+        }
+        assert_eq!(Delta {operations: vec![Operation::Retain(usize::MAX), Operation::Retain(2)]}.input_len(), usize::MAX);
+    }
+
+    #[test]
+    fn reject_compose_that_splits_chars() {
+        let mut insert = Delta::new();
+        insert.insert("é");
+        for operation in [Operation::Retain(1), Operation::Delete(1)] {
+            let other = Delta {operations: vec![operation, Operation::Insert("x".to_string())]};
+            assert_eq!(insert.compose(&other), None);
+        }
+    }
+
+    #[test]
+    fn lengths() {
+        let mut rng = Rng(1);
+        for _ in 0..200 {
+            let delta = random_delta(&mut rng, TEXT);
+            let output = delta.apply(TEXT).unwrap();
+            assert!(delta.input_len() <= TEXT.len());
+            assert_eq!(output.len(), TEXT.len() + delta.inserted_len() - delta.deleted_len());
+        }
+    }
+
+    #[test]
+    fn transform_converges() {
+        let mut rng = Rng(2);
+        for _ in 0..2000 {
+            let a = random_delta(&mut rng, TEXT);
+            let b = random_delta(&mut rng, TEXT);
+            let (a_after_b, b_after_a) = a.transform(&b);
+            let a_then_b = b_after_a.apply(&a.apply(TEXT).unwrap());
+            let b_then_a = a_after_b.apply(&b.apply(TEXT).unwrap());
+            assert!(a_then_b.is_some(), "{:?} {:?}", a, b);
+            assert_eq!(a_then_b, b_then_a, "{:?} {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn compose_matches_apply() {
+        let mut rng = Rng(3);
+        for _ in 0..2000 {
+            let a = random_delta(&mut rng, TEXT);
+            let a_text = a.apply(TEXT).unwrap();
+            let b = random_delta(&mut rng, &a_text);
+            let composed = a.compose(&b).unwrap();
+            assert_eq!(composed.apply(TEXT), b.apply(&a_text), "{:?} {:?}", a, b);
+        }
+    }
+}
@@ -2,6 +2,7 @@ use {
     crate::{
         makepad_live_id::*,
         makepad_micro_serde::{SerBin, DeBin, DeBinErr},
+        delta::Delta,
     },
 };
 
@@ -20,26 +21,39 @@ use {
 /// clients may have different revisions of the same file. The server maintains a linear history of
 /// all deltas from the oldest revision to the newest revision. Whenever a delta for an older
 /// revision comes in, it is transformed against these older revisions so it can be applied to the
-/// newest revision. Only when all clients have confirmed that they have seen a revision (by sending
-/// a delta based on that revision) will the server remove that delta from its history.
+/// newest revision. Only when all clients have confirmed that they have seen a revision (by acknowledging
+/// it, or by having a delta applied after it) will the server remove that delta from its history.
 /// 
 /// Whenever a server applies a delta to a file, it notifies all the participants of that file
 /// except the one from which the request to apply the delta originated of this fact. This allows
 /// the participants to update their revision of the file accordingly.
+/// 
+/// Files are identified by their path relative to the root of the server, because that is what
+/// every client agrees on. Participants are identified by the id of their connection.
  
 /// A type for representing a request to the collab server.
 #[derive(Clone, Debug, SerBin, DeBin)]
 pub enum FileRequest {
     /// Requests the collab server to return its file tree. 
     LoadFileTree{ with_data: bool },
-    /// Requests the collab server to add the client as a participant to the file with the given id.
+    /// Requests the collab server to add the client as a participant to the file with the given path.
     /// If the client is the first participant for the file, this also causes the file to be opened
-    /// on the server.
+    /// on the server. The id is echoed back in the response.
     OpenFile(String, u64),
     /// Requests the collab server to apply the given delta to the given revision of the file with
-    /// the given id.
+    /// the given path.
+    ApplyDelta(String, usize, Delta),
+    /// Confirms to the collab server that the client has seen the given revision of the file with
+    /// the given path, so deltas up to it can be removed from the history. Clients send this after
+    /// applying deltas of other participants.
+    AcknowledgeRevision(String, usize),
+    /// Requests the collab server to remove the client as a participant from the file with the
+    /// given path. If the client was the last participant, the file is closed on the server.
+    CloseFile(String),
+    /// Requests the collab server to write the file with the given path to disk. If the file is
+    /// open on the server, the text of its newest revision is written and the given text is
+    /// ignored, so a save never drops the edits of other participants.
     SaveFile(String, String, u64),
-
 }
 
 /// A type for representing either a response or a notification from the collab server.
@@ -57,10 +71,18 @@ pub enum FileResponse {
     /// The result of requesting the collab server to return its file tree.
     LoadFileTree(Result<FileTreeData, FileError>),
     /// The result of requesting the collab server to add the client as a participant to the file
-    /// with the given id.
-    OpenFile(Result<(String, String, u64), FileError>),
+    /// with the given path. Contains the path, the text, the id and the revision of the text.
+    OpenFile(Result<(String, String, u64, usize), FileError>),
     /// The result of requesting the collab server to apply a delta to a revision of the file with
-    /// the given id.
+    /// the given path. Contains the path and the revision the delta created.
+    ApplyDelta(Result<(String, usize), FileError>),
+    /// The result of acknowledging a revision of the file with the given path.
+    AcknowledgeRevision(Result<String, FileError>),
+    /// The result of requesting the collab server to remove the client as a participant from the
+    /// file with the given path.
+    CloseFile(Result<String, FileError>),
+    /// The result of requesting the collab server to write a file to disk. Contains the path, the
+    /// old and the new text on disk, and the id.
     SaveFile(Result<(String,String,String, u64), FileError>),
 }
 
@@ -97,16 +119,28 @@ pub struct DirectoryEntry {
 #[derive(Clone, Debug, SerBin, DeBin)]
 pub enum FileNotification {
     FileChangedOnDisk,
-    // Notifies the client that another client applied the given delta to the file with the given
-    // id. This is only sent for files for which the client is a participant.
-   // DeltaWasApplied(TextFileId),
+    /// Notifies the client that another client applied the given delta to the file with the given
+    /// path, creating the given revision. The delta applies to the revision before it. This is only
+    /// sent for files for which the client is a participant.
+    DeltaWasApplied(String, usize, Delta),
+    /// Notifies the client that the participant with the given id was added to the file with the
+    /// given path.
+    ParticipantWasAdded(String, u64),
+    /// Notifies the client that the participant with the given id was removed from the file with
+    /// the given path.
+    ParticipantWasRemoved(String, u64),
 }
 
 /// A type for representing errors from the collab server.
 #[derive(Clone, Debug, SerBin, DeBin)]
 pub enum FileError {
     Unknown(String),
-    CannotOpen(String)
+    CannotOpen(String),
+    /// The client is not a participant for the file with the given path.
+    NotAParticipant(String),
+    /// The delta for the file with the given path is based on a revision the server no longer
+    /// has, or doesn't fit the text of that revision.
+    InvalidDelta(String),
 }
 
 /// An identifier for files on the collab server.
@@ -1,5 +1,7 @@
 pub mod file_protocol;
+pub mod delta;
 
 pub use file_protocol::*;
+pub use delta::*;
 pub use makepad_live_id;
 pub use makepad_micro_serde;
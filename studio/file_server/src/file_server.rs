@@ -8,14 +8,16 @@ use {
             FileNotification,
             FileRequest,
             FileResponse,
+            Delta,
         },
     },
     std::{
         cmp::Ordering,
+        collections::HashMap,
         fmt,
         fs,
         path::{Path, PathBuf},
-        sync::{Arc, RwLock},
+        sync::{Arc, Mutex, RwLock},
     },
 };
 
@@ -33,6 +35,7 @@ impl FileServer {
             next_connection_id: 0,
             shared: Arc::new(RwLock::new(Shared {
                 root_path: root_path.into(),
+                sessions: Mutex::new(HashMap::new()),
             })),
         }
     }
@@ -45,9 +48,9 @@ impl FileServer {
         let connection_id = ConnectionId(self.next_connection_id);
         self.next_connection_id += 1;
         FileServerConnection {
-            _connection_id:connection_id,
+            connection_id,
             shared: self.shared.clone(),
-            _notification_sender: notification_sender
+            notification_sender
         }
     }
 }
@@ -55,11 +58,11 @@ impl FileServer {
 /// A connection to a collab server.
 pub struct FileServerConnection {
     // The id for this connection.
-    _connection_id: ConnectionId,
+    connection_id: ConnectionId,
     // State is shared between every connection.
     shared: Arc<RwLock<Shared >>,
     // Used to send notifications for this connection.
-    _notification_sender: Box<dyn NotificationSender>,
+    notification_sender: Box<dyn NotificationSender>,
 }
 
 impl FileServerConnection {
@@ -73,7 +76,10 @@ impl FileServerConnection {
         match request {
             FileRequest::LoadFileTree {with_data} => FileResponse::LoadFileTree(self.load_file_tree(with_data)),
             FileRequest::OpenFile(path,id) => FileResponse::OpenFile(self.open_file(path, id)),
-            FileRequest::SaveFile(path, delta, id) => FileResponse::SaveFile(self.save_file(path, delta, id)),
+            FileRequest::ApplyDelta(path, revision, delta) => FileResponse::ApplyDelta(self.apply_delta(path, revision, delta)),
+            FileRequest::AcknowledgeRevision(path, revision) => FileResponse::AcknowledgeRevision(self.acknowledge_revision(path, revision)),
+            FileRequest::CloseFile(path) => FileResponse::CloseFile(self.close_file(path)),
+            FileRequest::SaveFile(path, text, id) => FileResponse::SaveFile(self.save_file(path, text, id)),
         }
     }
     
@@ -165,25 +171,119 @@ impl FileServerConnection {
     }
     
     // Handles an `OpenFile` request.
-    fn open_file(&self, child_path: String, id:u64) -> Result<(String, String, u64), FileError> {
+    fn open_file(&self, child_path: String, id:u64) -> Result<(String, String, u64, usize), FileError> {
         let path = self.make_full_path(&child_path);
+        let shared = self.shared.read().unwrap();
+        let mut sessions = shared.sessions.lock().unwrap();
+        if !sessions.contains_key(&child_path) {
+            // This is the first participant, so open the file.
+            let bytes = fs::read(&path).map_err(
+                | error | FileError::Unknown(error.to_string())
+            ) ?;
+            // Converts the file contents to a `String`. This is necessarily a lossy conversion
+            // because we assume everything is UTF-8 encoded, and this isn't always the case for
+            // files on disk (is this a problem?)
+            let text = String::from_utf8_lossy(&bytes).to_string();
+            sessions.insert(child_path.clone(), Session {
+                text,
+                history_start: 0,
+                history: Vec::new(),
+                participants: HashMap::new(),
+            });
+        }
+        let session = sessions.get_mut(&child_path).unwrap();
+        let revision = session.revision();
+        let is_new = session.participants.insert(self.connection_id, Participant {
+            revision,
+            notification_sender: self.notification_sender.clone(),
+        }).is_none();
+        if is_new {
+            session.notify_others(self.connection_id, FileNotification::ParticipantWasAdded(
+                child_path.clone(),
+                self.connection_id.0 as u64
+            ));
+        }
+        // A participant that reopens the file starts over at the newest revision.
+        session.remove_seen_history();
+        Ok((child_path, session.text.clone(), id, revision))
+    }
+    
+    // Handles an `ApplyDelta` request.
+    fn apply_delta(&self, child_path: String, revision: usize, delta: Delta) -> Result<(String, usize), FileError> {
+        let shared = self.shared.read().unwrap();
+        let mut sessions = shared.sessions.lock().unwrap();
+        let session = match sessions.get_mut(&child_path) {
+            Some(session) if session.participants.contains_key(&self.connection_id) => session,
+            _ => return Err(FileError::NotAParticipant(child_path))
+        };
+        if revision < session.history_start || revision > session.revision() {
+            return Err(FileError::InvalidDelta(child_path))
+        }
+        let applied_deltas = &session.history[revision - session.history_start..];
+        // A delta that runs past the end of the text it is based on can't be transformed.
+        let base_len = applied_deltas.iter().rev().fold(session.text.len(), | len, (_, applied_delta) | {
+            len + applied_delta.deleted_len() - applied_delta.inserted_len()
+        });
+        if delta.input_len() > base_len {
+            return Err(FileError::InvalidDelta(child_path))
+        }
+        // Transform the delta against every delta that was applied since the revision it is based
+        // on, so it can be applied to the newest revision.
+        let mut delta = delta;
+        for (_, applied_delta) in applied_deltas {
+            delta = applied_delta.transform(&delta).1;
+        }
+        let text = match delta.apply(&session.text) {
+            Some(text) => text,
+            None => return Err(FileError::InvalidDelta(child_path))
+        };
+        session.text = text;
+        session.history.push((self.connection_id, delta.clone()));
+        let new_revision = session.revision();
         
-        let bytes = fs::read(&path).map_err(
-            | error | FileError::Unknown(error.to_string())
-        ) ?;
-        // Converts the file contents to a `Text`. This is necessarily a lossy conversion
-        // because `Text` assumes everything is UTF-8 encoded, and this isn't always the
-        // case for files on disk (is this a problem?)
-        /*let text: Text = Text::from_lines(String::from_utf8_lossy(&bytes)
-            .lines()
-            .map( | line | line.chars().collect::<Vec<_ >> ())
-            .collect::<Vec<_ >>());*/
+        // The participant gets every delta before its own before it gets the response, and bases
+        // its next delta on the revision this one created at the earliest.
+        session.participants.get_mut(&self.connection_id).unwrap().revision = new_revision;
+        session.remove_seen_history();
         
-        let text = String::from_utf8_lossy(&bytes);
-        Ok((child_path, text.to_string(), id))
+        session.notify_others(self.connection_id, FileNotification::DeltaWasApplied(
+            child_path.clone(),
+            new_revision,
+            delta
+        ));
+        Ok((child_path, new_revision))
     }
     
-    // Handles an `ApplyDelta` request.
+    // Handles an `AcknowledgeRevision` request.
+    fn acknowledge_revision(&self, child_path: String, revision: usize) -> Result<String, FileError> {
+        let shared = self.shared.read().unwrap();
+        let mut sessions = shared.sessions.lock().unwrap();
+        let session = match sessions.get_mut(&child_path) {
+            Some(session) => session,
+            None => return Err(FileError::NotAParticipant(child_path))
+        };
+        let newest_revision = session.revision();
+        let participant = match session.participants.get_mut(&self.connection_id) {
+            Some(participant) => participant,
+            None => return Err(FileError::NotAParticipant(child_path))
+        };
+        // Acknowledgements can cross the response to a delta, which already moved the participant
+        // past them.
+        participant.revision = participant.revision.max(revision.min(newest_revision));
+        session.remove_seen_history();
+        Ok(child_path)
+    }
+
+    // Handles a `CloseFile` request.
+    fn close_file(&self, child_path: String) -> Result<String, FileError> {
+        let shared = self.shared.read().unwrap();
+        if !shared.remove_participant(&child_path, self.connection_id) {
+            return Err(FileError::NotAParticipant(child_path))
+        }
+        Ok(child_path)
+    }
+    
+    // Handles a `SaveFile` request.
     fn save_file(
         &self,
         child_path: String,
@@ -192,6 +292,12 @@ impl FileServerConnection {
     ) -> Result<(String, String, String, u64), FileError> {
         let path = self.make_full_path(&child_path);
         
+        // If the file is open, its newest revision is what gets written.
+        let new_content = match self.shared.read().unwrap().sessions.lock().unwrap().get(&child_path) {
+            Some(session) => session.text.clone(),
+            None => new_content
+        };
+        
         let old_content = String::from_utf8_lossy(&fs::read(&path).map_err(
             | error | FileError::Unknown(error.to_string())
         ) ?).to_string();
@@ -204,6 +310,20 @@ impl FileServerConnection {
     }
 }
 
+impl Drop for FileServerConnection {
+    fn drop(&mut self) {
+        // Remove this connection as a participant from every file it still has open.
+        let shared = self.shared.read().unwrap();
+        let paths: Vec<String> = shared.sessions.lock().unwrap().iter()
+            .filter( | (_, session) | session.participants.contains_key(&self.connection_id))
+            .map( | (path, _) | path.clone())
+            .collect();
+        for path in paths {
+            shared.remove_participant(&path, self.connection_id);
+        }
+    }
+}
+
 /// A trait for sending notifications over a connection.
 pub trait NotificationSender: Send {
     /// This method is necessary to create clones of boxed trait objects.
@@ -239,9 +359,190 @@ impl fmt::Debug for dyn NotificationSender {
 #[derive(Debug)]
 struct Shared {
     root_path: PathBuf,
+    // The collaboration sessions of the open files, by path.
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+impl Shared {
+    // Removes a participant from the session of the file with the given path, and closes the file
+    // if it was the last one. Returns `false` if it wasn't a participant.
+    fn remove_participant(&self, path: &str, connection_id: ConnectionId) -> bool {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = match sessions.get_mut(path) {
+            Some(session) => session,
+            None => return false
+        };
+        if session.participants.remove(&connection_id).is_none() {
+            return false
+        }
+        if session.participants.is_empty() {
+            sessions.remove(path);
+        }
+        else {
+            session.notify_others(connection_id, FileNotification::ParticipantWasRemoved(
+                path.to_string(),
+                connection_id.0 as u64
+            ));
+            session.remove_seen_history();
+        }
+        true
+    }
+}
+
+// The collaboration session of an open file.
+#[derive(Debug)]
+struct Session {
+    // The text of the newest revision.
+    text: String,
+    // The revision the first delta in the history applies to.
+    history_start: usize,
+    // The deltas that not every participant has seen yet, and who applied them. The delta at index
+    // i turns revision history_start + i into revision history_start + i + 1.
+    history: Vec<(ConnectionId, Delta)>,
+    participants: HashMap<ConnectionId, Participant>,
+}
+
+impl Session {
+    // The newest revision.
+    fn revision(&self) -> usize {
+        self.history_start + self.history.len()
+    }
+    
+    // Removes the deltas from the history that every participant has seen.
+    fn remove_seen_history(&mut self) {
+        let seen = self.participants.values()
+            .map( | participant | participant.revision)
+            .min()
+            .unwrap_or_else( || self.revision());
+        if seen > self.history_start {
+            self.history.drain(..seen - self.history_start);
+            self.history_start = seen;
+        }
+    }
+    
+    fn notify_others(&self, connection_id: ConnectionId, notification: FileNotification) {
+        for (participant_id, participant) in &self.participants {
+            if *participant_id != connection_id {
+                participant.notification_sender.send_notification(notification.clone());
+            }
+        }
+    }
+}
+
+// A participant of a collaboration session.
+#[derive(Debug)]
+struct Participant {
+    // The newest revision the participant confirmed it has seen.
+    revision: usize,
+    notification_sender: Box<dyn NotificationSender>,
 }
 
 /// An identifier for a connection.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 struct ConnectionId(usize);
 
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::makepad_file_protocol::Operation,
+    };
+
+    fn server(name: &str, text: &str) -> FileServer {
+        let root_path = std::env::temp_dir().join(format!("makepad_file_server_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&root_path).unwrap();
+        fs::write(root_path.join("file.txt"), text).unwrap();
+        FileServer::new(root_path)
+    }
+
+    fn connect(server: &mut FileServer) -> (FileServerConnection, Arc<Mutex<Vec<FileNotification >> >) {
+        let notifications = Arc::new(Mutex::new(Vec::new()));
+        let sender = {
+            let notifications = notifications.clone();
+            move | notification | notifications.lock().unwrap().push(notification)
+        };
+        let connection = server.connect(Box::new(sender));
+        match connection.handle_request(FileRequest::OpenFile("file.txt".into(), 0)) {
+            FileResponse::OpenFile(Ok(_)) => {}
+            response => panic!("{:?}", response)
+        }
+        (connection, notifications)
+    }
+
+    fn apply_delta(connection: &FileServerConnection, revision: usize, operations: Vec<Operation>) -> Result<usize, FileError> {
+        match connection.handle_request(FileRequest::ApplyDelta("file.txt".into(), revision, Delta {operations})) {
+            FileResponse::ApplyDelta(result) => result.map( | (_, revision) | revision),
+            response => panic!("{:?}", response)
+        }
+    }
+
+    fn history_len(server: &FileServer) -> usize {
+        server.shared.read().unwrap().sessions.lock().unwrap()["file.txt"].history.len()
+    }
+
+    fn text(server: &FileServer) -> String {
+        server.shared.read().unwrap().sessions.lock().unwrap()["file.txt"].text.clone()
+    }
+
+    #[test]
+    fn prune_history_on_acknowledgements() {
+        let mut server = server("prune", "hello");
+        let (writer, _) = connect(&mut server);
+        let (reader, notifications) = connect(&mut server);
+        let mut revision = 0;
+        for _ in 0..3 {
+            revision = apply_delta(&writer, revision, vec![Operation::Insert("!".into())]).unwrap();
+        }
+        assert_eq!(revision, 3);
+        assert_eq!(text(&server), "!!!hello");
+        assert_eq!(notifications.lock().unwrap().len(), 3);
+        // the reader hasn't seen the deltas yet
+        assert_eq!(history_len(&server), 3);
+        reader.handle_request(FileRequest::AcknowledgeRevision("file.txt".into(), 2));
+        assert_eq!(history_len(&server), 1);
+        // acknowledging an older or a future revision doesn't lose history
+        reader.handle_request(FileRequest::AcknowledgeRevision("file.txt".into(), 1));
+        assert_eq!(history_len(&server), 1);
+        reader.handle_request(FileRequest::AcknowledgeRevision("file.txt".into(), 10));
+        assert_eq!(history_len(&server), 0);
+        revision = apply_delta(&writer, revision, vec![Operation::Insert("?".into())]).unwrap();
+        assert_eq!(revision, 4);
+        assert_eq!(history_len(&server), 1);
+    }
+
+    #[test]
+    fn transform_deltas_on_older_revisions() {
+        let mut server = server("transform", "hello");
+        let (first, _) = connect(&mut server);
+        let (second, _) = connect(&mut server);
+        assert_eq!(apply_delta(&first, 0, vec![Operation::Insert("oh ".into())]).unwrap(), 1);
+        // based on the revision before the first delta
+        assert_eq!(apply_delta(&second, 0, vec![Operation::Retain(5), Operation::Insert("!".into())]).unwrap(), 2);
+        assert_eq!(text(&server), "oh hello!");
+        // the first participant has seen the second delta once its own was applied
+        assert_eq!(history_len(&server), 1);
+    }
+
+    #[test]
+    fn reject_invalid_deltas() {
+        let mut server = server("reject", "héllo");
+        let (first, _) = connect(&mut server);
+        let (_second, _) = connect(&mut server);
+        assert_eq!(apply_delta(&first, 0, vec![Operation::Insert("a".into())]).unwrap(), 1);
+        let invalid = [
+            // past the end of the text of the base revision
+            (0, vec![Operation::Retain(7), Operation::Insert("x".into())]),
+            (1, vec![Operation::Retain(7), Operation::Delete(1)]),
+            (0, vec![Operation::Retain(usize::MAX), Operation::Retain(usize::MAX)]),
+            // inside 'é'
+            (0, vec![Operation::Retain(2), Operation::Insert("x".into())]),
+            // a revision the server doesn't have
+            (2, vec![Operation::Insert("x".into())]),
+        ];
+        for (revision, operations) in invalid {
+            assert!(matches!(apply_delta(&first, revision, operations), Err(FileError::InvalidDelta(_))));
+        }
+        assert_eq!(text(&server), "ahéllo");
+    }
+}
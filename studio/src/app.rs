@@ -18,8 +18,6 @@ use crate::{
         },
     }
 }; 
-use std::fs::File;
-use std::io::Write;
 use std::env;
 
 live_design!{
@@ -286,6 +284,7 @@ pub struct App {
     #[live] ui: WidgetRef,
     #[live] build_manager: BuildManager,
     #[rust] file_system: FileSystem,
+    #[rust] state_restored: bool,
 }
 
 impl LiveHook for App {
@@ -321,6 +320,40 @@ impl LiveHook for App {
 app_main!(App);
 
 impl App {
+    // restores the dock layout and the files of its editor tabs, once the file tree is known
+    fn restore_state(&mut self, cx: &mut Cx) {
+        self.state_restored = true;
+        let data = match std::fs::read_to_string(DockLayoutStore::state_file_path("makepad")) {
+            Ok(data) => data,
+            Err(_) => return
+        };
+        let state = match PersistentState::deserialize_ron(&data) {
+            Ok(state) if state.layout.is_current_version() => state,
+            _ => return
+        };
+        let dock = self.ui.dock(id!(dock));
+        if !dock.from_store_item(cx, &state.layout.items) {
+            return
+        }
+        for item in &state.layout.items {
+            if let DockItemStore::Tab {id, kind, ..} = item {
+                if kind.0 != live_id!(CodeEditor) {
+                    continue
+                }
+                let file_id = state.open_files.iter()
+                    .find( | file | file.tab_id.0 == id.0)
+                    .and_then( | file | self.file_system.path_to_file_node_id(&file.path));
+                if let Some(file_id) = file_id {
+                    self.file_system.request_open_file(id.0, file_id);
+                }
+                else {
+                    dock.close_tab(cx, id.0);
+                }
+            }
+        }
+        self.file_system.ensure_unique_tab_names(cx, &dock);
+    }
+    
     pub fn open_code_file_by_path(&mut self, cx: &mut Cx, path: &str) {
         if let Some(file_id) = self.file_system.path_to_file_node_id(&path) {
             let dock = self.ui.dock(id!(dock));            
@@ -373,6 +406,12 @@ impl AppMain for App {
                     if let Some(session) = self.file_system.get_session_mut(current_id) {
                         code_editor.draw(cx, session);
                     }
+                    // a tab that was opened to jump somewhere goes there once its file is loaded
+                    if let Some(position) = self.file_system.take_pending_cursor(current_id) {
+                        if let Some(session) = self.file_system.get_session_mut(current_id) {
+                            code_editor.set_cursor_and_scroll(cx, position, session);
+                        }
+                    }
                 }
             }
             //profile_end!(dt);
@@ -408,6 +447,9 @@ impl AppMain for App {
             match action {
                 FileSystemAction::TreeLoaded => {
                     file_tree.redraw(cx);
+                    if !self.state_restored {
+                        self.restore_state(cx);
+                    }
                     //self.open_code_file_by_path(cx, "examples/slides/src/app.rs");
                 }
                 FileSystemAction::RecompileNeeded => {
@@ -418,6 +460,25 @@ impl AppMain for App {
                     self.build_manager.clear_log(cx, &dock, &mut self.file_system);
                     log_list.redraw(cx);
                 }
+                FileSystemAction::GoToDefinition {path, position} => {
+                    if let Some(file_id) = self.file_system.path_to_file_node_id(&path) {
+                        let tab_id = match self.file_system.file_node_id_to_tab_id(file_id) {
+                            Some(tab_id) => {
+                                dock.select_tab(cx, tab_id);
+                                tab_id
+                            }
+                            None => {
+                                let tab_id = dock.unique_tab_id(file_id.0.0);
+                                self.file_system.request_open_file(tab_id, file_id);
+                                dock.create_and_select_tab(cx, live_id!(edit_tabs), tab_id, live_id!(CodeEditor), "".to_string(), TabClosable::Yes);
+                                self.file_system.ensure_unique_tab_names(cx, &dock);
+                                tab_id
+                            }
+                        };
+                        self.file_system.set_pending_cursor(tab_id, position);
+                        dock.item(tab_id).redraw(cx);
+                    }
+                }
             }
         }
         
@@ -434,6 +495,7 @@ impl AppMain for App {
                                 // lets write the file
                                 self.file_system.request_save_file(item_id)
                             }
+                            action => self.file_system.send_lsp_request(item_id, action)
                         }
                     }
 
@@ -620,18 +682,38 @@ impl AppMain for App {
                 }
                 true 
             });
+            let mut open_files = Vec::new();
+            for di in &dock_items {
+                if let DockItemStore::Tab{id,..} = di{
+                    if let Some(file_id) = self.file_system.tab_id_to_file_node_id.get(&id.0){
+                        open_files.push(OpenFileStore{
+                            tab_id: id.clone(),
+                            path: self.file_system.file_node_path(*file_id)
+                        });
+                    }
+                }
+            }
             let state = PersistentState{
-                dock_items
+                layout: DockLayoutStore::new(dock_items),
+                open_files
             };
             // alright lets save it to disk
             let saved = state.serialize_ron();
-            let mut f = File::create("makepad_state.ron").expect("Unable to create file");
-            f.write_all(saved.as_bytes()).expect("Unable to write data");
+            if let Err(err) = std::fs::write(DockLayoutStore::state_file_path("makepad"), saved){
+                log!("Unable to write state file {:?}", err);
+            }
         }
     }
 }
 
 #[derive(Clone, Debug, SerRon, DeRon)]
 struct PersistentState{
-    dock_items: Vec<DockItemStore>
+    layout: DockLayoutStore,
+    open_files: Vec<OpenFileStore>
+}
+
+#[derive(Clone, Debug, SerRon, DeRon)]
+struct OpenFileStore{
+    tab_id: LiveIdStore,
+    path: String
 }
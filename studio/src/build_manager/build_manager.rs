@@ -19,15 +19,13 @@ use {
         },
         makepad_shell::*,
     },
-    makepad_code_editor::{text, decoration::{Decoration, DecorationType}},
-    makepad_http::server::*,
+    makepad_code_editor::text,
+    makepad_http::{server::*, router::HttpRouter, static_files::{StaticFiles, WEB_EXTENSIONS}},
     std::{
         collections::HashMap,
         env,
-        io::prelude::*,
         path::PathBuf,
         path::Path,
-        fs::File,
     },
     std::sync::mpsc,
     std::thread,
@@ -246,36 +244,23 @@ impl BuildManager {
                                 byte_index: column_end as usize
                             };
                             //log!("{:?} {:?}", pos, pos + loc.length);
-                            if let Some(file_id) = file_system.path_to_file_node_id(&file_name) {
-                                match level{
-                                    LogLevel::Warning=>{
-                                        file_system.add_decoration(file_id, Decoration::new(
-                                            0,
-                                            start,
-                                            end,
-                                            DecorationType::Warning
-                                        ));
-                                        dispatch_action(cx, BuildManagerAction::RedrawFile(file_id))
-                                    }
-                                    LogLevel::Error=>{
-                                        file_system.add_decoration(file_id, Decoration::new(
-                                            0,
-                                            start,
-                                            end,
-                                            DecorationType::Error
-                                        ));
-                                        dispatch_action(cx, BuildManagerAction::RedrawFile(file_id))
-                                    }
-                                    _=>()
-                                }
-                            }
-                            self.log.push((build_id, LogItem::Location(LogItemLocation{
+                            let loc = LogItemLocation{
                                 level,
                                 file_name,
                                 start,
                                 end,
-                                message
-                            })));
+                                message,
+                                notes: Vec::new(),
+                                related_locations: Vec::new(),
+                                quick_fixes: Vec::new(),
+                            };
+                            if let Some(file_id) = file_system.path_to_file_node_id(&loc.file_name) {
+                                if let Some(decoration) = loc.to_decoration() {
+                                    file_system.add_decoration(file_id, decoration);
+                                    dispatch_action(cx, BuildManagerAction::RedrawFile(file_id))
+                                }
+                            }
+                            self.log.push((build_id, LogItem::Location(loc)));
                             dispatch_action(cx, BuildManagerAction::RedrawLog)
                         }
                         AppToStudio::ProfileSample(sample)=>{  
@@ -308,26 +293,9 @@ impl BuildManager {
             match wrap.item {
                 LogItem::Location(loc) => {
                     if let Some(file_id) = file_system.path_to_file_node_id(&loc.file_name) {
-                        match loc.level{
-                            LogLevel::Warning=>{
-                                file_system.add_decoration(file_id, Decoration::new(
-                                    0,
-                                    loc.start,
-                                    loc.end,
-                                    DecorationType::Warning
-                                ));
-                                dispatch_action(cx, BuildManagerAction::RedrawFile(file_id))
-                            }
-                            LogLevel::Error=>{
-                                file_system.add_decoration(file_id, Decoration::new(
-                                    0,
-                                    loc.start,
-                                    loc.end,
-                                    DecorationType::Error
-                                ));
-                                dispatch_action(cx, BuildManagerAction::RedrawFile(file_id))
-                            }
-                            _=>()
+                        if let Some(decoration) = loc.to_decoration() {
+                            file_system.add_decoration(file_id, decoration);
+                            dispatch_action(cx, BuildManagerAction::RedrawFile(file_id))
                         }
                     }
                     log.push((wrap.cmd_id, LogItem::Location(loc)));
@@ -378,6 +346,9 @@ impl BuildManager {
         start_http_server(HttpServer {
             listen_address: addr,
             post_max_size: 1024 * 1024,
+            worker_threads: 16,
+            max_connections: 256,
+            tls: None,
             request: tx_request
         });
         
@@ -405,28 +376,39 @@ impl BuildManager {
                     break;
                 }
             }
-            let remaps = [
+            // wasm builds need cross origin isolation for threads
+            let static_headers = "Cross-Origin-Embedder-Policy: require-corp\r\n\
+                Cross-Origin-Opener-Policy: same-origin\r\n\
+                Cache-Control: max-age=0\r\n";
+            let mut router = HttpRouter::default();
+            router.get("/$watch", | _ | HttpServerResponse::status(200));
+            router.get("/favicon.ico", | _ | HttpServerResponse::status(200));
+            let mounts = [
                 (format!("/makepad/{}/", abs_makepad_path), makepad_path.clone()),
-                (format!("/makepad/{}/", std::env::current_dir().unwrap().display()), "".to_string()),
+                (format!("/makepad/{}/", std::env::current_dir().unwrap().display()), "./".to_string()),
                 ("/makepad//".to_string(), format!("{}/{}",root,makepad_path.clone())),
                 ("/makepad/".to_string(), format!("{}/{}",root,makepad_path.clone())),
-                ("/".to_string(), "".to_string())
+                ("/".to_string(), "./".to_string())
             ];
+            for (prefix, dir) in mounts {
+                // everything under the current directory is reachable, so only hand out what a build needs
+                router.static_files(&prefix, StaticFiles {root: dir.into(), headers: static_headers.to_string(), extensions: Some(WEB_EXTENSIONS)});
+            }
             let mut socket_id_to_build_id = HashMap::new();
             while let Ok(message) = rx_request.recv() {
                 // only store last change, fix later
-                match message {
-                    HttpServerRequest::ConnectWebSocket {web_socket_id, response_sender: _,headers} => {
+                match router.handle(message) {
+                    Some(HttpServerRequest::ConnectWebSocket {web_socket_id, response_sender: _,headers}) => {
                         if let Some(id) = headers.path.rsplit("/").next(){
                             if let Ok(id) = id.parse::<u64>(){
                                 socket_id_to_build_id.insert(web_socket_id, LiveId(id));
                             }
                         }
                     },
-                    HttpServerRequest::DisconnectWebSocket {web_socket_id} => {
+                    Some(HttpServerRequest::DisconnectWebSocket {web_socket_id}) => {
                         socket_id_to_build_id.remove(&web_socket_id);
                     },
-                    HttpServerRequest::BinaryMessage {web_socket_id, response_sender: _, data} => {
+                    Some(HttpServerRequest::BinaryMessage {web_socket_id, response_sender: _, data}) => {
                         if let Some(id) = socket_id_to_build_id.get(&web_socket_id){
                             if let Ok(msg) = AppToStudioVec::deserialize_bin(&data){
                                 let _ = studio_sender.send((*id,msg));
@@ -435,73 +417,7 @@ impl BuildManager {
                         //println!("GOT BINARY MESSAGE");
                         // new incombing message from client
                     }
-                    HttpServerRequest::Get {headers, response_sender} => {
-                        let path = &headers.path;
-                        // ok so this live connection.. where do we do it
-                        // i mean its just a network event msg. we can ignore that
-                        // we could just handle this in 'window'
-                        // or where shall we handle it
-                        // lets give live edit an api so you can codegen/live edit shaders?
-                        
-                        // alright wasm http server
-                        if path == "/$watch" {
-                            let header = "HTTP/1.1 200 OK\r\n\
-                                Cache-Control: max-age:0\r\n\
-                                Connection: close\r\n\r\n".to_string();
-                            let _ = response_sender.send(HttpServerResponse {header, body: vec![]});
-                            continue
-                        }
-                        if path == "/favicon.ico" {
-                            let header = "HTTP/1.1 200 OK\r\n\r\n".to_string();
-                            let _ = response_sender.send(HttpServerResponse {header, body: vec![]});
-                            continue
-                        }
-                        
-                        let mime_type = if path.ends_with(".html") {"text/html"}
-                        else if path.ends_with(".wasm") {"application/wasm"}
-                        else if path.ends_with(".css") {"text/css"}
-                        else if path.ends_with(".js") {"text/javascript"}
-                        else if path.ends_with(".ttf") {"application/ttf"}
-                        else if path.ends_with(".png") {"image/png"}
-                        else if path.ends_with(".jpg") {"image/jpg"}
-                        else if path.ends_with(".svg") {"image/svg+xml"}
-                        else {continue};
-                        
-                        if path.contains("..") || path.contains('\\') {
-                            continue
-                        }
-                        
-                        let mut strip = None;
-                        for remap in &remaps {
-                            if let Some(s) = path.strip_prefix(&remap.0) {
-                                strip = Some(format!("{}{}", remap.1, s));
-                                break;
-                            }
-                        }
-                        if let Some(base) = strip {
-                            if let Ok(mut file_handle) = File::open(base) {
-                                let mut body = Vec::<u8>::new();
-                                if file_handle.read_to_end(&mut body).is_ok() {
-                                    let header = format!(
-                                        "HTTP/1.1 200 OK\r\n\
-                                            Content-Type: {}\r\n\
-                                            Cross-Origin-Embedder-Policy: require-corp\r\n\
-                                            Cross-Origin-Opener-Policy: same-origin\r\n\
-                                            Content-encoding: none\r\n\
-                                            Cache-Control: max-age:0\r\n\
-                                            Content-Length: {}\r\n\
-                                            Connection: close\r\n\r\n",
-                                        mime_type,
-                                        body.len()
-                                    );
-                                    let _ = response_sender.send(HttpServerResponse {header, body});
-                                }
-                            }
-                        }
-                        
-                    }
-                    HttpServerRequest::Post {..} => { //headers, body, response}=>{
-                    }
+                    _ => ()
                 }
             }
         });
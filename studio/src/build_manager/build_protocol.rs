@@ -1,7 +1,10 @@
 use crate::{
     makepad_live_id::LiveId,
     makepad_platform::log::LogLevel,
-    makepad_code_editor::text::{Position},
+    makepad_code_editor::{
+        text::{Position},
+        decoration::{Decoration, DecorationType, QuickFix, RelatedLocation},
+    },
 };
 
 
@@ -147,7 +150,31 @@ pub struct LogItemLocation{
     pub file_name: String,
     pub start: Position,
     pub end: Position,
-    pub message: String
+    pub message: String,
+    // the labels and notes that go with the message, shown in the editor tooltip
+    pub notes: Vec<String>,
+    pub related_locations: Vec<RelatedLocation>,
+    pub quick_fixes: Vec<QuickFix>,
+}
+
+impl LogItemLocation {
+    /// The decoration that marks this location in its file, if its level is shown in the editor.
+    pub fn to_decoration(&self) -> Option<Decoration> {
+        let ty = match self.level {
+            LogLevel::Warning => DecorationType::Warning,
+            LogLevel::Error => DecorationType::Error,
+            _ => return None
+        };
+        let mut decoration = Decoration::new(0, self.start, self.end, ty);
+        decoration.message = self.message.clone();
+        for note in &self.notes {
+            decoration.message.push('\n');
+            decoration.message.push_str(note);
+        }
+        decoration.related_locations = self.related_locations.clone();
+        decoration.quick_fixes = self.quick_fixes.clone();
+        Some(decoration)
+    }
 }
 
 #[derive(Clone, Debug)]
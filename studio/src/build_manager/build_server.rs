@@ -1,6 +1,6 @@
 use {
     crate::{
-        makepad_code_editor::text::{Position},
+        makepad_code_editor::decoration::{QuickFix, RelatedLocation, Replacement},
         makepad_micro_serde::*,
         makepad_live_id::*,
         makepad_platform::log::LogLevel,
@@ -426,17 +426,11 @@ pub trait MsgSender: Send {
     }
     
 
-    fn send_location_msg(&self, cmd_id: LiveId, level: LogLevel, file_name: String, start: Position, end: Position, message: String) {
+    fn send_location_msg(&self, cmd_id: LiveId, loc: LogItemLocation) {
         self.send_message(
             LogItemWrap{
                 cmd_id,
-                item:LogItem::Location(LogItemLocation {
-                level,
-                file_name,
-                start,
-                end,
-                message
-            })
+                item:LogItem::Location(loc)
         });
     }
     
@@ -455,7 +449,7 @@ pub trait MsgSender: Send {
                 }
             };
             if let Some(span) = msg.spans.iter().find( | span | span.is_primary) {
-                self.send_location_msg(cmd_id, level, span.file_name.clone(),span.start(), span.end(), msg.message.clone());
+                self.send_location_msg(cmd_id, compiler_message_location(level, &msg, span));
                 /*
                 if let Some(label) = &span.label {
                     self.send_location_msg(cmd_id, level, span.file_name.clone(), range, label.clone());
@@ -481,6 +475,66 @@ pub trait MsgSender: Send {
     }
 }
 
+// turns the primary span label, the notes and the machine applicable suggestions of a compiler
+// message into the details of its location
+fn compiler_message_location(level: LogLevel, msg: &RustcMessage, span: &RustcSpan) -> LogItemLocation {
+    let mut loc = LogItemLocation {
+        level,
+        file_name: span.file_name.clone(),
+        start: span.start(),
+        end: span.end(),
+        message: msg.message.clone(),
+        notes: Vec::new(),
+        related_locations: Vec::new(),
+        quick_fixes: Vec::new(),
+    };
+    if let Some(label) = &span.label {
+        loc.notes.push(label.clone());
+    }
+    for other in msg.spans.iter().filter( | other | !other.is_primary) {
+        if let Some(label) = &other.label {
+            loc.related_locations.push(related_location(other, label.clone()));
+        }
+    }
+    for child in &msg.children {
+        let replacements: Vec<Replacement> = child.spans.iter().filter_map( | child_span | {
+            // without the line text the columns can't be turned into byte indices
+            if child_span.file_name != span.file_name ||
+            child_span.suggestion_applicability.as_deref() != Some("MachineApplicable") ||
+            !child_span.has_line_text() {
+                return None
+            }
+            child_span.suggested_replacement.as_ref().map( | text | Replacement {
+                start: child_span.start(),
+                end: child_span.end(),
+                text: text.clone()
+            })
+        }).collect();
+        if !replacements.is_empty() {
+            loc.quick_fixes.push(QuickFix {
+                title: child.message.clone(),
+                replacements
+            });
+            continue;
+        }
+        loc.notes.push(format!("{}: {}", child.level, child.message));
+        for child_span in &child.spans {
+            let message = child_span.label.clone().unwrap_or_else( || child.message.clone());
+            loc.related_locations.push(related_location(child_span, message));
+        }
+    }
+    loc
+}
+
+fn related_location(span: &RustcSpan, message: String) -> RelatedLocation {
+    RelatedLocation {
+        path: span.file_name.clone(),
+        start: span.start(),
+        end: span.end(),
+        message
+    }
+}
+
 impl<F: Clone + Fn(LogItemWrap) + Send + 'static> MsgSender for F {
     fn box_clone(&self) -> Box<dyn MsgSender> {
         Box::new(self.clone())
@@ -44,17 +44,17 @@ pub struct RustcSpan {
 
 impl RustcSpan {
     pub fn start(&self) -> Position {
-        Position {
-            line_index: self.line_start - 1,
-            byte_index: self.column_start - 1
-        }
+        span_position(self.line_start, self.column_start, self.text.first())
     }
 
     pub fn end(&self) -> Position {
-        Position {
-            line_index: self.line_end - 1,
-            byte_index: self.column_end - 1
-        }
+        span_position(self.line_end, self.column_end, self.text.last())
+    }
+    
+    /// Whether rustc sent the text of every line of the span, without it start and end take
+    /// its char columns as byte indices, which is only right for ascii lines
+    pub fn has_line_text(&self) -> bool {
+        self.line_end >= self.line_start && self.text.len() == self.line_end - self.line_start + 1
     }
 
     pub fn length(&self) -> Length {
@@ -62,6 +62,22 @@ impl RustcSpan {
     }
 }
 
+// rustc lines and columns are 1-based and columns count chars, a position counts bytes
+fn span_position(line: usize, column: usize, text: Option<&RustcText>) -> Position {
+    let char_index = column.saturating_sub(1);
+    let byte_index = match text {
+        Some(text) => match text.text.char_indices().nth(char_index) {
+            Some((byte_index, _)) => byte_index,
+            None => text.text.len() + char_index - text.text.chars().count()
+        },
+        None => char_index
+    };
+    Position {
+        line_index: line.saturating_sub(1),
+        byte_index
+    }
+}
+
 #[derive(Clone, DeJson, Debug, Default)]
 pub struct RustcExpansion {
     pub span: Option<RustcSpan>,
@@ -116,3 +132,50 @@ pub struct RustcCompilerMessage {
     pub executable: Option<String>,
     pub fresh: Option<bool>
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn line_span(line: usize, column_start: usize, column_end: usize, text: &str) -> RustcSpan {
+        RustcSpan {
+            line_start: line,
+            line_end: line,
+            column_start,
+            column_end,
+            text: vec![RustcText {
+                text: text.to_string(),
+                highlight_start: column_start,
+                highlight_end: column_end
+            }],
+            ..Default::default()
+        }
+    }
+    
+    #[test]
+    fn columns_to_byte_indices() {
+        // the span is on `y`, rustc counts é as one column
+        let span = line_span(3, 14, 15, "let x = \"é\"; y");
+        assert!(span.has_line_text());
+        assert_eq!(span.start(), Position {line_index: 2, byte_index: 14});
+        assert_eq!(span.end(), Position {line_index: 2, byte_index: 15});
+        // the end of the line, and a column past it that stays past it
+        let span = line_span(1, 3, 4, "aé");
+        assert_eq!(span.start().byte_index, 3);
+        assert_eq!(span.end().byte_index, 4);
+    }
+    
+    #[test]
+    fn spans_without_line_text() {
+        let mut span = line_span(2, 3, 5, "");
+        span.text.clear();
+        assert!(!span.has_line_text());
+        assert_eq!(span.start(), Position {line_index: 1, byte_index: 2});
+        // a span over two lines needs the text of both
+        let mut span = line_span(2, 3, 5, "abcdef");
+        span.line_end = 3;
+        assert!(!span.has_line_text());
+        // malformed lines and columns don't underflow
+        assert_eq!(line_span(0, 0, 0, "").start(), Position {line_index: 0, byte_index: 0});
+    }
+}
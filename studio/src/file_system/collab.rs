@@ -0,0 +1,251 @@
+use {
+    std::sync::mpsc::{self, Receiver},
+    crate::{
+        makepad_code_editor::{
+            Document,
+            text::{Change, Drift, Edit, Position, Text},
+        },
+        makepad_file_protocol::{Delta, FileError, FileRequest, Operation},
+    },
+};
+
+/// The client side of the collab session of an open file.
+///
+/// Local edits are collected into a delta that is sent to the server. Only one delta is in flight
+/// at a time; edits made while waiting for the server to acknowledge it are buffered, and sent
+/// once it has. Deltas of other participants are transformed against the in flight and buffered
+/// deltas before they are applied to the document, the same way the server transforms ours
+/// against theirs, so every participant ends up with the same text. The revisions they create are
+/// acknowledged, so the server can drop them from its history even if we never edit the file.
+pub struct CollabFile {
+    pub path: String,
+    /// The newest revision of the server we know of
+    pub revision: usize,
+    /// The newest revision the server knows we have seen
+    acknowledged_revision: usize,
+    /// The ids of the other participants
+    pub participants: Vec<u64>,
+    document: Document,
+    /// A copy of the text of the document, which is used to turn edits into deltas and back
+    text: Text,
+    edit_receiver: Receiver<Vec<Edit>>,
+    in_flight: Option<Delta>,
+    buffer: Option<Delta>,
+    /// Notifications for revisions after the one our in flight delta creates, that arrived before
+    /// the acknowledgement of it did
+    early_deltas: Vec<(usize, Delta)>,
+    save_pending: bool,
+}
+
+impl CollabFile {
+    pub fn new(path: String, revision: usize, document: Document) -> Self {
+        let (edit_sender, edit_receiver) = mpsc::channel();
+        document.add_edit_listener(edit_sender);
+        let text = document.as_text().clone();
+        Self {
+            path,
+            revision,
+            acknowledged_revision: revision,
+            participants: Vec::new(),
+            document,
+            text,
+            edit_receiver,
+            in_flight: None,
+            buffer: None,
+            early_deltas: Vec::new(),
+            save_pending: false,
+        }
+    }
+
+    /// Turns the local edits that were made since the last call into a delta, and sends it unless
+    /// another delta is still in flight.
+    pub fn handle_edits(&mut self, send_request: &mut dyn FnMut(FileRequest)) {
+        while let Ok(edits) = self.edit_receiver.try_recv() {
+            for edit in edits {
+                let delta = edit_to_delta(&self.text, &edit);
+                self.text.apply_change(edit.change);
+                self.buffer = Some(match self.buffer.take() {
+                    // the edits come from the document, so they always fit the buffered text
+                    Some(buffer) => buffer.compose(&delta).unwrap(),
+                    None => delta
+                });
+            }
+        }
+        self.send_buffer(send_request);
+    }
+
+    /// Requests the file to be written to disk once every local edit made so far has been applied
+    /// on the server.
+    pub fn request_save(&mut self, text: String, id: u64, send_request: &mut dyn FnMut(FileRequest)) {
+        self.handle_edits(send_request);
+        if self.in_flight.is_some() {
+            self.save_pending = true;
+        }
+        else {
+            send_request(FileRequest::SaveFile(self.path.clone(), text, id));
+        }
+    }
+
+    /// Handles the acknowledgement of our in flight delta. Fails if a delta of another
+    /// participant that arrived before it doesn't fit the text.
+    pub fn handle_delta_applied(&mut self, revision: usize, id: u64, send_request: &mut dyn FnMut(FileRequest)) -> Result<(), FileError> {
+        self.in_flight = None;
+        self.revision = revision;
+        // the server counts the revision our delta created as seen
+        self.acknowledged_revision = revision;
+        self.early_deltas.sort_by_key( | (revision, _) | *revision);
+        for (early_revision, delta) in std::mem::take(&mut self.early_deltas) {
+            self.apply_remote_delta(early_revision, delta) ?;
+        }
+        self.handle_edits(send_request);
+        if self.save_pending && self.in_flight.is_none() {
+            self.save_pending = false;
+            send_request(FileRequest::SaveFile(self.path.clone(), self.text.to_string(), id));
+        }
+        self.acknowledge_revision(send_request);
+        Ok(())
+    }
+
+    /// Handles a delta that another participant applied, which creates the given revision. Fails
+    /// if the delta doesn't fit the text, in which case we are out of sync with the server.
+    pub fn handle_delta_was_applied(&mut self, revision: usize, delta: Delta, send_request: &mut dyn FnMut(FileRequest)) -> Result<(), FileError> {
+        // Local edits have to be part of the buffer before the delta can be transformed against it.
+        self.handle_edits(send_request);
+        if revision == self.revision + 1 {
+            self.apply_remote_delta(revision, delta) ?;
+            self.acknowledge_revision(send_request);
+            Ok(())
+        }
+        else {
+            // The server already applied our in flight delta before this one, but we haven't seen
+            // the acknowledgement yet.
+            self.early_deltas.push((revision, delta));
+            Ok(())
+        }
+    }
+
+    fn apply_remote_delta(&mut self, revision: usize, delta: Delta) -> Result<(), FileError> {
+        // The delta applies to the text before our in flight and buffered deltas, so it can't run
+        // past the end of that.
+        let base_len = [&self.buffer, &self.in_flight].into_iter().flatten().fold(text_len(&self.text), | len, local_delta | {
+            len + local_delta.deleted_len() - local_delta.inserted_len()
+        });
+        if delta.input_len() > base_len {
+            return Err(FileError::InvalidDelta(self.path.clone()))
+        }
+        // The server applied this delta before ours, so its inserts go first.
+        let mut delta = delta;
+        if let Some(in_flight) = self.in_flight.take() {
+            let (transformed_delta, transformed_in_flight) = delta.transform(&in_flight);
+            delta = transformed_delta;
+            self.in_flight = Some(transformed_in_flight);
+        }
+        if let Some(buffer) = self.buffer.take() {
+            let (transformed_delta, transformed_buffer) = delta.transform(&buffer);
+            delta = transformed_delta;
+            self.buffer = Some(transformed_buffer);
+        }
+        self.revision = revision;
+        let edits = match delta_to_edits(&mut self.text, &delta) {
+            Some(edits) => edits,
+            None => return Err(FileError::InvalidDelta(self.path.clone()))
+        };
+        if !edits.is_empty() {
+            self.document.apply_edits(edits);
+        }
+        Ok(())
+    }
+
+    // Tells the server which revision we have seen. While a delta is in flight this waits, the
+    // server counts the revision that delta creates as seen when it applies it.
+    fn acknowledge_revision(&mut self, send_request: &mut dyn FnMut(FileRequest)) {
+        if self.in_flight.is_none() && self.revision > self.acknowledged_revision {
+            send_request(FileRequest::AcknowledgeRevision(self.path.clone(), self.revision));
+            self.acknowledged_revision = self.revision;
+        }
+    }
+
+    fn send_buffer(&mut self, send_request: &mut dyn FnMut(FileRequest)) {
+        if self.in_flight.is_some() {
+            return
+        }
+        if let Some(buffer) = self.buffer.take() {
+            if buffer.is_identity() {
+                return
+            }
+            send_request(FileRequest::ApplyDelta(self.path.clone(), self.revision, buffer.clone()));
+            self.in_flight = Some(buffer);
+        }
+    }
+}
+
+// The byte offset of a position in a text, counting a newline as one byte.
+fn position_to_offset(text: &Text, position: Position) -> usize {
+    text.as_lines().range(0..position.line_index)
+        .map( | line | line.len() + 1)
+        .sum::<usize>() + position.byte_index
+}
+
+// The length of a text in bytes, counting a newline as one byte.
+fn text_len(text: &Text) -> usize {
+    text.as_lines().iter().map( | line | line.len() + 1).sum::<usize>() - 1
+}
+
+// The position the given number of bytes after a position in a text, or `None` if that is past
+// the end of the text.
+fn advance_position(text: &Text, position: Position, byte_count: usize) -> Option<Position> {
+    let lines = text.as_lines();
+    let mut line_index = position.line_index;
+    let mut byte_index = position.byte_index.checked_add(byte_count) ?;
+    while byte_index > lines.get(line_index) ?.len() {
+        byte_index -= lines[line_index].len() + 1;
+        line_index += 1;
+    }
+    Some(Position {line_index, byte_index})
+}
+
+// Turns an edit into a delta for the text it applies to.
+fn edit_to_delta(text: &Text, edit: &Edit) -> Delta {
+    let mut delta = Delta::new();
+    match &edit.change {
+        Change::Insert(position, insert) => {
+            delta.retain(position_to_offset(text, *position));
+            delta.insert(&insert.to_string());
+        }
+        Change::Delete(start, length) => {
+            let start_offset = position_to_offset(text, *start);
+            delta.retain(start_offset);
+            delta.delete(position_to_offset(text, *start + *length) - start_offset);
+        }
+    }
+    delta
+}
+
+// Turns a delta into edits, and applies them to the given text. Returns `None`, leaving the text as
+// it is, if the delta doesn't fit the text.
+fn delta_to_edits(text: &mut Text, delta: &Delta) -> Option<Vec<Edit>> {
+    if !delta.fits(&text.to_string()) {
+        return None
+    }
+    let mut edits = Vec::new();
+    let mut position = Position::zero();
+    for operation in &delta.operations {
+        let change = match operation {
+            Operation::Retain(byte_count) => {
+                position = advance_position(text, position, *byte_count) ?;
+                continue
+            }
+            Operation::Insert(insert) => Change::Insert(position, insert.into()),
+            Operation::Delete(byte_count) => {
+                let end = advance_position(text, position, *byte_count) ?;
+                Change::Delete(position, end - position)
+            }
+        };
+        text.apply_change(change.clone());
+        if let Change::Insert(_, insert) = &change {
+            position += insert.length();
+        }
+        edits.push(Edit {change, drift: Drift::Before});
+    }
+    Some(edits)
+}
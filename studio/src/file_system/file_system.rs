@@ -1,17 +1,26 @@
 use {
     std::collections::{HashMap, hash_map},
-    std::path::Path,
+    std::path::{Path, PathBuf},
     crate::{
-        makepad_code_editor::{Document, decoration::{Decoration, DecorationSet}, Session},
+        makepad_code_editor::{
+            Document,
+            Tokenizer,
+            decoration::{Decoration, DecorationSet},
+            lsp::{self, LspClient, LspEvent, LspPosition},
+            text::{Position, Text},
+            code_editor::{CodeEditorAction, CodeEditorWidgetRefExt},
+            Session
+        },
         makepad_platform::makepad_live_compiler::LiveFileChange,
         makepad_widgets::*,
         makepad_widgets::file_tree::*,
-        file_system::FileClient,
+        file_system::{FileClient, collab::CollabFile},
         makepad_file_protocol::{
             FileRequest,
             FileError,
             FileResponse,
             FileClientAction,
+            FileNotification,
             FileNodeData,
             FileTreeData,
         },
@@ -26,7 +35,14 @@ pub struct FileSystem {
     pub path_to_file_node_id: HashMap<String, FileNodeId>,
     pub tab_id_to_file_node_id: HashMap<LiveId, FileNodeId>,
     pub tab_id_to_session: HashMap<LiveId, Session>,
-    pub open_documents: HashMap<FileNodeId, OpenDoc>
+    pub open_documents: HashMap<FileNodeId, OpenDoc>,
+    pub collab_files: HashMap<FileNodeId, CollabFile>,
+    pub lsp: Option<LspClient>,
+    pub lsp_root: PathBuf,
+    // the tab that made each language server request, so the result goes to its editor
+    pub lsp_request_tabs: HashMap<u64, LiveId>,
+    // cursors for tabs that wait for their file to open, like after going to a definition
+    pub pending_cursors: HashMap<LiveId, LspPosition>,
 }
 
 pub enum OpenDoc {
@@ -57,13 +73,30 @@ pub struct FileEdge {
 pub enum FileSystemAction {
     TreeLoaded,
     RecompileNeeded,
-    LiveReloadNeeded(LiveFileChange)
+    LiveReloadNeeded(LiveFileChange),
+    GoToDefinition {path: String, position: LspPosition}
 }
 
 impl FileSystem {
     pub fn init(&mut self, cx: &mut Cx, path:&Path) {
         self.file_client.init(cx, path);
         self.reload_file_tree();
+        // the language server reports paths as it resolves them, so we do the same
+        self.lsp_root = path.canonicalize().unwrap_or_else( | _ | path.to_path_buf());
+        match LspClient::spawn("rust-analyzer", &[], &self.lsp_root, Signal::new()) {
+            Ok(lsp) => self.lsp = Some(lsp),
+            Err(err) => log!("Cannot start rust-analyzer, code intelligence is off: {}", err)
+        }
+    }
+    
+    fn path_to_uri(&self, path: &str) -> String {
+        lsp::path_to_uri(&self.lsp_root.join(path))
+    }
+    
+    fn uri_to_path(&self, uri: &str) -> Option<String> {
+        let path = lsp::uri_to_path(uri) ?;
+        let path = path.strip_prefix(&self.lsp_root).ok() ?;
+        Some(path.to_string_lossy().replace('\\', "/"))
     }
     
     pub fn reload_file_tree(&mut self) {
@@ -71,8 +104,22 @@ impl FileSystem {
     }
     
     pub fn remove_tab(&mut self, tab_id: LiveId) {
-        self.tab_id_to_file_node_id.remove(&tab_id);
+        let file_id = self.tab_id_to_file_node_id.remove(&tab_id);
         self.tab_id_to_session.remove(&tab_id);
+        // when the last tab of a file closes, we leave its collab session
+        if let Some(file_id) = file_id {
+            if self.file_node_id_to_tab_id(file_id).is_none() {
+                if let Some(mut collab_file) = self.collab_files.remove(&file_id) {
+                    collab_file.handle_edits(&mut self.file_client.request_sender());
+                    let uri = self.path_to_uri(&collab_file.path);
+                    if let Some(lsp) = &mut self.lsp {
+                        lsp.close_document(&uri);
+                    }
+                    self.file_client.send_request(FileRequest::CloseFile(collab_file.path));
+                    self.open_documents.remove(&file_id);
+                }
+            }
+        }
     }
     
     pub fn path_to_file_node_id(&self, path: &str) -> Option<FileNodeId> {
@@ -121,7 +168,7 @@ impl FileSystem {
                     }
                     FileResponse::OpenFile(result) => {
                         match result {
-                            Ok((_unix_path, data, id)) => {
+                            Ok((path, data, id, revision)) => {
                                 let file_id = FileNodeId(LiveId(id));
                                 let dock = ui.dock(id!(dock));
                                 for (tab_id, file_id) in &self.tab_id_to_file_node_id {
@@ -131,21 +178,56 @@ impl FileSystem {
                                 }
                                 if let Some(OpenDoc::Decorations(dec)) = self.open_documents.get(&file_id) {
                                     let dec = dec.clone();
-                                    self.open_documents.insert(file_id, OpenDoc::Document(Document::new(data.into(), dec)));
+                                    let document = Document::with_tokenizer(Text::from_shared(data), dec, Tokenizer::for_path(&path));
+                                    self.open_lsp_document(&path, &document);
+                                    self.collab_files.insert(file_id, CollabFile::new(path, revision, document.clone()));
+                                    self.open_documents.insert(file_id, OpenDoc::Document(document));
                                 }else {panic!()}
                                 
                                 ui.redraw(cx);
                             }
                             Err(FileError::CannotOpen(_unix_path)) => {
                             }
-                            Err(FileError::Unknown(err)) => {
-                                log!("File error unknown {}", err);
+                            Err(err) => {
+                                log!("File error {:?}", err);
                                 // ignore
                             }
                         }
                     }
+                    FileResponse::ApplyDelta(result) => match result {
+                        Ok((path, revision)) => {
+                            if let Some(file_id) = self.path_to_file_node_id(&path) {
+                                if let Some(collab_file) = self.collab_files.get_mut(&file_id) {
+                                    let result = collab_file.handle_delta_applied(revision, file_id.0.0, &mut self.file_client.request_sender());
+                                    if let Err(err) = result {
+                                        log!("Reopening {} because of {:?}", path, err);
+                                        self.reopen_file(file_id);
+                                        continue
+                                    }
+                                    self.handle_sessions();
+                                    self.redraw_view_by_file_id(cx, file_id, &ui.dock(id!(dock)));
+                                }
+                            }
+                        }
+                        Err(FileError::NotAParticipant(path)) | Err(FileError::InvalidDelta(path)) => {
+                            // we are out of sync with the server, so start over from its newest revision
+                            log!("Reopening {} because the server rejected our edits", path);
+                            if let Some(file_id) = self.path_to_file_node_id(&path) {
+                                self.reopen_file(file_id);
+                            }
+                        }
+                        Err(err) => {
+                            log!("File error {:?}", err);
+                        }
+                    }
+                    FileResponse::AcknowledgeRevision(_) => {}
+                    FileResponse::CloseFile(_) => {}
                     FileResponse::SaveFile(result) => match result {
                         Ok((path, old, new, _id)) => {
+                            let uri = self.path_to_uri(&path);
+                            if let Some(lsp) = &mut self.lsp {
+                                lsp.save_document(&uri);
+                            }
                             // alright file has been saved
                             // now we need to check if a live_design!{} changed or something outside it
                             if old != new {
@@ -182,17 +264,188 @@ impl FileSystem {
                         
                     }
                 },
-                FileClientAction::Notification(_notification) => {
-                    //self.editors.handle_collab_notification(cx, &mut state.editor_state, notification)
+                FileClientAction::Notification(notification) => match notification {
+                    FileNotification::DeltaWasApplied(path, revision, delta) => {
+                        if let Some(file_id) = self.path_to_file_node_id(&path) {
+                            if let Some(collab_file) = self.collab_files.get_mut(&file_id) {
+                                let result = collab_file.handle_delta_was_applied(revision, delta, &mut self.file_client.request_sender());
+                                if let Err(err) = result {
+                                    log!("Reopening {} because of {:?}", path, err);
+                                    self.reopen_file(file_id);
+                                    continue
+                                }
+                                self.handle_sessions();
+                                self.redraw_view_by_file_id(cx, file_id, &ui.dock(id!(dock)));
+                            }
+                        }
+                    }
+                    FileNotification::ParticipantWasAdded(path, participant) => {
+                        if let Some(file_id) = self.path_to_file_node_id(&path) {
+                            if let Some(collab_file) = self.collab_files.get_mut(&file_id) {
+                                collab_file.participants.push(participant);
+                            }
+                        }
+                    }
+                    FileNotification::ParticipantWasRemoved(path, participant) => {
+                        if let Some(file_id) = self.path_to_file_node_id(&path) {
+                            if let Some(collab_file) = self.collab_files.get_mut(&file_id) {
+                                collab_file.participants.retain( | p | *p != participant);
+                            }
+                        }
+                    }
+                    FileNotification::FileChangedOnDisk => {}
+                }
+            }
+        }
+        if let Event::Signal = event {
+            self.handle_lsp_events(cx, ui, dispatch_action);
+        }
+    }
+    
+    fn open_lsp_document(&mut self, path: &str, document: &Document) {
+        if !path.ends_with(".rs") {
+            return
+        }
+        let uri = self.path_to_uri(path);
+        if let Some(lsp) = &mut self.lsp {
+            // a reopened file gets a new document, so the old one is replaced
+            lsp.close_document(&uri);
+            lsp.open_document(&uri, "rust", document);
+        }
+    }
+    
+    fn handle_lsp_events(&mut self, cx: &mut Cx, ui: &WidgetRef, dispatch_action: &mut dyn FnMut(&mut Cx, FileSystemAction)) {
+        let events = match &mut self.lsp {
+            Some(lsp) => lsp.handle_messages(),
+            None => return
+        };
+        let dock = ui.dock(id!(dock));
+        for event in events {
+            match event {
+                LspEvent::Diagnostics {uri, diagnostics} => {
+                    let file_id = match self.uri_to_path(&uri).and_then( | path | self.path_to_file_node_id(&path)) {
+                        Some(file_id) => file_id,
+                        None => continue
+                    };
+                    if let Some(OpenDoc::Document(doc)) = self.open_documents.get_mut(&file_id) {
+                        // the build errors are kept, the diagnostics of the language server replace their own
+                        let text = doc.as_text().clone();
+                        doc.set_diagnostics(diagnostics.iter().enumerate().map( | (index, diagnostic) | {
+                            diagnostic.to_decoration(index, &text)
+                        }));
+                        self.redraw_view_by_file_id(cx, file_id, &dock);
+                    }
+                }
+                LspEvent::Completion {request_id, position, items, ..} => {
+                    if let Some(tab_id) = self.lsp_request_tabs.remove(&request_id) {
+                        if let Some(mut editor) = dock.item(tab_id).as_code_editor().borrow_mut() {
+                            if let Some(session) = self.get_session_mut(tab_id) {
+                                editor.show_completions(cx, session, position, items);
+                            }
+                        }
+                    }
+                }
+                LspEvent::Hover {request_id, position, contents, ..} => {
+                    if let Some(tab_id) = self.lsp_request_tabs.remove(&request_id) {
+                        if let Some(mut editor) = dock.item(tab_id).as_code_editor().borrow_mut() {
+                            editor.show_hover(cx, position, contents);
+                        }
+                    }
+                }
+                LspEvent::SignatureHelp {request_id, position, signature, ..} => {
+                    if let Some(tab_id) = self.lsp_request_tabs.remove(&request_id) {
+                        if let Some(mut editor) = dock.item(tab_id).as_code_editor().borrow_mut() {
+                            if let Some(session) = self.get_session_mut(tab_id) {
+                                editor.show_signature_help(cx, session, position, signature);
+                            }
+                        }
+                    }
+                }
+                LspEvent::Definition {request_id, locations, ..} => {
+                    self.lsp_request_tabs.remove(&request_id);
+                    // definitions outside of the project, like in the standard library, can't be opened
+                    if let Some((path, location)) = locations.iter().find_map( | location | {
+                        Some((self.uri_to_path(&location.uri) ?, location))
+                    }) {
+                        dispatch_action(cx, FileSystemAction::GoToDefinition {
+                            path,
+                            position: location.range.start
+                        });
+                    }
+                }
+                LspEvent::Error {request_id, ..} => {
+                    // requests fail routinely while the text is changing, so these aren't logged
+                    self.lsp_request_tabs.remove(&request_id);
+                }
+                LspEvent::Exited => {
+                    log!("rust-analyzer exited, code intelligence is off");
+                    self.lsp = None;
+                    self.lsp_request_tabs.clear();
+                    return
                 }
             }
         }
     }
+    
+    // Sends the language server request that goes with the action of the editor in the given tab
+    pub fn send_lsp_request(&mut self, tab_id: LiveId, action: CodeEditorAction) {
+        let path = match self.tab_id_to_file_node_id.get(&tab_id) {
+            Some(file_id) => self.file_node_path(*file_id),
+            None => return
+        };
+        let uri = self.path_to_uri(&path);
+        let lsp = match &mut self.lsp {
+            Some(lsp) => lsp,
+            None => return
+        };
+        let request_id = match action {
+            CodeEditorAction::RequestCompletion(position) => lsp.request_completion(&uri, position),
+            CodeEditorAction::RequestHover(position) => lsp.request_hover(&uri, position),
+            CodeEditorAction::RequestSignatureHelp(position) => lsp.request_signature_help(&uri, position),
+            CodeEditorAction::GoToDefinition(position) => lsp.request_definition(&uri, position),
+            CodeEditorAction::TextDidChange => None
+        };
+        if let Some(request_id) = request_id {
+            self.lsp_request_tabs.insert(request_id, tab_id);
+        }
+    }
+    
+    pub fn set_pending_cursor(&mut self, tab_id: LiveId, position: LspPosition) {
+        self.pending_cursors.insert(tab_id, position);
+    }
+    
+    // Returns the pending cursor of a tab once the document of its file has been loaded
+    pub fn take_pending_cursor(&mut self, tab_id: LiveId) -> Option<Position> {
+        let file_id = self.tab_id_to_file_node_id.get(&tab_id) ?;
+        if let Some(OpenDoc::Document(doc)) = self.open_documents.get(file_id) {
+            let position = self.pending_cursors.remove(&tab_id) ?;
+            return Some(position.to_position(&doc.as_text()))
+        }
+        None
+    }
 
     pub fn handle_sessions(&mut self) {
         for session in self.tab_id_to_session.values_mut() {
             session.handle_changes();
         }
+        // send the local edits to the collab sessions of their files
+        let mut request_sender = self.file_client.request_sender();
+        for collab_file in self.collab_files.values_mut() {
+            collab_file.handle_edits(&mut request_sender);
+        }
+        // and to the language server
+        if let Some(lsp) = &mut self.lsp {
+            lsp.sync_documents();
+        }
+    }
+    
+    // Throws away the document of a file and opens it again
+    fn reopen_file(&mut self, file_id: FileNodeId) {
+        self.collab_files.remove(&file_id);
+        self.open_documents.insert(file_id, OpenDoc::Decorations(DecorationSet::new()));
+        self.tab_id_to_session.retain( | tab_id, _ | self.tab_id_to_file_node_id.get(tab_id) != Some(&file_id));
+        let path = self.file_node_path(file_id);
+        self.file_client.send_request(FileRequest::OpenFile(path, file_id.0.0));
     }
     
     pub fn request_open_file(&mut self, tab_id: LiveId, file_id: FileNodeId) {
@@ -224,8 +477,14 @@ impl FileSystem {
         if let Some(file_id) = self.tab_id_to_file_node_id.get(&tab_id) {
             if let Some(OpenDoc::Document(doc)) = self.open_documents.get(&file_id) {
                 let text = doc.as_text().to_string();
-                let path = self.file_node_path(*file_id);
-                self.file_client.send_request(FileRequest::SaveFile(path.clone(), text, file_id.0.0));
+                if let Some(collab_file) = self.collab_files.get_mut(file_id) {
+                    // this waits for our edits to be applied on the server
+                    collab_file.request_save(text, file_id.0.0, &mut self.file_client.request_sender());
+                }
+                else {
+                    let path = self.file_node_path(*file_id);
+                    self.file_client.send_request(FileRequest::SaveFile(path.clone(), text, file_id.0.0));
+                }
             }
         };
     }
@@ -9,3 +9,4 @@ pub mod file_client_wasm;
 pub use file_client_wasm::*;
 
 pub mod file_system;
+pub mod collab;
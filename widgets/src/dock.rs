@@ -1,5 +1,6 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::str::Chars;
+use std::path::{Path, PathBuf};
 use crate::{
     makepad_micro_serde::*,
     makepad_derive_widget::*,
@@ -260,6 +261,125 @@ pub enum DockItemStore{
     }
 }
 
+/// A dock layout as it is saved to a state file. A layout saved with another version is ignored
+/// when loading, so the app falls back to the layout from its live design.
+#[derive(Clone, Debug, SerRon, DeRon)]
+pub struct DockLayoutStore {
+    pub version: u64,
+    pub items: Vec<DockItemStore>
+}
+
+impl DockLayoutStore {
+    pub const VERSION: u64 = 1;
+    
+    pub fn new(items: Vec<DockItemStore>) -> Self {
+        Self {
+            version: Self::VERSION,
+            items
+        }
+    }
+    
+    /// The state file of the app with the given name, in the current directory.
+    pub fn state_file_path(app_name: &str) -> PathBuf {
+        PathBuf::from(format!("{}_state.ron", app_name))
+    }
+    
+    pub fn is_current_version(&self) -> bool {
+        self.version == Self::VERSION
+    }
+    
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.serialize_ron())
+    }
+    
+    /// Loads a layout, or returns `None` if there is no state file, it cannot be parsed, or it
+    /// was saved with another version.
+    pub fn load(path: impl AsRef<Path>) -> Option<Self> {
+        let data = std::fs::read_to_string(path).ok() ?;
+        let layout = Self::deserialize_ron(&data).ok() ?;
+        if !layout.is_current_version() {
+            return None
+        }
+        Some(layout)
+    }
+}
+
+/// Turns stored dock items into a layout, leaving out the tabs of a kind has_kind doesn't know.
+/// Returns None when the items don't make a layout: an id is used twice, a tab is in more than
+/// one tab bar, or the splitters and tab bars below the root aren't a tree.
+fn dock_items_from_store(store: &[DockItemStore], has_kind: impl Fn(LiveId) -> bool) -> Option<HashMap<LiveId, DockItem>> {
+    let mut dock_items = HashMap::new();
+    let mut ids = HashSet::new();
+    for item in store {
+        let id = match item {
+            DockItemStore::Splitter {id, ..} | DockItemStore::Tabs {id, ..} | DockItemStore::Tab {id, ..} => id.0
+        };
+        if !ids.insert(id) {
+            return None
+        }
+        match item {
+            DockItemStore::Splitter {id, axis, align, a, b} => {
+                dock_items.insert(id.0, DockItem::Splitter {
+                    axis: *axis,
+                    align: *align,
+                    a: a.0,
+                    b: b.0
+                });
+            }
+            DockItemStore::Tabs {id, tabs, selected, closable} => {
+                dock_items.insert(id.0, DockItem::Tabs {
+                    tabs: tabs.iter().map( | v | v.0).collect(),
+                    selected: *selected,
+                    closable: *closable
+                });
+            }
+            DockItemStore::Tab {id, name, closable, kind} => {
+                if has_kind(kind.0) {
+                    dock_items.insert(id.0, DockItem::Tab {
+                        name: name.clone(),
+                        closable: *closable,
+                        kind: kind.0
+                    });
+                }
+                else {
+                    log!("Dock tab kind {} not found, leaving out tab {}", kind.0, id.0);
+                }
+            }
+        }
+    }
+    let tab_ids: HashSet<LiveId> = dock_items.iter().filter_map( | (id, item) | {
+        if let DockItem::Tab {..} = item {Some(*id)} else {None}
+    }).collect();
+    // a tab in two places would be one widget drawn twice
+    let mut placed_tab_ids = HashSet::new();
+    for item in dock_items.values_mut() {
+        if let DockItem::Tabs {tabs, selected, ..} = item {
+            tabs.retain( | tab_id | tab_ids.contains(tab_id));
+            if !tabs.iter().all( | tab_id | placed_tab_ids.insert(*tab_id)) {
+                return None
+            }
+            *selected = (*selected).min(tabs.len().saturating_sub(1));
+        }
+    }
+    // every splitter and tab bar has to be reachable from the root exactly once
+    let mut visited = HashSet::new();
+    let mut stack = vec![live_id!(root)];
+    while let Some(id) = stack.pop() {
+        if !visited.insert(id) {
+            return None
+        }
+        match dock_items.get(&id) {
+            Some(DockItem::Splitter {a, b, ..}) => {
+                stack.push(*a);
+                stack.push(*b);
+            }
+            Some(DockItem::Tabs {..}) => (),
+            _ => return None
+        }
+    }
+    Some(dock_items)
+}
+
 impl LiveHook for Dock {
     fn apply_value_instance(&mut self, cx: &mut Cx, from: ApplyFrom, index: usize, nodes: &[LiveNode]) -> usize {
         let id = nodes[index].id;
@@ -487,8 +607,33 @@ impl Dock {
         out
     }
     
-    pub fn from_store_item(&mut self, _store:&[DockItemStore]){
-        
+    /// Replaces the layout with a stored one, and creates the widgets of its tabs from their
+    /// templates. Tabs of a kind that no longer has a template are left out. Returns false, and
+    /// keeps the current layout, if the stored one has no root or refers to items it does not have.
+    pub fn from_store_item(&mut self, cx: &mut Cx, store: &[DockItemStore]) -> bool {
+        let dock_items = match dock_items_from_store(store, | kind | self.templates.contains_key(&kind)) {
+            Some(dock_items) => dock_items,
+            None => return false
+        };
+        let tab_ids: Vec<LiveId> = dock_items.iter().filter_map( | (id, item) | {
+            if let DockItem::Tab {..} = item {Some(*id)} else {None}
+        }).collect();
+        self.items.retain( | item_id, (kind, _) | {
+            matches!(dock_items.get(item_id), Some(DockItem::Tab {kind: tab_kind, ..}) if tab_kind == kind)
+        });
+        self.dock_items = dock_items;
+        for tab_id in tab_ids {
+            if let Some(DockItem::Tab {kind, ..}) = self.dock_items.get(&tab_id) {
+                let kind = *kind;
+                self.item_or_create(cx, tab_id, kind);
+            }
+        }
+        self.tab_bars.clear();
+        self.splitters.clear();
+        self.drop_state = None;
+        self.needs_save = false;
+        self.area.redraw(cx);
+        true
     }
     
     pub fn item(&mut self, entry_id: LiveId) -> Option<WidgetRef> {
@@ -863,6 +1008,7 @@ impl Widget for Dock {
         // call handle on all tab bars, splitters,
         let uid = self.widget_uid();
         let dock_items = &mut self.dock_items;
+        let needs_save = &mut self.needs_save;
         for (panel_id, splitter) in self.splitters.iter_mut() {
             splitter
                 .handle_event_with(cx, event, &mut | cx, action | match action {
@@ -871,6 +1017,7 @@ impl Widget for Dock {
                     if let Some(DockItem::Splitter {axis: _axis, align: _align, ..}) = dock_items.get_mut(&panel_id) {
                         *_axis = axis;
                         *_align = align;
+                        *needs_save = true;
                     }
                     dispatch_action(cx, DockAction::SplitPanelChanged {panel_id: *panel_id, axis, align}.into_action(uid));
                 },
@@ -886,7 +1033,7 @@ impl Widget for Dock {
                         DockAction::ShouldTabStartDrag(item).into_action(uid),
                     ),
                     TabBarAction::TabWasPressed(tab_id) => {
-                        self.needs_save = true;
+                        *needs_save = true;
                         if let Some(DockItem::Tabs {tabs, selected, ..}) = dock_items.get_mut(&panel_id) {
                             if let Some(sel) = tabs.iter().position( | v | *v == tab_id) {
                                 *selected = sel;
@@ -1235,10 +1382,151 @@ impl DockRef {
         None
     }
     
+    pub fn from_store_item(&self, cx: &mut Cx, store: &[DockItemStore]) -> bool {
+        if let Some(mut dock) = self.borrow_mut() {
+            return dock.from_store_item(cx, store)
+        }
+        false
+    }
+    
+    /// Restores the layout from the given state file, if there is a usable one.
+    pub fn load_layout(&self, cx: &mut Cx, path: impl AsRef<Path>) -> bool {
+        match DockLayoutStore::load(path) {
+            Some(layout) => self.from_store_item(cx, &layout.items),
+            None => false
+        }
+    }
+    
+    /// Writes the layout to the given state file if it changed since it was last saved.
+    pub fn save_layout_if_needed(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        if let Some(items) = self.needs_save() {
+            DockLayoutStore::new(items).save(path) ?;
+        }
+        Ok(())
+    }
+    
     pub fn tab_start_drag(&self, cx: &mut Cx, _tab_id: LiveId, item: DragItem) {
         cx.start_dragging(vec![item]);
     }
 }
 
 #[derive(Clone, WidgetSet)]
-pub struct DockSet(WidgetSet);
\ No newline at end of file
+pub struct DockSet(WidgetSet);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn splitter(id: LiveId, a: LiveId, b: LiveId) -> DockItemStore {
+        DockItemStore::Splitter {
+            id: LiveIdStore(id),
+            axis: SplitterAxis::Horizontal,
+            align: SplitterAlign::Weighted(0.5),
+            a: LiveIdStore(a),
+            b: LiveIdStore(b)
+        }
+    }
+
+    fn tabs(id: LiveId, tabs: &[LiveId], selected: usize) -> DockItemStore {
+        DockItemStore::Tabs {
+            id: LiveIdStore(id),
+            tabs: tabs.iter().map( | id | LiveIdStore(*id)).collect(),
+            selected,
+            closable: true
+        }
+    }
+
+    fn tab(id: LiveId, kind: LiveId) -> DockItemStore {
+        DockItemStore::Tab {
+            id: LiveIdStore(id),
+            name: "Tab".to_string(),
+            closable: true,
+            kind: LiveIdStore(kind)
+        }
+    }
+
+    fn from_store(store: &[DockItemStore]) -> Option<HashMap<LiveId, DockItem>> {
+        dock_items_from_store(store, | kind | kind == live_id!(editor))
+    }
+
+    #[test]
+    fn restore_layout() {
+        let dock_items = from_store(&[
+            splitter(live_id!(root), live_id!(left), live_id!(right)),
+            tabs(live_id!(left), &[live_id!(a), live_id!(gone)], 1),
+            tabs(live_id!(right), &[live_id!(b)], 0),
+            tab(live_id!(a), live_id!(editor)),
+            tab(live_id!(b), live_id!(editor)),
+            // a tab of a kind the dock doesn't have is left out
+            tab(live_id!(gone), live_id!(unknown)),
+        ]).unwrap();
+        assert_eq!(dock_items.len(), 5);
+        assert!(!dock_items.contains_key(&live_id!(gone)));
+        match dock_items.get(&live_id!(left)) {
+            Some(DockItem::Tabs {tabs, selected, ..}) => {
+                assert_eq!(tabs, &vec![live_id!(a)]);
+                assert_eq!(*selected, 0);
+            }
+            _ => panic!("left is not a tab bar")
+        }
+    }
+
+    #[test]
+    fn reject_invalid_layouts() {
+        for store in [
+            // no root
+            vec![tabs(live_id!(main), &[], 0)],
+            // the root is a tab
+            vec![tab(live_id!(root), live_id!(editor))],
+            // a splitter side that doesn't exist
+            vec![splitter(live_id!(root), live_id!(left), live_id!(right)), tabs(live_id!(left), &[], 0)],
+            // a tab bar below two splitters, and a splitter below itself
+            vec![
+                splitter(live_id!(root), live_id!(left), live_id!(left)),
+                tabs(live_id!(left), &[], 0),
+            ],
+            vec![
+                splitter(live_id!(root), live_id!(root), live_id!(left)),
+                tabs(live_id!(left), &[], 0),
+            ],
+            // one id for two items
+            vec![
+                tabs(live_id!(root), &[live_id!(a)], 0),
+                tab(live_id!(a), live_id!(editor)),
+                tab(live_id!(a), live_id!(editor)),
+            ],
+            vec![
+                splitter(live_id!(root), live_id!(left), live_id!(right)),
+                tabs(live_id!(left), &[], 0),
+                tabs(live_id!(right), &[], 0),
+                tabs(live_id!(left), &[], 0),
+            ],
+            // one tab in two tab bars, or twice in one
+            vec![
+                splitter(live_id!(root), live_id!(left), live_id!(right)),
+                tabs(live_id!(left), &[live_id!(a)], 0),
+                tabs(live_id!(right), &[live_id!(a)], 0),
+                tab(live_id!(a), live_id!(editor)),
+            ],
+            vec![
+                tabs(live_id!(root), &[live_id!(a), live_id!(a)], 0),
+                tab(live_id!(a), live_id!(editor)),
+            ],
+        ] {
+            assert!(from_store(&store).is_none(), "{:?}", store);
+        }
+    }
+
+    #[test]
+    fn stored_layout_round_trip() {
+        let layout = DockLayoutStore::new(vec![
+            splitter(live_id!(root), live_id!(left), live_id!(right)),
+            tabs(live_id!(left), &[live_id!(a)], 0),
+            tabs(live_id!(right), &[], 0),
+            tab(live_id!(a), live_id!(editor)),
+        ]);
+        let layout = DockLayoutStore::deserialize_ron(&layout.serialize_ron()).unwrap();
+        assert!(layout.is_current_version());
+        assert!(from_store(&layout.items).is_some());
+    }
+}
@@ -19,6 +19,8 @@ pub struct Image {
     #[live] fit: ImageFit,
     #[live] source: LiveDependency,
     #[rust] texture: Option<Texture>,
+    #[rust] animation_player: ImageAnimationPlayer,
+    #[rust] pending_image_path: Option<String>,
 }
 
 impl ImageCacheImpl for Image {
@@ -29,6 +31,14 @@ impl ImageCacheImpl for Image {
     fn set_texture(&mut self, texture: Option<Texture>) {
         self.texture = texture;
     }
+    
+    fn pending_image_path(&mut self) -> &mut Option<String> {
+        &mut self.pending_image_path
+    }
+    
+    fn set_animation(&mut self, cx: &mut Cx, animation: Option<ImageAnimation>) {
+        self.animation_player.set_animation(cx, animation);
+    }
 }
 
 impl LiveHook for Image {
@@ -46,6 +56,21 @@ impl LiveHook for Image {
 }
 
 impl Widget for Image {
+    fn handle_widget_event_with(
+        &mut self,
+        cx: &mut Cx,
+        event: &Event,
+        _dispatch_action: &mut dyn FnMut(&mut Cx, WidgetActionItem)
+    ) {
+        if self.handle_image_cache_event(cx, event) {
+            self.draw_bg.redraw(cx);
+        }
+        if let Some(texture) = self.animation_player.handle_event(cx, event) {
+            self.texture = Some(texture);
+            self.draw_bg.redraw(cx);
+        }
+    }
+    
     fn redraw(&mut self, cx: &mut Cx) {
         self.draw_bg.redraw(cx)
     }
@@ -135,12 +160,48 @@ impl ImageRef {
         }
     }
     
+    pub fn load_gif_from_data(&self, cx: &mut Cx, data: &[u8]) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.load_gif_from_data(cx, data)
+        }
+    }
+    
+    /// Loads PNG, JPEG, GIF, BMP, QOI or WebP data, the format is detected from the content
+    pub fn load_image_from_data(&self, cx: &mut Cx, data: &[u8]) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.load_image_from_data(cx, data)
+        }
+    }
+    
     pub fn set_texture(&self, texture: Option<Texture>) {
         if let Some(mut inner) = self.borrow_mut() {
+            inner.animation_player.stop();
+            inner.pending_image_path = None;
             inner.texture = texture
         }
     }
     
+    pub fn play_animation(&self, cx: &mut Cx) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.animation_player.play(cx)
+        }
+    }
+    
+    pub fn stop_animation(&self) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.animation_player.stop()
+        }
+    }
+    
+    pub fn is_animating(&self) -> bool {
+        if let Some(inner) = self.borrow() {
+            inner.animation_player.is_playing()
+        }
+        else {
+            false
+        }
+    }
+    
     pub fn set_uniform(&self, cx: &Cx, uniform: &[LiveId], value: &[f32]) {
         if let Some(mut inner) = self.borrow_mut() {
             inner.draw_bg.set_uniform(cx, uniform, value);
@@ -1,7 +1,9 @@
-use crate::{makepad_draw::*};
-use std::collections::HashMap;
+use crate::{makepad_draw::*, makepad_draw::svg::SvgDocument};
+use std::collections::{HashMap, HashSet};
 use makepad_zune_jpeg::JpegDecoder;
-use makepad_zune_png::PngDecoder;
+use makepad_zune_png::{PngDecoder, DisposeOp, BlendOp};
+use makepad_image_formats::gif::{self, GifDisposal};
+use makepad_image_formats::{bmp, qoi, webp};
 
 
 #[derive(Live, LiveHook)]
@@ -15,6 +17,78 @@ pub enum ImageFit{
 }
 
 
+/// Image file formats the cache can decode
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Bmp,
+    Qoi,
+    WebP,
+    Svg,
+}
+
+impl ImageFormat {
+    /// Detects the format from the magic bytes at the start of the file
+    pub fn sniff(data: &[u8]) -> Option<Self> {
+        if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Self::Png)
+        }
+        else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+            Some(Self::Jpeg)
+        }
+        else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            Some(Self::Gif)
+        }
+        else if data.starts_with(b"qoif") {
+            Some(Self::Qoi)
+        }
+        else if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+            Some(Self::WebP)
+        }
+        else if data.starts_with(b"BM") {
+            Some(Self::Bmp)
+        }
+        else if Self::is_svg(data) {
+            Some(Self::Svg)
+        }
+        else {
+            None
+        }
+    }
+    
+    /// Guesses the format from the file extension
+    pub fn from_path(path: &str) -> Option<Self> {
+        let ext = path.rsplit('.').next()?.to_lowercase();
+        match ext.as_str() {
+            "png" | "apng" => Some(Self::Png),
+            "jpg" | "jpeg" => Some(Self::Jpeg),
+            "gif" => Some(Self::Gif),
+            "bmp" => Some(Self::Bmp),
+            "qoi" => Some(Self::Qoi),
+            "webp" => Some(Self::WebP),
+            "svg" => Some(Self::Svg),
+            _ => None
+        }
+    }
+    
+    // SVG is text, so look for the root element after an optional XML declaration or comments
+    fn is_svg(data: &[u8]) -> bool {
+        let data = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+        let start = data.iter().position( | c | !c.is_ascii_whitespace()).unwrap_or(data.len());
+        let data = &data[start..];
+        if data.starts_with(b"<svg") {
+            return true
+        }
+        if data.starts_with(b"<?xml") || data.starts_with(b"<!") {
+            let head = &data[0..data.len().min(4096)];
+            return head.windows(4).any( | window | window == b"<svg")
+        }
+        false
+    }
+}
+
 #[derive(Default, Clone)] 
 pub struct ImageBuffer {
     pub width: usize,
@@ -55,6 +129,31 @@ impl ImageBuffer {
         })
     }
     
+    fn from_components(in_data: &[u8], width: usize, height: usize, components: usize) -> Result<ImageBuffer, String> {
+        let pixels = width * height;
+        if in_data.len() < pixels * components{
+            return Err("ImageBuffer::from_components Image buffer too small".to_string())
+        }
+        let data = match components{
+            1 => (0..pixels).map(|i|{
+                let l = in_data[i] as u32;
+                0xff000000 | (l<<16) | (l<<8) | l
+            }).collect(),
+            2 => (0..pixels).map(|i|{
+                let l = in_data[i*2] as u32;
+                let a = in_data[i*2+1] as u32;
+                (a<<24) | (l<<16) | (l<<8) | l
+            }).collect(),
+            3 | 4 => return ImageBuffer::new(&in_data[0..pixels * components], width, height),
+            _ => return Err(format!("ImageBuffer::from_components unsupported component count {}", components))
+        };
+        Ok(ImageBuffer {
+            width,
+            height,
+            data
+        })
+    }
+    
     pub fn into_new_texture(self, cx:&mut Cx)->Texture{
         let texture = Texture::new(cx);
         self.into_texture(cx, &texture);
@@ -108,16 +207,619 @@ impl ImageBuffer {
             }
         }
     }
+    
+    fn from_image_formats(image: makepad_image_formats::ImageBuffer) -> Self {
+        ImageBuffer {
+            width: image.width,
+            height: image.height,
+            data: image.data
+        }
+    }
+    
+    pub fn from_bmp(
+        data: &[u8]
+    ) -> Result<Self, String> {
+        match bmp::decode(data) {
+            Ok(image) => Ok(Self::from_image_formats(image)),
+            Err(err) => Err(format!("Error decoding BMP: {}", err))
+        }
+    }
+    
+    pub fn from_qoi(
+        data: &[u8]
+    ) -> Result<Self, String> {
+        match qoi::decode(data) {
+            Ok(image) => Ok(Self::from_image_formats(image)),
+            Err(err) => Err(format!("Error decoding QOI: {}", err))
+        }
+    }
+    
+    /// Renders an SVG at twice its intrinsic size, so it stays sharp on high dpi screens
+    pub fn from_svg(
+        data: &[u8]
+    ) -> Result<Self, String> {
+        const MAX_SIZE: f64 = 4096.0;
+        let document = SvgDocument::parse(data).map_err( | err | format!("Error decoding SVG: {}", err)) ?;
+        let mut size = document.size * 2.0;
+        if size.x <= 0.0 || size.y <= 0.0 || !size.x.is_finite() || !size.y.is_finite() {
+            return Err("Error decoding SVG: image has no size".to_string())
+        }
+        let largest = size.x.max(size.y);
+        if largest > MAX_SIZE {
+            size *= MAX_SIZE / largest;
+        }
+        let width = (size.x.ceil() as usize).max(1);
+        let height = (size.y.ceil() as usize).max(1);
+        Ok(ImageBuffer {
+            width,
+            height,
+            data: document.render(width, height)
+        })
+    }
+    
+    /// Decodes a lossy or lossless WebP, for animations only the first frame
+    pub fn from_webp(
+        data: &[u8]
+    ) -> Result<Self, String> {
+        match webp::decode(data) {
+            Ok(image) => Ok(Self::from_image_formats(image)),
+            Err(err) => Err(format!("Error decoding WebP: {}", err))
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrameDispose {
+    None,
+    Background,
+    Previous
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrameBlend {
+    Source,
+    Over
+}
+
+/// One frame of an animated image, covering a sub rectangle of the canvas
+pub struct AnimatedImageFrame {
+    pub x: usize,
+    pub y: usize,
+    pub buffer: ImageBuffer,
+    pub delay: f64,
+    pub dispose: FrameDispose,
+    pub blend: FrameBlend,
+}
+
+#[derive(Default)]
+pub struct AnimatedImageBuffer {
+    pub width: usize,
+    pub height: usize,
+    /// number of times the animation plays, 0 loops forever
+    pub num_plays: u32,
+    pub frames: Vec<AnimatedImageFrame>,
+}
+
+// browsers bump tiny frame delays up, many files rely on that
+fn frame_delay(delay: f64) -> f64 {
+    if delay < 0.011 {0.1} else {delay}
+}
+
+fn blend_over(dst: u32, src: u32) -> u32 {
+    let sa = src >> 24;
+    if sa == 255 {
+        return src
+    }
+    if sa == 0 {
+        return dst
+    }
+    let da = ((dst >> 24) * (255 - sa)) / 255;
+    let oa = sa + da;
+    let mut out = oa << 24;
+    for shift in [0, 8, 16] {
+        let sc = (src >> shift) & 0xff;
+        let dc = (dst >> shift) & 0xff;
+        out |= ((sc * sa + dc * da) / oa) << shift;
+    }
+    out
+}
+
+impl AnimatedImageBuffer {
+    pub fn from_png(
+        data: &[u8]
+    ) -> Result<Self, String> {
+        let mut decoder = PngDecoder::new(data);
+        if let Err(err) = decoder.decode_headers(){
+            return Err(format!("Error decoding PNG: {:?}", err))
+        }
+        let (width, height) = decoder.get_dimensions().unwrap();
+        let components = decoder.get_colorspace().unwrap().num_components();
+        let animated = decoder.actl_info().is_some();
+        let mut anim = AnimatedImageBuffer {
+            width,
+            height,
+            num_plays: decoder.actl_info().map(|actl| actl.num_plays).unwrap_or(1),
+            frames: Vec::new()
+        };
+        let mut index = 0;
+        while decoder.more_frames() {
+            let image = match decoder.decode() {
+                Ok(image) => image,
+                // keep the frames we have if the tail of the animation is broken
+                Err(_) if anim.frames.len() > 0 => break,
+                Err(err) => return Err(format!("Error decoding PNG: {:?}", err))
+            };
+            // the default image isn't always part of the animation
+            let skip = index == 0 && animated && !decoder.default_image_is_frame();
+            index += 1;
+            if skip {
+                continue;
+            }
+            let info = decoder.frame_info().unwrap();
+            let data = match image.u8() {
+                Some(data) => data,
+                None => return Err("Error decoding PNG: image data empty".to_string())
+            };
+            let delay_denom = if info.delay_denom == 0 {100.0} else {info.delay_denom as f64};
+            anim.frames.push(AnimatedImageFrame {
+                x: info.x_offset,
+                y: info.y_offset,
+                buffer: ImageBuffer::from_components(&data, info.width, info.height, components)?,
+                delay: frame_delay(info.delay_num as f64 / delay_denom),
+                dispose: match info.dispose_op {
+                    DisposeOp::None => FrameDispose::None,
+                    DisposeOp::Background => FrameDispose::Background,
+                    DisposeOp::Previous => FrameDispose::Previous,
+                },
+                blend: match info.blend_op {
+                    BlendOp::Source => FrameBlend::Source,
+                    BlendOp::Over => FrameBlend::Over,
+                },
+            });
+        }
+        if anim.frames.len() == 0 {
+            return Err("Error decoding PNG: no frames".to_string())
+        }
+        Ok(anim)
+    }
+    
+    pub fn from_gif(
+        data: &[u8]
+    ) -> Result<Self, String> {
+        let gif = match gif::decode_frames(data) {
+            Ok(gif) => gif,
+            Err(err) => return Err(format!("Error decoding GIF: {}", err))
+        };
+        Ok(AnimatedImageBuffer {
+            width: gif.width,
+            height: gif.height,
+            // the netscape loop count is the number of repeats after the first play
+            num_plays: match gif.loop_count {
+                None => 1,
+                Some(0) => 0,
+                Some(count) => count as u32 + 1
+            },
+            frames: gif.frames.into_iter().map( | frame | AnimatedImageFrame {
+                x: frame.x,
+                y: frame.y,
+                buffer: ImageBuffer {
+                    width: frame.width,
+                    height: frame.height,
+                    data: frame.data
+                },
+                delay: frame_delay(frame.delay as f64 / 100.0),
+                dispose: match frame.disposal {
+                    GifDisposal::None => FrameDispose::None,
+                    GifDisposal::Background => FrameDispose::Background,
+                    GifDisposal::Previous => FrameDispose::Previous,
+                },
+                blend: FrameBlend::Over
+            }).collect()
+        })
+    }
+    
+    pub fn from_webp(
+        data: &[u8]
+    ) -> Result<Self, String> {
+        let webp = match webp::decode_frames(data) {
+            Ok(webp) => webp,
+            Err(err) => return Err(format!("Error decoding WebP: {}", err))
+        };
+        Ok(AnimatedImageBuffer {
+            width: webp.width,
+            height: webp.height,
+            num_plays: webp.loop_count as u32,
+            frames: webp.frames.into_iter().map( | frame | AnimatedImageFrame {
+                x: frame.x,
+                y: frame.y,
+                buffer: ImageBuffer {
+                    width: frame.width,
+                    height: frame.height,
+                    data: frame.data
+                },
+                delay: frame_delay(frame.duration as f64 / 1000.0),
+                dispose: if frame.dispose {FrameDispose::Background} else {FrameDispose::None},
+                blend: if frame.blend {FrameBlend::Over} else {FrameBlend::Source}
+            }).collect()
+        })
+    }
+    
+    pub fn is_animated(&self) -> bool {
+        self.frames.len() > 1
+    }
+    
+    /// Renders every frame onto the full canvas applying the blend and dispose ops,
+    /// returning the resulting images with their delays
+    pub fn compose(self) -> Vec<(ImageBuffer, f64)> {
+        let (width, height) = (self.width, self.height);
+        let mut canvas = vec![0u32; width * height];
+        let mut out = Vec::new();
+        for (index, frame) in self.frames.into_iter().enumerate() {
+            let dispose = if index == 0 && frame.dispose == FrameDispose::Previous {
+                FrameDispose::Background
+            }
+            else {
+                frame.dispose
+            };
+            let previous = if dispose == FrameDispose::Previous {Some(canvas.clone())} else {None};
+            let fw = frame.buffer.width.min(width.saturating_sub(frame.x));
+            let fh = frame.buffer.height.min(height.saturating_sub(frame.y));
+            for y in 0..fh {
+                for x in 0..fw {
+                    let src = frame.buffer.data[y * frame.buffer.width + x];
+                    let dst = &mut canvas[(frame.y + y) * width + frame.x + x];
+                    *dst = match frame.blend {
+                        FrameBlend::Source => src,
+                        FrameBlend::Over => blend_over(*dst, src)
+                    };
+                }
+            }
+            out.push((ImageBuffer {width, height, data: canvas.clone()}, frame.delay));
+            match dispose {
+                FrameDispose::None => (),
+                FrameDispose::Background => for y in 0..fh {
+                    let start = (frame.y + y) * width + frame.x;
+                    canvas[start..start + fw].fill(0);
+                }
+                FrameDispose::Previous => canvas = previous.unwrap()
+            }
+        }
+        out
+    }
+    
+    pub fn into_animation(self, cx: &mut Cx) -> ImageAnimation {
+        let num_plays = self.num_plays;
+        ImageAnimation {
+            num_plays,
+            frames: self.compose().into_iter().map( | (buffer, delay) | {
+                ImageAnimationFrame {
+                    texture: buffer.into_new_texture(cx),
+                    delay
+                }
+            }).collect()
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ImageAnimationFrame {
+    pub texture: Texture,
+    pub delay: f64,
+}
+
+#[derive(Clone)]
+pub struct ImageAnimation {
+    pub num_plays: u32,
+    pub frames: Vec<ImageAnimationFrame>,
+}
+
+/// Steps through the frames of an `ImageAnimation` on `NextFrame` events
+#[derive(Default)]
+pub struct ImageAnimationPlayer {
+    animation: Option<ImageAnimation>,
+    frame: usize,
+    plays: u32,
+    frame_start: Option<f64>,
+    playing: bool,
+    next_frame: NextFrame,
+}
+
+impl ImageAnimationPlayer {
+    pub fn set_animation(&mut self, cx: &mut Cx, animation: Option<ImageAnimation>) {
+        self.animation = animation;
+        self.frame = 0;
+        self.plays = 0;
+        self.frame_start = None;
+        self.playing = false;
+        self.play(cx);
+    }
+    
+    pub fn animation(&self) -> &Option<ImageAnimation> {
+        &self.animation
+    }
+    
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+    
+    /// Starts or resumes playback, restarting from the first frame if the animation had finished
+    pub fn play(&mut self, cx: &mut Cx) {
+        if let Some(animation) = &self.animation {
+            if animation.frames.len() > 1 && !self.playing {
+                if animation.num_plays != 0 && self.plays >= animation.num_plays {
+                    self.frame = 0;
+                    self.plays = 0;
+                }
+                self.frame_start = None;
+                self.playing = true;
+                self.next_frame = cx.new_next_frame();
+            }
+        }
+    }
+    
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+    
+    pub fn current_texture(&self) -> Option<Texture> {
+        self.animation.as_ref().map( | animation | animation.frames[self.frame].texture.clone())
+    }
+    
+    /// Returns the texture to show when the animation moved on to another frame
+    pub fn handle_event(&mut self, cx: &mut Cx, event: &Event) -> Option<Texture> {
+        let ne = self.next_frame.is_event(event) ?;
+        if !self.playing {
+            return None
+        }
+        let animation = self.animation.as_ref() ?;
+        let total: f64 = animation.frames.iter().map( | frame | frame.delay).sum();
+        let mut start = match self.frame_start {
+            // after a long stall (app in background) don't replay all the missed frames
+            Some(start) if ne.time - start <= total => start,
+            _ => ne.time
+        };
+        let mut changed = false;
+        while ne.time - start >= animation.frames[self.frame].delay {
+            start += animation.frames[self.frame].delay;
+            if self.frame + 1 >= animation.frames.len() {
+                self.plays += 1;
+                if animation.num_plays != 0 && self.plays >= animation.num_plays {
+                    // come to rest on the final frame
+                    self.playing = false;
+                    return if changed {self.current_texture()} else {None}
+                }
+                self.frame = 0;
+            }
+            else {
+                self.frame += 1;
+            }
+            changed = true;
+        }
+        self.frame_start = Some(start);
+        self.next_frame = cx.new_next_frame();
+        if changed {self.current_texture()} else {None}
+    }
+}
+
+/// Result of decoding an image file on a worker thread
+pub enum DecodedImage {
+    Still(ImageBuffer),
+    Animated(AnimatedImageBuffer),
+}
+
+impl DecodedImage {
+    /// Decodes the image, detecting the format from its content and
+    /// falling back to the file extension of `image_path`
+    pub fn decode(image_path: &str, data: &[u8]) -> Result<Self, String> {
+        let format = match ImageFormat::sniff(data).or_else( || ImageFormat::from_path(image_path)) {
+            Some(format) => format,
+            None => return Err("Image format not supported".to_string())
+        };
+        let anim = match format {
+            ImageFormat::Jpeg => return ImageBuffer::from_jpg(data).map(DecodedImage::Still),
+            ImageFormat::Bmp => return ImageBuffer::from_bmp(data).map(DecodedImage::Still),
+            ImageFormat::Qoi => return ImageBuffer::from_qoi(data).map(DecodedImage::Still),
+            ImageFormat::Svg => return ImageBuffer::from_svg(data).map(DecodedImage::Still),
+            ImageFormat::Png => AnimatedImageBuffer::from_png(data) ?,
+            ImageFormat::Gif => AnimatedImageBuffer::from_gif(data) ?,
+            ImageFormat::WebP => AnimatedImageBuffer::from_webp(data) ?,
+        };
+        if anim.is_animated() {
+            Ok(DecodedImage::Animated(anim))
+        }
+        else {
+            Ok(DecodedImage::Still(anim.compose().pop().unwrap().0))
+        }
+    }
+}
+
+enum DecoderToUI {
+    Done(String, DecodedImage),
+    Error(String, String),
+}
+
+#[derive(Clone)]
+pub enum CachedImage {
+    Still(Texture),
+    Animated(ImageAnimation),
+}
+
+struct ImageCacheEntry {
+    image: CachedImage,
+    bytes: usize,
+    last_used: u64,
+}
+
+const DEFAULT_MEMORY_BUDGET: usize = 256 * 1024 * 1024;
+
+/// Global cache of decoded image textures keyed by dependency path.
+/// Decoding happens on a thread pool, finished images are turned into textures
+/// on the UI thread when widgets see the `Signal` event. When the decoded size of
+/// all entries exceeds the memory budget the least recently used ones are dropped.
 pub struct ImageCache {
-    map: HashMap<String, Texture>,
+    entries: HashMap<String, ImageCacheEntry>,
+    in_flight: HashSet<String>,
+    thread_pool: Option<TagThreadPool<String>>,
+    to_ui: ToUIReceiver<DecoderToUI>,
+    placeholder: Option<Texture>,
+    memory_budget: usize,
+    memory_used: usize,
+    use_counter: u64,
 }
 
 impl ImageCache {
     pub fn new() -> Self {
         Self {
-            map: HashMap::new(),
+            entries: HashMap::new(),
+            in_flight: HashSet::new(),
+            thread_pool: None,
+            to_ui: ToUIReceiver::default(),
+            placeholder: None,
+            memory_budget: DEFAULT_MEMORY_BUDGET,
+            memory_used: 0,
+            use_counter: 0,
+        }
+    }
+    
+    pub fn memory_budget(&self) -> usize {
+        self.memory_budget
+    }
+    
+    pub fn memory_used(&self) -> usize {
+        self.memory_used
+    }
+    
+    pub fn set_memory_budget(&mut self, bytes: usize) {
+        self.memory_budget = bytes;
+        self.evict();
+    }
+    
+    /// The texture shown by widgets while their image is being decoded,
+    /// a 1x1 transparent texture is used when none is set
+    pub fn set_placeholder(&mut self, texture: Option<Texture>) {
+        self.placeholder = texture;
+    }
+    
+    /// Drops the cached image so the next load decodes it again.
+    /// A decode that is already running for this path still lands in the cache.
+    pub fn invalidate(&mut self, image_path: &str) {
+        if let Some(entry) = self.entries.remove(image_path) {
+            self.memory_used -= entry.bytes;
+        }
+    }
+    
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.memory_used = 0;
+    }
+    
+    pub fn contains(&self, image_path: &str) -> bool {
+        self.entries.contains_key(image_path)
+    }
+    
+    pub fn is_in_flight(&self, image_path: &str) -> bool {
+        self.in_flight.contains(image_path)
+    }
+    
+    pub fn get(&mut self, image_path: &str) -> Option<CachedImage> {
+        self.use_counter += 1;
+        let use_counter = self.use_counter;
+        self.entries.get_mut(image_path).map( | entry | {
+            entry.last_used = use_counter;
+            entry.image.clone()
+        })
+    }
+    
+    pub fn insert(&mut self, image_path: &str, image: CachedImage, bytes: usize) {
+        self.invalidate(image_path);
+        self.use_counter += 1;
+        self.entries.insert(image_path.to_string(), ImageCacheEntry {
+            image,
+            bytes,
+            last_used: self.use_counter
+        });
+        self.memory_used += bytes;
+        self.evict();
+    }
+    
+    fn evict(&mut self) {
+        // always keep the most recent entry, even if it alone is over budget
+        while self.memory_used > self.memory_budget && self.entries.len() > 1 {
+            let oldest = self.entries.iter().min_by_key( | (_, entry) | entry.last_used).map( | (path, _) | path.clone()).unwrap();
+            self.invalidate(&oldest);
+        }
+    }
+    
+    fn placeholder(cx: &mut Cx) -> Texture {
+        if let Some(texture) = &cx.get_global::<ImageCache>().placeholder {
+            return texture.clone()
+        }
+        let texture = ImageBuffer {width: 1, height: 1, data: vec![0]}.into_new_texture(cx);
+        cx.get_global::<ImageCache>().placeholder = Some(texture.clone());
+        texture
+    }
+    
+    fn request_decode(cx: &mut Cx, image_path: &str, data: Vec<u8>) {
+        if cx.get_global::<ImageCache>().in_flight.contains(image_path) {
+            return
+        }
+        if cx.get_global::<ImageCache>().thread_pool.is_none() {
+            let use_cores = cx.cpu_cores().max(3) - 2;
+            let thread_pool = TagThreadPool::new(cx, use_cores);
+            cx.get_global::<ImageCache>().thread_pool = Some(thread_pool);
+        }
+        let cache = cx.get_global::<ImageCache>();
+        cache.in_flight.insert(image_path.to_string());
+        let to_ui = cache.to_ui.sender();
+        // most recently requested images are decoded first, those are the ones on screen
+        cache.thread_pool.as_ref().unwrap().execute_rev(image_path.to_string(), move | image_path | {
+            // a decoder that panics on a bad file still has to take the path out of in_flight,
+            // otherwise the image is never loaded again
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe( || {
+                DecodedImage::decode(&image_path, &data)
+            }));
+            match result {
+                Ok(Ok(image)) => {
+                    let _ = to_ui.send(DecoderToUI::Done(image_path, image));
+                }
+                Ok(Err(err)) => {
+                    let _ = to_ui.send(DecoderToUI::Error(image_path, err));
+                }
+                Err(_) => {
+                    let _ = to_ui.send(DecoderToUI::Error(image_path, "Decoder panicked".to_string()));
+                }
+            }
+        });
+    }
+    
+    /// Turns images decoded on the thread pool into textures and puts them in the cache
+    pub fn handle_decoded_images(cx: &mut Cx) {
+        let mut decoded = Vec::new();
+        while let Ok(msg) = cx.get_global::<ImageCache>().to_ui.try_recv() {
+            decoded.push(msg);
+        }
+        for msg in decoded {
+            match msg {
+                DecoderToUI::Done(image_path, image) => {
+                    let (image, bytes) = match image {
+                        DecodedImage::Still(buffer) => {
+                            let bytes = buffer.data.len() * 4;
+                            (CachedImage::Still(buffer.into_new_texture(cx)), bytes)
+                        }
+                        DecodedImage::Animated(anim) => {
+                            let bytes = anim.width * anim.height * 4 * anim.frames.len();
+                            (CachedImage::Animated(anim.into_animation(cx)), bytes)
+                        }
+                    };
+                    let cache = cx.get_global::<ImageCache>();
+                    cache.in_flight.remove(&image_path);
+                    cache.insert(&image_path, image, bytes);
+                }
+                DecoderToUI::Error(image_path, err) => {
+                    cx.get_global::<ImageCache>().in_flight.remove(&image_path);
+                    error!("load_image_dep_by_path: Cannot load image from path: {} {}", image_path, err);
+                }
+            }
         }
     }
 }
@@ -125,6 +827,12 @@ impl ImageCache {
 pub trait ImageCacheImpl {
     fn get_texture(&self) -> &Option<Texture>;
     fn set_texture(&mut self, texture: Option<Texture>);
+    
+    /// Path of the image this widget is waiting on while it decodes
+    fn pending_image_path(&mut self) -> &mut Option<String>;
+    
+    /// Widgets that can play animated images override this, the others show the first frame
+    fn set_animation(&mut self, _cx: &mut Cx, _animation: Option<ImageAnimation>) {}
 
     fn lazy_create_image_cache(&mut self,cx: &mut Cx) {
         if !cx.has_global::<ImageCache>() {
@@ -132,10 +840,37 @@ pub trait ImageCacheImpl {
         }
     }
 
+    fn show_animation(&mut self, cx: &mut Cx, animation: ImageAnimation) {
+        if animation.frames.len() > 1 {
+            self.set_texture(Some(animation.frames[0].texture.clone()));
+            self.set_animation(cx, Some(animation));
+        }
+        else {
+            self.set_animation(cx, None);
+            self.set_texture(Some(animation.frames[0].texture.clone()));
+        }
+    }
+    
+    fn show_cached_image(&mut self, cx: &mut Cx, image: CachedImage) {
+        match image {
+            CachedImage::Still(texture) => {
+                self.set_animation(cx, None);
+                self.set_texture(Some(texture));
+            }
+            CachedImage::Animated(animation) => self.show_animation(cx, animation)
+        }
+    }
 
     fn load_png_from_data(&mut self, cx:&mut Cx, data:&[u8]){
-        match ImageBuffer::from_png(&*data){
-            Ok(data)=>{
+        *self.pending_image_path() = None;
+        match AnimatedImageBuffer::from_png(&*data){
+            Ok(anim) if anim.is_animated() => {
+                let animation = anim.into_animation(cx);
+                self.show_animation(cx, animation);
+            }
+            Ok(anim)=>{
+                let data = anim.compose().pop().unwrap().0;
+                self.set_animation(cx, None);
                 if let Some(texture) = self.get_texture(){
                     data.into_texture(cx, texture);
                 }
@@ -149,9 +884,24 @@ pub trait ImageCacheImpl {
         }
     }
     
+    fn load_gif_from_data(&mut self, cx:&mut Cx, data:&[u8]){
+        *self.pending_image_path() = None;
+        match AnimatedImageBuffer::from_gif(&*data){
+            Ok(anim)=>{
+                let animation = anim.into_animation(cx);
+                self.show_animation(cx, animation);
+            }
+            Err(err)=>{
+                error!("load_gif_from_data: Cannot load gif image from data {}", err);
+            }
+        }
+    }
+    
     fn load_jpg_from_data(&mut self, cx:&mut Cx, data:&[u8]){
+        *self.pending_image_path() = None;
         match ImageBuffer::from_jpg(&*data){
             Ok(data)=>{
+                self.set_animation(cx, None);
                 if let Some(texture) = self.get_texture(){
                     data.into_texture(cx, texture);
                 }
@@ -165,47 +915,70 @@ pub trait ImageCacheImpl {
         }
     }
 
+    /// Decodes image data of any supported format, detected from its content
+    fn load_image_from_data(&mut self, cx:&mut Cx, data:&[u8]){
+        *self.pending_image_path() = None;
+        match DecodedImage::decode("", data){
+            Ok(DecodedImage::Still(data))=>{
+                self.set_animation(cx, None);
+                if let Some(texture) = self.get_texture(){
+                    data.into_texture(cx, texture);
+                }
+                else{
+                    self.set_texture(Some(data.into_new_texture(cx)));
+                }
+            }
+            Ok(DecodedImage::Animated(anim))=>{
+                let animation = anim.into_animation(cx);
+                self.show_animation(cx, animation);
+            }
+            Err(err)=>{
+                error!("load_image_from_data: Cannot load image from data {}", err);
+            }
+        }
+    }
+
+    /// Shows the image from the cache, or the placeholder while it is decoded on the thread pool
     fn load_image_dep_by_path(
         &mut self,
         cx: &mut Cx,
         image_path: &str,
     ) {
-        if let Some(texture) = cx.get_global::<ImageCache>().map.get(image_path){
-            self.set_texture(Some(texture.clone()));
+        *self.pending_image_path() = None;
+        if let Some(image) = cx.get_global::<ImageCache>().get(image_path){
+            self.show_cached_image(cx, image);
+            return
         }
-        else{
-            match cx.get_dependency(image_path) {
-                Ok(data) => {
-                    if image_path.ends_with(".jpg") {
-                        match ImageBuffer::from_jpg(&*data){
-                            Ok(data)=>{
-                                let texture = data.into_new_texture(cx);
-                                cx.get_global::<ImageCache>().map.insert(image_path.to_string(), texture.clone());
-                                self.set_texture(Some(texture));
-                            }
-                            Err(err)=>{
-                                error!("load_image_dep_by_path: Cannot load jpeg image from path: {} {}",image_path, err);
-                            }
-                        }
-                    } else if image_path.ends_with(".png") {
-                        match ImageBuffer::from_png(&*data){
-                            Ok(data)=>{
-                                let texture = data.into_new_texture(cx);
-                                cx.get_global::<ImageCache>().map.insert(image_path.to_string(), texture.clone());
-                                self.set_texture(Some(texture));
-                            }
-                            Err(err)=>{
-                                error!("load_image_dep_by_path: Cannot load png image from path: {} {}",image_path, err);
-                            }
-                        }
-                    } else {
-                        error!("load_image_dep_by_path: Image format not supported {}",image_path);
-                    }
+        match cx.get_dependency(image_path) {
+            Ok(data) => {
+                ImageCache::request_decode(cx, image_path, data.as_ref().clone());
+                let placeholder = ImageCache::placeholder(cx);
+                self.set_animation(cx, None);
+                self.set_texture(Some(placeholder));
+                *self.pending_image_path() = Some(image_path.to_string());
+            }
+            Err(err) => {
+                error!("load_image_dep_by_path:  Resource not found {} {}",image_path, err);
+            }
+        }
+    }
+    
+    /// Picks up images decoded on the thread pool, returns true when this widget's image changed
+    fn handle_image_cache_event(&mut self, cx: &mut Cx, event: &Event) -> bool {
+        if let Event::Signal = event {
+            ImageCache::handle_decoded_images(cx);
+            if let Some(image_path) = self.pending_image_path().clone() {
+                if let Some(image) = cx.get_global::<ImageCache>().get(&image_path) {
+                    *self.pending_image_path() = None;
+                    self.show_cached_image(cx, image);
+                    return true
                 }
-                Err(err) => {
-                    error!("load_image_dep_by_path:  Resource not found {} {}",image_path, err);
+                // decoding failed, keep showing the placeholder
+                if !cx.get_global::<ImageCache>().is_in_flight(&image_path) {
+                    *self.pending_image_path() = None;
                 }
             }
         }
+        false
     }
 }
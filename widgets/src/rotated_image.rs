@@ -13,6 +13,7 @@ pub struct RotatedImage {
     #[live] source: LiveDependency,
     #[rust(Texture::new(cx))] texture: Option<Texture>,
     #[live] scale: f64,
+    #[rust] pending_image_path: Option<String>,
 }
 
 impl ImageCacheImpl for RotatedImage {
@@ -23,6 +24,10 @@ impl ImageCacheImpl for RotatedImage {
     fn set_texture(&mut self, texture: Option<Texture>) {
         self.texture = texture;
     }
+
+    fn pending_image_path(&mut self) -> &mut Option<String> {
+        &mut self.pending_image_path
+    }
 }
 
 impl LiveHook for RotatedImage {
@@ -40,6 +45,17 @@ impl LiveHook for RotatedImage {
 }
 
 impl Widget for RotatedImage {
+    fn handle_widget_event_with(
+        &mut self,
+        cx: &mut Cx,
+        event: &Event,
+        _dispatch_action: &mut dyn FnMut(&mut Cx, WidgetActionItem)
+    ) {
+        if self.handle_image_cache_event(cx, event) {
+            self.draw_bg.redraw(cx);
+        }
+    }
+
     fn redraw(&mut self, cx: &mut Cx) {
         self.draw_bg.redraw(cx)
     }